@@ -0,0 +1,210 @@
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+pub const USAGE: &str = "\
+Usage:
+    wt connect <URL> [--insecure]
+    wt get <URL> <PATH> [--insecure]
+    wt serve --dir <DIR> [--port <PORT>] [--cert <PEM>] [--key <PEM>]
+    wt echo [--port <PORT>] [--cert <PEM>] [--key <PEM>]
+    wt bench <URL> [--insecure] [--streams <N>] [--size <BYTES>]
+    wt conformance --webdriver <URL> [--browser <NAME>] [--port <PORT>] [--cert <PEM>] [--key <PEM>]
+
+Commands:
+    connect    Pipes stdin and stdout through a bidirectional stream
+    get        Downloads a file from a `serve` server into stdout
+    serve      Serves the files of a directory
+    echo       Echoes back streams and datagrams
+    bench      Measures the throughput against an `echo` server
+    conformance
+               Runs conformance tests of a local server against a browser (through WebDriver)
+
+Options:
+    --insecure         Skips server certificate validation
+    --dir <DIR>        Root directory of served files
+    --port <PORT>      Listening port [default: 4433]
+    --cert <PEM>       Certificate chain file [default: cert.pem]
+    --key <PEM>        Private key file [default: key.pem]
+    --streams <N>      Number of concurrent streams [default: 4]
+    --size <BYTES>     Bytes sent on each stream [default: 1048576]
+    --webdriver <URL>  WebDriver server (e.g., chromedriver or geckodriver)
+    --browser <NAME>   Browser driven by WebDriver: chrome or firefox [default: chrome]";
+
+/// Parsed command line.
+pub enum Command {
+    Connect(ClientArgs),
+    Get(ClientArgs, String),
+    Serve(ServerArgs, PathBuf),
+    Echo(ServerArgs),
+    Bench(ClientArgs, BenchArgs),
+    Conformance(ServerArgs, ConformanceArgs),
+}
+
+pub struct ClientArgs {
+    pub url: String,
+    pub insecure: bool,
+}
+
+pub struct ServerArgs {
+    pub port: u16,
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+pub struct BenchArgs {
+    pub streams: usize,
+    pub size: usize,
+}
+
+pub struct ConformanceArgs {
+    pub webdriver: String,
+    pub browser: Browser,
+}
+
+/// Browser driven by WebDriver.
+#[derive(Copy, Clone)]
+pub enum Browser {
+    Chrome,
+    Firefox,
+}
+
+impl FromStr for Browser {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "chrome" => Ok(Browser::Chrome),
+            "firefox" => Ok(Browser::Firefox),
+            _ => bail!("Unknown browser '{s}'"),
+        }
+    }
+}
+
+impl Command {
+    pub fn parse<I>(args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut args = args.into_iter();
+
+        let command = args.next().ok_or_else(|| anyhow!("Missing command"))?;
+
+        let mut positional = Vec::new();
+        let mut options = Options::default();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow!("Missing value for '{arg}'"))
+            };
+
+            match arg.as_str() {
+                "--insecure" => options.insecure = true,
+                "--dir" => options.dir = Some(value()?.into()),
+                "--port" => options.port = Some(parse_value("--port", &value()?)?),
+                "--cert" => options.cert = Some(value()?.into()),
+                "--key" => options.key = Some(value()?.into()),
+                "--streams" => options.streams = Some(parse_value("--streams", &value()?)?),
+                "--size" => options.size = Some(parse_value("--size", &value()?)?),
+                "--webdriver" => options.webdriver = Some(value()?),
+                "--browser" => options.browser = Some(value()?.parse()?),
+                _ if arg.starts_with("--") => bail!("Unknown option '{arg}'"),
+                _ => positional.push(arg),
+            }
+        }
+
+        let command = match command.as_str() {
+            "connect" => Command::Connect(options.client_args(positional)?),
+            "get" => {
+                let path = match positional.len() {
+                    2 => positional.pop().expect("Two positional arguments"),
+                    _ => bail!("Expected <URL> <PATH>"),
+                };
+                Command::Get(options.client_args(positional)?, path)
+            }
+            "serve" => {
+                let dir = options
+                    .dir
+                    .take()
+                    .ok_or_else(|| anyhow!("Missing '--dir' option"))?;
+                Command::Serve(options.server_args(positional)?, dir)
+            }
+            "echo" => Command::Echo(options.server_args(positional)?),
+            "bench" => {
+                let bench_args = BenchArgs {
+                    streams: options.streams.unwrap_or(4),
+                    size: options.size.unwrap_or(1024 * 1024),
+                };
+                Command::Bench(options.client_args(positional)?, bench_args)
+            }
+            "conformance" => {
+                let conformance_args = ConformanceArgs {
+                    webdriver: options
+                        .webdriver
+                        .take()
+                        .ok_or_else(|| anyhow!("Missing '--webdriver' option"))?,
+                    browser: options.browser.take().unwrap_or(Browser::Chrome),
+                };
+                Command::Conformance(options.server_args(positional)?, conformance_args)
+            }
+            _ => bail!("Unknown command '{command}'"),
+        };
+
+        Ok(command)
+    }
+}
+
+#[derive(Default)]
+struct Options {
+    insecure: bool,
+    dir: Option<PathBuf>,
+    port: Option<u16>,
+    cert: Option<PathBuf>,
+    key: Option<PathBuf>,
+    streams: Option<usize>,
+    size: Option<usize>,
+    webdriver: Option<String>,
+    browser: Option<Browser>,
+}
+
+impl Options {
+    fn client_args(self, positional: Vec<String>) -> Result<ClientArgs> {
+        let mut positional = positional.into_iter();
+        let url = positional.next().ok_or_else(|| anyhow!("Missing URL"))?;
+
+        if let Some(arg) = positional.next() {
+            bail!("Unexpected argument '{arg}'");
+        }
+
+        Ok(ClientArgs {
+            url,
+            insecure: self.insecure,
+        })
+    }
+
+    fn server_args(self, positional: Vec<String>) -> Result<ServerArgs> {
+        if let Some(arg) = positional.into_iter().next() {
+            bail!("Unexpected argument '{arg}'");
+        }
+
+        Ok(ServerArgs {
+            port: self.port.unwrap_or(4433),
+            cert: self.cert.unwrap_or_else(|| "cert.pem".into()),
+            key: self.key.unwrap_or_else(|| "key.pem".into()),
+        })
+    }
+}
+
+fn parse_value<T>(option: &str, value: &str) -> Result<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    value
+        .parse()
+        .with_context(|| format!("Invalid value '{value}' for '{option}'"))
+}
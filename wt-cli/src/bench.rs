@@ -0,0 +1,86 @@
+use crate::args::BenchArgs;
+use crate::args::ClientArgs;
+use anyhow::ensure;
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::info;
+use wtransport::Connection;
+
+/// Size of the chunks written on streams.
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// Measures the round-trip throughput against an `echo` server.
+pub async fn run(client_args: ClientArgs, bench_args: BenchArgs) -> Result<()> {
+    let connection = Arc::new(
+        crate::client_endpoint(&client_args)?
+            .connect(&client_args.url)
+            .await?,
+    );
+
+    info!(
+        "Sending {} bytes on {} streams to '{}'",
+        bench_args.size,
+        bench_args.streams,
+        connection.remote_address()
+    );
+
+    let start = Instant::now();
+
+    let transfers = (0..bench_args.streams)
+        .map(|_| {
+            let connection = connection.clone();
+            tokio::spawn(async move { transfer(&connection, bench_args.size).await })
+        })
+        .collect::<Vec<_>>();
+
+    for transfer in transfers {
+        transfer.await??;
+    }
+
+    let elapsed = start.elapsed();
+    let total = (bench_args.size * bench_args.streams) as f64;
+
+    println!(
+        "{} bytes echoed in {:.3?} ({:.2} MiB/s)",
+        bench_args.size * bench_args.streams,
+        elapsed,
+        total / elapsed.as_secs_f64() / (1024.0 * 1024.0)
+    );
+
+    Ok(())
+}
+
+async fn transfer(connection: &Connection, size: usize) -> Result<()> {
+    let (mut send_stream, mut recv_stream) = connection.open_bi().await?.await?;
+
+    let upload = async {
+        let chunk = vec![0x42; CHUNK_SIZE];
+        let mut remaining = size;
+
+        while remaining > 0 {
+            let len = remaining.min(CHUNK_SIZE);
+            send_stream.write_all(&chunk[..len]).await?;
+            remaining -= len;
+        }
+
+        send_stream.finish().await?;
+        Ok::<_, anyhow::Error>(())
+    };
+
+    let download = async {
+        let mut buffer = vec![0; CHUNK_SIZE];
+        let mut received = 0;
+
+        while let Some(read) = recv_stream.read(&mut buffer).await? {
+            received += read;
+        }
+
+        ensure!(received == size, "Echoed {received} bytes out of {size}");
+        Ok(())
+    };
+
+    tokio::try_join!(upload, download)?;
+
+    Ok(())
+}
@@ -0,0 +1,112 @@
+// Conformance tests run in the browser by `wt conformance`.
+//
+// Called (through WebDriver BiDi `script.callFunction`) with the base URL of the server and
+// the SHA-256 hash of its certificate. Results are reported as lines `PASS <name>` or
+// `FAIL <name>: <error>`.
+
+async (baseUrl, certificateHash) => {
+  const options = {
+    serverCertificateHashes: [{ algorithm: "sha-256", value: new Uint8Array(certificateHash) }],
+  };
+
+  const TEST_TIMEOUT_MS = 5000;
+  const DATAGRAM_RETRY_MS = 200;
+
+  const encoder = new TextEncoder();
+  const decoder = new TextDecoder();
+  const results = [];
+
+  const sleep = (ms) => new Promise((resolve) => setTimeout(resolve, ms));
+
+  async function check(name, test) {
+    const timeout = sleep(TEST_TIMEOUT_MS).then(() => {
+      throw new Error("timed out");
+    });
+
+    try {
+      await Promise.race([test(), timeout]);
+      results.push(`PASS ${name}`);
+    } catch (error) {
+      results.push(`FAIL ${name}: ${error}`);
+    }
+  }
+
+  async function readAll(readable) {
+    const reader = readable.getReader();
+    const chunks = [];
+
+    for (;;) {
+      const { value, done } = await reader.read();
+      if (done) {
+        break;
+      }
+      chunks.push(...value);
+    }
+
+    return decoder.decode(new Uint8Array(chunks));
+  }
+
+  function expectEcho(echo) {
+    if (echo !== "hello") {
+      throw new Error(`echoed '${echo}'`);
+    }
+  }
+
+  let transport = null;
+
+  await check("handshake", async () => {
+    transport = new WebTransport(`${baseUrl}/echo`, options);
+    await transport.ready;
+  });
+
+  if (transport !== null) {
+    await check("bidirectional stream", async () => {
+      const stream = await transport.createBidirectionalStream();
+      const writer = stream.writable.getWriter();
+      await writer.write(encoder.encode("hello"));
+      await writer.close();
+      expectEcho(await readAll(stream.readable));
+    });
+
+    await check("unidirectional stream", async () => {
+      const stream = await transport.createUnidirectionalStream();
+      const writer = stream.getWriter();
+      await writer.write(encoder.encode("hello"));
+      await writer.close();
+
+      const reader = transport.incomingUnidirectionalStreams.getReader();
+      const { value } = await reader.read();
+      reader.releaseLock();
+      expectEcho(await readAll(value));
+    });
+
+    await check("datagram", async () => {
+      const writer = transport.datagrams.writable.getWriter();
+      const reader = transport.datagrams.readable.getReader();
+
+      // Datagrams are unreliable: send until the echo is received.
+      let received = null;
+      reader.read().then((result) => (received = result));
+
+      while (received === null) {
+        await writer.write(encoder.encode("hello"));
+        await sleep(DATAGRAM_RETRY_MS);
+      }
+
+      expectEcho(decoder.decode(received.value));
+    });
+
+    await check("client close", async () => {
+      transport.close({ closeCode: 7, reason: "bye" });
+      await transport.closed;
+    });
+  }
+
+  await check("server close", async () => {
+    const transport = new WebTransport(`${baseUrl}/close`, options);
+    await transport.ready;
+    await transport.closed.catch(() => {});
+  });
+
+  return results.join("\n");
+}
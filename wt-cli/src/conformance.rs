@@ -0,0 +1,595 @@
+use crate::args::Browser;
+use crate::args::ConformanceArgs;
+use crate::args::ServerArgs;
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Context;
+use anyhow::Result;
+use futures_util::SinkExt;
+use futures_util::StreamExt;
+use ring::digest::digest;
+use ring::digest::SHA256;
+use serde_json::json;
+use serde_json::Value;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::MaybeTlsStream;
+use tokio_tungstenite::WebSocketStream;
+use tracing::error;
+use tracing::info;
+use wtransport::endpoint::IncomingSession;
+use wtransport::tls::Certificate;
+use wtransport::Connection;
+use wtransport_proto::varint::VarInt;
+
+/// Tests run in the browser.
+const SCRIPT: &str = include_str!("conformance.js");
+
+/// Page loaded by the browser before running the tests (`localhost` is a secure context).
+const PAGE: &str = "<!DOCTYPE html><title>wt conformance</title>";
+
+/// Maximum time the tests may take in the browser (BiDi has no script timeout).
+const SCRIPT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Maximum time for the server to observe the end of the session closed by the browser.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Delay before the server closes the sessions of the `server close` test.
+const SERVER_CLOSE_DELAY: Duration = Duration::from_millis(100);
+
+/// Runs conformance tests of a local server against a browser, driven through WebDriver BiDi.
+///
+/// The browser connects to the server (built with this crate) and checks the handshake,
+/// streams, datagrams and close in both directions. The session is created with WebDriver
+/// classic (as `chromedriver` only supports BiDi on top of it), then driven over BiDi.
+pub async fn run(server_args: ServerArgs, conformance_args: ConformanceArgs) -> Result<()> {
+    let certificate = Certificate::load(&server_args.cert, &server_args.key)
+        .context("Cannot load server certificate")?;
+    let certificate_hash = digest(
+        &SHA256,
+        certificate
+            .certificates_der()
+            .next()
+            .context("Empty certificate chain")?,
+    );
+
+    let server = crate::server_endpoint(&server_args)?;
+    let (closed_sender, mut closed_receiver) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        loop {
+            let incoming_session = server.accept().await;
+            tokio::spawn(handle_session(incoming_session, closed_sender.clone()));
+        }
+    });
+
+    let page_listener = TcpListener::bind("127.0.0.1:0").await?;
+    let page_url = format!("http://localhost:{}/", page_listener.local_addr()?.port());
+    tokio::spawn(serve_page(page_listener));
+
+    let webdriver = WebDriver::new(&conformance_args.webdriver)?;
+    let session = webdriver.new_session(conformance_args.browser).await?;
+
+    info!(
+        "Running conformance tests (WebDriver session '{}')",
+        session.id
+    );
+
+    let server_url = format!("https://localhost:{}", server_args.port);
+    let script_args = json!([
+        { "type": "string", "value": server_url },
+        {
+            "type": "array",
+            "value": certificate_hash
+                .as_ref()
+                .iter()
+                .map(|byte| json!({ "type": "number", "value": byte }))
+                .collect::<Vec<_>>(),
+        },
+    ]);
+
+    let report = match Bidi::connect(&session.web_socket_url).await {
+        Ok(mut bidi) => {
+            tokio::time::timeout(SCRIPT_TIMEOUT, bidi.run_script(&page_url, script_args))
+                .await
+                .unwrap_or_else(|_| Err(anyhow!("Conformance tests timed out")))
+        }
+        Err(error) => Err(error),
+    };
+    webdriver.delete_session(&session.id).await;
+
+    let mut lines = report?.lines().map(str::to_string).collect::<Vec<_>>();
+
+    lines.push(
+        match tokio::time::timeout(CLOSE_TIMEOUT, closed_receiver.recv()).await {
+            Ok(Some(reason)) => format!("PASS client close observed by server: {reason}"),
+            _ => "FAIL client close observed by server: session still open".to_string(),
+        },
+    );
+
+    for line in &lines {
+        println!("{line}");
+    }
+
+    let failures = lines.iter().filter(|line| line.starts_with("FAIL")).count();
+    ensure!(failures == 0, "{failures} conformance tests failed");
+
+    Ok(())
+}
+
+async fn handle_session(incoming_session: IncomingSession, closed: mpsc::UnboundedSender<String>) {
+    if let Err(error) = handle_session_impl(incoming_session, closed).await {
+        error!("{:?}", error);
+    }
+}
+
+async fn handle_session_impl(
+    incoming_session: IncomingSession,
+    closed: mpsc::UnboundedSender<String>,
+) -> Result<()> {
+    let session_request = incoming_session.await?;
+
+    match session_request.path() {
+        "/echo" => {
+            let connection = session_request.accept().await?;
+
+            if let Err(error) = echo(&connection).await {
+                let _ = closed.send(error.to_string());
+            }
+        }
+        "/close" => {
+            let connection = session_request.accept().await?;
+            tokio::time::sleep(SERVER_CLOSE_DELAY).await;
+            connection.close(VarInt::from_u32(42), b"done");
+        }
+        _ => session_request.not_found().await,
+    }
+
+    Ok(())
+}
+
+/// Echoes back streams and datagrams, until the session ends.
+async fn echo(connection: &Connection) -> Result<()> {
+    loop {
+        tokio::select! {
+            stream = connection.accept_bi() => {
+                let (mut send_stream, mut recv_stream) = stream?;
+
+                tokio::spawn(async move {
+                    tokio::io::copy(&mut recv_stream, &mut send_stream).await?;
+                    send_stream.finish().await?;
+                    Ok::<_, anyhow::Error>(())
+                });
+            }
+            stream = connection.accept_uni() => {
+                let mut recv_stream = stream?;
+                let opening_stream = connection.open_uni().await?;
+
+                tokio::spawn(async move {
+                    let mut payload = Vec::new();
+                    recv_stream.read_to_end(&mut payload).await?;
+
+                    let mut send_stream = opening_stream.await?;
+                    send_stream.write_all(&payload).await?;
+                    send_stream.finish().await?;
+                    Ok::<_, anyhow::Error>(())
+                });
+            }
+            dgram = connection.receive_datagram() => {
+                let dgram = dgram?;
+                connection.send_datagram(&*dgram)?;
+            }
+        }
+    }
+}
+
+/// Serves [`PAGE`] to every HTTP request.
+async fn serve_page(listener: TcpListener) {
+    while let Ok((mut stream, _)) = listener.accept().await {
+        tokio::spawn(async move {
+            // The request is not parsed: a single read is enough for a GET.
+            let mut request = [0; 4096];
+            let _ = stream.read(&mut request).await?;
+
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{PAGE}",
+                PAGE.len()
+            );
+            stream.write_all(response.as_bytes()).await?;
+            stream.shutdown().await
+        });
+    }
+}
+
+/// A WebDriver session, with its BiDi endpoint.
+#[derive(Debug)]
+struct Session {
+    id: String,
+    web_socket_url: String,
+}
+
+impl Session {
+    /// Parses the response to a *New Session* command.
+    fn parse(response: &Value) -> Result<Self> {
+        let value = &response["value"];
+
+        let id = value["sessionId"]
+            .as_str()
+            .context("No session ID in WebDriver response")?;
+        let web_socket_url = value["capabilities"]["webSocketUrl"]
+            .as_str()
+            .context("WebDriver server does not support BiDi")?;
+
+        Ok(Self {
+            id: id.to_string(),
+            web_socket_url: web_socket_url.to_string(),
+        })
+    }
+}
+
+/// A minimal client of a WebDriver server (classic, for the session lifetime only).
+struct WebDriver {
+    address: String,
+    base_path: String,
+}
+
+impl WebDriver {
+    fn new(url: &str) -> Result<Self> {
+        let rest = url
+            .strip_prefix("http://")
+            .with_context(|| format!("Invalid WebDriver URL '{url}' (expected 'http://...')"))?;
+
+        let (address, base_path) = match rest.find('/') {
+            Some(index) => (&rest[..index], rest[index..].trim_end_matches('/')),
+            None => (rest, ""),
+        };
+
+        Ok(Self {
+            address: address.to_string(),
+            base_path: base_path.to_string(),
+        })
+    }
+
+    async fn new_session(&self, browser: Browser) -> Result<Session> {
+        let mut capabilities = match browser {
+            Browser::Chrome => json!({
+                "browserName": "chrome",
+                "goog:chromeOptions": { "args": ["--headless=new"] },
+            }),
+            Browser::Firefox => json!({
+                "browserName": "firefox",
+                "moz:firefoxOptions": { "args": ["-headless"] },
+            }),
+        };
+        capabilities["webSocketUrl"] = json!(true);
+
+        let body = json!({ "capabilities": { "alwaysMatch": capabilities } });
+        let response = self.request("POST", "/session", Some(&body)).await?;
+
+        Session::parse(&response)
+    }
+
+    async fn delete_session(&self, session_id: &str) {
+        if let Err(error) = self
+            .request("DELETE", &format!("/session/{session_id}"), None)
+            .await
+        {
+            error!("Cannot delete WebDriver session: {:?}", error);
+        }
+    }
+
+    /// Sends a request, returning the body of a successful response.
+    async fn request(&self, method: &str, path: &str, body: Option<&Value>) -> Result<Value> {
+        let mut stream = TcpStream::connect(&self.address)
+            .await
+            .with_context(|| format!("Cannot connect to WebDriver at '{}'", self.address))?;
+
+        let body = body.map(Value::to_string).unwrap_or_default();
+        let request = format!(
+            "{method} {}{path} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            self.base_path,
+            self.address,
+            body.len()
+        );
+        stream.write_all(request.as_bytes()).await?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        let response = String::from_utf8(response).context("Invalid WebDriver response")?;
+
+        parse_response(&response)
+    }
+}
+
+/// Parses an HTTP response of a WebDriver server, returning the body of a successful one.
+fn parse_response(response: &str) -> Result<Value> {
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .context("Malformed WebDriver response")?;
+
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse::<u16>().ok())
+        .context("Malformed WebDriver response status")?;
+
+    let body = if head
+        .to_ascii_lowercase()
+        .contains("transfer-encoding: chunked")
+    {
+        dechunk(body).context("Malformed WebDriver response body")?
+    } else {
+        body.to_string()
+    };
+
+    let body = serde_json::from_str::<Value>(&body).context("Invalid WebDriver response body")?;
+
+    if !(200..300).contains(&status) {
+        let message = body["value"]["message"].as_str().unwrap_or_default();
+        bail!("WebDriver error (status {status}): {message}");
+    }
+
+    Ok(body)
+}
+
+/// Decodes an HTTP body with chunked transfer encoding.
+fn dechunk(mut body: &str) -> Option<String> {
+    let mut decoded = String::new();
+
+    loop {
+        let (size, rest) = body.split_once("\r\n")?;
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+
+        if size == 0 {
+            return Some(decoded);
+        }
+
+        decoded.push_str(rest.get(..size)?);
+        body = rest.get(size..)?.strip_prefix("\r\n")?;
+    }
+}
+
+/// A WebDriver BiDi connection.
+struct Bidi {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    next_id: u64,
+}
+
+impl Bidi {
+    async fn connect(url: &str) -> Result<Self> {
+        let (socket, _) = tokio_tungstenite::connect_async(url)
+            .await
+            .with_context(|| format!("Cannot connect to WebDriver BiDi at '{url}'"))?;
+
+        Ok(Self { socket, next_id: 0 })
+    }
+
+    /// Loads `page_url` in the top-level browsing context, then calls [`SCRIPT`] with `args`
+    /// (BiDi local values), returning its report.
+    async fn run_script(&mut self, page_url: &str, args: Value) -> Result<String> {
+        let tree = self
+            .command("browsingContext.getTree", json!({ "maxDepth": 0 }))
+            .await?;
+        let context = tree["contexts"][0]["context"]
+            .as_str()
+            .context("No browsing context")?
+            .to_string();
+
+        self.command(
+            "browsingContext.navigate",
+            json!({ "context": context, "url": page_url, "wait": "complete" }),
+        )
+        .await?;
+
+        let result = self
+            .command(
+                "script.callFunction",
+                json!({
+                    "functionDeclaration": SCRIPT,
+                    "arguments": args,
+                    "target": { "context": context },
+                    "awaitPromise": true,
+                }),
+            )
+            .await?;
+
+        script_report(&result)
+    }
+
+    /// Sends a command, returning its result once the response is received.
+    ///
+    /// Events received meanwhile are ignored.
+    async fn command(&mut self, method: &str, params: Value) -> Result<Value> {
+        self.next_id += 1;
+        let id = self.next_id;
+
+        let command = json!({ "id": id, "method": method, "params": params });
+        self.socket
+            .send(Message::Text(command.to_string()))
+            .await
+            .context("Cannot send WebDriver BiDi command")?;
+
+        loop {
+            let message = self
+                .socket
+                .next()
+                .await
+                .context("WebDriver BiDi connection closed")?
+                .context("WebDriver BiDi connection failed")?;
+
+            if let Message::Text(text) = message {
+                let message = serde_json::from_str::<Value>(&text)
+                    .context("Invalid WebDriver BiDi message")?;
+
+                if let Some(result) = command_result(message, id)? {
+                    return Ok(result);
+                }
+            }
+        }
+    }
+}
+
+/// Returns the result of the command `id` if `message` is its response.
+fn command_result(mut message: Value, id: u64) -> Result<Option<Value>> {
+    if message["id"].as_u64() != Some(id) {
+        return Ok(None);
+    }
+
+    if message["type"] == "error" {
+        bail!(
+            "WebDriver BiDi error: {}: {}",
+            message["error"].as_str().unwrap_or("unknown error"),
+            message["message"].as_str().unwrap_or_default()
+        );
+    }
+
+    Ok(Some(message["result"].take()))
+}
+
+/// Returns the report of the tests from the result of `script.callFunction`.
+fn script_report(result: &Value) -> Result<String> {
+    match result["type"].as_str() {
+        Some("success") => result["result"]["value"]
+            .as_str()
+            .map(str::to_string)
+            .context("Conformance tests did not return a report"),
+        Some("exception") => bail!(
+            "Conformance tests threw: {}",
+            result["exceptionDetails"]["text"]
+                .as_str()
+                .unwrap_or("unknown exception")
+        ),
+        _ => bail!("Unexpected script result: {result}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn response() {
+        let response = "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\r\n{\"value\":{\"sessionId\":\"s1\",\"capabilities\":{\"webSocketUrl\":\"ws://127.0.0.1:9222/session/s1\"}}}";
+        let session = Session::parse(&parse_response(response).unwrap()).unwrap();
+        assert_eq!(session.id, "s1");
+        assert_eq!(session.web_socket_url, "ws://127.0.0.1:9222/session/s1");
+
+        // Chunked, without BiDi.
+        let response = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n8\r\n{\"value\"\r\n14\r\n:{\"sessionId\":\"s2\"}}\r\n0\r\n\r\n";
+        let error = Session::parse(&parse_response(response).unwrap()).unwrap_err();
+        assert!(error.to_string().contains("BiDi"), "{error}");
+
+        let response = "HTTP/1.1 500 Internal Server Error\r\n\r\n{\"value\":{\"error\":\"session not created\",\"message\":\"no \\\"chrome\\\" binary\"}}";
+        let error = parse_response(response).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "WebDriver error (status 500): no \"chrome\" binary"
+        );
+
+        assert!(parse_response("HTTP/1.1 200 OK\r\n\r\nnot json").is_err());
+        assert!(parse_response("HTTP/1.1 200 OK").is_err());
+    }
+
+    #[test]
+    fn chunked() {
+        assert_eq!(
+            dechunk("5;ext=1\r\nhello\r\n1\r\n!\r\n0\r\n\r\n").as_deref(),
+            Some("hello!")
+        );
+        assert_eq!(dechunk("5\r\nhel"), None);
+        assert_eq!(dechunk("x\r\n"), None);
+    }
+
+    #[test]
+    fn results() {
+        let event = json!({ "type": "event", "method": "log.entryAdded", "params": {} });
+        assert!(command_result(event, 1).unwrap().is_none());
+
+        let other = json!({ "type": "success", "id": 2, "result": {} });
+        assert!(command_result(other, 1).unwrap().is_none());
+
+        let success = json!({ "type": "success", "id": 1, "result": { "navigation": "n" } });
+        assert_eq!(
+            command_result(success, 1).unwrap(),
+            Some(json!({ "navigation": "n" }))
+        );
+
+        let error = json!({
+            "type": "error",
+            "id": 1,
+            "error": "no such frame",
+            "message": "context not found",
+        });
+        assert_eq!(
+            command_result(error, 1).unwrap_err().to_string(),
+            "WebDriver BiDi error: no such frame: context not found"
+        );
+    }
+
+    #[test]
+    fn reports() {
+        let success = json!({
+            "type": "success",
+            "result": { "type": "string", "value": "PASS handshake" },
+        });
+        assert_eq!(script_report(&success).unwrap(), "PASS handshake");
+
+        let exception = json!({
+            "type": "exception",
+            "exceptionDetails": { "text": "ReferenceError: WebTransport is not defined" },
+        });
+        assert!(script_report(&exception)
+            .unwrap_err()
+            .to_string()
+            .contains("WebTransport is not defined"));
+
+        let undefined = json!({ "type": "success", "result": { "type": "undefined" } });
+        assert!(script_report(&undefined).is_err());
+    }
+
+    /// A BiDi server answering `browsingContext.getTree` after an event.
+    #[tokio::test]
+    async fn command() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/session/s1", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+
+            let command = socket.next().await.unwrap().unwrap().into_text().unwrap();
+            let command = serde_json::from_str::<Value>(&command).unwrap();
+            assert_eq!(command["method"], "browsingContext.getTree");
+            assert_eq!(command["params"], json!({ "maxDepth": 0 }));
+
+            let event = json!({ "type": "event", "method": "log.entryAdded", "params": {} });
+            let response = json!({
+                "type": "success",
+                "id": command["id"],
+                "result": { "contexts": [{ "context": "c1" }] },
+            });
+
+            for message in [event, response] {
+                socket
+                    .send(Message::Text(message.to_string()))
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let mut bidi = Bidi::connect(&url).await.unwrap();
+        let tree = bidi
+            .command("browsingContext.getTree", json!({ "maxDepth": 0 }))
+            .await
+            .unwrap();
+        assert_eq!(tree["contexts"][0]["context"], "c1");
+
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,29 @@
+use crate::args::ClientArgs;
+use anyhow::Result;
+use tracing::info;
+
+/// Pipes stdin and stdout through a bidirectional stream.
+pub async fn run(client_args: ClientArgs) -> Result<()> {
+    let connection = crate::client_endpoint(&client_args)?
+        .connect(&client_args.url)
+        .await?;
+
+    info!("Connected to '{}'", connection.remote_address());
+
+    let (mut send_stream, mut recv_stream) = connection.open_bi().await?.await?;
+
+    let upload = async {
+        tokio::io::copy(&mut tokio::io::stdin(), &mut send_stream).await?;
+        send_stream.finish().await?;
+        Ok::<_, anyhow::Error>(())
+    };
+
+    let download = async {
+        tokio::io::copy(&mut recv_stream, &mut tokio::io::stdout()).await?;
+        Ok::<_, anyhow::Error>(())
+    };
+
+    tokio::try_join!(upload, download)?;
+
+    Ok(())
+}
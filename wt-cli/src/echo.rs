@@ -0,0 +1,51 @@
+use crate::args::ServerArgs;
+use anyhow::Result;
+use tracing::error;
+use tracing::info;
+use tracing::info_span;
+use tracing::Instrument;
+use wtransport::endpoint::IncomingSession;
+
+/// Echoes back bidirectional streams and datagrams.
+pub async fn run(server_args: ServerArgs) -> Result<()> {
+    let server = crate::server_endpoint(&server_args)?;
+
+    info!("Echo server listening on port {}", server_args.port);
+
+    for id in 0.. {
+        let incoming_session = server.accept().await;
+        tokio::spawn(handle_session(incoming_session).instrument(info_span!("Session", id)));
+    }
+
+    Ok(())
+}
+
+async fn handle_session(incoming_session: IncomingSession) {
+    if let Err(error) = handle_session_impl(incoming_session).await {
+        error!("{:?}", error);
+    }
+}
+
+async fn handle_session_impl(incoming_session: IncomingSession) -> Result<()> {
+    let connection = incoming_session.await?.accept().await?;
+
+    info!("Session from '{}'", connection.remote_address());
+
+    loop {
+        tokio::select! {
+            stream = connection.accept_bi() => {
+                let (mut send_stream, mut recv_stream) = stream?;
+
+                tokio::spawn(async move {
+                    tokio::io::copy(&mut recv_stream, &mut send_stream).await?;
+                    send_stream.finish().await?;
+                    Ok::<_, anyhow::Error>(())
+                });
+            }
+            dgram = connection.receive_datagram() => {
+                let dgram = dgram?;
+                connection.send_datagram(&*dgram)?;
+            }
+        }
+    }
+}
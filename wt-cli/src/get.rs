@@ -0,0 +1,30 @@
+use crate::args::ClientArgs;
+use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Result;
+use tracing::info;
+use wtransport::file_transfer::Status;
+
+/// Downloads `path` from a `serve` server into stdout.
+pub async fn run(client_args: ClientArgs, path: String) -> Result<()> {
+    let connection = crate::client_endpoint(&client_args)?
+        .connect(&client_args.url)
+        .await?;
+
+    let response = wtransport::file_transfer::fetch(&connection, &path).await?;
+
+    if response.status() != Status::OK {
+        bail!("Cannot get '{}': status {}", path, response.status());
+    }
+
+    let length = response.len();
+    info!("Receiving '{}' ({} bytes)", path, length);
+
+    let received = tokio::io::copy(&mut response.into_body(), &mut tokio::io::stdout()).await?;
+    ensure!(
+        received == length,
+        "Received {received} bytes out of {length}"
+    );
+
+    Ok(())
+}
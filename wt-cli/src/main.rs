@@ -0,0 +1,87 @@
+//! `wt`: command line utilities for WebTransport.
+//!
+//! Run `wt --help` for the list of commands.
+
+use anyhow::Context;
+use anyhow::Result;
+use args::ClientArgs;
+use args::Command;
+use args::ServerArgs;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::EnvFilter;
+use wtransport::endpoint::Client;
+use wtransport::endpoint::Server;
+use wtransport::tls::Certificate;
+use wtransport::ClientConfig;
+use wtransport::Endpoint;
+use wtransport::ServerConfig;
+
+mod args;
+mod bench;
+mod conformance;
+mod connect;
+mod echo;
+mod get;
+mod serve;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    if args.is_empty() || args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", args::USAGE);
+        return Ok(());
+    }
+
+    init_logging();
+
+    let command = Command::parse(args).context("Run 'wt --help' for usage")?;
+
+    match command {
+        Command::Connect(client_args) => connect::run(client_args).await,
+        Command::Get(client_args, path) => get::run(client_args, path).await,
+        Command::Serve(server_args, dir) => serve::run(server_args, dir).await,
+        Command::Echo(server_args) => echo::run(server_args).await,
+        Command::Bench(client_args, bench_args) => bench::run(client_args, bench_args).await,
+        Command::Conformance(server_args, conformance_args) => {
+            conformance::run(server_args, conformance_args).await
+        }
+    }
+}
+
+fn client_endpoint(client_args: &ClientArgs) -> Result<Endpoint<Client>> {
+    let builder = ClientConfig::builder().with_bind_default();
+
+    let config = if client_args.insecure {
+        builder.with_no_cert_validation().build()
+    } else {
+        builder.with_native_certs().build()
+    };
+
+    Ok(Endpoint::client(config)?)
+}
+
+fn server_endpoint(server_args: &ServerArgs) -> Result<Endpoint<Server>> {
+    let certificate = Certificate::load(&server_args.cert, &server_args.key)
+        .context("Cannot load server certificate")?;
+
+    let config = ServerConfig::builder()
+        .with_bind_default(server_args.port)
+        .with_certificate(certificate)
+        .build();
+
+    Ok(Endpoint::server(config)?)
+}
+
+fn init_logging() {
+    let env_filter = EnvFilter::builder()
+        .with_default_directive(LevelFilter::INFO.into())
+        .from_env_lossy();
+
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_target(true)
+        .with_level(true)
+        .with_env_filter(env_filter)
+        .init();
+}
@@ -0,0 +1,48 @@
+use crate::args::ServerArgs;
+use anyhow::Result;
+use std::path::PathBuf;
+use tracing::error;
+use tracing::info;
+use tracing::info_span;
+use tracing::Instrument;
+use wtransport::endpoint::IncomingSession;
+use wtransport::file_transfer::FileServer;
+
+/// Serves the files of `dir` (see [`wtransport::file_transfer`]).
+pub async fn run(server_args: ServerArgs, dir: PathBuf) -> Result<()> {
+    let server = crate::server_endpoint(&server_args)?;
+    let file_server = FileServer::new(dir);
+
+    info!(
+        "Serving '{}' on port {}",
+        file_server.root().display(),
+        server_args.port
+    );
+
+    for id in 0.. {
+        let incoming_session = server.accept().await;
+        tokio::spawn(
+            handle_session(incoming_session, file_server.clone())
+                .instrument(info_span!("Session", id)),
+        );
+    }
+
+    Ok(())
+}
+
+async fn handle_session(incoming_session: IncomingSession, file_server: FileServer) {
+    if let Err(error) = handle_session_impl(incoming_session, file_server).await {
+        error!("{:?}", error);
+    }
+}
+
+async fn handle_session_impl(
+    incoming_session: IncomingSession,
+    file_server: FileServer,
+) -> Result<()> {
+    let connection = incoming_session.await?.accept().await?;
+
+    info!("Session from '{}'", connection.remote_address());
+
+    Err(file_server.serve(&connection).await.into())
+}
@@ -1,8 +1,12 @@
 use crate::varint::VarInt;
+use alloc::vec::Vec;
+use core::ops::Deref;
+use core::ops::DerefMut;
 use octets::Octets;
 use octets::OctetsMut;
-use std::ops::Deref;
-use std::ops::DerefMut;
+
+#[cfg(not(feature = "std"))]
+use crate::octets;
 
 /// An error indicating write operation was not able to complete because
 /// end of buffer has been reached.
@@ -332,9 +336,9 @@ pub mod r#async {
         ///
         /// An implementation SHOULD only generates the following errors:
         ///   * [`std::io::ErrorKind::ConnectionReset`] if the read operation was explicitly truncated
-        ///      by the source.
+        ///     by the source.
         ///   * [`std::io::ErrorKind::NotConnected`] if the read operation aborted at any point because
-        ///      lack of communication with the source.
+        ///     lack of communication with the source.
         fn poll_read(
             self: Pin<&mut Self>,
             cx: &mut Context<'_>,
@@ -372,9 +376,9 @@ pub mod r#async {
         ///
         /// An implementation SHOULD only generates the following errors:
         ///   * [`std::io::ErrorKind::ConnectionReset`] if the write operation was explicitly stopped
-        ///      by the destination.
+        ///     by the destination.
         ///   * [`std::io::ErrorKind::NotConnected`] if the write operation aborted at any point because
-        ///      lack of communication with the destionation.
+        ///     lack of communication with the destionation.
         // TODO(bfesta): change return time from `usize` to `NonZero`.
         fn poll_write(
             self: Pin<&mut Self>,
@@ -402,7 +406,7 @@ pub mod r#async {
         /// If EOF is reached during read the very **first** byte:
         ///   * [`IoReadError::ImmediateFin`] is returned if `unexpected_fin_first` is false.
         ///   * [`IoReadError::UnexpectedFin`] is returned if `unexpected_fin_first` is true.
-        fn get_varint(&mut self, unexpected_fin_first: bool) -> GetVarint<Self>;
+        fn get_varint(&mut self, unexpected_fin_first: bool) -> GetVarint<'_, Self>;
 
         /// Reads the source until `buffer` is completly filled.
         ///
@@ -413,14 +417,14 @@ pub mod r#async {
             &'a mut self,
             buffer: &'a mut [u8],
             unexpected_fin_first: bool,
-        ) -> GetBuffer<Self>;
+        ) -> GetBuffer<'a, Self>;
     }
 
     impl<T> BytesReaderAsync for T
     where
         T: AsyncRead + ?Sized,
     {
-        fn get_varint(&mut self, unexpected_fin_first: bool) -> GetVarint<Self> {
+        fn get_varint(&mut self, unexpected_fin_first: bool) -> GetVarint<'_, Self> {
             GetVarint::new(self, unexpected_fin_first)
         }
 
@@ -428,7 +432,7 @@ pub mod r#async {
             &'a mut self,
             buffer: &'a mut [u8],
             unexpected_fin_first: bool,
-        ) -> GetBuffer<Self> {
+        ) -> GetBuffer<'a, Self> {
             GetBuffer::new(self, buffer, unexpected_fin_first)
         }
     }
@@ -437,11 +441,11 @@ pub mod r#async {
     where
         T: AsyncWrite + ?Sized,
     {
-        fn put_varint(&mut self, varint: VarInt) -> PutVarint<Self> {
+        fn put_varint(&mut self, varint: VarInt) -> PutVarint<'_, Self> {
             PutVarint::new(self, varint)
         }
 
-        fn put_buffer<'a>(&'a mut self, buffer: &'a [u8]) -> PutBuffer<Self> {
+        fn put_buffer<'a>(&'a mut self, buffer: &'a [u8]) -> PutBuffer<'a, Self> {
             PutBuffer::new(self, buffer)
         }
     }
@@ -451,10 +455,10 @@ pub mod r#async {
     pub trait BytesWriterAsync {
         /// Writes an unsigned variable-length integer in network byte-order to
         /// the source advancing the buffer's internal cursor.
-        fn put_varint(&mut self, varint: VarInt) -> PutVarint<Self>;
+        fn put_varint(&mut self, varint: VarInt) -> PutVarint<'_, Self>;
 
         /// Pushes some bytes into ths source advancing the buffer’s internal cursor.
-        fn put_buffer<'a>(&'a mut self, buffer: &'a [u8]) -> PutBuffer<Self>;
+        fn put_buffer<'a>(&'a mut self, buffer: &'a [u8]) -> PutBuffer<'a, Self>;
     }
 
     /// [`Future`] for reading a varint.
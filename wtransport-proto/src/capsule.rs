@@ -0,0 +1,520 @@
+use crate::bytes::BufferReader;
+use crate::bytes::BytesReader;
+use crate::bytes::BytesWriter;
+use crate::bytes::EndOfBuffer;
+use crate::varint::VarInt;
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// A [`Capsule`] type defined by HTTP or WebTransport.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CapsuleKind {
+    /// DATAGRAM capsule type (see [RFC 9297](https://www.rfc-editor.org/rfc/rfc9297#section-3.5)).
+    Datagram,
+
+    /// CLOSE_WEBTRANSPORT_SESSION capsule type.
+    CloseWebTransportSession,
+
+    /// DRAIN_WEBTRANSPORT_SESSION capsule type.
+    DrainWebTransportSession,
+}
+
+impl CapsuleKind {
+    /// Returns the kind of a capsule type identifier, if defined.
+    #[inline(always)]
+    pub const fn parse(id: VarInt) -> Option<Self> {
+        match id {
+            capsule_kind_ids::DATAGRAM => Some(CapsuleKind::Datagram),
+            capsule_kind_ids::CLOSE_WEBTRANSPORT_SESSION => {
+                Some(CapsuleKind::CloseWebTransportSession)
+            }
+            capsule_kind_ids::DRAIN_WEBTRANSPORT_SESSION => {
+                Some(CapsuleKind::DrainWebTransportSession)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the capsule type identifier, as encoded on the wire.
+    #[inline(always)]
+    pub const fn id(self) -> VarInt {
+        match self {
+            CapsuleKind::Datagram => capsule_kind_ids::DATAGRAM,
+            CapsuleKind::CloseWebTransportSession => capsule_kind_ids::CLOSE_WEBTRANSPORT_SESSION,
+            CapsuleKind::DrainWebTransportSession => capsule_kind_ids::DRAIN_WEBTRANSPORT_SESSION,
+        }
+    }
+}
+
+/// Error when parsing a defined capsule from a [`Capsule`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CapsuleParseError {
+    /// The capsule type is not the expected one.
+    UnexpectedKind,
+
+    /// The payload is shorter than required by the capsule format.
+    PayloadTooShort,
+
+    /// The payload is expected to be empty.
+    PayloadNotEmpty,
+
+    /// The close reason exceeds [`CloseSessionCapsule::MAX_REASON_LEN`] bytes.
+    ReasonTooLong,
+
+    /// The close reason is not valid UTF-8.
+    InvalidUtf8Reason,
+}
+
+/// An HTTP capsule (see [RFC 9297](https://www.rfc-editor.org/rfc/rfc9297#section-3.2)).
+///
+/// Capsules are carried, as a sequence, in the payload of DATA frames of the
+/// session stream.
+pub struct Capsule<'a> {
+    capsule_type: VarInt,
+    payload: Cow<'a, [u8]>,
+}
+
+impl<'a> Capsule<'a> {
+    /// Creates a new [`Capsule`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `payload` size if greater than [`VarInt::MAX`].
+    #[inline(always)]
+    pub fn new(capsule_type: VarInt, payload: Cow<'a, [u8]>) -> Self {
+        assert!(payload.len() <= VarInt::MAX.into_inner() as usize);
+
+        Self {
+            capsule_type,
+            payload,
+        }
+    }
+
+    /// Reads a [`Capsule`] from a [`BytesReader`].
+    ///
+    /// It returns [`None`] if the `bytes_reader` does not contain enough bytes
+    /// to parse an entire capsule.
+    ///
+    /// In case [`None`], `bytes_reader` might be partially read.
+    pub fn read<R>(bytes_reader: &mut R) -> Option<Self>
+    where
+        R: BytesReader<'a>,
+    {
+        let capsule_type = bytes_reader.get_varint()?;
+        let payload_len = bytes_reader.get_varint()?.into_inner() as usize;
+        let payload = bytes_reader.get_bytes(payload_len)?;
+
+        Some(Self::new(capsule_type, Cow::Borrowed(payload)))
+    }
+
+    /// Reads a [`Capsule`] from a [`BufferReader`].
+    ///
+    /// It returns [`None`] if the `buffer_reader` does not contain enough bytes
+    /// to parse an entire capsule.
+    ///
+    /// In case [`None`], `buffer_reader` offset if not advanced.
+    pub fn read_from_buffer(buffer_reader: &mut BufferReader<'a>) -> Option<Self> {
+        let mut buffer_reader_child = buffer_reader.child();
+
+        let capsule = Self::read(&mut *buffer_reader_child)?;
+        buffer_reader_child.commit();
+
+        Some(capsule)
+    }
+
+    /// Writes a [`Capsule`] into a [`BytesWriter`].
+    ///
+    /// It returns [`Err`] if the `bytes_writer` does not have enough capacity
+    /// to write the entire capsule.
+    /// See [`Self::write_size`] to retrieve the extact amount of required capacity.
+    ///
+    /// In case [`Err`], `bytes_writer` might be partially written.
+    pub fn write<W>(&self, bytes_writer: &mut W) -> Result<(), EndOfBuffer>
+    where
+        W: BytesWriter,
+    {
+        bytes_writer.put_varint(self.capsule_type)?;
+        bytes_writer.put_varint(self.payload_len())?;
+        bytes_writer.put_bytes(&self.payload)?;
+
+        Ok(())
+    }
+
+    /// Returns the needed capacity to write this capsule into a buffer.
+    pub fn write_size(&self) -> usize {
+        self.capsule_type.size() + self.payload_len().size() + self.payload.len()
+    }
+
+    /// Returns the type of this [`Capsule`].
+    #[inline(always)]
+    pub const fn capsule_type(&self) -> VarInt {
+        self.capsule_type
+    }
+
+    /// Returns the payload of this [`Capsule`].
+    #[inline(always)]
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Returns the kind of this [`Capsule`], if its type is defined.
+    #[inline(always)]
+    pub const fn kind(&self) -> Option<CapsuleKind> {
+        CapsuleKind::parse(self.capsule_type)
+    }
+
+    fn payload_len(&self) -> VarInt {
+        VarInt::try_from(self.payload.len() as u64)
+            .expect("Payload cannot be larger than varint max")
+    }
+
+    fn expect_kind(&self, kind: CapsuleKind) -> Result<(), CapsuleParseError> {
+        if self.capsule_type == kind.id() {
+            Ok(())
+        } else {
+            Err(CapsuleParseError::UnexpectedKind)
+        }
+    }
+}
+
+/// A DATAGRAM capsule, carrying an HTTP datagram payload on the request stream.
+pub struct DatagramCapsule<'a> {
+    payload: Cow<'a, [u8]>,
+}
+
+impl<'a> DatagramCapsule<'a> {
+    /// Creates a new [`DatagramCapsule`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `payload` size if greater than [`VarInt::MAX`].
+    #[inline(always)]
+    pub fn new(payload: Cow<'a, [u8]>) -> Self {
+        assert!(payload.len() <= VarInt::MAX.into_inner() as usize);
+        Self { payload }
+    }
+
+    /// Returns the HTTP datagram payload.
+    #[inline(always)]
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+}
+
+impl<'a> TryFrom<Capsule<'a>> for DatagramCapsule<'a> {
+    type Error = CapsuleParseError;
+
+    fn try_from(capsule: Capsule<'a>) -> Result<Self, Self::Error> {
+        capsule.expect_kind(CapsuleKind::Datagram)?;
+        Ok(Self {
+            payload: capsule.payload,
+        })
+    }
+}
+
+impl<'a> From<DatagramCapsule<'a>> for Capsule<'a> {
+    fn from(capsule: DatagramCapsule<'a>) -> Self {
+        Capsule::new(CapsuleKind::Datagram.id(), capsule.payload)
+    }
+}
+
+/// A CLOSE_WEBTRANSPORT_SESSION capsule, closing the session with an application
+/// error code and reason.
+pub struct CloseSessionCapsule<'a> {
+    error_code: u32,
+    reason: Cow<'a, str>,
+}
+
+impl<'a> CloseSessionCapsule<'a> {
+    /// Maximum length (in bytes) of the reason.
+    pub const MAX_REASON_LEN: usize = 1024;
+
+    /// Creates a new [`CloseSessionCapsule`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `reason` is longer than [`Self::MAX_REASON_LEN`] bytes.
+    #[inline(always)]
+    pub fn new(error_code: u32, reason: Cow<'a, str>) -> Self {
+        assert!(reason.len() <= Self::MAX_REASON_LEN);
+        Self { error_code, reason }
+    }
+
+    /// Returns the application error code.
+    #[inline(always)]
+    pub const fn error_code(&self) -> u32 {
+        self.error_code
+    }
+
+    /// Returns the reason of the closure.
+    #[inline(always)]
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+impl<'a> TryFrom<Capsule<'a>> for CloseSessionCapsule<'a> {
+    type Error = CapsuleParseError;
+
+    fn try_from(capsule: Capsule<'a>) -> Result<Self, Self::Error> {
+        capsule.expect_kind(CapsuleKind::CloseWebTransportSession)?;
+
+        let error_code = match capsule.payload.get(..4) {
+            Some(&[b0, b1, b2, b3]) => u32::from_be_bytes([b0, b1, b2, b3]),
+            _ => return Err(CapsuleParseError::PayloadTooShort),
+        };
+
+        if capsule.payload.len() - 4 > Self::MAX_REASON_LEN {
+            return Err(CapsuleParseError::ReasonTooLong);
+        }
+
+        let reason = match capsule.payload {
+            Cow::Borrowed(payload) => Cow::Borrowed(
+                core::str::from_utf8(&payload[4..])
+                    .map_err(|_| CapsuleParseError::InvalidUtf8Reason)?,
+            ),
+            Cow::Owned(mut payload) => {
+                payload.drain(..4);
+                Cow::Owned(
+                    String::from_utf8(payload).map_err(|_| CapsuleParseError::InvalidUtf8Reason)?,
+                )
+            }
+        };
+
+        Ok(Self { error_code, reason })
+    }
+}
+
+impl<'a> From<CloseSessionCapsule<'a>> for Capsule<'a> {
+    fn from(capsule: CloseSessionCapsule<'a>) -> Self {
+        let mut payload = Vec::with_capacity(4 + capsule.reason.len());
+        payload.extend_from_slice(&capsule.error_code.to_be_bytes());
+        payload.extend_from_slice(capsule.reason.as_bytes());
+
+        Capsule::new(
+            CapsuleKind::CloseWebTransportSession.id(),
+            Cow::Owned(payload),
+        )
+    }
+}
+
+/// A DRAIN_WEBTRANSPORT_SESSION capsule, asking the peer to gracefully end the session.
+pub struct DrainSessionCapsule;
+
+impl TryFrom<Capsule<'_>> for DrainSessionCapsule {
+    type Error = CapsuleParseError;
+
+    fn try_from(capsule: Capsule<'_>) -> Result<Self, Self::Error> {
+        capsule.expect_kind(CapsuleKind::DrainWebTransportSession)?;
+
+        if !capsule.payload.is_empty() {
+            return Err(CapsuleParseError::PayloadNotEmpty);
+        }
+
+        Ok(Self)
+    }
+}
+
+impl From<DrainSessionCapsule> for Capsule<'_> {
+    fn from(_capsule: DrainSessionCapsule) -> Self {
+        Capsule::new(
+            CapsuleKind::DrainWebTransportSession.id(),
+            Cow::Borrowed(&[]),
+        )
+    }
+}
+
+mod capsule_kind_ids {
+    use crate::varint::VarInt;
+
+    pub const DATAGRAM: VarInt = VarInt::from_u32(0x00);
+    pub const CLOSE_WEBTRANSPORT_SESSION: VarInt = VarInt::from_u32(0x2843);
+    pub const DRAIN_WEBTRANSPORT_SESSION: VarInt = VarInt::from_u32(0x78ae);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYLOAD: &[u8] = b"This is a test payload";
+
+    #[test]
+    fn serde() {
+        let capsule = Capsule::new(VarInt::from_u32(0x2843), Cow::Borrowed(PAYLOAD));
+
+        let mut buffer = Vec::new();
+        capsule.write(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), capsule.write_size());
+
+        let mut buffer = buffer.as_slice();
+        let capsule = Capsule::read(&mut buffer).unwrap();
+        assert!(buffer.is_empty());
+        assert_eq!(capsule.capsule_type(), VarInt::from_u32(0x2843));
+        assert_eq!(capsule.payload(), PAYLOAD);
+    }
+
+    #[test]
+    fn read_partial() {
+        let mut buffer = Vec::new();
+        Capsule::new(VarInt::from_u32(0), Cow::Borrowed(PAYLOAD))
+            .write(&mut buffer)
+            .unwrap();
+
+        let mut buffer_reader = BufferReader::new(&buffer[..buffer.len() - 1]);
+        assert!(Capsule::read_from_buffer(&mut buffer_reader).is_none());
+        assert_eq!(buffer_reader.offset(), 0);
+
+        let mut buffer_reader = BufferReader::new(&buffer);
+        assert!(Capsule::read_from_buffer(&mut buffer_reader).is_some());
+        assert_eq!(buffer_reader.offset(), buffer.len());
+    }
+
+    #[test]
+    fn read_sequence() {
+        let mut buffer = Vec::new();
+        for capsule_type in 0..3 {
+            Capsule::new(VarInt::from_u32(capsule_type), Cow::Borrowed(PAYLOAD))
+                .write(&mut buffer)
+                .unwrap();
+        }
+
+        let mut buffer_reader = BufferReader::new(&buffer);
+        for capsule_type in 0..3 {
+            let capsule = Capsule::read_from_buffer(&mut buffer_reader).unwrap();
+            assert_eq!(capsule.capsule_type(), VarInt::from_u32(capsule_type));
+        }
+        assert!(Capsule::read_from_buffer(&mut buffer_reader).is_none());
+    }
+
+    fn roundtrip(capsule: Capsule) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        capsule.write(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), capsule.write_size());
+        buffer
+    }
+
+    #[test]
+    fn kinds() {
+        for kind in [
+            CapsuleKind::Datagram,
+            CapsuleKind::CloseWebTransportSession,
+            CapsuleKind::DrainWebTransportSession,
+        ] {
+            assert_eq!(CapsuleKind::parse(kind.id()), Some(kind));
+        }
+
+        assert_eq!(CapsuleKind::parse(VarInt::from_u32(0x2842)), None);
+        assert_eq!(
+            Capsule::new(VarInt::from_u32(0x78ae), Cow::Borrowed(&[])).kind(),
+            Some(CapsuleKind::DrainWebTransportSession)
+        );
+    }
+
+    #[test]
+    fn datagram() {
+        let buffer = roundtrip(DatagramCapsule::new(Cow::Borrowed(PAYLOAD)).into());
+        assert_eq!(buffer[0], 0x00);
+
+        let capsule = Capsule::read(&mut buffer.as_slice()).unwrap();
+        let datagram = DatagramCapsule::try_from(capsule).unwrap();
+        assert_eq!(datagram.payload(), PAYLOAD);
+
+        let capsule = Capsule::new(VarInt::from_u32(0x2843), Cow::Borrowed(PAYLOAD));
+        assert!(matches!(
+            DatagramCapsule::try_from(capsule),
+            Err(CapsuleParseError::UnexpectedKind)
+        ));
+    }
+
+    #[test]
+    fn close_session() {
+        let capsule = CloseSessionCapsule::new(0xdead_beef, Cow::Borrowed("bye"));
+        let buffer = roundtrip(capsule.into());
+        assert_eq!(
+            buffer,
+            [0x68, 0x43, 0x07, 0xde, 0xad, 0xbe, 0xef, b'b', b'y', b'e']
+        );
+
+        let capsule = Capsule::read(&mut buffer.as_slice()).unwrap();
+        let close = CloseSessionCapsule::try_from(capsule).unwrap();
+        assert_eq!(close.error_code(), 0xdead_beef);
+        assert_eq!(close.reason(), "bye");
+
+        let owned = Capsule::new(
+            CapsuleKind::CloseWebTransportSession.id(),
+            Cow::Owned(vec![0, 0, 0, 1, b'o', b'k']),
+        );
+        let close = CloseSessionCapsule::try_from(owned).unwrap();
+        assert_eq!(close.error_code(), 1);
+        assert_eq!(close.reason(), "ok");
+
+        let empty_reason = Capsule::new(
+            CapsuleKind::CloseWebTransportSession.id(),
+            Cow::Borrowed(&[0, 0, 0, 0]),
+        );
+        assert_eq!(
+            CloseSessionCapsule::try_from(empty_reason)
+                .unwrap()
+                .reason(),
+            ""
+        );
+    }
+
+    #[test]
+    fn close_session_malformed() {
+        let kind = CapsuleKind::CloseWebTransportSession.id();
+
+        assert!(matches!(
+            CloseSessionCapsule::try_from(Capsule::new(kind, Cow::Borrowed(&[0, 0, 0]))),
+            Err(CapsuleParseError::PayloadTooShort)
+        ));
+
+        assert!(matches!(
+            CloseSessionCapsule::try_from(Capsule::new(kind, Cow::Borrowed(&[0, 0, 0, 0, 0xff]))),
+            Err(CapsuleParseError::InvalidUtf8Reason)
+        ));
+
+        let mut payload = vec![0; 4];
+        payload.extend(core::iter::repeat(b'a').take(CloseSessionCapsule::MAX_REASON_LEN + 1));
+        assert!(matches!(
+            CloseSessionCapsule::try_from(Capsule::new(kind, Cow::Owned(payload))),
+            Err(CapsuleParseError::ReasonTooLong)
+        ));
+
+        assert!(matches!(
+            CloseSessionCapsule::try_from(Capsule::new(
+                CapsuleKind::DrainWebTransportSession.id(),
+                Cow::Borrowed(&[0, 0, 0, 0])
+            )),
+            Err(CapsuleParseError::UnexpectedKind)
+        ));
+    }
+
+    #[test]
+    #[should_panic]
+    fn close_session_reason_too_long() {
+        let reason = "a".repeat(CloseSessionCapsule::MAX_REASON_LEN + 1);
+        CloseSessionCapsule::new(0, Cow::Owned(reason));
+    }
+
+    #[test]
+    fn drain_session() {
+        let buffer = roundtrip(DrainSessionCapsule.into());
+        assert_eq!(buffer, [0x80, 0x00, 0x78, 0xae, 0x00]);
+
+        let capsule = Capsule::read(&mut buffer.as_slice()).unwrap();
+        assert!(DrainSessionCapsule::try_from(capsule).is_ok());
+
+        let kind = CapsuleKind::DrainWebTransportSession.id();
+        assert!(matches!(
+            DrainSessionCapsule::try_from(Capsule::new(kind, Cow::Borrowed(&[0]))),
+            Err(CapsuleParseError::PayloadNotEmpty)
+        ));
+        assert!(matches!(
+            DrainSessionCapsule::try_from(Capsule::new(VarInt::from_u32(0), Cow::Borrowed(&[]))),
+            Err(CapsuleParseError::UnexpectedKind)
+        ));
+    }
+}
@@ -5,12 +5,69 @@ use crate::bytes::BytesWriter;
 use crate::bytes::EndOfBuffer;
 use crate::error::ErrorCode;
 use crate::ids::InvalidQStreamId;
+use crate::ids::InvalidSessionId;
 use crate::ids::QStreamId;
+use crate::ids::SessionId;
+use crate::ids::StreamId;
+use crate::settings::SettingId;
+use crate::settings::Settings;
+use crate::varint::VarInt;
+
+/// Format of the header of HTTP3 datagrams, depending on the negotiated draft.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DatagramFormat {
+    /// The datagram starts with the *Quarter Stream ID* of the request stream
+    /// (RFC 9297 and `draft-ietf-masque-h3-datagram-04` onwards).
+    #[default]
+    QuarterStreamId,
+
+    /// The datagram starts with a *Flow ID*, equal to the ID of the request stream
+    /// (`draft-ietf-masque-h3-datagram-00` to `-02`, used by older browsers), negotiated
+    /// with [`SettingId::H3DatagramDraft00`].
+    FlowId,
+}
+
+impl DatagramFormat {
+    /// Negotiates the datagram format from the `local` and `remote` settings.
+    ///
+    /// The format of the most recent draft enabled on both sides is chosen.
+    /// Returns `None` if no common draft is enabled.
+    pub fn negotiate(local: &Settings, remote: &Settings) -> Option<Self> {
+        let enabled = |id| local.get(id).is_some() && remote.get(id) == Some(VarInt::from_u32(1));
+
+        if enabled(SettingId::H3Datagram) || enabled(SettingId::H3DatagramDraft04) {
+            Some(Self::QuarterStreamId)
+        } else if enabled(SettingId::H3DatagramDraft00) {
+            Some(Self::FlowId)
+        } else {
+            None
+        }
+    }
+
+    fn encode(self, qstream_id: QStreamId) -> VarInt {
+        match self {
+            Self::QuarterStreamId => qstream_id.into_varint(),
+            Self::FlowId => qstream_id.into_session_id().into_varint(),
+        }
+    }
+
+    fn decode(self, varint: VarInt) -> Result<QStreamId, ErrorCode> {
+        match self {
+            Self::QuarterStreamId => {
+                QStreamId::try_from_varint(varint).map_err(|InvalidQStreamId| ErrorCode::Datagram)
+            }
+            Self::FlowId => SessionId::try_from_session_stream(StreamId::new(varint))
+                .map(QStreamId::from_session_id)
+                .map_err(|InvalidSessionId| ErrorCode::Datagram),
+        }
+    }
+}
 
 /// An HTTP3 datagram.
 pub struct Datagram<'a> {
     qstream_id: QStreamId,
     payload: &'a [u8],
+    format: DatagramFormat,
 }
 
 impl<'a> Datagram<'a> {
@@ -20,23 +77,39 @@ impl<'a> Datagram<'a> {
         Self {
             qstream_id,
             payload,
+            format: DatagramFormat::default(),
         }
     }
 
+    /// Sets the header format used when writing this datagram.
+    #[inline(always)]
+    pub fn with_format(mut self, format: DatagramFormat) -> Self {
+        self.format = format;
+        self
+    }
+
     /// Reads [`Datagram`] from a QUIC datagram.
+    #[inline(always)]
     pub fn read(quic_datagram: &'a [u8]) -> Result<Self, ErrorCode> {
+        Self::read_with_format(quic_datagram, DatagramFormat::default())
+    }
+
+    /// Reads [`Datagram`] from a QUIC datagram whose header has the given `format`.
+    pub fn read_with_format(
+        quic_datagram: &'a [u8],
+        format: DatagramFormat,
+    ) -> Result<Self, ErrorCode> {
         let mut buffer_reader = BufferReader::new(quic_datagram);
 
         let varint = buffer_reader.get_varint().ok_or(ErrorCode::Datagram)?;
-
-        let qstream_id =
-            QStreamId::try_from_varint(varint).map_err(|InvalidQStreamId| ErrorCode::Datagram)?;
+        let qstream_id = format.decode(varint)?;
 
         let payload = buffer_reader.buffer_remaining();
 
         Ok(Self {
             qstream_id,
             payload,
+            format,
         })
     }
 
@@ -55,7 +128,7 @@ impl<'a> Datagram<'a> {
         let mut buffer_writer = BufferWriter::new(buffer);
 
         buffer_writer
-            .put_varint(self.qstream_id.into_varint())
+            .put_varint(self.format.encode(self.qstream_id))
             .expect("Buffer has capacity");
 
         buffer_writer
@@ -68,7 +141,7 @@ impl<'a> Datagram<'a> {
     /// Returns the needed capacity to write this datagram into a buffer.
     #[inline(always)]
     pub fn write_size(&self) -> usize {
-        Self::header_size(self.qstream_id) + self.payload.len()
+        Self::header_size_with_format(self.qstream_id, self.format) + self.payload.len()
     }
 
     /// Returns the HTTP3 header.
@@ -77,7 +150,19 @@ impl<'a> Datagram<'a> {
     /// encoding into an HTTP3 datagram.
     #[inline(always)]
     pub fn header_size(qstream_id: QStreamId) -> usize {
-        qstream_id.into_varint().size()
+        Self::header_size_with_format(qstream_id, DatagramFormat::default())
+    }
+
+    /// Returns the HTTP3 header size, for a header of the given `format`.
+    #[inline(always)]
+    pub fn header_size_with_format(qstream_id: QStreamId, format: DatagramFormat) -> usize {
+        format.encode(qstream_id).size()
+    }
+
+    /// Returns the header format of this datagram.
+    #[inline(always)]
+    pub fn format(&self) -> DatagramFormat {
+        self.format
     }
 
     /// Returns the associated [`QStreamId`].
@@ -96,7 +181,9 @@ impl<'a> Datagram<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::frame::Frame;
     use crate::varint::VarInt;
+    use alloc::borrow::Cow;
     use utils::build_datagram;
     use utils::QStreamIdType;
     use utils::PAYLOAD;
@@ -140,6 +227,99 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn read_flow_id() {
+        let qstream_id = QStreamId::from_session_id(
+            SessionId::try_from_session_stream(StreamId::new(VarInt::from_u32(8))).unwrap(),
+        );
+        let dgram = Datagram::new(qstream_id, PAYLOAD).with_format(DatagramFormat::FlowId);
+
+        let mut buffer = vec![0; dgram.write_size()];
+        let written = dgram.write(&mut buffer).unwrap();
+        assert_eq!(written, 1 + PAYLOAD.len());
+        assert_eq!(buffer[0], 8);
+
+        let dgram = Datagram::read_with_format(&buffer, DatagramFormat::FlowId).unwrap();
+        assert_eq!(dgram.qstream_id(), qstream_id);
+        assert_eq!(dgram.payload(), PAYLOAD);
+
+        let dgram = Datagram::read(&buffer).unwrap();
+        assert_eq!(dgram.qstream_id().into_u64(), 8);
+    }
+
+    #[test]
+    fn read_invalid_flow_id() {
+        // Stream 2 is unidirectional
+        let buffer = [0x02, 0x00];
+
+        assert!(matches!(
+            Datagram::read_with_format(&buffer, DatagramFormat::FlowId),
+            Err(ErrorCode::Datagram)
+        ));
+    }
+
+    #[test]
+    fn negotiate_format() {
+        let current = Settings::builder().enable_h3_datagrams().build();
+        let drafts = Settings::builder().enable_h3_datagrams_drafts().build();
+        let all = Settings::builder()
+            .enable_h3_datagrams()
+            .enable_h3_datagrams_drafts()
+            .build();
+        let none = Settings::builder().build();
+
+        assert_eq!(
+            DatagramFormat::negotiate(&all, &current),
+            Some(DatagramFormat::QuarterStreamId)
+        );
+        assert_eq!(
+            DatagramFormat::negotiate(&all, &drafts),
+            Some(DatagramFormat::QuarterStreamId)
+        );
+        assert_eq!(DatagramFormat::negotiate(&current, &drafts), None);
+        assert_eq!(DatagramFormat::negotiate(&all, &none), None);
+
+        // H3_DATAGRAM (0x276) only
+        let draft00 =
+            Settings::with_frame(&Frame::new_settings(Cow::Borrowed(&[0x42, 0x76, 0x01]))).unwrap();
+        assert_eq!(
+            DatagramFormat::negotiate(&all, &draft00),
+            Some(DatagramFormat::FlowId)
+        );
+        assert_eq!(DatagramFormat::negotiate(&current, &draft00), None);
+    }
+
+    #[test]
+    fn format_per_setting() {
+        let cases: [(&[u8], _, _); 3] = [
+            (
+                &[0x33, 0x01],
+                SettingId::H3Datagram,
+                DatagramFormat::QuarterStreamId,
+            ),
+            (
+                &[0x80, 0xff, 0xd2, 0x77, 0x01],
+                SettingId::H3DatagramDraft04,
+                DatagramFormat::QuarterStreamId,
+            ),
+            (
+                &[0x42, 0x76, 0x01],
+                SettingId::H3DatagramDraft00,
+                DatagramFormat::FlowId,
+            ),
+        ];
+
+        for (payload, id, format) in cases {
+            let settings =
+                Settings::with_frame(&Frame::new_settings(Cow::Borrowed(payload))).unwrap();
+            assert_eq!(settings.get(id), Some(VarInt::from_u32(1)));
+            assert_eq!(
+                DatagramFormat::negotiate(&settings, &settings),
+                Some(format)
+            );
+        }
+    }
+
     #[test]
     fn write_ok() {
         let dgram = build_datagram(QStreamIdType::Valid, PAYLOAD);
@@ -183,7 +363,7 @@ mod tests {
         }
 
         /// This function is for **testing purpose only**; it might produce an invalid `Datagram`!
-        pub fn build_datagram(qstream_id_type: QStreamIdType, payload: &[u8]) -> Datagram {
+        pub fn build_datagram(qstream_id_type: QStreamIdType, payload: &[u8]) -> Datagram<'_> {
             Datagram::new(qstream_id_type.into_session_id(), payload)
         }
     }
@@ -1,10 +1,11 @@
 use crate::varint::VarInt;
-use std::fmt::Debug;
-use std::fmt::Display;
-use std::fmt::Formatter;
+use core::fmt::Debug;
+use core::fmt::Display;
+use core::fmt::Formatter;
 
 /// HTTP3 protocol errors.
 #[derive(Clone, Copy)]
+#[non_exhaustive]
 pub enum ErrorCode {
     /// H3_DATAGRAM_ERROR.
     Datagram,
@@ -24,6 +25,9 @@ pub enum ErrorCode {
     /// H3_FRAME_ERROR.
     Frame,
 
+    /// H3_EXCESSIVE_LOAD.
+    ExcessiveLoad,
+
     /// H3_ID_ERROR.
     Id,
 
@@ -36,6 +40,9 @@ pub enum ErrorCode {
     /// H3_REQUEST_REJECTED.
     RequestRejected,
 
+    /// H3_REQUEST_CANCELLED.
+    RequestCancelled,
+
     /// H3_MESSAGE_ERROR.
     Message,
 
@@ -59,10 +66,12 @@ impl ErrorCode {
             ErrorCode::ClosedCriticalStream => h3_error_codes::H3_CLOSED_CRITICAL_STREAM,
             ErrorCode::FrameUnexpected => h3_error_codes::H3_FRAME_UNEXPECTED,
             ErrorCode::Frame => h3_error_codes::H3_FRAME_ERROR,
+            ErrorCode::ExcessiveLoad => h3_error_codes::H3_EXCESSIVE_LOAD,
             ErrorCode::Id => h3_error_codes::H3_ID_ERROR,
             ErrorCode::Settings => h3_error_codes::H3_SETTINGS_ERROR,
             ErrorCode::MissingSettings => h3_error_codes::H3_MISSING_SETTINGS,
             ErrorCode::RequestRejected => h3_error_codes::H3_REQUEST_REJECTED,
+            ErrorCode::RequestCancelled => h3_error_codes::H3_REQUEST_CANCELLED,
             ErrorCode::Message => h3_error_codes::H3_MESSAGE_ERROR,
             ErrorCode::Decompression => qpack_error_codes::QPACK_DECOMPRESSION_FAILED,
             ErrorCode::BufferedStreamRejected => {
@@ -74,13 +83,13 @@ impl ErrorCode {
 }
 
 impl Debug for ErrorCode {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "{} (code: {})", self, self.to_code())
     }
 }
 
 impl Display for ErrorCode {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
             ErrorCode::Datagram => write!(f, "DatagramError"),
             ErrorCode::NoError => write!(f, "NoError"),
@@ -88,10 +97,12 @@ impl Display for ErrorCode {
             ErrorCode::ClosedCriticalStream => write!(f, "ClosedCriticalStreamError"),
             ErrorCode::FrameUnexpected => write!(f, "FrameUnexpectedError"),
             ErrorCode::Frame => write!(f, "FrameError"),
+            ErrorCode::ExcessiveLoad => write!(f, "ExcessiveLoadError"),
             ErrorCode::Id => write!(f, "IdError"),
             ErrorCode::Settings => write!(f, "SettingsError"),
             ErrorCode::MissingSettings => write!(f, "MissingSettingsError"),
             ErrorCode::RequestRejected => write!(f, "RequestRejectedError"),
+            ErrorCode::RequestCancelled => write!(f, "RequestCancelledError"),
             ErrorCode::Message => write!(f, "MessageError"),
             ErrorCode::Decompression => write!(f, "DecompressionError"),
             ErrorCode::BufferedStreamRejected => write!(f, "BufferedStreamRejected"),
@@ -100,6 +111,7 @@ impl Display for ErrorCode {
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for ErrorCode {}
 
 mod h3_error_codes {
@@ -111,10 +123,12 @@ mod h3_error_codes {
     pub const H3_CLOSED_CRITICAL_STREAM: VarInt = VarInt::from_u32(0x0104);
     pub const H3_FRAME_UNEXPECTED: VarInt = VarInt::from_u32(0x0105);
     pub const H3_FRAME_ERROR: VarInt = VarInt::from_u32(0x0106);
+    pub const H3_EXCESSIVE_LOAD: VarInt = VarInt::from_u32(0x0107);
     pub const H3_ID_ERROR: VarInt = VarInt::from_u32(0x0108);
     pub const H3_SETTINGS_ERROR: VarInt = VarInt::from_u32(0x0109);
     pub const H3_MISSING_SETTINGS: VarInt = VarInt::from_u32(0x010a);
     pub const H3_REQUEST_REJECTED: VarInt = VarInt::from_u32(0x010b);
+    pub const H3_REQUEST_CANCELLED: VarInt = VarInt::from_u32(0x010c);
     pub const H3_MESSAGE_ERROR: VarInt = VarInt::from_u32(0x010e);
 }
 
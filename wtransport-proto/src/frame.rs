@@ -6,7 +6,7 @@ use crate::bytes::EndOfBuffer;
 use crate::ids::InvalidSessionId;
 use crate::ids::SessionId;
 use crate::varint::VarInt;
-use std::borrow::Cow;
+use alloc::borrow::Cow;
 
 #[cfg(feature = "async")]
 use crate::bytes::AsyncRead;
@@ -25,6 +25,9 @@ pub enum ParseError {
 
     /// Error for invalid session ID.
     InvalidSessionId,
+
+    /// Error for a payload larger than the allowed size (see [`Frame::MAX_PAYLOAD_SIZE`]).
+    PayloadTooLarge,
 }
 
 /// An error during frame I/O read operation.
@@ -53,6 +56,7 @@ pub type IoWriteError = bytes::IoWriteError;
 
 /// An HTTP3 [`Frame`] type.
 #[derive(Copy, Clone, Debug)]
+#[non_exhaustive]
 pub enum FrameKind {
     /// DATA frame type.
     Data,
@@ -68,6 +72,11 @@ pub enum FrameKind {
 
     /// Exercise frame.
     Exercise(VarInt),
+
+    /// Frame of a type unknown to this implementation.
+    ///
+    /// Only returned by [`Frame::read_any`] and [`Frame::read_any_async`].
+    Unknown(VarInt),
 }
 
 impl FrameKind {
@@ -88,13 +97,23 @@ impl FrameKind {
         }
     }
 
-    const fn id(self) -> VarInt {
+    const fn parse_any(id: VarInt) -> Self {
+        match FrameKind::parse(id) {
+            Some(kind) => kind,
+            None => FrameKind::Unknown(id),
+        }
+    }
+
+    /// Returns the frame type identifier, as encoded on the wire.
+    #[inline(always)]
+    pub const fn id(self) -> VarInt {
         match self {
             FrameKind::Data => frame_kind_ids::DATA,
             FrameKind::Headers => frame_kind_ids::HEADERS,
             FrameKind::Settings => frame_kind_ids::SETTINGS,
             FrameKind::WebTransport => frame_kind_ids::WEBTRANSPORT_STREAM,
             FrameKind::Exercise(id) => id,
+            FrameKind::Unknown(id) => id,
         }
     }
 }
@@ -107,6 +126,16 @@ pub struct Frame<'a> {
 }
 
 impl<'a> Frame<'a> {
+    /// Creates a new frame of type [`FrameKind::Data`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `payload` size if greater than [`VarInt::MAX`].
+    #[inline(always)]
+    pub fn new_data(payload: Cow<'a, [u8]>) -> Self {
+        Self::new(FrameKind::Data, payload, None)
+    }
+
     /// Creates a new frame of type [`FrameKind::Headers`].
     ///
     /// # Panics
@@ -149,21 +178,43 @@ impl<'a> Frame<'a> {
         Self::new(FrameKind::Exercise(id), payload, None)
     }
 
+    /// Maximum payload size of a frame read by [`Self::read`] and [`Self::read_async`].
+    pub const MAX_PAYLOAD_SIZE: usize = 16 * 1024 * 1024;
+
+    /// Size of the chunks of payload read at once by [`Self::read_async`].
+    ///
+    /// The payload buffer grows as data arrives, rather than being allocated upfront
+    /// for the declared length.
+    #[cfg(feature = "async")]
+    const READ_CHUNK_SIZE: usize = 64 * 1024;
+
     /// Reads a [`Frame`] from a [`BytesReader`].
     ///
     /// It returns [`None`] if the `bytes_reader` does not contain enough bytes
     /// to parse an entire frame.
     ///
+    /// A frame of unknown type is skipped (its payload is consumed), and reported
+    /// as [`ParseError::UnknownFrame`].
+    ///
     /// In case [`None`] or [`Err`], `bytes_reader` might be partially read.
     pub fn read<R>(bytes_reader: &mut R) -> Option<Result<Self, ParseError>>
     where
         R: BytesReader<'a>,
     {
-        let kind_id = bytes_reader.get_varint()?;
-        let kind = match FrameKind::parse(kind_id) {
-            Some(kind) => kind,
-            None => return Some(Err(ParseError::UnknownFrame)),
-        };
+        match Self::read_any(bytes_reader)? {
+            Ok(frame) if matches!(frame.kind, FrameKind::Unknown(_)) => {
+                Some(Err(ParseError::UnknownFrame))
+            }
+            result => Some(result),
+        }
+    }
+
+    /// Like [`Self::read`], but a frame of unknown type is returned as [`FrameKind::Unknown`].
+    pub fn read_any<R>(bytes_reader: &mut R) -> Option<Result<Self, ParseError>>
+    where
+        R: BytesReader<'a>,
+    {
+        let kind = FrameKind::parse_any(bytes_reader.get_varint()?);
 
         if matches!(kind, FrameKind::WebTransport) {
             let session_id = match SessionId::try_from_varint(bytes_reader.get_varint()?) {
@@ -173,24 +224,75 @@ impl<'a> Frame<'a> {
 
             Some(Ok(Self::new_webtransport(session_id)))
         } else {
-            let payload_len = bytes_reader.get_varint()?.into_inner() as usize;
-            let payload = bytes_reader.get_bytes(payload_len)?;
+            let payload_len = bytes_reader.get_varint()?.into_inner();
+            if payload_len > Self::MAX_PAYLOAD_SIZE as u64 {
+                return Some(Err(ParseError::PayloadTooLarge));
+            }
+
+            let payload = bytes_reader.get_bytes(payload_len as usize)?;
 
             Some(Ok(Self::new(kind, Cow::Borrowed(payload), None)))
         }
     }
 
     /// Reads a [`Frame`] from a `reader`.
+    ///
+    /// Frames with a payload larger than [`Self::MAX_PAYLOAD_SIZE`] are rejected.
     #[cfg(feature = "async")]
     #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
     pub async fn read_async<R>(reader: &mut R) -> Result<Frame<'a>, IoReadError>
+    where
+        R: AsyncRead + Unpin + ?Sized,
+    {
+        Self::read_async_with_limit(reader, Self::MAX_PAYLOAD_SIZE).await
+    }
+
+    /// Reads a [`Frame`] from a `reader`, rejecting payloads larger than `max_payload_size`.
+    ///
+    /// A frame of unknown type is skipped (its payload is consumed), and reported
+    /// as [`ParseError::UnknownFrame`].
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn read_async_with_limit<R>(
+        reader: &mut R,
+        max_payload_size: usize,
+    ) -> Result<Frame<'a>, IoReadError>
+    where
+        R: AsyncRead + Unpin + ?Sized,
+    {
+        match Self::read_any_async_with_limit(reader, max_payload_size).await {
+            Ok(frame) if matches!(frame.kind, FrameKind::Unknown(_)) => {
+                Err(IoReadError::Parse(ParseError::UnknownFrame))
+            }
+            result => result,
+        }
+    }
+
+    /// Like [`Self::read_async`], but a frame of unknown type is returned as
+    /// [`FrameKind::Unknown`].
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn read_any_async<R>(reader: &mut R) -> Result<Frame<'a>, IoReadError>
+    where
+        R: AsyncRead + Unpin + ?Sized,
+    {
+        Self::read_any_async_with_limit(reader, Self::MAX_PAYLOAD_SIZE).await
+    }
+
+    /// Like [`Self::read_async_with_limit`], but a frame of unknown type is returned as
+    /// [`FrameKind::Unknown`].
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn read_any_async_with_limit<R>(
+        reader: &mut R,
+        max_payload_size: usize,
+    ) -> Result<Frame<'a>, IoReadError>
     where
         R: AsyncRead + Unpin + ?Sized,
     {
         use crate::bytes::BytesReaderAsync;
 
-        let kind_id = reader.get_varint(false).await?;
-        let kind = FrameKind::parse(kind_id).ok_or(IoReadError::Parse(ParseError::UnknownFrame))?;
+        let kind = FrameKind::parse_any(reader.get_varint(false).await?);
 
         if matches!(kind, FrameKind::WebTransport) {
             let session_id = SessionId::try_from_varint(reader.get_varint(true).await?)
@@ -198,10 +300,25 @@ impl<'a> Frame<'a> {
 
             Ok(Self::new_webtransport(session_id))
         } else {
-            let payload_len = reader.get_varint(true).await?.into_inner() as usize;
-            let mut payload = vec![0; payload_len];
+            let payload_len = reader.get_varint(true).await?.into_inner();
+            if payload_len > max_payload_size as u64 {
+                return Err(IoReadError::Parse(ParseError::PayloadTooLarge));
+            }
+
+            let payload_len = payload_len as usize;
+            let mut payload = Vec::new();
 
-            reader.get_buffer(&mut payload, true).await?;
+            loop {
+                let start = payload.len();
+                let end = start + (payload_len - start).min(Self::READ_CHUNK_SIZE);
+
+                payload.resize(end, 0);
+                reader.get_buffer(&mut payload[start..], true).await?;
+
+                if end == payload_len {
+                    break;
+                }
+            }
 
             payload.shrink_to_fit();
 
@@ -348,6 +465,8 @@ impl<'a> Frame<'a> {
     fn new(kind: FrameKind, payload: Cow<'a, [u8]>, session_id: Option<SessionId>) -> Self {
         if let FrameKind::Exercise(id) = kind {
             debug_assert!(FrameKind::is_id_exercise(id))
+        } else if let FrameKind::Unknown(id) = kind {
+            debug_assert!(FrameKind::parse(id).is_none())
         } else if let FrameKind::WebTransport = kind {
             debug_assert!(payload.is_empty());
             debug_assert!(session_id.is_some())
@@ -560,6 +679,93 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn unknown_frame_skipped() {
+        let mut buffer =
+            Frame::serialize_any(VarInt::from_u32(0x424242), b"This is a test payload");
+        Frame::new_data(Cow::Borrowed(b"data"))
+            .write(&mut buffer)
+            .unwrap();
+        let mut buffer = buffer.as_slice();
+
+        assert!(matches!(
+            Frame::read(&mut buffer).unwrap(),
+            Err(ParseError::UnknownFrame)
+        ));
+
+        let frame = Frame::read(&mut buffer).unwrap().unwrap();
+        assert!(matches!(frame.kind(), FrameKind::Data));
+        assert_eq!(frame.payload(), b"data");
+    }
+
+    #[test]
+    fn read_any_unknown_frame() {
+        let buffer = Frame::serialize_any(VarInt::from_u32(0x424242), b"This is a test payload");
+
+        let frame = Frame::read_any(&mut buffer.as_slice()).unwrap().unwrap();
+        assert!(matches!(frame.kind(), FrameKind::Unknown(id) if id == VarInt::from_u32(0x424242)));
+        assert_eq!(frame.payload(), b"This is a test payload");
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn unknown_frame_skipped_async() {
+        let mut buffer =
+            Frame::serialize_any(VarInt::from_u32(0x424242), b"This is a test payload");
+        Frame::new_data(Cow::Borrowed(b"data"))
+            .write(&mut buffer)
+            .unwrap();
+        let mut buffer = buffer.as_slice();
+
+        assert!(matches!(
+            Frame::read_async(&mut buffer).await,
+            Err(IoReadError::Parse(ParseError::UnknownFrame))
+        ));
+
+        let frame = Frame::read_async(&mut buffer).await.unwrap();
+        assert!(matches!(frame.kind(), FrameKind::Data));
+        assert_eq!(frame.payload(), b"data");
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_any_unknown_frame_async() {
+        let buffer = Frame::serialize_any(VarInt::from_u32(0x424242), b"This is a test payload");
+
+        let frame = Frame::read_any_async(&mut buffer.as_slice()).await.unwrap();
+        assert!(matches!(frame.kind(), FrameKind::Unknown(id) if id == VarInt::from_u32(0x424242)));
+        assert_eq!(frame.payload(), b"This is a test payload");
+    }
+
+    #[test]
+    fn payload_too_large() {
+        let mut buffer = Vec::new();
+        buffer.put_varint(FrameKind::Data.id()).unwrap();
+        buffer
+            .put_varint(VarInt::try_from(Frame::MAX_PAYLOAD_SIZE as u64 + 1).unwrap())
+            .unwrap();
+
+        assert!(matches!(
+            Frame::read(&mut buffer.as_slice()).unwrap(),
+            Err(ParseError::PayloadTooLarge)
+        ));
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn payload_too_large_async() {
+        let buffer = Frame::serialize_any(FrameKind::Data.id(), &[0; 1024]);
+
+        assert!(matches!(
+            Frame::read_async_with_limit(&mut buffer.as_slice(), 1023).await,
+            Err(IoReadError::Parse(ParseError::PayloadTooLarge))
+        ));
+
+        let buffer = Frame::serialize_any(FrameKind::Data.id(), &[1; 200 * 1024]);
+        let frame = Frame::read_async(&mut buffer.as_slice()).await.unwrap();
+        assert_eq!(frame.payload(), &[1; 200 * 1024][..]);
+    }
+
     #[test]
     fn invalid_session_id() {
         let invalid_session_id = SessionId::maybe_invalid(VarInt::from_u32(1));
@@ -600,7 +806,7 @@ mod tests {
         }
 
         #[cfg(feature = "async")]
-        pub async fn assert_serde_async(frame: Frame<'_>) -> Frame {
+        pub async fn assert_serde_async(frame: Frame<'_>) -> Frame<'_> {
             let mut buffer = Vec::new();
 
             frame.write_async(&mut buffer).await.unwrap();
@@ -0,0 +1,126 @@
+use crate::bytes::BufferReader;
+use crate::capsule::Capsule;
+use crate::datagram::Datagram;
+use crate::frame::Frame;
+use crate::frame::FrameKind;
+use crate::headers::Headers;
+use crate::ids::StreamId;
+use crate::session::SessionRequest;
+use crate::session::SessionResponse;
+use crate::settings::Settings;
+use crate::stream_header::StreamHeader;
+use crate::varint::VarInt;
+
+/// Parses a sequence of frames, as received on a stream.
+///
+/// `HEADERS` payloads are decoded (up to a session request or response) and
+/// `SETTINGS` payloads are parsed.
+pub fn frames(data: &[u8]) {
+    let mut buffer_reader = BufferReader::new(data);
+
+    while let Some(Ok(frame)) = Frame::read_from_buffer(&mut buffer_reader) {
+        match frame.kind() {
+            FrameKind::Headers => headers_frame(&frame),
+            FrameKind::Settings => {
+                let _ = Settings::with_frame(&frame);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Decodes a QPACK field section (i.e., the payload of a `HEADERS` frame).
+pub fn headers(data: &[u8]) {
+    headers_frame(&Frame::new_headers(data.into()));
+}
+
+/// Parses a sequence of capsules, as received on the session stream.
+pub fn capsules(data: &[u8]) {
+    let mut buffer_reader = BufferReader::new(data);
+
+    while Capsule::read_from_buffer(&mut buffer_reader).is_some() {}
+}
+
+/// Parses a stream header, followed by the frames of the stream.
+pub fn stream(data: &[u8]) {
+    let mut buffer_reader = BufferReader::new(data);
+
+    if let Some(Ok(_stream_header)) = StreamHeader::read_from_buffer(&mut buffer_reader) {
+        frames(buffer_reader.buffer_remaining());
+    }
+}
+
+/// Parses an HTTP3 datagram.
+pub fn datagram(data: &[u8]) {
+    let _ = Datagram::read(data);
+}
+
+/// Dispatches `data` to one of the parsers above, according to its first byte.
+///
+/// This is a convenient single entry point for a fuzzer.
+pub fn any(data: &[u8]) {
+    let (selector, data) = match data.split_first() {
+        Some((selector, data)) => (selector, data),
+        None => return,
+    };
+
+    match selector % 5 {
+        0 => frames(data),
+        1 => headers(data),
+        2 => capsules(data),
+        3 => stream(data),
+        _ => datagram(data),
+    }
+}
+
+fn headers_frame(frame: &Frame) {
+    let headers = match Headers::with_frame(frame, StreamId::new(VarInt::from_u32(0))) {
+        Ok(headers) => headers,
+        Err(_) => return,
+    };
+
+    if let Ok(request) = SessionRequest::try_from(headers.clone()) {
+        let _ = (request.authority(), request.path(), request.origin());
+    }
+
+    if let Ok(response) = SessionResponse::try_from(headers) {
+        let _ = response.code();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds pseudo-random inputs (and mutations of valid ones) through all parsers.
+    #[test]
+    fn random_inputs() {
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        let mut request = Vec::new();
+        SessionRequest::new("https://example.com/path")
+            .unwrap()
+            .headers()
+            .generate_frame(StreamId::new(VarInt::from_u32(0)))
+            .write(&mut request)
+            .unwrap();
+
+        for _ in 0..5_000 {
+            let len = (next() % 64) as usize;
+            let mut data = (0..len).map(|_| next() as u8).collect::<Vec<_>>();
+            any(&data);
+
+            data.clone_from(&request);
+            let index = (next() as usize) % data.len();
+            data[index] = next() as u8;
+            frames(&data);
+            headers(&data[2..]);
+        }
+    }
+}
@@ -1,74 +1,211 @@
 use crate::error::ErrorCode;
 use crate::frame::Frame;
 use crate::frame::FrameKind;
+use crate::huffman;
 use crate::ids::StreamId;
+use crate::qpack;
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
 use ls_qpack::decoder::Decoder;
+
+#[cfg(feature = "std")]
 use ls_qpack::decoder::DecoderOutput;
+
+#[cfg(feature = "std")]
 use ls_qpack::encoder::Encoder;
+
+#[cfg(feature = "std")]
 use ls_qpack::errors::DecoderError;
-use std::borrow::Cow;
-use std::collections::HashMap;
 
 /// HTTP3 headers from the request or response.
-#[derive(Debug)]
-pub struct Headers(HashMap<String, String>);
+///
+/// Field names are case-insensitive (they are stored lowercase, as required by HTTP3)
+/// and a field can have multiple values, like in
+/// [`http::HeaderMap`](https://docs.rs/http/latest/http/header/struct.HeaderMap.html).
+#[derive(Clone, Debug, Default)]
+pub struct Headers(Vec<(String, String)>);
 
 impl Headers {
+    /// Maximum size of a single field (name and value), once decoded.
+    pub const MAX_FIELD_SIZE: usize = 16 * 1024;
+
     /// Constructs the headers from a HTTP3 [`Frame`].
     ///
+    /// Field sections referring to the QPACK dynamic table (which is disabled), or with
+    /// a field larger than [`Self::MAX_FIELD_SIZE`], are rejected.
+    ///
     /// # Panics
     ///
     /// Panics if `frame` is not type [`FrameKind::Headers`].
     pub fn with_frame(frame: &Frame, stream_id: StreamId) -> Result<Self, ErrorCode> {
+        Self::with_frame_limited(frame, stream_id, &HeadersLimits::default())
+    }
+
+    /// Like [`Self::with_frame`], but with the field section checked against `limits`.
+    ///
+    /// A field section larger than the section size or the field count of `limits` is
+    /// rejected with [`ErrorCode::ExcessiveLoad`], before being decoded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frame` is not type [`FrameKind::Headers`].
+    pub fn with_frame_limited(
+        frame: &Frame,
+        stream_id: StreamId,
+        limits: &HeadersLimits,
+    ) -> Result<Self, ErrorCode> {
         assert!(matches!(frame.kind(), FrameKind::Headers));
 
+        validate_field_section(frame.payload(), limits)?;
+
+        Self::decode(frame.payload(), stream_id)
+    }
+
+    /// Generates a [`Frame`] with these headers.
+    pub fn generate_frame(&self, stream_id: StreamId) -> Frame<'static> {
+        Frame::new_headers(Cow::Owned(self.encode(stream_id)))
+    }
+
+    #[cfg(feature = "std")]
+    fn decode(payload: &[u8], stream_id: StreamId) -> Result<Self, ErrorCode> {
         let mut decoder = Decoder::new(0, 0);
 
         match decoder
-            .decode(stream_id.into(), frame.payload())
+            .decode(stream_id.into(), payload)
             .map_err(|DecoderError| ErrorCode::Decompression)?
         {
             DecoderOutput::Done(headers) => Ok(headers
                 .into_iter()
                 .map(|h| (h.name().to_string(), h.value().to_string()))
                 .collect()),
-            DecoderOutput::BlockedStream => todo!(),
+            // The dynamic table is disabled: a field section cannot refer to it.
+            DecoderOutput::BlockedStream => Err(ErrorCode::Decompression),
         }
     }
 
-    /// Generates a [`Frame`] with these headers.
-    pub fn generate_frame(&self, stream_id: StreamId) -> Frame<'static> {
+    #[cfg(feature = "std")]
+    fn encode(&self, stream_id: StreamId) -> Vec<u8> {
         let mut encoder = Encoder::new();
 
         let (enc_headers, enc_stream) = encoder
-            .encode_all(stream_id.into(), self.0.iter())
+            .encode_all(stream_id.into(), self.iter())
             .expect("Static encoding is not expected to fail")
             .take();
 
         debug_assert_eq!(enc_stream.len(), 0);
 
-        Frame::new_headers(Cow::Owned(enc_headers.to_vec()))
+        enc_headers.to_vec()
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn encode(&self, _stream_id: StreamId) -> Vec<u8> {
+        crate::qpack::encode(self.iter())
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn decode(payload: &[u8], _stream_id: StreamId) -> Result<Self, ErrorCode> {
+        crate::qpack::decode(payload)
+            .map(|fields| fields.into_iter().collect())
+            .ok_or(ErrorCode::Decompression)
     }
 
-    /// Returns a reference to the value associated with the key.
+    /// Returns a reference to the first value associated with the key.
     #[inline(always)]
     pub fn get<K>(&self, key: K) -> Option<&str>
     where
         K: AsRef<str>,
     {
-        self.0.get(key.as_ref()).map(|s| s.as_str())
+        self.get_all(key).next()
+    }
+
+    /// Returns all values associated with the key, in insertion order.
+    pub fn get_all<K>(&self, key: K) -> impl Iterator<Item = &str>
+    where
+        K: AsRef<str>,
+    {
+        self.0
+            .iter()
+            .filter_map(move |(k, v)| k.eq_ignore_ascii_case(key.as_ref()).then_some(v.as_str()))
+    }
+
+    /// Returns whether the headers contain the key.
+    #[inline(always)]
+    pub fn contains_key<K>(&self, key: K) -> bool
+    where
+        K: AsRef<str>,
+    {
+        self.get(key).is_some()
     }
 
     /// Inserts a field (key, value) in the headers.
     ///
-    /// If the headers did have this key present, the value is updated.
-    #[inline(always)]
+    /// If the headers did have this key present, all its previous values are replaced.
     pub fn insert<K, V>(&mut self, key: K, value: V)
     where
         K: ToString,
         V: ToString,
     {
-        self.0.insert(key.to_string(), value.to_string());
+        let key = key.to_string().to_ascii_lowercase();
+        self.remove(&key);
+        self.0.push((key, value.to_string()));
+    }
+
+    /// Appends a field (key, value) in the headers.
+    ///
+    /// Previous values for the same key are kept.
+    #[inline(always)]
+    pub fn append<K, V>(&mut self, key: K, value: V)
+    where
+        K: ToString,
+        V: ToString,
+    {
+        self.0
+            .push((key.to_string().to_ascii_lowercase(), value.to_string()));
+    }
+
+    /// Removes all values associated with the key.
+    ///
+    /// It returns the first removed value, if any.
+    pub fn remove<K>(&mut self, key: K) -> Option<String>
+    where
+        K: AsRef<str>,
+    {
+        let mut removed = None;
+
+        self.0.retain_mut(|(k, v)| {
+            if k.eq_ignore_ascii_case(key.as_ref()) {
+                removed.get_or_insert_with(|| core::mem::take(v));
+                false
+            } else {
+                true
+            }
+        });
+
+        removed
+    }
+
+    /// Returns an iterator over all fields (key, value), in insertion order.
+    ///
+    /// Keys with multiple values are yielded once per value.
+    #[inline(always)]
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Returns the number of fields (i.e., values) in the headers.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns whether the headers contain no fields.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
     }
 }
 
@@ -77,24 +214,374 @@ where
     K: ToString,
     V: ToString,
 {
+    /// Collects fields with [`Headers::append`] semantics.
     fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
-        Self(
-            iter.into_iter()
-                .map(|(k, v)| (k.to_string(), v.to_string()))
-                .collect(),
-        )
+        let mut headers = Self::default();
+
+        for (key, value) in iter {
+            headers.append(key, value);
+        }
+
+        headers
+    }
+}
+
+/// Limits on a field section, checked before decoding it (see [`Headers::with_frame_limited`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HeadersLimits {
+    max_field_size: usize,
+    max_section_size: usize,
+    max_field_count: usize,
+}
+
+impl HeadersLimits {
+    /// Upper bound of [`Self::with_max_field_size`]: the decoder cannot handle larger fields.
+    pub const MAX_FIELD_SIZE_CEILING: usize = 32 * 1024;
+
+    /// Overhead of each field in the size of a field section
+    /// (see [RFC 9114](https://www.rfc-editor.org/rfc/rfc9114#section-4.2.2)).
+    pub const FIELD_OVERHEAD: usize = 32;
+
+    /// Creates the default limits.
+    pub const fn new() -> Self {
+        Self {
+            max_field_size: Headers::MAX_FIELD_SIZE,
+            max_section_size: usize::MAX,
+            max_field_count: usize::MAX,
+        }
+    }
+
+    /// Sets the maximum size of a single field (name and value), once decoded.
+    ///
+    /// Values larger than [`Self::MAX_FIELD_SIZE_CEILING`] are treated as the ceiling.
+    /// Default is [`Headers::MAX_FIELD_SIZE`].
+    pub const fn with_max_field_size(mut self, value: usize) -> Self {
+        self.max_field_size = if value < Self::MAX_FIELD_SIZE_CEILING {
+            value
+        } else {
+            Self::MAX_FIELD_SIZE_CEILING
+        };
+        self
+    }
+
+    /// Sets the maximum size of the field section, once decoded: the sum of the size of
+    /// the name and value of each field, plus [`Self::FIELD_OVERHEAD`] per field.
+    ///
+    /// Default is unlimited.
+    pub const fn with_max_section_size(mut self, value: usize) -> Self {
+        self.max_section_size = value;
+        self
+    }
+
+    /// Sets the maximum number of fields of the field section.
+    ///
+    /// Default is unlimited.
+    pub const fn with_max_field_count(mut self, value: usize) -> Self {
+        self.max_field_count = value;
+        self
+    }
+
+    /// Returns the maximum size of a single field.
+    pub const fn max_field_size(&self) -> usize {
+        self.max_field_size
+    }
+
+    /// Returns the maximum size of the field section.
+    pub const fn max_section_size(&self) -> usize {
+        self.max_section_size
+    }
+
+    /// Returns the maximum number of fields of the field section.
+    pub const fn max_field_count(&self) -> usize {
+        self.max_field_count
+    }
+}
+
+impl Default for HeadersLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Checks the structure of a QPACK field section
+/// (see [RFC 9204](https://www.rfc-editor.org/rfc/rfc9204#section-4.5)) before decoding it.
+///
+/// Only the static table can be referenced, and literals must be complete, valid UTF-8
+/// and within the field size of `limits`: the decoder would abort otherwise
+/// ([`ErrorCode::Decompression`]). A field section exceeding the section size or the
+/// field count of `limits` is rejected with [`ErrorCode::ExcessiveLoad`].
+fn validate_field_section(mut payload: &[u8], limits: &HeadersLimits) -> Result<(), ErrorCode> {
+    let max = limits.max_field_size;
+    let mut section_size = 0usize;
+    let mut field_count = 0;
+
+    let required_insert_count = read_prefix_int(&mut payload, 8)
+        .ok_or(ErrorCode::Decompression)?
+        .1;
+    let _delta_base = read_prefix_int(&mut payload, 7).ok_or(ErrorCode::Decompression)?;
+
+    if required_insert_count != 0 {
+        return Err(ErrorCode::Decompression);
+    }
+
+    while let Some(&first) = payload.first() {
+        let field_size =
+            read_field_line(&mut payload, first, max).ok_or(ErrorCode::Decompression)?;
+
+        if field_size > max {
+            return Err(ErrorCode::Decompression);
+        }
+
+        field_count += 1;
+        section_size = section_size.saturating_add(field_size + HeadersLimits::FIELD_OVERHEAD);
+
+        if field_count > limits.max_field_count || section_size > limits.max_section_size {
+            return Err(ErrorCode::ExcessiveLoad);
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a field line starting with `first`, returning its decoded size (name and value).
+fn read_field_line(payload: &mut &[u8], first: u8, max: usize) -> Option<usize> {
+    if first & 0b1000_0000 != 0 {
+        // Indexed field line (static only).
+        let (flags, index) = read_prefix_int(payload, 6)?;
+        if flags & 0b0100_0000 == 0 {
+            return None;
+        }
+        let (name, value) = qpack::static_entry(index)?;
+        Some(name.len() + value.len())
+    } else if first & 0b0100_0000 != 0 {
+        // Literal field line with name reference (static only).
+        let (flags, index) = read_prefix_int(payload, 4)?;
+        if flags & 0b0001_0000 == 0 {
+            return None;
+        }
+        let (name, _) = qpack::static_entry(index)?;
+        Some(name.len() + read_string(payload, 7, max)?)
+    } else if first & 0b0010_0000 != 0 {
+        // Literal field line with literal name.
+        Some(read_string(payload, 3, max)? + read_string(payload, 7, max)?)
+    } else {
+        // Post-base representations refer to the dynamic table.
+        None
+    }
+}
+
+/// Reads a string literal with an `n`-bit prefix length (preceded by the `H` bit),
+/// returning its decoded size.
+///
+/// The literal must be valid UTF-8, and not larger than `max`, once decoded.
+fn read_string(payload: &mut &[u8], n: u32, max: usize) -> Option<usize> {
+    let (flags, len) = read_prefix_int(payload, n)?;
+    let huffman = flags & (1 << n) != 0;
+
+    let len = usize::try_from(len).ok()?;
+    if payload.len() < len || len > max {
+        return None;
     }
+
+    let (string, rest) = payload.split_at(len);
+    *payload = rest;
+
+    let decoded_len = if huffman {
+        let decoded = huffman::decode(string)?;
+        core::str::from_utf8(&decoded).ok()?;
+        decoded.len()
+    } else {
+        core::str::from_utf8(string).ok()?;
+        len
+    };
+
+    Some(decoded_len)
 }
 
-impl AsRef<HashMap<String, String>> for Headers {
-    fn as_ref(&self) -> &HashMap<String, String> {
-        &self.0
+/// Reads an integer with an `n`-bit prefix
+/// (see [RFC 7541](https://www.rfc-editor.org/rfc/rfc7541#section-5.1)).
+///
+/// It returns the first byte (for the flags before the prefix) and the integer.
+pub(crate) fn read_prefix_int(payload: &mut &[u8], n: u32) -> Option<(u8, u64)> {
+    let (&first, rest) = payload.split_first()?;
+    *payload = rest;
+
+    let mask = ((1u16 << n) - 1) as u8;
+    let mut value = u64::from(first & mask);
+
+    if value < u64::from(mask) {
+        return Some((first, value));
+    }
+
+    let mut shift = 0;
+
+    loop {
+        let (&byte, rest) = payload.split_first()?;
+        *payload = rest;
+
+        if shift > 56 {
+            return None;
+        }
+
+        value += u64::from(byte & 0x7f) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return Some((first, value));
+        }
     }
 }
 
+#[cfg(feature = "std")]
 impl From<StreamId> for ls_qpack::StreamId {
     #[inline(always)]
     fn from(value: StreamId) -> Self {
         ls_qpack::StreamId::new(value.into_u64())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::varint::VarInt;
+
+    #[test]
+    fn case_insensitive() {
+        let headers = Headers::from_iter([("Content-Type", "text/plain")]);
+        assert_eq!(headers.get("content-type"), Some("text/plain"));
+        assert_eq!(headers.get("CONTENT-TYPE"), Some("text/plain"));
+        assert_eq!(headers.iter().next(), Some(("content-type", "text/plain")));
+    }
+
+    #[test]
+    fn field_section_validation() {
+        let stream_id = StreamId::new(VarInt::from_u32(0));
+        let limits = HeadersLimits::default();
+
+        let headers = Headers::from_iter([(":path", "/"), ("user-agent", &"a".repeat(1024))]);
+        let frame = headers.generate_frame(stream_id);
+        assert!(validate_field_section(frame.payload(), &limits).is_ok());
+        assert_eq!(Headers::with_frame(&frame, stream_id).unwrap().len(), 2);
+
+        // Literal with literal name ("x-l"), and a value of `MAX_FIELD_SIZE` bytes.
+        let mut payload = vec![0x00, 0x00, 0x23, b'x', b'-', b'l', 0x7f, 0x81, 0x7f];
+        payload.resize(payload.len() + Headers::MAX_FIELD_SIZE, b'a');
+        assert!(validate_field_section(&payload, &limits).is_err());
+
+        // Same, with a value of `MAX_FIELD_SIZE - 3` bytes.
+        let mut payload = vec![0x00, 0x00, 0x23, b'x', b'-', b'l', 0x7f, 0xfe, 0x7e];
+        payload.resize(payload.len() + Headers::MAX_FIELD_SIZE - 3, b'a');
+        assert!(validate_field_section(&payload, &limits).is_ok());
+
+        // Invalid UTF-8 value.
+        assert!(validate_field_section(&[0x00, 0x00, 0x21, b'x', 0x01, 0xff], &limits).is_err());
+
+        // Required insert count (dynamic table).
+        assert!(validate_field_section(&[0x01, 0x00], &limits).is_err());
+
+        // Indexed field line, dynamic table.
+        assert!(validate_field_section(&[0x00, 0x00, 0x80], &limits).is_err());
+
+        // Literal with literal name, truncated name.
+        assert!(validate_field_section(&[0x00, 0x00, 0x27, 0xff, 0x7f, b'a'], &limits).is_err());
+    }
+
+    #[test]
+    fn custom_limits() {
+        let stream_id = StreamId::new(VarInt::from_u32(0));
+        let frame = Headers::from_iter([("user-agent", "a".repeat(100))]).generate_frame(stream_id);
+
+        let limits = HeadersLimits::new().with_max_field_size(64);
+        assert_eq!(limits.max_field_size(), 64);
+        assert!(matches!(
+            Headers::with_frame_limited(&frame, stream_id, &limits),
+            Err(ErrorCode::Decompression)
+        ));
+
+        let limits = HeadersLimits::new().with_max_field_size(usize::MAX);
+        assert_eq!(
+            limits.max_field_size(),
+            HeadersLimits::MAX_FIELD_SIZE_CEILING
+        );
+
+        let limits = HeadersLimits::new().with_max_field_size(256);
+        assert_eq!(
+            Headers::with_frame_limited(&frame, stream_id, &limits)
+                .unwrap()
+                .get("user-agent"),
+            Some("a".repeat(100).as_str())
+        );
+    }
+
+    #[test]
+    fn section_limits() {
+        let stream_id = StreamId::new(VarInt::from_u32(0));
+        // ":path: /" is indexed: 5 + 1 + 32 bytes, once decoded.
+        let fields = [(":path", "/"), ("x-a", "1"), ("x-b", "22")];
+        let frame = Headers::from_iter(fields).generate_frame(stream_id);
+        let section_size = 38 + 36 + 37;
+        assert!(frame.payload().len() < section_size);
+
+        let limits = HeadersLimits::new()
+            .with_max_section_size(section_size)
+            .with_max_field_count(3);
+        assert_eq!(limits.max_section_size(), section_size);
+        assert_eq!(limits.max_field_count(), 3);
+        assert_eq!(
+            Headers::with_frame_limited(&frame, stream_id, &limits)
+                .unwrap()
+                .len(),
+            3
+        );
+
+        let limits = HeadersLimits::new().with_max_section_size(section_size - 1);
+        assert!(matches!(
+            Headers::with_frame_limited(&frame, stream_id, &limits),
+            Err(ErrorCode::ExcessiveLoad)
+        ));
+
+        let limits = HeadersLimits::new().with_max_field_count(2);
+        assert!(matches!(
+            Headers::with_frame_limited(&frame, stream_id, &limits),
+            Err(ErrorCode::ExcessiveLoad)
+        ));
+
+        // Indexed field line, beyond the static table.
+        assert!(matches!(
+            validate_field_section(&[0x00, 0x00, 0xff, 0x25], &limits),
+            Err(ErrorCode::Decompression)
+        ));
+    }
+
+    #[test]
+    fn multi_value() {
+        let mut headers = Headers::default();
+        headers.append("cookie", "a=1");
+        headers.append("Cookie", "b=2");
+
+        assert_eq!(headers.get("cookie"), Some("a=1"));
+        assert_eq!(
+            headers.get_all("cookie").collect::<Vec<_>>(),
+            ["a=1", "b=2"]
+        );
+        assert_eq!(headers.len(), 2);
+
+        headers.insert("cookie", "c=3");
+        assert_eq!(headers.get_all("cookie").collect::<Vec<_>>(), ["c=3"]);
+
+        assert_eq!(headers.remove("COOKIE").as_deref(), Some("c=3"));
+        assert!(headers.is_empty());
+    }
+
+    #[test]
+    fn frame_roundtrip() {
+        let stream_id = StreamId::new(VarInt::from_u32(0));
+        let headers = Headers::from_iter([("key", "value1"), ("key", "value2")]);
+
+        let headers = Headers::with_frame(&headers.generate_frame(stream_id), stream_id).unwrap();
+        assert_eq!(
+            headers.get_all("key").collect::<Vec<_>>(),
+            ["value1", "value2"]
+        );
+    }
+}
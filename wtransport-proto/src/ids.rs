@@ -1,6 +1,6 @@
 use crate::varint::VarInt;
-use std::fmt;
-use std::str::FromStr;
+use core::fmt;
+use core::str::FromStr;
 
 /// QUIC stream id.
 #[derive(Copy, Clone, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -252,6 +252,9 @@ impl StatusCode {
     /// The smallest code.
     pub const MIN: Self = Self(100);
 
+    /// HTTP 102 Processing status code (interim response).
+    pub const PROCESSING: Self = Self(102);
+
     /// HTTP 200 OK status code.
     pub const OK: Self = Self(200);
 
@@ -270,6 +273,12 @@ impl StatusCode {
         self.0
     }
 
+    /// Returns true if the status code is 1xx (interim response).
+    #[inline(always)]
+    pub fn is_informational(self) -> bool {
+        (100..200).contains(&self.0)
+    }
+
     /// Returns true if the status code is 2xx.
     #[inline(always)]
     pub fn is_successful(self) -> bool {
@@ -335,14 +344,14 @@ impl FromStr for StatusCode {
 
 impl fmt::Debug for StatusCode {
     #[inline]
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         self.0.fmt(f)
     }
 }
 
 impl fmt::Display for StatusCode {
     #[inline]
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         self.0.fmt(f)
     }
 }
@@ -404,7 +413,8 @@ mod tests {
     #[test]
     fn qstream_id() {
         for (quarter, id) in stream_types(1024)
-            .filter_map(|(id, r#type)| matches!(r#type, StreamType::ClientBi).then(|| id))
+            .filter(|(_id, r#type)| matches!(r#type, StreamType::ClientBi))
+            .map(|(id, _type)| id)
             .enumerate()
         {
             let session_id = SessionId::try_from_varint(id).unwrap();
@@ -416,6 +426,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn status_code_classes() {
+        assert!(StatusCode::PROCESSING.is_informational());
+        assert!(!StatusCode::PROCESSING.is_successful());
+        assert!(StatusCode::OK.is_successful());
+        assert!(!StatusCode::OK.is_informational());
+        assert!(!StatusCode::NOT_FOUND.is_informational());
+        assert!(!StatusCode::NOT_FOUND.is_successful());
+    }
+
     mod utils {
         use super::*;
 
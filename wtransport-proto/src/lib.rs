@@ -1,10 +1,22 @@
 //! WebTransport protocol implementation.
+//!
+//! The crate is `no_std` (with `alloc`) when its default `std` feature is disabled: the
+//! codecs (frames, headers, capsules, etc.) remain available, while the `async` I/O
+//! requires `std`. Without `std`, field sections are encoded and decoded by a built-in
+//! QPACK codec, restricted to the static table (the dynamic table is disabled with `std`
+//! too), and session URLs are parsed without IDNA or percent-encoding processing.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 #![warn(missing_docs)]
 
+extern crate alloc;
+
 /// I/O and buffer operations.
 pub mod bytes;
 
+/// HTTP capsules.
+pub mod capsule;
+
 /// HTTP3 datagrams.
 pub mod datagram;
 
@@ -14,12 +26,27 @@ pub mod error;
 /// HTTP3 frame.
 pub mod frame;
 
+/// Entry points for fuzzing the parsers.
+///
+/// Each function parses arbitrary bytes and discards the result: it only fails by
+/// panicking. They are used by the `cargo fuzz` targets of this crate (see `fuzz/`).
+#[cfg(feature = "fuzz")]
+#[cfg_attr(docsrs, doc(cfg(feature = "fuzz")))]
+pub mod fuzz;
+
 /// HTTP3 HEADERS frame payload.
 pub mod headers;
 
+mod huffman;
+
 /// Types for identifiers.
 pub mod ids;
 
+#[cfg(not(feature = "std"))]
+mod octets;
+
+mod qpack;
+
 /// WebTransport session utilities.
 pub mod session;
 
@@ -0,0 +1,111 @@
+//! Minimal replacement of the `octets` crate (which requires `std`), limited to the
+//! operations used by [`crate::bytes`].
+
+use crate::varint::VarInt;
+
+/// The buffer is too short for the operation.
+#[derive(Debug)]
+pub(crate) struct BufferTooShortError;
+
+/// An immutable buffer with an offset.
+pub(crate) struct Octets<'a> {
+    buf: &'a [u8],
+    off: usize,
+}
+
+impl<'a> Octets<'a> {
+    pub(crate) fn with_slice(buf: &'a [u8]) -> Self {
+        Self { buf, off: 0 }
+    }
+
+    pub(crate) fn cap(&self) -> usize {
+        self.buf.len() - self.off
+    }
+
+    pub(crate) fn off(&self) -> usize {
+        self.off
+    }
+
+    pub(crate) fn buf(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    pub(crate) fn skip(&mut self, len: usize) -> Result<(), BufferTooShortError> {
+        if len > self.cap() {
+            return Err(BufferTooShortError);
+        }
+
+        self.off += len;
+        Ok(())
+    }
+
+    pub(crate) fn get_varint(&mut self) -> Result<u64, BufferTooShortError> {
+        let remaining = &self.buf[self.off..];
+        let size = VarInt::parse_size(*remaining.first().ok_or(BufferTooShortError)?);
+        let encoded = remaining.get(..size).ok_or(BufferTooShortError)?;
+
+        let value = encoded[1..]
+            .iter()
+            .fold(u64::from(encoded[0] & 0x3f), |value, byte| {
+                (value << 8) | u64::from(*byte)
+            });
+
+        self.off += size;
+        Ok(value)
+    }
+
+    pub(crate) fn get_bytes(&mut self, len: usize) -> Result<Octets<'a>, BufferTooShortError> {
+        let bytes = self.buf[self.off..].get(..len).ok_or(BufferTooShortError)?;
+
+        self.off += len;
+        Ok(Octets::with_slice(bytes))
+    }
+}
+
+/// A mutable buffer with an offset.
+pub(crate) struct OctetsMut<'a> {
+    buf: &'a mut [u8],
+    off: usize,
+}
+
+impl<'a> OctetsMut<'a> {
+    pub(crate) fn with_slice(buf: &'a mut [u8]) -> Self {
+        Self { buf, off: 0 }
+    }
+
+    pub(crate) fn cap(&self) -> usize {
+        self.buf.len() - self.off
+    }
+
+    pub(crate) fn off(&self) -> usize {
+        self.off
+    }
+
+    pub(crate) fn buf(&self) -> &[u8] {
+        self.buf
+    }
+
+    pub(crate) fn put_varint(&mut self, value: u64) -> Result<(), BufferTooShortError> {
+        let size = VarInt::try_from_u64(value)
+            .map_err(|_| BufferTooShortError)?
+            .size();
+        let encoded = &value.to_be_bytes()[8 - size..];
+
+        self.put_bytes(encoded)?;
+
+        // The 2 most significant bits encode the size
+        self.buf[self.off - size] |= (size.trailing_zeros() as u8) << 6;
+
+        Ok(())
+    }
+
+    pub(crate) fn put_bytes(&mut self, bytes: &[u8]) -> Result<(), BufferTooShortError> {
+        if bytes.len() > self.cap() {
+            return Err(BufferTooShortError);
+        }
+
+        self.buf[self.off..self.off + bytes.len()].copy_from_slice(bytes);
+        self.off += bytes.len();
+        Ok(())
+    }
+}
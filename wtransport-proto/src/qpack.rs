@@ -0,0 +1,311 @@
+//! QPACK codec restricted to the static table
+//! (see [RFC 9204](https://www.rfc-editor.org/rfc/rfc9204)), used without `std`.
+//!
+//! The static table is also used with `std`, to validate field sections.
+
+#[cfg(any(test, not(feature = "std")))]
+use crate::headers::read_prefix_int;
+
+#[cfg(any(test, not(feature = "std")))]
+use crate::huffman;
+
+#[cfg(any(test, not(feature = "std")))]
+use alloc::string::String;
+
+#[cfg(any(test, not(feature = "std")))]
+use alloc::vec::Vec;
+
+/// Decodes a field section whose structure has been validated.
+///
+/// Returns `None` if the field section is malformed, or refers to the dynamic table.
+#[cfg(any(test, not(feature = "std")))]
+pub(crate) fn decode(mut payload: &[u8]) -> Option<Vec<(String, String)>> {
+    let required_insert_count = read_prefix_int(&mut payload, 8)?.1;
+    let _delta_base = read_prefix_int(&mut payload, 7)?;
+
+    if required_insert_count != 0 {
+        return None;
+    }
+
+    let mut fields = Vec::new();
+
+    while let Some(&first) = payload.first() {
+        let field = if first & 0b1000_0000 != 0 {
+            // Indexed field line.
+            let (flags, index) = read_prefix_int(&mut payload, 6)?;
+            if flags & 0b0100_0000 == 0 {
+                return None;
+            }
+            let (name, value) = static_entry(index)?;
+            (String::from(name), String::from(value))
+        } else if first & 0b0100_0000 != 0 {
+            // Literal field line with name reference.
+            let (flags, index) = read_prefix_int(&mut payload, 4)?;
+            if flags & 0b0001_0000 == 0 {
+                return None;
+            }
+            let (name, _) = static_entry(index)?;
+            (String::from(name), read_string(&mut payload, 7)?)
+        } else if first & 0b0010_0000 != 0 {
+            // Literal field line with literal name.
+            let name = read_string(&mut payload, 3)?;
+            (name, read_string(&mut payload, 7)?)
+        } else {
+            return None;
+        };
+
+        fields.push(field);
+    }
+
+    Some(fields)
+}
+
+/// Encodes a field section, referring to the static table where possible.
+#[cfg(any(test, not(feature = "std")))]
+pub(crate) fn encode<'a, I>(fields: I) -> Vec<u8>
+where
+    I: IntoIterator<Item = (&'a str, &'a str)>,
+{
+    // Required insert count and delta base: no dynamic table.
+    let mut payload = Vec::from([0x00, 0x00]);
+
+    for (name, value) in fields {
+        if let Some(index) = STATIC_TABLE
+            .iter()
+            .position(|&entry| entry == (name, value))
+        {
+            // Indexed field line.
+            write_prefix_int(&mut payload, 0b1100_0000, 6, index as u64);
+        } else if let Some(index) = STATIC_TABLE.iter().position(|&(entry, _)| entry == name) {
+            // Literal field line with name reference.
+            write_prefix_int(&mut payload, 0b0101_0000, 4, index as u64);
+            write_string(&mut payload, 0, 7, value);
+        } else {
+            // Literal field line with literal name.
+            write_string(&mut payload, 0b0010_0000, 3, name);
+            write_string(&mut payload, 0, 7, value);
+        }
+    }
+
+    payload
+}
+
+/// Returns the entry of the static table at `index`.
+pub(crate) fn static_entry(index: u64) -> Option<(&'static str, &'static str)> {
+    STATIC_TABLE.get(usize::try_from(index).ok()?).copied()
+}
+
+/// Reads a string literal with an `n`-bit prefix length (preceded by the `H` bit).
+#[cfg(any(test, not(feature = "std")))]
+fn read_string(payload: &mut &[u8], n: u32) -> Option<String> {
+    let (flags, len) = read_prefix_int(payload, n)?;
+    let huffman = flags & (1 << n) != 0;
+
+    let len = usize::try_from(len).ok()?;
+    if payload.len() < len {
+        return None;
+    }
+
+    let (string, rest) = payload.split_at(len);
+    *payload = rest;
+
+    let string = if huffman {
+        huffman::decode(string)?
+    } else {
+        string.to_vec()
+    };
+
+    String::from_utf8(string).ok()
+}
+
+/// Writes a string literal, without Huffman encoding, with an `n`-bit prefix length
+/// after `flags`.
+#[cfg(any(test, not(feature = "std")))]
+fn write_string(payload: &mut Vec<u8>, flags: u8, n: u32, string: &str) {
+    write_prefix_int(payload, flags, n, string.len() as u64);
+    payload.extend_from_slice(string.as_bytes());
+}
+
+/// Writes an integer with an `n`-bit prefix
+/// (see [RFC 7541](https://www.rfc-editor.org/rfc/rfc7541#section-5.1)), after `flags`.
+#[cfg(any(test, not(feature = "std")))]
+fn write_prefix_int(payload: &mut Vec<u8>, flags: u8, n: u32, mut value: u64) {
+    let mask = ((1u16 << n) - 1) as u8;
+
+    if value < u64::from(mask) {
+        payload.push(flags | value as u8);
+        return;
+    }
+
+    payload.push(flags | mask);
+    value -= u64::from(mask);
+
+    while value >= 0x80 {
+        payload.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+
+    payload.push(value as u8);
+}
+
+/// The QPACK static table
+/// (see [RFC 9204](https://www.rfc-editor.org/rfc/rfc9204#appendix-A)).
+const STATIC_TABLE: [(&str, &str); 99] = [
+    (":authority", ""),
+    (":path", "/"),
+    ("age", "0"),
+    ("content-disposition", ""),
+    ("content-length", "0"),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("referer", ""),
+    ("set-cookie", ""),
+    (":method", "CONNECT"),
+    (":method", "DELETE"),
+    (":method", "GET"),
+    (":method", "HEAD"),
+    (":method", "OPTIONS"),
+    (":method", "POST"),
+    (":method", "PUT"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "103"),
+    (":status", "200"),
+    (":status", "304"),
+    (":status", "404"),
+    (":status", "503"),
+    ("accept", "*/*"),
+    ("accept", "application/dns-message"),
+    ("accept-encoding", "gzip, deflate, br"),
+    ("accept-ranges", "bytes"),
+    ("access-control-allow-headers", "cache-control"),
+    ("access-control-allow-headers", "content-type"),
+    ("access-control-allow-origin", "*"),
+    ("cache-control", "max-age=0"),
+    ("cache-control", "max-age=2592000"),
+    ("cache-control", "max-age=604800"),
+    ("cache-control", "no-cache"),
+    ("cache-control", "no-store"),
+    ("cache-control", "public, max-age=31536000"),
+    ("content-encoding", "br"),
+    ("content-encoding", "gzip"),
+    ("content-type", "application/dns-message"),
+    ("content-type", "application/javascript"),
+    ("content-type", "application/json"),
+    ("content-type", "application/x-www-form-urlencoded"),
+    ("content-type", "image/gif"),
+    ("content-type", "image/jpeg"),
+    ("content-type", "image/png"),
+    ("content-type", "text/css"),
+    ("content-type", "text/html; charset=utf-8"),
+    ("content-type", "text/plain"),
+    ("content-type", "text/plain;charset=utf-8"),
+    ("range", "bytes=0-"),
+    ("strict-transport-security", "max-age=31536000"),
+    (
+        "strict-transport-security",
+        "max-age=31536000; includesubdomains",
+    ),
+    (
+        "strict-transport-security",
+        "max-age=31536000; includesubdomains; preload",
+    ),
+    ("vary", "accept-encoding"),
+    ("vary", "origin"),
+    ("x-content-type-options", "nosniff"),
+    ("x-xss-protection", "1; mode=block"),
+    (":status", "100"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "302"),
+    (":status", "400"),
+    (":status", "403"),
+    (":status", "421"),
+    (":status", "425"),
+    (":status", "500"),
+    ("accept-language", ""),
+    ("access-control-allow-credentials", "FALSE"),
+    ("access-control-allow-credentials", "TRUE"),
+    ("access-control-allow-headers", "*"),
+    ("access-control-allow-methods", "get"),
+    ("access-control-allow-methods", "get, post, options"),
+    ("access-control-allow-methods", "options"),
+    ("access-control-expose-headers", "content-length"),
+    ("access-control-request-headers", "content-type"),
+    ("access-control-request-method", "get"),
+    ("access-control-request-method", "post"),
+    ("alt-svc", "clear"),
+    ("authorization", ""),
+    (
+        "content-security-policy",
+        "script-src 'none'; object-src 'none'; base-uri 'none'",
+    ),
+    ("early-data", "1"),
+    ("expect-ct", ""),
+    ("forwarded", ""),
+    ("if-range", ""),
+    ("origin", ""),
+    ("purpose", "prefetch"),
+    ("server", ""),
+    ("timing-allow-origin", "*"),
+    ("upgrade-insecure-requests", "1"),
+    ("user-agent", ""),
+    ("x-forwarded-for", ""),
+    ("x-frame-options", "deny"),
+    ("x-frame-options", "sameorigin"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::Frame;
+    use crate::headers::Headers;
+    use crate::ids::StreamId;
+    use crate::varint::VarInt;
+    use alloc::borrow::Cow;
+
+    #[test]
+    fn static_table() {
+        let stream_id = StreamId::new(VarInt::from_u32(0));
+
+        for &(name, value) in STATIC_TABLE.iter() {
+            let frame = Headers::from_iter([(name, value)]).generate_frame(stream_id);
+            assert_eq!(
+                decode(frame.payload()).unwrap(),
+                [(name.to_string(), value.to_string())]
+            );
+            assert_eq!(encode([(name, value)]), frame.payload());
+        }
+    }
+
+    #[test]
+    fn interoperability() {
+        let stream_id = StreamId::new(VarInt::from_u32(0));
+        let fields = [
+            (":method", "CONNECT"),
+            (":path", "/session?id=1"),
+            ("user-agent", "wtransport"),
+            ("x-custom", &"a".repeat(300)),
+        ];
+
+        let frame = Headers::from_iter(fields).generate_frame(stream_id);
+        let decoded = decode(frame.payload()).unwrap();
+        assert!(decoded
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .eq(fields));
+
+        let frame = Frame::new_headers(Cow::Owned(encode(fields)));
+        let headers = Headers::with_frame(&frame, stream_id).unwrap();
+        assert!(headers.iter().eq(fields));
+
+        // Dynamic table reference.
+        assert!(decode(&[0x00, 0x00, 0x80]).is_none());
+    }
+}
@@ -7,9 +7,20 @@ use crate::error::ErrorCode;
 use crate::frame::Frame;
 use crate::frame::FrameKind;
 use crate::varint::VarInt;
-use std::borrow::Cow;
-use std::collections::hash_map;
-use std::collections::HashMap;
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
+use std::collections::hash_map as map;
+
+#[cfg(feature = "std")]
+use std::collections::HashMap as Map;
+
+#[cfg(not(feature = "std"))]
+use alloc::collections::btree_map as map;
+
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap as Map;
 
 enum ParseError {
     ReservedSetting,
@@ -17,7 +28,7 @@ enum ParseError {
 }
 
 /// Settings IDs for an HTTP3 connection.
-#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
 pub enum SettingId {
     /// SETTINGS_QPACK_MAX_TABLE_CAPACITY.
     QPackMaxTableCapacity,
@@ -34,6 +45,15 @@ pub enum SettingId {
     /// SETTINGS_H3_DATAGRAM.
     H3Datagram,
 
+    /// SETTINGS_H3_DATAGRAM of `draft-ietf-masque-h3-datagram-04` and `-05`.
+    H3DatagramDraft04,
+
+    /// H3_DATAGRAM of `draft-ietf-masque-h3-datagram-00` to `-02`, whose datagrams start
+    /// with a Flow ID (see [`DatagramFormat::FlowId`](crate::datagram::DatagramFormat::FlowId)).
+    ///
+    /// `-03` advertised the same setting with another datagram format, which is not supported.
+    H3DatagramDraft00,
+
     /// SETTINGS_ENABLE_WEBTRANSPORT.
     EnableWebTransport,
 
@@ -59,6 +79,8 @@ impl SettingId {
                 setting_ids::SETTINGS_QPACK_BLOCKED_STREAMS => Ok(Self::QPackBlockedStreams),
                 setting_ids::SETTINGS_ENABLE_CONNECT_PROTOCOL => Ok(Self::EnableConnectProtocol),
                 setting_ids::SETTINGS_H3_DATAGRAM => Ok(Self::H3Datagram),
+                setting_ids::SETTINGS_H3_DATAGRAM_DRAFT04 => Ok(Self::H3DatagramDraft04),
+                setting_ids::SETTINGS_H3_DATAGRAM_DRAFT00 => Ok(Self::H3DatagramDraft00),
                 setting_ids::SETTINGS_ENABLE_WEBTRANSPORT => Ok(Self::EnableWebTransport),
                 setting_ids::SETTINGS_WEBTRANSPORT_MAX_SESSIONS => {
                     Ok(Self::WebTransportMaxSessions)
@@ -75,6 +97,8 @@ impl SettingId {
             Self::QPackBlockedStreams => setting_ids::SETTINGS_QPACK_BLOCKED_STREAMS,
             Self::EnableConnectProtocol => setting_ids::SETTINGS_ENABLE_CONNECT_PROTOCOL,
             Self::H3Datagram => setting_ids::SETTINGS_H3_DATAGRAM,
+            Self::H3DatagramDraft04 => setting_ids::SETTINGS_H3_DATAGRAM_DRAFT04,
+            Self::H3DatagramDraft00 => setting_ids::SETTINGS_H3_DATAGRAM_DRAFT00,
             Self::EnableWebTransport => setting_ids::SETTINGS_ENABLE_WEBTRANSPORT,
             Self::WebTransportMaxSessions => setting_ids::SETTINGS_WEBTRANSPORT_MAX_SESSIONS,
             Self::Exercise(id) => id,
@@ -94,7 +118,7 @@ impl SettingId {
 
 /// Collection of settings for an HTTP3 connection.
 #[derive(Clone, Debug)]
-pub struct Settings(HashMap<SettingId, VarInt>);
+pub struct Settings(Map<SettingId, VarInt>);
 
 impl Settings {
     /// Produces a new [`SettingsBuilder`] for new [`Settings`] construction.
@@ -125,10 +149,10 @@ impl Settings {
 
             match SettingId::parse(id) {
                 Ok(setting_id) => match settings.0.entry(setting_id) {
-                    hash_map::Entry::Vacant(slot) => {
+                    map::Entry::Vacant(slot) => {
                         slot.insert(value);
                     }
-                    hash_map::Entry::Occupied(_) => {
+                    map::Entry::Occupied(_) => {
                         return Err(ErrorCode::Settings);
                     }
                 },
@@ -144,7 +168,7 @@ impl Settings {
     ///
     /// This function allocates heap-memory, producing a [`Frame`] with owned payload.
     /// See [`Self::generate_frame_ref`] for a version without inner memory allocation.
-    pub fn generate_frame(&self) -> Frame {
+    pub fn generate_frame(&self) -> Frame<'_> {
         let mut payload = Vec::new();
 
         for (id, value) in &self.0 {
@@ -182,7 +206,7 @@ impl Settings {
     }
 
     fn new() -> Self {
-        Self(HashMap::new())
+        Self(Map::new())
     }
 }
 
@@ -224,12 +248,36 @@ impl SettingsBuilder {
         self
     }
 
+    /// Enables HTTP3 datagrams support of older drafts, along with the current one.
+    ///
+    /// See [`DatagramFormat::negotiate`](crate::datagram::DatagramFormat::negotiate).
+    pub fn enable_h3_datagrams_drafts(mut self) -> Self {
+        self.0
+             .0
+            .insert(SettingId::H3DatagramDraft04, VarInt::from_u32(1));
+        self.0
+             .0
+            .insert(SettingId::H3DatagramDraft00, VarInt::from_u32(1));
+        self
+    }
+
     /// Sets the max number of webtransport sessions server accepts over single HTTP/3 connection.
     pub fn webtransport_max_sessions(mut self, value: VarInt) -> Self {
         self.0 .0.insert(SettingId::WebTransportMaxSessions, value);
         self
     }
 
+    /// Sets an exercise setting, which the peer ignores.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` is not a valid exercise (i.e., `0x1f * N + 0x21`).
+    pub fn exercise(mut self, id: VarInt, value: VarInt) -> Self {
+        assert!(SettingId::is_exercise(id));
+        self.0 .0.insert(SettingId::Exercise(id), value);
+        self
+    }
+
     /// Builds [`Settings`].
     pub fn build(self) -> Settings {
         self.0
@@ -244,6 +292,8 @@ mod setting_ids {
     pub const SETTINGS_QPACK_BLOCKED_STREAMS: VarInt = VarInt::from_u32(0x07);
     pub const SETTINGS_ENABLE_CONNECT_PROTOCOL: VarInt = VarInt::from_u32(0x08);
     pub const SETTINGS_H3_DATAGRAM: VarInt = VarInt::from_u32(0x33);
+    pub const SETTINGS_H3_DATAGRAM_DRAFT04: VarInt = VarInt::from_u32(0xffd277);
+    pub const SETTINGS_H3_DATAGRAM_DRAFT00: VarInt = VarInt::from_u32(0x276);
     pub const SETTINGS_ENABLE_WEBTRANSPORT: VarInt = VarInt::from_u32(0x2b603742);
     pub const SETTINGS_WEBTRANSPORT_MAX_SESSIONS: VarInt = VarInt::from_u32(0xc671706a);
 }
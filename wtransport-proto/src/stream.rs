@@ -38,6 +38,25 @@ pub enum IoReadError {
 #[cfg(feature = "async")]
 pub type IoWriteError = bytes::IoWriteError;
 
+/// Returns the error code rejecting a frame which failed to parse.
+fn frame_error_code(parse_error: frame::ParseError) -> ErrorCode {
+    match parse_error {
+        // Only returned when unknown frames are not read.
+        frame::ParseError::UnknownFrame => ErrorCode::FrameUnexpected,
+        frame::ParseError::InvalidSessionId => ErrorCode::Id,
+        frame::ParseError::PayloadTooLarge => ErrorCode::ExcessiveLoad,
+    }
+}
+
+/// Returns the error code rejecting a stream whose header failed to parse.
+fn stream_header_error_code(parse_error: stream_header::ParseError) -> ErrorCode {
+    match parse_error {
+        // Only returned when unknown stream types are not read.
+        stream_header::ParseError::UnknownStream => ErrorCode::StreamCreation,
+        stream_header::ParseError::InvalidSessionId => ErrorCode::Id,
+    }
+}
+
 /// A QUIC/HTTP3/WebTransport stream.
 pub struct Stream<K, S> {
     kind: K,
@@ -77,7 +96,8 @@ pub mod biremote {
     }
 
     impl StreamBiRemoteH3 {
-        /// See [`Frame::read`].
+        /// See [`Frame::read_any`]: frames of unknown type are returned, and MUST be ignored
+        /// unless understood by the caller.
         pub fn read_frame<'a, R>(
             &mut self,
             bytes_reader: &mut R,
@@ -85,22 +105,14 @@ pub mod biremote {
         where
             R: BytesReader<'a>,
         {
-            loop {
-                match Frame::read(bytes_reader)? {
-                    Ok(frame) => {
-                        return Some(self.validate_frame(frame));
-                    }
-                    Err(frame::ParseError::UnknownFrame) => {
-                        continue;
-                    }
-                    Err(frame::ParseError::InvalidSessionId) => {
-                        return Some(Err(ErrorCode::Id));
-                    }
-                }
+            match Frame::read_any(bytes_reader)? {
+                Ok(frame) => Some(self.validate_frame(frame)),
+                Err(parse_error) => Some(Err(frame_error_code(parse_error))),
             }
         }
 
-        /// See [`Frame::read_async`].
+        /// See [`Frame::read_any_async`]: frames of unknown type are returned, and MUST be
+        /// ignored unless understood by the caller.
         #[cfg(feature = "async")]
         #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
         pub async fn read_frame_async<'a, R>(
@@ -110,23 +122,16 @@ pub mod biremote {
         where
             R: AsyncRead + Unpin + ?Sized,
         {
-            loop {
-                match Frame::read_async(reader).await {
-                    Ok(frame) => {
-                        return self.validate_frame(frame).map_err(IoReadError::H3);
-                    }
-                    Err(frame::IoReadError::Parse(frame::ParseError::UnknownFrame)) => {
-                        continue;
-                    }
-                    Err(frame::IoReadError::Parse(frame::ParseError::InvalidSessionId)) => {
-                        return Err(IoReadError::H3(ErrorCode::Id));
-                    }
-                    Err(frame::IoReadError::IO(io_error)) => {
-                        if matches!(io_error, bytes::IoReadError::UnexpectedFin) {
-                            return Err(IoReadError::H3(ErrorCode::Frame));
-                        } else {
-                            return Err(IoReadError::IO(io_error));
-                        }
+            match Frame::read_any_async(reader).await {
+                Ok(frame) => self.validate_frame(frame).map_err(IoReadError::H3),
+                Err(frame::IoReadError::Parse(parse_error)) => {
+                    Err(IoReadError::H3(frame_error_code(parse_error)))
+                }
+                Err(frame::IoReadError::IO(io_error)) => {
+                    if matches!(io_error, bytes::IoReadError::UnexpectedFin) {
+                        Err(IoReadError::H3(ErrorCode::Frame))
+                    } else {
+                        Err(IoReadError::IO(io_error))
                     }
                 }
             }
@@ -213,6 +218,7 @@ pub mod biremote {
                     }
                 }
                 FrameKind::Exercise(_) => Ok(frame),
+                FrameKind::Unknown(_) => Ok(frame),
             }
         }
     }
@@ -259,7 +265,8 @@ pub mod bilocal {
     }
 
     impl StreamBiLocalH3 {
-        /// See [`Frame::read`].
+        /// See [`Frame::read_any`]: frames of unknown type are returned, and MUST be ignored
+        /// unless understood by the caller.
         pub fn read_frame<'a, R>(
             &self,
             bytes_reader: &mut R,
@@ -267,22 +274,14 @@ pub mod bilocal {
         where
             R: BytesReader<'a>,
         {
-            loop {
-                match Frame::read(bytes_reader)? {
-                    Ok(frame) => {
-                        return Some(self.validate_frame(frame));
-                    }
-                    Err(frame::ParseError::UnknownFrame) => {
-                        continue;
-                    }
-                    Err(frame::ParseError::InvalidSessionId) => {
-                        return Some(Err(ErrorCode::Id));
-                    }
-                }
+            match Frame::read_any(bytes_reader)? {
+                Ok(frame) => Some(self.validate_frame(frame)),
+                Err(parse_error) => Some(Err(frame_error_code(parse_error))),
             }
         }
 
-        /// See [`Frame::read_async`].
+        /// See [`Frame::read_any_async`]: frames of unknown type are returned, and MUST be
+        /// ignored unless understood by the caller.
         #[cfg(feature = "async")]
         #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
         pub async fn read_frame_async<'a, R>(
@@ -292,23 +291,16 @@ pub mod bilocal {
         where
             R: AsyncRead + Unpin + ?Sized,
         {
-            loop {
-                match Frame::read_async(reader).await {
-                    Ok(frame) => {
-                        return self.validate_frame(frame).map_err(IoReadError::H3);
-                    }
-                    Err(frame::IoReadError::Parse(frame::ParseError::UnknownFrame)) => {
-                        continue;
-                    }
-                    Err(frame::IoReadError::Parse(frame::ParseError::InvalidSessionId)) => {
-                        return Err(IoReadError::H3(ErrorCode::Id));
-                    }
-                    Err(frame::IoReadError::IO(io_error)) => {
-                        if matches!(io_error, bytes::IoReadError::UnexpectedFin) {
-                            return Err(IoReadError::H3(ErrorCode::Frame));
-                        } else {
-                            return Err(IoReadError::IO(io_error));
-                        }
+            match Frame::read_any_async(reader).await {
+                Ok(frame) => self.validate_frame(frame).map_err(IoReadError::H3),
+                Err(frame::IoReadError::Parse(parse_error)) => {
+                    Err(IoReadError::H3(frame_error_code(parse_error)))
+                }
+                Err(frame::IoReadError::IO(io_error)) => {
+                    if matches!(io_error, bytes::IoReadError::UnexpectedFin) {
+                        Err(IoReadError::H3(ErrorCode::Frame))
+                    } else {
+                        Err(IoReadError::IO(io_error))
                     }
                 }
             }
@@ -455,6 +447,7 @@ pub mod bilocal {
                 FrameKind::Settings => Err(ErrorCode::FrameUnexpected),
                 FrameKind::WebTransport => Err(ErrorCode::FrameUnexpected),
                 FrameKind::Exercise(_) => Ok(frame),
+                FrameKind::Unknown(_) => Ok(frame),
             }
         }
     }
@@ -508,26 +501,25 @@ pub mod uniremote {
         /// In case there are no enough information, [`MaybeUpgradeH3::Quic`] (i.e, `self`)
         /// will be returned.
         ///
-        /// If the stream type is unknown [`ErrorCode::StreamCreation`] is returned.
+        /// If the stream type is unknown, the stream is upgraded with [`StreamKind::Unknown`].
         /// In that case, MUST NOT consider unknown stream types to be a connection error of any kind.
         pub fn upgrade<'a, R>(self, bytes_reader: &mut R) -> Result<MaybeUpgradeH3, ErrorCode>
         where
             R: BytesReader<'a>,
         {
-            match StreamHeader::read(bytes_reader) {
+            match StreamHeader::read_any(bytes_reader) {
                 Some(Ok(stream_header)) => Ok(MaybeUpgradeH3::H3(StreamUniRemoteH3 {
                     kind: self.kind,
                     stage: H3::new(Some(stream_header)),
                 })),
-                Some(Err(stream_header::ParseError::UnknownStream)) => {
-                    Err(ErrorCode::StreamCreation)
-                }
-                Some(Err(stream_header::ParseError::InvalidSessionId)) => Err(ErrorCode::Id),
+                Some(Err(parse_error)) => Err(stream_header_error_code(parse_error)),
                 None => Ok(MaybeUpgradeH3::Quic(self)),
             }
         }
 
         /// Upgrades to an HTTP3 stream.
+        ///
+        /// If the stream type is unknown, the stream is upgraded with [`StreamKind::Unknown`].
         #[cfg(feature = "async")]
         #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
         pub async fn upgrade_async<R>(
@@ -537,19 +529,15 @@ pub mod uniremote {
         where
             R: AsyncRead + Unpin + ?Sized,
         {
-            match StreamHeader::read_async(reader).await {
+            match StreamHeader::read_any_async(reader).await {
                 Ok(stream_header) => Ok(StreamUniRemoteH3 {
                     kind: self.kind,
                     stage: H3::new(Some(stream_header)),
                 }),
 
-                Err(stream_header::IoReadError::Parse(
-                    stream_header::ParseError::UnknownStream,
-                )) => Err(IoReadError::H3(ErrorCode::StreamCreation)),
-
-                Err(stream_header::IoReadError::Parse(
-                    stream_header::ParseError::InvalidSessionId,
-                )) => Err(IoReadError::H3(ErrorCode::Id)),
+                Err(stream_header::IoReadError::Parse(parse_error)) => {
+                    Err(IoReadError::H3(stream_header_error_code(parse_error)))
+                }
 
                 Err(stream_header::IoReadError::IO(io_error)) => {
                     if matches!(io_error, bytes::IoReadError::UnexpectedFin) {
@@ -564,7 +552,8 @@ pub mod uniremote {
     }
 
     impl StreamUniRemoteH3 {
-        /// See [`Frame::read`].
+        /// See [`Frame::read_any`]: frames of unknown type are returned, and MUST be ignored
+        /// unless understood by the caller.
         ///
         /// # Panics
         ///
@@ -578,22 +567,14 @@ pub mod uniremote {
         {
             assert!(!matches!(self.kind(), StreamKind::WebTransport));
 
-            loop {
-                match Frame::read(bytes_reader)? {
-                    Ok(frame) => {
-                        return Some(self.validate_frame(frame));
-                    }
-                    Err(frame::ParseError::UnknownFrame) => {
-                        continue;
-                    }
-                    Err(frame::ParseError::InvalidSessionId) => {
-                        return Some(Err(ErrorCode::Id));
-                    }
-                }
+            match Frame::read_any(bytes_reader)? {
+                Ok(frame) => Some(self.validate_frame(frame)),
+                Err(parse_error) => Some(Err(frame_error_code(parse_error))),
             }
         }
 
-        /// See [`Frame::read_async`].
+        /// See [`Frame::read_any_async`]: frames of unknown type are returned, and MUST be
+        /// ignored unless understood by the caller.
         ///
         /// # Panics
         ///
@@ -609,23 +590,16 @@ pub mod uniremote {
         {
             assert!(!matches!(self.kind(), StreamKind::WebTransport));
 
-            loop {
-                match Frame::read_async(reader).await {
-                    Ok(frame) => {
-                        return self.validate_frame(frame).map_err(IoReadError::H3);
-                    }
-                    Err(frame::IoReadError::Parse(frame::ParseError::UnknownFrame)) => {
-                        continue;
-                    }
-                    Err(frame::IoReadError::Parse(frame::ParseError::InvalidSessionId)) => {
-                        return Err(IoReadError::H3(ErrorCode::Id));
-                    }
-                    Err(frame::IoReadError::IO(io_error)) => {
-                        if matches!(io_error, bytes::IoReadError::UnexpectedFin) {
-                            return Err(IoReadError::H3(ErrorCode::Frame));
-                        } else {
-                            return Err(IoReadError::IO(io_error));
-                        }
+            match Frame::read_any_async(reader).await {
+                Ok(frame) => self.validate_frame(frame).map_err(IoReadError::H3),
+                Err(frame::IoReadError::Parse(parse_error)) => {
+                    Err(IoReadError::H3(frame_error_code(parse_error)))
+                }
+                Err(frame::IoReadError::IO(io_error)) => {
+                    if matches!(io_error, bytes::IoReadError::UnexpectedFin) {
+                        Err(IoReadError::H3(ErrorCode::Frame))
+                    } else {
+                        Err(IoReadError::IO(io_error))
                     }
                 }
             }
@@ -695,6 +669,7 @@ pub mod uniremote {
                 FrameKind::Settings => Ok(frame),
                 FrameKind::WebTransport => Err(ErrorCode::FrameUnexpected),
                 FrameKind::Exercise(_) => Ok(frame),
+                FrameKind::Unknown(_) => Ok(frame),
             }
         }
     }
@@ -893,7 +868,8 @@ pub mod session {
     pub type StreamSession = Stream<Bi, Session>;
 
     impl StreamSession {
-        /// See [`Frame::read`].
+        /// See [`Frame::read_any`]: frames of unknown type are returned, and MUST be ignored
+        /// unless understood by the caller.
         pub fn read_frame<'a, R>(
             &self,
             bytes_reader: &mut R,
@@ -901,22 +877,14 @@ pub mod session {
         where
             R: BytesReader<'a>,
         {
-            loop {
-                match Frame::read(bytes_reader)? {
-                    Ok(frame) => {
-                        return Some(self.validate_frame(frame));
-                    }
-                    Err(frame::ParseError::UnknownFrame) => {
-                        continue;
-                    }
-                    Err(frame::ParseError::InvalidSessionId) => {
-                        return Some(Err(ErrorCode::Id));
-                    }
-                }
+            match Frame::read_any(bytes_reader)? {
+                Ok(frame) => Some(self.validate_frame(frame)),
+                Err(parse_error) => Some(Err(frame_error_code(parse_error))),
             }
         }
 
-        /// See [`Frame::read_async`].
+        /// See [`Frame::read_any_async`]: frames of unknown type are returned, and MUST be
+        /// ignored unless understood by the caller.
         #[cfg(feature = "async")]
         #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
         pub async fn read_frame_async<'a, R>(
@@ -926,23 +894,32 @@ pub mod session {
         where
             R: AsyncRead + Unpin + ?Sized,
         {
-            loop {
-                match Frame::read_async(reader).await {
-                    Ok(frame) => {
-                        return self.validate_frame(frame).map_err(IoReadError::H3);
-                    }
-                    Err(frame::IoReadError::Parse(frame::ParseError::UnknownFrame)) => {
-                        continue;
-                    }
-                    Err(frame::IoReadError::Parse(frame::ParseError::InvalidSessionId)) => {
-                        return Err(IoReadError::H3(ErrorCode::Id));
-                    }
-                    Err(frame::IoReadError::IO(io_error)) => {
-                        if matches!(io_error, bytes::IoReadError::UnexpectedFin) {
-                            return Err(IoReadError::H3(ErrorCode::Frame));
-                        } else {
-                            return Err(IoReadError::IO(io_error));
-                        }
+            self.read_frame_async_with_limit(reader, Frame::MAX_PAYLOAD_SIZE)
+                .await
+        }
+
+        /// Like [`Self::read_frame_async`], but frames with a payload larger than
+        /// `max_payload_size` are rejected (with [`ErrorCode::ExcessiveLoad`]).
+        #[cfg(feature = "async")]
+        #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+        pub async fn read_frame_async_with_limit<'a, R>(
+            &self,
+            reader: &mut R,
+            max_payload_size: usize,
+        ) -> Result<Frame<'a>, IoReadError>
+        where
+            R: AsyncRead + Unpin + ?Sized,
+        {
+            match Frame::read_any_async_with_limit(reader, max_payload_size).await {
+                Ok(frame) => self.validate_frame(frame).map_err(IoReadError::H3),
+                Err(frame::IoReadError::Parse(parse_error)) => {
+                    Err(IoReadError::H3(frame_error_code(parse_error)))
+                }
+                Err(frame::IoReadError::IO(io_error)) => {
+                    if matches!(io_error, bytes::IoReadError::UnexpectedFin) {
+                        Err(IoReadError::H3(ErrorCode::Frame))
+                    } else {
+                        Err(IoReadError::IO(io_error))
                     }
                 }
             }
@@ -1008,6 +985,7 @@ pub mod session {
                 FrameKind::Settings => Err(ErrorCode::FrameUnexpected),
                 FrameKind::WebTransport => Err(ErrorCode::FrameUnexpected),
                 FrameKind::Exercise(_) => Ok(frame),
+                FrameKind::Unknown(_) => Ok(frame),
             }
         }
     }
@@ -1040,7 +1018,7 @@ pub mod types {
         /// Returns the previous value (false it this is the first frame).
         #[inline(always)]
         pub(super) fn set_first_frame(&mut self) -> bool {
-            std::mem::replace(&mut self.first_frame_done, true)
+            core::mem::replace(&mut self.first_frame_done, true)
         }
 
         #[inline(always)]
@@ -1140,7 +1118,7 @@ pub mod types {
 mod tests {
     use super::*;
     use crate::varint::VarInt;
-    use std::borrow::Cow;
+    use alloc::borrow::Cow;
 
     #[test]
     fn bi_remote_webtransport() {
@@ -1187,4 +1165,43 @@ mod tests {
 
         assert!(matches!(frame, Err(ErrorCode::Frame)));
     }
+
+    #[test]
+    fn bi_remote_unknown_frame() {
+        let mut buffer = Frame::serialize_any(VarInt::from_u32(0x424242), b"Payload");
+        Frame::new_headers(Cow::Borrowed(b"Headers"))
+            .write(&mut buffer)
+            .unwrap();
+
+        let mut buffer_reader = BufferReader::new(buffer.as_slice());
+        let mut stream = Stream::accept_bi().upgrade();
+        let frame = stream
+            .read_frame_from_buffer(&mut buffer_reader)
+            .unwrap()
+            .unwrap();
+
+        assert!(matches!(frame.kind(), FrameKind::Unknown(id) if id == VarInt::from_u32(0x424242)));
+        assert_eq!(frame.payload(), b"Payload");
+
+        let frame = stream
+            .read_frame_from_buffer(&mut buffer_reader)
+            .unwrap()
+            .unwrap();
+
+        assert!(matches!(frame.kind(), FrameKind::Headers));
+    }
+
+    #[test]
+    fn uni_remote_unknown_stream() {
+        let buffer = StreamHeader::serialize_any(VarInt::from_u32(0x424242));
+
+        let stream = match Stream::accept_uni().upgrade(&mut buffer.as_slice()) {
+            Ok(uniremote::MaybeUpgradeH3::H3(stream)) => stream,
+            _ => panic!("Stream is upgraded"),
+        };
+
+        assert!(
+            matches!(stream.kind(), StreamKind::Unknown(id) if id == VarInt::from_u32(0x424242))
+        );
+    }
 }
@@ -52,6 +52,7 @@ pub type IoWriteError = bytes::IoWriteError;
 
 /// An HTTP3 stream type.
 #[derive(Copy, Clone, Debug)]
+#[non_exhaustive]
 pub enum StreamKind {
     /// CONTROL stream type.
     Control,
@@ -67,6 +68,11 @@ pub enum StreamKind {
 
     /// Exercise stream.
     Exercise(VarInt),
+
+    /// Stream of a type unknown to this implementation.
+    ///
+    /// Only returned by [`StreamHeader::read_any`] and [`StreamHeader::read_any_async`].
+    Unknown(VarInt),
 }
 
 impl StreamKind {
@@ -87,6 +93,13 @@ impl StreamKind {
         }
     }
 
+    const fn parse_any(id: VarInt) -> Self {
+        match StreamKind::parse(id) {
+            Some(kind) => kind,
+            None => StreamKind::Unknown(id),
+        }
+    }
+
     const fn id(self) -> VarInt {
         match self {
             StreamKind::Control => stream_type_ids::CONTROL_STREAM,
@@ -94,6 +107,7 @@ impl StreamKind {
             StreamKind::QPackDecoder => stream_type_ids::QPACK_DECODER_STREAM,
             StreamKind::WebTransport => stream_type_ids::WEBTRANSPORT_STREAM,
             StreamKind::Exercise(id) => id,
+            StreamKind::Unknown(id) => id,
         }
     }
 }
@@ -122,6 +136,17 @@ impl StreamHeader {
         Self::new(StreamKind::WebTransport, Some(session_id))
     }
 
+    /// Creates a new stream header of type [`StreamKind::Exercise`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` is not a valid exercise (see [`StreamKind::is_id_exercise`]).
+    #[inline(always)]
+    pub fn new_exercise(id: VarInt) -> Self {
+        assert!(StreamKind::is_id_exercise(id));
+        Self::new(StreamKind::Exercise(id), None)
+    }
+
     /// Reads a [`StreamHeader`] from a [`BytesReader`].
     ///
     /// It returns [`None`] if the `bytes_reader` does not contain enough bytes
@@ -132,11 +157,20 @@ impl StreamHeader {
     where
         R: BytesReader<'a>,
     {
-        let kind_id = bytes_reader.get_varint()?;
-        let kind = match StreamKind::parse(kind_id) {
-            Some(kind) => kind,
-            None => return Some(Err(ParseError::UnknownStream)),
-        };
+        match Self::read_any(bytes_reader)? {
+            Ok(header) if matches!(header.kind, StreamKind::Unknown(_)) => {
+                Some(Err(ParseError::UnknownStream))
+            }
+            result => Some(result),
+        }
+    }
+
+    /// Like [`Self::read`], but a stream of unknown type is returned as [`StreamKind::Unknown`].
+    pub fn read_any<'a, R>(bytes_reader: &mut R) -> Option<Result<Self, ParseError>>
+    where
+        R: BytesReader<'a>,
+    {
+        let kind = StreamKind::parse_any(bytes_reader.get_varint()?);
 
         let session_id = if matches!(kind, StreamKind::WebTransport) {
             let session_id = match SessionId::try_from_varint(bytes_reader.get_varint()?) {
@@ -156,14 +190,28 @@ impl StreamHeader {
     #[cfg(feature = "async")]
     #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
     pub async fn read_async<R>(reader: &mut R) -> Result<Self, IoReadError>
+    where
+        R: AsyncRead + Unpin + ?Sized,
+    {
+        match Self::read_any_async(reader).await {
+            Ok(header) if matches!(header.kind, StreamKind::Unknown(_)) => {
+                Err(IoReadError::Parse(ParseError::UnknownStream))
+            }
+            result => result,
+        }
+    }
+
+    /// Like [`Self::read_async`], but a stream of unknown type is returned as
+    /// [`StreamKind::Unknown`].
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn read_any_async<R>(reader: &mut R) -> Result<Self, IoReadError>
     where
         R: AsyncRead + Unpin + ?Sized,
     {
         use crate::bytes::BytesReaderAsync;
 
-        let kind_id = reader.get_varint(false).await?;
-        let kind =
-            StreamKind::parse(kind_id).ok_or(IoReadError::Parse(ParseError::UnknownStream))?;
+        let kind = StreamKind::parse_any(reader.get_varint(false).await?);
 
         let session_id = if matches!(kind, StreamKind::WebTransport) {
             let session_id = SessionId::try_from_varint(reader.get_varint(true).await?)
@@ -276,6 +324,9 @@ impl StreamHeader {
         if let StreamKind::Exercise(id) = kind {
             debug_assert!(StreamKind::is_id_exercise(id));
             debug_assert!(session_id.is_none());
+        } else if let StreamKind::Unknown(id) = kind {
+            debug_assert!(StreamKind::parse(id).is_none());
+            debug_assert!(session_id.is_none());
         } else if let StreamKind::WebTransport = kind {
             debug_assert!(session_id.is_some());
         } else {
@@ -422,6 +473,52 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn exercise() {
+        let stream_header = StreamHeader::new_exercise(VarInt::from_u32(0x21));
+        assert!(stream_header.session_id().is_none());
+
+        let stream_header = utils::assert_serde(stream_header);
+        assert!(matches!(
+            stream_header.kind(),
+            StreamKind::Exercise(id) if id == VarInt::from_u32(0x21)
+        ));
+    }
+
+    #[test]
+    #[should_panic]
+    fn exercise_invalid_id() {
+        StreamHeader::new_exercise(VarInt::from_u32(0x22));
+    }
+
+    #[test]
+    fn read_any_unknown_stream() {
+        let buffer = StreamHeader::serialize_any(VarInt::from_u32(0x424242));
+
+        let stream_header = StreamHeader::read_any(&mut buffer.as_slice())
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            stream_header.kind(),
+            StreamKind::Unknown(id) if id == VarInt::from_u32(0x424242)
+        ));
+        assert!(stream_header.session_id().is_none());
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn read_any_unknown_stream_async() {
+        let buffer = StreamHeader::serialize_any(VarInt::from_u32(0x424242));
+
+        let stream_header = StreamHeader::read_any_async(&mut buffer.as_slice())
+            .await
+            .unwrap();
+        assert!(matches!(
+            stream_header.kind(),
+            StreamKind::Unknown(id) if id == VarInt::from_u32(0x424242)
+        ));
+    }
+
     #[test]
     fn invalid_session_id() {
         let invalid_session_id = SessionId::maybe_invalid(VarInt::from_u32(1));
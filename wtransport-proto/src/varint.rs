@@ -1,4 +1,4 @@
-use std::fmt;
+use core::fmt;
 
 /// Error returned when constructing a [`VarInt`] from a value >= 2^62
 #[derive(Debug)]
@@ -122,14 +122,14 @@ impl From<VarInt> for u64 {
 
 impl fmt::Debug for VarInt {
     #[inline]
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         self.0.fmt(f)
     }
 }
 
 impl fmt::Display for VarInt {
     #[inline]
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         self.0.fmt(f)
     }
 }
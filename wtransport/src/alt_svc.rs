@@ -0,0 +1,165 @@
+use std::fmt;
+use std::time::Duration;
+
+/// An HTTP3 alternative service (see [RFC 7838](https://www.rfc-editor.org/rfc/rfc7838)).
+///
+/// HTTPS servers advertise the WebTransport endpoint to browsers with an `Alt-Svc` header
+/// field, whose value is the [`Display`](fmt::Display) representation of this type
+/// (e.g., `h3=":4433"; ma=86400`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AltSvc {
+    host: Option<String>,
+    port: u16,
+    max_age: Option<Duration>,
+}
+
+impl AltSvc {
+    /// Protocol identifier of HTTP3.
+    pub const PROTOCOL_ID: &'static str = "h3";
+
+    /// Creates an alternative service on `port`, on the same host of the origin.
+    pub fn new(port: u16) -> Self {
+        Self {
+            host: None,
+            port,
+            max_age: None,
+        }
+    }
+
+    /// Sets an alternative host.
+    pub fn with_host<H>(mut self, host: H) -> Self
+    where
+        H: ToString,
+    {
+        self.host = Some(host.to_string());
+        self
+    }
+
+    /// Sets for how long the client can cache the advertisement (`ma` parameter).
+    ///
+    /// Clients assume 24 hours if not specified.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Returns the alternative host, if different from the origin.
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_deref()
+    }
+
+    /// Returns the alternative port.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Returns the `ma` parameter, if present.
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age
+    }
+
+    /// Parses the HTTP3 alternatives from the value of an `Alt-Svc` header field.
+    ///
+    /// Alternatives for other protocols and malformed entries are skipped.
+    pub fn parse(value: &str) -> Vec<Self> {
+        split_unquoted(value, ',')
+            .filter_map(|entry| {
+                let mut params = split_unquoted(entry, ';');
+
+                let (protocol_id, authority) = params.next()?.split_once('=')?;
+                if protocol_id.trim() != Self::PROTOCOL_ID {
+                    return None;
+                }
+
+                let authority = authority.trim().strip_prefix('"')?.strip_suffix('"')?;
+                let (host, port) = authority.rsplit_once(':')?;
+
+                let mut alt_svc = Self::new(port.parse().ok()?);
+
+                if !host.is_empty() {
+                    alt_svc = alt_svc.with_host(host);
+                }
+
+                for param in params {
+                    if let Some(("ma", max_age)) = param.split_once('=').map(|(k, v)| (k.trim(), v))
+                    {
+                        let max_age = max_age.trim().trim_matches('"').parse().ok()?;
+                        alt_svc = alt_svc.with_max_age(Duration::from_secs(max_age));
+                    }
+                }
+
+                Some(alt_svc)
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for AltSvc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}=\"{}:{}\"",
+            Self::PROTOCOL_ID,
+            self.host.as_deref().unwrap_or_default(),
+            self.port
+        )?;
+
+        if let Some(max_age) = self.max_age {
+            write!(f, "; ma={}", max_age.as_secs())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits `value` on `separator`, ignoring the ones in quoted strings.
+fn split_unquoted(value: &str, separator: char) -> impl Iterator<Item = &str> {
+    let mut quoted = false;
+
+    value
+        .split(move |c| {
+            if c == '"' {
+                quoted = !quoted;
+            }
+
+            c == separator && !quoted
+        })
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        assert_eq!(AltSvc::new(443).to_string(), "h3=\":443\"");
+        assert_eq!(
+            AltSvc::new(4433)
+                .with_host("example.com")
+                .with_max_age(Duration::from_secs(3600))
+                .to_string(),
+            "h3=\"example.com:4433\"; ma=3600"
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let alt_svcs =
+            AltSvc::parse(r#"h3-29=":443", h3=":4433"; ma=60, h3="[::1]:443"; persist=1"#);
+
+        assert_eq!(
+            alt_svcs,
+            [
+                AltSvc::new(4433).with_max_age(Duration::from_secs(60)),
+                AltSvc::new(443).with_host("[::1]"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_clear() {
+        assert!(AltSvc::parse("clear").is_empty());
+    }
+}
@@ -0,0 +1,555 @@
+use quinn::TransportConfig;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+use tokio::sync::Notify;
+use tracing::debug;
+
+/// What to do with an incoming connection when the [`MemoryBudget`] is exhausted.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BudgetPolicy {
+    /// The connection is refused: it is closed with `H3_EXCESSIVE_LOAD`.
+    #[default]
+    Shed,
+
+    /// The connection waits for memory to be released (back-pressure), within the
+    /// [SETTINGS exchange timeout](crate::config::Timeouts::settings_exchange). Past the
+    /// timeout, it is refused as with [`Shed`](Self::Shed).
+    Wait,
+}
+
+/// Limits enforced by a [`MemoryBudget`].
+#[derive(Copy, Clone, Debug)]
+pub struct BudgetLimits {
+    max_bytes: u64,
+    session_cap: u64,
+    policy: BudgetPolicy,
+}
+
+impl BudgetLimits {
+    /// Default cap of the memory buffered by a session.
+    pub const DEFAULT_SESSION_CAP: u64 = 4 * 1024 * 1024;
+
+    /// Creates limits allowing `max_bytes` buffered across all the sessions, with the
+    /// [default session cap](Self::DEFAULT_SESSION_CAP).
+    pub fn new(max_bytes: u64) -> Self {
+        Self {
+            max_bytes,
+            session_cap: Self::DEFAULT_SESSION_CAP,
+            policy: BudgetPolicy::default(),
+        }
+    }
+
+    /// Sets the maximum number of bytes buffered by a session.
+    ///
+    /// The cap is split among the buffers of each connection: 3/8 for the QUIC
+    /// [send window](crate::config::ServerConfigBuilder::send_window), 3/8 for the QUIC
+    /// [receive window](crate::config::ServerConfigBuilder::receive_window), 1/16 for each
+    /// of the QUIC datagram send and receive buffers, and 1/8 for the data buffered by
+    /// wtransport till the application reads it (datagrams and capsules).
+    pub fn session_cap(mut self, value: u64) -> Self {
+        self.session_cap = value.max(16);
+        self
+    }
+
+    /// Sets what to do when the budget is exhausted (by default, [`BudgetPolicy::Shed`]).
+    pub fn policy(mut self, policy: BudgetPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Returns the maximum number of bytes buffered across all the sessions.
+    #[inline(always)]
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    /// Returns the maximum number of bytes buffered by a session.
+    #[inline(always)]
+    pub fn max_session_bytes(&self) -> u64 {
+        self.session_cap
+    }
+
+    /// Bounds the QUIC buffers of each connection to their share of the session cap.
+    pub(crate) fn apply(&self, transport_config: &mut TransportConfig) {
+        let datagram_buffer = usize::try_from(self.session_cap / 16).unwrap_or(usize::MAX);
+
+        transport_config.send_window(self.window());
+        transport_config.receive_window(self.receive_window());
+        transport_config.datagram_send_buffer_size(datagram_buffer);
+        transport_config.datagram_receive_buffer_size(Some(datagram_buffer));
+    }
+
+    /// Returns the connection-level receive window of each connection.
+    pub(crate) fn receive_window(&self) -> quinn::VarInt {
+        quinn::VarInt::from_u64(self.window()).unwrap_or(quinn::VarInt::MAX)
+    }
+
+    fn window(&self) -> u64 {
+        self.session_cap / 8 * 3
+    }
+
+    /// Returns the bytes reserved by a connection for its QUIC buffers.
+    fn quic_reservation(&self) -> u64 {
+        self.window() * 2 + self.session_cap / 16 * 2
+    }
+
+    /// Returns the bytes a connection may buffer in wtransport.
+    fn buffered_cap(&self) -> u64 {
+        self.session_cap - self.quic_reservation()
+    }
+}
+
+/// Live usage of a [`MemoryBudget`].
+///
+/// See [`MemoryBudget::usage`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BudgetUsage {
+    sessions: usize,
+    reserved_bytes: u64,
+    buffered_bytes: u64,
+    waiting: usize,
+    shed: u64,
+}
+
+impl BudgetUsage {
+    /// Returns the number of admitted connections.
+    #[inline(always)]
+    pub fn sessions(&self) -> usize {
+        self.sessions
+    }
+
+    /// Returns the number of bytes reserved for the QUIC buffers of the connections, i.e.,
+    /// the maximum memory they may buffer in QUIC.
+    ///
+    /// QUIC does not report how much of its buffers is in use, so they count in full.
+    #[inline(always)]
+    pub fn reserved_bytes(&self) -> u64 {
+        self.reserved_bytes
+    }
+
+    /// Returns the number of bytes buffered by wtransport till the application reads
+    /// them (datagrams and capsules).
+    #[inline(always)]
+    pub fn buffered_bytes(&self) -> u64 {
+        self.buffered_bytes
+    }
+
+    /// Returns the number of bytes counted against the budget (reserved and buffered).
+    #[inline(always)]
+    pub fn used_bytes(&self) -> u64 {
+        self.reserved_bytes + self.buffered_bytes
+    }
+
+    /// Returns the number of connections waiting for memory (see [`BudgetPolicy::Wait`]).
+    #[inline(always)]
+    pub fn waiting(&self) -> usize {
+        self.waiting
+    }
+
+    /// Returns the cumulative number of connections refused right away
+    /// (see [`BudgetPolicy::Shed`]).
+    #[inline(always)]
+    pub fn shed(&self) -> u64 {
+        self.shed
+    }
+}
+
+/// Budget of the memory buffered by the connections of an endpoint.
+///
+/// Right after its QUIC handshake, before any HTTP3 processing, each incoming connection
+/// reserves its QUIC buffers, which are bounded by the session cap
+/// (see [`BudgetLimits::session_cap`]). The data wtransport then buffers for the
+/// application (datagrams and capsules not yet read) is counted as it arrives: incoming
+/// datagrams are dropped, and capsules reset the session stream with
+/// `H3_EXCESSIVE_LOAD`, if they would exceed the session cap or the budget.
+///
+/// Peers not reading their streams, or sending faster than the application reads, can
+/// therefore never make the endpoint buffer more than the budget. Once the budget is
+/// exhausted, new connections are shed or wait, depending on the [`BudgetPolicy`].
+/// The memory of a connection is released once it is dropped.
+///
+/// The budget is enabled with
+/// [`ServerConfigBuilder::memory_budget`](crate::config::ServerConfigBuilder::memory_budget):
+/// the application keeps a clone to query the live usage.
+#[derive(Clone)]
+pub struct MemoryBudget(Arc<BudgetInner>);
+
+struct BudgetInner {
+    limits: BudgetLimits,
+    usage: Mutex<Usage>,
+    released: Notify,
+    waiting: AtomicUsize,
+    shed: AtomicU64,
+}
+
+/// Memory counted against a budget.
+#[derive(Default)]
+struct Usage {
+    sessions: usize,
+    reserved: u64,
+    buffered: u64,
+}
+
+impl MemoryBudget {
+    /// Creates a budget enforcing `limits`.
+    pub fn new(limits: BudgetLimits) -> Self {
+        Self(Arc::new(BudgetInner {
+            limits,
+            usage: Mutex::new(Usage::default()),
+            released: Notify::new(),
+            waiting: AtomicUsize::new(0),
+            shed: AtomicU64::new(0),
+        }))
+    }
+
+    /// Returns the limits.
+    #[inline(always)]
+    pub fn limits(&self) -> &BudgetLimits {
+        &self.0.limits
+    }
+
+    /// Returns the live usage.
+    pub fn usage(&self) -> BudgetUsage {
+        let usage = self.0.lock_usage();
+
+        BudgetUsage {
+            sessions: usage.sessions,
+            reserved_bytes: usage.reserved,
+            buffered_bytes: usage.buffered,
+            waiting: self.0.waiting.load(Ordering::Relaxed),
+            shed: self.0.shed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Admits a connection, reserving its QUIC buffers, or returns `None` if it is shed.
+    ///
+    /// With [`BudgetPolicy::Wait`], this future completes once enough memory is
+    /// released: the caller bounds the wait.
+    pub(crate) async fn reserve(&self) -> Option<MemoryAccount> {
+        if let Some(account) = self.try_reserve() {
+            return Some(account);
+        }
+
+        match self.0.limits.policy {
+            BudgetPolicy::Shed => {
+                debug!("Memory budget exhausted: shedding connection");
+                self.0.shed.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+            BudgetPolicy::Wait => {
+                debug!("Memory budget exhausted: connection waiting");
+                let _waiting = Waiting::count(&self.0);
+
+                loop {
+                    let released = self.0.released.notified();
+                    tokio::pin!(released);
+                    released.as_mut().enable();
+
+                    if let Some(account) = self.try_reserve() {
+                        return Some(account);
+                    }
+
+                    released.await;
+                }
+            }
+        }
+    }
+
+    fn try_reserve(&self) -> Option<MemoryAccount> {
+        let bytes = self.0.limits.quic_reservation();
+
+        {
+            let mut usage = self.0.lock_usage();
+            if usage.reserved + usage.buffered + bytes > self.0.limits.max_bytes {
+                return None;
+            }
+
+            usage.sessions += 1;
+            usage.reserved += bytes;
+        }
+
+        Some(MemoryAccount(Some(Arc::new(AccountInner {
+            budget: self.clone(),
+            buffered: AtomicU64::new(0),
+        }))))
+    }
+
+    /// Counts `bytes` buffered by the connection of `account`, if the limits allow.
+    fn charge(&self, account: &AccountInner, bytes: u64) -> bool {
+        let mut usage = self.0.lock_usage();
+        let buffered = account.buffered.load(Ordering::Relaxed);
+
+        if buffered + bytes > self.0.limits.buffered_cap()
+            || usage.reserved + usage.buffered + bytes > self.0.limits.max_bytes
+        {
+            return false;
+        }
+
+        usage.buffered += bytes;
+        account.buffered.store(buffered + bytes, Ordering::Relaxed);
+        true
+    }
+
+    fn release(&self, account: &AccountInner, bytes: u64) {
+        if bytes == 0 {
+            return;
+        }
+
+        {
+            let mut usage = self.0.lock_usage();
+            let buffered = account.buffered.load(Ordering::Relaxed);
+            usage.buffered = usage.buffered.saturating_sub(bytes);
+            account
+                .buffered
+                .store(buffered.saturating_sub(bytes), Ordering::Relaxed);
+        }
+
+        self.0.released.notify_waiters();
+    }
+}
+
+impl BudgetInner {
+    fn lock_usage(&self) -> MutexGuard<'_, Usage> {
+        self.usage.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl std::fmt::Debug for MemoryBudget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryBudget")
+            .field("limits", &self.0.limits)
+            .finish()
+    }
+}
+
+/// The memory of a connection, counted against its [`MemoryBudget`] till dropped.
+///
+/// The default account is not subject to any budget.
+#[derive(Clone, Default)]
+pub(crate) struct MemoryAccount(Option<Arc<AccountInner>>);
+
+struct AccountInner {
+    budget: MemoryBudget,
+    buffered: AtomicU64,
+}
+
+impl MemoryAccount {
+    /// Counts `bytes` buffered by wtransport, returning `false` if they would exceed the
+    /// session cap or the budget.
+    pub(crate) fn charge(&self, bytes: usize) -> bool {
+        match &self.0 {
+            Some(inner) => inner.budget.charge(inner, bytes as u64),
+            None => true,
+        }
+    }
+
+    /// Releases `bytes` previously [charged](Self::charge).
+    pub(crate) fn release(&self, bytes: usize) {
+        if let Some(inner) = &self.0 {
+            inner.budget.release(inner, bytes as u64);
+        }
+    }
+}
+
+impl Drop for AccountInner {
+    fn drop(&mut self) {
+        {
+            let mut usage = self.budget.0.lock_usage();
+            usage.sessions -= 1;
+            usage.reserved -= self.budget.0.limits.quic_reservation();
+            usage.buffered = usage.buffered.saturating_sub(*self.buffered.get_mut());
+        }
+
+        self.budget.0.released.notify_waiters();
+    }
+}
+
+/// A connection waiting for memory, counted till dropped (e.g., on timeout).
+struct Waiting<'a>(&'a BudgetInner);
+
+impl<'a> Waiting<'a> {
+    fn count(budget: &'a BudgetInner) -> Self {
+        budget.waiting.fetch_add(1, Ordering::Relaxed);
+        Self(budget)
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.waiting.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Timeouts;
+    use crate::error::ConnectionError;
+    use crate::test_utils;
+    use crate::Endpoint;
+    use std::time::Duration;
+
+    /// Limits of sessions reserving 1400 bytes, and buffering up to 200 bytes.
+    fn limits(max_bytes: u64) -> BudgetLimits {
+        BudgetLimits::new(max_bytes).session_cap(1600)
+    }
+
+    #[tokio::test]
+    async fn reserve() {
+        let budget = MemoryBudget::new(limits(3000));
+
+        let first = budget.reserve().await.unwrap();
+        let second = budget.reserve().await.unwrap();
+        assert!(budget.reserve().await.is_none());
+        assert_eq!(budget.usage().sessions(), 2);
+        assert_eq!(budget.usage().reserved_bytes(), 2800);
+        assert_eq!(budget.usage().shed(), 1);
+
+        drop(first);
+        let _third = budget.reserve().await.unwrap();
+        drop(second);
+        assert_eq!(budget.usage().sessions(), 1);
+        assert_eq!(budget.usage().used_bytes(), 1400);
+    }
+
+    #[tokio::test]
+    async fn charge() {
+        let budget = MemoryBudget::new(limits(3000));
+        let first = budget.reserve().await.unwrap();
+        let second = budget.reserve().await.unwrap();
+
+        // Up to the session cap.
+        assert!(first.charge(150));
+        assert!(!first.charge(51));
+        assert!(first.charge(50));
+        first.release(100);
+        assert_eq!(budget.usage().buffered_bytes(), 100);
+
+        // Up to the budget.
+        assert!(second.charge(100));
+        assert!(!second.charge(1));
+        assert_eq!(budget.usage().used_bytes(), 3000);
+
+        // Buffered memory delays new connections.
+        second.release(100);
+        drop(first);
+        let _third = budget.reserve().await.unwrap();
+        assert!(budget.reserve().await.is_none());
+        assert_eq!(budget.usage().buffered_bytes(), 0);
+
+        assert!(MemoryAccount::default().charge(usize::MAX));
+    }
+
+    #[tokio::test]
+    async fn wait() {
+        let budget = MemoryBudget::new(limits(2000).policy(BudgetPolicy::Wait));
+        let account = budget.reserve().await.unwrap();
+        assert!(account.charge(200));
+
+        let waiting = tokio::spawn({
+            let budget = budget.clone();
+            async move { budget.reserve().await.is_some() }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(budget.usage().waiting(), 1);
+
+        // Releasing the buffered bytes is not enough.
+        account.release(200);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+
+        drop(account);
+        assert!(waiting.await.unwrap());
+        assert_eq!(budget.usage().waiting(), 0);
+        assert_eq!(budget.usage().shed(), 0);
+    }
+
+    /// Connects a client, returning the result of its session on the server.
+    async fn accept(
+        server: &Endpoint<crate::endpoint::Server>,
+        certificate: &crate::tls::Certificate,
+        timeouts: Timeouts,
+    ) -> Result<crate::Connection, ConnectionError> {
+        let client = Endpoint::client(test_utils::client_config(certificate).build()).unwrap();
+        let url = test_utils::url(server);
+        let connecting = tokio::spawn(async move {
+            let _connection = client.connect(url).await;
+        });
+
+        let result = match server.accept().await.with_timeouts(timeouts).await {
+            Ok(session_request) => session_request.accept().await,
+            Err(error) => Err(error),
+        };
+        connecting.abort();
+        result
+    }
+
+    #[tokio::test]
+    async fn exhausted() {
+        let certificate = test_utils::certificate();
+        let budget = MemoryBudget::new(limits(2000));
+        let server_config = test_utils::server_config(certificate.clone())
+            .memory_budget(budget.clone())
+            .build();
+        let server = Endpoint::server(server_config).unwrap();
+
+        let first = accept(&server, &certificate, Timeouts::default()).await;
+        assert!(first.is_ok());
+
+        let second = accept(&server, &certificate, Timeouts::default()).await;
+        assert!(matches!(second, Err(ConnectionError::MemoryBudgetExceeded)));
+        assert_eq!(budget.usage().shed(), 1);
+        assert_eq!(budget.usage().sessions(), 1);
+    }
+
+    #[tokio::test]
+    async fn wait_timed_out() {
+        let certificate = test_utils::certificate();
+        let budget = MemoryBudget::new(limits(2000).policy(BudgetPolicy::Wait));
+        let server_config = test_utils::server_config(certificate.clone())
+            .memory_budget(budget.clone())
+            .build();
+        let server = Endpoint::server(server_config).unwrap();
+        let timeouts = Timeouts::default().with_settings_exchange(Some(Duration::from_millis(100)));
+
+        let first = accept(&server, &certificate, timeouts).await;
+        assert!(first.is_ok());
+
+        // Still exhausted at the SETTINGS exchange timeout.
+        let second = accept(&server, &certificate, timeouts).await;
+        assert!(matches!(second, Err(ConnectionError::MemoryBudgetExceeded)));
+        assert_eq!(budget.usage().waiting(), 0);
+
+        // Admitted once released.
+        drop(first);
+        let third = accept(&server, &certificate, timeouts).await;
+        assert!(third.is_ok());
+    }
+
+    #[tokio::test]
+    async fn buffered_datagrams() {
+        let certificate = test_utils::certificate();
+        let budget = MemoryBudget::new(BudgetLimits::new(20000).session_cap(16000));
+        let client_config = test_utils::client_config(&certificate).build();
+        let server_config = test_utils::server_config(certificate)
+            .memory_budget(budget.clone())
+            .build();
+        let peers = test_utils::connect_with(server_config, client_config).await;
+
+        peers.client_connection.send_datagram([0; 150]).unwrap();
+        while budget.usage().buffered_bytes() == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(budget.usage().buffered_bytes(), 150);
+
+        let datagram = peers.server_connection.receive_datagram().await.unwrap();
+        assert_eq!(datagram.len(), 150);
+        assert_eq!(budget.usage().buffered_bytes(), 0);
+    }
+}
@@ -0,0 +1,61 @@
+use bytes::Bytes;
+use std::borrow::Cow;
+use wtransport_proto::capsule::Capsule as H3Capsule;
+use wtransport_proto::varint::VarInt;
+
+/// An application capsule exchanged on the session stream.
+///
+/// See [`Connection::session_stream`](crate::Connection::session_stream).
+#[derive(Clone, Debug)]
+pub struct Capsule {
+    capsule_type: VarInt,
+    payload: Bytes,
+}
+
+impl Capsule {
+    /// Creates a new capsule.
+    ///
+    /// Types registered for WebTransport (e.g., `CLOSE_WEBTRANSPORT_SESSION`) are
+    /// not interpreted by this crate: sending them has the effect defined by
+    /// the protocol on the peer.
+    #[inline(always)]
+    pub fn new<P>(capsule_type: VarInt, payload: P) -> Self
+    where
+        P: Into<Bytes>,
+    {
+        Self {
+            capsule_type,
+            payload: payload.into(),
+        }
+    }
+
+    /// Returns the capsule type.
+    #[inline(always)]
+    pub fn capsule_type(&self) -> VarInt {
+        self.capsule_type
+    }
+
+    /// Returns the capsule payload.
+    #[inline(always)]
+    pub fn payload(&self) -> Bytes {
+        self.payload.clone()
+    }
+
+    pub(crate) fn read(h3capsule: &H3Capsule) -> Self {
+        Self::new(
+            h3capsule.capsule_type(),
+            Bytes::copy_from_slice(h3capsule.payload()),
+        )
+    }
+
+    pub(crate) fn write(&self) -> Vec<u8> {
+        let h3capsule = H3Capsule::new(self.capsule_type, Cow::Borrowed(&self.payload));
+
+        let mut buffer = Vec::with_capacity(h3capsule.write_size());
+        h3capsule
+            .write(&mut buffer)
+            .expect("Vec has unbounded capacity");
+
+        buffer
+    }
+}
@@ -0,0 +1,335 @@
+use crate::driver::utils::Activity;
+use ring::rand::SecureRandom;
+use ring::rand::SystemRandom;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::debug;
+use wtransport_proto::ids::StreamId;
+use wtransport_proto::varint::VarInt;
+
+/// Failures injected into a live connection, for chaos testing.
+///
+/// Failures are injected locally, as if caused by the network or by a misbehaving peer,
+/// so that tests can check how both endpoints recover. See
+/// [`Connection::chaos`](crate::Connection::chaos).
+///
+/// The delay of the SETTINGS of a connection is configured on the endpoint instead (see
+/// [`ServerConfigBuilder::delay_settings`](crate::config::ServerConfigBuilder::delay_settings)
+/// and
+/// [`ClientConfigBuilder::delay_settings`](crate::config::ClientConfigBuilder::delay_settings)).
+#[derive(Clone)]
+pub struct Chaos {
+    quic_connection: quinn::Connection,
+    activity: Activity,
+}
+
+impl Chaos {
+    pub(crate) fn new(quic_connection: quinn::Connection, activity: Activity) -> Self {
+        Self {
+            quic_connection,
+            activity,
+        }
+    }
+
+    /// Drops the next `count` datagrams received on the session, before they are
+    /// delivered to the application.
+    ///
+    /// This replaces the count of datagrams still to be dropped.
+    pub fn drop_incoming_datagrams(&self, count: u64) {
+        self.activity
+            .chaos()
+            .drop_incoming
+            .store(count, Ordering::Relaxed);
+    }
+
+    /// Drops the next `count` datagrams sent on the session, as if lost on the network:
+    /// sending them succeeds, but they never reach the peer.
+    ///
+    /// This replaces the count of datagrams still to be dropped.
+    pub fn drop_outgoing_datagrams(&self, count: u64) {
+        self.activity
+            .chaos()
+            .drop_outgoing
+            .store(count, Ordering::Relaxed);
+    }
+
+    /// Resets a stream chosen at random among those in use by the application.
+    ///
+    /// The sending side of the stream is reset (`RESET_STREAM`), and its receiving side
+    /// stopped (`STOP_SENDING`), with `error_code`. The failure is applied on the next
+    /// read or write of the application on the stream, which then fails as if the peer
+    /// had reset or stopped it ([`StreamReadError::Reset`](crate::error::StreamReadError::Reset)
+    /// and [`StreamWriteError::Stopped`](crate::error::StreamWriteError::Stopped)).
+    ///
+    /// Returns the ID of the stream, or `None` if no stream is in use.
+    pub fn reset_random_stream(&self, error_code: VarInt) -> Option<StreamId> {
+        let streams = self.activity.streams_in_use();
+
+        if streams.is_empty() {
+            return None;
+        }
+
+        let mut random = [0; 8];
+        SystemRandom::new()
+            .fill(&mut random)
+            .expect("Random generation failed");
+        let stream = &streams[(u64::from_le_bytes(random) % streams.len() as u64) as usize];
+
+        debug!("Chaos: resetting stream {}", stream.id());
+        stream.inject_reset(error_code);
+
+        Some(stream.id())
+    }
+
+    /// Stalls the flow control for `duration`: the reads of the application on the
+    /// streams of the session are held, so that no more credit is granted to the peer,
+    /// which is blocked once it has sent what it was already allowed to.
+    ///
+    /// This replaces any stall in progress.
+    pub fn stall_flow_control(&self, duration: Duration) {
+        debug!("Chaos: stalling flow control for {:?}", duration);
+        *self.activity.chaos().lock_stall() = Some(Instant::now() + duration);
+    }
+}
+
+/// Failures pending on a connection.
+#[derive(Default)]
+pub(crate) struct ChaosState {
+    drop_incoming: AtomicU64,
+    drop_outgoing: AtomicU64,
+    stall: Mutex<Option<Instant>>,
+}
+
+impl ChaosState {
+    /// Returns whether the datagram received must be dropped.
+    #[inline(always)]
+    pub(crate) fn drops_incoming(&self) -> bool {
+        take(&self.drop_incoming)
+    }
+
+    /// Returns whether the datagram sent must be dropped.
+    #[inline(always)]
+    pub(crate) fn drops_outgoing(&self) -> bool {
+        take(&self.drop_outgoing)
+    }
+
+    /// Returns when the stall of the reads ends, if they are stalled.
+    pub(crate) fn stalled_until(&self) -> Option<Instant> {
+        let mut stall = self.lock_stall();
+        match *stall {
+            Some(deadline) if deadline > Instant::now() => Some(deadline),
+            _ => {
+                *stall = None;
+                None
+            }
+        }
+    }
+
+    fn lock_stall(&self) -> MutexGuard<'_, Option<Instant>> {
+        self.stall.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A failure injected into a stream, both halves of a bidirectional stream taking it.
+#[derive(Default)]
+pub(crate) struct StreamChaos {
+    reset: AtomicU64,
+    stop: AtomicU64,
+}
+
+impl StreamChaos {
+    pub(crate) fn inject(&self, error_code: VarInt) {
+        // Codes are stored plus one, zero meaning none.
+        let code = error_code.into_inner() + 1;
+        self.reset.store(code, Ordering::Relaxed);
+        self.stop.store(code, Ordering::Relaxed);
+    }
+
+    /// Returns the code the sending side must be reset with, if any.
+    #[inline(always)]
+    pub(crate) fn take_reset(&self) -> Option<VarInt> {
+        take_code(&self.reset)
+    }
+
+    /// Returns the code the receiving side must be stopped with, if any.
+    #[inline(always)]
+    pub(crate) fn take_stop(&self) -> Option<VarInt> {
+        take_code(&self.stop)
+    }
+}
+
+impl std::fmt::Debug for Chaos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Chaos")
+            .field("quic_id", &self.quic_connection.stable_id())
+            .finish()
+    }
+}
+
+/// Decrements `counter` unless zero, returning whether it was decremented.
+fn take(counter: &AtomicU64) -> bool {
+    counter
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+            count.checked_sub(1)
+        })
+        .is_ok()
+}
+
+fn take_code(code: &AtomicU64) -> Option<VarInt> {
+    match code.swap(0, Ordering::Relaxed) {
+        0 => None,
+        code => Some(VarInt::try_from_u64(code - 1).expect("Stored from a varint")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::StreamReadError;
+    use crate::error::StreamWriteError;
+    use crate::test_utils;
+    use std::time::Duration;
+    use tokio::io::AsyncReadExt;
+    use wtransport_proto::varint::VarInt;
+
+    #[tokio::test]
+    async fn drop_datagrams() {
+        let peers = test_utils::connect().await;
+
+        peers.server_connection.chaos().drop_incoming_datagrams(1);
+        peers.client_connection.chaos().drop_outgoing_datagrams(1);
+
+        // Lost on the network, then dropped on reception.
+        for payload in [b"one", b"two", b"tri"] {
+            peers.client_connection.send_datagram(payload).unwrap();
+        }
+
+        let datagram = peers.server_connection.receive_datagram().await.unwrap();
+        assert_eq!(datagram.payload(), &b"tri"[..]);
+    }
+
+    #[tokio::test]
+    async fn reset_random_stream() {
+        let peers = test_utils::connect().await;
+        let error_code = VarInt::from_u32(42);
+        assert!(peers
+            .client_connection
+            .chaos()
+            .reset_random_stream(error_code)
+            .is_none());
+
+        let (mut client_send, mut client_recv) = peers
+            .client_connection
+            .open_bi()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        client_send.write_all(b"hello").await.unwrap();
+        let (server_send, mut server_recv) = peers.server_connection.accept_bi().await.unwrap();
+        let mut data = [0; 5];
+        server_recv.read_exact(&mut data).await.unwrap();
+
+        assert_eq!(
+            peers
+                .client_connection
+                .chaos()
+                .reset_random_stream(error_code),
+            Some(client_send.id())
+        );
+
+        // Locally, as if reset and stopped by the peer.
+        assert!(matches!(
+            client_send.write_all(b"more").await,
+            Err(StreamWriteError::Stopped(code)) if code == error_code
+        ));
+        assert!(matches!(
+            client_recv.read(&mut data).await,
+            Err(StreamReadError::Reset(code)) if code == error_code
+        ));
+        assert_eq!(
+            client_recv.end(),
+            Some(crate::stream::StreamEnd::Reset(error_code))
+        );
+        assert_eq!(client_send.priority(), 0);
+
+        // The peer observes the reset and the stop.
+        assert!(matches!(
+            server_recv.read(&mut data).await,
+            Err(StreamReadError::Reset(code)) if code == error_code
+        ));
+        assert_eq!(server_send.stopped().await.stopped_code(), Some(error_code));
+    }
+
+    #[tokio::test]
+    async fn reset_random_stream_io() {
+        let peers = test_utils::connect().await;
+        let error_code = VarInt::from_u32(7);
+
+        let mut stream = peers
+            .client_connection
+            .open_uni()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        stream.write_all(b"hello").await.unwrap();
+        let mut server_recv = peers.server_connection.accept_uni().await.unwrap();
+
+        peers
+            .server_connection
+            .chaos()
+            .reset_random_stream(error_code)
+            .unwrap();
+
+        let error = server_recv.read_to_end(&mut Vec::new()).await.unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::ConnectionReset);
+        assert_eq!(
+            server_recv.end(),
+            Some(crate::stream::StreamEnd::Reset(error_code))
+        );
+    }
+
+    #[tokio::test]
+    async fn stall_flow_control() {
+        const STALL: Duration = Duration::from_millis(200);
+        const WINDOW: usize = 4096;
+
+        let certificate = test_utils::certificate();
+        let client_config = test_utils::client_config(&certificate).build();
+        let server_config = test_utils::server_config(certificate)
+            .stream_receive_window(WINDOW as u32)
+            .build();
+        let peers = test_utils::connect_with(server_config, client_config).await;
+
+        let mut client_send = peers
+            .client_connection
+            .open_uni()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        client_send.write_all(b"x").await.unwrap();
+        let mut server_recv = peers.server_connection.accept_uni().await.unwrap();
+
+        peers.server_connection.chaos().stall_flow_control(STALL);
+        let start = tokio::time::Instant::now();
+
+        // The writer is blocked once the stream window is filled, till the stall ends.
+        let writer = tokio::spawn(async move {
+            client_send.write_all(&[0; 4 * WINDOW]).await.unwrap();
+            client_send.finish().await.unwrap();
+            start.elapsed()
+        });
+
+        let mut data = Vec::new();
+        server_recv.read_to_end(&mut data).await.unwrap();
+        assert!(start.elapsed() >= STALL);
+        assert_eq!(data.len(), 1 + 4 * WINDOW);
+        assert!(writer.await.unwrap() >= STALL);
+    }
+}
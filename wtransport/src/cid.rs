@@ -0,0 +1,354 @@
+use quinn_proto::ConnectionId;
+use quinn_proto::ConnectionIdGenerator;
+use ring::rand::SecureRandom;
+use ring::rand::SystemRandom;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Invalid connection ID configuration.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum InvalidConnectionIdConfig {
+    /// The length is not in `1..=`[`ConnectionIdConfig::MAX_LENGTH`], or too short for the encoder.
+    #[error("invalid connection ID length: {0}")]
+    Length(usize),
+
+    /// The QUIC-LB config rotation is not in `0..=6`.
+    #[error("invalid config rotation: {0}")]
+    ConfigRotation(u8),
+
+    /// The QUIC-LB server ID is empty or longer than 15 bytes.
+    #[error("invalid server ID length: {0}")]
+    ServerIdLength(usize),
+}
+
+/// Encoding of routing information into server-chosen connection IDs.
+///
+/// This allows stateless load balancers to route the packets of a connection, even after
+/// a migration, as described by
+/// [QUIC-LB](https://datatracker.ietf.org/doc/draft-ietf-quic-load-balancers/).
+///
+/// The encoder is invoked on the endpoint I/O task: it MUST NOT block.
+pub trait ConnectionIdEncoder: Send + Sync + 'static {
+    /// Encodes routing information into `cid`.
+    ///
+    /// `cid` has the configured length, and it is initially filled with random bytes:
+    /// the bytes not written by the encoder are left random. Connection IDs MUST NOT allow
+    /// an external observer to correlate them with other connection IDs of the same connection.
+    fn encode(&self, cid: &mut [u8]);
+
+    /// Returns the minimum connection ID length required by this encoder.
+    fn min_length(&self) -> usize {
+        1
+    }
+}
+
+/// QUIC-LB *plaintext* connection IDs.
+///
+/// The first octet holds the config rotation bits and the length of the connection ID,
+/// followed by the server ID, then by a random nonce.
+///
+/// **Note**: the server ID is not encrypted, so only use this behind a trusted load balancer.
+#[derive(Clone, Debug)]
+pub struct QuicLbPlaintext {
+    config_rotation: u8,
+    server_id: Vec<u8>,
+}
+
+impl QuicLbPlaintext {
+    /// Minimum length of the nonce following the server ID.
+    pub const MIN_NONCE_LENGTH: usize = 4;
+
+    /// Creates a plaintext encoder with the given `config_rotation` (`0..=6`) and
+    /// `server_id` (from 1 to 15 bytes).
+    pub fn new(config_rotation: u8, server_id: &[u8]) -> Result<Self, InvalidConnectionIdConfig> {
+        if config_rotation > 6 {
+            return Err(InvalidConnectionIdConfig::ConfigRotation(config_rotation));
+        }
+
+        if server_id.is_empty() || server_id.len() > 15 {
+            return Err(InvalidConnectionIdConfig::ServerIdLength(server_id.len()));
+        }
+
+        Ok(Self {
+            config_rotation,
+            server_id: server_id.to_vec(),
+        })
+    }
+
+    /// Returns the config rotation.
+    #[inline(always)]
+    pub fn config_rotation(&self) -> u8 {
+        self.config_rotation
+    }
+
+    /// Returns the server ID.
+    #[inline(always)]
+    pub fn server_id(&self) -> &[u8] {
+        &self.server_id
+    }
+
+    /// Decodes a plaintext connection ID whose server ID is `server_id_length` bytes long,
+    /// as a load balancer would.
+    ///
+    /// Returns the config rotation and the server ID, or [`None`] if `cid` is too short or
+    /// uses the unroutable config rotation (`7`).
+    pub fn decode(cid: &[u8], server_id_length: usize) -> Option<(u8, &[u8])> {
+        if cid.len() < 1 + server_id_length + Self::MIN_NONCE_LENGTH {
+            return None;
+        }
+
+        let config_rotation = cid[0] >> 5;
+        if config_rotation == 7 {
+            return None;
+        }
+
+        Some((config_rotation, &cid[1..=server_id_length]))
+    }
+}
+
+impl ConnectionIdEncoder for QuicLbPlaintext {
+    fn encode(&self, cid: &mut [u8]) {
+        debug_assert!(cid.len() >= self.min_length());
+
+        cid[0] = (self.config_rotation << 5) | ((cid.len() - 1) as u8 & 0x1f);
+        cid[1..=self.server_id.len()].copy_from_slice(&self.server_id);
+    }
+
+    fn min_length(&self) -> usize {
+        1 + self.server_id.len() + Self::MIN_NONCE_LENGTH
+    }
+}
+
+/// Configuration of the connection IDs chosen by a server endpoint.
+///
+/// See [`ServerConfigBuilder::connection_ids`](crate::config::ServerConfigBuilder::connection_ids).
+#[derive(Clone)]
+pub struct ConnectionIdConfig {
+    length: usize,
+    lifetime: Option<Duration>,
+    encoder: Option<Arc<dyn ConnectionIdEncoder>>,
+}
+
+impl ConnectionIdConfig {
+    /// Maximum length of a QUIC connection ID.
+    pub const MAX_LENGTH: usize = 20;
+
+    /// Creates a configuration of random connection IDs of `length` bytes.
+    pub fn new(length: usize) -> Result<Self, InvalidConnectionIdConfig> {
+        if !(1..=Self::MAX_LENGTH).contains(&length) {
+            return Err(InvalidConnectionIdConfig::Length(length));
+        }
+
+        Ok(Self {
+            length,
+            lifetime: None,
+            encoder: None,
+        })
+    }
+
+    /// Retires connection IDs after `lifetime`, so that they get rotated.
+    ///
+    /// By default, connection IDs are not retired.
+    pub fn lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = Some(lifetime);
+        self
+    }
+
+    /// Sets an encoder of routing information into connection IDs.
+    ///
+    /// Returns an [`Err`] if the length is too short for the encoder.
+    pub fn encoder<E>(mut self, encoder: E) -> Result<Self, InvalidConnectionIdConfig>
+    where
+        E: ConnectionIdEncoder,
+    {
+        if self.length < encoder.min_length() {
+            return Err(InvalidConnectionIdConfig::Length(self.length));
+        }
+
+        self.encoder = Some(Arc::new(encoder));
+        Ok(self)
+    }
+
+    /// Returns the length of connection IDs.
+    #[inline(always)]
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    pub(crate) fn apply(self, endpoint_config: &mut quinn::EndpointConfig) {
+        endpoint_config.cid_generator(move || Box::new(Generator::new(self.clone())));
+    }
+}
+
+impl std::fmt::Debug for ConnectionIdConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConnectionIdConfig")
+            .field("length", &self.length)
+            .field("lifetime", &self.lifetime)
+            .field("encoder", &self.encoder.is_some())
+            .finish()
+    }
+}
+
+/// Connection ID generator of a [`ConnectionIdConfig`].
+struct Generator {
+    config: ConnectionIdConfig,
+    rng: SystemRandom,
+}
+
+impl Generator {
+    fn new(config: ConnectionIdConfig) -> Self {
+        Self {
+            config,
+            rng: SystemRandom::new(),
+        }
+    }
+}
+
+impl ConnectionIdGenerator for Generator {
+    fn generate_cid(&mut self) -> ConnectionId {
+        let mut cid = [0; ConnectionIdConfig::MAX_LENGTH];
+        let cid = &mut cid[..self.config.length];
+
+        self.rng.fill(cid).expect("System random available");
+
+        if let Some(encoder) = &self.config.encoder {
+            encoder.encode(cid);
+        }
+
+        ConnectionId::new(cid)
+    }
+
+    fn cid_len(&self) -> usize {
+        self.config.length
+    }
+
+    fn cid_lifetime(&self) -> Option<Duration> {
+        self.config.lifetime
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn quic_lb_plaintext() {
+        // (config rotation, server ID, nonce, connection ID). The first one is the
+        // unencrypted test vector of QUIC-LB, the others follow its layout: the first octet
+        // holds the config rotation bits and the length of the connection ID minus one.
+        let vectors = [
+            (0, "c4605e", "4504cc4f", "07c4605e4504cc4f"),
+            (1, "350d28b420", "3487d970", "29350d28b4203487d970"),
+            (2, "ab", "00112233445566", "48ab00112233445566"),
+            (
+                6,
+                "0102030405060708090a0b0c0d0e0f",
+                "a0a1a2a3",
+                "d30102030405060708090a0b0c0d0e0fa0a1a2a3",
+            ),
+        ];
+
+        for (config_rotation, server_id, nonce, expected) in vectors {
+            let server_id = from_hex(server_id);
+            let nonce = from_hex(nonce);
+            let expected = from_hex(expected);
+
+            let encoder = QuicLbPlaintext::new(config_rotation, &server_id).unwrap();
+            assert!(expected.len() >= encoder.min_length());
+
+            // The nonce is the random content left by the encoder.
+            let mut cid = vec![0xff; 1 + server_id.len()];
+            cid.extend_from_slice(&nonce);
+            encoder.encode(&mut cid);
+            assert_eq!(cid, expected);
+
+            assert_eq!(
+                QuicLbPlaintext::decode(&cid, server_id.len()),
+                Some((config_rotation, &server_id[..]))
+            );
+        }
+    }
+
+    #[test]
+    fn quic_lb_plaintext_decode() {
+        // Too short for the nonce.
+        assert_eq!(
+            QuicLbPlaintext::decode(&from_hex("06c4605e4504cc"), 3),
+            None
+        );
+
+        // Unroutable config rotation.
+        assert_eq!(
+            QuicLbPlaintext::decode(&from_hex("e7c4605e4504cc4f"), 3),
+            None
+        );
+    }
+
+    #[test]
+    fn invalid_configs() {
+        assert!(matches!(
+            QuicLbPlaintext::new(7, &[1]),
+            Err(InvalidConnectionIdConfig::ConfigRotation(7))
+        ));
+        assert!(matches!(
+            QuicLbPlaintext::new(0, &[]),
+            Err(InvalidConnectionIdConfig::ServerIdLength(0))
+        ));
+        assert!(matches!(
+            QuicLbPlaintext::new(0, &[0; 16]),
+            Err(InvalidConnectionIdConfig::ServerIdLength(16))
+        ));
+
+        for length in [0, ConnectionIdConfig::MAX_LENGTH + 1] {
+            assert!(matches!(
+                ConnectionIdConfig::new(length),
+                Err(InvalidConnectionIdConfig::Length(l)) if l == length
+            ));
+        }
+
+        let encoder = QuicLbPlaintext::new(0, &[1, 2, 3]).unwrap();
+        assert!(matches!(
+            ConnectionIdConfig::new(7).unwrap().encoder(encoder.clone()),
+            Err(InvalidConnectionIdConfig::Length(7))
+        ));
+        assert!(ConnectionIdConfig::new(8).unwrap().encoder(encoder).is_ok());
+    }
+
+    #[test]
+    fn generator() {
+        let encoder = QuicLbPlaintext::new(3, &[0xaa, 0xbb]).unwrap();
+        let config = ConnectionIdConfig::new(12)
+            .unwrap()
+            .lifetime(Duration::from_secs(60))
+            .encoder(encoder)
+            .unwrap();
+        let mut generator = Generator::new(config);
+
+        assert_eq!(generator.cid_len(), 12);
+        assert_eq!(generator.cid_lifetime(), Some(Duration::from_secs(60)));
+
+        let first = generator.generate_cid();
+        let second = generator.generate_cid();
+
+        for cid in [&first, &second] {
+            assert_eq!(cid.len(), 12);
+            assert_eq!(cid[0], (3 << 5) | 11);
+            assert_eq!(
+                QuicLbPlaintext::decode(cid, 2),
+                Some((3, &[0xaa, 0xbb][..]))
+            );
+        }
+
+        // Random nonces.
+        assert_ne!(first[3..], second[3..]);
+    }
+}
@@ -0,0 +1,18 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Encoding of typed payloads (e.g., JSON or CBOR).
+pub trait Codec {
+    /// Encoding or decoding error.
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// Encodes `value`.
+    fn encode<T>(value: &T) -> Result<Vec<u8>, Self::Error>
+    where
+        T: Serialize;
+
+    /// Decodes a value from `bytes`.
+    fn decode<T>(bytes: &[u8]) -> Result<T, Self::Error>
+    where
+        T: DeserializeOwned;
+}
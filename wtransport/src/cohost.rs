@@ -0,0 +1,313 @@
+use crate::alt_svc::AltSvc;
+use crate::driver::utils::Spawner;
+use crate::endpoint::IncomingSession;
+use crate::endpoint::Server;
+use crate::Endpoint;
+use crate::ServerConfig;
+use axum::response::Response;
+use axum::Router;
+use http::header::InvalidHeaderValue;
+use http::header::ALT_SVC;
+use http::HeaderValue;
+use hyper_util::rt::TokioIo;
+use hyper_util::server::conn::auto;
+use hyper_util::service::TowerToHyperService;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tracing::debug;
+
+/// ALPN protocols of the HTTPS server.
+const HTTPS_ALPN: [&[u8]; 2] = [b"h2", b"http/1.1"];
+
+/// A WebTransport endpoint co-hosted with an HTTPS server (HTTP/1.1 and HTTP/2, over TCP).
+///
+/// Both servers listen on the same port (UDP and TCP respectively) and present the
+/// certificate of the [`ServerConfig`]. Every HTTPS response advertises the
+/// WebTransport endpoint with an `Alt-Svc` header field, so that browsers can discover it.
+///
+/// # Example
+///
+/// ```no_run
+/// # use wtransport::cohost::CoHostedServer;
+/// # use wtransport::ServerConfig;
+/// # async fn run(config: ServerConfig) -> std::io::Result<()> {
+/// let router = axum::Router::new().route("/", axum::routing::get(|| async { "Hello" }));
+///
+/// CoHostedServer::bind(config)
+///     .await?
+///     .serve(router, |incoming_session| async move {
+///         if let Ok(session_request) = incoming_session.await {
+///             let _connection = session_request.accept().await;
+///         }
+///     })
+///     .await;
+/// # Ok(())
+/// # }
+/// ```
+pub struct CoHostedServer {
+    endpoint: Endpoint<Server>,
+    listener: TcpListener,
+    tls_acceptor: TlsAcceptor,
+    alt_svc: AltSvc,
+    alt_svc_value: HeaderValue,
+}
+
+impl CoHostedServer {
+    /// Binds the WebTransport endpoint, then the TCP listener on the same address and port.
+    ///
+    /// If the port of the bind address is `0`, the TCP listener uses the port picked for the
+    /// endpoint. The advertised alternative service is the port of the endpoint (see
+    /// [`ServerConfig::alt_svc`]).
+    pub async fn bind(config: ServerConfig) -> std::io::Result<Self> {
+        let mut tls_config = (*config.tls_config).clone();
+        tls_config.alpn_protocols = HTTPS_ALPN.iter().map(|alpn| alpn.to_vec()).collect();
+
+        let endpoint = Endpoint::server(config)?;
+        let address = endpoint.local_addr()?;
+        let listener = TcpListener::bind(address).await?;
+        let alt_svc = AltSvc::new(address.port());
+
+        Ok(Self {
+            endpoint,
+            listener,
+            tls_acceptor: TlsAcceptor::from(Arc::new(tls_config)),
+            alt_svc_value: HeaderValue::try_from(alt_svc.to_string())
+                .expect("Alt-Svc of a port is a valid header value"),
+            alt_svc,
+        })
+    }
+
+    /// Replaces the advertised alternative service (e.g., to set its max age, or the
+    /// public port when behind a port forwarding).
+    pub fn with_alt_svc(mut self, alt_svc: AltSvc) -> Result<Self, InvalidHeaderValue> {
+        self.alt_svc_value = HeaderValue::try_from(alt_svc.to_string())?;
+        self.alt_svc = alt_svc;
+        Ok(self)
+    }
+
+    /// Returns the WebTransport endpoint.
+    pub fn endpoint(&self) -> &Endpoint<Server> {
+        &self.endpoint
+    }
+
+    /// Returns the local address of the HTTPS server.
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Returns the advertised alternative service.
+    pub fn alt_svc(&self) -> &AltSvc {
+        &self.alt_svc
+    }
+
+    /// Runs both servers: `router` handles HTTPS requests, and every incoming session
+    /// is handed to `handler` on its own task.
+    ///
+    /// This future never completes.
+    pub async fn serve<H, F>(&self, router: Router, handler: H)
+    where
+        H: Fn(IncomingSession) -> F,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let sessions = async {
+            loop {
+                let incoming_session = self.endpoint.accept().await;
+                self.endpoint.spawner().spawn(handler(incoming_session));
+            }
+        };
+
+        tokio::join!(self.serve_https(router), sessions);
+    }
+
+    /// Runs the HTTPS server only, with `router` handling the requests.
+    ///
+    /// Sessions must be accepted on [`endpoint`](Self::endpoint). This future never completes.
+    pub async fn serve_https(&self, router: Router) {
+        let alt_svc = self.alt_svc_value.clone();
+        let router = router.layer(axum::middleware::map_response(
+            move |mut response: Response| {
+                let alt_svc = alt_svc.clone();
+                async move {
+                    response.headers_mut().insert(ALT_SVC, alt_svc);
+                    response
+                }
+            },
+        ));
+
+        let spawner = self.endpoint.spawner();
+
+        loop {
+            let (stream, remote_address) = match self.listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(error) => {
+                    // Likely out of file descriptors: give some time to release them.
+                    debug!("TCP accept failed: {}", error);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+
+            let tls_acceptor = self.tls_acceptor.clone();
+            let service = TowerToHyperService::new(router.clone());
+            let executor = SpawnerExecutor(spawner.clone());
+
+            spawner.spawn(async move {
+                let stream = match tls_acceptor.accept(stream).await {
+                    Ok(stream) => stream,
+                    Err(error) => {
+                        debug!("TLS handshake with {} failed: {}", remote_address, error);
+                        return;
+                    }
+                };
+
+                if let Err(error) = auto::Builder::new(executor)
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    debug!("HTTPS connection with {} failed: {}", remote_address, error);
+                }
+            });
+        }
+    }
+}
+
+/// Runs the tasks of HTTP/2 connections with the [`Spawner`] of the endpoint.
+#[derive(Clone)]
+struct SpawnerExecutor(Spawner);
+
+impl<F> hyper::rt::Executor<F> for SpawnerExecutor
+where
+    F: Future + Send + 'static,
+{
+    fn execute(&self, future: F) {
+        self.0.spawn(async move {
+            future.await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+    use axum::routing::get;
+    use tokio::io::AsyncReadExt;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpStream;
+    use tokio_rustls::TlsConnector;
+
+    async fn get_https(address: SocketAddr, certificate: &crate::tls::Certificate) -> String {
+        let mut roots = rustls::RootCertStore::empty();
+        for der in certificate.certificates_der() {
+            roots
+                .add(&rustls::Certificate(der.to_vec()))
+                .expect("Valid certificate");
+        }
+        let tls_config = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        let stream = TcpStream::connect(address).await.expect("TCP connection");
+        let mut stream = TlsConnector::from(Arc::new(tls_config))
+            .connect("localhost".try_into().expect("Valid name"), stream)
+            .await
+            .expect("TLS handshake");
+
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .expect("Request sent");
+
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .await
+            .expect("Response received");
+        response
+    }
+
+    #[tokio::test]
+    async fn cohosted() {
+        let certificate = test_utils::certificate();
+        let server = CoHostedServer::bind(test_utils::server_config(certificate.clone()).build())
+            .await
+            .expect("Bound server");
+        let address = server.local_addr().expect("Bound listener");
+        assert_eq!(server.endpoint().local_addr().unwrap(), address);
+
+        let router = Router::new().route("/", get(|| async { "hello" }));
+        let server = Arc::new(server);
+        let serving = server.clone();
+        let serving = tokio::spawn(async move {
+            serving
+                .serve(router, |incoming_session| async move {
+                    let connection = incoming_session
+                        .await
+                        .expect("Session request")
+                        .accept()
+                        .await
+                        .expect("Session accepted");
+                    connection.closed().await;
+                })
+                .await;
+        });
+
+        let response = get_https(address, &certificate).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        assert!(
+            response
+                .to_ascii_lowercase()
+                .contains(&format!("alt-svc: h3=\":{}\"", address.port())),
+            "{response}"
+        );
+        assert!(response.ends_with("hello"), "{response}");
+
+        let client = Endpoint::client(test_utils::client_config(&certificate).build())
+            .expect("Client endpoint");
+        client
+            .connect(test_utils::url(server.endpoint()))
+            .await
+            .expect("Session established");
+
+        serving.abort();
+    }
+
+    #[tokio::test]
+    async fn custom_alt_svc() {
+        let certificate = test_utils::certificate();
+        let server = CoHostedServer::bind(test_utils::server_config(certificate.clone()).build())
+            .await
+            .expect("Bound server")
+            .with_alt_svc(AltSvc::new(443).with_max_age(Duration::from_secs(60)))
+            .expect("Valid Alt-Svc");
+        let address = server.local_addr().expect("Bound listener");
+
+        let serving = tokio::spawn(async move {
+            server.serve_https(Router::new()).await;
+        });
+
+        let response = get_https(address, &certificate).await;
+        assert!(response.starts_with("HTTP/1.1 404"), "{response}");
+        assert!(
+            response
+                .to_ascii_lowercase()
+                .contains("alt-svc: h3=\":443\"; ma=60"),
+            "{response}"
+        );
+
+        assert!(
+            CoHostedServer::bind(test_utils::server_config(certificate).build())
+                .await
+                .expect("Bound server")
+                .with_alt_svc(AltSvc::new(443).with_host("local\nhost"))
+                .is_err()
+        );
+
+        serving.abort();
+    }
+}
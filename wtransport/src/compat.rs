@@ -0,0 +1,29 @@
+use crate::driver::utils::streamid_q2w;
+use crate::driver::utils::varint_q2w;
+use crate::driver::utils::varint_w2q;
+use wtransport_proto::ids::StreamId;
+use wtransport_proto::varint::VarInt;
+
+/// Converts a [`quinn::VarInt`] into a [`VarInt`].
+#[inline(always)]
+pub fn varint_from_quinn(varint: quinn::VarInt) -> VarInt {
+    varint_q2w(varint)
+}
+
+/// Converts a [`VarInt`] into a [`quinn::VarInt`].
+#[inline(always)]
+pub fn varint_to_quinn(varint: VarInt) -> quinn::VarInt {
+    varint_w2q(varint)
+}
+
+/// Converts a [`quinn::StreamId`] into a [`StreamId`].
+#[inline(always)]
+pub fn stream_id_from_quinn(stream_id: quinn::StreamId) -> StreamId {
+    streamid_q2w(stream_id)
+}
+
+/// Converts a [`StreamId`] into a [`quinn::StreamId`].
+#[inline(always)]
+pub fn stream_id_to_quinn(stream_id: StreamId) -> quinn::StreamId {
+    quinn::StreamId(stream_id.into_u64())
+}
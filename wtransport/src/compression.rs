@@ -0,0 +1,935 @@
+use crate::error::StreamReadError;
+use crate::error::StreamWriteError;
+use crate::RecvStream;
+use crate::SendStream;
+use brotli::enc::BrotliEncoderParams;
+use brotli::BrotliDecompressStream;
+use brotli::BrotliResult;
+use brotli::BrotliState;
+use brotli::CompressorWriter;
+use brotli::HeapAlloc;
+use brotli::HuffmanCode;
+use std::io::Write;
+use std::sync::Arc;
+
+/// Request header listing the codecs supported by the client, in order of preference.
+pub(crate) const ACCEPT_ENCODING_HEADER: &str = "wt-accept-encoding";
+
+/// Response header holding the codec selected by the server.
+pub(crate) const ENCODING_HEADER: &str = "wt-encoding";
+
+/// Size of the chunks of compressed data read from the stream.
+const READ_CHUNK_SIZE: usize = 4096;
+
+/// An error on a compressed stream.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum CompressionError {
+    /// The stream cannot be written.
+    #[error(transparent)]
+    Write(#[from] StreamWriteError),
+
+    /// The stream cannot be read.
+    #[error(transparent)]
+    Read(#[from] StreamReadError),
+
+    /// The decoded data exceed [`CompressionLimits::max_decoded_size`].
+    #[error("decoded size limit exceeded")]
+    LimitExceeded,
+
+    /// The codec failed (e.g., corrupted data).
+    #[error("codec error: {0}")]
+    Codec(#[source] std::io::Error),
+}
+
+/// A compression algorithm.
+///
+/// [`Brotli`] and [`Identity`] are bundled. Other algorithms (e.g., zstd) can be
+/// implemented by wrapping the compression library of the application's choice.
+pub trait Compression: Send + Sync + 'static {
+    /// Returns the name of the codec, negotiated in the session headers (e.g., `"br"`).
+    ///
+    /// It must be a non-empty string of printable ASCII characters, without spaces
+    /// nor commas.
+    fn name(&self) -> &str;
+
+    /// Creates an encoder for a new stream.
+    fn encoder(&self) -> Box<dyn Encoder>;
+
+    /// Creates a decoder for a new stream.
+    fn decoder(&self) -> Box<dyn Decoder>;
+}
+
+/// Streaming compression of a stream.
+pub trait Encoder: Send {
+    /// Compresses `input`, appending compressed data to `output`.
+    ///
+    /// The encoder may buffer data internally until [`flush`](Self::flush).
+    fn encode(&mut self, input: &[u8], output: &mut Vec<u8>) -> std::io::Result<()>;
+
+    /// Appends to `output` the compressed data needed to decode all input so far.
+    fn flush(&mut self, output: &mut Vec<u8>) -> std::io::Result<()>;
+
+    /// Ends the compression, appending the remaining compressed data to `output`.
+    fn finish(&mut self, output: &mut Vec<u8>) -> std::io::Result<()>;
+}
+
+/// Streaming decompression of a stream.
+pub trait Decoder: Send {
+    /// Decompresses `input`, appending at most `max_output` bytes to `output`.
+    ///
+    /// Returns the number of bytes of `input` consumed. Unconsumed input is passed again
+    /// on the next call. With an empty `input`, the decoder outputs the data it buffered,
+    /// if any.
+    fn decode(
+        &mut self,
+        input: &[u8],
+        output: &mut Vec<u8>,
+        max_output: usize,
+    ) -> std::io::Result<usize>;
+
+    /// Ends the decompression, once all input is consumed and all output is produced.
+    ///
+    /// Fails if the compressed data are truncated.
+    fn finish(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The identity codec (no compression).
+///
+/// It allows peers to negotiate uncompressed streams.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Identity;
+
+impl Compression for Identity {
+    fn name(&self) -> &str {
+        "identity"
+    }
+
+    fn encoder(&self) -> Box<dyn Encoder> {
+        Box::new(Identity)
+    }
+
+    fn decoder(&self) -> Box<dyn Decoder> {
+        Box::new(Identity)
+    }
+}
+
+impl Encoder for Identity {
+    fn encode(&mut self, input: &[u8], output: &mut Vec<u8>) -> std::io::Result<()> {
+        output.extend_from_slice(input);
+        Ok(())
+    }
+
+    fn flush(&mut self, _output: &mut Vec<u8>) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn finish(&mut self, _output: &mut Vec<u8>) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Decoder for Identity {
+    fn decode(
+        &mut self,
+        input: &[u8],
+        output: &mut Vec<u8>,
+        max_output: usize,
+    ) -> std::io::Result<usize> {
+        let len = input.len().min(max_output);
+        output.extend_from_slice(&input[..len]);
+        Ok(len)
+    }
+}
+
+/// The [Brotli](https://www.rfc-editor.org/rfc/rfc7932) codec, named `"br"`.
+#[derive(Copy, Clone, Debug)]
+pub struct Brotli {
+    quality: u32,
+    window_bits: u32,
+}
+
+impl Brotli {
+    /// Default compression quality.
+    pub const DEFAULT_QUALITY: u32 = 5;
+
+    /// Default base-2 logarithm of the sliding window size.
+    pub const DEFAULT_WINDOW_BITS: u32 = 22;
+
+    /// Creates a Brotli codec with the default parameters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the compression quality, from 0 (fastest) to 11 (smallest).
+    ///
+    /// By default, it is [`DEFAULT_QUALITY`](Self::DEFAULT_QUALITY).
+    pub fn with_quality(mut self, quality: u32) -> Self {
+        self.quality = quality.min(11);
+        self
+    }
+
+    /// Sets the base-2 logarithm of the sliding window size, from 10 to 24.
+    ///
+    /// Larger windows compress better, but the decoder of the peer may allocate up to
+    /// the window size. By default, it is [`DEFAULT_WINDOW_BITS`](Self::DEFAULT_WINDOW_BITS).
+    pub fn with_window_bits(mut self, window_bits: u32) -> Self {
+        self.window_bits = window_bits.clamp(10, 24);
+        self
+    }
+}
+
+impl Default for Brotli {
+    fn default() -> Self {
+        Self {
+            quality: Self::DEFAULT_QUALITY,
+            window_bits: Self::DEFAULT_WINDOW_BITS,
+        }
+    }
+}
+
+impl Compression for Brotli {
+    fn name(&self) -> &str {
+        "br"
+    }
+
+    fn encoder(&self) -> Box<dyn Encoder> {
+        let params = BrotliEncoderParams {
+            quality: self.quality as i32,
+            lgwin: self.window_bits as i32,
+            ..Default::default()
+        };
+
+        Box::new(BrotliEncoder(Some(CompressorWriter::with_params(
+            Vec::new(),
+            READ_CHUNK_SIZE,
+            &params,
+        ))))
+    }
+
+    fn decoder(&self) -> Box<dyn Decoder> {
+        Box::new(BrotliDecoder {
+            state: Box::new(BrotliState::new(
+                HeapAlloc::default(),
+                HeapAlloc::default(),
+                HeapAlloc::default(),
+            )),
+            done: false,
+        })
+    }
+}
+
+/// Brotli encoder, which is `None` once finished.
+struct BrotliEncoder(Option<CompressorWriter<Vec<u8>>>);
+
+impl BrotliEncoder {
+    fn writer(&mut self) -> std::io::Result<&mut CompressorWriter<Vec<u8>>> {
+        self.0
+            .as_mut()
+            .ok_or_else(|| std::io::Error::other("finished encoder"))
+    }
+}
+
+impl Encoder for BrotliEncoder {
+    fn encode(&mut self, input: &[u8], output: &mut Vec<u8>) -> std::io::Result<()> {
+        let writer = self.writer()?;
+        writer.write_all(input)?;
+        output.append(writer.get_mut());
+        Ok(())
+    }
+
+    fn flush(&mut self, output: &mut Vec<u8>) -> std::io::Result<()> {
+        let writer = self.writer()?;
+        writer.flush()?;
+        output.append(writer.get_mut());
+        Ok(())
+    }
+
+    fn finish(&mut self, output: &mut Vec<u8>) -> std::io::Result<()> {
+        if let Some(writer) = self.0.take() {
+            output.append(&mut writer.into_inner());
+        }
+
+        Ok(())
+    }
+}
+
+/// Brotli decoder.
+struct BrotliDecoder {
+    state: Box<BrotliState<HeapAlloc<u8>, HeapAlloc<u32>, HeapAlloc<HuffmanCode>>>,
+    done: bool,
+}
+
+impl Decoder for BrotliDecoder {
+    fn decode(
+        &mut self,
+        input: &[u8],
+        output: &mut Vec<u8>,
+        max_output: usize,
+    ) -> std::io::Result<usize> {
+        if self.done {
+            if input.is_empty() {
+                return Ok(0);
+            }
+
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "data after the end of the brotli stream",
+            ));
+        }
+
+        let start = output.len();
+        output.resize(start + max_output, 0);
+
+        let mut available_in = input.len();
+        let mut input_offset = 0;
+        let mut available_out = max_output;
+        let mut output_offset = start;
+        let mut total_out = 0;
+
+        let result = BrotliDecompressStream(
+            &mut available_in,
+            &mut input_offset,
+            input,
+            &mut available_out,
+            &mut output_offset,
+            output,
+            &mut total_out,
+            &mut self.state,
+        );
+
+        output.truncate(output_offset);
+
+        match result {
+            BrotliResult::ResultSuccess => {
+                self.done = true;
+                Ok(input_offset)
+            }
+            BrotliResult::NeedsMoreInput | BrotliResult::NeedsMoreOutput => Ok(input_offset),
+            BrotliResult::ResultFailure => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "invalid brotli stream",
+            )),
+        }
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        if self.done {
+            Ok(())
+        } else {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "truncated brotli stream",
+            ))
+        }
+    }
+}
+
+/// Codecs supported for a session, in order of preference.
+///
+/// A client offers its codecs in the session request (see
+/// [`ConnectOptions::compression`](crate::endpoint::ConnectOptions::compression)), then the
+/// server selects one (see
+/// [`SessionRequest::select_codec`](crate::endpoint::SessionRequest::select_codec)). The
+/// negotiated codec is available with
+/// [`Connection::codec`](crate::Connection::codec) on both sides.
+#[derive(Clone, Default)]
+pub struct Codecs(Vec<Arc<dyn Compression>>);
+
+impl Codecs {
+    /// Creates an empty set of codecs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `codec` with the lowest preference, replacing any codec of the same name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the codec [name](Compression::name) is not valid.
+    pub fn register<C>(mut self, codec: C) -> Self
+    where
+        C: Compression,
+    {
+        assert!(
+            is_valid_name(codec.name()),
+            "invalid codec name: {:?}",
+            codec.name()
+        );
+
+        self.0
+            .retain(|registered| registered.name() != codec.name());
+        self.0.push(Arc::new(codec));
+        self
+    }
+
+    /// Returns the codec named `name`.
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn Compression>> {
+        self.0.iter().find(|codec| codec.name() == name)
+    }
+
+    /// Returns whether no codec is registered.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the names of the codecs, in order of preference.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|codec| codec.name())
+    }
+
+    /// Returns the value of the header offering the codecs.
+    pub(crate) fn header_value(&self) -> String {
+        self.names().collect::<Vec<_>>().join(", ")
+    }
+
+    /// Selects the first codec of `offered` (a header value) which is registered.
+    pub(crate) fn select(&self, offered: &str) -> Option<&Arc<dyn Compression>> {
+        offered.split(',').find_map(|name| self.get(name.trim()))
+    }
+}
+
+impl std::fmt::Debug for Codecs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_graphic() && byte != b',')
+}
+
+/// Memory limits of a [`CompressedRecvStream`].
+#[derive(Copy, Clone, Debug)]
+pub struct CompressionLimits {
+    max_decoded_size: Option<u64>,
+    max_buffered: usize,
+}
+
+impl CompressionLimits {
+    /// Default maximum size of the decoded data buffered by a stream.
+    pub const DEFAULT_MAX_BUFFERED: usize = 64 * 1024;
+
+    /// Creates the default limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum total size of the decoded data of a stream.
+    ///
+    /// Once exceeded, reading fails with [`CompressionError::LimitExceeded`]. Unlimited by default.
+    pub fn max_decoded_size(mut self, value: u64) -> Self {
+        self.max_decoded_size = Some(value);
+        self
+    }
+
+    /// Sets the maximum size of the decoded data buffered by a stream (at least 1).
+    ///
+    /// This bounds the memory used by a stream whatever the compression ratio.
+    /// By default, it is [`DEFAULT_MAX_BUFFERED`](Self::DEFAULT_MAX_BUFFERED).
+    pub fn max_buffered(mut self, value: usize) -> Self {
+        self.max_buffered = value.max(1);
+        self
+    }
+}
+
+impl Default for CompressionLimits {
+    fn default() -> Self {
+        Self {
+            max_decoded_size: None,
+            max_buffered: Self::DEFAULT_MAX_BUFFERED,
+        }
+    }
+}
+
+/// A compressing wrapper of a [`SendStream`].
+///
+/// The peer must decompress the stream with the same codec, typically the one negotiated
+/// for the session (see [`Codecs`]).
+pub struct CompressedSendStream {
+    stream: SendStream,
+    encoder: Box<dyn Encoder>,
+    buffer: Vec<u8>,
+}
+
+impl CompressedSendStream {
+    /// Starts compressing `stream` with `codec`.
+    pub fn new(stream: SendStream, codec: &dyn Compression) -> Self {
+        Self {
+            stream,
+            encoder: codec.encoder(),
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Compresses and writes all of `data`.
+    ///
+    /// Data might be buffered by the encoder: see [`flush`](Self::flush).
+    pub async fn write_all(&mut self, data: &[u8]) -> Result<(), CompressionError> {
+        self.encoder
+            .encode(data, &mut self.buffer)
+            .map_err(CompressionError::Codec)?;
+        self.send_buffer().await
+    }
+
+    /// Writes all the data buffered by the encoder, so that the peer can decode it.
+    pub async fn flush(&mut self) -> Result<(), CompressionError> {
+        self.encoder
+            .flush(&mut self.buffer)
+            .map_err(CompressionError::Codec)?;
+        self.send_buffer().await
+    }
+
+    /// Ends the compression, then finishes the stream.
+    pub async fn finish(mut self) -> Result<(), CompressionError> {
+        self.encoder
+            .finish(&mut self.buffer)
+            .map_err(CompressionError::Codec)?;
+        self.send_buffer().await?;
+        self.stream.finish().await?;
+        Ok(())
+    }
+
+    /// Returns the underlying stream (e.g., for setting its priority).
+    #[inline(always)]
+    pub fn stream(&self) -> &SendStream {
+        &self.stream
+    }
+
+    async fn send_buffer(&mut self) -> Result<(), CompressionError> {
+        if !self.buffer.is_empty() {
+            self.stream.write_all(&self.buffer).await?;
+            self.buffer.clear();
+        }
+
+        Ok(())
+    }
+}
+
+/// A decompressing wrapper of a [`RecvStream`].
+pub struct CompressedRecvStream {
+    stream: RecvStream,
+    decoder: Box<dyn Decoder>,
+    limits: CompressionLimits,
+    input: Vec<u8>,
+    input_offset: usize,
+    output: Vec<u8>,
+    output_offset: usize,
+    decoded: u64,
+    finished: bool,
+}
+
+impl CompressedRecvStream {
+    /// Starts decompressing `stream` with `codec`.
+    pub fn new(stream: RecvStream, codec: &dyn Compression, limits: CompressionLimits) -> Self {
+        Self {
+            stream,
+            decoder: codec.decoder(),
+            limits,
+            input: Vec::new(),
+            input_offset: 0,
+            output: Vec::new(),
+            output_offset: 0,
+            decoded: 0,
+            finished: false,
+        }
+    }
+
+    /// Reads decompressed data into `buf`.
+    ///
+    /// Returns the number of bytes read, or `None` once the stream is finished and
+    /// all data are decoded.
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<Option<usize>, CompressionError> {
+        if buf.is_empty() {
+            return Ok(Some(0));
+        }
+
+        while self.output_offset == self.output.len() {
+            self.output.clear();
+            self.output_offset = 0;
+
+            if !self.decode()? {
+                if self.finished {
+                    if self.input_offset < self.input.len() {
+                        return Err(CompressionError::Codec(
+                            std::io::ErrorKind::UnexpectedEof.into(),
+                        ));
+                    }
+
+                    self.decoder.finish().map_err(CompressionError::Codec)?;
+                    return Ok(None);
+                }
+
+                self.fill_input().await?;
+            }
+        }
+
+        let len = buf.len().min(self.output.len() - self.output_offset);
+        buf[..len].copy_from_slice(&self.output[self.output_offset..self.output_offset + len]);
+        self.output_offset += len;
+
+        Ok(Some(len))
+    }
+
+    /// Returns the underlying stream.
+    #[inline(always)]
+    pub fn stream(&self) -> &RecvStream {
+        &self.stream
+    }
+
+    /// Decodes the pending input into the (empty) output buffer.
+    ///
+    /// Returns whether output was produced.
+    fn decode(&mut self) -> Result<bool, CompressionError> {
+        let input = &self.input[self.input_offset..];
+
+        let consumed = self
+            .decoder
+            .decode(input, &mut self.output, self.limits.max_buffered)
+            .map_err(CompressionError::Codec)?;
+        self.input_offset += consumed.min(input.len());
+
+        if self.input_offset == self.input.len() {
+            self.input.clear();
+            self.input_offset = 0;
+        }
+
+        self.decoded += self.output.len() as u64;
+
+        if matches!(self.limits.max_decoded_size, Some(max) if self.decoded > max) {
+            return Err(CompressionError::LimitExceeded);
+        }
+
+        Ok(!self.output.is_empty())
+    }
+
+    async fn fill_input(&mut self) -> Result<(), CompressionError> {
+        let mut chunk = [0; READ_CHUNK_SIZE];
+
+        match self.stream.read(&mut chunk).await? {
+            Some(read) => self.input.extend_from_slice(&chunk[..read]),
+            None => self.finished = true,
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::endpoint::ConnectOptions;
+    use crate::test_utils;
+    use crate::Endpoint;
+
+    fn telemetry() -> Vec<u8> {
+        (0..2000)
+            .flat_map(|i| {
+                format!("{{\"sensor\":\"temperature\",\"seq\":{i},\"ok\":true}}\n").into_bytes()
+            })
+            .collect()
+    }
+
+    fn encode(codec: &dyn Compression, data: &[u8]) -> Vec<u8> {
+        let mut encoder = codec.encoder();
+        let mut output = Vec::new();
+        let (first, second) = data.split_at(data.len() / 2);
+        encoder.encode(first, &mut output).unwrap();
+        encoder.flush(&mut output).unwrap();
+        encoder.encode(second, &mut output).unwrap();
+        encoder.finish(&mut output).unwrap();
+        output
+    }
+
+    /// Decodes `input` in chunks of `chunk_size`, with outputs of at most `max_output`.
+    fn decode(
+        codec: &dyn Compression,
+        input: &[u8],
+        chunk_size: usize,
+        max_output: usize,
+    ) -> std::io::Result<Vec<u8>> {
+        let mut decoder = codec.decoder();
+        let mut decoded = Vec::new();
+        let mut pending = Vec::new();
+
+        for chunk in input.chunks(chunk_size).chain([&[][..]]) {
+            pending.extend_from_slice(chunk);
+
+            loop {
+                let mut output = Vec::new();
+                let consumed = decoder.decode(&pending, &mut output, max_output)?;
+                assert!(output.len() <= max_output);
+                pending.drain(..consumed);
+                decoded.extend_from_slice(&output);
+
+                if output.is_empty() && (consumed == 0 || pending.is_empty()) {
+                    break;
+                }
+            }
+        }
+
+        decoder.finish()?;
+        Ok(decoded)
+    }
+
+    #[test]
+    fn brotli() {
+        let data = telemetry();
+
+        for codec in [
+            Brotli::new(),
+            Brotli::new().with_quality(11).with_window_bits(16),
+        ] {
+            let compressed = encode(&codec, &data);
+            assert!(compressed.len() * 5 < data.len());
+
+            for (chunk_size, max_output) in [(compressed.len(), 1 << 20), (7, 100)] {
+                assert_eq!(
+                    decode(&codec, &compressed, chunk_size, max_output).unwrap(),
+                    data
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn brotli_flush() {
+        let codec = Brotli::new();
+        let mut encoder = codec.encoder();
+        let mut decoder = codec.decoder();
+        let mut compressed = Vec::new();
+
+        // Flushed data are decodable before the end of the stream.
+        encoder.encode(b"hello", &mut compressed).unwrap();
+        encoder.flush(&mut compressed).unwrap();
+
+        let mut output = Vec::new();
+        let consumed = decoder.decode(&compressed, &mut output, 1024).unwrap();
+        assert_eq!(consumed, compressed.len());
+        assert_eq!(output, b"hello");
+        assert!(decoder.finish().is_err());
+    }
+
+    #[test]
+    fn brotli_invalid() {
+        let codec = Brotli::new();
+        let compressed = encode(&codec, &telemetry());
+
+        // Truncated.
+        let error = decode(&codec, &compressed[..compressed.len() - 1], 64, 1024).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+
+        // Corrupted.
+        let error = decode(&codec, &[0xff; 64], 64, 1024).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+        // Trailing data.
+        let mut trailing = compressed.clone();
+        trailing.push(0);
+        let error = decode(&codec, &trailing, trailing.len(), 1 << 20).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn identity() {
+        let data = telemetry();
+        let compressed = encode(&Identity, &data);
+        assert_eq!(compressed, data);
+        assert_eq!(decode(&Identity, &compressed, 100, 30).unwrap(), data);
+    }
+
+    #[test]
+    fn codecs() {
+        let codecs = Codecs::new()
+            .register(Identity)
+            .register(Brotli::new())
+            .register(Identity);
+
+        assert_eq!(codecs.names().collect::<Vec<_>>(), ["br", "identity"]);
+        assert_eq!(codecs.header_value(), "br, identity");
+        assert_eq!(format!("{codecs:?}"), r#"["br", "identity"]"#);
+
+        assert_eq!(
+            codecs.select("zstd, identity ,br").unwrap().name(),
+            "identity"
+        );
+        assert_eq!(codecs.select("br").unwrap().name(), "br");
+        assert!(codecs.select("zstd").is_none());
+        assert!(codecs.select("").is_none());
+        assert!(Codecs::new().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid codec name")]
+    fn invalid_codec_name() {
+        struct Invalid;
+
+        impl Compression for Invalid {
+            fn name(&self) -> &str {
+                "a,b"
+            }
+
+            fn encoder(&self) -> Box<dyn Encoder> {
+                Box::new(Identity)
+            }
+
+            fn decoder(&self) -> Box<dyn Decoder> {
+                Box::new(Identity)
+            }
+        }
+
+        let _ = Codecs::new().register(Invalid);
+    }
+
+    /// Establishes a session where the client offers `offered` and the server supports
+    /// `supported`.
+    async fn negotiate(offered: Codecs, supported: Codecs) -> test_utils::Peers {
+        let certificate = test_utils::certificate();
+        let server = Endpoint::server(test_utils::server_config(certificate.clone()).build())
+            .expect("Server endpoint");
+        let client = Endpoint::client(test_utils::client_config(&certificate).build())
+            .expect("Client endpoint");
+
+        let options = ConnectOptions::new().compression(offered);
+        let (server_connection, client_connection) = tokio::join!(
+            async {
+                let mut request = server.accept().await.await.expect("Session request");
+                let selected = request
+                    .select_codec(&supported)
+                    .map(|codec| codec.name().to_string());
+                let connection = request.accept().await.expect("Session accepted");
+                assert_eq!(
+                    connection.codec().map(|codec| codec.name().to_string()),
+                    selected
+                );
+                connection
+            },
+            async {
+                client
+                    .connect_with_options(test_utils::url(&server), &options)
+                    .await
+                    .expect("Session established")
+            },
+        );
+
+        test_utils::Peers {
+            server,
+            client,
+            server_connection,
+            client_connection,
+        }
+    }
+
+    fn codec_name(connection: &crate::Connection) -> Option<&str> {
+        connection.codec().map(|codec| codec.name())
+    }
+
+    #[tokio::test]
+    async fn negotiation() {
+        // The preference of the client wins.
+        let peers = negotiate(
+            Codecs::new().register(Brotli::new()).register(Identity),
+            Codecs::new().register(Identity).register(Brotli::new()),
+        )
+        .await;
+        assert_eq!(codec_name(&peers.server_connection), Some("br"));
+        assert_eq!(codec_name(&peers.client_connection), Some("br"));
+
+        // No codec in common.
+        let peers = negotiate(
+            Codecs::new().register(Identity),
+            Codecs::new().register(Brotli::new()),
+        )
+        .await;
+        assert_eq!(codec_name(&peers.server_connection), None);
+        assert_eq!(codec_name(&peers.client_connection), None);
+
+        // Nothing offered.
+        let peers = negotiate(Codecs::new(), Codecs::new().register(Brotli::new())).await;
+        assert_eq!(codec_name(&peers.server_connection), None);
+        assert_eq!(codec_name(&peers.client_connection), None);
+    }
+
+    #[tokio::test]
+    async fn compressed_streams() {
+        let codecs = Codecs::new().register(Brotli::new());
+        let peers = negotiate(codecs.clone(), codecs).await;
+        let data = telemetry();
+
+        let codec = peers.client_connection.codec().unwrap().clone();
+        let stream = peers
+            .client_connection
+            .open_uni()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        let mut stream = CompressedSendStream::new(stream, codec.as_ref());
+
+        let receiving = async {
+            let codec = peers.server_connection.codec().unwrap();
+            let stream = peers.server_connection.accept_uni().await.unwrap();
+            let mut stream = CompressedRecvStream::new(
+                stream,
+                codec.as_ref(),
+                CompressionLimits::new().max_buffered(1000),
+            );
+
+            let mut received = Vec::new();
+            let mut buf = [0; 4096];
+            while let Some(read) = stream.read(&mut buf).await.unwrap() {
+                assert!(read <= 1000);
+                received.extend_from_slice(&buf[..read]);
+            }
+            received
+        };
+
+        let sending = async {
+            stream.write_all(&data).await.unwrap();
+            stream.finish().await.unwrap();
+        };
+
+        let (received, ()) = tokio::join!(receiving, sending);
+        assert_eq!(received, data);
+    }
+
+    #[tokio::test]
+    async fn decoded_size_limit() {
+        let codecs = Codecs::new().register(Brotli::new());
+        let peers = negotiate(codecs.clone(), codecs).await;
+
+        let codec = peers.client_connection.codec().unwrap().clone();
+        let stream = peers
+            .client_connection
+            .open_uni()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        let mut stream = CompressedSendStream::new(stream, codec.as_ref());
+        stream.write_all(&vec![0; 1_000_000]).await.unwrap();
+        stream.finish().await.unwrap();
+
+        let stream = peers.server_connection.accept_uni().await.unwrap();
+        let mut stream = CompressedRecvStream::new(
+            stream,
+            codec.as_ref(),
+            CompressionLimits::new().max_decoded_size(100_000),
+        );
+
+        let mut buf = [0; 4096];
+        let error = loop {
+            match stream.read(&mut buf).await {
+                Ok(Some(_)) => {}
+                Ok(None) => panic!("limit not enforced"),
+                Err(error) => break error,
+            }
+        };
+        assert!(matches!(error, CompressionError::LimitExceeded));
+    }
+}
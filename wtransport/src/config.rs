@@ -1,21 +1,61 @@
+use crate::alt_svc::AltSvc;
+use crate::budget::MemoryBudget;
+use crate::cid::ConnectionIdConfig;
+use crate::connection::DRAFT_HEADER;
+use crate::dns::HttpsResolver;
+use crate::driver::utils::Spawner;
+use crate::driver::DriverConfig;
+use crate::endpoint::ExtendedConnectHandler;
+use crate::endpoint::SessionRequest;
+use crate::extension::Grease;
+use crate::extension::UnknownPolicy;
+#[cfg(feature = "masque")]
+use crate::masque::UdpProxy;
+#[cfg(feature = "masque")]
+use crate::masque::CONNECT_UDP_PROTOCOL;
+use crate::quota::Quotas;
+use crate::resumption::ResumptionTokens;
+use crate::socket::ExternalPacketHandler;
+use crate::socket::PacketTap;
 use crate::tls::Certificate;
+use crate::tls::ClientHello;
+use crate::tls::ClientHelloAction;
+use crate::tls::ClientHelloResolver;
+use crate::tls::ExternalKeyResolver;
+use crate::tls::SigningKey;
+use crate::tls_session::ClientCrypto;
+use crate::tls_session::ServerCrypto;
 use quinn::ClientConfig as QuicClientConfig;
 use quinn::ServerConfig as QuicServerConfig;
 use quinn::TransportConfig;
 use rustls::ClientConfig as TlsClientConfig;
 use rustls::RootCertStore;
 use rustls::ServerConfig as TlsServerConfig;
+use std::collections::HashMap;
+use std::future::Future;
 use std::net::IpAddr;
 use std::net::Ipv4Addr;
 use std::net::Ipv6Addr;
 use std::net::SocketAddr;
 use std::net::SocketAddrV6;
+#[cfg(feature = "serde")]
+use std::path::PathBuf;
+use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Duration;
+use wtransport_proto::headers::Headers;
+use wtransport_proto::headers::HeadersLimits;
+use wtransport_proto::ids::StatusCode;
+use wtransport_proto::session::SessionRequest as SessionRequestProto;
 use wtransport_proto::WEBTRANSPORT_ALPN;
 
 /// Configuration for IP address socket bind.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum IpBindConfig {
     /// Bind to LOCALHOST IPv4 address (no IPv6).
     LocalV4,
@@ -59,6 +99,11 @@ impl IpBindConfig {
 
 /// Configuration for IPv6 dual stack.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum Ipv6DualStackConfig {
     /// Do not configure dual stack. Use OS's default.
     OsDefault,
@@ -78,13 +123,434 @@ pub enum Ipv6DualStackConfig {
 #[derive(Debug)]
 pub struct InvalidIdleTimeout;
 
+/// QUIC protocol version.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum QuicVersion {
+    /// QUIC version 1 ([RFC 9000](https://www.rfc-editor.org/rfc/rfc9000)).
+    V1,
+
+    /// QUIC version 2 ([RFC 9369](https://www.rfc-editor.org/rfc/rfc9369)).
+    ///
+    /// **Note**: not supported yet by the underlying QUIC implementation.
+    V2,
+
+    /// QUIC IETF draft version (e.g., `29` for `draft-ietf-quic-transport-29`).
+    Draft(u8),
+}
+
+impl QuicVersion {
+    /// Returns `true` if the version can be used by the endpoint.
+    pub fn is_supported(self) -> bool {
+        quinn_proto::DEFAULT_SUPPORTED_VERSIONS.contains(&u32::from(self))
+    }
+}
+
+impl From<QuicVersion> for u32 {
+    fn from(version: QuicVersion) -> Self {
+        match version {
+            QuicVersion::V1 => 0x0000_0001,
+            QuicVersion::V2 => 0x6b33_43cf,
+            QuicVersion::Draft(draft) => 0xff00_0000 | u32::from(draft),
+        }
+    }
+}
+
+/// Congestion control algorithm.
+///
+/// # Pacing
+///
+/// Turning pacing off is not supported. The underlying QUIC implementation always paces
+/// packets, in bursts of about 2 milliseconds worth of congestion window (between 10 and
+/// 256 packets), and it offers no setting to disable it. Only the initial window,
+/// set along with the algorithm, shapes the first bursts.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum CongestionControl {
+    /// CUBIC ([RFC 8312](https://www.rfc-editor.org/rfc/rfc8312)), the default.
+    #[default]
+    Cubic,
+
+    /// NewReno ([RFC 9002](https://www.rfc-editor.org/rfc/rfc9002#section-7)).
+    NewReno,
+
+    /// BBR (experimental), more tolerant of random losses (e.g., cellular links).
+    Bbr,
+}
+
+impl CongestionControl {
+    fn apply(self, transport_config: &mut TransportConfig, initial_window: Option<u64>) {
+        match self {
+            CongestionControl::Cubic => {
+                let mut config = quinn::congestion::CubicConfig::default();
+                if let Some(initial_window) = initial_window {
+                    config.initial_window(initial_window);
+                }
+                transport_config.congestion_controller_factory(Arc::new(config));
+            }
+            CongestionControl::NewReno => {
+                let mut config = quinn::congestion::NewRenoConfig::default();
+                if let Some(initial_window) = initial_window {
+                    config.initial_window(initial_window);
+                }
+                transport_config.congestion_controller_factory(Arc::new(config));
+            }
+            CongestionControl::Bbr => {
+                let mut config = quinn::congestion::BbrConfig::default();
+                if let Some(initial_window) = initial_window {
+                    config.initial_window(initial_window);
+                }
+                transport_config.congestion_controller_factory(Arc::new(config));
+            }
+        }
+    }
+}
+
+/// A custom TLS configuration not suitable for WebTransport.
+///
+/// See [`ServerConfigBuilder::with_custom_tls`] and [`ClientConfigBuilder::with_custom_tls`].
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum InvalidTlsConfig {
+    /// TLS 1.3, required by QUIC, is not enabled.
+    #[error("TLS 1.3 is not enabled")]
+    MissingTls13,
+
+    /// The ALPN protocols do not include the WebTransport one (`h3`).
+    #[error("ALPN protocols do not include 'h3'")]
+    MissingAlpn,
+
+    /// The maximum size of early data is neither `0` nor `u32::MAX`, as required by QUIC.
+    #[error("invalid max early data size: {0}")]
+    MaxEarlyDataSize(u32),
+}
+
+/// Adds the WebTransport ALPN if `alpn_protocols` is empty, or checks it is present.
+fn validate_alpn(alpn_protocols: &mut Vec<Vec<u8>>) -> Result<(), InvalidTlsConfig> {
+    if alpn_protocols.is_empty() {
+        alpn_protocols.push(WEBTRANSPORT_ALPN.to_vec());
+    }
+
+    if alpn_protocols
+        .iter()
+        .any(|protocol| protocol == WEBTRANSPORT_ALPN)
+    {
+        Ok(())
+    } else {
+        Err(InvalidTlsConfig::MissingAlpn)
+    }
+}
+
+/// Invalid set of QUIC versions.
+///
+/// The set is empty, or it contains a version not supported by the
+/// underlying QUIC implementation (see [`QuicVersion::is_supported`]).
+#[derive(Debug)]
+pub struct UnsupportedQuicVersion;
+
+/// Timeouts of the phases of a connection.
+///
+/// Every timeout is disabled (`None`) by default. Phases exceeding their timeout fail
+/// with [`ConnectionError::TimedOut`](crate::error::ConnectionError::TimedOut), after
+/// closing the connection.
+///
+/// Timeouts are set for all connections with [`ServerConfigBuilder::timeouts`] and
+/// [`ClientConfigBuilder::timeouts`], and can be overridden for a single connection with
+/// [`IncomingSession::with_timeouts`](crate::endpoint::IncomingSession::with_timeouts) and
+/// [`ConnectOptions::timeouts`](crate::endpoint::ConnectOptions::timeouts).
+///
+/// # Example
+///
+/// ```
+/// # use std::time::Duration;
+/// # use wtransport::config::Timeouts;
+/// let timeouts = Timeouts::new()
+///     .with_handshake(Some(Duration::from_secs(5)))
+///     .with_session_request(Some(Duration::from_secs(10)));
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Timeouts {
+    /// Maximum duration of the QUIC (and TLS) handshake.
+    ///
+    /// On client side, it covers all the connection attempts
+    /// (see [`ClientConfigBuilder::max_connect_attempts`]).
+    pub handshake: Option<Duration>,
+
+    /// Maximum duration, after the handshake, to receive the HTTP3 SETTINGS of the peer.
+    pub settings_exchange: Option<Duration>,
+
+    /// Maximum duration, after the settings exchange, to receive the session request
+    /// (on server side) or the session response (on client side).
+    pub session_request: Option<Duration>,
+
+    /// Maximum duration of inactivity before timing out the connection
+    /// (see [`ServerConfigBuilder::max_idle_timeout`]).
+    ///
+    /// `None` keeps the idle timeout of the transport. It is only applied by the
+    /// configuration builders: per-connection overrides ignore it.
+    pub idle: Option<Duration>,
+
+    /// Maximum duration to wait for streams in use to complete in
+    /// [`Connection::shutdown`](crate::Connection::shutdown).
+    ///
+    /// `None` waits without limit.
+    pub drain: Option<Duration>,
+}
+
+impl Timeouts {
+    /// Creates timeouts, all disabled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the [`handshake`](Self::handshake) timeout.
+    pub fn with_handshake(mut self, timeout: Option<Duration>) -> Self {
+        self.handshake = timeout;
+        self
+    }
+
+    /// Sets the [`settings_exchange`](Self::settings_exchange) timeout.
+    pub fn with_settings_exchange(mut self, timeout: Option<Duration>) -> Self {
+        self.settings_exchange = timeout;
+        self
+    }
+
+    /// Sets the [`session_request`](Self::session_request) timeout.
+    pub fn with_session_request(mut self, timeout: Option<Duration>) -> Self {
+        self.session_request = timeout;
+        self
+    }
+
+    /// Sets the [`idle`](Self::idle) timeout.
+    pub fn with_idle(mut self, timeout: Option<Duration>) -> Self {
+        self.idle = timeout;
+        self
+    }
+
+    /// Sets the [`drain`](Self::drain) timeout.
+    pub fn with_drain(mut self, timeout: Option<Duration>) -> Self {
+        self.drain = timeout;
+        self
+    }
+}
+
+/// Validation of session (CONNECT) requests, applied by the server before delivering them
+/// to the application.
+///
+/// Requests failing validation are rejected with a distinct status code:
+/// * `421` (Misdirected Request) when `:authority` does not match the server name;
+/// * `414` (URI Too Long) when `:path` is longer than allowed;
+/// * `400` (Bad Request) when a required header field is missing.
+///
+/// Requests with a `:scheme` other than `https` are malformed: their stream is reset
+/// with `H3_MESSAGE_ERROR`.
+///
+/// See [`ServerConfigBuilder::request_validation`].
+#[derive(Clone, Debug)]
+pub struct RequestValidation {
+    pub(crate) strict_scheme: bool,
+    pub(crate) authority_matches_server_name: bool,
+    pub(crate) max_path_length: Option<usize>,
+    pub(crate) required_headers: Vec<String>,
+}
+
+impl RequestValidation {
+    /// Creates the default validation.
+    ///
+    /// Only the requirements of the protocol are enforced, with `:scheme` matched strictly.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether `:scheme` must be exactly `https` (default), or is compared
+    /// case-insensitively.
+    pub fn strict_scheme(mut self, strict: bool) -> Self {
+        self.strict_scheme = strict;
+        self
+    }
+
+    /// Requires the host of `:authority` to match the server name (SNI) of the TLS handshake.
+    ///
+    /// When the client sent no server name, the host must be an IP address.
+    pub fn authority_matches_server_name(mut self, enabled: bool) -> Self {
+        self.authority_matches_server_name = enabled;
+        self
+    }
+
+    /// Sets the maximum length (in bytes) of `:path`, including the query.
+    ///
+    /// By default, there is no limit besides the one on header fields.
+    pub fn max_path_length(mut self, max_path_length: usize) -> Self {
+        self.max_path_length = Some(max_path_length);
+        self
+    }
+
+    /// Requires a header field (e.g., `origin`) to be present in requests.
+    pub fn require_header<K>(mut self, key: K) -> Self
+    where
+        K: ToString,
+    {
+        self.required_headers
+            .push(key.to_string().to_ascii_lowercase());
+        self
+    }
+}
+
+impl RequestValidation {
+    /// Checks `request`, returning the status code rejecting it on failure.
+    pub(crate) fn check(
+        &self,
+        request: &SessionRequestProto,
+        server_name: Option<&str>,
+    ) -> Result<(), StatusCode> {
+        if self.authority_matches_server_name {
+            let host = authority_host(request.authority());
+
+            let matches = match server_name {
+                Some(server_name) => host
+                    .trim_end_matches('.')
+                    .eq_ignore_ascii_case(server_name.trim_end_matches('.')),
+                None => host.parse::<IpAddr>().is_ok(),
+            };
+
+            if !matches {
+                return Err(StatusCode::try_from(421u16).expect("Valid status code"));
+            }
+        }
+
+        if let Some(max_path_length) = self.max_path_length {
+            if request.path().len() > max_path_length {
+                return Err(StatusCode::try_from(414u16).expect("Valid status code"));
+            }
+        }
+
+        if self
+            .required_headers
+            .iter()
+            .any(|key| request.get(key).is_none())
+        {
+            return Err(StatusCode::try_from(400u16).expect("Valid status code"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the host of an `:authority` field, without brackets for IPv6 addresses.
+fn authority_host(authority: &str) -> &str {
+    match authority.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or(rest),
+        None => authority
+            .rsplit_once(':')
+            .map_or(authority, |(host, _port)| host),
+    }
+}
+
+impl Default for RequestValidation {
+    fn default() -> Self {
+        Self {
+            strict_scheme: true,
+            authority_matches_server_name: false,
+            max_path_length: None,
+            required_headers: Vec::new(),
+        }
+    }
+}
+
+/// Response served by the server to HTTP3 requests which are not session requests: a
+/// `GET` (or any other method), a classic `CONNECT` (without `:protocol`), or an extended
+/// `CONNECT` of a protocol other than `webtransport` (and not
+/// [handled](ServerConfigBuilder::extended_connect_handler)).
+///
+/// This helps diagnosing misconfigured clients, e.g., with a `426` (Upgrade Required) and a
+/// message telling the client to open a WebTransport session. Without a fallback response,
+/// the request stream is just rejected.
+///
+/// The connection is not affected: the peer can still send a session request.
+///
+/// See [`ServerConfigBuilder::fallback_response`].
+#[derive(Clone, Debug)]
+pub struct FallbackResponse {
+    pub(crate) headers: Headers,
+    pub(crate) body: Vec<u8>,
+}
+
+impl FallbackResponse {
+    /// Creates a response with `status_code`, without body.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `status_code` is not within `200..=599`.
+    pub fn new(status_code: u16) -> Self {
+        assert!(
+            (200..=599).contains(&status_code),
+            "Invalid fallback status code"
+        );
+
+        Self {
+            headers: [(":status", status_code.to_string())].into_iter().collect(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Adds a header field to the response.
+    ///
+    /// If the key is already present, the value is updated. Pseudo-header fields
+    /// (e.g., `:status`) are ignored.
+    pub fn header<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: ToString,
+        V: ToString,
+    {
+        let key = key.to_string();
+        if !key.starts_with(':') {
+            self.headers.insert(key, value);
+        }
+        self
+    }
+
+    /// Sets the body of the response.
+    pub fn body<B>(mut self, body: B) -> Self
+    where
+        B: Into<Vec<u8>>,
+    {
+        self.body = body.into();
+        self
+    }
+
+    /// Sets a plain text body (e.g., instructions), with its `content-type`.
+    pub fn text<T>(self, text: T) -> Self
+    where
+        T: ToString,
+    {
+        self.header("content-type", "text/plain; charset=utf-8")
+            .body(text.to_string())
+    }
+}
+
 /// Server configuration.
 ///
 /// Configuration can be created via [`ServerConfig::builder`] function.
 pub struct ServerConfig {
     pub(crate) bind_address: SocketAddr,
     pub(crate) dual_stack_config: Ipv6DualStackConfig,
+    pub(crate) additional_bind_addresses: Vec<SocketAddr>,
     pub(crate) quic_config: QuicServerConfig,
+    pub(crate) endpoint_config: quinn::EndpointConfig,
+    pub(crate) quic_version: Option<QuicVersion>,
+    pub(crate) driver_config: DriverConfig,
+    pub(crate) max_concurrent_setups: Option<usize>,
+    pub(crate) response_headers: Headers,
+    pub(crate) request_validation: RequestValidation,
+    pub(crate) timeouts: Timeouts,
+    pub(crate) connections_registry: bool,
+    pub(crate) external_packet_handler: Option<Arc<dyn ExternalPacketHandler>>,
+    pub(crate) packet_tap: Option<Arc<dyn PacketTap>>,
+    pub(crate) resumption_tokens: Option<ResumptionTokens>,
+    pub(crate) quotas: Option<Quotas>,
+    pub(crate) memory_budget: Option<MemoryBudget>,
+    pub(crate) extended_connect_handlers: HashMap<String, ExtendedConnectHandler>,
+    #[cfg(feature = "axum")]
+    pub(crate) tls_config: Arc<TlsServerConfig>,
 }
 
 impl ServerConfig {
@@ -94,6 +560,32 @@ impl ServerConfig {
     pub fn builder() -> ServerConfigBuilder<WantsBindAddress> {
         ServerConfigBuilder::default()
     }
+
+    /// Returns the alternative service advertising this server on its bind port.
+    ///
+    /// Its representation is the value of the `Alt-Svc` header field HTTPS servers send
+    /// to let browsers discover the WebTransport endpoint (e.g., `h3=":4433"`).
+    pub fn alt_svc(&self) -> AltSvc {
+        AltSvc::new(self.bind_address.port())
+    }
+
+    /// Returns the effective QUIC configuration.
+    ///
+    /// See the [`quinn-compat` stability notes](crate::config#quinn-compat-feature).
+    #[cfg(feature = "quinn-compat")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "quinn-compat")))]
+    pub fn quic_config(&self) -> &QuicServerConfig {
+        &self.quic_config
+    }
+
+    /// Returns the effective QUIC endpoint configuration.
+    ///
+    /// See the [`quinn-compat` stability notes](crate::config#quinn-compat-feature).
+    #[cfg(feature = "quinn-compat")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "quinn-compat")))]
+    pub fn quic_endpoint_config(&self) -> &quinn::EndpointConfig {
+        &self.endpoint_config
+    }
 }
 
 /// Server builder configuration.
@@ -163,6 +655,11 @@ impl ServerConfigBuilder<WantsBindAddress> {
 }
 
 impl ServerConfigBuilder<WantsCertificate> {
+    const DEFAULT_MAX_CONCURRENT_CONNECTIONS: u32 = 10_000;
+    const DEFAULT_RETRY_TOKEN_LIFETIME: Duration = Duration::from_secs(15);
+    const DEFAULT_MAX_HANDSHAKE_BUFFER_SIZE: usize = 16 * 1024;
+    const DEFAULT_MAX_CONCURRENT_SETUPS: usize = 64;
+
     /// Sets the TLS certificate the server will present to incoming
     /// WebTransport connections.
     pub fn with_certificate(
@@ -170,17 +667,106 @@ impl ServerConfigBuilder<WantsCertificate> {
         certificate: Certificate,
     ) -> ServerConfigBuilder<WantsTransportConfigServer> {
         let tls_config = Self::build_tls_config(certificate);
-        let transport_config = TransportConfig::default();
+        self.with_tls_config(tls_config)
+    }
+
+    /// Sets the TLS certificate the server will present, whose private key is held by
+    /// `signing_key` (e.g., in an HSM or a cloud KMS) rather than loaded in memory.
+    ///
+    /// `certificates` is a chain where each certificate-data must be *DER-encoded* *X.509*.
+    pub fn with_signing_key<K>(
+        self,
+        certificates: Vec<Vec<u8>>,
+        signing_key: K,
+    ) -> ServerConfigBuilder<WantsTransportConfigServer>
+    where
+        K: SigningKey,
+    {
+        let mut tls_config = TlsServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(ExternalKeyResolver::new(
+                certificates,
+                Arc::new(signing_key),
+            )));
+
+        tls_config.alpn_protocols = [WEBTRANSPORT_ALPN.to_vec()].to_vec();
+
+        self.with_tls_config(tls_config)
+    }
+
+    /// Uses a custom TLS configuration (e.g., with a client certificate verifier
+    /// or a certificate resolver signing with a hardware key).
+    ///
+    /// The configuration is validated for QUIC: TLS 1.3 must be enabled, and the maximum size
+    /// of early data must be either `0` or `u32::MAX`. ALPN protocols must include the
+    /// WebTransport one (`h3`); if none is set, it is added.
+    ///
+    /// **Note**: the [`rustls`] version must be the one this crate depends on
+    /// (re-exported as [`tls::rustls`](crate::tls::rustls)).
+    pub fn with_custom_tls(
+        self,
+        mut tls_config: TlsServerConfig,
+    ) -> Result<ServerConfigBuilder<WantsTransportConfigServer>, InvalidTlsConfig> {
+        if tls_config.max_early_data_size != 0 && tls_config.max_early_data_size != u32::MAX {
+            return Err(InvalidTlsConfig::MaxEarlyDataSize(
+                tls_config.max_early_data_size,
+            ));
+        }
+
+        // The only other requirement checked by rustls is TLS 1.3 support.
+        rustls::quic::ServerConnection::new(
+            Arc::new(tls_config.clone()),
+            rustls::quic::Version::V1,
+            Vec::new(),
+        )
+        .map_err(|_| InvalidTlsConfig::MissingTls13)?;
+
+        validate_alpn(&mut tls_config.alpn_protocols)?;
+
+        Ok(self.with_tls_config(tls_config))
+    }
+
+    fn with_tls_config(
+        self,
+        tls_config: TlsServerConfig,
+    ) -> ServerConfigBuilder<WantsTransportConfigServer> {
+        let mut transport_config = TransportConfig::default();
+        transport_config.crypto_buffer_size(Self::DEFAULT_MAX_HANDSHAKE_BUFFER_SIZE);
 
         ServerConfigBuilder(WantsTransportConfigServer {
             bind_address: self.0.bind_address,
             dual_stack_config: self.0.dual_stack_config,
+            additional_bind_addresses: Vec::new(),
             tls_config,
             transport_config,
+            endpoint_config: quinn::EndpointConfig::default(),
+            quic_versions: None,
+            quic_config: None,
             migration: true,
+            max_concurrent_connections: Self::DEFAULT_MAX_CONCURRENT_CONNECTIONS,
+            use_retry: false,
+            retry_token_lifetime: Self::DEFAULT_RETRY_TOKEN_LIFETIME,
+            driver_config: DriverConfig::default(),
+            max_concurrent_setups: Some(Self::DEFAULT_MAX_CONCURRENT_SETUPS),
+            response_headers: Self::default_response_headers(),
+            request_validation: RequestValidation::default(),
+            timeouts: Timeouts::default(),
+            connections_registry: false,
+            external_packet_handler: None,
+            packet_tap: None,
+            resumption_tokens: None,
+            quotas: None,
+            memory_budget: None,
+            extended_connect_handlers: HashMap::new(),
         })
     }
 
+    fn default_response_headers() -> Headers {
+        // Chrome support
+        [(DRAFT_HEADER, "draft02")].into_iter().collect()
+    }
+
     fn build_tls_config(certificate: Certificate) -> TlsServerConfig {
         let mut tls_config = TlsServerConfig::builder()
             .with_safe_defaults()
@@ -197,56 +783,617 @@ impl ServerConfigBuilder<WantsCertificate> {
 impl ServerConfigBuilder<WantsTransportConfigServer> {
     /// Completes configuration process.
     pub fn build(self) -> ServerConfig {
-        let mut quic_config = QuicServerConfig::with_crypto(Arc::new(self.0.tls_config));
-        quic_config.transport_config(Arc::new(self.0.transport_config));
-        quic_config.migration(self.0.migration);
+        let tls_config = Arc::new(self.0.tls_config);
+
+        let quic_config = match self.0.quic_config {
+            Some(quic_config) => quic_config,
+            None => {
+                let mut transport_config = self.0.transport_config;
+                if let Some(memory_budget) = &self.0.memory_budget {
+                    memory_budget.limits().apply(&mut transport_config);
+                }
+
+                let mut quic_config =
+                    QuicServerConfig::with_crypto(Arc::new(ServerCrypto(tls_config.clone())));
+                quic_config.transport_config(Arc::new(transport_config));
+                quic_config.migration(self.0.migration);
+                quic_config.concurrent_connections(self.0.max_concurrent_connections);
+                quic_config.use_retry(self.0.use_retry);
+                quic_config.retry_token_lifetime(self.0.retry_token_lifetime);
+                quic_config
+            }
+        };
+
+        let mut endpoint_config = self.0.endpoint_config;
+        if self.0.external_packet_handler.is_some() {
+            endpoint_config.grease_quic_bit(false);
+        }
+
+        let quic_version = match self.0.quic_versions {
+            Some(versions) => {
+                endpoint_config
+                    .supported_versions(versions.iter().copied().map(u32::from).collect());
+                (versions.len() == 1).then(|| versions[0])
+            }
+            None => None,
+        };
+
+        let mut driver_config = self.0.driver_config;
+        driver_config.extended_connect_protocols =
+            self.0.extended_connect_handlers.keys().cloned().collect();
 
         ServerConfig {
             bind_address: self.0.bind_address,
             dual_stack_config: self.0.dual_stack_config,
+            additional_bind_addresses: self.0.additional_bind_addresses,
             quic_config,
+            endpoint_config,
+            quic_version,
+            driver_config,
+            max_concurrent_setups: self.0.max_concurrent_setups,
+            response_headers: self.0.response_headers,
+            request_validation: self.0.request_validation,
+            timeouts: self.0.timeouts,
+            connections_registry: self.0.connections_registry,
+            external_packet_handler: self.0.external_packet_handler,
+            packet_tap: self.0.packet_tap,
+            resumption_tokens: self.0.resumption_tokens,
+            quotas: self.0.quotas,
+            memory_budget: self.0.memory_budget,
+            extended_connect_handlers: self.0.extended_connect_handlers,
+            #[cfg(feature = "axum")]
+            tls_config,
         }
     }
 
     /// Maximum duration of inactivity to accept before timing out the connection.
     ///
-    /// The true idle timeout is the minimum of this and the peer's own max idle timeout. `None`
-    /// represents an infinite timeout.
+    /// The true idle timeout is the minimum of this and the peer's own max idle timeout. `None`
+    /// represents an infinite timeout.
+    ///
+    /// **WARNING**: If a peer or its network path malfunctions or acts maliciously, an infinite
+    /// idle timeout can result in permanently hung futures!
+    pub fn max_idle_timeout(
+        mut self,
+        idle_timeout: Option<Duration>,
+    ) -> Result<Self, InvalidIdleTimeout> {
+        let idle_timeout = idle_timeout
+            .map(quinn::IdleTimeout::try_from)
+            .transpose()
+            .map_err(|_| InvalidIdleTimeout)?;
+
+        self.0.transport_config.max_idle_timeout(idle_timeout);
+
+        Ok(self)
+    }
+
+    /// Sets the timeouts of the phases of connections.
+    ///
+    /// If set, the [`idle`](Timeouts::idle) timeout replaces the one set with
+    /// [`max_idle_timeout`](Self::max_idle_timeout).
+    /// See [`Timeouts`].
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Result<Self, InvalidIdleTimeout> {
+        if timeouts.idle.is_some() {
+            self = self.max_idle_timeout(timeouts.idle)?;
+        }
+
+        self.0.timeouts = timeouts;
+        Ok(self)
+    }
+
+    /// Period of inactivity before sending a keep-alive packet
+    ///
+    /// Keep-alive packets prevent an inactive but otherwise healthy connection from timing out.
+    ///
+    /// `None` to disable, which is the default. Only one side of any given connection needs keep-alive
+    /// enabled for the connection to be preserved. Must be set lower than the idle_timeout of both
+    /// peers to be effective.
+    pub fn keep_alive_interval(mut self, interval: Option<Duration>) -> Self {
+        self.0.transport_config.keep_alive_interval(interval);
+        self
+    }
+
+    /// Whether to allow clients to migrate to new addresses.
+    ///
+    /// Improves behavior for clients that move between different internet connections or suffer NAT
+    /// rebinding. Enabled by default.
+    pub fn allow_migration(mut self, value: bool) -> Self {
+        self.0.migration = value;
+        self
+    }
+
+    /// Sets the congestion control algorithm and its initial window, in bytes.
+    ///
+    /// `None` keeps the default initial window (of about 14 KB, see
+    /// [RFC 9002](https://www.rfc-editor.org/rfc/rfc9002#section-7.2)). Larger values
+    /// speed up the first round-trips on high bandwidth-delay links.
+    ///
+    /// Pacing cannot be turned off (see [pacing](CongestionControl#pacing)).
+    pub fn congestion_control(
+        mut self,
+        algorithm: CongestionControl,
+        initial_window: Option<u64>,
+    ) -> Self {
+        algorithm.apply(&mut self.0.transport_config, initial_window);
+        self
+    }
+
+    /// Sets the RTT assumed before any measurement. Default is 333 milliseconds.
+    pub fn initial_rtt(mut self, value: Duration) -> Self {
+        self.0.transport_config.initial_rtt(value);
+        self
+    }
+
+    /// Maximum number of unacknowledged bytes buffered, per connection, for sending to the peer.
+    ///
+    /// It bounds the data in flight, whatever the congestion window.
+    /// Default is 10 MB: it should be raised for links with a large bandwidth-delay product.
+    pub fn send_window(mut self, value: u64) -> Self {
+        self.0.transport_config.send_window(value);
+        self
+    }
+
+    /// Maximum number of bytes the peer may send on a stream, before it is read
+    /// by the application (i.e., the initial flow control window of each stream).
+    ///
+    /// The window applies to all the streams, whatever their type (see
+    /// [receive windows](crate::config#receive-windows)).
+    ///
+    /// Default is 1.25 MB.
+    pub fn stream_receive_window(mut self, value: u32) -> Self {
+        self.0
+            .transport_config
+            .stream_receive_window(quinn::VarInt::from_u32(value));
+        self
+    }
+
+    /// Maximum number of bytes the peer may send on all the streams of a connection,
+    /// before they are read by the application.
+    ///
+    /// It should be at least a few times the
+    /// [`stream_receive_window`](Self::stream_receive_window), so that the unread data of
+    /// a few streams leaves credit for the others, including the control streams.
+    /// Default is unlimited: each stream is only bounded by its own window.
+    pub fn receive_window(mut self, value: u64) -> Self {
+        let value = quinn::VarInt::from_u64(value).unwrap_or(quinn::VarInt::MAX);
+        self.0.transport_config.receive_window(value);
+        self
+    }
+
+    /// Sets the QUIC versions supported by the endpoint, in order of preference.
+    ///
+    /// By default, [`QuicVersion::V1`] and the most recent drafts are supported.
+    pub fn quic_versions(
+        mut self,
+        versions: &[QuicVersion],
+    ) -> Result<Self, UnsupportedQuicVersion> {
+        if versions.is_empty() || !versions.iter().all(|version| version.is_supported()) {
+            return Err(UnsupportedQuicVersion);
+        }
+
+        self.0.quic_versions = Some(versions.to_vec());
+        Ok(self)
+    }
+
+    /// Whether to randomize the QUIC *fixed bit* (a.k.a. greasing,
+    /// see [RFC 9287](https://www.rfc-editor.org/rfc/rfc9287)) when the peer supports it.
+    ///
+    /// Enabled by default, unless an [external packet handler](Self::external_packet_handler)
+    /// is set: the fixed bit then tells QUIC packets apart, hence peers are not allowed to
+    /// grease it.
+    pub fn grease_quic_bit(mut self, value: bool) -> Self {
+        self.0.endpoint_config.grease_quic_bit(value);
+        self
+    }
+
+    /// Sets the connection IDs chosen by the server.
+    ///
+    /// This allows, for example, embedding routing information for stateless load
+    /// balancers, so that packets still reach this server after a client migration.
+    /// See [`ConnectionIdConfig`].
+    ///
+    /// By default, connection IDs are random, of 8 bytes.
+    pub fn connection_ids(mut self, config: ConnectionIdConfig) -> Self {
+        config.apply(&mut self.0.endpoint_config);
+        self
+    }
+
+    /// Maximum number of simultaneous connections (including the ones still in handshake).
+    ///
+    /// New incoming connections are refused once this limit is reached.
+    /// Default is `10_000`.
+    pub fn max_concurrent_connections(mut self, value: u32) -> Self {
+        self.0.max_concurrent_connections = value;
+        self
+    }
+
+    /// Whether to require clients to prove ownership of their address before
+    /// committing any resource (i.e., QUIC *Retry*).
+    ///
+    /// Introduces an additional round-trip to the handshake, but it strongly limits
+    /// amplification and spoofed-address attacks. Disabled by default.
+    pub fn use_retry(mut self, value: bool) -> Self {
+        self.0.use_retry = value;
+        self
+    }
+
+    /// Duration after a retry token was issued for which it's considered valid.
+    ///
+    /// Only meaningful when [`use_retry`](Self::use_retry) is enabled. Default is 15 seconds.
+    pub fn retry_token_lifetime(mut self, value: Duration) -> Self {
+        self.0.retry_token_lifetime = value;
+        self
+    }
+
+    /// Maximum quantity of out-of-order handshake (crypto) data to buffer per connection.
+    ///
+    /// This bounds the memory a single handshake can consume. Default is 16 KiB.
+    pub fn max_handshake_buffer_size(mut self, value: usize) -> Self {
+        self.0.transport_config.crypto_buffer_size(value);
+        self
+    }
+
+    /// Sets a hook invoked with the TLS `ClientHello` of each handshake.
+    ///
+    /// The hook runs before the certificate selection: it can abort the handshake (e.g.,
+    /// blocking scanners or unknown tenants by SNI) or present another certificate.
+    /// It is ignored if the QUIC configuration is replaced with `with_quic_config`.
+    pub fn on_client_hello<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&ClientHello) -> ClientHelloAction + Send + Sync + 'static,
+    {
+        self.0.tls_config.cert_resolver = Arc::new(ClientHelloResolver::new(
+            Arc::new(hook),
+            self.0.tls_config.cert_resolver.clone(),
+        ));
+        self
+    }
+
+    /// Binds an additional (local) socket address, in addition to the bind address.
+    ///
+    /// This allows a single endpoint to listen on several interfaces, or on both IPv4 and
+    /// IPv6 with distinct sockets where dual stack sockets are not available.
+    /// Connections of all sockets are returned by [`Endpoint::accept`](crate::Endpoint::accept).
+    ///
+    /// IPv6 additional addresses deny dual stack, so that they do not conflict with an
+    /// IPv4 address on the same port.
+    pub fn additional_bind_address(mut self, address: SocketAddr) -> Self {
+        self.0.additional_bind_addresses.push(address);
+        self
+    }
+
+    /// Maximum number of session requests, per connection, waiting to be accepted.
+    ///
+    /// Once the queue is full, no further requests are read from the peer until
+    /// the application accepts the pending ones. Values lower than `1` are treated as `1`.
+    /// Default is `1`.
+    pub fn max_pending_sessions(mut self, value: usize) -> Self {
+        self.0.driver_config.max_pending_sessions = value.max(1);
+        self
+    }
+
+    /// Maximum number of connections setting up their HTTP3 layer (driver initialization
+    /// and SETTINGS exchange) at the same time, across the endpoint.
+    ///
+    /// Further connections wait for a slot in FIFO order, so that a burst of new clients
+    /// (e.g., reconnecting after a rollout) cannot starve the established sessions.
+    /// The wait counts against the SETTINGS exchange timeout
+    /// (see [`Timeouts::settings_exchange`]). `None` means no limit.
+    /// Default is `Some(64)`.
+    pub fn max_concurrent_setups(mut self, value: Option<usize>) -> Self {
+        self.0.max_concurrent_setups = value.map(|value| value.max(1));
+        self
+    }
+
+    /// Maximum number of events (streams, datagrams, frames) a connection driver
+    /// processes in a row before yielding to other tasks.
+    ///
+    /// Lower values improve the fairness between busy connections, at the cost of
+    /// throughput. Values lower than `1` are treated as `1`. Default is `32`.
+    pub fn driver_event_budget(mut self, value: usize) -> Self {
+        self.0.driver_config.event_budget = value.max(1);
+        self
+    }
+
+    /// What to do with HTTP3 frames of unknown (or reserved) type.
+    ///
+    /// Default is [`UnknownPolicy::Ignore`], as required by HTTP3.
+    /// In any case, they are counted (see [`Connection::unknown_stats`](crate::Connection::unknown_stats)).
+    pub fn unknown_frames(mut self, policy: UnknownPolicy) -> Self {
+        self.0.driver_config.unknown_frames = policy;
+        self
+    }
+
+    /// What to do with unidirectional streams of unknown (or reserved) type.
+    ///
+    /// Default is [`UnknownPolicy::Ignore`], as required by HTTP3.
+    /// In any case, they are counted (see [`Connection::unknown_stats`](crate::Connection::unknown_stats)).
+    pub fn unknown_uni_streams(mut self, policy: UnknownPolicy) -> Self {
+        self.0.driver_config.unknown_uni_streams = policy;
+        self
+    }
+
+    /// Sends reserved settings, frames and streams (*grease*) to the peer, as configured
+    /// by `grease`.
+    ///
+    /// This checks that peers ignore what they do not know, e.g., in interoperability tests.
+    /// By default, nothing is sent.
+    pub fn grease(mut self, grease: Grease) -> Self {
+        self.0.driver_config.grease = grease;
+        self
+    }
+
+    /// Queues the opening of streams beyond the peer's stream limit.
+    ///
+    /// When enabled, [`Connection::open_uni`](crate::Connection::open_uni) and
+    /// [`Connection::open_bi`](crate::Connection::open_bi) return at once; the returned
+    /// futures then wait, in FIFO order, for stream credit. At most `capacity` opens per
+    /// direction can wait at once: further ones fail with
+    /// [`StreamOpeningError::QueueFull`](crate::error::StreamOpeningError::QueueFull).
+    ///
+    /// Disabled by default: each open waits for credit on its own, in no particular order.
+    pub fn stream_open_queue(mut self, capacity: usize) -> Self {
+        self.0.driver_config.open_queue_capacity = Some(capacity);
+        self
+    }
+
+    /// Sends a heartbeat on the session stream after `interval` without sending on it.
+    ///
+    /// Some intermediaries (e.g., HTTP proxies) time out CONNECT streams without
+    /// activity, even while QUIC keep-alives flow. Heartbeats are capsules of a reserved
+    /// type, ignored by any peer. They can still be set per connection with
+    /// [`Connection::set_session_heartbeat`](crate::Connection::set_session_heartbeat).
+    ///
+    /// Disabled by default.
+    pub fn session_heartbeat(mut self, interval: Duration) -> Self {
+        self.0.driver_config.session_heartbeat = Some(interval);
+        self
+    }
+
+    /// Advertises and negotiates the HTTP3 datagrams of older drafts, for compatibility
+    /// with older browsers and clients.
+    ///
+    /// The datagram format is then chosen per connection, according to the drafts supported
+    /// by the peer (see [`Connection::datagram_format`](crate::Connection::datagram_format)).
+    ///
+    /// Disabled by default.
+    pub fn legacy_datagram_drafts(mut self, enabled: bool) -> Self {
+        self.0.driver_config.legacy_datagrams = enabled;
+        self
+    }
+
+    /// Accepts the datagram timestamps requested by clients, for measuring the one-way
+    /// delay and jitter of datagrams (see [`TimestampedDatagrams`](crate::datagram::TimestampedDatagrams)).
+    ///
+    /// Timestamps are negotiated with a header field of the session request and response:
+    /// they are only used on sessions whose client enabled them too. Disabled by default.
+    pub fn datagram_timestamps(mut self, enabled: bool) -> Self {
+        self.0.driver_config.datagram_timestamps = enabled;
+        self
+    }
+
+    /// Delays the SETTINGS of each connection by `delay`, for chaos testing.
+    ///
+    /// The connection also starts processing the HTTP3 streams of the peer after the delay,
+    /// as a slow server would. See also [`Connection::chaos`](crate::Connection::chaos).
+    #[cfg(feature = "chaos")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "chaos")))]
+    pub fn delay_settings(mut self, delay: Duration) -> Self {
+        self.0.driver_config.settings_delay = Some(delay);
+        self
+    }
+
+    /// Sets a custom function for spawning the internal tasks of each connection.
+    ///
+    /// This allows, for example, pinning connections to a dedicated runtime or
+    /// instrumenting their tasks. By default, tasks are spawned with `tokio::spawn`.
+    ///
+    /// **Note**: QUIC endpoint I/O is still driven by the runtime the endpoint is created on.
+    pub fn spawn_with<F>(mut self, spawn: F) -> Self
+    where
+        F: Fn(Pin<Box<dyn Future<Output = ()> + Send>>) + Send + Sync + 'static,
+    {
+        self.0.driver_config.spawner = Spawner::new(spawn);
+        self
+    }
+
+    /// Spawns the internal tasks of each connection on the runtime of `handle`.
+    ///
+    /// See [`spawn_with`](Self::spawn_with).
+    pub fn runtime_handle(self, handle: tokio::runtime::Handle) -> Self {
+        self.spawn_with(move |task| {
+            handle.spawn(task);
+        })
+    }
+
+    /// Adds a header field to every session response sent by the server.
+    ///
+    /// If the key is already present, the value is updated. Pseudo-header fields
+    /// (e.g., `:status`) are ignored.
+    ///
+    /// By default, only `sec-webtransport-http3-draft: draft02` is present (omitted for
+    /// Firefox clients). Headers can still be overridden for each request
+    /// (see [`SessionRequest`]).
+    pub fn add_response_header<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: ToString,
+        V: ToString,
+    {
+        self.0.response_headers.insert(key, value);
+        self
+    }
+
+    /// Removes a header field previously added with [`add_response_header`](Self::add_response_header).
+    pub fn remove_response_header<K>(mut self, key: K) -> Self
+    where
+        K: AsRef<str>,
+    {
+        self.0.response_headers.remove(key);
+        self
+    }
+
+    /// Sets the validation of session requests.
+    ///
+    /// See [`RequestValidation`].
+    pub fn request_validation(mut self, validation: RequestValidation) -> Self {
+        self.0.driver_config.strict_scheme = validation.strict_scheme;
+        self.0.request_validation = validation;
+        self
+    }
+
+    /// Maximum size of a single field (name and value, once decoded) of the session
+    /// requests, in bytes.
+    ///
+    /// Requests with a larger field fail the connection with a QPACK decompression error.
+    /// Values larger than 32 KiB (the most the decoder handles) are treated as 32 KiB.
+    /// Default is 16 KiB.
+    pub fn max_request_header_field_size(mut self, value: usize) -> Self {
+        self.0.driver_config.header_limits = self
+            .0
+            .driver_config
+            .header_limits
+            .with_max_field_size(value);
+        self
+    }
+
+    /// Serves `response` to HTTP3 requests which are not session requests, instead of
+    /// rejecting their stream.
+    ///
+    /// See [`FallbackResponse`].
+    pub fn fallback_response(mut self, response: FallbackResponse) -> Self {
+        self.0.driver_config.fallback_response = Some(Arc::new(response));
+        self
+    }
+
+    /// Hands the session requests of the extended CONNECT `protocol` (e.g., `connect-udp`)
+    /// to `handler`, instead of rejecting them.
+    ///
+    /// The handler runs on its own task, with the request already validated: it can
+    /// [`accept`](SessionRequest::accept) the request (the session is then established like a
+    /// WebTransport session) or reject it. The [`IncomingSession`](crate::endpoint::IncomingSession)
+    /// of the connection completes with [`ConnectionError::HandedOff`](crate::error::ConnectionError::HandedOff).
+    ///
+    /// Registering `webtransport` has no effect.
+    pub fn extended_connect_handler<P, H, F>(mut self, protocol: P, handler: H) -> Self
+    where
+        P: ToString,
+        H: Fn(SessionRequest) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let handler: ExtendedConnectHandler =
+            Arc::new(move |session_request| Box::pin(handler(session_request)));
+
+        self.0
+            .extended_connect_handlers
+            .insert(protocol.to_string(), handler);
+        self
+    }
+
+    /// Serves connect-udp requests with `proxy`
+    /// (see [`extended_connect_handler`](Self::extended_connect_handler)).
+    #[cfg(feature = "masque")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "masque")))]
+    pub fn masque_udp_proxy(self, proxy: UdpProxy) -> Self {
+        self.extended_connect_handler(CONNECT_UDP_PROTOCOL, move |session_request| {
+            proxy.clone().handle(session_request)
+        })
+    }
+
+    /// Enables application-level resumption tokens, issued and redeemed with `tokens`.
+    ///
+    /// See [`SessionRequest::issue_resumption_token`](crate::endpoint::SessionRequest::issue_resumption_token)
+    /// and [`SessionRequest::resumption_state`](crate::endpoint::SessionRequest::resumption_state).
+    pub fn resumption_tokens(mut self, tokens: ResumptionTokens) -> Self {
+        self.0.resumption_tokens = Some(tokens);
+        self
+    }
+
+    /// Enforces `quotas` on the sessions with a principal
+    /// (see [`SessionRequest::set_principal`]).
+    ///
+    /// A clone of `quotas` can be kept to query the live usage of each principal.
+    pub fn quotas(mut self, quotas: Quotas) -> Self {
+        self.0.quotas = Some(quotas);
+        self
+    }
+
+    /// Enforces `memory_budget` on the connections of the endpoint.
+    ///
+    /// The QUIC buffers of each connection are bounded by the session cap of the budget:
+    /// this overrides the [`send_window`](Self::send_window) and the
+    /// [`receive_window`](Self::receive_window), unless the QUIC configuration is replaced.
+    /// A clone of `memory_budget` can be kept to query the live usage.
+    pub fn memory_budget(mut self, memory_budget: MemoryBudget) -> Self {
+        self.0.memory_budget = Some(memory_budget);
+        self
+    }
+
+    /// Whether to keep track of live connections.
+    ///
+    /// When enabled, they can be listed with [`Endpoint::connections`](crate::Endpoint::connections).
+    /// Disabled by default.
+    pub fn connections_registry(mut self, value: bool) -> Self {
+        self.0.connections_registry = value;
+        self
+    }
+
+    /// Replaces the QUIC transport configuration.
+    ///
+    /// Transport settings previously applied by this builder (e.g., idle timeout or
+    /// keep-alive) are discarded; the following ones are applied on top of `transport_config`.
     ///
-    /// **WARNING**: If a peer or its network path malfunctions or acts maliciously, an infinite
-    /// idle timeout can result in permanently hung futures!
-    pub fn max_idle_timeout(
-        mut self,
-        idle_timeout: Option<Duration>,
-    ) -> Result<Self, InvalidIdleTimeout> {
-        let idle_timeout = idle_timeout
-            .map(quinn::IdleTimeout::try_from)
-            .transpose()
-            .map_err(|_| InvalidIdleTimeout)?;
-
-        self.0.transport_config.max_idle_timeout(idle_timeout);
+    /// See the [`quinn-compat` stability notes](crate::config#quinn-compat-feature).
+    #[cfg(feature = "quinn-compat")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "quinn-compat")))]
+    pub fn with_transport_config(mut self, transport_config: quinn::TransportConfig) -> Self {
+        self.0.transport_config = transport_config;
+        self
+    }
 
-        Ok(self)
+    /// Replaces the QUIC endpoint configuration.
+    ///
+    /// See the [`quinn-compat` stability notes](crate::config#quinn-compat-feature).
+    #[cfg(feature = "quinn-compat")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "quinn-compat")))]
+    pub fn with_endpoint_config(mut self, endpoint_config: quinn::EndpointConfig) -> Self {
+        self.0.endpoint_config = endpoint_config;
+        self
     }
 
-    /// Period of inactivity before sending a keep-alive packet
+    /// Uses `quic_config` as it is, ignoring TLS and transport settings of this builder.
     ///
-    /// Keep-alive packets prevent an inactive but otherwise healthy connection from timing out.
+    /// The TLS configuration must advertise the WebTransport ALPN (`h3`).
     ///
-    /// `None` to disable, which is the default. Only one side of any given connection needs keep-alive
-    /// enabled for the connection to be preserved. Must be set lower than the idle_timeout of both
-    /// peers to be effective.
-    pub fn keep_alive_interval(mut self, interval: Option<Duration>) -> Self {
-        self.0.transport_config.keep_alive_interval(interval);
+    /// See the [`quinn-compat` stability notes](crate::config#quinn-compat-feature).
+    #[cfg(feature = "quinn-compat")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "quinn-compat")))]
+    pub fn with_quic_config(mut self, quic_config: quinn::ServerConfig) -> Self {
+        self.0.quic_config = Some(quic_config);
         self
     }
 
-    /// Whether to allow clients to migrate to new addresses.
+    /// Sets a handler for non-QUIC datagrams received on the endpoint socket.
     ///
-    /// Improves behavior for clients that move between different internet connections or suffer NAT
-    /// rebinding. Enabled by default.
-    pub fn allow_migration(mut self, value: bool) -> Self {
-        self.0.migration = value;
+    /// This allows sharing the UDP port with other protocols (e.g., STUN).
+    /// See [`ExternalPacketHandler`] for more information.
+    pub fn external_packet_handler<H>(mut self, handler: H) -> Self
+    where
+        H: ExternalPacketHandler,
+    {
+        self.0.external_packet_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Sets a tap receiving the metadata of every UDP datagram sent and received on the
+    /// endpoint socket.
+    ///
+    /// Packets are copied, not intercepted: this allows lightweight in-process diagnostics
+    /// when a full capture is not possible. See [`PacketTap`] for more information.
+    pub fn packet_tap<T>(mut self, tap: T) -> Self
+    where
+        T: PacketTap,
+    {
+        self.0.packet_tap = Some(Arc::new(tap));
         self
     }
 }
@@ -258,6 +1405,14 @@ pub struct ClientConfig {
     pub(crate) bind_address: SocketAddr,
     pub(crate) dual_stack_config: Ipv6DualStackConfig,
     pub(crate) quic_config: QuicClientConfig,
+    pub(crate) endpoint_config: quinn::EndpointConfig,
+    pub(crate) quic_version: QuicVersion,
+    pub(crate) max_connect_attempts: u32,
+    pub(crate) timeouts: Timeouts,
+    pub(crate) driver_config: DriverConfig,
+    pub(crate) external_packet_handler: Option<Arc<dyn ExternalPacketHandler>>,
+    pub(crate) packet_tap: Option<Arc<dyn PacketTap>>,
+    pub(crate) https_resolver: Option<Arc<dyn HttpsResolver>>,
 }
 
 impl ClientConfig {
@@ -267,6 +1422,24 @@ impl ClientConfig {
     pub fn builder() -> ClientConfigBuilder<WantsBindAddress> {
         ClientConfigBuilder::default()
     }
+
+    /// Returns the effective QUIC configuration.
+    ///
+    /// See the [`quinn-compat` stability notes](crate::config#quinn-compat-feature).
+    #[cfg(feature = "quinn-compat")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "quinn-compat")))]
+    pub fn quic_config(&self) -> &QuicClientConfig {
+        &self.quic_config
+    }
+
+    /// Returns the effective QUIC endpoint configuration.
+    ///
+    /// See the [`quinn-compat` stability notes](crate::config#quinn-compat-feature).
+    #[cfg(feature = "quinn-compat")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "quinn-compat")))]
+    pub fn quic_endpoint_config(&self) -> &quinn::EndpointConfig {
+        &self.endpoint_config
+    }
 }
 
 impl Default for ClientConfig {
@@ -341,16 +1514,65 @@ impl ClientConfigBuilder<WantsBindAddress> {
 }
 
 impl ClientConfigBuilder<WantsRootStore> {
+    const DEFAULT_MAX_CONNECT_ATTEMPTS: u32 = 3;
+    const DEFAULT_MAX_RESPONSE_HEADER_SIZE: usize = 64 * 1024;
+    const DEFAULT_MAX_RESPONSE_HEADER_COUNT: usize = 128;
+
     /// Loads local (native) root certificate for server validation.
     pub fn with_native_certs(self) -> ClientConfigBuilder<WantsTransportConfigClient> {
         let tls_config = Self::build_tls_config(Self::native_cert_store());
-        let transport_config = TransportConfig::default();
+        self.with_tls_config(tls_config)
+    }
+
+    /// Uses a custom TLS configuration (e.g., with a custom certificate verifier,
+    /// root store, or client authentication through a hardware key).
+    ///
+    /// The configuration is validated for QUIC: TLS 1.3 must be enabled. ALPN protocols
+    /// must include the WebTransport one (`h3`); if none is set, it is added.
+    ///
+    /// **Note**: the [`rustls`] version must be the one this crate depends on
+    /// (re-exported as [`tls::rustls`](crate::tls::rustls)).
+    pub fn with_custom_tls(
+        self,
+        mut tls_config: TlsClientConfig,
+    ) -> Result<ClientConfigBuilder<WantsTransportConfigClient>, InvalidTlsConfig> {
+        // TLS 1.3 support is the only requirement checked by rustls.
+        rustls::quic::ClientConnection::new(
+            Arc::new(tls_config.clone()),
+            rustls::quic::Version::V1,
+            rustls::ServerName::try_from("tls13.check.invalid").expect("Valid DNS name"),
+            Vec::new(),
+        )
+        .map_err(|_| InvalidTlsConfig::MissingTls13)?;
+
+        validate_alpn(&mut tls_config.alpn_protocols)?;
+
+        Ok(self.with_tls_config(tls_config))
+    }
 
+    fn with_tls_config(
+        self,
+        tls_config: TlsClientConfig,
+    ) -> ClientConfigBuilder<WantsTransportConfigClient> {
         ClientConfigBuilder(WantsTransportConfigClient {
             bind_address: self.0.bind_address,
             dual_stack_config: self.0.dual_stack_config,
             tls_config,
-            transport_config,
+            transport_config: TransportConfig::default(),
+            endpoint_config: quinn::EndpointConfig::default(),
+            quic_versions: None,
+            quic_config: None,
+            max_connect_attempts: Self::DEFAULT_MAX_CONNECT_ATTEMPTS,
+            timeouts: Timeouts::default(),
+            driver_config: DriverConfig {
+                header_limits: HeadersLimits::new()
+                    .with_max_section_size(Self::DEFAULT_MAX_RESPONSE_HEADER_SIZE)
+                    .with_max_field_count(Self::DEFAULT_MAX_RESPONSE_HEADER_COUNT),
+                ..DriverConfig::default()
+            },
+            external_packet_handler: None,
+            packet_tap: None,
+            https_resolver: None,
         })
     }
 
@@ -363,17 +1585,10 @@ impl ClientConfigBuilder<WantsRootStore> {
             .dangerous()
             .set_certificate_verifier(Arc::new(dangerous_configuration::NoServerVerification));
 
-        let transport_config = TransportConfig::default();
-
-        ClientConfigBuilder(WantsTransportConfigClient {
-            bind_address: self.0.bind_address,
-            dual_stack_config: self.0.dual_stack_config,
-            tls_config,
-            transport_config,
-        })
+        self.with_tls_config(tls_config)
     }
 
-    fn native_cert_store() -> RootCertStore {
+    pub(crate) fn native_cert_store() -> RootCertStore {
         let mut root_store = RootCertStore::empty();
 
         match rustls_native_certs::load_native_certs() {
@@ -405,13 +1620,43 @@ impl ClientConfigBuilder<WantsRootStore> {
 impl ClientConfigBuilder<WantsTransportConfigClient> {
     /// Completes configuration process.
     pub fn build(self) -> ClientConfig {
-        let mut quic_config = QuicClientConfig::new(Arc::new(self.0.tls_config));
-        quic_config.transport_config(Arc::new(self.0.transport_config));
+        let mut quic_config = match self.0.quic_config {
+            Some(quic_config) => quic_config,
+            None => {
+                let mut quic_config =
+                    QuicClientConfig::new(Arc::new(ClientCrypto(Arc::new(self.0.tls_config))));
+                quic_config.transport_config(Arc::new(self.0.transport_config));
+                quic_config
+            }
+        };
+
+        let mut endpoint_config = self.0.endpoint_config;
+        if self.0.external_packet_handler.is_some() {
+            endpoint_config.grease_quic_bit(false);
+        }
+
+        let quic_version = match self.0.quic_versions {
+            Some(versions) => {
+                endpoint_config
+                    .supported_versions(versions.iter().copied().map(u32::from).collect());
+                quic_config.version(u32::from(versions[0]));
+                versions[0]
+            }
+            None => QuicVersion::V1,
+        };
 
         ClientConfig {
             bind_address: self.0.bind_address,
             dual_stack_config: self.0.dual_stack_config,
             quic_config,
+            endpoint_config,
+            quic_version,
+            max_connect_attempts: self.0.max_connect_attempts,
+            timeouts: self.0.timeouts,
+            driver_config: self.0.driver_config,
+            external_packet_handler: self.0.external_packet_handler,
+            packet_tap: self.0.packet_tap,
+            https_resolver: self.0.https_resolver,
         }
     }
 
@@ -436,6 +1681,20 @@ impl ClientConfigBuilder<WantsTransportConfigClient> {
         Ok(self)
     }
 
+    /// Sets the timeouts of the phases of connections.
+    ///
+    /// If set, the [`idle`](Timeouts::idle) timeout replaces the one set with
+    /// [`max_idle_timeout`](Self::max_idle_timeout).
+    /// See [`Timeouts`].
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Result<Self, InvalidIdleTimeout> {
+        if timeouts.idle.is_some() {
+            self = self.max_idle_timeout(timeouts.idle)?;
+        }
+
+        self.0.timeouts = timeouts;
+        Ok(self)
+    }
+
     /// Period of inactivity before sending a keep-alive packet
     ///
     /// Keep-alive packets prevent an inactive but otherwise healthy connection from timing out.
@@ -447,6 +1706,309 @@ impl ClientConfigBuilder<WantsTransportConfigClient> {
         self.0.transport_config.keep_alive_interval(interval);
         self
     }
+
+    /// Sets the congestion control algorithm and its initial window, in bytes.
+    ///
+    /// `None` keeps the default initial window (of about 14 KB, see
+    /// [RFC 9002](https://www.rfc-editor.org/rfc/rfc9002#section-7.2)). Larger values
+    /// speed up the first round-trips on high bandwidth-delay links.
+    ///
+    /// Pacing cannot be turned off (see [pacing](CongestionControl#pacing)).
+    pub fn congestion_control(
+        mut self,
+        algorithm: CongestionControl,
+        initial_window: Option<u64>,
+    ) -> Self {
+        algorithm.apply(&mut self.0.transport_config, initial_window);
+        self
+    }
+
+    /// Sets the RTT assumed before any measurement. Default is 333 milliseconds.
+    pub fn initial_rtt(mut self, value: Duration) -> Self {
+        self.0.transport_config.initial_rtt(value);
+        self
+    }
+
+    /// Maximum number of unacknowledged bytes buffered, per connection, for sending to the peer.
+    ///
+    /// It bounds the data in flight, whatever the congestion window.
+    /// Default is 10 MB: it should be raised for links with a large bandwidth-delay product.
+    pub fn send_window(mut self, value: u64) -> Self {
+        self.0.transport_config.send_window(value);
+        self
+    }
+
+    /// Maximum number of bytes the peer may send on a stream, before it is read
+    /// by the application (i.e., the initial flow control window of each stream).
+    ///
+    /// The window applies to all the streams, whatever their type (see
+    /// [receive windows](crate::config#receive-windows)).
+    ///
+    /// Default is 1.25 MB.
+    pub fn stream_receive_window(mut self, value: u32) -> Self {
+        self.0
+            .transport_config
+            .stream_receive_window(quinn::VarInt::from_u32(value));
+        self
+    }
+
+    /// Maximum number of bytes the peer may send on all the streams of a connection,
+    /// before they are read by the application.
+    ///
+    /// It should be at least a few times the
+    /// [`stream_receive_window`](Self::stream_receive_window), so that the unread data of
+    /// a few streams leaves credit for the others, including the control streams.
+    /// Default is unlimited: each stream is only bounded by its own window.
+    pub fn receive_window(mut self, value: u64) -> Self {
+        let value = quinn::VarInt::from_u64(value).unwrap_or(quinn::VarInt::MAX);
+        self.0.transport_config.receive_window(value);
+        self
+    }
+
+    /// Sets the QUIC versions supported by the endpoint, in order of preference.
+    ///
+    /// By default, [`QuicVersion::V1`] and the most recent drafts are supported.
+    pub fn quic_versions(
+        mut self,
+        versions: &[QuicVersion],
+    ) -> Result<Self, UnsupportedQuicVersion> {
+        if versions.is_empty() || !versions.iter().all(|version| version.is_supported()) {
+            return Err(UnsupportedQuicVersion);
+        }
+
+        self.0.quic_versions = Some(versions.to_vec());
+        Ok(self)
+    }
+
+    /// Whether to randomize the QUIC *fixed bit* (a.k.a. greasing,
+    /// see [RFC 9287](https://www.rfc-editor.org/rfc/rfc9287)) when the peer supports it.
+    ///
+    /// Enabled by default, unless an [external packet handler](Self::external_packet_handler)
+    /// is set: the fixed bit then tells QUIC packets apart, hence peers are not allowed to
+    /// grease it.
+    pub fn grease_quic_bit(mut self, value: bool) -> Self {
+        self.0.endpoint_config.grease_quic_bit(value);
+        self
+    }
+
+    /// Maximum number of QUIC handshake attempts for each [`connect`](crate::Endpoint::connect).
+    ///
+    /// Handshakes failing because of a stateless reset or an invalid retry token (e.g.,
+    /// middleboxes interfering with QUIC *Retry*) are transparently attempted again till
+    /// this budget is exhausted. Version mismatches are not retried, as the server would
+    /// refuse the same versions again. Values lower than `1` are treated as `1`.
+    /// Default is `3`.
+    pub fn max_connect_attempts(mut self, value: u32) -> Self {
+        self.0.max_connect_attempts = value.max(1);
+        self
+    }
+
+    /// Maximum size of the headers of the session responses, in bytes.
+    ///
+    /// The size is computed as the HTTP3 field section size: the sum of the length of
+    /// the name and value of each field, plus an overhead of 32 bytes per field. Larger
+    /// responses fail the connection with [`ConnectingError::ResponseHeadersTooLarge`],
+    /// protecting the client (e.g., a crawler) against hostile servers. Default is 64 KiB.
+    ///
+    /// [`ConnectingError::ResponseHeadersTooLarge`]: crate::error::ConnectingError::ResponseHeadersTooLarge
+    pub fn max_response_header_size(mut self, value: usize) -> Self {
+        self.0.driver_config.header_limits = self
+            .0
+            .driver_config
+            .header_limits
+            .with_max_section_size(value);
+        self
+    }
+
+    /// Maximum number of fields in the headers of the session responses.
+    ///
+    /// See [`max_response_header_size`](Self::max_response_header_size). Default is `128`.
+    pub fn max_response_header_count(mut self, value: usize) -> Self {
+        self.0.driver_config.header_limits = self
+            .0
+            .driver_config
+            .header_limits
+            .with_max_field_count(value);
+        self
+    }
+
+    /// Maximum size of a single field (name and value, once decoded) of the session
+    /// responses, in bytes.
+    ///
+    /// Responses with a larger field fail the connection with a QPACK decompression error.
+    /// Values larger than 32 KiB (the most the decoder handles) are treated as 32 KiB.
+    /// Default is 16 KiB.
+    pub fn max_response_header_field_size(mut self, value: usize) -> Self {
+        self.0.driver_config.header_limits = self
+            .0
+            .driver_config
+            .header_limits
+            .with_max_field_size(value);
+        self
+    }
+
+    /// Queues the opening of streams beyond the peer's stream limit.
+    ///
+    /// See [`ServerConfigBuilder::stream_open_queue`].
+    pub fn stream_open_queue(mut self, capacity: usize) -> Self {
+        self.0.driver_config.open_queue_capacity = Some(capacity);
+        self
+    }
+
+    /// Sends a heartbeat on the session stream after `interval` without sending on it.
+    ///
+    /// See [`ServerConfigBuilder::session_heartbeat`].
+    pub fn session_heartbeat(mut self, interval: Duration) -> Self {
+        self.0.driver_config.session_heartbeat = Some(interval);
+        self
+    }
+
+    /// Advertises and negotiates the HTTP3 datagrams of older drafts.
+    ///
+    /// See [`ServerConfigBuilder::legacy_datagram_drafts`].
+    pub fn legacy_datagram_drafts(mut self, enabled: bool) -> Self {
+        self.0.driver_config.legacy_datagrams = enabled;
+        self
+    }
+
+    /// Requests datagram timestamps to the server, for measuring the one-way delay and jitter
+    /// of datagrams (see [`TimestampedDatagrams`](crate::datagram::TimestampedDatagrams)).
+    ///
+    /// See [`ServerConfigBuilder::datagram_timestamps`].
+    pub fn datagram_timestamps(mut self, enabled: bool) -> Self {
+        self.0.driver_config.datagram_timestamps = enabled;
+        self
+    }
+
+    /// Delays the SETTINGS of each connection by `delay`, for chaos testing.
+    ///
+    /// See [`ServerConfigBuilder::delay_settings`].
+    #[cfg(feature = "chaos")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "chaos")))]
+    pub fn delay_settings(mut self, delay: Duration) -> Self {
+        self.0.driver_config.settings_delay = Some(delay);
+        self
+    }
+
+    /// What to do with HTTP3 frames of unknown (or reserved) type.
+    ///
+    /// See [`ServerConfigBuilder::unknown_frames`].
+    pub fn unknown_frames(mut self, policy: UnknownPolicy) -> Self {
+        self.0.driver_config.unknown_frames = policy;
+        self
+    }
+
+    /// What to do with unidirectional streams of unknown (or reserved) type.
+    ///
+    /// See [`ServerConfigBuilder::unknown_uni_streams`].
+    pub fn unknown_uni_streams(mut self, policy: UnknownPolicy) -> Self {
+        self.0.driver_config.unknown_uni_streams = policy;
+        self
+    }
+
+    /// Sends reserved settings, frames and streams (*grease*) to the peer.
+    ///
+    /// See [`ServerConfigBuilder::grease`].
+    pub fn grease(mut self, grease: Grease) -> Self {
+        self.0.driver_config.grease = grease;
+        self
+    }
+
+    /// Sets a custom function for spawning the internal tasks of each connection.
+    ///
+    /// This allows, for example, pinning connections to a dedicated runtime or
+    /// instrumenting their tasks. By default, tasks are spawned with `tokio::spawn`.
+    ///
+    /// **Note**: QUIC endpoint I/O is still driven by the runtime the endpoint is created on.
+    pub fn spawn_with<F>(mut self, spawn: F) -> Self
+    where
+        F: Fn(Pin<Box<dyn Future<Output = ()> + Send>>) + Send + Sync + 'static,
+    {
+        self.0.driver_config.spawner = Spawner::new(spawn);
+        self
+    }
+
+    /// Spawns the internal tasks of each connection on the runtime of `handle`.
+    ///
+    /// See [`spawn_with`](Self::spawn_with).
+    pub fn runtime_handle(self, handle: tokio::runtime::Handle) -> Self {
+        self.spawn_with(move |task| {
+            handle.spawn(task);
+        })
+    }
+
+    /// Replaces the QUIC transport configuration.
+    ///
+    /// Transport settings previously applied by this builder (e.g., idle timeout or
+    /// keep-alive) are discarded; the following ones are applied on top of `transport_config`.
+    ///
+    /// See the [`quinn-compat` stability notes](crate::config#quinn-compat-feature).
+    #[cfg(feature = "quinn-compat")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "quinn-compat")))]
+    pub fn with_transport_config(mut self, transport_config: quinn::TransportConfig) -> Self {
+        self.0.transport_config = transport_config;
+        self
+    }
+
+    /// Replaces the QUIC endpoint configuration.
+    ///
+    /// See the [`quinn-compat` stability notes](crate::config#quinn-compat-feature).
+    #[cfg(feature = "quinn-compat")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "quinn-compat")))]
+    pub fn with_endpoint_config(mut self, endpoint_config: quinn::EndpointConfig) -> Self {
+        self.0.endpoint_config = endpoint_config;
+        self
+    }
+
+    /// Uses `quic_config` as it is, ignoring TLS and transport settings of this builder.
+    ///
+    /// The TLS configuration must advertise the WebTransport ALPN (`h3`).
+    ///
+    /// See the [`quinn-compat` stability notes](crate::config#quinn-compat-feature).
+    #[cfg(feature = "quinn-compat")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "quinn-compat")))]
+    pub fn with_quic_config(mut self, quic_config: quinn::ClientConfig) -> Self {
+        self.0.quic_config = Some(quic_config);
+        self
+    }
+
+    /// Sets a handler for non-QUIC datagrams received on the endpoint socket.
+    ///
+    /// This allows sharing the UDP port with other protocols (e.g., STUN).
+    /// See [`ExternalPacketHandler`] for more information.
+    pub fn external_packet_handler<H>(mut self, handler: H) -> Self
+    where
+        H: ExternalPacketHandler,
+    {
+        self.0.external_packet_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Sets a tap receiving the metadata of every UDP datagram sent and received on the
+    /// endpoint socket.
+    ///
+    /// Packets are copied, not intercepted: this allows lightweight in-process diagnostics
+    /// when a full capture is not possible. See [`PacketTap`] for more information.
+    pub fn packet_tap<T>(mut self, tap: T) -> Self
+    where
+        T: PacketTap,
+    {
+        self.0.packet_tap = Some(Arc::new(tap));
+        self
+    }
+
+    /// Sets a resolver of DNS `HTTPS` records, for discovering the endpoint of an origin.
+    ///
+    /// When connecting to a domain, its `HTTPS` records can redirect the client to
+    /// another host or port (see [`HttpsResolver`]). Without usable records, the client
+    /// falls back to the address records of the origin.
+    pub fn https_resolver<R>(mut self, resolver: R) -> Self
+    where
+        R: HttpsResolver,
+    {
+        self.0.https_resolver = Some(Arc::new(resolver));
+        self
+    }
 }
 
 impl Default for ServerConfigBuilder<WantsBindAddress> {
@@ -480,9 +2042,28 @@ pub struct WantsRootStore {
 pub struct WantsTransportConfigServer {
     bind_address: SocketAddr,
     dual_stack_config: Ipv6DualStackConfig,
+    additional_bind_addresses: Vec<SocketAddr>,
     tls_config: TlsServerConfig,
     transport_config: quinn::TransportConfig,
+    endpoint_config: quinn::EndpointConfig,
+    quic_versions: Option<Vec<QuicVersion>>,
+    quic_config: Option<QuicServerConfig>,
     migration: bool,
+    max_concurrent_connections: u32,
+    use_retry: bool,
+    retry_token_lifetime: Duration,
+    driver_config: DriverConfig,
+    max_concurrent_setups: Option<usize>,
+    response_headers: Headers,
+    request_validation: RequestValidation,
+    timeouts: Timeouts,
+    connections_registry: bool,
+    external_packet_handler: Option<Arc<dyn ExternalPacketHandler>>,
+    packet_tap: Option<Arc<dyn PacketTap>>,
+    resumption_tokens: Option<ResumptionTokens>,
+    quotas: Option<Quotas>,
+    memory_budget: Option<MemoryBudget>,
+    extended_connect_handlers: HashMap<String, ExtendedConnectHandler>,
 }
 
 /// Config builder state where transport properties can be set.
@@ -491,6 +2072,246 @@ pub struct WantsTransportConfigClient {
     dual_stack_config: Ipv6DualStackConfig,
     tls_config: TlsClientConfig,
     transport_config: quinn::TransportConfig,
+    endpoint_config: quinn::EndpointConfig,
+    quic_versions: Option<Vec<QuicVersion>>,
+    quic_config: Option<QuicClientConfig>,
+    max_connect_attempts: u32,
+    timeouts: Timeouts,
+    driver_config: DriverConfig,
+    external_packet_handler: Option<Arc<dyn ExternalPacketHandler>>,
+    packet_tap: Option<Arc<dyn PacketTap>>,
+    https_resolver: Option<Arc<dyn HttpsResolver>>,
+}
+
+/// Plain-data part of a [`ServerConfig`], e.g., loaded from a TOML or YAML file.
+///
+/// Optional fields left unset keep the builder's default.
+/// Durations are expressed in seconds.
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerSettings {
+    /// Socket address to bind.
+    pub bind_address: SocketAddr,
+
+    /// Dual stack configuration, only used for IPv6 [`bind_address`](Self::bind_address).
+    #[serde(default = "settings::default_dual_stack")]
+    pub dual_stack: Ipv6DualStackConfig,
+
+    /// Path of the PEM file containing the certificate chain.
+    pub certificate_path: PathBuf,
+
+    /// Path of the PEM file containing the private key.
+    pub private_key_path: PathBuf,
+
+    /// See [`ServerConfigBuilder::max_idle_timeout`].
+    #[serde(
+        default,
+        with = "settings::secs",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_idle_timeout: Option<Duration>,
+
+    /// See [`ServerConfigBuilder::keep_alive_interval`].
+    #[serde(
+        default,
+        with = "settings::secs",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub keep_alive_interval: Option<Duration>,
+
+    /// See [`ServerConfigBuilder::allow_migration`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_migration: Option<bool>,
+
+    /// See [`ServerConfigBuilder::max_concurrent_connections`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_connections: Option<u32>,
+
+    /// See [`ServerConfigBuilder::max_pending_sessions`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_pending_sessions: Option<usize>,
+
+    /// See [`ServerConfigBuilder::use_retry`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub use_retry: Option<bool>,
+}
+
+#[cfg(feature = "serde")]
+impl ServerSettings {
+    /// Converts these settings into a builder, loading the certificate from files.
+    ///
+    /// The returned builder can be further customized (e.g., with a runtime handle)
+    /// before calling [`build`](ServerConfigBuilder::build).
+    pub fn into_builder(
+        self,
+    ) -> Result<ServerConfigBuilder<WantsTransportConfigServer>, SettingsError> {
+        let certificate = Certificate::load(&self.certificate_path, &self.private_key_path)
+            .map_err(SettingsError::Certificate)?;
+
+        let builder = match self.bind_address {
+            SocketAddr::V4(_) => ServerConfig::builder().with_bind_address(self.bind_address),
+            SocketAddr::V6(address) => {
+                ServerConfig::builder().with_bind_address_v6(address, self.dual_stack)
+            }
+        };
+
+        let mut builder = builder
+            .with_certificate(certificate)
+            .keep_alive_interval(self.keep_alive_interval);
+
+        if self.max_idle_timeout.is_some() {
+            builder = builder
+                .max_idle_timeout(self.max_idle_timeout)
+                .map_err(|_| SettingsError::InvalidIdleTimeout)?;
+        }
+
+        if let Some(value) = self.allow_migration {
+            builder = builder.allow_migration(value);
+        }
+
+        if let Some(value) = self.max_concurrent_connections {
+            builder = builder.max_concurrent_connections(value);
+        }
+
+        if let Some(value) = self.max_pending_sessions {
+            builder = builder.max_pending_sessions(value);
+        }
+
+        if let Some(value) = self.use_retry {
+            builder = builder.use_retry(value);
+        }
+
+        Ok(builder)
+    }
+}
+
+/// Plain-data part of a [`ClientConfig`], e.g., loaded from a TOML or YAML file.
+///
+/// Optional fields left unset keep the builder's default.
+/// Durations are expressed in seconds.
+/// Server certificates are validated with the native root store.
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClientSettings {
+    /// Socket address to bind. If not set, see [`ClientConfigBuilder::with_bind_default`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bind_address: Option<SocketAddr>,
+
+    /// Dual stack configuration, only used for IPv6 [`bind_address`](Self::bind_address).
+    #[serde(default = "settings::default_dual_stack")]
+    pub dual_stack: Ipv6DualStackConfig,
+
+    /// See [`ClientConfigBuilder::max_idle_timeout`].
+    #[serde(
+        default,
+        with = "settings::secs",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_idle_timeout: Option<Duration>,
+
+    /// See [`ClientConfigBuilder::keep_alive_interval`].
+    #[serde(
+        default,
+        with = "settings::secs",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub keep_alive_interval: Option<Duration>,
+
+    /// See [`ClientConfigBuilder::max_connect_attempts`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_connect_attempts: Option<u32>,
+}
+
+#[cfg(feature = "serde")]
+impl ClientSettings {
+    /// Converts these settings into a builder.
+    ///
+    /// The returned builder can be further customized (e.g., with a runtime handle)
+    /// before calling [`build`](ClientConfigBuilder::build).
+    pub fn into_builder(
+        self,
+    ) -> Result<ClientConfigBuilder<WantsTransportConfigClient>, SettingsError> {
+        let builder = match self.bind_address {
+            None => ClientConfig::builder().with_bind_default(),
+            Some(address @ SocketAddr::V4(_)) => ClientConfig::builder().with_bind_address(address),
+            Some(SocketAddr::V6(address)) => {
+                ClientConfig::builder().with_bind_address_v6(address, self.dual_stack)
+            }
+        };
+
+        let mut builder = builder
+            .with_native_certs()
+            .keep_alive_interval(self.keep_alive_interval);
+
+        if self.max_idle_timeout.is_some() {
+            builder = builder
+                .max_idle_timeout(self.max_idle_timeout)
+                .map_err(|_| SettingsError::InvalidIdleTimeout)?;
+        }
+
+        if let Some(value) = self.max_connect_attempts {
+            builder = builder.max_connect_attempts(value);
+        }
+
+        Ok(builder)
+    }
+}
+
+/// Error converting [`ServerSettings`] or [`ClientSettings`] into a builder.
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum SettingsError {
+    /// The certificate or the private key cannot be loaded.
+    #[error("cannot load certificate: {0}")]
+    Certificate(std::io::Error),
+
+    /// The idle timeout is out of range.
+    #[error("invalid idle timeout")]
+    InvalidIdleTimeout,
+}
+
+#[cfg(feature = "serde")]
+mod settings {
+    use super::Ipv6DualStackConfig;
+
+    pub(super) fn default_dual_stack() -> Ipv6DualStackConfig {
+        Ipv6DualStackConfig::OsDefault
+    }
+
+    /// Optional [`Duration`](std::time::Duration) as (fractional) seconds.
+    pub(super) mod secs {
+        use serde::de::Error;
+        use serde::Deserialize;
+        use serde::Deserializer;
+        use serde::Serialize;
+        use serde::Serializer;
+        use std::time::Duration;
+
+        pub fn serialize<S>(value: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            value.map(|d| d.as_secs_f64()).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            Option::<f64>::deserialize(deserializer)?
+                .map(|secs| {
+                    Duration::try_from_secs_f64(secs)
+                        .map_err(|_| D::Error::custom("invalid duration in seconds"))
+                })
+                .transpose()
+        }
+    }
 }
 
 #[cfg(feature = "dangerous-configuration")]
@@ -514,3 +2335,188 @@ mod dangerous_configuration {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn settings_durations() {
+        let settings: ClientSettings =
+            serde_json::from_str(r#"{"keep_alive_interval": 1.5}"#).unwrap();
+        assert_eq!(
+            settings.keep_alive_interval,
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(settings.max_idle_timeout, None);
+
+        for secs in ["-1", "1e300", "18446744073709551616"] {
+            let json = format!(r#"{{"keep_alive_interval": {secs}}}"#);
+            assert!(serde_json::from_str::<ClientSettings>(&json).is_err());
+        }
+    }
+
+    #[test]
+    fn timeouts() {
+        let timeouts = Timeouts::new()
+            .with_handshake(Some(Duration::from_secs(1)))
+            .with_settings_exchange(Some(Duration::from_secs(2)))
+            .with_session_request(Some(Duration::from_secs(3)))
+            .with_idle(Some(Duration::from_secs(4)))
+            .with_drain(Some(Duration::from_secs(5)));
+
+        assert_eq!(timeouts.handshake, Some(Duration::from_secs(1)));
+        assert_eq!(timeouts.settings_exchange, Some(Duration::from_secs(2)));
+        assert_eq!(timeouts.session_request, Some(Duration::from_secs(3)));
+        assert_eq!(timeouts.idle, Some(Duration::from_secs(4)));
+        assert_eq!(timeouts.drain, Some(Duration::from_secs(5)));
+
+        assert_eq!(timeouts.with_drain(None).drain, None);
+        assert_eq!(Timeouts::new(), Timeouts::default());
+    }
+
+    fn request(url: &str) -> SessionRequestProto {
+        SessionRequestProto::new(url).unwrap()
+    }
+
+    fn status(code: u16) -> Result<(), StatusCode> {
+        Err(StatusCode::try_from(code).unwrap())
+    }
+
+    #[test]
+    fn request_validation() {
+        let default = RequestValidation::new();
+        assert_eq!(
+            default.check(&request("https://example.com/"), None),
+            Ok(())
+        );
+
+        let validation = RequestValidation::new().max_path_length(4);
+        assert_eq!(
+            validation.check(&request("https://example.com/abc"), None),
+            Ok(())
+        );
+        assert_eq!(
+            validation.check(&request("https://example.com/abcd"), None),
+            status(414)
+        );
+
+        let validation = RequestValidation::new().require_header("Origin");
+        let mut with_origin = request("https://example.com/");
+        with_origin.add("origin", "https://example.com");
+        assert_eq!(validation.check(&with_origin, None), Ok(()));
+        assert_eq!(
+            validation.check(&request("https://example.com/"), None),
+            status(400)
+        );
+    }
+
+    #[test]
+    fn request_validation_authority() {
+        let validation = RequestValidation::new().authority_matches_server_name(true);
+
+        let named = request("https://Example.com:4433/");
+        assert_eq!(validation.check(&named, Some("example.com")), Ok(()));
+        assert_eq!(validation.check(&named, Some("example.com.")), Ok(()));
+        assert_eq!(validation.check(&named, Some("other.com")), status(421));
+        // Without SNI, only IP addresses are allowed.
+        assert_eq!(validation.check(&named, None), status(421));
+        assert_eq!(
+            validation.check(&request("https://127.0.0.1:4433/"), None),
+            Ok(())
+        );
+        assert_eq!(
+            validation.check(&request("https://[::1]:4433/"), None),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn authority_hosts() {
+        assert_eq!(authority_host("example.com"), "example.com");
+        assert_eq!(authority_host("example.com:443"), "example.com");
+        assert_eq!(authority_host("[::1]:443"), "::1");
+        assert_eq!(authority_host("[::1]"), "::1");
+        assert_eq!(authority_host("[::1"), "::1");
+    }
+
+    #[tokio::test]
+    async fn stream_receive_window() {
+        const WINDOW: usize = 4096;
+
+        let certificate = test_utils::certificate();
+        let server_config = test_utils::server_config(certificate.clone())
+            .stream_receive_window(WINDOW as u32)
+            .receive_window(16 * WINDOW as u64)
+            .build();
+        let peers = test_utils::connect_with(
+            server_config,
+            test_utils::client_config(&certificate).build(),
+        )
+        .await;
+
+        // The server never reads the first stream: it is blocked by its window.
+        let mut unread = peers
+            .client_connection
+            .open_uni()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        let chunk = [0; 1024];
+        let mut written = 0;
+        while let Ok(result) =
+            tokio::time::timeout(Duration::from_millis(200), unread.write(&chunk)).await
+        {
+            written += result.unwrap();
+        }
+        assert!(
+            written >= WINDOW - chunk.len() && written <= WINDOW,
+            "{written}"
+        );
+        let _unread = peers.server_connection.accept_uni().await.unwrap();
+
+        // Other streams, and the session, are not starved.
+        let mut stream = peers
+            .client_connection
+            .open_uni()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        stream.write_all(&chunk).await.unwrap();
+        stream.finish().await.unwrap();
+        let mut stream = peers.server_connection.accept_uni().await.unwrap();
+        let mut buffer = [1; 1024];
+        stream.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(buffer, chunk);
+    }
+
+    #[test]
+    fn fallback_response() {
+        let response = FallbackResponse::new(426)
+            .header("upgrade", "webtransport")
+            .header(":status", "200")
+            .text("Open a WebTransport session");
+
+        assert_eq!(response.headers.get(":status"), Some("426"));
+        assert_eq!(response.headers.get("upgrade"), Some("webtransport"));
+        assert_eq!(
+            response.headers.get("content-type"),
+            Some("text/plain; charset=utf-8")
+        );
+        assert_eq!(response.body, b"Open a WebTransport session");
+
+        let response = FallbackResponse::new(404).body(vec![1, 2, 3]);
+        assert_eq!(response.headers.get("content-type"), None);
+        assert_eq!(response.body, [1, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid fallback status code")]
+    fn fallback_response_invalid_status() {
+        FallbackResponse::new(101);
+    }
+}
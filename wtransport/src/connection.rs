@@ -1,15 +1,51 @@
+use crate::capsule::Capsule;
+#[cfg(feature = "chaos")]
+use crate::chaos::Chaos;
+#[cfg(feature = "serde")]
+use crate::codec::Codec;
+#[cfg(feature = "compression")]
+use crate::compression::Compression;
+use crate::config::QuicVersion;
+use crate::config::Timeouts;
 use crate::datagram::Datagram;
 use crate::driver::utils::varint_w2q;
+use crate::driver::utils::Activity;
+use crate::driver::utils::SharedDatagramFormat;
+use crate::driver::utils::Spawner;
 use crate::driver::Driver;
+use crate::error::CloseAction;
 use crate::error::ConnectionError;
+use crate::error::OpenStreamError;
+use crate::error::PreambleError;
+use crate::error::ReuniteError;
 use crate::error::SendDatagramError;
+use crate::error::StreamOpeningError;
+use crate::error::StreamReadExactError;
+use crate::extension::Unknown;
+use crate::extension::UnknownStats;
+use crate::framing;
+use crate::quota::QuotaAccount;
+use crate::resumption::ResumptionToken;
 use crate::stream::OpeningBiStream;
 use crate::stream::OpeningUniStream;
 use crate::stream::RecvStream;
 use crate::stream::SendStream;
+use crate::stream::StreamEnd;
+use crate::tls::TlsInfo;
+use std::collections::HashMap;
+use std::fmt;
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::task::Context;
+use std::task::Poll;
 use std::time::Duration;
+use tokio::time::Instant;
+use url::Url;
+use wtransport_proto::datagram::DatagramFormat;
+use wtransport_proto::headers::Headers;
 use wtransport_proto::ids::SessionId;
+use wtransport_proto::ids::StreamId;
 use wtransport_proto::varint::VarInt;
 
 /// A WebTransport session connection.
@@ -17,6 +53,19 @@ pub struct Connection {
     quic_connection: quinn::Connection,
     driver: Driver,
     session_id: SessionId,
+    connect_timings: ConnectTimings,
+    quic_version: Option<QuicVersion>,
+    resumption_token: Option<ResumptionToken>,
+    timeouts: Timeouts,
+    datagram_timestamps: bool,
+    draft: Option<String>,
+    draft_header: bool,
+    subprotocol: Option<String>,
+    #[cfg(feature = "compression")]
+    codec: Option<Arc<dyn Compression>>,
+    url: Option<Url>,
+    request_info: SessionRequestInfo,
+    _registration: Option<Registration>,
 }
 
 impl Connection {
@@ -25,39 +74,256 @@ impl Connection {
         driver: Driver,
         session_id: SessionId,
     ) -> Self {
+        driver
+            .activity()
+            .milestones()
+            .record(Milestone::SessionEstablished);
+
         Self {
             quic_connection,
             driver,
             session_id,
+            connect_timings: ConnectTimings::default(),
+            quic_version: None,
+            resumption_token: None,
+            timeouts: Timeouts::default(),
+            datagram_timestamps: false,
+            draft: None,
+            draft_header: false,
+            subprotocol: None,
+            #[cfg(feature = "compression")]
+            codec: None,
+            url: None,
+            request_info: SessionRequestInfo::new(Headers::default()),
+            _registration: None,
+        }
+    }
+
+    pub(crate) fn with_connect_timings(mut self, timings: ConnectTimings) -> Self {
+        self.connect_timings = timings;
+        self
+    }
+
+    pub(crate) fn with_quic_version(mut self, quic_version: Option<QuicVersion>) -> Self {
+        self.quic_version = quic_version;
+        self
+    }
+
+    pub(crate) fn quic_connection(&self) -> &quinn::Connection {
+        &self.quic_connection
+    }
+
+    pub(crate) fn spawner(&self) -> &Spawner {
+        self.driver.spawner()
+    }
+
+    pub(crate) fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    pub(crate) fn with_resumption_token(mut self, token: Option<ResumptionToken>) -> Self {
+        self.resumption_token = token;
+        self
+    }
+
+    pub(crate) fn with_datagram_timestamps(mut self, enabled: bool) -> Self {
+        self.datagram_timestamps = enabled;
+        self
+    }
+
+    pub(crate) fn with_draft(mut self, draft: Option<String>, draft_header: bool) -> Self {
+        self.draft = draft;
+        self.draft_header = draft_header;
+        self
+    }
+
+    pub(crate) fn with_subprotocol(mut self, subprotocol: Option<String>) -> Self {
+        self.subprotocol = subprotocol;
+        self
+    }
+
+    #[cfg(feature = "compression")]
+    pub(crate) fn with_codec(mut self, codec: Option<Arc<dyn Compression>>) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    pub(crate) fn with_url(mut self, url: Option<Url>) -> Self {
+        self.url = url;
+        self
+    }
+
+    pub(crate) fn with_request_info(mut self, request_info: SessionRequestInfo) -> Self {
+        self.request_info = request_info;
+        self
+    }
+
+    pub(crate) fn register(&mut self, registry: &ConnectionsRegistry) {
+        self._registration = Some(registry.register(
+            &self.quic_connection,
+            self.session_id,
+            self.driver.activity(),
+            self.driver.datagram_format(),
+        ));
+    }
+
+    /// Returns the principal the session is accounted to, if it is subject to quotas
+    /// (see [`SessionRequest::set_principal`](crate::endpoint::SessionRequest::set_principal)).
+    pub fn principal(&self) -> Option<&str> {
+        self.driver.quota().map(QuotaAccount::principal)
+    }
+
+    /// Splits the connection into two halves, which can be owned by different tasks.
+    ///
+    /// The [`IncomingHalf`] accepts the streams and datagrams initiated by the peer,
+    /// the [`OutgoingHalf`] opens streams and sends datagrams. Both halves give access
+    /// to the whole connection for other operations (e.g., closing it).
+    ///
+    /// `Connection` is `Send` and `Sync` anyway: it can also be shared by reference
+    /// (or with an [`Arc`]); splitting makes the ownership of each side explicit.
+    /// The connection can be joined again with [`IncomingHalf::reunite`].
+    pub fn split(self) -> (IncomingHalf, OutgoingHalf) {
+        let connection = Arc::new(self);
+
+        (
+            IncomingHalf {
+                connection: connection.clone(),
+            },
+            OutgoingHalf { connection },
+        )
+    }
+
+    /// Returns a handle to this connection.
+    ///
+    /// See [`ConnectionHandle`].
+    pub fn handle(&self) -> ConnectionHandle {
+        ConnectionHandle {
+            quic_connection: self.quic_connection.clone(),
+            session_id: self.session_id,
+            activity: self.driver.activity().clone(),
+            datagram_format: self.driver.datagram_format().clone(),
         }
     }
 
     /// Accepts the next bi-directional stream.
     pub async fn accept_uni(&self) -> Result<RecvStream, ConnectionError> {
+        let (stream, guard) =
+            self.driver
+                .accept_uni(self.session_id)
+                .await
+                .map_err(|driver_error| {
+                    ConnectionError::with_driver_error(driver_error, &self.quic_connection)
+                })?;
+
+        Ok(RecvStream::new(stream.into_stream(), guard))
+    }
+
+    /// Accepts the next uni-directional stream.
+    pub async fn accept_bi(&self) -> Result<(SendStream, RecvStream), ConnectionError> {
+        let (stream, guard) =
+            self.driver
+                .accept_bi(self.session_id)
+                .await
+                .map_err(|driver_error| {
+                    ConnectionError::with_driver_error(driver_error, &self.quic_connection)
+                })?;
+        let stream = stream.into_stream();
+
+        Ok((
+            SendStream::new(stream.0, guard.clone()),
+            RecvStream::new(stream.1, guard),
+        ))
+    }
+
+    /// Polls for the next unidirectional stream.
+    ///
+    /// This is the poll-based variant of [`accept_uni`](Self::accept_uni), for manual
+    /// [`Future`](std::future::Future) implementations. Several tasks can poll concurrently:
+    /// all of them are woken when a stream arrives.
+    pub fn poll_accept_uni(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<RecvStream, ConnectionError>> {
+        self.driver
+            .poll_accept_uni(cx, self.session_id)
+            .map_ok(|(stream, guard)| RecvStream::new(stream.into_stream(), guard))
+            .map_err(|driver_error| {
+                ConnectionError::with_driver_error(driver_error, &self.quic_connection)
+            })
+    }
+
+    /// Polls for the next bidirectional stream.
+    ///
+    /// See [`poll_accept_uni`](Self::poll_accept_uni).
+    pub fn poll_accept_bi(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(SendStream, RecvStream), ConnectionError>> {
+        self.driver
+            .poll_accept_bi(cx, self.session_id)
+            .map_ok(|(stream, guard)| {
+                let stream = stream.into_stream();
+
+                (
+                    SendStream::new(stream.0, guard.clone()),
+                    RecvStream::new(stream.1, guard),
+                )
+            })
+            .map_err(|driver_error| {
+                ConnectionError::with_driver_error(driver_error, &self.quic_connection)
+            })
+    }
+
+    /// Polls for the next application datagram.
+    ///
+    /// See [`poll_accept_uni`](Self::poll_accept_uni).
+    pub fn poll_receive_datagram(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Datagram, ConnectionError>> {
+        self.driver
+            .poll_receive_datagram(cx, self.session_id)
+            .map_err(|driver_error| {
+                ConnectionError::with_driver_error(driver_error, &self.quic_connection)
+            })
+    }
+
+    /// Accepts the next unidirectional stream, if one is ready.
+    ///
+    /// Unlike [`accept_uni`](Self::accept_uni), it never waits: `None` is returned if no
+    /// stream is ready. This suits applications polling the connection at fixed rates
+    /// (e.g., game loops).
+    pub fn try_accept_uni(&self) -> Result<Option<RecvStream>, ConnectionError> {
         let stream = self
             .driver
-            .accept_uni(self.session_id)
-            .await
+            .try_accept_uni(self.session_id)
             .map_err(|driver_error| {
                 ConnectionError::with_driver_error(driver_error, &self.quic_connection)
-            })?
-            .into_stream();
+            })?;
 
-        Ok(RecvStream::new(stream))
+        Ok(stream.map(|(stream, guard)| RecvStream::new(stream.into_stream(), guard)))
     }
 
-    /// Accepts the next uni-directional stream.
-    pub async fn accept_bi(&self) -> Result<(SendStream, RecvStream), ConnectionError> {
+    /// Accepts the next bidirectional stream, if one is ready.
+    ///
+    /// See [`try_accept_uni`](Self::try_accept_uni).
+    pub fn try_accept_bi(&self) -> Result<Option<(SendStream, RecvStream)>, ConnectionError> {
         let stream = self
             .driver
-            .accept_bi(self.session_id)
-            .await
+            .try_accept_bi(self.session_id)
             .map_err(|driver_error| {
                 ConnectionError::with_driver_error(driver_error, &self.quic_connection)
-            })?
-            .into_stream();
+            })?;
+
+        Ok(stream.map(|(stream, guard)| {
+            let stream = stream.into_stream();
 
-        Ok((SendStream::new(stream.0), RecvStream::new(stream.1)))
+            (
+                SendStream::new(stream.0, guard.clone()),
+                RecvStream::new(stream.1, guard),
+            )
+        }))
     }
 
     /// Initiates a new outgoing bidirectional stream.
@@ -80,6 +346,125 @@ impl Connection {
             })
     }
 
+    /// Opens a new outgoing unidirectional stream, within `timeout`.
+    ///
+    /// This combines [`open_uni`](Self::open_uni) and the awaiting of the
+    /// [`OpeningUniStream`] under a single deadline, failing with
+    /// [`StreamOpeningError::TimedOut`].
+    /// If dropped before completion, the stream being opened is aborted.
+    pub async fn open_uni_now(&self, timeout: Duration) -> Result<SendStream, OpenStreamError> {
+        tokio::time::timeout(timeout, async { Ok(self.open_uni().await?.await?) })
+            .await
+            .unwrap_or(Err(StreamOpeningError::TimedOut.into()))
+    }
+
+    /// Opens a new outgoing bidirectional stream, within `timeout`.
+    ///
+    /// This combines [`open_bi`](Self::open_bi) and the awaiting of the
+    /// [`OpeningBiStream`] under a single deadline, failing with
+    /// [`StreamOpeningError::TimedOut`].
+    /// If dropped before completion, the stream being opened is aborted.
+    pub async fn open_bi_now(
+        &self,
+        timeout: Duration,
+    ) -> Result<(SendStream, RecvStream), OpenStreamError> {
+        tokio::time::timeout(timeout, async { Ok(self.open_bi().await?.await?) })
+            .await
+            .unwrap_or(Err(StreamOpeningError::TimedOut.into()))
+    }
+
+    /// Opens a new outgoing bidirectional stream, and sends `preamble` on it.
+    ///
+    /// The preamble is prefixed by its length (a varint), so that the peer reads it
+    /// entirely, and nothing else, with [`accept_bi_with_preamble`](Self::accept_bi_with_preamble).
+    /// It typically tells the purpose of the stream (e.g., a type tag, or request headers).
+    pub async fn open_bi_with_preamble(
+        &self,
+        preamble: &[u8],
+    ) -> Result<(SendStream, RecvStream), PreambleError> {
+        let (mut send_stream, recv_stream) = self.open_bi().await?.await?;
+        framing::write_message(&mut send_stream, preamble).await?;
+
+        Ok((send_stream, recv_stream))
+    }
+
+    /// Accepts the next bidirectional stream, and reads the preamble sent with
+    /// [`open_bi_with_preamble`](Self::open_bi_with_preamble).
+    ///
+    /// Preambles larger than `max_size` bytes are refused with [`PreambleError::TooLarge`].
+    /// The stream is then unusable, as the rest of the preamble is not read.
+    pub async fn accept_bi_with_preamble(
+        &self,
+        max_size: usize,
+    ) -> Result<(Vec<u8>, SendStream, RecvStream), PreambleError> {
+        let (send_stream, mut recv_stream) = self.accept_bi().await?;
+        let preamble = framing::read_message(&mut recv_stream, max_size)
+            .await?
+            .ok_or(PreambleError::Read(StreamReadExactError::FinishedEarly))?;
+
+        Ok((preamble, send_stream, recv_stream))
+    }
+
+    /// Like [`open_bi_with_preamble`](Self::open_bi_with_preamble), with `preamble`
+    /// encoded by `C`.
+    #[cfg(feature = "serde")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+    pub async fn open_bi_with<C, T>(
+        &self,
+        preamble: &T,
+    ) -> Result<(SendStream, RecvStream), PreambleError>
+    where
+        C: Codec,
+        T: serde::Serialize,
+    {
+        let preamble =
+            C::encode(preamble).map_err(|error| PreambleError::Codec(Box::new(error)))?;
+        self.open_bi_with_preamble(&preamble).await
+    }
+
+    /// Like [`accept_bi_with_preamble`](Self::accept_bi_with_preamble), with the preamble
+    /// decoded by `C`.
+    #[cfg(feature = "serde")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+    pub async fn accept_bi_with<C, T>(
+        &self,
+        max_size: usize,
+    ) -> Result<(T, SendStream, RecvStream), PreambleError>
+    where
+        C: Codec,
+        T: serde::de::DeserializeOwned,
+    {
+        let (preamble, send_stream, recv_stream) = self.accept_bi_with_preamble(max_size).await?;
+        let preamble =
+            C::decode(&preamble).map_err(|error| PreambleError::Codec(Box::new(error)))?;
+
+        Ok((preamble, send_stream, recv_stream))
+    }
+
+    /// Initiates a new outgoing unidirectional stream, if the peer's stream limit allows it.
+    ///
+    /// Unlike [`open_uni`](Self::open_uni), it never waits for stream credit: `None` is
+    /// returned if the limit is reached, or if other opens are queued (see
+    /// [`ServerConfigBuilder::stream_open_queue`](crate::config::ServerConfigBuilder::stream_open_queue)).
+    pub fn try_open_uni(&self) -> Result<Option<OpeningUniStream>, ConnectionError> {
+        self.driver
+            .try_open_uni(self.session_id)
+            .map_err(|driver_error| {
+                ConnectionError::with_driver_error(driver_error, &self.quic_connection)
+            })
+    }
+
+    /// Initiates a new outgoing bidirectional stream, if the peer's stream limit allows it.
+    ///
+    /// See [`try_open_uni`](Self::try_open_uni).
+    pub fn try_open_bi(&self) -> Result<Option<OpeningBiStream>, ConnectionError> {
+        self.driver
+            .try_open_bi(self.session_id)
+            .map_err(|driver_error| {
+                ConnectionError::with_driver_error(driver_error, &self.quic_connection)
+            })
+    }
+
     /// Receives an application datagram.
     pub async fn receive_datagram(&self) -> Result<Datagram, ConnectionError> {
         self.driver
@@ -90,7 +475,25 @@ impl Connection {
             })
     }
 
+    /// Receives an application datagram, if one is ready.
+    ///
+    /// See [`try_accept_uni`](Self::try_accept_uni).
+    pub fn try_receive_datagram(&self) -> Result<Option<Datagram>, ConnectionError> {
+        self.driver
+            .try_receive_datagram(self.session_id)
+            .map_err(|driver_error| {
+                ConnectionError::with_driver_error(driver_error, &self.quic_connection)
+            })
+    }
+
     /// Sends an application datagram.
+    ///
+    /// `payload` can be a `&[u8]`, a `Vec<u8>` or a [`Bytes`](bytes::Bytes) (e.g., the payload of
+    /// a received datagram). It is copied once, after the HTTP3 datagram header.
+    /// To forward a received datagram without copy, see [`forward_datagram`](Self::forward_datagram).
+    ///
+    /// It fails with [`SendDatagramError::UnsupportedByPeer`] if the peer does not support
+    /// datagrams (see [`datagram_support`](Self::datagram_support)).
     pub fn send_datagram<D>(&self, payload: D) -> Result<(), SendDatagramError>
     where
         D: AsRef<[u8]>,
@@ -98,16 +501,141 @@ impl Connection {
         self.driver.send_datagram(self.session_id, payload.as_ref())
     }
 
+    /// Sends a batch of application datagrams, returning the result of each one, in order.
+    ///
+    /// All datagrams are encoded first, then queued back to back on the QUIC connection,
+    /// without yielding to the runtime. Hence, the connection driver, woken by the first one,
+    /// typically finds the whole batch queued: it coalesces the datagrams into as few QUIC
+    /// packets as possible, sent with as few system calls as the platform allows (GSO,
+    /// `sendmmsg`).
+    ///
+    /// Unlike [`send_datagram`](Self::send_datagram), queuing a datagram never drops the
+    /// datagrams queued before it: a datagram not fitting in the free space of the QUIC
+    /// datagram send buffer fails with [`SendDatagramError::BufferFull`].
+    pub fn send_datagram_batch<I>(&self, payloads: I) -> Vec<Result<(), SendDatagramError>>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        self.driver.send_datagram_batch(self.session_id, payloads)
+    }
+
+    /// Sends the payload of a datagram received on this or another connection.
+    ///
+    /// If the datagram has the same HTTP3 header as the datagrams of this session (i.e., the
+    /// same session ID and datagram format), the received QUIC datagram is sent as is, without
+    /// copy. Otherwise, this is equivalent to [`send_datagram`](Self::send_datagram).
+    pub fn forward_datagram(&self, datagram: &Datagram) -> Result<(), SendDatagramError> {
+        self.driver.forward_datagram(self.session_id, datagram)
+    }
+
+    /// Accepts the next frame, or unidirectional stream, of unknown type received on the
+    /// connection.
+    ///
+    /// They are only passed to the application with
+    /// [`UnknownPolicy::Surface`](crate::extension::UnknownPolicy::Surface) (see
+    /// [`ServerConfigBuilder::unknown_frames`](crate::config::ServerConfigBuilder::unknown_frames)
+    /// and [`ServerConfigBuilder::unknown_uni_streams`](crate::config::ServerConfigBuilder::unknown_uni_streams)).
+    pub async fn accept_unknown(&self) -> Result<Unknown, ConnectionError> {
+        let event = self.driver.accept_unknown().await.map_err(|driver_error| {
+            ConnectionError::with_driver_error(driver_error, &self.quic_connection)
+        })?;
+
+        Ok(event.into_unknown(|stream| RecvStream::new(stream, self.driver.stream_guard(None))))
+    }
+
+    /// Returns the session stream (i.e., the stream of the CONNECT request).
+    ///
+    /// See [`SessionStream`].
+    #[inline(always)]
+    pub fn session_stream(&self) -> SessionStream<'_> {
+        SessionStream { connection: self }
+    }
+
+    /// Sets the session heartbeat of this connection (`None` to disable it).
+    ///
+    /// This overrides the configured value
+    /// (see [`ServerConfigBuilder::session_heartbeat`](crate::config::ServerConfigBuilder::session_heartbeat)).
+    pub fn set_session_heartbeat(&self, interval: Option<Duration>) {
+        self.driver.set_session_heartbeat(interval);
+    }
+
     /// Close the connection immediately.
     pub fn close(&self, error_code: VarInt, reason: &[u8]) {
         self.quic_connection.close(varint_w2q(error_code), reason);
     }
 
+    /// Close the connection immediately, as described by `action`.
+    ///
+    /// See [`ConnectionError::close_action`].
+    pub fn close_with(&self, action: &CloseAction) {
+        self.close(action.error_code(), action.reason());
+    }
+
+    /// Gracefully closes the connection.
+    ///
+    /// It immediately stops accepting new streams initiated by the peer (they are rejected).
+    /// Then it waits, at most for `timeout`, for all streams in use to be completed; a stream
+    /// is considered completed once the application drops it (i.e., both halves of
+    /// bidirectional streams).
+    /// Finally, the connection is closed with `error_code` and `reason`: streams still
+    /// in use at that point are aborted.
+    pub async fn drain(&self, timeout: Duration, error_code: VarInt, reason: &[u8]) -> DrainReport {
+        self.driver.stop_accepting().await;
+
+        let active_streams = self.driver.active_streams();
+        let _ = tokio::time::timeout(timeout, self.driver.streams_idle()).await;
+        let aborted_streams = self.driver.active_streams();
+
+        self.close(error_code, reason);
+
+        DrainReport {
+            completed_streams: active_streams.saturating_sub(aborted_streams),
+            aborted_streams,
+        }
+    }
+
+    /// Gracefully closes the connection, waiting for streams at most for the
+    /// [`drain`](Timeouts::drain) timeout.
+    ///
+    /// See [`drain`](Self::drain) and [`timeouts`](Self::timeouts).
+    pub async fn shutdown(&self, error_code: VarInt, reason: &[u8]) -> DrainReport {
+        let timeout = self.timeouts.drain.unwrap_or(Duration::MAX);
+        self.drain(timeout, error_code, reason).await
+    }
+
+    /// Returns the timeouts applying to this connection.
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+
     /// Waits for the connection to be closed for any reason.
     pub async fn closed(&self) {
         let _ = self.quic_connection.closed().await;
     }
 
+    /// Registers `hook` to be called, exactly once, when the connection is closed.
+    ///
+    /// The hook receives the close reason, whichever side or code path closed the connection
+    /// (including idle timeouts): it is a convenient place to release external resources
+    /// associated with the session. If the connection is already closed, the hook is called
+    /// as soon as possible.
+    ///
+    /// Hooks are run on tasks spawned like the other internal tasks of the connection
+    /// (see [`ServerConfigBuilder::spawn_with`](crate::config::ServerConfigBuilder::spawn_with)):
+    /// they are not called if the runtime shuts down first.
+    pub fn on_closed<F>(&self, hook: F)
+    where
+        F: FnOnce(ConnectionError) + Send + 'static,
+    {
+        let quic_connection = self.quic_connection.clone();
+
+        self.driver.spawner().spawn(async move {
+            let reason = quic_connection.closed().await;
+            hook(reason.into());
+        });
+    }
+
     /// Returns the WebTransport session identifier.
     #[inline(always)]
     pub fn session_id(&self) -> SessionId {
@@ -144,9 +672,153 @@ impl Connection {
     /// Not necessarily the maximum size of received datagrams.
     #[inline(always)]
     pub fn max_datagram_size(&self) -> Option<usize> {
+        let format = self.driver.datagram_format().negotiated()?;
+
         self.quic_connection
             .max_datagram_size()
-            .map(|quic_max_size| quic_max_size - Datagram::header_size(self.session_id))
+            .map(|quic_max_size| quic_max_size - Datagram::header_size(self.session_id, format))
+    }
+
+    /// Returns whether the peer supports datagrams, and the maximum size of their payload.
+    ///
+    /// Applications can check it to fall back to streams before sending any datagram.
+    pub fn datagram_support(&self) -> DatagramSupport {
+        DatagramSupport {
+            negotiated: self.driver.datagram_format().negotiated().is_some(),
+            max_size: self.max_datagram_size(),
+        }
+    }
+
+    /// Returns whether both endpoints enabled datagram timestamps
+    /// (see [`TimestampedDatagrams`](crate::datagram::TimestampedDatagrams)).
+    #[inline(always)]
+    pub fn datagram_timestamps(&self) -> bool {
+        self.datagram_timestamps
+    }
+
+    /// Returns the format of the HTTP3 datagrams negotiated with the peer.
+    ///
+    /// It is [`DatagramFormat::FlowId`] only if the draft datagrams are enabled (see
+    /// [`ServerConfigBuilder::legacy_datagram_drafts`](crate::config::ServerConfigBuilder::legacy_datagram_drafts))
+    /// and the peer only supports them. The format is taken into account when sending and
+    /// receiving datagrams.
+    #[inline(always)]
+    pub fn datagram_format(&self) -> DatagramFormat {
+        self.driver.datagram_format().get()
+    }
+
+    /// Returns the application protocol negotiated for the session, if any
+    /// (see [`ConnectOptions::subprotocols`](crate::endpoint::ConnectOptions::subprotocols)).
+    #[inline(always)]
+    pub fn subprotocol(&self) -> Option<&str> {
+        self.subprotocol.as_deref()
+    }
+
+    /// Returns the compression codec negotiated for the session, if any
+    /// (see [`Codecs`](crate::compression::Codecs)).
+    ///
+    /// Streams are not compressed implicitly: wrap them into
+    /// [`CompressedSendStream`](crate::compression::CompressedSendStream) and
+    /// [`CompressedRecvStream`](crate::compression::CompressedRecvStream) with this codec.
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    #[inline(always)]
+    pub fn codec(&self) -> Option<&Arc<dyn Compression>> {
+        self.codec.as_ref()
+    }
+
+    /// Returns the URL of the session: the URL connected to on the client, and the URL
+    /// requested by the client on the server (see
+    /// [`SessionRequest::url`](crate::endpoint::SessionRequest::url)).
+    ///
+    /// It is `None` on the server if the `:authority` and `:path` of the request do not
+    /// form a valid URL.
+    #[inline(always)]
+    pub fn url(&self) -> Option<&Url> {
+        self.url.as_ref()
+    }
+
+    /// Returns the authority (host and port, if not the default one) of the
+    /// [URL](Self::url) of the session.
+    #[inline(always)]
+    pub fn authority(&self) -> Option<&str> {
+        self.url.as_ref().map(Url::authority)
+    }
+
+    /// Returns the session request (headers, path, origin, etc.), still available once the
+    /// session is established.
+    #[inline(always)]
+    pub fn session_request_info(&self) -> &SessionRequestInfo {
+        &self.request_info
+    }
+
+    /// Returns the WebTransport draft and the interoperability quirks applied to the
+    /// session (see [`SessionProfile`]).
+    pub fn profile(&self) -> SessionProfile {
+        SessionProfile {
+            draft: self.draft.clone(),
+            draft_header: self.draft_header,
+            datagram_format: self.datagram_format(),
+        }
+    }
+
+    /// Returns the injector of failures into the connection, for chaos testing.
+    ///
+    /// Failures are injected programmatically on the live connection: dropping the next
+    /// datagrams, resetting a stream in use, stalling the flow control. See [`Chaos`].
+    #[cfg(feature = "chaos")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "chaos")))]
+    pub fn chaos(&self) -> Chaos {
+        self.driver.chaos()
+    }
+
+    /// Subscribes to the establishment milestones of the connection.
+    ///
+    /// The milestones already reached are reported first. See [`Milestones`].
+    pub fn milestones(&self) -> Milestones {
+        Milestones {
+            receiver: self.driver.activity().milestones().subscribe(),
+            quic_connection: self.quic_connection.clone(),
+            next: 0,
+        }
+    }
+
+    /// Returns the durations of the connection establishment phases.
+    #[inline(always)]
+    pub fn connect_timings(&self) -> &ConnectTimings {
+        &self.connect_timings
+    }
+
+    /// Returns the QUIC version in use.
+    ///
+    /// On server side, the version is known only if the endpoint supports exactly
+    /// one version (see [`ServerConfigBuilder::quic_versions`](crate::config::ServerConfigBuilder::quic_versions)),
+    /// otherwise `None` is returned.
+    #[inline(always)]
+    pub fn quic_version(&self) -> Option<QuicVersion> {
+        self.quic_version
+    }
+
+    /// Returns the resumption token issued by the server for this session.
+    ///
+    /// The token can be presented on reconnection, with
+    /// [`Endpoint::connect_resuming`](crate::Endpoint::connect_resuming).
+    /// On server side, `None` is always returned.
+    #[inline(always)]
+    pub fn resumption_token(&self) -> Option<&ResumptionToken> {
+        self.resumption_token.as_ref()
+    }
+
+    /// Returns the parameters negotiated by the TLS handshake.
+    ///
+    /// Returns `None` if the endpoint is configured with a custom QUIC configuration
+    /// (see [`quinn-compat`](crate::config#quinn-compat-feature)).
+    pub fn tls_info(&self) -> Option<TlsInfo> {
+        self.quic_connection
+            .handshake_data()?
+            .downcast::<TlsInfo>()
+            .ok()
+            .map(|tls_info| *tls_info)
     }
 
     /// Current best estimate of this connection's latency (round-trip-time).
@@ -154,4 +826,1566 @@ impl Connection {
     pub fn rtt(&self) -> Duration {
         self.quic_connection.rtt()
     }
+
+    /// Returns flow control and congestion statistics, since the connection establishment.
+    ///
+    /// See [`FlowControlStats`].
+    pub fn flow_control_stats(&self) -> FlowControlStats {
+        FlowControlStats::new(&self.quic_connection.stats())
+    }
+
+    /// Returns the application bytes exchanged on the session, per direction and transport.
+    ///
+    /// See [`SessionStats`].
+    pub fn stats(&self) -> SessionStats {
+        self.driver.activity().session_stats()
+    }
+
+    /// Returns the counters of frames and unidirectional streams of unknown type received,
+    /// since the connection establishment.
+    #[inline(always)]
+    pub fn unknown_stats(&self) -> UnknownStats {
+        self.driver.unknown_stats()
+    }
+
+    /// Returns whether the connection is still open.
+    ///
+    /// It becomes `false` as soon as the connection is closed, by either peer or because
+    /// of a timeout.
+    #[inline(always)]
+    pub fn is_alive(&self) -> bool {
+        self.quic_connection.close_reason().is_none()
+    }
+
+    /// Returns the instant of the last application activity on the connection.
+    ///
+    /// Activity includes streams accepted or opened, data read from or written to streams,
+    /// datagrams and capsules sent or received. Session heartbeats
+    /// (see [`ServerConfigBuilder::session_heartbeat`](crate::config::ServerConfigBuilder::session_heartbeat))
+    /// are not counted as activity.
+    #[inline(always)]
+    pub fn last_activity(&self) -> Instant {
+        self.driver.activity().last()
+    }
+
+    /// Returns the time elapsed since the [last activity](Self::last_activity).
+    #[inline(always)]
+    pub fn idle_duration(&self) -> Duration {
+        self.last_activity().elapsed()
+    }
+}
+
+/// Flow control and congestion statistics of a connection.
+///
+/// They help distinguishing the causes of a low throughput:
+///
+///   * the network (congestion events and lost packets are increasing);
+///   * the local application not reading fast enough: the peer reports being blocked by
+///     the local flow control windows (`*_BLOCKED` frames received);
+///   * the peer application not reading fast enough: the peer flow control windows are not
+///     updated (`MAX_DATA` and `MAX_STREAM_DATA` frames received), while data is pending.
+///
+/// **Note**: the underlying QUIC implementation does not send `*_BLOCKED` frames, hence
+/// the local endpoint being blocked by the peer windows is only visible through the
+/// window updates. Statistics are per connection: streams are not tracked individually.
+///
+/// Counters are cumulative; use [`FlowControlStats::since`] to compare two snapshots.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FlowControlStats {
+    pub(crate) peer_data_blocked: u64,
+    pub(crate) peer_stream_data_blocked: u64,
+    pub(crate) peer_streams_blocked: u64,
+    pub(crate) max_data_received: u64,
+    pub(crate) max_stream_data_received: u64,
+    pub(crate) congestion_window: u64,
+    pub(crate) congestion_events: u64,
+    pub(crate) lost_packets: u64,
+}
+
+impl FlowControlStats {
+    fn new(stats: &quinn_proto::ConnectionStats) -> Self {
+        Self {
+            peer_data_blocked: stats.frame_rx.data_blocked,
+            peer_stream_data_blocked: stats.frame_rx.stream_data_blocked,
+            peer_streams_blocked: stats.frame_rx.streams_blocked_bidi
+                + stats.frame_rx.streams_blocked_uni,
+            max_data_received: stats.frame_rx.max_data,
+            max_stream_data_received: stats.frame_rx.max_stream_data,
+            congestion_window: stats.path.cwnd,
+            congestion_events: stats.path.congestion_events,
+            lost_packets: stats.path.lost_packets,
+        }
+    }
+
+    /// Returns the times the peer was blocked by the local connection window
+    /// (`DATA_BLOCKED` frames received).
+    #[inline(always)]
+    pub fn peer_data_blocked(&self) -> u64 {
+        self.peer_data_blocked
+    }
+
+    /// Returns the times the peer was blocked by a local stream window
+    /// (`STREAM_DATA_BLOCKED` frames received).
+    #[inline(always)]
+    pub fn peer_stream_data_blocked(&self) -> u64 {
+        self.peer_stream_data_blocked
+    }
+
+    /// Returns the times the peer was blocked by the local limit of concurrent streams
+    /// (`STREAMS_BLOCKED` frames received).
+    #[inline(always)]
+    pub fn peer_streams_blocked(&self) -> u64 {
+        self.peer_streams_blocked
+    }
+
+    /// Returns the number of connection window updates received (`MAX_DATA` frames).
+    #[inline(always)]
+    pub fn max_data_received(&self) -> u64 {
+        self.max_data_received
+    }
+
+    /// Returns the number of stream window updates received (`MAX_STREAM_DATA` frames).
+    #[inline(always)]
+    pub fn max_stream_data_received(&self) -> u64 {
+        self.max_stream_data_received
+    }
+
+    /// Returns the current congestion window, in bytes.
+    #[inline(always)]
+    pub fn congestion_window(&self) -> u64 {
+        self.congestion_window
+    }
+
+    /// Returns the number of congestion events.
+    #[inline(always)]
+    pub fn congestion_events(&self) -> u64 {
+        self.congestion_events
+    }
+
+    /// Returns the number of lost packets.
+    #[inline(always)]
+    pub fn lost_packets(&self) -> u64 {
+        self.lost_packets
+    }
+
+    /// Returns the counters increments since the `earlier` snapshot.
+    ///
+    /// The congestion window is the one of `self`.
+    pub fn since(&self, earlier: &Self) -> Self {
+        Self {
+            peer_data_blocked: self
+                .peer_data_blocked
+                .saturating_sub(earlier.peer_data_blocked),
+            peer_stream_data_blocked: self
+                .peer_stream_data_blocked
+                .saturating_sub(earlier.peer_stream_data_blocked),
+            peer_streams_blocked: self
+                .peer_streams_blocked
+                .saturating_sub(earlier.peer_streams_blocked),
+            max_data_received: self
+                .max_data_received
+                .saturating_sub(earlier.max_data_received),
+            max_stream_data_received: self
+                .max_stream_data_received
+                .saturating_sub(earlier.max_stream_data_received),
+            congestion_window: self.congestion_window,
+            congestion_events: self
+                .congestion_events
+                .saturating_sub(earlier.congestion_events),
+            lost_packets: self.lost_packets.saturating_sub(earlier.lost_packets),
+        }
+    }
+}
+
+/// Application bytes exchanged on a session, per direction and transport.
+///
+/// Unlike [`FlowControlStats`], accounting is at WebTransport level: only stream data and
+/// datagram payloads, as read and written by the application, are counted (neither QUIC
+/// and HTTP3 framing, nor retransmissions). Counters are cumulative since the session
+/// establishment, and include the streams already dropped.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SessionStats {
+    pub(crate) stream_bytes_sent: u64,
+    pub(crate) stream_bytes_received: u64,
+    pub(crate) datagrams_sent: u64,
+    pub(crate) datagram_bytes_sent: u64,
+    pub(crate) datagrams_received: u64,
+    pub(crate) datagram_bytes_received: u64,
+    pub(crate) streams: Vec<StreamStats>,
+}
+
+impl SessionStats {
+    /// Returns the number of bytes written to streams.
+    #[inline(always)]
+    pub fn stream_bytes_sent(&self) -> u64 {
+        self.stream_bytes_sent
+    }
+
+    /// Returns the number of bytes read from streams.
+    #[inline(always)]
+    pub fn stream_bytes_received(&self) -> u64 {
+        self.stream_bytes_received
+    }
+
+    /// Returns the number of datagrams sent.
+    #[inline(always)]
+    pub fn datagrams_sent(&self) -> u64 {
+        self.datagrams_sent
+    }
+
+    /// Returns the number of payload bytes of the datagrams sent.
+    #[inline(always)]
+    pub fn datagram_bytes_sent(&self) -> u64 {
+        self.datagram_bytes_sent
+    }
+
+    /// Returns the number of datagrams received by the application.
+    #[inline(always)]
+    pub fn datagrams_received(&self) -> u64 {
+        self.datagrams_received
+    }
+
+    /// Returns the number of payload bytes of the datagrams received by the application.
+    #[inline(always)]
+    pub fn datagram_bytes_received(&self) -> u64 {
+        self.datagram_bytes_received
+    }
+
+    /// Returns the number of bytes sent, on streams and in datagrams.
+    #[inline(always)]
+    pub fn bytes_sent(&self) -> u64 {
+        self.stream_bytes_sent + self.datagram_bytes_sent
+    }
+
+    /// Returns the number of bytes received, on streams and in datagrams.
+    #[inline(always)]
+    pub fn bytes_received(&self) -> u64 {
+        self.stream_bytes_received + self.datagram_bytes_received
+    }
+
+    /// Returns the byte counts of the streams in use (i.e., not dropped yet by the
+    /// application), ordered by stream ID.
+    #[inline(always)]
+    pub fn streams(&self) -> &[StreamStats] {
+        &self.streams
+    }
+}
+
+/// Application bytes exchanged on a stream.
+///
+/// See [`SessionStats::streams`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StreamStats {
+    pub(crate) id: StreamId,
+    pub(crate) bytes_sent: u64,
+    pub(crate) bytes_received: u64,
+}
+
+impl StreamStats {
+    /// Returns the [`StreamId`] of the stream.
+    #[inline(always)]
+    pub fn id(&self) -> StreamId {
+        self.id
+    }
+
+    /// Returns the number of bytes written to the stream.
+    ///
+    /// Always `0` for streams opened by the peer, if unidirectional.
+    #[inline(always)]
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
+    /// Returns the number of bytes read from the stream.
+    ///
+    /// Always `0` for streams opened locally, if unidirectional.
+    #[inline(always)]
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+}
+
+/// The header field announcing the WebTransport draft implemented by the server.
+pub(crate) const DRAFT_HEADER: &str = "sec-webtransport-http3-draft";
+
+/// The WebTransport draft and the interoperability quirks applied to a session.
+///
+/// Browsers implement different drafts, hence the server adapts its behavior to the
+/// client: e.g., the [draft header](Self::draft_header) is only sent to Chrome. The
+/// profile is displayed as a compact label (e.g., `draft02+chrome-header`), suited for
+/// logs and interoperability reports.
+///
+/// See [`Connection::profile`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SessionProfile {
+    draft: Option<String>,
+    draft_header: bool,
+    datagram_format: DatagramFormat,
+}
+
+impl SessionProfile {
+    /// Returns the draft implemented by the server (e.g., `draft02`), if known.
+    ///
+    /// On client side, it is known only if announced by the server.
+    #[inline(always)]
+    pub fn draft(&self) -> Option<&str> {
+        self.draft.as_deref()
+    }
+
+    /// Returns whether the `sec-webtransport-http3-draft` header was sent in the session
+    /// response.
+    ///
+    /// Chrome requires the header, Firefox does not support it: the server omits it when
+    /// the user agent is Firefox, unless added back with
+    /// [`SessionRequest::add_response_header`](crate::endpoint::SessionRequest::add_response_header).
+    #[inline(always)]
+    pub fn draft_header(&self) -> bool {
+        self.draft_header
+    }
+
+    /// Returns the format of the HTTP3 datagrams (see [`Connection::datagram_format`]).
+    #[inline(always)]
+    pub fn datagram_format(&self) -> DatagramFormat {
+        self.datagram_format
+    }
+}
+
+impl fmt::Display for SessionProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.draft.as_deref().unwrap_or("unknown"))?;
+
+        if self.draft_header {
+            write!(f, "+chrome-header")?;
+        }
+
+        if self.datagram_format == DatagramFormat::FlowId {
+            write!(f, "+flow-id-datagrams")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Support of datagrams by the peer of a connection (see [`Connection::datagram_support`]).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DatagramSupport {
+    negotiated: bool,
+    max_size: Option<usize>,
+}
+
+impl DatagramSupport {
+    /// Returns whether datagrams can be sent to the peer.
+    #[inline(always)]
+    pub fn is_supported(&self) -> bool {
+        self.max_size.is_some()
+    }
+
+    /// Returns whether the peer negotiated HTTP3 datagrams in its SETTINGS
+    /// (`SETTINGS_H3_DATAGRAM`).
+    #[inline(always)]
+    pub fn negotiated(&self) -> bool {
+        self.negotiated
+    }
+
+    /// Returns the maximum size of the payload of a datagram (see
+    /// [`Connection::max_datagram_size`]), or `None` if datagrams are not supported.
+    ///
+    /// Datagrams are not supported, although negotiated, if the peer does not accept
+    /// QUIC datagram frames.
+    #[inline(always)]
+    pub fn max_size(&self) -> Option<usize> {
+        self.max_size
+    }
+}
+
+/// The session request of a connection, kept once the session is established.
+///
+/// On server side, it is the request received (see
+/// [`SessionRequest`](crate::endpoint::SessionRequest)); on client side, the request sent.
+///
+/// See [`Connection::session_request_info`].
+#[derive(Clone, Debug)]
+pub struct SessionRequestInfo(Headers);
+
+impl SessionRequestInfo {
+    pub(crate) fn new(headers: Headers) -> Self {
+        Self(headers)
+    }
+
+    /// Returns the `:method` field of the request (i.e., `CONNECT`).
+    pub fn method(&self) -> &str {
+        self.0.get(":method").unwrap_or_default()
+    }
+
+    /// Returns the `:protocol` field of the request.
+    pub fn protocol(&self) -> &str {
+        self.0.get(":protocol").unwrap_or_default()
+    }
+
+    /// Returns the `:authority` field of the request.
+    pub fn authority(&self) -> &str {
+        self.0.get(":authority").unwrap_or_default()
+    }
+
+    /// Returns the `:path` field of the request.
+    pub fn path(&self) -> &str {
+        self.0.get(":path").unwrap_or_default()
+    }
+
+    /// Returns the `origin` field of the request if present.
+    pub fn origin(&self) -> Option<&str> {
+        self.0.get("origin")
+    }
+
+    /// Returns the `user-agent` field of the request if present.
+    pub fn user_agent(&self) -> Option<&str> {
+        self.0.get("user-agent")
+    }
+
+    /// Returns all header fields of the request.
+    pub fn headers(&self) -> &Headers {
+        &self.0
+    }
+}
+
+/// Durations of the phases of a connection establishment.
+///
+/// A phase is `None` if it has not been completed (or it does not apply, e.g. DNS
+/// resolution on server side).
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ConnectTimings {
+    pub(crate) dns: Option<Duration>,
+    pub(crate) quic_handshake: Option<Duration>,
+    pub(crate) settings_exchange: Option<Duration>,
+    pub(crate) session_exchange: Option<Duration>,
+}
+
+impl ConnectTimings {
+    /// Returns the duration of the DNS resolution (client only).
+    #[inline(always)]
+    pub fn dns(&self) -> Option<Duration> {
+        self.dns
+    }
+
+    /// Returns the duration of the QUIC handshake (including retries).
+    #[inline(always)]
+    pub fn quic_handshake(&self) -> Option<Duration> {
+        self.quic_handshake
+    }
+
+    /// Returns the time spent waiting for the peer's HTTP3 SETTINGS.
+    #[inline(always)]
+    pub fn settings_exchange(&self) -> Option<Duration> {
+        self.settings_exchange
+    }
+
+    /// Returns the duration of the WebTransport session (`CONNECT`) request/response exchange.
+    ///
+    /// On server side, this includes the time the application took to accept the request.
+    #[inline(always)]
+    pub fn session_exchange(&self) -> Option<Duration> {
+        self.session_exchange
+    }
+
+    /// Returns the sum of all completed phases.
+    pub fn total(&self) -> Duration {
+        [
+            self.dns,
+            self.quic_handshake,
+            self.settings_exchange,
+            self.session_exchange,
+        ]
+        .into_iter()
+        .flatten()
+        .sum()
+    }
+}
+
+/// An establishment milestone of a connection.
+///
+/// Milestones are reached in order, except [`FirstApplicationData`](Self::FirstApplicationData).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Milestone {
+    /// The QUIC handshake is done: 1-RTT keys are ready.
+    HandshakeDone,
+
+    /// The peer's HTTP3 SETTINGS are received.
+    SettingsReceived,
+
+    /// The WebTransport session is established.
+    SessionEstablished,
+
+    /// The application sent or received data (on a stream or a datagram) for the first time.
+    FirstApplicationData,
+}
+
+impl Milestone {
+    const COUNT: usize = 4;
+}
+
+/// A [`Milestone`] and the time it was reached.
+#[derive(Copy, Clone, Debug)]
+pub struct MilestoneEvent {
+    milestone: Milestone,
+    at: Instant,
+}
+
+impl MilestoneEvent {
+    pub(crate) fn new(milestone: Milestone, at: Instant) -> Self {
+        Self { milestone, at }
+    }
+
+    /// Returns the milestone.
+    #[inline(always)]
+    pub fn milestone(&self) -> Milestone {
+        self.milestone
+    }
+
+    /// Returns the time the milestone was reached.
+    #[inline(always)]
+    pub fn at(&self) -> Instant {
+        self.at
+    }
+}
+
+/// Subscription to the establishment milestones of a connection.
+///
+/// See [`Connection::milestones`].
+pub struct Milestones {
+    receiver: tokio::sync::watch::Receiver<Vec<MilestoneEvent>>,
+    quic_connection: quinn::Connection,
+    next: usize,
+}
+
+impl Milestones {
+    /// Returns the next milestone reached.
+    ///
+    /// Returns `None` once all milestones are reported, or the connection is closed.
+    pub async fn next(&mut self) -> Option<MilestoneEvent> {
+        loop {
+            if let Some(event) = self.receiver.borrow_and_update().get(self.next) {
+                self.next += 1;
+                return Some(*event);
+            }
+
+            if self.next == Milestone::COUNT || self.quic_connection.close_reason().is_some() {
+                return None;
+            }
+
+            tokio::select! {
+                result = self.receiver.changed() => result.ok()?,
+                _ = self.quic_connection.closed() => {}
+            }
+        }
+    }
+}
+
+/// Measures consecutive phases.
+pub(crate) struct Stopwatch(Instant);
+
+impl Stopwatch {
+    #[inline(always)]
+    pub(crate) fn start() -> Self {
+        Self(Instant::now())
+    }
+
+    /// Returns the time elapsed since the previous lap (or start).
+    pub(crate) fn lap(&mut self) -> Duration {
+        let now = Instant::now();
+        let elapsed = now - self.0;
+        self.0 = now;
+        elapsed
+    }
+}
+
+/// The session stream of a [`Connection`].
+///
+/// Once the session is established, the stream carrying the CONNECT request
+/// transports a sequence of [capsules](Capsule), which can be used for
+/// application-level handshake payloads.
+/// Capsules are framed by this crate: a capsule is always delivered entirely.
+pub struct SessionStream<'a> {
+    connection: &'a Connection,
+}
+
+impl SessionStream<'_> {
+    /// Sends a capsule to the peer.
+    pub async fn send(&self, capsule: Capsule) -> Result<(), ConnectionError> {
+        self.connection
+            .driver
+            .send_capsule(capsule)
+            .await
+            .map_err(|driver_error| {
+                ConnectionError::with_driver_error(driver_error, &self.connection.quic_connection)
+            })
+    }
+
+    /// Receives the next capsule sent by the peer.
+    pub async fn receive(&self) -> Result<Capsule, ConnectionError> {
+        self.connection
+            .driver
+            .receive_capsule()
+            .await
+            .map_err(|driver_error| {
+                ConnectionError::with_driver_error(driver_error, &self.connection.quic_connection)
+            })
+    }
+
+    /// Returns how the peer ended the session stream, if it already did.
+    ///
+    /// See [`ended`](Self::ended).
+    #[inline(always)]
+    pub fn end(&self) -> Option<StreamEnd> {
+        self.connection.driver.session_stream_end()
+    }
+
+    /// Waits for the peer to end the session stream.
+    ///
+    /// Finishing the session stream ([`StreamEnd::Finished`]) is how a peer terminates
+    /// the session gracefully, typically after a close capsule; a reset
+    /// ([`StreamEnd::Reset`]) aborts it. An error is returned if the connection is closed
+    /// first, without the session stream being ended.
+    pub async fn ended(&self) -> Result<StreamEnd, ConnectionError> {
+        self.connection
+            .driver
+            .session_stream_ended()
+            .await
+            .map_err(|driver_error| {
+                ConnectionError::with_driver_error(driver_error, &self.connection.quic_connection)
+            })
+    }
+}
+
+/// The half of a [`Connection`] receiving what the peer initiates.
+///
+/// See [`Connection::split`].
+pub struct IncomingHalf {
+    connection: Arc<Connection>,
+}
+
+impl IncomingHalf {
+    /// Accepts the next unidirectional stream.
+    pub async fn accept_uni(&self) -> Result<RecvStream, ConnectionError> {
+        self.connection.accept_uni().await
+    }
+
+    /// Accepts the next bidirectional stream.
+    pub async fn accept_bi(&self) -> Result<(SendStream, RecvStream), ConnectionError> {
+        self.connection.accept_bi().await
+    }
+
+    /// Receives an application datagram.
+    pub async fn receive_datagram(&self) -> Result<Datagram, ConnectionError> {
+        self.connection.receive_datagram().await
+    }
+
+    /// See [`Connection::poll_accept_uni`].
+    pub fn poll_accept_uni(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<RecvStream, ConnectionError>> {
+        self.connection.poll_accept_uni(cx)
+    }
+
+    /// See [`Connection::poll_accept_bi`].
+    pub fn poll_accept_bi(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(SendStream, RecvStream), ConnectionError>> {
+        self.connection.poll_accept_bi(cx)
+    }
+
+    /// See [`Connection::poll_receive_datagram`].
+    pub fn poll_receive_datagram(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Datagram, ConnectionError>> {
+        self.connection.poll_receive_datagram(cx)
+    }
+
+    /// See [`Connection::try_accept_uni`].
+    pub fn try_accept_uni(&self) -> Result<Option<RecvStream>, ConnectionError> {
+        self.connection.try_accept_uni()
+    }
+
+    /// See [`Connection::try_accept_bi`].
+    pub fn try_accept_bi(&self) -> Result<Option<(SendStream, RecvStream)>, ConnectionError> {
+        self.connection.try_accept_bi()
+    }
+
+    /// See [`Connection::try_receive_datagram`].
+    pub fn try_receive_datagram(&self) -> Result<Option<Datagram>, ConnectionError> {
+        self.connection.try_receive_datagram()
+    }
+
+    /// Returns the whole connection (e.g., for closing it or reading its statistics).
+    #[inline(always)]
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Joins the halves back into the connection.
+    ///
+    /// It fails if `outgoing` does not come from the same [`Connection::split`].
+    pub fn reunite(self, outgoing: OutgoingHalf) -> Result<Connection, ReuniteError> {
+        if !Arc::ptr_eq(&self.connection, &outgoing.connection) {
+            return Err(ReuniteError::new(self, outgoing));
+        }
+
+        drop(outgoing);
+
+        Ok(Arc::try_unwrap(self.connection)
+            .ok()
+            .expect("Halves are the only owners of the connection"))
+    }
+}
+
+/// The half of a [`Connection`] initiating streams and datagrams.
+///
+/// See [`Connection::split`].
+pub struct OutgoingHalf {
+    connection: Arc<Connection>,
+}
+
+impl OutgoingHalf {
+    /// Initiates a new outgoing unidirectional stream.
+    pub async fn open_uni(&self) -> Result<OpeningUniStream, ConnectionError> {
+        self.connection.open_uni().await
+    }
+
+    /// Initiates a new outgoing bidirectional stream.
+    pub async fn open_bi(&self) -> Result<OpeningBiStream, ConnectionError> {
+        self.connection.open_bi().await
+    }
+
+    /// See [`Connection::open_uni_now`].
+    pub async fn open_uni_now(&self, timeout: Duration) -> Result<SendStream, OpenStreamError> {
+        self.connection.open_uni_now(timeout).await
+    }
+
+    /// See [`Connection::open_bi_now`].
+    pub async fn open_bi_now(
+        &self,
+        timeout: Duration,
+    ) -> Result<(SendStream, RecvStream), OpenStreamError> {
+        self.connection.open_bi_now(timeout).await
+    }
+
+    /// See [`Connection::try_open_uni`].
+    pub fn try_open_uni(&self) -> Result<Option<OpeningUniStream>, ConnectionError> {
+        self.connection.try_open_uni()
+    }
+
+    /// See [`Connection::try_open_bi`].
+    pub fn try_open_bi(&self) -> Result<Option<OpeningBiStream>, ConnectionError> {
+        self.connection.try_open_bi()
+    }
+
+    /// Sends an application datagram.
+    pub fn send_datagram<D>(&self, payload: D) -> Result<(), SendDatagramError>
+    where
+        D: AsRef<[u8]>,
+    {
+        self.connection.send_datagram(payload)
+    }
+
+    /// See [`Connection::send_datagram_batch`].
+    pub fn send_datagram_batch<I>(&self, payloads: I) -> Vec<Result<(), SendDatagramError>>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        self.connection.send_datagram_batch(payloads)
+    }
+
+    /// See [`Connection::forward_datagram`].
+    pub fn forward_datagram(&self, datagram: &Datagram) -> Result<(), SendDatagramError> {
+        self.connection.forward_datagram(datagram)
+    }
+
+    /// Returns the whole connection (e.g., for closing it or reading its statistics).
+    #[inline(always)]
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+}
+
+/// A lightweight handle to a [`Connection`].
+///
+/// It allows closing the connection or sending datagrams without owning the connection
+/// (e.g., from administration tooling).
+///
+/// **Note**: the connection is kept alive as long as any handle exists, therefore handles
+/// should not be stored for long-term usage.
+#[derive(Clone)]
+pub struct ConnectionHandle {
+    quic_connection: quinn::Connection,
+    session_id: SessionId,
+    activity: Activity,
+    datagram_format: SharedDatagramFormat,
+}
+
+impl ConnectionHandle {
+    /// Sends an application datagram.
+    pub fn send_datagram<D>(&self, payload: D) -> Result<(), SendDatagramError>
+    where
+        D: AsRef<[u8]>,
+    {
+        let format = self
+            .datagram_format
+            .negotiated()
+            .ok_or(SendDatagramError::UnsupportedByPeer)?;
+
+        if !self.activity.drops_outgoing_datagram() {
+            crate::driver::send_datagram(
+                &self.quic_connection,
+                self.session_id,
+                payload.as_ref(),
+                format,
+            )?;
+        }
+        self.activity.datagram_sent(payload.as_ref().len());
+        Ok(())
+    }
+
+    /// Close the connection immediately.
+    pub fn close(&self, error_code: VarInt, reason: &[u8]) {
+        self.quic_connection.close(varint_w2q(error_code), reason);
+    }
+
+    /// Close the connection immediately, as described by `action`.
+    ///
+    /// See [`ConnectionError::close_action`].
+    pub fn close_with(&self, action: &CloseAction) {
+        self.close(action.error_code(), action.reason());
+    }
+
+    /// Returns the WebTransport session identifier.
+    #[inline(always)]
+    pub fn session_id(&self) -> SessionId {
+        self.session_id
+    }
+
+    /// Returns the peer's UDP address.
+    #[inline(always)]
+    pub fn remote_address(&self) -> SocketAddr {
+        self.quic_connection.remote_address()
+    }
+
+    /// A stable identifier for this connection.
+    ///
+    /// See [`Connection::stable_id`].
+    #[inline(always)]
+    pub fn stable_id(&self) -> usize {
+        self.quic_connection.stable_id()
+    }
+}
+
+/// Information about a live connection.
+///
+/// See [`Endpoint::connections`](crate::Endpoint::connections).
+pub struct ConnectionInfo {
+    handle: ConnectionHandle,
+    established_at: Instant,
+}
+
+impl ConnectionInfo {
+    /// A stable identifier for the connection.
+    #[inline(always)]
+    pub fn stable_id(&self) -> usize {
+        self.handle.stable_id()
+    }
+
+    /// Returns the WebTransport session identifier.
+    #[inline(always)]
+    pub fn session_id(&self) -> SessionId {
+        self.handle.session_id()
+    }
+
+    /// Returns the peer's UDP address.
+    #[inline(always)]
+    pub fn remote_address(&self) -> SocketAddr {
+        self.handle.remote_address()
+    }
+
+    /// Returns the time elapsed since the session has been established.
+    #[inline(always)]
+    pub fn uptime(&self) -> Duration {
+        self.established_at.elapsed()
+    }
+
+    /// Returns a handle to the connection.
+    #[inline(always)]
+    pub fn handle(&self) -> &ConnectionHandle {
+        &self.handle
+    }
+
+    /// Converts into a handle to the connection.
+    #[inline(always)]
+    pub fn into_handle(self) -> ConnectionHandle {
+        self.handle
+    }
+}
+
+/// Registry of live connections of an endpoint.
+#[derive(Clone, Default)]
+pub(crate) struct ConnectionsRegistry(Arc<Mutex<HashMap<usize, RegistryEntry>>>);
+
+struct RegistryEntry {
+    quic_connection: quinn::Connection,
+    session_id: SessionId,
+    activity: Activity,
+    datagram_format: SharedDatagramFormat,
+    established_at: Instant,
+}
+
+impl ConnectionsRegistry {
+    fn register(
+        &self,
+        quic_connection: &quinn::Connection,
+        session_id: SessionId,
+        activity: &Activity,
+        datagram_format: &SharedDatagramFormat,
+    ) -> Registration {
+        let id = quic_connection.stable_id();
+
+        self.0.lock().expect("Registry lock").insert(
+            id,
+            RegistryEntry {
+                quic_connection: quic_connection.clone(),
+                session_id,
+                activity: activity.clone(),
+                datagram_format: datagram_format.clone(),
+                established_at: Instant::now(),
+            },
+        );
+
+        Registration {
+            registry: self.clone(),
+            id,
+        }
+    }
+
+    /// Returns the connections not closed yet.
+    pub(crate) fn connections(&self) -> Vec<ConnectionInfo> {
+        self.0
+            .lock()
+            .expect("Registry lock")
+            .values()
+            .filter(|entry| entry.quic_connection.close_reason().is_none())
+            .map(|entry| ConnectionInfo {
+                handle: ConnectionHandle {
+                    quic_connection: entry.quic_connection.clone(),
+                    session_id: entry.session_id,
+                    activity: entry.activity.clone(),
+                    datagram_format: entry.datagram_format.clone(),
+                },
+                established_at: entry.established_at,
+            })
+            .collect()
+    }
+}
+
+/// Keeps a connection in the registry till dropped.
+struct Registration {
+    registry: ConnectionsRegistry,
+    id: usize,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Ok(mut lock) = self.registry.0.lock() {
+            lock.remove(&self.id);
+        }
+    }
+}
+
+/// Outcome of [`Connection::drain`].
+#[derive(Copy, Clone, Debug)]
+pub struct DrainReport {
+    completed_streams: usize,
+    aborted_streams: usize,
+}
+
+impl DrainReport {
+    /// Returns the number of streams completed while draining.
+    #[inline(always)]
+    pub fn completed_streams(&self) -> usize {
+        self.completed_streams
+    }
+
+    /// Returns the number of streams aborted because of the drain timeout.
+    #[inline(always)]
+    pub fn aborted_streams(&self) -> usize {
+        self.aborted_streams
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::BudgetLimits;
+    use crate::budget::MemoryBudget;
+
+    #[tokio::test(start_paused = true)]
+    async fn stopwatch_follows_paused_clock() {
+        let mut stopwatch = Stopwatch::start();
+
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        assert_eq!(stopwatch.lap(), Duration::from_secs(30));
+
+        tokio::time::advance(Duration::from_millis(5)).await;
+        assert_eq!(stopwatch.lap(), Duration::from_millis(5));
+    }
+
+    /// Handing connections, streams and requests over to other tasks must not require
+    /// wrapping them (e.g., in a `Mutex`).
+    #[test]
+    fn handover_types_are_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<Connection>();
+        assert_send_sync::<IncomingHalf>();
+        assert_send_sync::<OutgoingHalf>();
+        assert_send_sync::<ConnectionHandle>();
+        assert_send_sync::<SessionStream<'_>>();
+        assert_send_sync::<crate::endpoint::IncomingSession>();
+        assert_send_sync::<crate::endpoint::SessionRequest>();
+        assert_send_sync::<crate::Endpoint<crate::endpoint::Server>>();
+        assert_send_sync::<crate::Endpoint<crate::endpoint::Client>>();
+        assert_send_sync::<SendStream>();
+        assert_send_sync::<RecvStream>();
+        assert_send_sync::<OpeningUniStream>();
+        assert_send_sync::<OpeningBiStream>();
+        assert_send_sync::<crate::stream::ExpiringSendStream>();
+        assert_send_sync::<Datagram>();
+    }
+
+    /// Returns the number of STREAM frames received by `connection`.
+    fn stream_frames_received(connection: &Connection) -> u64 {
+        connection.quic_connection.stats().frame_rx.stream
+    }
+
+    #[tokio::test]
+    async fn session_heartbeat() {
+        let certificate = crate::test_utils::certificate();
+        let peers = crate::test_utils::connect_with(
+            crate::test_utils::server_config(certificate.clone()).build(),
+            crate::test_utils::client_config(&certificate)
+                .session_heartbeat(Duration::from_millis(20))
+                .build(),
+        )
+        .await;
+        let interval = Duration::from_millis(300);
+
+        // Heartbeats of the client reach the server, but are not delivered as capsules.
+        let before = stream_frames_received(&peers.server_connection);
+        let received =
+            tokio::time::timeout(interval, peers.server_connection.session_stream().receive())
+                .await;
+        assert!(received.is_err());
+        assert!(stream_frames_received(&peers.server_connection) >= before + 5);
+
+        // Disabled per connection.
+        peers.client_connection.set_session_heartbeat(None);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let before = stream_frames_received(&peers.server_connection);
+        tokio::time::sleep(interval).await;
+        assert_eq!(stream_frames_received(&peers.server_connection), before);
+
+        // Enabled per connection, on the server.
+        let before = stream_frames_received(&peers.client_connection);
+        peers
+            .server_connection
+            .set_session_heartbeat(Some(Duration::from_millis(20)));
+        tokio::time::sleep(interval).await;
+        assert!(stream_frames_received(&peers.client_connection) >= before + 5);
+    }
+
+    #[tokio::test]
+    async fn milestones() {
+        let peers = crate::test_utils::connect().await;
+        let mut client_milestones = peers.client_connection.milestones();
+        let mut server_milestones = peers.server_connection.milestones();
+
+        for milestones in [&mut client_milestones, &mut server_milestones] {
+            let mut previous = None;
+
+            for expected in [
+                Milestone::HandshakeDone,
+                Milestone::SettingsReceived,
+                Milestone::SessionEstablished,
+            ] {
+                let event = milestones.next().await.unwrap();
+                assert_eq!(event.milestone(), expected);
+                assert!(previous <= Some(event.at()));
+                previous = Some(event.at());
+            }
+        }
+
+        let mut stream = peers
+            .client_connection
+            .open_uni()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        stream.write_all(b"data").await.unwrap();
+
+        let event = client_milestones.next().await.unwrap();
+        assert_eq!(event.milestone(), Milestone::FirstApplicationData);
+        assert!(client_milestones.next().await.is_none());
+
+        // A new subscription reports the milestones already reached.
+        let mut milestones = peers.client_connection.milestones();
+        for _ in 0..Milestone::COUNT {
+            assert!(milestones.next().await.is_some());
+        }
+        assert!(milestones.next().await.is_none());
+
+        // No more milestones once closed.
+        peers.client_connection.close(VarInt::from_u32(0), b"");
+        assert!(server_milestones.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn bi_with_preamble() {
+        let peers = crate::test_utils::connect().await;
+
+        let (mut send_stream, _recv_stream) = peers
+            .client_connection
+            .open_bi_with_preamble(b"kind")
+            .await
+            .unwrap();
+        send_stream.write_all(b"data").await.unwrap();
+        send_stream.finish().await.unwrap();
+
+        let (preamble, _send_stream, mut recv_stream) = peers
+            .server_connection
+            .accept_bi_with_preamble(4)
+            .await
+            .unwrap();
+        assert_eq!(preamble, b"kind");
+
+        // The rest of the stream follows the preamble.
+        let mut data = [0; 4];
+        recv_stream.read_exact(&mut data).await.unwrap();
+        assert_eq!(&data, b"data");
+
+        peers
+            .client_connection
+            .open_bi_with_preamble(b"too large")
+            .await
+            .unwrap();
+        assert!(matches!(
+            peers.server_connection.accept_bi_with_preamble(4).await,
+            Err(PreambleError::TooLarge)
+        ));
+
+        // Finished before the preamble.
+        let (mut send_stream, _recv_stream) = peers
+            .client_connection
+            .open_bi()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        send_stream.finish().await.unwrap();
+        assert!(matches!(
+            peers.server_connection.accept_bi_with_preamble(4).await,
+            Err(PreambleError::Read(StreamReadExactError::FinishedEarly))
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn bi_with_typed_preamble() {
+        struct Json;
+
+        impl Codec for Json {
+            type Error = serde_json::Error;
+
+            fn encode<T>(value: &T) -> Result<Vec<u8>, Self::Error>
+            where
+                T: serde::Serialize,
+            {
+                serde_json::to_vec(value)
+            }
+
+            fn decode<T>(bytes: &[u8]) -> Result<T, Self::Error>
+            where
+                T: serde::de::DeserializeOwned,
+            {
+                serde_json::from_slice(bytes)
+            }
+        }
+
+        let peers = crate::test_utils::connect().await;
+
+        peers
+            .client_connection
+            .open_bi_with::<Json, _>(&("chat", 7u32))
+            .await
+            .unwrap();
+        let (preamble, _send_stream, _recv_stream) = peers
+            .server_connection
+            .accept_bi_with::<Json, (String, u32)>(64)
+            .await
+            .unwrap();
+        assert_eq!(preamble, ("chat".to_string(), 7));
+
+        peers
+            .client_connection
+            .open_bi_with::<Json, _>(&"not a number")
+            .await
+            .unwrap();
+        assert!(matches!(
+            peers
+                .server_connection
+                .accept_bi_with::<Json, u32>(64)
+                .await,
+            Err(PreambleError::Codec(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn session_stats() {
+        let peers = crate::test_utils::connect().await;
+
+        let (mut client_send, mut client_recv) = peers
+            .client_connection
+            .open_bi()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        client_send.write_all(b"hello").await.unwrap();
+        client_send.finish().await.unwrap();
+
+        let (mut server_send, mut server_recv) = peers.server_connection.accept_bi().await.unwrap();
+        let mut data = [0; 5];
+        server_recv.read_exact(&mut data).await.unwrap();
+        server_send.write_all(b"hi").await.unwrap();
+        server_send.finish().await.unwrap();
+
+        let mut data = [0; 2];
+        client_recv.read_exact(&mut data).await.unwrap();
+
+        peers.client_connection.send_datagram(b"abc").unwrap();
+        peers.server_connection.receive_datagram().await.unwrap();
+
+        let client_stats = peers.client_connection.stats();
+        assert_eq!(client_stats.stream_bytes_sent(), 5);
+        assert_eq!(client_stats.stream_bytes_received(), 2);
+        assert_eq!(client_stats.datagrams_sent(), 1);
+        assert_eq!(client_stats.datagram_bytes_sent(), 3);
+        assert_eq!(client_stats.datagrams_received(), 0);
+        assert_eq!(client_stats.bytes_sent(), 8);
+        assert_eq!(client_stats.bytes_received(), 2);
+
+        let server_stats = peers.server_connection.stats();
+        assert_eq!(server_stats.stream_bytes_sent(), 2);
+        assert_eq!(server_stats.stream_bytes_received(), 5);
+        assert_eq!(server_stats.datagrams_received(), 1);
+        assert_eq!(server_stats.datagram_bytes_received(), 3);
+        assert_eq!(server_stats.bytes_received(), 8);
+
+        // Both halves of the stream share the same counters.
+        for (stats, stream_id) in [
+            (&client_stats, client_send.id()),
+            (&server_stats, server_send.id()),
+        ] {
+            let [stream] = stats.streams() else {
+                panic!("Single stream expected: {:?}", stats.streams());
+            };
+            assert_eq!(stream.id(), stream_id);
+            assert_eq!(stream.bytes_sent() + stream.bytes_received(), 7);
+        }
+
+        // Dropped streams are not listed anymore, but still counted.
+        drop((client_send, client_recv));
+        let client_stats = peers.client_connection.stats();
+        assert!(client_stats.streams().is_empty());
+        assert_eq!(client_stats.bytes_sent(), 8);
+    }
+
+    #[test]
+    fn session_profile_label() {
+        let profile = |draft: Option<&str>, draft_header, datagram_format| SessionProfile {
+            draft: draft.map(str::to_string),
+            draft_header,
+            datagram_format,
+        };
+
+        assert_eq!(
+            profile(Some("draft02"), true, DatagramFormat::QuarterStreamId).to_string(),
+            "draft02+chrome-header"
+        );
+        assert_eq!(
+            profile(Some("draft02"), false, DatagramFormat::QuarterStreamId).to_string(),
+            "draft02"
+        );
+        assert_eq!(
+            profile(None, false, DatagramFormat::FlowId).to_string(),
+            "unknown+flow-id-datagrams"
+        );
+    }
+
+    #[tokio::test]
+    async fn session_profile() {
+        let peers = crate::test_utils::connect().await;
+
+        for connection in [&peers.server_connection, &peers.client_connection] {
+            let profile = connection.profile();
+            assert_eq!(profile.draft(), Some("draft02"));
+            assert!(profile.draft_header());
+            assert_eq!(profile.datagram_format(), DatagramFormat::QuarterStreamId);
+        }
+
+        // The application removes the draft header.
+        let certificate = crate::test_utils::certificate();
+        let server =
+            crate::Endpoint::server(crate::test_utils::server_config(certificate.clone()).build())
+                .unwrap();
+        let client =
+            crate::Endpoint::client(crate::test_utils::client_config(&certificate).build())
+                .unwrap();
+
+        let (server_connection, client_connection) = tokio::join!(
+            async {
+                let mut request = server.accept().await.await.unwrap();
+                request.remove_response_header(DRAFT_HEADER);
+                request.accept().await.unwrap()
+            },
+            async {
+                client
+                    .connect(crate::test_utils::url(&server))
+                    .await
+                    .unwrap()
+            },
+        );
+
+        for connection in [&server_connection, &client_connection] {
+            let profile = connection.profile();
+            assert_eq!(profile.draft(), None);
+            assert!(!profile.draft_header());
+            assert_eq!(profile.to_string(), "unknown");
+        }
+    }
+
+    /// Establishes a session to `path` on a new server, returning the URL of the session
+    /// request and both connections.
+    async fn connect_to(path: &str) -> (Option<Url>, Connection, Connection) {
+        let certificate = crate::test_utils::certificate();
+        let server =
+            crate::Endpoint::server(crate::test_utils::server_config(certificate.clone()).build())
+                .unwrap();
+        let client =
+            crate::Endpoint::client(crate::test_utils::client_config(&certificate).build())
+                .unwrap();
+        let url = format!("{}{}", crate::test_utils::url(&server), path);
+
+        let ((request_url, server_connection), client_connection) = tokio::join!(
+            async {
+                let request = server.accept().await.await.unwrap();
+                let request_url = request.url().cloned();
+                (request_url, request.accept().await.unwrap())
+            },
+            async { client.connect(url).await.unwrap() },
+        );
+
+        (request_url, server_connection, client_connection)
+    }
+
+    #[tokio::test]
+    async fn session_url() {
+        let (request_url, server_connection, client_connection) = connect_to("chat?room=1").await;
+
+        let url = client_connection.url().unwrap().clone();
+        assert_eq!(url.path(), "/chat");
+        assert_eq!(url.query(), Some("room=1"));
+        assert_eq!(request_url.as_ref(), Some(&url));
+        assert_eq!(server_connection.url(), Some(&url));
+
+        let authority = format!("localhost:{}", url.port().unwrap());
+        for connection in [&server_connection, &client_connection] {
+            assert_eq!(connection.authority(), Some(authority.as_str()));
+        }
+    }
+
+    #[tokio::test]
+    async fn session_request_info() {
+        let (_, server_connection, client_connection) = connect_to("chat").await;
+        let authority = client_connection.authority().unwrap().to_string();
+
+        for connection in [&server_connection, &client_connection] {
+            let request = connection.session_request_info();
+            assert_eq!(request.method(), "CONNECT");
+            assert_eq!(request.protocol(), "webtransport");
+            assert_eq!(request.authority(), authority);
+            assert_eq!(request.path(), "/chat");
+            assert_eq!(request.headers().get(":scheme"), Some("https"));
+            assert_eq!(request.origin(), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn datagram_support() {
+        let peers = crate::test_utils::connect().await;
+
+        for connection in [&peers.server_connection, &peers.client_connection] {
+            let support = connection.datagram_support();
+            assert!(support.is_supported());
+            assert!(support.negotiated());
+            assert_eq!(support.max_size(), connection.max_datagram_size());
+            assert!(support.max_size().is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn send_datagram_batch() {
+        let peers = crate::test_utils::connect().await;
+
+        let results = peers
+            .client_connection
+            .send_datagram_batch([&b"one"[..], b"two", b"three"]);
+        assert!(results.iter().all(Result::is_ok));
+
+        let too_large = vec![0; 64 * 1024];
+        let results = peers.client_connection.send_datagram_batch([
+            &b"four"[..],
+            &too_large[..],
+            &b"five"[..],
+        ]);
+        assert!(matches!(
+            results[..],
+            [Ok(()), Err(SendDatagramError::TooLarge), Ok(())]
+        ));
+
+        for expected in [&b"one"[..], b"two", b"three", b"four", b"five"] {
+            let datagram = peers.server_connection.receive_datagram().await.unwrap();
+            assert_eq!(&datagram[..], expected);
+        }
+
+        // Small datagrams of a batch share packets.
+        let udp_tx = || {
+            peers
+                .client_connection
+                .quic_connection
+                .stats()
+                .udp_tx
+                .datagrams
+        };
+        let before = udp_tx();
+        let results = peers.client_connection.send_datagram_batch([[0; 10]; 50]);
+        assert!(results.iter().all(Result::is_ok));
+        for _ in 0..50 {
+            peers.server_connection.receive_datagram().await.unwrap();
+        }
+        assert!(udp_tx() - before < 10, "{}", udp_tx() - before);
+    }
+
+    #[tokio::test]
+    async fn send_datagram_batch_buffer_full() {
+        let certificate = crate::test_utils::certificate();
+
+        // A session cap of 16 KiB leaves 1 KiB to the QUIC datagram send buffer.
+        let budget = MemoryBudget::new(BudgetLimits::new(1 << 30).session_cap(16 * 1024));
+        let server_config = crate::test_utils::server_config(certificate.clone())
+            .memory_budget(budget)
+            .build();
+        let client_config = crate::test_utils::client_config(&certificate).build();
+        let peers = crate::test_utils::connect_with(server_config, client_config).await;
+
+        // The connection driver cannot run during the batch on this single-threaded runtime.
+        let results = peers
+            .server_connection
+            .send_datagram_batch((0..10).map(|i| [i; 200]));
+        let sent = results.iter().take_while(|result| result.is_ok()).count();
+        assert!(sent > 0 && sent < 10, "{sent}");
+        assert!(results[sent..]
+            .iter()
+            .all(|result| matches!(result, Err(SendDatagramError::BufferFull))));
+
+        // None of the queued datagrams was dropped to make room for the others.
+        for i in 0..sent {
+            let datagram = peers.client_connection.receive_datagram().await.unwrap();
+            assert_eq!(&datagram[..], &[i as u8; 200][..]);
+        }
+    }
+
+    #[cfg(feature = "quinn-compat")]
+    #[tokio::test]
+    async fn datagram_support_without_quic_datagrams() {
+        let certificate = crate::test_utils::certificate();
+
+        let mut transport_config = quinn::TransportConfig::default();
+        transport_config.datagram_receive_buffer_size(None);
+        let client_config = crate::test_utils::client_config(&certificate)
+            .with_transport_config(transport_config)
+            .build();
+
+        let peers = crate::test_utils::connect_with(
+            crate::test_utils::server_config(certificate).build(),
+            client_config,
+        )
+        .await;
+
+        let support = peers.server_connection.datagram_support();
+        assert!(support.negotiated());
+        assert!(!support.is_supported());
+        assert_eq!(support.max_size(), None);
+        assert!(matches!(
+            peers.server_connection.send_datagram(b"hello"),
+            Err(SendDatagramError::UnsupportedByPeer)
+        ));
+
+        assert!(peers.client_connection.datagram_support().is_supported());
+    }
+
+    #[tokio::test]
+    async fn dropped_opening_stream_is_reset() {
+        let peers = crate::test_utils::connect().await;
+
+        drop(peers.client_connection.open_uni().await.unwrap());
+        drop(peers.client_connection.open_bi().await.unwrap());
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while peers
+                .server_connection
+                .quic_connection
+                .stats()
+                .frame_rx
+                .reset_stream
+                < 2
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Streams reset");
+
+        // The aborted streams are never accepted.
+        let mut stream = peers
+            .client_connection
+            .open_uni()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        stream.write_all(b"next").await.unwrap();
+        stream.finish().await.unwrap();
+
+        let mut stream = peers.server_connection.accept_uni().await.unwrap();
+        let mut buffer = [0; 4];
+        stream.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(&buffer, b"next");
+        assert!(tokio::time::timeout(
+            Duration::from_millis(100),
+            peers.server_connection.accept_bi()
+        )
+        .await
+        .is_err());
+    }
 }
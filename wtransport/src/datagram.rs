@@ -1,26 +1,58 @@
+use crate::error::ConnectionError;
+use crate::error::SendDatagramError;
+use crate::Connection;
 use bytes::Bytes;
+use std::collections::HashMap;
 use std::ops::Deref;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+use std::sync::Weak;
+use std::time::Duration;
+use std::time::SystemTime;
+use tokio::sync::mpsc;
+use tokio::sync::oneshot;
+use tracing::debug;
+use wtransport_proto::bytes::BytesReader;
+use wtransport_proto::bytes::BytesWriter;
 use wtransport_proto::datagram::Datagram as H3Datagram;
+use wtransport_proto::datagram::DatagramFormat;
 use wtransport_proto::error::ErrorCode;
 use wtransport_proto::ids::QStreamId;
 use wtransport_proto::ids::SessionId;
+use wtransport_proto::varint::VarInt;
 
 /// An application Datagram.
 pub struct Datagram {
     quic_dgram: Bytes,
     payload_offset: usize,
     session_id: SessionId,
+    format: DatagramFormat,
 }
 
 impl Datagram {
     /// Returns the datagram payload.
+    ///
+    /// This is a slice of the received QUIC datagram: no data is copied.
     #[inline(always)]
     pub fn payload(&self) -> Bytes {
         self.quic_dgram.slice(self.payload_offset..)
     }
 
-    pub(crate) fn read(quic_dgram: Bytes) -> Result<Self, ErrorCode> {
-        let h3dgram = H3Datagram::read(&quic_dgram)?;
+    /// Returns the datagram payload, consuming the datagram.
+    ///
+    /// Like [`payload`](Self::payload), no data is copied.
+    #[inline(always)]
+    pub fn into_payload(self) -> Bytes {
+        let mut quic_dgram = self.quic_dgram;
+        quic_dgram.split_off(self.payload_offset)
+    }
+
+    pub(crate) fn read(quic_dgram: Bytes, format: DatagramFormat) -> Result<Self, ErrorCode> {
+        let h3dgram = H3Datagram::read_with_format(&quic_dgram, format)?;
         let payload_offset = quic_dgram.len() - h3dgram.payload().len();
         let session_id = h3dgram.qstream_id().into_session_id();
 
@@ -28,11 +60,13 @@ impl Datagram {
             quic_dgram,
             payload_offset,
             session_id,
+            format,
         })
     }
 
-    pub(crate) fn write(session_id: SessionId, payload: &[u8]) -> Self {
-        let h3dgram = H3Datagram::new(QStreamId::from_session_id(session_id), payload);
+    pub(crate) fn write(session_id: SessionId, payload: &[u8], format: DatagramFormat) -> Self {
+        let h3dgram =
+            H3Datagram::new(QStreamId::from_session_id(session_id), payload).with_format(format);
 
         let mut buffer = vec![0; h3dgram.write_size()].into_boxed_slice();
         h3dgram.write(&mut buffer).expect("Preallocated capacity");
@@ -45,12 +79,13 @@ impl Datagram {
             quic_dgram,
             payload_offset,
             session_id,
+            format,
         }
     }
 
     #[inline(always)]
-    pub(crate) fn header_size(session_id: SessionId) -> usize {
-        H3Datagram::header_size(QStreamId::from_session_id(session_id))
+    pub(crate) fn header_size(session_id: SessionId, format: DatagramFormat) -> usize {
+        H3Datagram::header_size_with_format(QStreamId::from_session_id(session_id), format)
     }
 
     /// Returns the associated [`SessionId`].
@@ -59,6 +94,16 @@ impl Datagram {
         self.session_id
     }
 
+    /// Returns the QUIC datagram if it has the HTTP3 header of `session_id` in `format`.
+    #[inline(always)]
+    pub(crate) fn quic_bytes_for(
+        &self,
+        session_id: SessionId,
+        format: DatagramFormat,
+    ) -> Option<&Bytes> {
+        (self.session_id == session_id && self.format == format).then_some(&self.quic_dgram)
+    }
+
     #[inline(always)]
     pub(crate) fn into_quic_bytes(self) -> Bytes {
         self.quic_dgram
@@ -73,3 +118,594 @@ impl Deref for Datagram {
         &self.quic_dgram[self.payload_offset..]
     }
 }
+
+/// Capacity of the queue of each datagram context.
+const CONTEXT_QUEUE_CAPACITY: usize = 64;
+
+/// Queues of the registered datagram contexts.
+#[derive(Clone, Default)]
+struct Contexts(Arc<Mutex<HashMap<VarInt, mpsc::Sender<Bytes>>>>);
+
+impl Contexts {
+    fn lock(&self) -> MutexGuard<'_, HashMap<VarInt, mpsc::Sender<Bytes>>> {
+        // The map of queues remains consistent even if a holder panicked.
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Routes the datagrams of a connection by context ID
+/// (see [RFC 9297](https://www.rfc-editor.org/rfc/rfc9297#section-2.1)).
+///
+/// Each datagram payload starts with a context ID (a varint), followed by the payload
+/// of the context. This allows multiplexing different datagram flows within one session.
+///
+/// Once created, the router takes over the datagrams of the connection: datagrams of
+/// unregistered contexts, or without a valid context ID, are dropped.
+/// Its task is spawned like the other internal tasks of the connection, and it runs until
+/// the connection is closed or the router is dropped. Then, registered contexts receive
+/// `None`.
+pub struct DatagramContexts {
+    connection: Arc<Connection>,
+    contexts: Contexts,
+    dropped: Arc<AtomicU64>,
+    _stop: oneshot::Sender<()>,
+}
+
+impl DatagramContexts {
+    /// Creates a router for the datagrams of `connection`.
+    pub fn new(connection: Arc<Connection>) -> Self {
+        let contexts = Contexts::default();
+        let dropped = Arc::new(AtomicU64::new(0));
+        let (stop, stopped) = oneshot::channel();
+
+        connection.spawner().spawn(Self::route(
+            Arc::downgrade(&connection),
+            contexts.clone(),
+            dropped.clone(),
+            stopped,
+        ));
+
+        Self {
+            connection,
+            contexts,
+            dropped,
+            _stop: stop,
+        }
+    }
+
+    /// Registers the context `context_id`.
+    ///
+    /// Returns `None` if the context is already registered.
+    pub fn register(&self, context_id: VarInt) -> Option<DatagramContext> {
+        let (sender, receiver) = mpsc::channel(CONTEXT_QUEUE_CAPACITY);
+
+        let mut contexts = self.contexts.lock();
+        if contexts.contains_key(&context_id) {
+            return None;
+        }
+        contexts.insert(context_id, sender);
+
+        Some(DatagramContext {
+            context_id,
+            connection: self.connection.clone(),
+            receiver,
+            contexts: self.contexts.clone(),
+        })
+    }
+
+    /// Returns the number of datagrams dropped because of an unregistered context,
+    /// a missing context ID or a lagging context.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    async fn route(
+        connection: Weak<Connection>,
+        contexts: Contexts,
+        dropped: Arc<AtomicU64>,
+        mut stopped: oneshot::Receiver<()>,
+    ) {
+        loop {
+            // The connection is only borrowed while waiting for a datagram: the router
+            // does not keep it alive once every other owner dropped it.
+            let datagram = match connection.upgrade() {
+                Some(connection) => tokio::select! {
+                    datagram = connection.receive_datagram() => datagram,
+                    _ = &mut stopped => break,
+                },
+                None => break,
+            };
+
+            let datagram = match datagram {
+                Ok(datagram) => datagram,
+                Err(_) => break,
+            };
+
+            let mut bytes = &*datagram;
+
+            let context_id = match bytes.get_varint() {
+                Some(context_id) => context_id,
+                None => {
+                    dropped.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+            };
+
+            let payload = datagram.payload().slice(datagram.len() - bytes.len()..);
+
+            let delivered = match contexts.lock().get(&context_id) {
+                // Datagrams are unreliable: they are dropped if the context is lagging.
+                Some(sender) => sender.try_send(payload).is_ok(),
+                None => false,
+            };
+
+            if !delivered {
+                dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        debug!("Datagram routing ended");
+        contexts.lock().clear();
+    }
+}
+
+/// A datagram context registered with [`DatagramContexts::register`].
+///
+/// Dropping it unregisters the context.
+pub struct DatagramContext {
+    context_id: VarInt,
+    connection: Arc<Connection>,
+    receiver: mpsc::Receiver<Bytes>,
+    contexts: Contexts,
+}
+
+impl DatagramContext {
+    /// Returns the context ID.
+    pub fn id(&self) -> VarInt {
+        self.context_id
+    }
+
+    /// Sends a datagram on this context.
+    pub fn send<D>(&self, payload: D) -> Result<(), SendDatagramError>
+    where
+        D: AsRef<[u8]>,
+    {
+        let payload = payload.as_ref();
+
+        let mut buffer = Vec::with_capacity(self.context_id.size() + payload.len());
+        buffer
+            .put_varint(self.context_id)
+            .expect("Vec has unbounded capacity");
+        buffer.extend_from_slice(payload);
+
+        self.connection.send_datagram(buffer)
+    }
+
+    /// Receives the payload of the next datagram of this context.
+    ///
+    /// Returns `None` once the connection is closed.
+    pub async fn receive(&mut self) -> Option<Bytes> {
+        self.receiver.recv().await
+    }
+}
+
+impl Drop for DatagramContext {
+    fn drop(&mut self) {
+        self.contexts.lock().remove(&self.context_id);
+    }
+}
+
+/// Header field of the session request and response, enabling datagram timestamps.
+pub(crate) const DATAGRAM_TIMESTAMPS_HEADER: &str = "wtransport-datagram-timestamps";
+
+/// Size of the timestamp prepended to each datagram.
+const TIMESTAMP_SIZE: usize = 8;
+
+/// Sends and receives datagrams carrying their sending time, measuring their one-way delay
+/// and jitter.
+///
+/// Timestamps are only added if both endpoints enabled them (see
+/// [`ServerConfigBuilder::datagram_timestamps`](crate::config::ServerConfigBuilder::datagram_timestamps)),
+/// which [`Connection::datagram_timestamps`] tells. Otherwise, datagrams are sent and
+/// received unchanged, and no latency is measured. In both cases, payloads are transparent
+/// to the application.
+///
+/// Timestamps are the wall-clock time of the sender, as microseconds since the UNIX epoch
+/// (a big-endian `u64` prepended to the payload). Hence, delays are only accurate if clocks
+/// are synchronized (e.g., with NTP); jitter does not depend on the clock offset.
+pub struct TimestampedDatagrams {
+    connection: Arc<Connection>,
+    latency: Mutex<DatagramLatency>,
+}
+
+impl TimestampedDatagrams {
+    /// Creates a wrapper of the datagrams of `connection`.
+    pub fn new(connection: Arc<Connection>) -> Self {
+        Self {
+            connection,
+            latency: Mutex::new(DatagramLatency::default()),
+        }
+    }
+
+    /// Computes the maximum size of payloads that may be passed to [`send`](Self::send).
+    ///
+    /// See [`Connection::max_datagram_size`].
+    pub fn max_datagram_size(&self) -> Option<usize> {
+        let max_size = self.connection.max_datagram_size()?;
+
+        if self.connection.datagram_timestamps() {
+            max_size.checked_sub(TIMESTAMP_SIZE)
+        } else {
+            Some(max_size)
+        }
+    }
+
+    /// Sends a datagram, with its sending time if timestamps are enabled.
+    pub fn send<D>(&self, payload: D) -> Result<(), SendDatagramError>
+    where
+        D: AsRef<[u8]>,
+    {
+        let payload = payload.as_ref();
+
+        if !self.connection.datagram_timestamps() {
+            return self.connection.send_datagram(payload);
+        }
+
+        let mut buffer = Vec::with_capacity(TIMESTAMP_SIZE + payload.len());
+        buffer.extend_from_slice(&unix_micros().to_be_bytes());
+        buffer.extend_from_slice(payload);
+
+        self.connection.send_datagram(buffer)
+    }
+
+    /// Receives the payload of the next datagram, measuring its delay if timestamps are enabled.
+    ///
+    /// Datagrams too short to carry a timestamp are dropped (see
+    /// [`DatagramLatency::malformed`]).
+    pub async fn receive(&self) -> Result<Bytes, ConnectionError> {
+        loop {
+            let datagram = self.connection.receive_datagram().await?;
+
+            if !self.connection.datagram_timestamps() {
+                return Ok(datagram.into_payload());
+            }
+
+            let mut payload = datagram.into_payload();
+
+            if payload.len() < TIMESTAMP_SIZE {
+                self.lock_latency().malformed += 1;
+                continue;
+            }
+
+            let timestamp = payload.split_to(TIMESTAMP_SIZE);
+            let sent_at = u64::from_be_bytes(timestamp[..].try_into().expect("Timestamp size"));
+            self.lock_latency().record(sent_at, unix_micros());
+
+            return Ok(payload);
+        }
+    }
+
+    /// Returns the latency measured on the datagrams received so far.
+    pub fn latency(&self) -> DatagramLatency {
+        self.lock_latency().clone()
+    }
+
+    fn lock_latency(&self) -> MutexGuard<'_, DatagramLatency> {
+        // Measures remain consistent even if a holder panicked.
+        self.latency.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// One-way delay and jitter of the datagrams received with [`TimestampedDatagrams`].
+#[derive(Clone, Debug, Default)]
+pub struct DatagramLatency {
+    histogram: [u64; DatagramLatency::BUCKETS],
+    samples: u64,
+    malformed: u64,
+    min_delay: Option<Duration>,
+    max_delay: Option<Duration>,
+    jitter_micros: f64,
+    last_transit: Option<i64>,
+}
+
+impl DatagramLatency {
+    /// Number of buckets of the delay [`histogram`](Self::histogram).
+    pub const BUCKETS: usize = 12;
+
+    /// Returns the histogram of the one-way delays.
+    ///
+    /// Bucket `0` counts delays below 1 ms, and bucket `i` delays in `[2^(i-1), 2^i)` ms.
+    /// The last bucket also counts the larger delays. Negative delays (because of a clock
+    /// offset) are counted as `0`.
+    #[inline(always)]
+    pub fn histogram(&self) -> &[u64; Self::BUCKETS] {
+        &self.histogram
+    }
+
+    /// Returns the number of delays measured.
+    #[inline(always)]
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    /// Returns the number of datagrams dropped because they are too short to carry
+    /// a timestamp.
+    #[inline(always)]
+    pub fn malformed(&self) -> u64 {
+        self.malformed
+    }
+
+    /// Returns the smallest one-way delay measured, if any.
+    #[inline(always)]
+    pub fn min_delay(&self) -> Option<Duration> {
+        self.min_delay
+    }
+
+    /// Returns the largest one-way delay measured, if any.
+    #[inline(always)]
+    pub fn max_delay(&self) -> Option<Duration> {
+        self.max_delay
+    }
+
+    /// Returns the interarrival jitter, as estimated by
+    /// [RFC 3550](https://www.rfc-editor.org/rfc/rfc3550#section-6.4.1).
+    pub fn jitter(&self) -> Duration {
+        Duration::from_micros(self.jitter_micros as u64)
+    }
+
+    fn record(&mut self, sent_at: u64, received_at: u64) {
+        let transit = received_at as i64 - sent_at as i64;
+        let delay = Duration::from_micros(transit.max(0) as u64);
+
+        let delay_millis = delay.as_millis() as u64;
+        let bucket = if delay_millis == 0 {
+            0
+        } else {
+            (64 - delay_millis.leading_zeros() as usize).min(Self::BUCKETS - 1)
+        };
+        self.histogram[bucket] += 1;
+        self.samples += 1;
+
+        self.min_delay = Some(self.min_delay.map_or(delay, |min| min.min(delay)));
+        self.max_delay = Some(self.max_delay.map_or(delay, |max| max.max(delay)));
+
+        if let Some(last_transit) = self.last_transit {
+            let difference = (transit - last_transit).unsigned_abs() as f64;
+            self.jitter_micros += (difference - self.jitter_micros) / 16.0;
+        }
+        self.last_transit = Some(transit);
+    }
+}
+
+fn unix_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_micros() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+
+    #[tokio::test]
+    async fn datagram_contexts() {
+        let peers = test_utils::connect().await;
+        let server = DatagramContexts::new(Arc::new(peers.server_connection));
+        let client = DatagramContexts::new(Arc::new(peers.client_connection));
+
+        let mut server_one = server.register(VarInt::from_u32(1)).unwrap();
+        let mut server_two = server.register(VarInt::from_u32(300)).unwrap();
+        assert!(server.register(VarInt::from_u32(1)).is_none());
+
+        let client_one = client.register(VarInt::from_u32(1)).unwrap();
+        let client_two = client.register(VarInt::from_u32(300)).unwrap();
+        let client_three = client.register(VarInt::from_u32(3)).unwrap();
+
+        client_three.send(b"unregistered").unwrap();
+        client_two.send(b"two").unwrap();
+        client_one.send(b"one").unwrap();
+
+        assert_eq!(server_one.receive().await.unwrap(), &b"one"[..]);
+        assert_eq!(server_two.receive().await.unwrap(), &b"two"[..]);
+        assert_eq!(server.dropped(), 1);
+
+        // Dropping a context unregisters it.
+        drop(server_one);
+        let mut server_one = server.register(VarInt::from_u32(1)).unwrap();
+        client_one.send(b"again").unwrap();
+        assert_eq!(server_one.receive().await.unwrap(), &b"again"[..]);
+
+        // Without a context ID.
+        client.connection.send_datagram(b"").unwrap();
+        client_one.send(b"last").unwrap();
+        assert_eq!(server_one.receive().await.unwrap(), &b"last"[..]);
+        assert_eq!(server.dropped(), 2);
+
+        client.connection.close(VarInt::from_u32(0), b"");
+        assert!(server_one.receive().await.is_none());
+        assert!(server_two.receive().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn datagram_contexts_dropped() {
+        let peers = test_utils::connect().await;
+        let server_connection = Arc::new(peers.server_connection);
+
+        let contexts = DatagramContexts::new(server_connection.clone());
+        let mut context = contexts.register(VarInt::from_u32(1)).unwrap();
+
+        // The router stops, releasing the datagrams of the connection.
+        drop(contexts);
+        assert!(context.receive().await.is_none());
+        assert_eq!(Arc::strong_count(&server_connection), 2);
+
+        peers.client_connection.send_datagram(b"\x01raw").unwrap();
+        let datagram = server_connection.receive_datagram().await.unwrap();
+        assert_eq!(datagram.payload().as_ref(), b"\x01raw");
+    }
+
+    fn session_id(stream_id: u32) -> SessionId {
+        SessionId::try_from_session_stream(wtransport_proto::ids::StreamId::new(VarInt::from_u32(
+            stream_id,
+        )))
+        .unwrap()
+    }
+
+    #[test]
+    fn into_payload() {
+        let datagram = Datagram::write(session_id(4), b"payload", DatagramFormat::QuarterStreamId);
+        assert_eq!(datagram.payload(), &b"payload"[..]);
+        assert_eq!(datagram.len(), 7);
+
+        let quic_dgram = datagram.quic_dgram.clone();
+        let payload = datagram.into_payload();
+        assert_eq!(payload, &b"payload"[..]);
+
+        // The payload is a slice of the QUIC datagram.
+        assert_eq!(
+            payload.as_ptr(),
+            quic_dgram[quic_dgram.len() - payload.len()..].as_ptr()
+        );
+    }
+
+    #[test]
+    fn quic_bytes_for() {
+        let datagram = Datagram::write(session_id(4), b"payload", DatagramFormat::QuarterStreamId);
+
+        assert_eq!(
+            datagram
+                .quic_bytes_for(session_id(4), DatagramFormat::QuarterStreamId)
+                .map(|bytes| bytes.as_ptr()),
+            Some(datagram.quic_dgram.as_ptr())
+        );
+        assert!(datagram
+            .quic_bytes_for(session_id(8), DatagramFormat::QuarterStreamId)
+            .is_none());
+        assert!(datagram
+            .quic_bytes_for(session_id(4), DatagramFormat::FlowId)
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn send_bytes_and_forward() {
+        let first = test_utils::connect().await;
+        let second = test_utils::connect().await;
+
+        // Payloads can be `Bytes`.
+        for payload in [&b"one"[..], b"two", b"three"] {
+            first
+                .client_connection
+                .send_datagram(Bytes::from_static(payload))
+                .unwrap();
+        }
+
+        for expected in [&b"one"[..], b"two", b"three"] {
+            let datagram = first.server_connection.receive_datagram().await.unwrap();
+            assert_eq!(datagram.payload(), expected);
+
+            // Back on the same session, and to another connection.
+            first.server_connection.forward_datagram(&datagram).unwrap();
+            second
+                .server_connection
+                .forward_datagram(&datagram)
+                .unwrap();
+
+            let forwarded = first.client_connection.receive_datagram().await.unwrap();
+            assert_eq!(forwarded.payload(), expected);
+            let forwarded = second.client_connection.receive_datagram().await.unwrap();
+            assert_eq!(forwarded.into_payload(), expected);
+        }
+    }
+
+    #[test]
+    fn latency_record() {
+        let mut latency = DatagramLatency::default();
+        assert_eq!(latency.samples(), 0);
+        assert_eq!(latency.min_delay(), None);
+        assert_eq!(latency.jitter(), Duration::ZERO);
+
+        // 500 µs, 3 ms, then 1 s.
+        latency.record(1_000_000, 1_000_500);
+        latency.record(2_000_000, 2_003_000);
+        latency.record(3_000_000, 4_000_000);
+
+        assert_eq!(latency.samples(), 3);
+        assert_eq!(latency.histogram()[0], 1);
+        assert_eq!(latency.histogram()[2], 1);
+        assert_eq!(latency.histogram()[10], 1);
+        assert_eq!(latency.histogram().iter().sum::<u64>(), 3);
+        assert_eq!(latency.min_delay(), Some(Duration::from_micros(500)));
+        assert_eq!(latency.max_delay(), Some(Duration::from_secs(1)));
+
+        // |3000 - 500| / 16, then + (|1_000_000 - 3000| - 156.25) / 16.
+        let jitter = 2500.0 / 16.0;
+        let jitter = jitter + (997_000.0 - jitter) / 16.0;
+        assert_eq!(latency.jitter(), Duration::from_micros(jitter as u64));
+
+        // Large delays go to the last bucket, negative ones (a clock offset) count as 0.
+        latency.record(0, 3_600_000_000);
+        latency.record(5_000_000, 4_000_000);
+        assert_eq!(latency.histogram()[DatagramLatency::BUCKETS - 1], 1);
+        assert_eq!(latency.histogram()[0], 2);
+        assert_eq!(latency.min_delay(), Some(Duration::ZERO));
+        assert_eq!(latency.max_delay(), Some(Duration::from_secs(3600)));
+        assert_eq!(latency.samples(), 5);
+    }
+
+    #[tokio::test]
+    async fn timestamped_datagrams() {
+        let certificate = test_utils::certificate();
+        let peers = test_utils::connect_with(
+            test_utils::server_config(certificate.clone())
+                .datagram_timestamps(true)
+                .build(),
+            test_utils::client_config(&certificate)
+                .datagram_timestamps(true)
+                .build(),
+        )
+        .await;
+        let server = TimestampedDatagrams::new(Arc::new(peers.server_connection));
+        let client = TimestampedDatagrams::new(Arc::new(peers.client_connection));
+        assert!(server.connection.datagram_timestamps());
+
+        client.send(b"ping").unwrap();
+        assert_eq!(server.receive().await.unwrap(), &b"ping"[..]);
+
+        // Too short to carry a timestamp.
+        client.connection.send_datagram(b"short").unwrap();
+        client.send(b"").unwrap();
+        assert_eq!(server.receive().await.unwrap(), &b""[..]);
+
+        let latency = server.latency();
+        assert_eq!(latency.samples(), 2);
+        assert_eq!(latency.malformed(), 1);
+        assert!(latency.max_delay().unwrap() < Duration::from_secs(1));
+        assert_eq!(
+            server.max_datagram_size(),
+            server
+                .connection
+                .max_datagram_size()
+                .map(|size| size - TIMESTAMP_SIZE)
+        );
+    }
+
+    #[tokio::test]
+    async fn timestamped_datagrams_disabled() {
+        let certificate = test_utils::certificate();
+        let peers = test_utils::connect_with(
+            test_utils::server_config(certificate.clone()).build(),
+            test_utils::client_config(&certificate)
+                .datagram_timestamps(true)
+                .build(),
+        )
+        .await;
+        let server = TimestampedDatagrams::new(Arc::new(peers.server_connection));
+        let client = TimestampedDatagrams::new(Arc::new(peers.client_connection));
+        assert!(!client.connection.datagram_timestamps());
+
+        client.send(b"ping").unwrap();
+        assert_eq!(server.receive().await.unwrap(), &b"ping"[..]);
+        assert_eq!(server.latency().samples(), 0);
+    }
+}
@@ -0,0 +1,368 @@
+use std::future::Future;
+use std::io;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::pin::Pin;
+
+/// Maximum number of `AliasMode` records followed by a single lookup.
+const MAX_ALIASES: usize = 8;
+
+/// Future returned by [`HttpsResolver::resolve`].
+pub type ResolveFuture<'a> =
+    Pin<Box<dyn Future<Output = io::Result<Vec<HttpsRecord>>> + Send + 'a>>;
+
+/// Resolver of DNS `HTTPS` records.
+///
+/// This crate does not embed a DNS client: the application integrates its own resolver
+/// (see [`ClientConfigBuilder::https_resolver`](crate::config::ClientConfigBuilder::https_resolver)).
+pub trait HttpsResolver: Send + Sync + 'static {
+    /// Resolves the `HTTPS` records (type 65) of `name`.
+    ///
+    /// For origins on a port other than 443, `name` is already prefixed with the port
+    /// (e.g., `_4433._https.example.com`), as specified by
+    /// [RFC 9460](https://www.rfc-editor.org/rfc/rfc9460#section-9.1).
+    ///
+    /// An empty list means that the name has no such records.
+    fn resolve<'a>(&'a self, name: &'a str) -> ResolveFuture<'a>;
+}
+
+/// A DNS `HTTPS` (or `SVCB`) record (see [RFC 9460](https://www.rfc-editor.org/rfc/rfc9460)).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HttpsRecord {
+    priority: u16,
+    target: String,
+    alpn: Vec<String>,
+    port: Option<u16>,
+    ipv4_hints: Vec<Ipv4Addr>,
+    ipv6_hints: Vec<Ipv6Addr>,
+    ech_config_list: Option<Vec<u8>>,
+}
+
+/// Malformed `HTTPS` record data.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid HTTPS record")]
+pub struct InvalidHttpsRecord;
+
+impl HttpsRecord {
+    const KEY_ALPN: u16 = 1;
+    const KEY_PORT: u16 = 3;
+    const KEY_IPV4_HINT: u16 = 4;
+    const KEY_ECH: u16 = 5;
+    const KEY_IPV6_HINT: u16 = 6;
+
+    /// Creates a record with `priority` and `target` name, without parameters.
+    ///
+    /// A zero `priority` makes an `AliasMode` record. A `.` target stands for the
+    /// queried name itself.
+    pub fn new<T>(priority: u16, target: T) -> Self
+    where
+        T: ToString,
+    {
+        Self {
+            priority,
+            target: target.to_string(),
+            alpn: Vec::new(),
+            port: None,
+            ipv4_hints: Vec::new(),
+            ipv6_hints: Vec::new(),
+            ech_config_list: None,
+        }
+    }
+
+    /// Adds a protocol to the `alpn` parameter.
+    pub fn with_alpn<A>(mut self, alpn: A) -> Self
+    where
+        A: ToString,
+    {
+        self.alpn.push(alpn.to_string());
+        self
+    }
+
+    /// Sets the `port` parameter.
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// Adds an address to the `ipv4hint` or `ipv6hint` parameter.
+    pub fn with_ip_hint(mut self, address: IpAddr) -> Self {
+        match address {
+            IpAddr::V4(address) => self.ipv4_hints.push(address),
+            IpAddr::V6(address) => self.ipv6_hints.push(address),
+        }
+        self
+    }
+
+    /// Sets the `ech` parameter (an `ECHConfigList`).
+    pub fn with_ech_config_list(mut self, ech_config_list: Vec<u8>) -> Self {
+        self.ech_config_list = Some(ech_config_list);
+        self
+    }
+
+    /// Parses a record from its wire-format RDATA.
+    ///
+    /// Unknown parameters are ignored.
+    pub fn parse_rdata(mut rdata: &[u8]) -> Result<Self, InvalidHttpsRecord> {
+        let priority = take_u16(&mut rdata)?;
+        let target = take_name(&mut rdata)?;
+        let mut record = Self::new(priority, target);
+
+        while !rdata.is_empty() {
+            let key = take_u16(&mut rdata)?;
+            let len = take_u16(&mut rdata)? as usize;
+            let mut value = take(&mut rdata, len)?;
+
+            match key {
+                Self::KEY_ALPN => {
+                    while !value.is_empty() {
+                        let len = take(&mut value, 1)?[0] as usize;
+                        let alpn = take(&mut value, len)?;
+                        record.alpn.push(String::from_utf8_lossy(alpn).into_owned());
+                    }
+                }
+                Self::KEY_PORT => record.port = Some(take_u16(&mut value)?),
+                Self::KEY_IPV4_HINT => {
+                    while !value.is_empty() {
+                        let octets: [u8; 4] = take(&mut value, 4)?.try_into().expect("4 bytes");
+                        record.ipv4_hints.push(Ipv4Addr::from(octets));
+                    }
+                }
+                Self::KEY_ECH => record.ech_config_list = Some(value.to_vec()),
+                Self::KEY_IPV6_HINT => {
+                    while !value.is_empty() {
+                        let octets: [u8; 16] = take(&mut value, 16)?.try_into().expect("16 bytes");
+                        record.ipv6_hints.push(Ipv6Addr::from(octets));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(record)
+    }
+
+    /// Returns the priority of the record (lower values are preferred).
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+
+    /// Returns `true` for an `AliasMode` record, redirecting to [`Self::target`].
+    pub fn is_alias(&self) -> bool {
+        self.priority == 0
+    }
+
+    /// Returns the target name, or [`None`] if it is the queried name itself.
+    pub fn target(&self) -> Option<&str> {
+        match self.target.as_str() {
+            "." | "" => None,
+            target => Some(target.trim_end_matches('.')),
+        }
+    }
+
+    /// Returns the protocols of the `alpn` parameter.
+    pub fn alpn(&self) -> impl Iterator<Item = &str> {
+        self.alpn.iter().map(String::as_str)
+    }
+
+    /// Returns `true` if the endpoint supports HTTP3 (hence WebTransport).
+    pub fn supports_h3(&self) -> bool {
+        self.alpn().any(|alpn| alpn == "h3")
+    }
+
+    /// Returns the `port` parameter.
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    /// Returns the addresses of the `ipv4hint` and `ipv6hint` parameters.
+    pub fn ip_hints(&self) -> impl Iterator<Item = IpAddr> + '_ {
+        self.ipv6_hints
+            .iter()
+            .copied()
+            .map(IpAddr::V6)
+            .chain(self.ipv4_hints.iter().copied().map(IpAddr::V4))
+    }
+
+    /// Returns the `ech` parameter (an `ECHConfigList`).
+    pub fn ech_config_list(&self) -> Option<&[u8]> {
+        self.ech_config_list.as_deref()
+    }
+}
+
+/// WebTransport endpoint discovered through `HTTPS` records.
+#[derive(Debug)]
+pub(crate) struct Discovered {
+    pub(crate) host: String,
+    pub(crate) port: u16,
+    pub(crate) ip_hints: Vec<IpAddr>,
+}
+
+/// Looks up the HTTP3 endpoint of the origin `domain:port`.
+///
+/// It returns [`None`] if there are no usable records: the client falls back to
+/// address records of the origin.
+pub(crate) async fn discover(
+    resolver: &dyn HttpsResolver,
+    domain: &str,
+    port: u16,
+) -> io::Result<Option<Discovered>> {
+    let mut name = match port {
+        443 => domain.to_string(),
+        port => format!("_{port}._https.{domain}"),
+    };
+    let mut owner = domain.to_string();
+
+    for _ in 0..MAX_ALIASES {
+        let records = resolver.resolve(&name).await?;
+
+        if let Some(alias) = records.iter().find(|record| record.is_alias()) {
+            match alias.target() {
+                Some(target) => {
+                    name = target.to_string();
+                    owner = target.to_string();
+                    continue;
+                }
+                None => return Ok(None),
+            }
+        }
+
+        let record = records
+            .into_iter()
+            .filter(HttpsRecord::supports_h3)
+            .min_by_key(HttpsRecord::priority);
+
+        return Ok(record.map(|record| Discovered {
+            host: record.target().map(str::to_string).unwrap_or(owner),
+            port: record.port().unwrap_or(port),
+            ip_hints: record.ip_hints().collect(),
+        }));
+    }
+
+    Ok(None)
+}
+
+fn take<'a>(buffer: &mut &'a [u8], len: usize) -> Result<&'a [u8], InvalidHttpsRecord> {
+    if buffer.len() < len {
+        return Err(InvalidHttpsRecord);
+    }
+
+    let (head, tail) = buffer.split_at(len);
+    *buffer = tail;
+    Ok(head)
+}
+
+fn take_u16(buffer: &mut &[u8]) -> Result<u16, InvalidHttpsRecord> {
+    let bytes = take(buffer, 2)?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn take_name(buffer: &mut &[u8]) -> Result<String, InvalidHttpsRecord> {
+    let mut labels = Vec::new();
+
+    loop {
+        let len = take(buffer, 1)?[0] as usize;
+
+        // Name compression is not allowed in SVCB RDATA.
+        if len == 0 {
+            break;
+        } else if len > 63 {
+            return Err(InvalidHttpsRecord);
+        }
+
+        let label = take(buffer, len)?;
+        labels.push(String::from_utf8_lossy(label).into_owned());
+    }
+
+    if labels.is_empty() {
+        Ok(".".to_string())
+    } else {
+        Ok(labels.join("."))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rdata() {
+        let rdata = [
+            0, 1, // priority
+            3, b'w', b'e', b'b', 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 3, b'c', b'o', b'm',
+            0, // target
+            0, 1, 0, 6, 2, b'h', b'3', 2, b'h', b'2', // alpn
+            0, 3, 0, 2, 0x11, 0x51, // port
+            0, 4, 0, 4, 192, 0, 2, 1, // ipv4hint
+            0, 5, 0, 3, 1, 2, 3, // ech
+            0, 99, 0, 1, 0, // unknown
+        ];
+
+        let record = HttpsRecord::parse_rdata(&rdata).unwrap();
+        assert_eq!(record.priority(), 1);
+        assert!(!record.is_alias());
+        assert_eq!(record.target(), Some("web.example.com"));
+        assert_eq!(record.alpn().collect::<Vec<_>>(), ["h3", "h2"]);
+        assert!(record.supports_h3());
+        assert_eq!(record.port(), Some(4433));
+        assert_eq!(
+            record.ip_hints().collect::<Vec<_>>(),
+            [IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))]
+        );
+        assert_eq!(record.ech_config_list(), Some(&[1, 2, 3][..]));
+
+        let record = HttpsRecord::parse_rdata(&[0, 0, 0]).unwrap();
+        assert!(record.is_alias());
+        assert_eq!(record.target(), None);
+
+        assert!(HttpsRecord::parse_rdata(&[0, 1, 0, 0, 3, 0, 2, 0]).is_err());
+        assert!(HttpsRecord::parse_rdata(&[0, 1, 4, b'w']).is_err());
+    }
+
+    struct Records;
+
+    impl HttpsResolver for Records {
+        fn resolve<'a>(&'a self, name: &'a str) -> ResolveFuture<'a> {
+            let records = match name {
+                "example.com" => vec![HttpsRecord::new(0, "cdn.example.net")],
+                "cdn.example.net" => vec![
+                    HttpsRecord::new(2, ".").with_alpn("h3"),
+                    HttpsRecord::new(1, ".").with_alpn("h2"),
+                    HttpsRecord::new(3, "backup.example.net")
+                        .with_alpn("h3")
+                        .with_port(8443),
+                ],
+                "_4433._https.example.org" => vec![HttpsRecord::new(1, "wt.example.org")
+                    .with_alpn("h3")
+                    .with_ip_hint(IpAddr::V4(Ipv4Addr::LOCALHOST))],
+                _ => vec![],
+            };
+
+            Box::pin(async move { Ok(records) })
+        }
+    }
+
+    #[tokio::test]
+    async fn discover() {
+        let discovered = super::discover(&Records, "example.com", 443)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(discovered.host, "cdn.example.net");
+        assert_eq!(discovered.port, 443);
+        assert!(discovered.ip_hints.is_empty());
+
+        let discovered = super::discover(&Records, "example.org", 4433)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(discovered.host, "wt.example.org");
+        assert_eq!(discovered.port, 4433);
+        assert_eq!(discovered.ip_hints, [IpAddr::V4(Ipv4Addr::LOCALHOST)]);
+
+        assert!(super::discover(&Records, "example.org", 443)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
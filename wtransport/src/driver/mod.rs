@@ -1,3 +1,9 @@
+use crate::budget::MemoryAccount;
+use crate::capsule::Capsule;
+#[cfg(feature = "chaos")]
+use crate::chaos::Chaos;
+use crate::config::FallbackResponse;
+use crate::connection::Milestone;
 use crate::datagram::Datagram;
 use crate::driver::streams::biremote::StreamBiRemoteH3;
 use crate::driver::streams::biremote::StreamBiRemoteWT;
@@ -5,63 +11,289 @@ use crate::driver::streams::session::StreamSession;
 use crate::driver::streams::uniremote::StreamUniRemoteWT;
 use crate::driver::streams::Stream;
 use crate::driver::utils::bichannel;
+use crate::driver::utils::poll_once;
 use crate::driver::utils::shared_result;
+use crate::driver::utils::Activity;
+use crate::driver::utils::OpenQueue;
 use crate::driver::utils::SendError;
+use crate::driver::utils::SharedDatagramFormat;
+use crate::driver::utils::SharedReceiver;
 use crate::driver::utils::SharedResultGet;
 use crate::driver::utils::SharedResultSet;
+use crate::driver::utils::Spawner;
+use crate::driver::utils::StreamGuard;
+use crate::driver::utils::StreamsTracker;
+use crate::error::ProtocolPhase;
 use crate::error::SendDatagramError;
+use crate::extension::Grease;
+use crate::extension::UnknownEvent;
+use crate::extension::UnknownHandler;
+use crate::extension::UnknownPolicy;
+use crate::extension::UnknownStats;
+use crate::quota::QuotaAccount;
 use crate::stream::OpeningBiStream;
 use crate::stream::OpeningUniStream;
+use crate::stream::StreamEnd;
+use bytes::Bytes;
+use std::borrow::Cow;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::ready;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
 use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::watch;
 use tokio::sync::Mutex;
 use tracing::debug;
 use tracing::debug_span;
 use tracing::instrument;
 use tracing::trace;
+use tracing::warn;
 use tracing::Instrument;
 use utils::BiChannelEndpoint;
+use wtransport_proto::datagram::DatagramFormat;
 use wtransport_proto::error::ErrorCode;
 use wtransport_proto::frame::Frame;
+use wtransport_proto::frame::FrameKind;
+use wtransport_proto::headers::HeadersLimits;
 use wtransport_proto::ids::SessionId;
+use wtransport_proto::ids::StreamId;
+use wtransport_proto::session::HeadersParseError;
 use wtransport_proto::session::SessionRequest;
 use wtransport_proto::settings::Settings;
 
 #[derive(Copy, Clone, Debug)]
 pub enum DriverError {
-    Proto(ErrorCode),
+    Proto(Violation),
     NotConnected,
 }
 
+impl DriverError {
+    #[inline(always)]
+    pub fn proto(error_code: ErrorCode, rule: &'static str) -> Self {
+        DriverError::Proto(Violation::new(error_code, rule))
+    }
+}
+
+/// An HTTP3 protocol violation, closing the connection.
+#[derive(Copy, Clone, Debug)]
+pub struct Violation {
+    pub error_code: ErrorCode,
+    pub rule: &'static str,
+    pub stream_id: Option<StreamId>,
+    pub frame_kind: Option<FrameKind>,
+    pub phase: Option<ProtocolPhase>,
+}
+
+impl Violation {
+    pub fn new(error_code: ErrorCode, rule: &'static str) -> Self {
+        Self {
+            error_code,
+            rule,
+            stream_id: None,
+            frame_kind: None,
+            phase: None,
+        }
+    }
+
+    pub fn on_stream(mut self, stream_id: StreamId) -> Self {
+        self.stream_id = Some(stream_id);
+        self
+    }
+
+    pub fn with_frame(mut self, frame_kind: FrameKind) -> Self {
+        self.frame_kind = Some(frame_kind);
+        self
+    }
+
+    pub fn in_phase(mut self, phase: ProtocolPhase) -> Self {
+        self.phase = Some(phase);
+        self
+    }
+}
+
+/// Logs `violation` and closes the QUIC connection with its error code.
+pub fn close_on_violation(quic_connection: &quinn::Connection, violation: &Violation) {
+    warn!(
+        peer = %quic_connection.remote_address(),
+        stream_id = ?violation.stream_id,
+        frame_kind = ?violation.frame_kind,
+        phase = ?violation.phase,
+        rule = violation.rule,
+        error_code = ?violation.error_code,
+        "Protocol violation: closing connection"
+    );
+
+    quic_connection.close(utils::varint_w2q(violation.error_code.to_code()), b"");
+}
+
+#[derive(Clone, Debug)]
+pub struct DriverConfig {
+    /// Maximum number of session requests read from the wire but not yet accepted.
+    pub max_pending_sessions: usize,
+
+    /// Spawner for internal tasks.
+    pub spawner: Spawner,
+
+    /// Capacity of the queues of stream opens waiting for credit (`None` for no queuing).
+    pub open_queue_capacity: Option<usize>,
+
+    /// Idle interval after which a heartbeat capsule is sent on the session stream.
+    pub session_heartbeat: Option<Duration>,
+
+    /// Whether the `:scheme` of session requests must be exactly `https`.
+    pub strict_scheme: bool,
+
+    /// Whether HTTP3 datagrams of older drafts are advertised and negotiated.
+    pub legacy_datagrams: bool,
+
+    /// Extended CONNECT protocols accepted in addition to `webtransport`.
+    pub extended_connect_protocols: Arc<[String]>,
+
+    /// Response served to requests which are not session requests (`None` to reject them).
+    pub fallback_response: Option<Arc<FallbackResponse>>,
+
+    /// Maximum number of events processed by the worker before yielding.
+    pub event_budget: usize,
+
+    /// What to do with frames of unknown type.
+    pub unknown_frames: UnknownPolicy,
+
+    /// What to do with unidirectional streams of unknown type.
+    pub unknown_uni_streams: UnknownPolicy,
+
+    /// Reserved settings, frames and streams sent to the peer.
+    pub grease: Grease,
+
+    /// Whether datagram timestamps are requested (client), or accepted (server).
+    pub datagram_timestamps: bool,
+
+    /// Limits of the received field sections (session requests or responses).
+    pub header_limits: HeadersLimits,
+
+    /// Delay before sending the SETTINGS (chaos testing).
+    #[cfg(feature = "chaos")]
+    pub settings_delay: Option<Duration>,
+}
+
+impl Default for DriverConfig {
+    fn default() -> Self {
+        Self {
+            max_pending_sessions: 1,
+            spawner: Spawner::default(),
+            open_queue_capacity: None,
+            session_heartbeat: None,
+            strict_scheme: true,
+            legacy_datagrams: false,
+            extended_connect_protocols: Arc::from(Vec::new()),
+            fallback_response: None,
+            event_budget: 32,
+            unknown_frames: UnknownPolicy::default(),
+            unknown_uni_streams: UnknownPolicy::default(),
+            grease: Grease::default(),
+            datagram_timestamps: false,
+            header_limits: HeadersLimits::default(),
+            #[cfg(feature = "chaos")]
+            settings_delay: None,
+        }
+    }
+}
+
 pub struct Driver {
     quic_connection: quinn::Connection,
     ready_settings: Mutex<mpsc::Receiver<Settings>>,
     ready_sessions: BiChannelEndpoint<StreamSession>,
-    ready_uni_wt_streams: Mutex<mpsc::Receiver<StreamUniRemoteWT>>,
-    ready_bi_wt_streams: Mutex<mpsc::Receiver<StreamBiRemoteWT>>,
-    ready_datagrams: Mutex<mpsc::Receiver<Datagram>>,
+    ready_uni_wt_streams: SharedReceiver<StreamUniRemoteWT>,
+    ready_bi_wt_streams: SharedReceiver<StreamBiRemoteWT>,
+    ready_datagrams: SharedReceiver<Datagram>,
+    datagram_format: SharedDatagramFormat,
+    ready_capsules: Mutex<mpsc::Receiver<Capsule>>,
+    outgoing_capsules: mpsc::Sender<Capsule>,
+    session_stream_end: watch::Receiver<Option<StreamEnd>>,
+    session_heartbeat: watch::Sender<Option<Duration>>,
     driver_result: SharedResultGet<DriverError>,
+    draining: Arc<AtomicBool>,
+    streams_tracker: StreamsTracker,
+    activity: Activity,
+    spawner: Spawner,
+    uni_open_queue: Option<OpenQueue>,
+    bi_open_queue: Option<OpenQueue>,
+    quota: Option<QuotaAccount>,
+    memory: MemoryAccount,
+    unknown: UnknownHandler,
+    ready_unknown: Mutex<mpsc::Receiver<UnknownEvent>>,
+    grease: Grease,
 }
 
 impl Driver {
-    pub fn init(quic_connection: quinn::Connection) -> Self {
+    pub fn init(
+        quic_connection: quinn::Connection,
+        config: DriverConfig,
+        memory: MemoryAccount,
+    ) -> Self {
         let ready_settings = mpsc::channel(1);
-        let ready_sessions = bichannel(1);
+        let ready_sessions = bichannel(config.max_pending_sessions.max(1));
         let ready_uni_wt_streams = mpsc::channel(4);
         let ready_bi_wt_streams = mpsc::channel(1);
         let ready_datagrams = mpsc::channel(1);
+        let ready_capsules = mpsc::channel(4);
+        let outgoing_capsules = mpsc::channel(4);
+        let session_stream_end = watch::channel(None);
+        let session_heartbeat = watch::channel(config.session_heartbeat);
         let driver_result = shared_result();
+        let draining = Arc::new(AtomicBool::new(false));
+        let datagram_format = SharedDatagramFormat::default();
+        let spawner = config.spawner.clone();
+        let ready_unknown = mpsc::channel(8);
+        let unknown = UnknownHandler::new(
+            config.unknown_frames,
+            config.unknown_uni_streams,
+            ready_unknown.0,
+        );
+
+        let activity = Activity::new();
+        activity.milestones().record(Milestone::HandshakeDone);
+
+        #[cfg(feature = "chaos")]
+        let settings_delay = config.settings_delay;
+
+        let worker = worker::Worker::new(
+            quic_connection.clone(),
+            ready_settings.0,
+            ready_sessions.0,
+            ready_uni_wt_streams.0,
+            ready_bi_wt_streams.0,
+            ready_datagrams.0,
+            datagram_format.clone(),
+            (ready_capsules.0, outgoing_capsules.1, session_stream_end.0),
+            memory.clone(),
+            session_heartbeat.1,
+            driver_result.0,
+            draining.clone(),
+            config.spawner,
+            config.strict_scheme,
+            config.header_limits,
+            config.legacy_datagrams,
+            config.extended_connect_protocols,
+            config.fallback_response,
+            config.event_budget,
+            unknown.clone(),
+            config.grease,
+            activity.milestones().clone(),
+        );
+
+        spawner.clone().spawn(
+            async move {
+                #[cfg(feature = "chaos")]
+                if let Some(delay) = settings_delay {
+                    tokio::time::sleep(delay).await;
+                }
 
-        tokio::spawn(
-            worker::Worker::new(
-                quic_connection.clone(),
-                ready_settings.0,
-                ready_sessions.0,
-                ready_uni_wt_streams.0,
-                ready_bi_wt_streams.0,
-                ready_datagrams.0,
-                driver_result.0,
-            )
-            .run()
+                worker.run().await
+            }
             .instrument(debug_span!("Driver", quic_id = quic_connection.stable_id())),
         );
 
@@ -69,13 +301,35 @@ impl Driver {
             quic_connection,
             ready_settings: Mutex::new(ready_settings.1),
             ready_sessions: ready_sessions.1,
-            ready_uni_wt_streams: Mutex::new(ready_uni_wt_streams.1),
-            ready_bi_wt_streams: Mutex::new(ready_bi_wt_streams.1),
-            ready_datagrams: Mutex::new(ready_datagrams.1),
+            ready_uni_wt_streams: SharedReceiver::new(ready_uni_wt_streams.1),
+            ready_bi_wt_streams: SharedReceiver::new(ready_bi_wt_streams.1),
+            ready_datagrams: SharedReceiver::new(ready_datagrams.1),
+            datagram_format,
+            ready_capsules: Mutex::new(ready_capsules.1),
+            outgoing_capsules: outgoing_capsules.0,
+            session_stream_end: session_stream_end.1,
+            session_heartbeat: session_heartbeat.0,
             driver_result: driver_result.1,
+            draining,
+            streams_tracker: StreamsTracker::new(),
+            activity,
+            spawner,
+            uni_open_queue: config.open_queue_capacity.map(OpenQueue::new),
+            bi_open_queue: config.open_queue_capacity.map(OpenQueue::new),
+            quota: None,
+            memory,
+            unknown,
+            ready_unknown: Mutex::new(ready_unknown.1),
+            grease: config.grease,
         }
     }
 
+    /// Returns the injector of failures into the connection.
+    #[cfg(feature = "chaos")]
+    pub fn chaos(&self) -> Chaos {
+        Chaos::new(self.quic_connection.clone(), self.activity.clone())
+    }
+
     pub async fn accept_settings(&self) -> Result<Settings, DriverError> {
         let mut lock = self.ready_settings.lock().await;
 
@@ -115,120 +369,522 @@ impl Driver {
     pub async fn accept_uni(
         &self,
         session_id: SessionId,
-    ) -> Result<StreamUniRemoteWT, DriverError> {
-        let mut lock = self.ready_uni_wt_streams.lock().await;
+    ) -> Result<(StreamUniRemoteWT, StreamGuard), DriverError> {
+        std::future::poll_fn(|cx| self.poll_accept_uni(cx, session_id)).await
+    }
 
+    pub fn poll_accept_uni(
+        &self,
+        cx: &mut Context<'_>,
+        session_id: SessionId,
+    ) -> Poll<Result<(StreamUniRemoteWT, StreamGuard), DriverError>> {
         loop {
-            let stream = match lock.recv().await {
-                Some(stream) => stream,
-                None => return Err(self.result().await),
-            };
-
-            if stream.session_id() == session_id {
-                return Ok(stream);
-            } else {
-                debug!(
-                    "Discarding WT stream (stream_id: {}, session_id: {})",
-                    stream.id(),
-                    stream.session_id()
-                );
+            match ready!(self.ready_uni_wt_streams.poll_recv(cx)) {
+                Some(stream) if stream.session_id() == session_id => {
+                    match self.remote_stream_guard(session_id) {
+                        Some(guard) => return Poll::Ready(Ok((stream, guard))),
+                        None => refuse_uni_stream(stream),
+                    }
+                }
+                Some(stream) => discard_uni_stream(stream),
+                None => return Poll::Ready(Err(self.try_result())),
+            }
+        }
+    }
 
-                stream
-                    .into_stream()
-                    .stop(ErrorCode::BufferedStreamRejected.to_code())
-                    .expect("Stream not already stopped");
+    /// Like [`accept_uni`](Self::accept_uni), returning `None` if no stream is ready.
+    pub fn try_accept_uni(
+        &self,
+        session_id: SessionId,
+    ) -> Result<Option<(StreamUniRemoteWT, StreamGuard)>, DriverError> {
+        loop {
+            match self.ready_uni_wt_streams.try_recv() {
+                Ok(stream) if stream.session_id() == session_id => {
+                    match self.remote_stream_guard(session_id) {
+                        Some(guard) => return Ok(Some((stream, guard))),
+                        None => refuse_uni_stream(stream),
+                    }
+                }
+                Ok(stream) => discard_uni_stream(stream),
+                Err(TryRecvError::Empty) => return Ok(None),
+                Err(TryRecvError::Disconnected) => return Err(self.try_result()),
             }
         }
     }
 
-    pub async fn accept_bi(&self, session_id: SessionId) -> Result<StreamBiRemoteWT, DriverError> {
-        let mut lock = self.ready_bi_wt_streams.lock().await;
+    pub async fn accept_bi(
+        &self,
+        session_id: SessionId,
+    ) -> Result<(StreamBiRemoteWT, StreamGuard), DriverError> {
+        std::future::poll_fn(|cx| self.poll_accept_bi(cx, session_id)).await
+    }
 
+    pub fn poll_accept_bi(
+        &self,
+        cx: &mut Context<'_>,
+        session_id: SessionId,
+    ) -> Poll<Result<(StreamBiRemoteWT, StreamGuard), DriverError>> {
         loop {
-            let stream = match lock.recv().await {
-                Some(stream) => stream,
-                None => return Err(self.result().await),
-            };
-
-            if stream.session_id() == session_id {
-                return Ok(stream);
-            } else {
-                debug!(
-                    "Discarding WT stream (stream_id: {}, session_id: {})",
-                    stream.id(),
-                    stream.session_id()
-                );
+            match ready!(self.ready_bi_wt_streams.poll_recv(cx)) {
+                Some(stream) if stream.session_id() == session_id => {
+                    match self.remote_stream_guard(session_id) {
+                        Some(guard) => return Poll::Ready(Ok((stream, guard))),
+                        None => refuse_bi_stream(stream),
+                    }
+                }
+                Some(stream) => discard_bi_stream(stream),
+                None => return Poll::Ready(Err(self.try_result())),
+            }
+        }
+    }
 
-                stream
-                    .into_stream()
-                    .1
-                    .stop(ErrorCode::BufferedStreamRejected.to_code())
-                    .expect("Stream not already stopped");
+    /// Like [`accept_bi`](Self::accept_bi), returning `None` if no stream is ready.
+    pub fn try_accept_bi(
+        &self,
+        session_id: SessionId,
+    ) -> Result<Option<(StreamBiRemoteWT, StreamGuard)>, DriverError> {
+        loop {
+            match self.ready_bi_wt_streams.try_recv() {
+                Ok(stream) if stream.session_id() == session_id => {
+                    match self.remote_stream_guard(session_id) {
+                        Some(guard) => return Ok(Some((stream, guard))),
+                        None => refuse_bi_stream(stream),
+                    }
+                }
+                Ok(stream) => discard_bi_stream(stream),
+                Err(TryRecvError::Empty) => return Ok(None),
+                Err(TryRecvError::Disconnected) => return Err(self.try_result()),
             }
         }
     }
 
     pub async fn receive_datagram(&self, session_id: SessionId) -> Result<Datagram, DriverError> {
-        let mut lock = self.ready_datagrams.lock().await;
+        std::future::poll_fn(|cx| self.poll_receive_datagram(cx, session_id)).await
+    }
 
+    pub fn poll_receive_datagram(
+        &self,
+        cx: &mut Context<'_>,
+        session_id: SessionId,
+    ) -> Poll<Result<Datagram, DriverError>> {
         loop {
-            let datagram = match lock.recv().await {
-                Some(datagram) => datagram,
-                None => {
-                    return Err(self.result().await);
+            let datagram = ready!(self.ready_datagrams.poll_recv(cx));
+            if let Some(datagram) = &datagram {
+                self.memory.release(datagram.len());
+            }
+
+            match datagram {
+                Some(datagram)
+                    if datagram.session_id() == session_id
+                        && self.activity.drops_incoming_datagram() =>
+                {
+                    debug!("Incoming datagram dropped (chaos)");
                 }
-            };
+                Some(datagram) if datagram.session_id() == session_id => {
+                    self.activity.datagram_received(datagram.len());
+                    return Poll::Ready(Ok(datagram));
+                }
+                Some(datagram) => discard_datagram(datagram),
+                None => return Poll::Ready(Err(self.try_result())),
+            }
+        }
+    }
 
-            if datagram.session_id() == session_id {
-                return Ok(datagram);
+    /// Like [`receive_datagram`](Self::receive_datagram), returning `None` if no datagram is ready.
+    pub fn try_receive_datagram(
+        &self,
+        session_id: SessionId,
+    ) -> Result<Option<Datagram>, DriverError> {
+        loop {
+            let datagram = self.ready_datagrams.try_recv();
+            if let Ok(datagram) = &datagram {
+                self.memory.release(datagram.len());
             }
 
-            debug!(
-                "Incoming datagram discarded (session_id: {})",
-                datagram.session_id()
-            );
+            match datagram {
+                Ok(datagram)
+                    if datagram.session_id() == session_id
+                        && self.activity.drops_incoming_datagram() =>
+                {
+                    debug!("Incoming datagram dropped (chaos)");
+                }
+                Ok(datagram) if datagram.session_id() == session_id => {
+                    self.activity.datagram_received(datagram.len());
+                    return Ok(Some(datagram));
+                }
+                Ok(datagram) => discard_datagram(datagram),
+                Err(TryRecvError::Empty) => return Ok(None),
+                Err(TryRecvError::Disconnected) => return Err(self.try_result()),
+            }
         }
     }
 
+    pub async fn receive_capsule(&self) -> Result<Capsule, DriverError> {
+        let mut lock = self.ready_capsules.lock().await;
+
+        match lock.recv().await {
+            Some(capsule) => {
+                self.memory.release(capsule.payload().len());
+                self.activity.touch();
+                Ok(capsule)
+            }
+            None => Err(self.result().await),
+        }
+    }
+
+    pub async fn send_capsule(&self, capsule: Capsule) -> Result<(), DriverError> {
+        match self.outgoing_capsules.send(capsule).await {
+            Ok(()) => {
+                self.activity.touch();
+                Ok(())
+            }
+            Err(mpsc::error::SendError(_)) => Err(self.result().await),
+        }
+    }
+
+    /// Returns how the peer ended the session stream, if it did.
+    #[inline(always)]
+    pub fn session_stream_end(&self) -> Option<StreamEnd> {
+        *self.session_stream_end.borrow()
+    }
+
+    /// Waits for the peer to end the session stream.
+    pub async fn session_stream_ended(&self) -> Result<StreamEnd, DriverError> {
+        let mut end = self.session_stream_end.clone();
+
+        loop {
+            if let Some(end) = *end.borrow_and_update() {
+                return Ok(end);
+            }
+
+            if end.changed().await.is_err() {
+                return Err(self.result().await);
+            }
+        }
+    }
+
+    pub async fn accept_unknown(&self) -> Result<UnknownEvent, DriverError> {
+        let mut lock = self.ready_unknown.lock().await;
+
+        // Tasks of the worker might still hold the sender after it ended.
+        tokio::select! {
+            biased;
+            Some(event) = lock.recv() => Ok(event),
+            error = self.result() => Err(error),
+        }
+    }
+
+    /// Returns the handler of unknown frames and streams.
+    #[inline(always)]
+    pub fn unknown(&self) -> &UnknownHandler {
+        &self.unknown
+    }
+
+    /// Returns the reserved settings, frames and streams sent to the peer.
+    #[inline(always)]
+    pub fn grease(&self) -> &Grease {
+        &self.grease
+    }
+
+    /// Returns the counters of unknown frames and streams.
+    #[inline(always)]
+    pub fn unknown_stats(&self) -> UnknownStats {
+        self.unknown.stats()
+    }
+
+    pub fn set_session_heartbeat(&self, interval: Option<Duration>) {
+        self.session_heartbeat.send_replace(interval);
+    }
+
     pub async fn open_uni(&self, session_id: SessionId) -> Result<OpeningUniStream, DriverError> {
+        if let Some(queue) = &self.uni_open_queue {
+            return Ok(OpeningUniStream::queued(
+                session_id,
+                self.quic_connection.clone(),
+                queue.clone(),
+                self.stream_guard(Some(session_id)),
+            ));
+        }
+
         let quic_stream = Stream::open_uni(&self.quic_connection)
             .await
             .ok_or(DriverError::NotConnected)?;
 
-        Ok(OpeningUniStream::new(session_id, quic_stream))
+        Ok(OpeningUniStream::new(
+            session_id,
+            quic_stream,
+            self.stream_guard(Some(session_id)),
+        ))
     }
 
     pub async fn open_bi(&self, session_id: SessionId) -> Result<OpeningBiStream, DriverError> {
+        if let Some(queue) = &self.bi_open_queue {
+            return Ok(OpeningBiStream::queued(
+                session_id,
+                self.quic_connection.clone(),
+                queue.clone(),
+                self.stream_guard(Some(session_id)),
+            ));
+        }
+
         let quic_stream = Stream::open_bi(&self.quic_connection)
             .await
             .ok_or(DriverError::NotConnected)?;
 
-        Ok(OpeningBiStream::new(session_id, quic_stream))
+        Ok(OpeningBiStream::new(
+            session_id,
+            quic_stream,
+            self.stream_guard(Some(session_id)),
+        ))
+    }
+
+    /// Opens a unidirectional stream, only if stream credit is available right away.
+    pub fn try_open_uni(
+        &self,
+        session_id: SessionId,
+    ) -> Result<Option<OpeningUniStream>, DriverError> {
+        let _turn = match self.uni_open_queue.as_ref().map(OpenQueue::try_enter) {
+            Some(None) => return Ok(None),
+            Some(turn) => turn,
+            None => None,
+        };
+
+        match poll_once(Stream::open_uni(&self.quic_connection)) {
+            Some(Some(quic_stream)) => Ok(Some(OpeningUniStream::new(
+                session_id,
+                quic_stream,
+                self.stream_guard(Some(session_id)),
+            ))),
+            Some(None) => Err(DriverError::NotConnected),
+            None => Ok(None),
+        }
+    }
+
+    /// Opens a bidirectional stream, only if stream credit is available right away.
+    pub fn try_open_bi(
+        &self,
+        session_id: SessionId,
+    ) -> Result<Option<OpeningBiStream>, DriverError> {
+        let _turn = match self.bi_open_queue.as_ref().map(OpenQueue::try_enter) {
+            Some(None) => return Ok(None),
+            Some(turn) => turn,
+            None => None,
+        };
+
+        match poll_once(Stream::open_bi(&self.quic_connection)) {
+            Some(Some(quic_stream)) => Ok(Some(OpeningBiStream::new(
+                session_id,
+                quic_stream,
+                self.stream_guard(Some(session_id)),
+            ))),
+            Some(None) => Err(DriverError::NotConnected),
+            None => Ok(None),
+        }
     }
 
+    #[inline(always)]
     pub fn send_datagram(
         &self,
         session_id: SessionId,
         payload: &[u8],
     ) -> Result<(), SendDatagramError> {
-        let quic_datagram = Datagram::write(session_id, payload).into_quic_bytes();
+        let format = self
+            .datagram_format
+            .negotiated()
+            .ok_or(SendDatagramError::UnsupportedByPeer)?;
 
-        match self.quic_connection.send_datagram(quic_datagram) {
-            Ok(()) => Ok(()),
-            Err(quinn::SendDatagramError::UnsupportedByPeer) => {
-                Err(SendDatagramError::UnsupportedByPeer)
-            }
-            Err(quinn::SendDatagramError::Disabled) => {
-                unreachable!()
-            }
+        if !self.activity.drops_outgoing_datagram() {
+            send_datagram(&self.quic_connection, session_id, payload, format)?;
+        }
+        self.activity.datagram_sent(payload.len());
+        Ok(())
+    }
 
-            Err(quinn::SendDatagramError::TooLarge) => Err(SendDatagramError::TooLarge),
-            Err(quinn::SendDatagramError::ConnectionLost(_)) => {
-                Err(SendDatagramError::NotConnected)
+    /// Sends all `payloads` on `session_id`, back to back, without dropping the datagrams
+    /// already queued.
+    pub fn send_datagram_batch<I>(
+        &self,
+        session_id: SessionId,
+        payloads: I,
+    ) -> Vec<Result<(), SendDatagramError>>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        let format = match self.datagram_format.negotiated() {
+            Some(format) => format,
+            None => {
+                return payloads
+                    .into_iter()
+                    .map(|_| Err(SendDatagramError::UnsupportedByPeer))
+                    .collect()
             }
+        };
+
+        // Encoded beforehand, so that nothing delays the queuing of the next datagram.
+        let quic_datagrams = payloads
+            .into_iter()
+            .map(|payload| {
+                let payload = payload.as_ref();
+                let quic_datagram = Datagram::write(session_id, payload, format).into_quic_bytes();
+                (payload.len(), quic_datagram)
+            })
+            .collect::<Vec<_>>();
+
+        quic_datagrams
+            .into_iter()
+            .map(|(payload_len, quic_datagram)| {
+                let size = quic_datagram.len();
+
+                // Otherwise, `quinn` would drop older datagrams to make room for this one.
+                // Datagrams too large for the connection are reported by `quinn` instead.
+                if size > self.quic_connection.datagram_send_buffer_space()
+                    && self
+                        .quic_connection
+                        .max_datagram_size()
+                        .is_some_and(|max_size| size <= max_size)
+                {
+                    return Err(SendDatagramError::BufferFull);
+                }
+
+                if !self.activity.drops_outgoing_datagram() {
+                    send_quic_datagram(&self.quic_connection, quic_datagram)?;
+                }
+                self.activity.datagram_sent(payload_len);
+                Ok(())
+            })
+            .collect()
+    }
+
+    /// Sends `datagram` on `session_id`, reusing its QUIC datagram if it has the same header.
+    pub fn forward_datagram(
+        &self,
+        session_id: SessionId,
+        datagram: &Datagram,
+    ) -> Result<(), SendDatagramError> {
+        let format = self
+            .datagram_format
+            .negotiated()
+            .ok_or(SendDatagramError::UnsupportedByPeer)?;
+
+        let quic_datagram = match datagram.quic_bytes_for(session_id, format) {
+            Some(quic_datagram) => quic_datagram.clone(),
+            None => Datagram::write(session_id, datagram, format).into_quic_bytes(),
+        };
+
+        if !self.activity.drops_outgoing_datagram() {
+            send_quic_datagram(&self.quic_connection, quic_datagram)?;
+        }
+        self.activity.datagram_sent(datagram.len());
+        Ok(())
+    }
+
+    /// Returns the datagram format negotiated with the peer.
+    #[inline(always)]
+    pub fn datagram_format(&self) -> &SharedDatagramFormat {
+        &self.datagram_format
+    }
+
+    /// Stops accepting incoming WebTransport streams.
+    ///
+    /// Future incoming streams, and the ones already queued but not yet accepted
+    /// by the application, are rejected.
+    pub async fn stop_accepting(&self) {
+        self.draining.store(true, Ordering::Relaxed);
+
+        while let Ok(stream) = self.ready_uni_wt_streams.try_recv() {
+            debug!("Rejecting WT stream (stream_id: {})", stream.id());
+
+            stream
+                .into_stream()
+                .stop(ErrorCode::RequestRejected.to_code())
+                .expect("Stream not already stopped");
+        }
+
+        while let Ok(stream) = self.ready_bi_wt_streams.try_recv() {
+            debug!("Rejecting WT stream (stream_id: {})", stream.id());
+
+            stream
+                .into_stream()
+                .1
+                .stop(ErrorCode::RequestRejected.to_code())
+                .expect("Stream not already stopped");
         }
     }
 
+    /// Returns a new guard for tracking a stream of session `session_id` in use by the
+    /// application.
+    #[inline(always)]
+    pub fn stream_guard(&self, session_id: Option<SessionId>) -> StreamGuard {
+        let guard = self
+            .streams_tracker
+            .guard(self.activity.clone(), session_id);
+
+        match &self.quota {
+            Some(quota) => guard.with_quota(quota.stream_slot()),
+            None => guard,
+        }
+    }
+
+    /// Subjects the session to `quota`.
+    #[inline(always)]
+    pub fn set_quota(&mut self, quota: QuotaAccount) {
+        self.quota = Some(quota);
+    }
+
+    /// Returns the quota the session is subject to, if any.
+    #[inline(always)]
+    pub fn quota(&self) -> Option<&QuotaAccount> {
+        self.quota.as_ref()
+    }
+
+    /// Returns a new guard for tracking a stream of session `session_id` opened by the
+    /// peer, or `None` if the stream is beyond the quota.
+    fn remote_stream_guard(&self, session_id: SessionId) -> Option<StreamGuard> {
+        let slot = match &self.quota {
+            Some(quota) => Some(quota.reserve_stream()?),
+            None => None,
+        };
+
+        let guard = self
+            .streams_tracker
+            .guard(self.activity.clone(), Some(session_id));
+
+        Some(match slot {
+            Some(slot) => guard.with_quota(slot),
+            None => guard,
+        })
+    }
+
+    /// Returns the application activity on the connection.
+    #[inline(always)]
+    pub fn activity(&self) -> &Activity {
+        &self.activity
+    }
+
+    /// Returns the spawner of the connection tasks.
+    #[inline(always)]
+    pub fn spawner(&self) -> &Spawner {
+        &self.spawner
+    }
+
+    /// Returns the number of streams in use by the application.
+    #[inline(always)]
+    pub fn active_streams(&self) -> usize {
+        self.streams_tracker.active()
+    }
+
+    /// Awaits all streams in use by the application are dropped.
+    #[inline(always)]
+    pub async fn streams_idle(&self) {
+        self.streams_tracker.idle().await
+    }
+
+    /// Like [`result`](Self::result), without waiting for the worker to set it.
+    fn try_result(&self) -> DriverError {
+        poll_once(self.result()).unwrap_or(DriverError::NotConnected)
+    }
+
     async fn result(&self) -> DriverError {
         match self.driver_result.result().await {
             Some(error) => error,
@@ -237,8 +893,110 @@ impl Driver {
     }
 }
 
+fn discard_uni_stream(stream: StreamUniRemoteWT) {
+    debug!(
+        "Discarding WT stream (stream_id: {}, session_id: {})",
+        stream.id(),
+        stream.session_id()
+    );
+
+    stream
+        .into_stream()
+        .stop(ErrorCode::BufferedStreamRejected.to_code())
+        .expect("Stream not already stopped");
+}
+
+fn refuse_uni_stream(stream: StreamUniRemoteWT) {
+    debug!(
+        "Refusing WT stream beyond quota (stream_id: {})",
+        stream.id()
+    );
+
+    stream
+        .into_stream()
+        .stop(ErrorCode::RequestRejected.to_code())
+        .expect("Stream not already stopped");
+}
+
+fn refuse_bi_stream(stream: StreamBiRemoteWT) {
+    debug!(
+        "Refusing WT stream beyond quota (stream_id: {})",
+        stream.id()
+    );
+
+    stream
+        .into_stream()
+        .1
+        .stop(ErrorCode::RequestRejected.to_code())
+        .expect("Stream not already stopped");
+}
+
+fn discard_bi_stream(stream: StreamBiRemoteWT) {
+    debug!(
+        "Discarding WT stream (stream_id: {}, session_id: {})",
+        stream.id(),
+        stream.session_id()
+    );
+
+    stream
+        .into_stream()
+        .1
+        .stop(ErrorCode::BufferedStreamRejected.to_code())
+        .expect("Stream not already stopped");
+}
+
+fn discard_datagram(datagram: Datagram) {
+    debug!(
+        "Incoming datagram discarded (session_id: {})",
+        datagram.session_id()
+    );
+}
+
+/// Sends a WebTransport datagram directly on the QUIC connection.
+pub fn send_datagram(
+    quic_connection: &quinn::Connection,
+    session_id: SessionId,
+    payload: &[u8],
+    format: DatagramFormat,
+) -> Result<(), SendDatagramError> {
+    let quic_datagram = Datagram::write(session_id, payload, format).into_quic_bytes();
+    send_quic_datagram(quic_connection, quic_datagram)
+}
+
+/// Sends a QUIC datagram, already including its HTTP3 header.
+fn send_quic_datagram(
+    quic_connection: &quinn::Connection,
+    quic_datagram: Bytes,
+) -> Result<(), SendDatagramError> {
+    quic_connection
+        .send_datagram(quic_datagram)
+        .map_err(SendDatagramError::from_quinn)
+}
+
+/// Returns whether a request failing to parse as a session request with `error` is
+/// actually another kind of request (e.g., `GET`), rather than a malformed one.
+///
+/// `extended` tells whether the request has a `:protocol` (extended CONNECT).
+fn is_not_session_request(error: &HeadersParseError, extended: bool) -> bool {
+    match error {
+        HeadersParseError::MethodNotConnect | HeadersParseError::ProtocolNotWebTransport => true,
+        HeadersParseError::MissingMethod => false,
+        // A classic CONNECT has neither `:scheme` nor `:path`.
+        _ => !extended,
+    }
+}
+
+/// Channels of the session stream: capsules received, capsules to send, and how the
+/// peer ends the stream.
+type SessionCapsules = (
+    mpsc::Sender<Capsule>,
+    mpsc::Receiver<Capsule>,
+    watch::Sender<Option<StreamEnd>>,
+);
+
 mod worker {
     use super::*;
+    use crate::driver::streams::capsules;
     use crate::driver::streams::qpack::RemoteQPackDecStream;
     use crate::driver::streams::qpack::RemoteQPackEncStream;
     use crate::driver::streams::settings::LocalSettingsStream;
@@ -246,11 +1004,12 @@ mod worker {
     use crate::driver::streams::uniremote::StreamUniRemoteH3;
     use crate::driver::streams::ProtoReadError;
     use crate::driver::streams::ProtoWriteError;
+    use crate::driver::utils::MilestoneLog;
     use crate::driver::utils::TrySendError;
-    use utils::varint_w2q;
-    use wtransport_proto::frame::FrameKind;
+    use tokio::time::Instant;
+    use tokio::time::Interval;
+    use tokio::time::MissedTickBehavior;
     use wtransport_proto::headers::Headers;
-    use wtransport_proto::session::HeadersParseError;
     use wtransport_proto::stream_header::StreamHeader;
     use wtransport_proto::stream_header::StreamKind;
 
@@ -261,15 +1020,31 @@ mod worker {
         ready_uni_wt_streams: mpsc::Sender<StreamUniRemoteWT>,
         ready_bi_wt_streams: mpsc::Sender<StreamBiRemoteWT>,
         ready_datagrams: mpsc::Sender<Datagram>,
+        datagram_format: SharedDatagramFormat,
+        session_capsules: Option<SessionCapsules>,
+        memory: MemoryAccount,
+        session_heartbeat: watch::Receiver<Option<Duration>>,
         driver_result: SharedResultSet<DriverError>,
+        draining: Arc<AtomicBool>,
+        spawner: Spawner,
+        strict_scheme: bool,
+        header_limits: HeadersLimits,
+        extended_connect_protocols: Arc<[String]>,
+        fallback_response: Option<Arc<FallbackResponse>>,
+        event_budget: usize,
+        unknown: UnknownHandler,
+        grease: Grease,
+        milestones: MilestoneLog,
         local_settings_stream: LocalSettingsStream,
         remote_settings_stream: RemoteSettingsStream,
         remote_qpack_enc_stream: RemoteQPackEncStream,
         remote_qpack_dec_stream: RemoteQPackDecStream,
-        stream_session: Option<StreamSession>,
+        remote_settings_received: bool,
+        session_established: bool,
     }
 
     impl Worker {
+        #[allow(clippy::too_many_arguments)]
         pub fn new(
             quic_connection: quinn::Connection,
             ready_settings: mpsc::Sender<Settings>,
@@ -277,7 +1052,22 @@ mod worker {
             ready_uni_wt_streams: mpsc::Sender<StreamUniRemoteWT>,
             ready_bi_wt_streams: mpsc::Sender<StreamBiRemoteWT>,
             ready_datagrams: mpsc::Sender<Datagram>,
+            datagram_format: SharedDatagramFormat,
+            session_capsules: SessionCapsules,
+            memory: MemoryAccount,
+            session_heartbeat: watch::Receiver<Option<Duration>>,
             driver_result: SharedResultSet<DriverError>,
+            draining: Arc<AtomicBool>,
+            spawner: Spawner,
+            strict_scheme: bool,
+            header_limits: HeadersLimits,
+            legacy_datagrams: bool,
+            extended_connect_protocols: Arc<[String]>,
+            fallback_response: Option<Arc<FallbackResponse>>,
+            event_budget: usize,
+            unknown: UnknownHandler,
+            grease: Grease,
+            milestones: MilestoneLog,
         ) -> Self {
             Self {
                 quic_connection,
@@ -286,12 +1076,27 @@ mod worker {
                 ready_uni_wt_streams,
                 ready_bi_wt_streams,
                 ready_datagrams,
+                datagram_format,
+                session_capsules: Some(session_capsules),
+                memory,
+                session_heartbeat,
                 driver_result,
-                local_settings_stream: LocalSettingsStream::empty(),
-                remote_settings_stream: RemoteSettingsStream::empty(),
+                draining,
+                spawner,
+                strict_scheme,
+                header_limits,
+                extended_connect_protocols,
+                fallback_response,
+                event_budget,
+                local_settings_stream: LocalSettingsStream::empty(legacy_datagrams, &grease),
+                remote_settings_stream: RemoteSettingsStream::empty(unknown.clone()),
+                unknown,
+                grease,
+                milestones,
                 remote_qpack_enc_stream: RemoteQPackEncStream::empty(),
                 remote_qpack_dec_stream: RemoteQPackDecStream::empty(),
-                stream_session: None,
+                remote_settings_received: false,
+                session_established: false,
             }
         }
 
@@ -305,9 +1110,15 @@ mod worker {
 
             debug!("Ended with error: {:?}", error);
 
-            if let DriverError::Proto(error_code) = &error {
-                self.quic_connection
-                    .close(varint_w2q(error_code.to_code()), b"");
+            let error = match error {
+                DriverError::Proto(violation) if violation.phase.is_none() => {
+                    DriverError::Proto(violation.in_phase(self.phase()))
+                }
+                error => error,
+            };
+
+            if let DriverError::Proto(violation) = &error {
+                close_on_violation(&self.quic_connection, violation);
             }
 
             self.driver_result.set(error);
@@ -317,25 +1128,51 @@ mod worker {
             let mut remote_settings_watcher = self.remote_settings_stream.subscribe();
             let mut ready_uni_h3_streams = mpsc::channel(4);
             let mut ready_bi_h3_streams = mpsc::channel(1);
+            let mut pending_session = None;
 
             self.open_and_send_settings().await?;
+            self.send_grease();
+
+            let mut grease_interval = self.grease.repeat_interval().map(|period| {
+                let mut interval = tokio::time::interval_at(Instant::now() + period, period);
+                interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                interval
+            });
+
+            let mut events = 0;
 
             loop {
+                // Quinn futures do not take part in the cooperative budget of tokio: yield
+                // explicitly, so that a busy connection does not starve the others.
+                events += 1;
+                if events >= self.event_budget {
+                    events = 0;
+                    tokio::task::yield_now().await;
+                }
+
                 tokio::select! {
                     result = Self::accept_uni(&self.quic_connection,
                                               &ready_uni_h3_streams.0,
-                                              &self.ready_uni_wt_streams) => {
+                                              &self.ready_uni_wt_streams,
+                                              &self.draining,
+                                              &self.unknown,
+                                              &self.spawner) => {
                         result?;
                     }
 
                     result = Self::accept_bi(&self.quic_connection,
                                              &ready_bi_h3_streams.0,
-                                             &self.ready_bi_wt_streams) => {
+                                             &self.ready_bi_wt_streams,
+                                             &self.draining,
+                                             &self.unknown,
+                                             &self.spawner) => {
                         result?;
                     }
 
                     result = Self::accept_datagram(&self.quic_connection,
-                                                   &self.ready_datagrams) => {
+                                                   &self.ready_datagrams,
+                                                   &self.datagram_format,
+                                                   &self.memory) => {
                         result?;
                     }
 
@@ -344,11 +1181,17 @@ mod worker {
                         self.handle_uni_h3_stream(uni_h3_stream)?;
                     }
 
-                    bi_h3_stream = ready_bi_h3_streams.1.recv() => {
+                    // Stop processing H3 bi streams (and so accepting new ones) while a session
+                    // request is waiting for the application to accept it.
+                    bi_h3_stream = ready_bi_h3_streams.1.recv(), if pending_session.is_none() => {
                         let (bi_h3_stream, first_frame) = bi_h3_stream.expect("Sender cannot be dropped")?;
-                        self.handle_bi_h3_stream(bi_h3_stream, first_frame)?;
+                        pending_session = self.handle_bi_h3_stream(bi_h3_stream, first_frame)?;
                     }
 
+                    slot = self.ready_sessions.reserve_owned(), if pending_session.is_some() => {
+                        let slot = slot.map_err(|SendError| DriverError::NotConnected)?;
+                        slot.send(pending_session.take().expect("Pending session is set"));
+                    }
 
                     settings = remote_settings_watcher.accept_settings() => {
                         let settings = settings.expect("Channel cannot be dropped");
@@ -358,8 +1201,9 @@ mod worker {
                     stream_session = self.ready_sessions.recv() => {
                         match stream_session {
                             Some(stream_session) => {
-                                if self.stream_session.is_none() {
-                                    self.stream_session = Some(stream_session);
+                                if !self.session_established {
+                                    self.session_established = true;
+                                    self.run_session_stream(stream_session);
                                 }
                             }
                             None => return Err(DriverError::NotConnected),
@@ -369,11 +1213,14 @@ mod worker {
                     error = Self::run_control_streams(&mut self.local_settings_stream,
                                                       &mut self.remote_settings_stream,
                                                       &mut self.remote_qpack_enc_stream,
-                                                      &mut self.remote_qpack_dec_stream,
-                                                      &mut self.stream_session) => {
+                                                      &mut self.remote_qpack_dec_stream) => {
                         return Err(error);
                     }
 
+                    () = grease_tick(&mut grease_interval) => {
+                        self.send_grease();
+                    }
+
                     () = self.driver_result.closed() => {
                         return Err(DriverError::NotConnected);
                     }
@@ -381,6 +1228,39 @@ mod worker {
             }
         }
 
+        /// Queues a reserved frame on the control stream, and opens a stream of reserved
+        /// type, as configured.
+        ///
+        /// Nothing is awaited: the frame is written while the control streams are run.
+        fn send_grease(&mut self) {
+            if let Some(frame) = self.grease.frame() {
+                self.local_settings_stream.queue_frame(frame);
+            }
+
+            if let Some((stream_header, payload)) = self.grease.uni_stream() {
+                let quic_connection = self.quic_connection.clone();
+
+                self.spawner.spawn(
+                    async move {
+                        let stream = match Stream::open_uni(&quic_connection).await {
+                            Some(stream) => stream,
+                            None => return,
+                        };
+
+                        let mut stream = match stream.upgrade(stream_header).await {
+                            Ok(stream) => stream.into_stream(),
+                            Err(_) => return,
+                        };
+
+                        if stream.write_all(&payload).await.is_ok() {
+                            let _ = stream.finish().await;
+                        }
+                    }
+                    .instrument(debug_span!("GreaseStream")),
+                );
+            }
+        }
+
         async fn open_and_send_settings(&mut self) -> Result<(), DriverError> {
             assert!(self.local_settings_stream.is_empty());
 
@@ -393,7 +1273,10 @@ mod worker {
                 Ok(h3_stream) => h3_stream,
                 Err(ProtoWriteError::NotConnected) => return Err(DriverError::NotConnected),
                 Err(ProtoWriteError::Stopped) => {
-                    return Err(DriverError::Proto(ErrorCode::ClosedCriticalStream));
+                    return Err(DriverError::proto(
+                        ErrorCode::ClosedCriticalStream,
+                        "Control stream stopped",
+                    ));
                 }
             };
 
@@ -405,6 +1288,9 @@ mod worker {
             quic_connection: &quinn::Connection,
             ready_uni_h3_streams: &mpsc::Sender<Result<StreamUniRemoteH3, DriverError>>,
             ready_uni_wt_streams: &mpsc::Sender<StreamUniRemoteWT>,
+            draining: &Arc<AtomicBool>,
+            unknown: &UnknownHandler,
+            spawner: &Spawner,
         ) -> Result<(), DriverError> {
             trace!("H3 uni queue capacity: {}", ready_uni_h3_streams.capacity());
             let h3_slot = ready_uni_h3_streams
@@ -425,12 +1311,18 @@ mod worker {
             let stream_id = stream_quic.id();
             debug!("New incoming uni stream ({})", stream_id);
 
-            tokio::spawn(
+            let draining = draining.clone();
+            let unknown = unknown.clone();
+
+            spawner.spawn(
                 async move {
                     let stream_h3 = match stream_quic.upgrade().await {
                         Ok(stream_h3) => stream_h3,
                         Err(ProtoReadError::H3(error_code)) => {
-                            h3_slot.send(Err(DriverError::Proto(error_code)));
+                            h3_slot.send(Err(DriverError::Proto(
+                                Violation::new(error_code, "Invalid stream header")
+                                    .on_stream(stream_id),
+                            )));
                             return;
                         }
                         Err(ProtoReadError::IO(_)) => {
@@ -441,9 +1333,23 @@ mod worker {
                     let stream_kind = stream_h3.kind();
                     debug!("Type: {:?}", stream_kind);
 
-                    if matches!(stream_kind, StreamKind::WebTransport) {
+                    if let StreamKind::Exercise(kind) | StreamKind::Unknown(kind) = stream_kind {
+                        if let Err(violation) = unknown.on_uni_stream(kind, stream_h3.into_stream())
+                        {
+                            h3_slot.send(Err(DriverError::Proto(violation)));
+                        }
+                    } else if matches!(stream_kind, StreamKind::WebTransport) {
                         let stream_wt = stream_h3.upgrade();
-                        wt_slot.send(stream_wt);
+
+                        if draining.load(Ordering::Relaxed) {
+                            debug!("Rejecting WT stream: connection is draining");
+                            stream_wt
+                                .into_stream()
+                                .stop(ErrorCode::RequestRejected.to_code())
+                                .expect("Stream not already stopped");
+                        } else {
+                            wt_slot.send(stream_wt);
+                        }
                     } else {
                         h3_slot.send(Ok(stream_h3));
                     }
@@ -460,6 +1366,9 @@ mod worker {
                 Result<(StreamBiRemoteH3, Frame<'static>), DriverError>,
             >,
             ready_bi_wt_streams: &mpsc::Sender<StreamBiRemoteWT>,
+            draining: &Arc<AtomicBool>,
+            unknown: &UnknownHandler,
+            spawner: &Spawner,
         ) -> Result<(), DriverError> {
             trace!("H3 bi queue capacity: {}", ready_bi_h3_streams.capacity());
             let h3_slot = ready_bi_h3_streams
@@ -480,17 +1389,34 @@ mod worker {
             let stream_id = stream_quic.id();
             debug!("New incoming bi stream ({})", stream_id);
 
-            tokio::spawn(
+            let draining = draining.clone();
+            let unknown = unknown.clone();
+
+            spawner.spawn(
                 async move {
                     let mut stream_h3 = stream_quic.upgrade();
 
-                    let frame = match stream_h3.read_frame().await {
-                        Ok(frame) => frame,
-                        Err(ProtoReadError::H3(error_code)) => {
-                            h3_slot.send(Err(DriverError::Proto(error_code)));
-                            return;
+                    let frame = loop {
+                        let frame = match stream_h3.read_frame().await {
+                            Ok(frame) => frame,
+                            Err(ProtoReadError::H3(error_code)) => {
+                                h3_slot.send(Err(DriverError::Proto(
+                                    Violation::new(error_code, "Invalid first frame")
+                                        .on_stream(stream_id),
+                                )));
+                                return;
+                            }
+                            Err(ProtoReadError::IO(_)) => {
+                                return;
+                            }
+                        };
+
+                        if !matches!(frame.kind(), FrameKind::Exercise(_) | FrameKind::Unknown(_)) {
+                            break frame;
                         }
-                        Err(ProtoReadError::IO(_)) => {
+
+                        if let Err(violation) = unknown.on_frame(stream_id, &frame) {
+                            h3_slot.send(Err(DriverError::Proto(violation)));
                             return;
                         }
                     };
@@ -500,7 +1426,17 @@ mod worker {
                     match frame.session_id() {
                         Some(session_id) => {
                             let stream_wt = stream_h3.upgrade(session_id);
-                            wt_slot.send(stream_wt);
+
+                            if draining.load(Ordering::Relaxed) {
+                                debug!("Rejecting WT stream: connection is draining");
+                                stream_wt
+                                    .into_stream()
+                                    .1
+                                    .stop(ErrorCode::RequestRejected.to_code())
+                                    .expect("Stream not already stopped");
+                            } else {
+                                wt_slot.send(stream_wt);
+                            }
                         }
                         None => {
                             h3_slot.send(Ok((stream_h3, frame)));
@@ -516,6 +1452,8 @@ mod worker {
         async fn accept_datagram(
             quic_connection: &quinn::Connection,
             ready_datagrams: &mpsc::Sender<Datagram>,
+            datagram_format: &SharedDatagramFormat,
+            memory: &MemoryAccount,
         ) -> Result<(), DriverError> {
             let slot = match ready_datagrams.reserve().await {
                 Ok(slot) => slot,
@@ -527,9 +1465,9 @@ mod worker {
                 Err(_) => return Err(DriverError::NotConnected),
             };
 
-            let datagram = match Datagram::read(quic_dgram) {
+            let datagram = match Datagram::read(quic_dgram, datagram_format.get()) {
                 Ok(datagram) => datagram,
-                Err(error_code) => return Err(DriverError::Proto(error_code)),
+                Err(error_code) => return Err(DriverError::proto(error_code, "Invalid datagram")),
             };
 
             debug!(
@@ -537,6 +1475,11 @@ mod worker {
                 datagram.session_id()
             );
 
+            if !memory.charge(datagram.len()) {
+                debug!("Incoming datagram dropped (memory budget exhausted)");
+                return Ok(());
+            }
+
             slot.send(datagram);
 
             Ok(())
@@ -546,27 +1489,44 @@ mod worker {
             match stream.kind() {
                 StreamKind::Control => {
                     if !self.remote_settings_stream.is_empty() {
-                        return Err(DriverError::Proto(ErrorCode::StreamCreation));
+                        return Err(DriverError::Proto(
+                            Violation::new(ErrorCode::StreamCreation, "Duplicate control stream")
+                                .on_stream(stream.id()),
+                        ));
                     }
 
                     self.remote_settings_stream.set_stream(stream);
                 }
                 StreamKind::QPackEncoder => {
                     if !self.remote_qpack_enc_stream.is_empty() {
-                        return Err(DriverError::Proto(ErrorCode::StreamCreation));
+                        return Err(DriverError::Proto(
+                            Violation::new(
+                                ErrorCode::StreamCreation,
+                                "Duplicate QPACK encoder stream",
+                            )
+                            .on_stream(stream.id()),
+                        ));
                     }
 
                     self.remote_qpack_enc_stream.set_stream(stream);
                 }
                 StreamKind::QPackDecoder => {
                     if !self.remote_qpack_dec_stream.is_empty() {
-                        return Err(DriverError::Proto(ErrorCode::StreamCreation));
+                        return Err(DriverError::Proto(
+                            Violation::new(
+                                ErrorCode::StreamCreation,
+                                "Duplicate QPACK decoder stream",
+                            )
+                            .on_stream(stream.id()),
+                        ));
                     }
 
                     self.remote_qpack_dec_stream.set_stream(stream);
                 }
-                StreamKind::WebTransport => unreachable!(),
-                StreamKind::Exercise(_) => {}
+                kind => {
+                    // Other kinds are dispatched before, in `accept_uni`.
+                    debug!("Discarding unexpected H3 stream (kind: {:?})", kind);
+                }
             }
 
             Ok(())
@@ -577,55 +1537,145 @@ mod worker {
             &mut self,
             mut stream: StreamBiRemoteH3,
             first_frame: Frame<'static>,
-        ) -> Result<(), DriverError> {
+        ) -> Result<Option<StreamSession>, DriverError> {
             match first_frame.kind() {
                 FrameKind::Data => {
-                    return Err(DriverError::Proto(ErrorCode::FrameUnexpected));
+                    return Err(DriverError::Proto(
+                        Violation::new(ErrorCode::FrameUnexpected, "DATA frame before HEADERS")
+                            .on_stream(stream.id())
+                            .with_frame(FrameKind::Data),
+                    ));
                 }
                 FrameKind::Headers => {
-                    let headers = match Headers::with_frame(&first_frame, stream.id()) {
+                    let headers = match Headers::with_frame_limited(
+                        &first_frame,
+                        stream.id(),
+                        &self.header_limits,
+                    ) {
                         Ok(headers) => headers,
-                        Err(error_code) => return Err(DriverError::Proto(error_code)),
+                        Err(error_code) => {
+                            return Err(DriverError::Proto(
+                                Violation::new(error_code, "Invalid HEADERS frame")
+                                    .on_stream(stream.id())
+                                    .with_frame(FrameKind::Headers),
+                            ))
+                        }
                     };
 
                     debug!("Headers: {:?}", headers);
 
-                    let stream_session = match SessionRequest::try_from(headers) {
+                    if self.session_established {
+                        debug!("Discarding session request: session already established");
+                        stream
+                            .stop(ErrorCode::RequestRejected.to_code())
+                            .expect("Stream not already stopped");
+                        return Ok(None);
+                    }
+
+                    let extended = headers.contains_key(":protocol");
+
+                    let stream_session = match self.parse_session_request(headers) {
                         Ok(session_request) => stream.into_session(session_request),
-                        Err(HeadersParseError::MethodNotConnect) => {
-                            stream
-                                .stop(ErrorCode::RequestRejected.to_code())
-                                .expect("Stream not already stopped");
-                            return Ok(());
+                        Err(error) if is_not_session_request(&error, extended) => {
+                            match &self.fallback_response {
+                                Some(response) => self.serve_fallback(stream, response.clone()),
+                                None if matches!(error, HeadersParseError::MethodNotConnect) => {
+                                    stream
+                                        .stop(ErrorCode::RequestRejected.to_code())
+                                        .expect("Stream not already stopped")
+                                }
+                                None => stream
+                                    .stop(ErrorCode::Message.to_code())
+                                    .expect("Stream not already stopped"),
+                            }
+                            return Ok(None);
                         }
                         // TODO(biagio): we might have more granularity with errors
                         Err(_) => {
                             stream
                                 .stop(ErrorCode::Message.to_code())
                                 .expect("Stream not already stopped");
-                            return Ok(());
+                            return Ok(None);
                         }
                     };
 
                     match self.ready_sessions.try_send(stream_session) {
                         Ok(()) => {}
-                        Err(TrySendError::Full(mut stream)) => {
-                            debug!("Discarding session request: sessions queue is full");
-                            stream
-                                .stop(ErrorCode::RequestRejected.to_code())
-                                .expect("Stream not already stopped");
+                        Err(TrySendError::Full(stream_session)) => {
+                            debug!("Sessions queue is full: pausing session requests");
+                            return Ok(Some(stream_session));
                         }
                         Err(TrySendError::Closed(_)) => return Err(DriverError::NotConnected),
                     }
                 }
                 FrameKind::Settings => {
-                    return Err(DriverError::Proto(ErrorCode::FrameUnexpected));
+                    return Err(DriverError::Proto(
+                        Violation::new(
+                            ErrorCode::FrameUnexpected,
+                            "SETTINGS frame on request stream",
+                        )
+                        .on_stream(stream.id())
+                        .with_frame(FrameKind::Settings),
+                    ));
+                }
+                kind => {
+                    // Other kinds are dispatched before, in `accept_bi`.
+                    return Err(DriverError::Proto(
+                        Violation::new(ErrorCode::FrameUnexpected, "Unexpected first frame")
+                            .on_stream(stream.id())
+                            .with_frame(kind),
+                    ));
                 }
-                FrameKind::WebTransport => unreachable!(),
-                FrameKind::Exercise(_) => {}
             }
 
-            Ok(())
+            Ok(None)
+        }
+
+        /// Answers a request which is not a session request with `response`, then stops
+        /// reading it.
+        fn serve_fallback(&self, mut stream: StreamBiRemoteH3, response: Arc<FallbackResponse>) {
+            debug!("Serving fallback response");
+
+            let _ = stream.stop(ErrorCode::NoError.to_code());
+
+            self.spawner.spawn(async move {
+                let headers = response.headers.generate_frame(stream.id());
+
+                if stream.write_frame(headers).await.is_err() {
+                    return;
+                }
+
+                if !response.body.is_empty()
+                    && stream
+                        .write_frame(Frame::new_data(Cow::Borrowed(&response.body)))
+                        .await
+                        .is_err()
+                {
+                    return;
+                }
+
+                stream.finish().await;
+            });
+        }
+
+        /// Parses a session request, with `webtransport` or an accepted extended CONNECT protocol.
+        fn parse_session_request(
+            &self,
+            headers: Headers,
+        ) -> Result<SessionRequest, HeadersParseError> {
+            let session_request = SessionRequest::parse_extended(headers, self.strict_scheme)?;
+
+            let accepted = session_request.is_webtransport()
+                || self
+                    .extended_connect_protocols
+                    .iter()
+                    .any(|protocol| protocol == session_request.protocol());
+
+            if !accepted {
+                return Err(HeadersParseError::ProtocolNotWebTransport);
+            }
+
+            Ok(session_request)
         }
 
         async fn run_control_streams(
@@ -633,9 +1683,7 @@ mod worker {
             remote_settings: &mut RemoteSettingsStream,
             remote_qpack_enc: &mut RemoteQPackEncStream,
             remote_qpack_dec: &mut RemoteQPackDecStream,
-            _stream_session: &mut Option<StreamSession>,
         ) -> DriverError {
-            // TODO(biagio): run stream_session
             tokio::select! {
                 error = local_settings.run() => error,
                 error = remote_settings.run() => error,
@@ -644,9 +1692,50 @@ mod worker {
             }
         }
 
+        fn run_session_stream(&mut self, stream_session: StreamSession) {
+            let (incoming, outgoing, end) = self
+                .session_capsules
+                .take()
+                .expect("Session stream is run once");
+
+            self.spawner.spawn(
+                capsules::run(
+                    stream_session,
+                    incoming,
+                    outgoing,
+                    end,
+                    self.memory.clone(),
+                    self.session_heartbeat.clone(),
+                    self.unknown.clone(),
+                    self.quic_connection.clone(),
+                )
+                .instrument(debug_span!("SessionStream")),
+            );
+        }
+
+        fn phase(&self) -> ProtocolPhase {
+            if !self.remote_settings_received {
+                ProtocolPhase::Settings
+            } else if !self.session_established {
+                ProtocolPhase::SessionEstablishment
+            } else {
+                ProtocolPhase::Session
+            }
+        }
+
         fn handle_remote_settings(&mut self, settings: Settings) -> Result<(), DriverError> {
             debug!("Received: {:?}", settings);
 
+            self.remote_settings_received = true;
+            self.milestones.record(Milestone::SettingsReceived);
+
+            if let Some(format) =
+                DatagramFormat::negotiate(self.local_settings_stream.settings(), &settings)
+            {
+                debug!("Datagram format: {:?}", format);
+                self.datagram_format.set(format);
+            }
+
             match self.ready_settings.try_send(settings) {
                 Ok(()) => Ok(()),
                 Err(mpsc::error::TrySendError::Closed(_)) => Err(DriverError::NotConnected),
@@ -656,6 +1745,15 @@ mod worker {
             }
         }
     }
+
+    async fn grease_tick(interval: &mut Option<Interval>) {
+        match interval {
+            Some(interval) => {
+                interval.tick().await;
+            }
+            None => std::future::pending().await,
+        }
+    }
 }
 
 pub(crate) mod streams;
@@ -0,0 +1,303 @@
+use crate::budget::MemoryAccount;
+use crate::capsule::Capsule;
+use crate::driver::close_on_violation;
+use crate::driver::streams::session::StreamSession;
+use crate::driver::streams::QuicRecvStream;
+use crate::driver::utils::varint_q2w;
+use crate::extension::UnknownHandler;
+use crate::stream::StreamEnd;
+use bytes::Bytes;
+use std::borrow::Cow;
+use std::future::pending;
+use std::pin::Pin;
+use std::task::ready;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::sync::watch;
+use tracing::debug;
+use tracing::trace;
+use wtransport_proto::bytes::AsyncRead;
+use wtransport_proto::bytes::BufferReader;
+use wtransport_proto::bytes::BytesReader;
+use wtransport_proto::bytes::IoReadError;
+use wtransport_proto::capsule::Capsule as H3Capsule;
+use wtransport_proto::error::ErrorCode;
+use wtransport_proto::frame::Frame;
+use wtransport_proto::frame::FrameKind;
+use wtransport_proto::stream::IoReadError as FrameReadError;
+use wtransport_proto::varint::VarInt;
+
+/// Capsule type of heartbeats (a reserved type, ignored by any peer).
+const HEARTBEAT_CAPSULE_TYPE: VarInt = VarInt::from_u32(0x29 * 0x1f + 0x17);
+
+/// Maximum size of a capsule received, header included (also bounding DATA frames).
+///
+/// Larger capsules reset the session stream with `H3_EXCESSIVE_LOAD`.
+const MAX_CAPSULE_SIZE: usize = 64 * 1024;
+
+/// Runs the session stream once the session is established.
+///
+/// Capsules received from the peer are forwarded to `incoming`, while the ones
+/// from `outgoing` are sent to the peer.
+/// If `heartbeat` is set, a heartbeat capsule is sent whenever nothing was
+/// sent for that interval.
+/// Frames of unknown type are passed to `unknown`, closing `quic_connection` if
+/// its policy says so.
+/// How the peer ends the stream (finished or reset) is reported to `end`.
+/// Capsules larger than [`MAX_CAPSULE_SIZE`], or exceeding the `memory` of the
+/// connection while buffered, reset the stream.
+/// It returns when the stream, or either channel, is closed.
+#[allow(clippy::too_many_arguments)]
+pub async fn run(
+    mut stream_session: StreamSession,
+    incoming: mpsc::Sender<Capsule>,
+    mut outgoing: mpsc::Receiver<Capsule>,
+    end: watch::Sender<Option<StreamEnd>>,
+    memory: MemoryAccount,
+    mut heartbeat: watch::Receiver<Option<Duration>>,
+    unknown: UnknownHandler,
+    quic_connection: quinn::Connection,
+) {
+    let stream_id = stream_session.id();
+    let proto = &stream_session.proto;
+    let (send_stream, recv_stream) = &mut stream_session.stream;
+
+    // Bytes received but not yet parsed into capsules, counted in `memory`.
+    let mut buffer = Vec::new();
+
+    // Returns whether the peer sent too large a capsule.
+    let reader = async {
+        let mut recv_stream = EndRecorder {
+            stream: &mut *recv_stream,
+            reset: None,
+        };
+
+        loop {
+            let frame = match proto
+                .read_frame_async_with_limit(&mut recv_stream, MAX_CAPSULE_SIZE)
+                .await
+            {
+                Ok(frame) => frame,
+                Err(FrameReadError::H3(ErrorCode::ExcessiveLoad)) => return true,
+                Err(error) => {
+                    debug!("Session stream reading ended: {:?}", error);
+
+                    match error {
+                        FrameReadError::IO(IoReadError::ImmediateFin) => {
+                            end.send_replace(Some(StreamEnd::Finished));
+                        }
+                        FrameReadError::IO(IoReadError::Reset) => {
+                            end.send_replace(recv_stream.reset.map(StreamEnd::Reset));
+                        }
+                        _ => {}
+                    }
+
+                    return false;
+                }
+            };
+
+            if matches!(frame.kind(), FrameKind::Exercise(_) | FrameKind::Unknown(_)) {
+                if let Err(violation) = unknown.on_frame(stream_id, &frame) {
+                    close_on_violation(&quic_connection, &violation);
+                    return false;
+                }
+            }
+
+            if !matches!(frame.kind(), FrameKind::Data) {
+                continue;
+            }
+
+            // Capsules might span across multiple DATA frames.
+            if !memory.charge(frame.payload().len()) {
+                debug!("Memory budget exhausted by capsules");
+                return true;
+            }
+            buffer.extend_from_slice(frame.payload());
+
+            // The payloads of the capsules stay counted till the application reads them.
+            let mut forwarded = 0;
+            let mut buffer_reader = BufferReader::new(&buffer);
+            while let Some(h3capsule) = H3Capsule::read_from_buffer(&mut buffer_reader) {
+                if h3capsule.capsule_type() == HEARTBEAT_CAPSULE_TYPE {
+                    trace!("Heartbeat received");
+                    continue;
+                }
+
+                let capsule = Capsule::read(&h3capsule);
+                forwarded += capsule.payload().len();
+
+                if incoming.send(capsule).await.is_err() {
+                    return false;
+                }
+            }
+
+            let consumed = buffer_reader.offset();
+            buffer.drain(..consumed);
+            memory.release(consumed - forwarded);
+
+            if is_capsule_too_large(&buffer) {
+                return true;
+            }
+        }
+    };
+
+    let writer = async {
+        loop {
+            let interval = *heartbeat.borrow_and_update();
+
+            let capsule = tokio::select! {
+                capsule = outgoing.recv() => match capsule {
+                    Some(capsule) => capsule,
+                    None => return,
+                },
+                () = heartbeat_timeout(interval) => {
+                    trace!("Sending heartbeat");
+                    Capsule::new(HEARTBEAT_CAPSULE_TYPE, Bytes::new())
+                }
+                result = heartbeat.changed() => match result {
+                    Ok(()) => continue,
+                    Err(_) => return,
+                },
+            };
+
+            let frame = Frame::new_data(Cow::Owned(capsule.write()));
+
+            if let Err(error) = proto.write_frame_async(frame, &mut *send_stream).await {
+                debug!("Session stream writing ended: {:?}", error);
+                return;
+            }
+        }
+    };
+
+    let too_large = tokio::select! {
+        too_large = reader => too_large,
+        () = writer => false,
+    };
+
+    memory.release(buffer.len());
+
+    if too_large {
+        debug!("Capsule too large: resetting session stream");
+        let error_code = ErrorCode::ExcessiveLoad.to_code();
+        send_stream.abort(error_code);
+        let _ = recv_stream.stop(error_code);
+    }
+}
+
+/// Returns whether the capsule at the start of `buffer`, possibly incomplete, is larger
+/// than [`MAX_CAPSULE_SIZE`].
+fn is_capsule_too_large(buffer: &[u8]) -> bool {
+    if buffer.len() > MAX_CAPSULE_SIZE {
+        return true;
+    }
+
+    let mut buffer_reader = BufferReader::new(buffer);
+    let size = buffer_reader.get_varint().and_then(|_capsule_type| {
+        let payload_len = buffer_reader.get_varint()?.into_inner();
+        Some((buffer_reader.offset() as u64).saturating_add(payload_len))
+    });
+
+    matches!(size, Some(size) if size > MAX_CAPSULE_SIZE as u64)
+}
+
+/// Reads a stream, recording the error code if the peer resets it.
+struct EndRecorder<'a> {
+    stream: &'a mut QuicRecvStream,
+    reset: Option<VarInt>,
+}
+
+impl AsyncRead for EndRecorder<'_> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let result = ready!(Pin::new(&mut *self.stream).poll_read(cx, buf));
+
+        if let Err(io_error) = &result {
+            if let Some(quinn::ReadError::Reset(code)) = io_error
+                .get_ref()
+                .and_then(|error| error.downcast_ref::<quinn::ReadError>())
+            {
+                self.reset = Some(varint_q2w(*code));
+            }
+        }
+
+        Poll::Ready(result)
+    }
+}
+
+async fn heartbeat_timeout(interval: Option<Duration>) {
+    match interval {
+        Some(interval) => tokio::time::sleep(interval).await,
+        None => pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+    use wtransport_proto::bytes::BufferWriter;
+    use wtransport_proto::bytes::BytesWriter;
+
+    fn capsule_header(payload_len: usize) -> Vec<u8> {
+        let mut buffer = [0; 16];
+        let mut buffer_writer = BufferWriter::new(&mut buffer);
+        buffer_writer.put_varint(VarInt::from_u32(0x01)).unwrap();
+        buffer_writer
+            .put_varint(VarInt::try_from_u64(payload_len as u64).unwrap())
+            .unwrap();
+        buffer_writer.buffer_written().to_vec()
+    }
+
+    #[test]
+    fn capsule_size() {
+        assert!(!is_capsule_too_large(&[]));
+        assert!(!is_capsule_too_large(&capsule_header(1024)));
+        // Header of 5 bytes.
+        assert!(!is_capsule_too_large(&capsule_header(MAX_CAPSULE_SIZE - 5)));
+        assert!(is_capsule_too_large(&capsule_header(MAX_CAPSULE_SIZE - 4)));
+
+        // Declared too large, even though (almost) nothing was received.
+        assert!(is_capsule_too_large(&capsule_header(MAX_CAPSULE_SIZE)));
+        assert!(is_capsule_too_large(&capsule_header(1 << 40)));
+
+        // Endless partial capsule.
+        let mut buffer = capsule_header(1024);
+        buffer.resize(MAX_CAPSULE_SIZE + 1, 0);
+        assert!(is_capsule_too_large(&buffer));
+    }
+
+    #[tokio::test]
+    async fn too_large_capsule_resets() {
+        let peers = test_utils::connect().await;
+        let capsule_type = VarInt::from_u32(0x42);
+
+        let small = crate::capsule::Capsule::new(capsule_type, vec![0; 1024]);
+        peers
+            .client_connection
+            .session_stream()
+            .send(small)
+            .await
+            .unwrap();
+        let received = peers.server_connection.session_stream().receive().await;
+        assert_eq!(received.unwrap().payload().len(), 1024);
+
+        let large = crate::capsule::Capsule::new(capsule_type, vec![0; MAX_CAPSULE_SIZE]);
+        peers
+            .client_connection
+            .session_stream()
+            .send(large)
+            .await
+            .unwrap();
+
+        let end = peers.client_connection.session_stream().ended().await;
+        assert_eq!(
+            end.unwrap(),
+            StreamEnd::Reset(ErrorCode::ExcessiveLoad.to_code())
+        );
+    }
+}
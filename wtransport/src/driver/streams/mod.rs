@@ -4,11 +4,14 @@ use crate::driver::utils::varint_w2q;
 use crate::error::StreamReadError;
 use crate::error::StreamReadExactError;
 use crate::error::StreamWriteError;
+use bytes::Bytes;
+use std::future::Future;
 use std::pin::Pin;
 use std::task::ready;
 use std::task::Context;
 use std::task::Poll;
 use tokio::io::ReadBuf;
+use wtransport_proto::error::ErrorCode;
 use wtransport_proto::frame::Frame;
 use wtransport_proto::ids::SessionId;
 use wtransport_proto::ids::StreamId;
@@ -40,12 +43,43 @@ impl QuicSendStream {
         Ok(())
     }
 
+    #[inline(always)]
+    pub async fn write_chunk(&mut self, chunk: Bytes) -> Result<(), StreamWriteError> {
+        self.0.write_chunk(chunk).await?;
+        Ok(())
+    }
+
     #[inline(always)]
     pub async fn finish(&mut self) -> Result<(), StreamWriteError> {
         self.0.finish().await?;
         Ok(())
     }
 
+    pub fn poll_write(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, StreamWriteError>> {
+        tokio::io::AsyncWrite::poll_write(Pin::new(&mut self.0), cx, buf).map_err(|io_error| {
+            io_error
+                .into_inner()
+                .and_then(|error| error.downcast::<quinn::WriteError>().ok())
+                .map_or(StreamWriteError::QuicProto, |error| (*error).into())
+        })
+    }
+
+    #[inline(always)]
+    pub fn poll_finish(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), StreamWriteError>> {
+        self.0.poll_finish(cx).map_err(Into::into)
+    }
+
+    pub fn poll_stopped(&mut self, cx: &mut Context<'_>) -> Poll<StreamWriteError> {
+        self.0.poll_stopped(cx).map(|result| match result {
+            Ok(code) => StreamWriteError::Stopped(varint_q2w(code)),
+            Err(error) => StreamWriteError::from_quinn_stopped(error),
+        })
+    }
+
     #[inline(always)]
     pub fn set_priority(&self, priority: i32) {
         let _ = self.0.set_priority(priority);
@@ -53,16 +87,16 @@ impl QuicSendStream {
 
     #[inline(always)]
     pub fn priority(&self) -> i32 {
+        // Streams reset by chaos testing are still in use by the application.
+        #[cfg(feature = "chaos")]
+        return self.0.priority().unwrap_or_default();
+
+        #[cfg(not(feature = "chaos"))]
         self.0.priority().expect("Stream has been reset")
     }
 
     pub async fn stopped(&mut self) -> StreamWriteError {
-        match self.0.stopped().await {
-            Ok(code) => StreamWriteError::Stopped(varint_q2w(code)),
-            Err(quinn::StoppedError::ConnectionLost(_)) => StreamWriteError::NotConnected,
-            Err(quinn::StoppedError::UnknownStream) => StreamWriteError::QuicProto,
-            Err(quinn::StoppedError::ZeroRttRejected) => StreamWriteError::QuicProto,
-        }
+        std::future::poll_fn(|cx| self.poll_stopped(cx)).await
     }
 
     #[inline(always)]
@@ -72,6 +106,11 @@ impl QuicSendStream {
             .expect("Stream has been already reset")
     }
 
+    /// Resets the stream in place, ignoring whether it is already reset.
+    pub fn abort(&mut self, error_code: VarInt) {
+        let _ = self.0.reset(varint_w2q(error_code));
+    }
+
     #[inline(always)]
     pub fn id(&self) -> StreamId {
         streamid_q2w(self.0.id())
@@ -145,10 +184,44 @@ impl QuicRecvStream {
         self.0
             .read_exact(buf)
             .await
-            .map_err(|quic_error| match quic_error {
-                quinn::ReadExactError::FinishedEarly => StreamReadExactError::FinishedEarly,
-                quinn::ReadExactError::ReadError(read) => StreamReadExactError::Read(read.into()),
-            })
+            .map_err(StreamReadExactError::from_quinn)
+    }
+
+    #[inline(always)]
+    pub async fn read_chunk(
+        &mut self,
+        max_length: usize,
+    ) -> Result<Option<Bytes>, StreamReadError> {
+        Ok(self
+            .0
+            .read_chunk(max_length, true)
+            .await?
+            .map(|chunk| chunk.bytes))
+    }
+
+    pub fn poll_read(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<Option<usize>, StreamReadError>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(Some(0)));
+        }
+
+        let mut buffer = ReadBuf::new(buf);
+
+        match ready!(tokio::io::AsyncRead::poll_read(
+            Pin::new(&mut self.0),
+            cx,
+            &mut buffer
+        )) {
+            Ok(()) if buffer.filled().is_empty() => Poll::Ready(Ok(None)),
+            Ok(()) => Poll::Ready(Ok(Some(buffer.filled().len()))),
+            Err(io_error) => Poll::Ready(Err(io_error
+                .into_inner()
+                .and_then(|error| error.downcast::<quinn::ReadError>().ok())
+                .map_or(StreamReadError::QuicProto, |error| (*error).into()))),
+        }
     }
 
     #[inline(always)]
@@ -198,6 +271,33 @@ pub struct Stream<S, P> {
     proto: P,
 }
 
+/// A stream whose header is being written, reset if dropped before completion.
+///
+/// Otherwise, the stream would be implicitly finished, with a truncated header.
+struct PendingSendStream(Option<QuicSendStream>);
+
+impl PendingSendStream {
+    fn new(stream: QuicSendStream) -> Self {
+        Self(Some(stream))
+    }
+
+    fn get_mut(&mut self) -> &mut QuicSendStream {
+        self.0.as_mut().expect("Stream taken only once")
+    }
+
+    fn into_inner(mut self) -> QuicSendStream {
+        self.0.take().expect("Stream taken only once")
+    }
+}
+
+impl Drop for PendingSendStream {
+    fn drop(&mut self) {
+        if let Some(stream) = self.0.as_mut() {
+            stream.abort(ErrorCode::RequestCancelled.to_code());
+        }
+    }
+}
+
 pub mod biremote {
     use super::*;
 
@@ -237,6 +337,16 @@ pub mod biremote {
             self.proto.read_frame_async(&mut self.stream.1).await
         }
 
+        pub async fn write_frame<'a>(&mut self, frame: Frame<'a>) -> Result<(), ProtoWriteError> {
+            self.proto
+                .write_frame_async(frame, &mut self.stream.0)
+                .await
+        }
+
+        pub async fn finish(mut self) {
+            let _ = self.stream.0.finish().await;
+        }
+
         pub fn stop(&mut self, error_code: VarInt) -> Result<(), AlreadyStop> {
             self.stream.1.stop(error_code)
         }
@@ -308,19 +418,24 @@ pub mod bilocal {
     }
 
     impl StreamBiLocalH3 {
-        pub async fn upgrade(
-            mut self,
+        /// Writes the stream header.
+        ///
+        /// If dropped before completion (or failing), the stream is reset rather than finished.
+        pub fn upgrade(
+            self,
             session_id: SessionId,
-        ) -> Result<StreamBiLocalWT, ProtoWriteError> {
-            let proto = self
-                .proto
-                .upgrade_async(session_id, &mut self.stream.0)
-                .await?;
+        ) -> impl Future<Output = Result<StreamBiLocalWT, ProtoWriteError>> {
+            let (send, recv) = self.stream;
+            let mut send = PendingSendStream::new(send);
 
-            Ok(StreamBiLocalWT {
-                stream: self.stream,
-                proto,
-            })
+            async move {
+                let proto = self.proto.upgrade_async(session_id, send.get_mut()).await?;
+
+                Ok(StreamBiLocalWT {
+                    stream: (send.into_inner(), recv),
+                    proto,
+                })
+            }
         }
 
         pub fn into_session(self, session_request: SessionRequest) -> session::StreamSession {
@@ -380,6 +495,11 @@ pub mod uniremote {
             self.proto.kind()
         }
 
+        #[inline(always)]
+        pub fn id(&self) -> StreamId {
+            self.stream.id()
+        }
+
         pub fn upgrade(self) -> StreamUniRemoteWT {
             StreamUniRemoteWT {
                 stream: self.stream,
@@ -387,6 +507,10 @@ pub mod uniremote {
             }
         }
 
+        pub fn into_stream(self) -> QuicRecvStream {
+            self.stream
+        }
+
         pub fn stream_mut(&mut self) -> &mut QuicRecvStream {
             &mut self.stream
         }
@@ -429,19 +553,26 @@ pub mod unilocal {
             })
         }
 
-        pub async fn upgrade(
-            mut self,
+        /// Writes the stream header.
+        ///
+        /// If dropped before completion (or failing), the stream is reset rather than finished.
+        pub fn upgrade(
+            self,
             stream_header: StreamHeader,
-        ) -> Result<StreamUniLocalH3, ProtoWriteError> {
-            let proto = self
-                .proto
-                .upgrade_async(stream_header, &mut self.stream)
-                .await?;
-
-            Ok(StreamUniLocalH3 {
-                stream: self.stream,
-                proto,
-            })
+        ) -> impl Future<Output = Result<StreamUniLocalH3, ProtoWriteError>> {
+            let mut stream = PendingSendStream::new(self.stream);
+
+            async move {
+                let proto = self
+                    .proto
+                    .upgrade_async(stream_header, stream.get_mut())
+                    .await?;
+
+                Ok(StreamUniLocalH3 {
+                    stream: stream.into_inner(),
+                    proto,
+                })
+            }
         }
     }
 
@@ -454,10 +585,25 @@ pub mod unilocal {
             self.proto.kind()
         }
 
+        #[inline(always)]
+        pub fn id(&self) -> StreamId {
+            self.stream.id()
+        }
+
         pub async fn stopped(&mut self) -> StreamWriteError {
             self.stream.stopped().await
         }
 
+        /// Writes raw bytes (e.g., encoded frames) on the stream. Cancel-safe.
+        #[inline(always)]
+        pub async fn write(&mut self, buf: &[u8]) -> Result<usize, StreamWriteError> {
+            self.stream.write(buf).await
+        }
+
+        pub fn into_stream(self) -> QuicSendStream {
+            self.stream
+        }
+
         pub fn upgrade(self) -> StreamUniLocalWT {
             StreamUniLocalWT {
                 stream: self.stream,
@@ -480,8 +626,13 @@ pub mod session {
         Stream<(QuicSendStream, QuicRecvStream), stream_proto::session::StreamSession>;
 
     impl StreamSession {
-        pub async fn read_frame<'a>(&mut self) -> Result<Frame<'a>, ProtoReadError> {
-            self.proto.read_frame_async(&mut self.stream.1).await
+        pub async fn read_frame_with_limit<'a>(
+            &mut self,
+            max_payload_size: usize,
+        ) -> Result<Frame<'a>, ProtoReadError> {
+            self.proto
+                .read_frame_async_with_limit(&mut self.stream.1, max_payload_size)
+                .await
         }
 
         pub async fn write_frame<'a>(&mut self, frame: Frame<'a>) -> Result<(), ProtoWriteError> {
@@ -490,10 +641,6 @@ pub mod session {
                 .await
         }
 
-        pub fn stop(&mut self, error_code: VarInt) -> Result<(), AlreadyStop> {
-            self.stream.1.stop(error_code)
-        }
-
         pub fn id(&self) -> StreamId {
             self.stream.0.id()
         }
@@ -512,28 +659,6 @@ pub mod session {
     }
 }
 
-impl From<quinn::WriteError> for StreamWriteError {
-    fn from(error: quinn::WriteError) -> Self {
-        match error {
-            quinn::WriteError::Stopped(code) => StreamWriteError::Stopped(varint_q2w(code)),
-            quinn::WriteError::ConnectionLost(_) => StreamWriteError::NotConnected,
-            quinn::WriteError::UnknownStream => StreamWriteError::QuicProto,
-            quinn::WriteError::ZeroRttRejected => StreamWriteError::QuicProto,
-        }
-    }
-}
-
-impl From<quinn::ReadError> for StreamReadError {
-    fn from(error: quinn::ReadError) -> Self {
-        match error {
-            quinn::ReadError::Reset(code) => StreamReadError::Reset(varint_q2w(code)),
-            quinn::ReadError::ConnectionLost(_) => StreamReadError::NotConnected,
-            quinn::ReadError::UnknownStream => StreamReadError::QuicProto,
-            quinn::ReadError::IllegalOrderedRead => StreamReadError::QuicProto,
-            quinn::ReadError::ZeroRttRejected => StreamReadError::QuicProto,
-        }
-    }
-}
-
+pub mod capsules;
 pub mod qpack;
 pub mod settings;
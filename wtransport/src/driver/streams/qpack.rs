@@ -1,5 +1,6 @@
 use crate::driver::streams::uniremote::StreamUniRemoteH3;
 use crate::driver::DriverError;
+use crate::driver::Violation;
 use crate::error::StreamReadError;
 use crate::error::StreamReadExactError;
 use std::future::pending;
@@ -40,16 +41,25 @@ impl RemoteQPackEncStream {
             match stream.stream_mut().read_exact(&mut self.buffer).await {
                 Ok(()) => {}
                 Err(StreamReadExactError::FinishedEarly) => {
-                    return DriverError::Proto(ErrorCode::ClosedCriticalStream);
+                    return DriverError::Proto(
+                        Violation::new(ErrorCode::ClosedCriticalStream, "QPACK stream closed")
+                            .on_stream(stream.id()),
+                    );
                 }
                 Err(StreamReadExactError::Read(StreamReadError::NotConnected)) => {
                     return DriverError::NotConnected;
                 }
                 Err(StreamReadExactError::Read(StreamReadError::Reset(_))) => {
-                    return DriverError::Proto(ErrorCode::ClosedCriticalStream);
+                    return DriverError::Proto(
+                        Violation::new(ErrorCode::ClosedCriticalStream, "QPACK stream closed")
+                            .on_stream(stream.id()),
+                    );
                 }
                 Err(StreamReadExactError::Read(StreamReadError::QuicProto)) => {
-                    return DriverError::Proto(ErrorCode::ClosedCriticalStream);
+                    return DriverError::Proto(
+                        Violation::new(ErrorCode::ClosedCriticalStream, "QPACK stream closed")
+                            .on_stream(stream.id()),
+                    );
                 }
             }
         }
@@ -89,16 +99,25 @@ impl RemoteQPackDecStream {
             match stream.stream_mut().read_exact(&mut self.buffer).await {
                 Ok(()) => {}
                 Err(StreamReadExactError::FinishedEarly) => {
-                    return DriverError::Proto(ErrorCode::ClosedCriticalStream);
+                    return DriverError::Proto(
+                        Violation::new(ErrorCode::ClosedCriticalStream, "QPACK stream closed")
+                            .on_stream(stream.id()),
+                    );
                 }
                 Err(StreamReadExactError::Read(StreamReadError::NotConnected)) => {
                     return DriverError::NotConnected;
                 }
                 Err(StreamReadExactError::Read(StreamReadError::Reset(_))) => {
-                    return DriverError::Proto(ErrorCode::ClosedCriticalStream);
+                    return DriverError::Proto(
+                        Violation::new(ErrorCode::ClosedCriticalStream, "QPACK stream closed")
+                            .on_stream(stream.id()),
+                    );
                 }
                 Err(StreamReadExactError::Read(StreamReadError::QuicProto)) => {
-                    return DriverError::Proto(ErrorCode::ClosedCriticalStream);
+                    return DriverError::Proto(
+                        Violation::new(ErrorCode::ClosedCriticalStream, "QPACK stream closed")
+                            .on_stream(stream.id()),
+                    );
                 }
             }
         }
@@ -3,7 +3,10 @@ use crate::driver::streams::uniremote::StreamUniRemoteH3;
 use crate::driver::streams::ProtoReadError;
 use crate::driver::streams::ProtoWriteError;
 use crate::driver::DriverError;
+use crate::driver::Violation;
 use crate::error::StreamWriteError;
+use crate::extension::Grease;
+use crate::extension::UnknownHandler;
 use std::future::pending;
 use tokio::sync::watch;
 use wtransport_proto::bytes;
@@ -17,22 +20,33 @@ use wtransport_proto::varint::VarInt;
 pub struct LocalSettingsStream {
     stream: Option<StreamUniLocalH3>,
     settings: Settings,
+    pending: Vec<u8>,
 }
 
 impl LocalSettingsStream {
-    pub fn empty() -> Self {
-        let settings = Settings::builder()
+    pub fn empty(legacy_datagrams: bool, grease: &Grease) -> Self {
+        let mut builder = Settings::builder()
             .qpack_max_table_capacity(VarInt::from_u32(0))
             .qpack_blocked_streams(VarInt::from_u32(0))
             .enable_connect_protocol() // TODO(biagio): it would be nice to have this only for server
             .enable_webtransport()
             .enable_h3_datagrams()
-            .webtransport_max_sessions(VarInt::from_u32(1))
-            .build();
+            .webtransport_max_sessions(VarInt::from_u32(1));
+
+        if legacy_datagrams {
+            builder = builder.enable_h3_datagrams_drafts();
+        }
+
+        if let Some((id, value)) = grease.setting() {
+            builder = builder.exercise(id, value);
+        }
+
+        let settings = builder.build();
 
         Self {
             stream: None,
             settings,
+            pending: Vec::new(),
         }
     }
 
@@ -40,35 +54,73 @@ impl LocalSettingsStream {
         self.stream.is_none()
     }
 
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
     pub fn set_stream(&mut self, stream: StreamUniLocalH3) {
         assert!(matches!(stream.kind(), StreamKind::Control));
         self.stream = Some(stream);
     }
 
     pub async fn send_settings(&mut self) -> Result<(), DriverError> {
-        match self
-            .stream
+        Self::write_frame(&mut self.stream, self.settings.generate_frame()).await
+    }
+
+    /// Queues a frame, sent after SETTINGS while [`run`](Self::run) is polled.
+    pub fn queue_frame(&mut self, frame: Frame<'_>) {
+        frame
+            .write(&mut self.pending)
+            .expect("Vec does not have EOF");
+    }
+
+    async fn write_frame(
+        stream: &mut Option<StreamUniLocalH3>,
+        frame: Frame<'_>,
+    ) -> Result<(), DriverError> {
+        match stream
             .as_mut()
-            .expect("Cannot send settings on empty stream")
-            .write_frame(self.settings.generate_frame())
+            .expect("Cannot send frames on empty stream")
+            .write_frame(frame)
             .await
         {
             Ok(()) => Ok(()),
             Err(ProtoWriteError::NotConnected) => Err(DriverError::NotConnected),
-            Err(ProtoWriteError::Stopped) => {
-                Err(DriverError::Proto(ErrorCode::ClosedCriticalStream))
-            }
+            Err(ProtoWriteError::Stopped) => Err(DriverError::proto(
+                ErrorCode::ClosedCriticalStream,
+                "Control stream stopped",
+            )),
         }
     }
 
+    /// Sends the queued frames, then awaits the stream is stopped.
+    ///
+    /// Cancel-safe: queued bytes are only discarded once written.
     pub async fn run(&mut self) -> DriverError {
-        match self.stream.as_mut() {
-            Some(stream) => match stream.stopped().await {
-                StreamWriteError::NotConnected => DriverError::NotConnected,
-                StreamWriteError::Stopped(_) => DriverError::Proto(ErrorCode::ClosedCriticalStream),
-                StreamWriteError::QuicProto => DriverError::Proto(ErrorCode::ClosedCriticalStream),
-            },
-            None => pending().await,
+        let stream = match self.stream.as_mut() {
+            Some(stream) => stream,
+            None => return pending().await,
+        };
+
+        let error = loop {
+            if self.pending.is_empty() {
+                break stream.stopped().await;
+            }
+
+            match stream.write(&self.pending).await {
+                Ok(written) => {
+                    self.pending.drain(..written);
+                }
+                Err(error) => break error,
+            }
+        };
+
+        match error {
+            StreamWriteError::NotConnected => DriverError::NotConnected,
+            StreamWriteError::Stopped(_) | StreamWriteError::QuicProto => DriverError::Proto(
+                Violation::new(ErrorCode::ClosedCriticalStream, "Control stream stopped")
+                    .on_stream(stream.id()),
+            ),
         }
     }
 }
@@ -76,13 +128,15 @@ impl LocalSettingsStream {
 pub struct RemoteSettingsStream {
     stream: Option<StreamUniRemoteH3>,
     settings: watch::Sender<Option<Settings>>,
+    unknown: UnknownHandler,
 }
 
 impl RemoteSettingsStream {
-    pub fn empty() -> Self {
+    pub fn empty(unknown: UnknownHandler) -> Self {
         Self {
             stream: None,
             settings: watch::channel(None).0,
+            unknown,
         }
     }
 
@@ -108,36 +162,70 @@ impl RemoteSettingsStream {
 
             if self.settings.borrow().is_none() {
                 if !matches!(frame.kind(), FrameKind::Settings) {
-                    return DriverError::Proto(ErrorCode::MissingSettings);
+                    return DriverError::Proto(
+                        self.violation(ErrorCode::MissingSettings, "First frame is not SETTINGS")
+                            .with_frame(frame.kind()),
+                    );
                 }
 
                 let settings = match Settings::with_frame(&frame) {
                     Ok(settings) => settings,
-                    Err(error_code) => return DriverError::Proto(error_code),
+                    Err(error_code) => {
+                        return DriverError::Proto(
+                            self.violation(error_code, "Invalid SETTINGS frame")
+                                .with_frame(frame.kind()),
+                        )
+                    }
                 };
 
                 self.settings.send_replace(Some(settings));
-            } else if !matches!(frame.kind(), FrameKind::Exercise(_)) {
-                return DriverError::Proto(ErrorCode::FrameUnexpected);
+            } else if matches!(frame.kind(), FrameKind::Exercise(_) | FrameKind::Unknown(_)) {
+                let stream_id = self.stream.as_ref().expect("Stream is set").id();
+
+                if let Err(violation) = self.unknown.on_frame(stream_id, &frame) {
+                    return DriverError::Proto(violation);
+                }
+            } else {
+                return DriverError::Proto(
+                    self.violation(
+                        ErrorCode::FrameUnexpected,
+                        "Unexpected frame on control stream",
+                    )
+                    .with_frame(frame.kind()),
+                );
             }
         }
     }
 
+    fn violation(&self, error_code: ErrorCode, rule: &'static str) -> Violation {
+        let violation = Violation::new(error_code, rule);
+
+        match self.stream.as_ref() {
+            Some(stream) => violation.on_stream(stream.id()),
+            None => violation,
+        }
+    }
+
     async fn read_frame<'a>(&mut self) -> Result<Frame<'a>, DriverError> {
         let stream = match self.stream.as_mut() {
             Some(stream) => stream,
             None => return pending().await,
         };
 
+        let stream_id = stream.id();
+
         match stream.read_frame().await {
             Ok(frame) => Ok(frame),
-            Err(ProtoReadError::H3(error_code)) => Err(DriverError::Proto(error_code)),
+            Err(ProtoReadError::H3(error_code)) => Err(DriverError::Proto(
+                Violation::new(error_code, "Invalid frame on control stream").on_stream(stream_id),
+            )),
             Err(ProtoReadError::IO(io_error)) => match io_error {
                 bytes::IoReadError::ImmediateFin
                 | bytes::IoReadError::UnexpectedFin
-                | bytes::IoReadError::Reset => {
-                    Err(DriverError::Proto(ErrorCode::ClosedCriticalStream))
-                }
+                | bytes::IoReadError::Reset => Err(DriverError::Proto(
+                    Violation::new(ErrorCode::ClosedCriticalStream, "Control stream closed")
+                        .on_stream(stream_id),
+                )),
                 bytes::IoReadError::NotConnected => Err(DriverError::NotConnected),
             },
         }
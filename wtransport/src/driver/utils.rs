@@ -1,7 +1,36 @@
+#[cfg(feature = "chaos")]
+use crate::chaos::ChaosState;
+#[cfg(feature = "chaos")]
+use crate::chaos::StreamChaos;
+use crate::connection::Milestone;
+use crate::connection::MilestoneEvent;
+use crate::connection::SessionStats;
+use crate::connection::StreamStats;
+use crate::quota::QuotaStreamSlot;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicU8;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::sync::Weak;
+use std::task::Context;
+use std::task::Poll;
+use std::task::RawWaker;
+use std::task::RawWakerVTable;
+use std::task::Wake;
+use std::task::Waker;
+use std::time::Duration;
 use tokio::sync::mpsc;
 use tokio::sync::watch;
 use tokio::sync::Mutex;
+use tokio::sync::OwnedMutexGuard;
+use tokio::time::Instant;
+use wtransport_proto::datagram::DatagramFormat;
+use wtransport_proto::ids::SessionId;
 use wtransport_proto::ids::StreamId;
 use wtransport_proto::varint::VarInt;
 
@@ -115,6 +144,460 @@ where
     }
 }
 
+/// Tracks the number of streams currently handed to the application.
+pub struct StreamsTracker(Arc<watch::Sender<usize>>);
+
+impl StreamsTracker {
+    #[inline(always)]
+    pub fn new() -> Self {
+        Self(Arc::new(watch::channel(0).0))
+    }
+
+    /// Registers a new stream of session `session_id` (`None` for streams outside of
+    /// any session), recording its opening and I/O in `activity`.
+    ///
+    /// The stream is considered active till the returned guard (and all its clones)
+    /// are dropped.
+    pub fn guard(&self, activity: Activity, session_id: Option<SessionId>) -> StreamGuard {
+        self.0.send_modify(|active| *active += 1);
+        activity.touch();
+        StreamGuard {
+            _inner: Arc::new(StreamGuardInner(self.0.clone())),
+            _quota: None,
+            activity,
+            session_id,
+        }
+    }
+
+    /// Returns the number of active streams.
+    #[inline(always)]
+    pub fn active(&self) -> usize {
+        *self.0.borrow()
+    }
+
+    /// Awaits there are no more active streams.
+    pub async fn idle(&self) {
+        let mut watcher = self.0.subscribe();
+
+        loop {
+            if *watcher.borrow_and_update() == 0 {
+                return;
+            }
+
+            if watcher.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct StreamGuard {
+    _inner: Arc<StreamGuardInner>,
+    _quota: Option<Arc<QuotaStreamSlot>>,
+    activity: Activity,
+    session_id: Option<SessionId>,
+}
+
+impl StreamGuard {
+    /// Counts the stream in a quota too, till the guard (and all its clones) are dropped.
+    #[inline(always)]
+    pub fn with_quota(mut self, slot: QuotaStreamSlot) -> Self {
+        self._quota = Some(Arc::new(slot));
+        self
+    }
+
+    /// Records I/O on the stream.
+    #[inline(always)]
+    pub fn touch(&self) {
+        self.activity.touch();
+    }
+
+    /// Returns the byte counters of stream `id`.
+    #[inline(always)]
+    pub fn stream_flow(&self, id: StreamId) -> Arc<StreamFlow> {
+        self.activity.stream_flow(id)
+    }
+
+    /// Returns the session the stream belongs to, if any.
+    #[inline(always)]
+    pub fn session_id(&self) -> Option<SessionId> {
+        self.session_id
+    }
+}
+
+/// Time of the last application activity (sending or receiving) on a connection.
+#[derive(Clone)]
+pub struct Activity(Arc<ActivityInner>);
+
+struct ActivityInner {
+    epoch: Instant,
+    last: AtomicU64,
+    first_data: AtomicBool,
+    milestones: MilestoneLog,
+    flow: FlowCounters,
+    #[cfg(feature = "chaos")]
+    chaos: ChaosState,
+}
+
+impl Activity {
+    pub fn new() -> Self {
+        Self(Arc::new(ActivityInner {
+            epoch: Instant::now(),
+            last: AtomicU64::new(0),
+            first_data: AtomicBool::new(false),
+            milestones: MilestoneLog::new(),
+            flow: FlowCounters::default(),
+            #[cfg(feature = "chaos")]
+            chaos: ChaosState::default(),
+        }))
+    }
+
+    /// Records activity now.
+    #[inline(always)]
+    pub fn touch(&self) {
+        let elapsed = self.0.epoch.elapsed().as_micros() as u64;
+        self.0.last.fetch_max(elapsed, Ordering::Relaxed);
+
+        if !self.0.first_data.load(Ordering::Relaxed)
+            && !self.0.first_data.swap(true, Ordering::Relaxed)
+        {
+            self.0.milestones.record(Milestone::FirstApplicationData);
+        }
+    }
+
+    /// Returns the establishment milestones of the connection.
+    #[inline(always)]
+    pub fn milestones(&self) -> &MilestoneLog {
+        &self.0.milestones
+    }
+
+    /// Returns the time of the last activity (the creation time if none).
+    pub fn last(&self) -> Instant {
+        self.0.epoch + Duration::from_micros(self.0.last.load(Ordering::Relaxed))
+    }
+
+    /// Records a datagram sent, with a payload of `size` bytes.
+    #[inline(always)]
+    pub fn datagram_sent(&self, size: usize) {
+        self.touch();
+        self.0.flow.datagrams_sent.fetch_add(1, Ordering::Relaxed);
+        self.0
+            .flow
+            .datagram_bytes_sent
+            .fetch_add(size as u64, Ordering::Relaxed);
+    }
+
+    /// Records a datagram received, with a payload of `size` bytes.
+    #[inline(always)]
+    pub fn datagram_received(&self, size: usize) {
+        self.touch();
+        self.0
+            .flow
+            .datagrams_received
+            .fetch_add(1, Ordering::Relaxed);
+        self.0
+            .flow
+            .datagram_bytes_received
+            .fetch_add(size as u64, Ordering::Relaxed);
+    }
+
+    /// Returns the counters of stream `id`, registering it if needed.
+    ///
+    /// Both halves of a bidirectional stream share the same counters.
+    pub fn stream_flow(&self, id: StreamId) -> Arc<StreamFlow> {
+        let mut streams = self.0.flow.streams.lock().expect("Not poisoned");
+
+        if let Some(flow) = streams.get(&id).and_then(Weak::upgrade) {
+            return flow;
+        }
+
+        let flow = Arc::new(StreamFlow {
+            id,
+            sent: AtomicU64::new(0),
+            received: AtomicU64::new(0),
+            activity: self.clone(),
+            #[cfg(feature = "chaos")]
+            chaos: StreamChaos::default(),
+        });
+        streams.insert(id, Arc::downgrade(&flow));
+
+        flow
+    }
+
+    /// Returns the failures pending on the connection.
+    #[cfg(feature = "chaos")]
+    #[inline(always)]
+    pub fn chaos(&self) -> &ChaosState {
+        &self.0.chaos
+    }
+
+    /// Returns whether the next datagram received must be dropped (chaos testing).
+    #[cfg(feature = "chaos")]
+    #[inline(always)]
+    pub fn drops_incoming_datagram(&self) -> bool {
+        self.0.chaos.drops_incoming()
+    }
+
+    #[cfg(not(feature = "chaos"))]
+    #[inline(always)]
+    pub fn drops_incoming_datagram(&self) -> bool {
+        false
+    }
+
+    /// Returns whether the next datagram sent must be dropped (chaos testing).
+    #[cfg(feature = "chaos")]
+    #[inline(always)]
+    pub fn drops_outgoing_datagram(&self) -> bool {
+        self.0.chaos.drops_outgoing()
+    }
+
+    #[cfg(not(feature = "chaos"))]
+    #[inline(always)]
+    pub fn drops_outgoing_datagram(&self) -> bool {
+        false
+    }
+
+    /// Returns the streams in use by the application.
+    #[cfg(feature = "chaos")]
+    pub fn streams_in_use(&self) -> Vec<Arc<StreamFlow>> {
+        self.0
+            .flow
+            .streams
+            .lock()
+            .expect("Not poisoned")
+            .values()
+            .filter_map(Weak::upgrade)
+            .collect()
+    }
+
+    /// Returns the bytes exchanged by the application so far.
+    pub fn session_stats(&self) -> SessionStats {
+        let flow = &self.0.flow;
+
+        let mut streams = flow
+            .streams
+            .lock()
+            .expect("Not poisoned")
+            .values()
+            .filter_map(Weak::upgrade)
+            .map(|stream| StreamStats {
+                id: stream.id,
+                bytes_sent: stream.sent.load(Ordering::Relaxed),
+                bytes_received: stream.received.load(Ordering::Relaxed),
+            })
+            .collect::<Vec<_>>();
+        streams.sort_by_key(|stream| stream.id);
+
+        SessionStats {
+            stream_bytes_sent: flow.stream_bytes_sent.load(Ordering::Relaxed),
+            stream_bytes_received: flow.stream_bytes_received.load(Ordering::Relaxed),
+            datagrams_sent: flow.datagrams_sent.load(Ordering::Relaxed),
+            datagram_bytes_sent: flow.datagram_bytes_sent.load(Ordering::Relaxed),
+            datagrams_received: flow.datagrams_received.load(Ordering::Relaxed),
+            datagram_bytes_received: flow.datagram_bytes_received.load(Ordering::Relaxed),
+            streams,
+        }
+    }
+}
+
+/// Bytes exchanged by the application on a connection, per direction and transport.
+#[derive(Default)]
+struct FlowCounters {
+    stream_bytes_sent: AtomicU64,
+    stream_bytes_received: AtomicU64,
+    datagrams_sent: AtomicU64,
+    datagram_bytes_sent: AtomicU64,
+    datagrams_received: AtomicU64,
+    datagram_bytes_received: AtomicU64,
+    streams: std::sync::Mutex<HashMap<StreamId, Weak<StreamFlow>>>,
+}
+
+/// Bytes exchanged by the application on a stream in use.
+///
+/// The stream is unregistered from the connection once dropped.
+pub struct StreamFlow {
+    id: StreamId,
+    sent: AtomicU64,
+    received: AtomicU64,
+    activity: Activity,
+    #[cfg(feature = "chaos")]
+    chaos: StreamChaos,
+}
+
+impl StreamFlow {
+    /// Returns the ID of the stream.
+    #[cfg(feature = "chaos")]
+    #[inline(always)]
+    pub fn id(&self) -> StreamId {
+        self.id
+    }
+
+    /// Injects a reset of the stream, applied on its next read or write.
+    #[cfg(feature = "chaos")]
+    pub fn inject_reset(&self, error_code: VarInt) {
+        self.chaos.inject(error_code);
+    }
+
+    /// Returns the code the sending side must be reset with, if injected.
+    #[cfg(feature = "chaos")]
+    #[inline(always)]
+    pub fn take_injected_reset(&self) -> Option<VarInt> {
+        self.chaos.take_reset()
+    }
+
+    /// Returns the code the receiving side must be stopped with, if injected.
+    #[cfg(feature = "chaos")]
+    #[inline(always)]
+    pub fn take_injected_stop(&self) -> Option<VarInt> {
+        self.chaos.take_stop()
+    }
+
+    /// Returns when the stall of the reads of the connection ends, if they are stalled.
+    #[cfg(feature = "chaos")]
+    #[inline(always)]
+    pub fn stalled_until(&self) -> Option<tokio::time::Instant> {
+        self.activity.chaos().stalled_until()
+    }
+
+    /// Records `size` bytes written to the stream.
+    #[inline(always)]
+    pub fn sent(&self, size: usize) {
+        self.sent.fetch_add(size as u64, Ordering::Relaxed);
+        self.activity
+            .0
+            .flow
+            .stream_bytes_sent
+            .fetch_add(size as u64, Ordering::Relaxed);
+    }
+
+    /// Records `size` bytes read from the stream.
+    #[inline(always)]
+    pub fn received(&self, size: usize) {
+        self.received.fetch_add(size as u64, Ordering::Relaxed);
+        self.activity
+            .0
+            .flow
+            .stream_bytes_received
+            .fetch_add(size as u64, Ordering::Relaxed);
+    }
+}
+
+impl Drop for StreamFlow {
+    fn drop(&mut self) {
+        if let Ok(mut streams) = self.activity.0.flow.streams.lock() {
+            streams.remove(&self.id);
+        }
+    }
+}
+
+/// Datagram format of a connection, set once the settings are exchanged, if the peer
+/// negotiated HTTP3 datagrams.
+#[derive(Clone, Default)]
+pub struct SharedDatagramFormat(Arc<AtomicU8>);
+
+impl SharedDatagramFormat {
+    // Zero while not negotiated.
+    const QUARTER_STREAM_ID: u8 = 1;
+    const FLOW_ID: u8 = 2;
+
+    /// Returns the format, [`DatagramFormat::QuarterStreamId`] if not negotiated.
+    #[inline(always)]
+    pub fn get(&self) -> DatagramFormat {
+        self.negotiated().unwrap_or(DatagramFormat::QuarterStreamId)
+    }
+
+    /// Returns the format, or `None` if the peer did not negotiate HTTP3 datagrams.
+    #[inline(always)]
+    pub fn negotiated(&self) -> Option<DatagramFormat> {
+        match self.0.load(Ordering::Relaxed) {
+            Self::QUARTER_STREAM_ID => Some(DatagramFormat::QuarterStreamId),
+            Self::FLOW_ID => Some(DatagramFormat::FlowId),
+            _ => None,
+        }
+    }
+
+    pub fn set(&self, format: DatagramFormat) {
+        let format = match format {
+            DatagramFormat::QuarterStreamId => Self::QUARTER_STREAM_ID,
+            DatagramFormat::FlowId => Self::FLOW_ID,
+        };
+        self.0.store(format, Ordering::Relaxed);
+    }
+}
+
+/// Milestones reached by a connection, in order.
+#[derive(Clone)]
+pub struct MilestoneLog(Arc<watch::Sender<Vec<MilestoneEvent>>>);
+
+impl MilestoneLog {
+    fn new() -> Self {
+        Self(Arc::new(watch::channel(Vec::new()).0))
+    }
+
+    /// Records `milestone` now, unless it is already reached.
+    pub fn record(&self, milestone: Milestone) {
+        self.0.send_if_modified(|events| {
+            if events.iter().any(|event| event.milestone() == milestone) {
+                return false;
+            }
+
+            events.push(MilestoneEvent::new(milestone, Instant::now()));
+            true
+        });
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Vec<MilestoneEvent>> {
+        self.0.subscribe()
+    }
+}
+
+struct StreamGuardInner(Arc<watch::Sender<usize>>);
+
+impl Drop for StreamGuardInner {
+    fn drop(&mut self) {
+        self.0.send_modify(|active| *active -= 1);
+    }
+}
+
+/// A task spawned by the driver.
+pub type Task = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// Spawns driver tasks with a custom function or, by default, with [`tokio::spawn`].
+#[derive(Clone, Default)]
+pub struct Spawner(Option<Arc<dyn Fn(Task) + Send + Sync>>);
+
+impl Spawner {
+    #[inline(always)]
+    pub fn new<F>(spawn: F) -> Self
+    where
+        F: Fn(Task) + Send + Sync + 'static,
+    {
+        Self(Some(Arc::new(spawn)))
+    }
+
+    pub fn spawn<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        match &self.0 {
+            Some(spawn) => spawn(Box::pin(future)),
+            None => {
+                tokio::spawn(future);
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Spawner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            Some(_) => f.write_str("Spawner(Custom)"),
+            None => f.write_str("Spawner(Tokio)"),
+        }
+    }
+}
+
 pub struct SendError;
 
 pub enum TrySendError<T> {
@@ -140,6 +623,15 @@ impl<T> BiChannelEndpoint<T> {
         })
     }
 
+    #[inline(always)]
+    pub async fn reserve_owned(&self) -> Result<mpsc::OwnedPermit<T>, SendError> {
+        self.sender
+            .clone()
+            .reserve_owned()
+            .await
+            .map_err(|_| SendError)
+    }
+
     #[inline(always)]
     pub async fn recv(&self) -> Option<T> {
         self.receiver.lock().await.recv().await
@@ -200,6 +692,58 @@ mod tests {
         assert!(matches!(poll_once(get.result()).await.unwrap(), Some(1)));
     }
 
+    #[test]
+    fn shared_datagram_format() {
+        let format = SharedDatagramFormat::default();
+        assert_eq!(format.negotiated(), None);
+        assert_eq!(format.get(), DatagramFormat::QuarterStreamId);
+
+        let shared = format.clone();
+        shared.set(DatagramFormat::FlowId);
+        assert_eq!(format.negotiated(), Some(DatagramFormat::FlowId));
+        assert_eq!(format.get(), DatagramFormat::FlowId);
+
+        shared.set(DatagramFormat::QuarterStreamId);
+        assert_eq!(format.negotiated(), Some(DatagramFormat::QuarterStreamId));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn activity_follows_paused_clock() {
+        let activity = Activity::new();
+        let created = Instant::now();
+        assert_eq!(activity.last(), created);
+
+        tokio::time::advance(Duration::from_secs(60)).await;
+        activity.touch();
+        assert_eq!(activity.last(), created + Duration::from_secs(60));
+
+        let events = activity.milestones().subscribe().borrow().clone();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].milestone(), Milestone::FirstApplicationData);
+        assert_eq!(events[0].at(), created + Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn streams_tracker() {
+        let tracker = StreamsTracker::new();
+        assert_eq!(tracker.active(), 0);
+        assert!(poll_once(tracker.idle()).await.is_some());
+
+        let guard_a = tracker.guard(Activity::new(), None);
+        let guard_b = tracker.guard(Activity::new(), None);
+        let guard_b_clone = guard_b.clone();
+        assert_eq!(tracker.active(), 2);
+
+        drop(guard_a);
+        drop(guard_b);
+        assert_eq!(tracker.active(), 1);
+        assert!(poll_once(tracker.idle()).await.is_none());
+
+        drop(guard_b_clone);
+        assert_eq!(tracker.active(), 0);
+        assert!(poll_once(tracker.idle()).await.is_some());
+    }
+
     mod utils {
         use std::future::Future;
         use std::pin::Pin;
@@ -230,3 +774,151 @@ mod tests {
         }
     }
 }
+
+/// Polls `future` once, without registering for wake-ups.
+pub fn poll_once<F>(future: F) -> Option<F::Output>
+where
+    F: Future,
+{
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(
+        |_| RawWaker::new(std::ptr::null(), &VTABLE),
+        |_| {},
+        |_| {},
+        |_| {},
+    );
+
+    // SAFETY: the vtable functions do nothing with the (null) data pointer
+    let waker = unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) };
+    let mut context = Context::from_waker(&waker);
+
+    match Box::pin(future).as_mut().poll(&mut context) {
+        Poll::Ready(output) => Some(output),
+        Poll::Pending => None,
+    }
+}
+
+/// FIFO queue of stream opens waiting for the peer's stream credit.
+///
+/// Only the head of the queue (the one holding the turn) waits for credit.
+#[derive(Clone)]
+pub struct OpenQueue {
+    turn: Arc<Mutex<()>>,
+    queued: Arc<AtomicUsize>,
+    capacity: usize,
+}
+
+impl OpenQueue {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            turn: Arc::new(Mutex::new(())),
+            queued: Arc::new(AtomicUsize::new(0)),
+            capacity,
+        }
+    }
+
+    /// Awaits the turn to open a stream.
+    ///
+    /// It returns `None` if `capacity` opens are already waiting (including the head).
+    pub async fn enter(&self) -> Option<OpenTurn> {
+        if self.queued.fetch_add(1, Ordering::AcqRel) >= self.capacity {
+            self.queued.fetch_sub(1, Ordering::AcqRel);
+            return None;
+        }
+
+        let queued = QueuedGuard(self.queued.clone());
+
+        Some(OpenTurn {
+            _guard: self.turn.clone().lock_owned().await,
+            _queued: queued,
+        })
+    }
+
+    /// Takes the turn, only if no other open is queued.
+    pub fn try_enter(&self) -> Option<OwnedMutexGuard<()>> {
+        if self.queued.load(Ordering::Acquire) > 0 {
+            return None;
+        }
+
+        self.turn.clone().try_lock_owned().ok()
+    }
+}
+
+/// Turn to open a stream, see [`OpenQueue::enter`].
+pub struct OpenTurn {
+    _guard: OwnedMutexGuard<()>,
+    _queued: QueuedGuard,
+}
+
+struct QueuedGuard(Arc<AtomicUsize>);
+
+impl Drop for QueuedGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// A [`mpsc::Receiver`] shared by tasks receiving concurrently.
+///
+/// Unlike [`mpsc::Receiver::poll_recv`], which only wakes the last task polling it,
+/// all pending tasks are woken when a value is sent (the first one takes it).
+pub struct SharedReceiver<T> {
+    receiver: std::sync::Mutex<mpsc::Receiver<T>>,
+    wakers: Arc<WakerList>,
+}
+
+impl<T> SharedReceiver<T> {
+    pub fn new(receiver: mpsc::Receiver<T>) -> Self {
+        Self {
+            receiver: std::sync::Mutex::new(receiver),
+            wakers: Arc::new(WakerList::default()),
+        }
+    }
+
+    pub fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        // Registered before polling, so that a value sent in between wakes this task
+        self.wakers.register(cx.waker());
+
+        let waker = Waker::from(self.wakers.clone());
+        let mut context = Context::from_waker(&waker);
+
+        self.receiver
+            .lock()
+            .expect("Receiver lock is not poisoned")
+            .poll_recv(&mut context)
+    }
+
+    pub fn try_recv(&self) -> Result<T, mpsc::error::TryRecvError> {
+        self.receiver
+            .lock()
+            .expect("Receiver lock is not poisoned")
+            .try_recv()
+    }
+}
+
+/// Wakers of the tasks pending on a [`SharedReceiver`].
+#[derive(Default)]
+struct WakerList(std::sync::Mutex<Vec<Waker>>);
+
+impl WakerList {
+    fn register(&self, waker: &Waker) {
+        let mut wakers = self.0.lock().expect("Wakers lock is not poisoned");
+
+        if !wakers.iter().any(|registered| registered.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+    }
+}
+
+impl Wake for WakerList {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let wakers = std::mem::take(&mut *self.0.lock().expect("Wakers lock is not poisoned"));
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
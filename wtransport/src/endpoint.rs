@@ -1,44 +1,142 @@
+use crate::alt_svc::AltSvc;
+use crate::budget::MemoryAccount;
+use crate::budget::MemoryBudget;
+#[cfg(feature = "compression")]
+use crate::compression::Codecs;
+#[cfg(feature = "compression")]
+use crate::compression::Compression;
+#[cfg(feature = "compression")]
+use crate::compression::ACCEPT_ENCODING_HEADER;
+#[cfg(feature = "compression")]
+use crate::compression::ENCODING_HEADER;
 use crate::config::ClientConfig;
 use crate::config::Ipv6DualStackConfig;
+use crate::config::QuicVersion;
+use crate::config::RequestValidation;
 use crate::config::ServerConfig;
+use crate::config::Timeouts;
+use crate::connection::ConnectTimings;
 use crate::connection::Connection;
+use crate::connection::ConnectionInfo;
+use crate::connection::ConnectionsRegistry;
+use crate::connection::SessionRequestInfo;
+use crate::connection::Stopwatch;
+use crate::connection::DRAFT_HEADER;
+use crate::datagram::DATAGRAM_TIMESTAMPS_HEADER;
+use crate::dns;
+use crate::dns::HttpsResolver;
+use crate::driver::close_on_violation;
 use crate::driver::streams::session::StreamSession;
 use crate::driver::streams::ProtoReadError;
 use crate::driver::streams::ProtoWriteError;
+use crate::driver::utils::poll_once;
 use crate::driver::utils::varint_w2q;
+#[cfg(any(feature = "axum", feature = "tower"))]
+use crate::driver::utils::Spawner;
 use crate::driver::Driver;
+use crate::driver::DriverConfig;
+use crate::driver::Violation;
+use crate::error::ConnectAnyError;
 use crate::error::ConnectingError;
 use crate::error::ConnectionError;
-use quinn::TokioRuntime;
+use crate::error::InvalidUrl;
+use crate::error::ProtocolPhase;
+use crate::quota::Quotas;
+use crate::resumption::ResumptionToken;
+use crate::resumption::ResumptionTokens;
+use crate::resumption::RESUMPTION_TOKEN_HEADER;
+use crate::socket::DemuxSocket;
+use crate::socket::ExternalPacketHandler;
+use crate::socket::PacketTap;
+use crate::socket::SocketStats;
+use crate::tls::TlsInfo;
+use crate::url_validation;
+use crate::worker::spawning_on;
+use crate::worker::QuicRuntime;
+use crate::worker::Relocation;
+use crate::worker::Worker;
+use quinn::Runtime;
 use socket2::Domain as SocketDomain;
 use socket2::Protocol as SocketProtocol;
 use socket2::Socket;
 use socket2::Type as SocketType;
 use std::collections::HashMap;
 use std::future::Future;
-use std::marker::PhantomData;
 use std::net::SocketAddr;
 use std::net::SocketAddrV4;
 use std::net::SocketAddrV6;
 use std::pin::Pin;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::task::Context;
 use std::task::Poll;
+use std::time::Duration;
 use tokio::net::lookup_host;
+use tokio::sync::broadcast;
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
 use tracing::debug;
 use url::Host;
 use url::Url;
 use wtransport_proto::error::ErrorCode;
 use wtransport_proto::frame::FrameKind;
 use wtransport_proto::headers::Headers;
+use wtransport_proto::ids::StatusCode;
+use wtransport_proto::session::is_valid_protocol;
 use wtransport_proto::session::SessionRequest as SessionRequestProto;
 use wtransport_proto::session::SessionResponse as SessionResponseProto;
 
 /// Type of endpoint accepting multiple WebTransport connections.
-pub struct Server;
+pub struct Server {
+    context: ServerContext,
+    runtimes: Vec<Arc<QuicRuntime>>,
+    next_accept: AtomicUsize,
+    admission: Mutex<Admission>,
+    pending_accepts: Mutex<Vec<Option<PendingAccept>>>,
+}
+
+/// An accept in progress on a QUIC endpoint, kept between calls to [`Endpoint::poll_accept`].
+type PendingAccept = Pin<Box<dyn Future<Output = Option<quinn::Connecting>> + Send>>;
+
+/// Admission of new QUIC connections by a server.
+struct Admission {
+    quic_config: quinn::ServerConfig,
+    paused: bool,
+}
+
+/// Server settings shared by all incoming sessions.
+#[derive(Clone)]
+struct ServerContext {
+    driver_config: DriverConfig,
+    response_headers: Arc<Headers>,
+    request_validation: Arc<RequestValidation>,
+    timeouts: Timeouts,
+    registry: Option<ConnectionsRegistry>,
+    quic_version: Option<QuicVersion>,
+    resumption_tokens: Option<ResumptionTokens>,
+    extended_connect_handlers: Arc<HashMap<String, ExtendedConnectHandler>>,
+    setup_slots: Option<Arc<Semaphore>>,
+    quotas: Option<Quotas>,
+    memory_budget: Option<MemoryBudget>,
+    events: broadcast::Sender<EndpointEvent>,
+}
+
+/// Handler of the session requests of an extended CONNECT protocol.
+pub(crate) type ExtendedConnectHandler =
+    Arc<dyn Fn(SessionRequest) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
 
 /// Type of endpoint opening a WebTransport connection.
-pub struct Client;
+pub struct Client {
+    driver_config: DriverConfig,
+    max_connect_attempts: u32,
+    timeouts: Timeouts,
+    connect_counters: ConnectCounters,
+    quic_version: QuicVersion,
+    https_resolver: Option<Arc<dyn HttpsResolver>>,
+}
 
 /// Entrypoint for creating client or server connections.
 ///
@@ -46,7 +144,9 @@ pub struct Client;
 /// * For creating a client: [`Endpoint::client`].
 pub struct Endpoint<Side> {
     endpoint: quinn::Endpoint,
-    _marker: PhantomData<Side>,
+    additional_endpoints: Vec<quinn::Endpoint>,
+    sockets: Vec<Socket>,
+    side: Side,
 }
 
 impl<Side> Endpoint<Side> {
@@ -72,9 +172,72 @@ impl<Side> Endpoint<Side> {
         Ok(socket)
     }
 
+    fn new_quic_endpoint(
+        endpoint_config: quinn::EndpointConfig,
+        server_config: Option<quinn::ServerConfig>,
+        socket: Socket,
+        external_packet_handler: Option<Arc<dyn ExternalPacketHandler>>,
+        packet_tap: Option<Arc<dyn PacketTap>>,
+        runtime: Arc<QuicRuntime>,
+    ) -> std::io::Result<(quinn::Endpoint, Socket)> {
+        // A handle on the same socket, kept for its statistics.
+        let stats_socket = socket.try_clone()?;
+        let socket: std::net::UdpSocket = socket.into();
+
+        if external_packet_handler.is_none() && packet_tap.is_none() {
+            let endpoint = quinn::Endpoint::new(endpoint_config, server_config, socket, runtime)?;
+            return Ok((endpoint, stats_socket));
+        }
+
+        let handler = match external_packet_handler {
+            Some(handler) => Some((handler, socket.try_clone()?)),
+            None => None,
+        };
+
+        let socket = DemuxSocket::new(runtime.wrap_udp_socket(socket)?, handler, packet_tap);
+
+        let endpoint = quinn::Endpoint::new_with_abstract_socket(
+            endpoint_config,
+            server_config,
+            socket,
+            runtime,
+        )?;
+
+        Ok((endpoint, stats_socket))
+    }
+
+    /// Returns the local socket address of the endpoint.
+    ///
+    /// For a server bound to several addresses, this is the main one.
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.endpoint.local_addr()
+    }
+
+    /// Returns the local socket addresses of the endpoint, the main one first.
+    ///
+    /// See [`ServerConfigBuilder::additional_bind_address`](crate::config::ServerConfigBuilder::additional_bind_address).
+    pub fn local_addrs(&self) -> std::io::Result<Vec<SocketAddr>> {
+        self.quic_endpoints()
+            .map(quinn::Endpoint::local_addr)
+            .collect()
+    }
+
     /// Waits for all connections on the endpoint to be cleanly shut down.
     pub async fn wait_idle(&self) {
-        self.endpoint.wait_idle().await;
+        for endpoint in self.quic_endpoints() {
+            endpoint.wait_idle().await;
+        }
+    }
+
+    /// Returns the statistics of the UDP sockets of the endpoint, the main one first.
+    ///
+    /// See [`SocketStats`].
+    pub fn socket_stats(&self) -> std::io::Result<Vec<SocketStats>> {
+        self.sockets.iter().map(SocketStats::read).collect()
+    }
+
+    fn quic_endpoints(&self) -> impl Iterator<Item = &quinn::Endpoint> {
+        std::iter::once(&self.endpoint).chain(&self.additional_endpoints)
     }
 }
 
@@ -84,32 +247,281 @@ impl Endpoint<Server> {
         let quic_config = server_config.quic_config;
         let socket =
             Self::bind_socket(server_config.bind_address, server_config.dual_stack_config)?;
-        let runtime = Arc::new(TokioRuntime);
 
-        let endpoint = quinn::Endpoint::new(
-            quinn::EndpointConfig::default(),
-            Some(quic_config),
-            socket.into(),
-            runtime,
+        let additional_endpoints = server_config
+            .additional_bind_addresses
+            .iter()
+            .map(|address| {
+                let dual_stack_config = match address {
+                    SocketAddr::V4(_) => Ipv6DualStackConfig::OsDefault,
+                    SocketAddr::V6(_) => Ipv6DualStackConfig::Deny,
+                };
+                let socket = Self::bind_socket(*address, dual_stack_config)?;
+                let runtime = Arc::new(QuicRuntime::default());
+                let (endpoint, socket) = Self::new_quic_endpoint(
+                    server_config.endpoint_config.clone(),
+                    Some(quic_config.clone()),
+                    socket,
+                    server_config.external_packet_handler.clone(),
+                    server_config.packet_tap.clone(),
+                    runtime.clone(),
+                )?;
+                Ok(((endpoint, socket), runtime))
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+
+        let runtime = Arc::new(QuicRuntime::default());
+        let (endpoint, socket) = Self::new_quic_endpoint(
+            server_config.endpoint_config,
+            Some(quic_config.clone()),
+            socket,
+            server_config.external_packet_handler,
+            server_config.packet_tap,
+            runtime.clone(),
         )?;
 
+        let (additional_endpoints, additional_runtimes): (Vec<_>, Vec<_>) =
+            additional_endpoints.into_iter().unzip();
+        let (additional_endpoints, additional_sockets): (Vec<_>, Vec<_>) =
+            additional_endpoints.into_iter().unzip();
+
+        let runtimes = std::iter::once(runtime)
+            .chain(additional_runtimes)
+            .collect::<Vec<_>>();
+        for runtime in &runtimes {
+            runtime.relocate_incoming();
+        }
+
+        let registry = server_config
+            .connections_registry
+            .then(ConnectionsRegistry::default);
+
         Ok(Self {
             endpoint,
-            _marker: PhantomData,
+            additional_endpoints,
+            sockets: std::iter::once(socket).chain(additional_sockets).collect(),
+            side: Server {
+                context: ServerContext {
+                    driver_config: server_config.driver_config,
+                    response_headers: Arc::new(server_config.response_headers),
+                    request_validation: Arc::new(server_config.request_validation),
+                    timeouts: server_config.timeouts,
+                    registry,
+                    quic_version: server_config.quic_version,
+                    resumption_tokens: server_config.resumption_tokens,
+                    extended_connect_handlers: Arc::new(server_config.extended_connect_handlers),
+                    setup_slots: server_config
+                        .max_concurrent_setups
+                        .map(|value| Arc::new(Semaphore::new(value))),
+                    quotas: server_config.quotas,
+                    memory_budget: server_config.memory_budget,
+                    events: broadcast::channel(EndpointEvents::CAPACITY).0,
+                },
+                runtimes,
+                next_accept: AtomicUsize::new(0),
+                admission: Mutex::new(Admission {
+                    quic_config,
+                    paused: false,
+                }),
+                pending_accepts: Mutex::new(Vec::new()),
+            },
         })
     }
 
     /// Get the next incoming connection attempt from a client.
+    ///
+    /// Attempts are accepted from all the bind addresses of the endpoint.
     pub async fn accept(&self) -> IncomingSession {
-        let quic_connecting = self
-            .endpoint
-            .accept()
-            .await
-            .expect("Endpoint cannot be closed");
+        let mut accepts = self
+            .quic_endpoints()
+            .map(|endpoint| Box::pin(endpoint.accept()))
+            .collect::<Vec<_>>();
+
+        // Rotates the first polled endpoint, so that a busy one cannot starve the others.
+        let first = self.side.next_accept.fetch_add(1, Ordering::Relaxed);
+
+        let (quic_connecting, relocation) = std::future::poll_fn(|cx| {
+            for offset in 0..accepts.len() {
+                let index = (first + offset) % accepts.len();
+                let runtime = &self.side.runtimes[index];
+                let accepting = runtime.accepting();
+
+                if let Poll::Ready(quic_connecting) = accepts[index].as_mut().poll(cx) {
+                    return Poll::Ready(
+                        quic_connecting
+                            .map(|connecting| (connecting, runtime.relocation(&accepting))),
+                    );
+                }
+            }
+
+            Poll::Pending
+        })
+        .await
+        .expect("Endpoint cannot be closed");
 
         debug!("New incoming QUIC connection");
 
-        IncomingSession::new(quic_connecting)
+        IncomingSession::new(quic_connecting, relocation, self.side.context.clone())
+    }
+
+    /// Polls for the next incoming connection attempt from a client.
+    ///
+    /// This allows integrating the endpoint into a manual event loop or a custom scheduler,
+    /// without a dedicated task. Like [`accept`](Self::accept), attempts are accepted from
+    /// all the bind addresses of the endpoint. Only the waker of the last call is woken.
+    pub fn poll_accept(&self, cx: &mut Context<'_>) -> Poll<IncomingSession> {
+        let mut pending_accepts = self
+            .side
+            .pending_accepts
+            .lock()
+            .expect("Mutex is not poisoned");
+
+        let endpoints = self.quic_endpoints().collect::<Vec<_>>();
+        pending_accepts.resize_with(endpoints.len(), || None);
+
+        let first = self.side.next_accept.fetch_add(1, Ordering::Relaxed);
+
+        for offset in 0..endpoints.len() {
+            let index = (first + offset) % endpoints.len();
+
+            let pending_accept = pending_accepts[index].get_or_insert_with(|| {
+                let endpoint = endpoints[index].clone();
+                Box::pin(async move { endpoint.accept().await })
+            });
+
+            let runtime = &self.side.runtimes[index];
+            let accepting = runtime.accepting();
+
+            if let Poll::Ready(quic_connecting) = pending_accept.as_mut().poll(cx) {
+                pending_accepts[index] = None;
+                let relocation = runtime.relocation(&accepting);
+
+                debug!("New incoming QUIC connection");
+
+                let quic_connecting = quic_connecting.expect("Endpoint cannot be closed");
+                return Poll::Ready(IncomingSession::new(
+                    quic_connecting,
+                    relocation,
+                    self.side.context.clone(),
+                ));
+            }
+        }
+
+        Poll::Pending
+    }
+
+    /// Returns the next incoming connection attempt from a client, if one is ready.
+    ///
+    /// It never waits: `None` means no attempt is queued at the moment.
+    pub fn try_accept(&self) -> Option<IncomingSession> {
+        let first = self.side.next_accept.fetch_add(1, Ordering::Relaxed);
+        let endpoints = self.quic_endpoints().collect::<Vec<_>>();
+
+        (0..endpoints.len()).find_map(|offset| {
+            let index = (first + offset) % endpoints.len();
+            let runtime = &self.side.runtimes[index];
+            let accepting = runtime.accepting();
+
+            // A new accept takes the first queued attempt, if any, on its first poll.
+            let quic_connecting =
+                poll_once(endpoints[index].accept())?.expect("Endpoint cannot be closed");
+            let relocation = runtime.relocation(&accepting);
+
+            debug!("New incoming QUIC connection");
+
+            Some(IncomingSession::new(
+                quic_connecting,
+                relocation,
+                self.side.context.clone(),
+            ))
+        })
+    }
+
+    /// Pauses accepting new connections.
+    ///
+    /// New QUIC connections are refused during the handshake (with `CONNECTION_REFUSED`),
+    /// so that an overloaded server sheds load before committing resources to them.
+    /// Established connections, and the attempts already queued for [`accept`](Self::accept),
+    /// are not affected.
+    pub fn pause_accepting(&self) {
+        let mut admission = self.side.admission.lock().expect("Mutex is not poisoned");
+        admission.paused = true;
+        self.apply_admission(&admission);
+    }
+
+    /// Resumes accepting new connections, after [`pause_accepting`](Self::pause_accepting).
+    pub fn resume_accepting(&self) {
+        let mut admission = self.side.admission.lock().expect("Mutex is not poisoned");
+        admission.paused = false;
+        self.apply_admission(&admission);
+    }
+
+    /// Returns whether new connections are accepted (i.e., not paused).
+    pub fn is_accepting(&self) -> bool {
+        !self
+            .side
+            .admission
+            .lock()
+            .expect("Mutex is not poisoned")
+            .paused
+    }
+
+    /// Sets the maximum number of concurrent connections, beyond which new ones are refused.
+    ///
+    /// See [`ServerConfigBuilder::max_concurrent_connections`](crate::config::ServerConfigBuilder::max_concurrent_connections).
+    pub fn set_max_concurrent_connections(&self, value: u32) {
+        let mut admission = self.side.admission.lock().expect("Mutex is not poisoned");
+        admission.quic_config.concurrent_connections(value);
+        self.apply_admission(&admission);
+    }
+
+    fn apply_admission(&self, admission: &Admission) {
+        let mut quic_config = admission.quic_config.clone();
+
+        if admission.paused {
+            quic_config.concurrent_connections(0);
+        }
+
+        for endpoint in self.quic_endpoints() {
+            endpoint.set_server_config(Some(quic_config.clone()));
+        }
+    }
+
+    /// Returns the alternative service advertising this endpoint on its local port.
+    ///
+    /// Unlike [`ServerConfig::alt_svc`], it reports the actual port when the endpoint
+    /// was bound to port `0`.
+    pub fn alt_svc(&self) -> std::io::Result<AltSvc> {
+        Ok(AltSvc::new(self.local_addr()?.port()))
+    }
+
+    #[cfg(any(feature = "axum", feature = "tower"))]
+    pub(crate) fn spawner(&self) -> &Spawner {
+        &self.side.context.driver_config.spawner
+    }
+
+    /// Subscribes to the operational events of this endpoint (see [`EndpointEvent`]).
+    ///
+    /// Only the events happening after the subscription are reported.
+    pub fn events(&self) -> EndpointEvents {
+        EndpointEvents {
+            receiver: self.side.context.events.subscribe(),
+            missed: 0,
+        }
+    }
+
+    /// Returns the live connections of this endpoint.
+    ///
+    /// The registry must be enabled with
+    /// [`ServerConfigBuilder::connections_registry`](crate::config::ServerConfigBuilder::connections_registry),
+    /// otherwise the returned list is always empty.
+    pub fn connections(&self) -> Vec<ConnectionInfo> {
+        self.side
+            .context
+            .registry
+            .as_ref()
+            .map(ConnectionsRegistry::connections)
+            .unwrap_or_default()
     }
 }
 
@@ -119,88 +531,444 @@ impl Endpoint<Client> {
         let quic_config = client_config.quic_config;
         let socket =
             Self::bind_socket(client_config.bind_address, client_config.dual_stack_config)?;
-        let runtime = Arc::new(TokioRuntime);
 
-        let mut endpoint = quinn::Endpoint::new(
-            quinn::EndpointConfig::default(),
+        let (mut endpoint, socket) = Self::new_quic_endpoint(
+            client_config.endpoint_config,
             None,
-            socket.into(),
-            runtime,
+            socket,
+            client_config.external_packet_handler,
+            client_config.packet_tap,
+            Arc::new(QuicRuntime::default()),
         )?;
 
         endpoint.set_default_client_config(quic_config);
 
         Ok(Self {
             endpoint,
-            _marker: PhantomData,
+            additional_endpoints: Vec::new(),
+            sockets: vec![socket],
+            side: Client {
+                driver_config: client_config.driver_config,
+                max_connect_attempts: client_config.max_connect_attempts,
+                timeouts: client_config.timeouts,
+                connect_counters: ConnectCounters::default(),
+                quic_version: client_config.quic_version,
+                https_resolver: client_config.https_resolver,
+            },
         })
     }
 
     /// Connects to a remote endpoint.
     ///
     /// `server_name` must be covered by the certificate presented by the server.
+    ///
+    /// `url` must be an `https` URL, without user information nor fragment.
+    /// International domain names are converted to their ASCII form, and percent-encoding
+    /// is normalized (see [`InvalidUrl`] for the rejected URLs).
     pub async fn connect<S>(&self, url: S) -> Result<Connection, ConnectingError>
     where
         S: AsRef<str>,
     {
-        let url = Url::parse(url.as_ref())
-            .map_err(|parse_error| ConnectingError::InvalidUrl(parse_error.to_string()))?;
+        self.connect_impl(url.as_ref(), None, None).await
+    }
 
-        if url.scheme() != "https" {
-            return Err(ConnectingError::InvalidUrl(
-                "WebTransport URL scheme must be 'https'".to_string(),
-            ));
+    /// Connects to a remote endpoint, presenting a resumption `token`.
+    ///
+    /// The token has been issued by the server on a previous session
+    /// (see [`Connection::resumption_token`]): if the server is still able to redeem it,
+    /// it restores the application state of that session.
+    pub async fn connect_resuming<S>(
+        &self,
+        url: S,
+        token: &ResumptionToken,
+    ) -> Result<Connection, ConnectingError>
+    where
+        S: AsRef<str>,
+    {
+        self.connect_impl(url.as_ref(), None, Some(token)).await
+    }
+
+    /// Connects to a remote endpoint through an alternative service.
+    ///
+    /// The session is still requested for `url` (i.e., with its authority and TLS server
+    /// name), but the QUIC connection is established with the host and port of `alt_svc`
+    /// (e.g., learned out of band from an `Alt-Svc` header field, see [`AltSvc::parse`]).
+    pub async fn connect_alt_svc<S>(
+        &self,
+        url: S,
+        alt_svc: &AltSvc,
+    ) -> Result<Connection, ConnectingError>
+    where
+        S: AsRef<str>,
+    {
+        self.connect_impl(url.as_ref(), Some(alt_svc), None).await
+    }
+
+    /// Connects to the first reachable candidate among `urls` (e.g., regional endpoints).
+    ///
+    /// Attempts are started in order: the next one after `stagger`, or as soon as the
+    /// last started one fails. On the first success, the other pending attempts are
+    /// dropped and the index of the winning URL is returned with the connection.
+    /// With a zero `stagger`, all attempts race at once.
+    pub async fn connect_any<I, S>(
+        &self,
+        urls: I,
+        stagger: Duration,
+    ) -> Result<(usize, Connection), ConnectAnyError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        type Attempt<'a> =
+            Pin<Box<dyn Future<Output = Result<Connection, ConnectingError>> + Send + 'a>>;
+
+        let urls = urls
+            .into_iter()
+            .map(|url| url.as_ref().to_string())
+            .collect::<Vec<_>>();
+
+        let mut attempts = Vec::<(usize, Attempt<'_>)>::new();
+        let mut failures = Vec::new();
+        let mut next = 0;
+        let mut start_next = true;
+        let mut stagger_timer = Box::pin(tokio::time::sleep(stagger));
+
+        std::future::poll_fn(|cx| loop {
+            while next < urls.len()
+                && (start_next || stagger.is_zero() || stagger_timer.as_mut().poll(cx).is_ready())
+            {
+                attempts.push((next, Box::pin(self.connect_impl(&urls[next], None, None))));
+                next += 1;
+                start_next = false;
+
+                stagger_timer
+                    .as_mut()
+                    .reset(tokio::time::Instant::now() + stagger);
+            }
+
+            let mut index = 0;
+            while index < attempts.len() {
+                match attempts[index].1.as_mut().poll(cx) {
+                    Poll::Ready(Ok(connection)) => {
+                        return Poll::Ready(Ok((attempts[index].0, connection)));
+                    }
+                    Poll::Ready(Err(error)) => {
+                        let (url_index, _) = attempts.swap_remove(index);
+                        debug!(
+                            "Connection candidate '{}' failed: {}",
+                            urls[url_index], error
+                        );
+                        failures.push((urls[url_index].clone(), error));
+
+                        if url_index + 1 == next {
+                            start_next = true;
+                        }
+                    }
+                    Poll::Pending => index += 1,
+                }
+            }
+
+            if start_next && next < urls.len() {
+                continue;
+            }
+
+            if !attempts.is_empty() {
+                return Poll::Pending;
+            }
+
+            return Poll::Ready(Err(ConnectAnyError::new(std::mem::take(&mut failures))));
+        })
+        .await
+    }
+
+    /// Connects to a remote endpoint, reporting the progress of the attempt.
+    ///
+    /// The attempt is abandoned if the connection is not established by `deadline`
+    /// (with [`ConnectingError::DeadlineExceeded`]).
+    /// Either way, the returned [`ConnectReport`] describes how far the attempt went:
+    /// the stage reached (the failed one on error), the resolved addresses, the outcome
+    /// of the QUIC handshake, and the timings of the completed phases.
+    pub async fn connect_with_report<S>(
+        &self,
+        url: S,
+        deadline: Instant,
+    ) -> (Result<Connection, ConnectingError>, ConnectReport)
+    where
+        S: AsRef<str>,
+    {
+        let mut report = ConnectReport::default();
+
+        let result = tokio::time::timeout_at(
+            deadline,
+            self.connect_reporting(
+                url.as_ref(),
+                None,
+                None,
+                &ConnectOptions::default(),
+                &mut report,
+            ),
+        )
+        .await;
+
+        match result {
+            Ok(result) => (result, report),
+            Err(_elapsed) => (
+                Err(ConnectingError::DeadlineExceeded(report.timings)),
+                report,
+            ),
         }
+    }
+
+    /// Connects to a remote endpoint, with `options`.
+    ///
+    /// See [`ConnectOptions`].
+    pub async fn connect_with_options<S>(
+        &self,
+        url: S,
+        options: &ConnectOptions,
+    ) -> Result<Connection, ConnectingError>
+    where
+        S: AsRef<str>,
+    {
+        self.connect_reporting(
+            url.as_ref(),
+            None,
+            None,
+            options,
+            &mut ConnectReport::default(),
+        )
+        .await
+    }
+
+    async fn connect_impl(
+        &self,
+        url: &str,
+        alt_svc: Option<&AltSvc>,
+        resumption_token: Option<&ResumptionToken>,
+    ) -> Result<Connection, ConnectingError> {
+        self.connect_reporting(
+            url,
+            alt_svc,
+            resumption_token,
+            &ConnectOptions::default(),
+            &mut ConnectReport::default(),
+        )
+        .await
+    }
+
+    async fn connect_reporting(
+        &self,
+        url: &str,
+        alt_svc: Option<&AltSvc>,
+        resumption_token: Option<&ResumptionToken>,
+        options: &ConnectOptions,
+        report: &mut ConnectReport,
+    ) -> Result<Connection, ConnectingError> {
+        let url = url_validation::parse(url).map_err(ConnectingError::InvalidUrl)?;
 
         let host = url.host().expect("https scheme must have an host");
         let port = url.port().unwrap_or(443);
 
-        let (socket_address, server_name) = match host {
-            Host::Domain(domain) => {
-                let socket_address = lookup_host(format!("{domain}:{port}"))
-                    .await
-                    .map_err(ConnectingError::DnsLookup)?
-                    .next()
-                    .ok_or(ConnectingError::DnsNotFound)?;
-                (socket_address, domain.to_string())
+        let server_name = match (&options.server_name, &host) {
+            (Some(server_name), _) => {
+                if rustls::ServerName::try_from(server_name.as_str()).is_err() {
+                    return Err(ConnectingError::InvalidServerName(server_name.clone()));
+                }
+                server_name.clone()
+            }
+            (None, Host::Domain(domain)) => domain.to_string(),
+            (None, Host::Ipv4(address)) => address.to_string(),
+            (None, Host::Ipv6(address)) => address.to_string(),
+        };
+
+        if let Some(subprotocol) = options
+            .subprotocols
+            .iter()
+            .find(|subprotocol| !is_valid_protocol(subprotocol))
+        {
+            return Err(ConnectingError::InvalidSubprotocol(subprotocol.clone()));
+        }
+
+        let mut stopwatch = Stopwatch::start();
+        report.stage = ConnectStage::DnsResolution;
+
+        let mut ip_hints = Vec::new();
+
+        let (host, port) = match (alt_svc, &host, &self.side.https_resolver) {
+            (Some(alt_svc), _, _) => {
+                let host = match alt_svc.host() {
+                    Some(alt_host) => Host::parse(alt_host).map_err(|parse_error| {
+                        ConnectingError::InvalidUrl(InvalidUrl::with_parse_error(parse_error))
+                    })?,
+                    None => host.to_owned(),
+                };
+                (host, alt_svc.port())
+            }
+            (None, Host::Domain(domain), Some(resolver)) => {
+                match dns::discover(resolver.as_ref(), domain, port).await {
+                    Ok(Some(discovered)) => {
+                        debug!(
+                            "Discovered endpoint {}:{} for '{}'",
+                            discovered.host, discovered.port, domain
+                        );
+                        ip_hints = discovered.ip_hints;
+                        let host = Host::parse(&discovered.host).map_err(|parse_error| {
+                            ConnectingError::InvalidUrl(InvalidUrl::with_parse_error(parse_error))
+                        })?;
+                        (host, discovered.port)
+                    }
+                    Ok(None) => (host.to_owned(), port),
+                    Err(error) => {
+                        debug!("HTTPS records lookup failed for '{}': {}", domain, error);
+                        (host.to_owned(), port)
+                    }
+                }
+            }
+            (None, _, _) => (host.to_owned(), port),
+        };
+
+        report.resolved_addresses = match host {
+            Host::Domain(_) if !ip_hints.is_empty() => ip_hints
+                .iter()
+                .map(|ip_hint| SocketAddr::new(*ip_hint, port))
+                .collect(),
+            Host::Domain(domain) => lookup_host(format!("{domain}:{port}"))
+                .await
+                .map_err(ConnectingError::DnsLookup)?
+                .collect(),
+            Host::Ipv4(address) => vec![SocketAddr::V4(SocketAddrV4::new(address, port))],
+            Host::Ipv6(address) => vec![SocketAddr::V6(SocketAddrV6::new(address, port, 0, 0))],
+        };
+
+        let socket_address = *report
+            .resolved_addresses
+            .first()
+            .ok_or(ConnectingError::DnsNotFound)?;
+
+        report.timings.dns = Some(stopwatch.lap());
+        report.remote_address = Some(socket_address);
+
+        let timeouts = options.timeouts.unwrap_or(self.side.timeouts);
+
+        let result = self
+            .establish(
+                &url,
+                socket_address,
+                &server_name,
+                resumption_token,
+                &timeouts,
+                options,
+                stopwatch,
+                report,
+            )
+            .await;
+
+        match result {
+            Ok(connection) => {
+                report.stage = ConnectStage::Established;
+                Ok(connection
+                    .with_connect_timings(report.timings)
+                    .with_quic_version(Some(self.side.quic_version))
+                    .with_timeouts(timeouts))
             }
-            Host::Ipv4(address) => {
-                let socket_address = SocketAddr::V4(SocketAddrV4::new(address, port));
-                (socket_address, address.to_string())
+            Err(error) => Err(error.with_timings(report.timings)),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn establish(
+        &self,
+        url: &Url,
+        socket_address: SocketAddr,
+        server_name: &str,
+        resumption_token: Option<&ResumptionToken>,
+        timeouts: &Timeouts,
+        options: &ConnectOptions,
+        mut stopwatch: Stopwatch,
+        report: &mut ConnectReport,
+    ) -> Result<Connection, ConnectingError> {
+        report.stage = ConnectStage::QuicHandshake;
+
+        let quic_connection = match with_timeout(
+            timeouts.handshake,
+            self.connect_quic(socket_address, server_name, options.worker.as_ref()),
+        )
+        .await
+        {
+            Some(Ok(quic_connection)) => quic_connection,
+            Some(Err(connection_error)) => {
+                let connection_error = ConnectionError::from(connection_error);
+                report.quic_handshake = Some(Err(connection_error.clone()));
+                return Err(ConnectingError::connection_error(connection_error));
             }
-            Host::Ipv6(address) => {
-                let socket_address = SocketAddr::V6(SocketAddrV6::new(address, port, 0, 0));
-                (socket_address, address.to_string())
+            None => {
+                report.quic_handshake = Some(Err(ConnectionError::TimedOut));
+                return Err(ConnectingError::connection_error(ConnectionError::TimedOut));
             }
         };
 
-        let quic_connection = self
-            .endpoint
-            .connect(socket_address, &server_name)
-            .expect("QUIC connection parameters must be validated")
+        report.quic_handshake = Some(Ok(()));
+        report.timings.quic_handshake = Some(stopwatch.lap());
+        report.stage = ConnectStage::SettingsExchange;
+
+        let mut driver_config = self.side.driver_config.clone();
+        if let Some(worker) = &options.worker {
+            driver_config.spawner = worker.spawner();
+        }
+
+        let driver = Driver::init(
+            quic_connection.clone(),
+            driver_config,
+            MemoryAccount::default(),
+        );
+
+        let _settings = with_timeout(timeouts.settings_exchange, driver.accept_settings())
             .await
-            .map_err(|connection_error| {
-                ConnectingError::ConnectionError(connection_error.into())
+            .ok_or_else(|| {
+                close_on_timeout(&quic_connection, ErrorCode::MissingSettings);
+                ConnectingError::connection_error(ConnectionError::TimedOut)
+            })?
+            .map_err(|driver_error| {
+                ConnectingError::connection_error(ConnectionError::with_driver_error(
+                    driver_error,
+                    &quic_connection,
+                ))
             })?;
 
-        let driver = Driver::init(quic_connection.clone());
-
-        let _settings = driver.accept_settings().await.map_err(|driver_error| {
-            ConnectingError::ConnectionError(ConnectionError::with_driver_error(
-                driver_error,
-                &quic_connection,
-            ))
-        })?;
+        report.timings.settings_exchange = Some(stopwatch.lap());
+        report.stage = ConnectStage::SessionExchange;
 
         // TODO(biagio): validate settings
 
-        let session_request_proto =
+        let mut session_request_proto =
             SessionRequestProto::new(url.as_ref()).expect("Url has been already validate");
 
+        if let Some(token) = resumption_token {
+            session_request_proto.add(RESUMPTION_TOKEN_HEADER, token);
+        }
+
+        if self.side.driver_config.datagram_timestamps {
+            session_request_proto.add(DATAGRAM_TIMESTAMPS_HEADER, "?1");
+        }
+
+        #[cfg(feature = "compression")]
+        if !options.codecs.is_empty() {
+            session_request_proto.add(ACCEPT_ENCODING_HEADER, options.codecs.header_value());
+        }
+
+        let valid_subprotocols =
+            session_request_proto.set_available_protocols(&options.subprotocols);
+        debug_assert!(
+            valid_subprotocols,
+            "Subprotocols have been already validated"
+        );
+
+        let request_info = SessionRequestInfo::new(session_request_proto.headers().clone());
+
         let mut stream_session = match driver.open_session(session_request_proto).await {
             Ok(stream_session) => stream_session,
             Err(driver_error) => {
-                return Err(ConnectingError::ConnectionError(
+                return Err(ConnectingError::connection_error(
                     ConnectionError::with_driver_error(driver_error, &quic_connection),
                 ))
             }
@@ -209,178 +977,1297 @@ impl Endpoint<Client> {
         let stream_id = stream_session.id();
         let session_id = stream_session.session_id();
 
-        match stream_session
-            .write_frame(stream_session.request().headers().generate_frame(stream_id))
-            .await
-        {
+        let write_request = async {
+            if let Some(frame) = driver.grease().frame() {
+                stream_session.write_frame(frame).await?;
+            }
+
+            stream_session
+                .write_frame(stream_session.request().headers().generate_frame(stream_id))
+                .await
+        };
+
+        match write_request.await {
             Ok(()) => {}
             Err(ProtoWriteError::Stopped) => {
-                return Err(ConnectingError::SessionRejected);
+                return Err(ConnectingError::session_rejected());
             }
             Err(ProtoWriteError::NotConnected) => {
                 return Err(ConnectingError::with_no_connection(&quic_connection));
             }
         }
 
-        let frame = match stream_session.read_frame().await {
-            Ok(frame) => frame,
-            Err(ProtoReadError::H3(error_code)) => {
-                quic_connection.close(varint_w2q(error_code.to_code()), b"");
-                return Err(ConnectingError::ConnectionError(
-                    ConnectionError::local_h3_error(error_code),
+        // Interim (1xx) responses report the progress of the server (e.g., a slow
+        // authorization), restarting the wait for the final response, at most
+        // `MAX_INTERIM_RESPONSES` times.
+        let header_limits = self.side.driver_config.header_limits;
+        let mut interim_responses = 0;
+
+        let session_response = loop {
+            let read_response = async {
+                loop {
+                    // A field section within the size limit is encoded in fewer bytes
+                    // (the field overhead exceeds the QPACK one, and literals are only
+                    // Huffman-encoded when shorter): larger frames are not even buffered.
+                    // The decoded size is still checked, a few bytes can refer to large
+                    // static table entries.
+                    let frame = match stream_session
+                        .read_frame_with_limit(header_limits.max_section_size())
+                        .await
+                    {
+                        Ok(frame) => frame,
+                        // Larger than the field section size limit.
+                        Err(ProtoReadError::H3(ErrorCode::ExcessiveLoad)) => {
+                            return Err(reject_response_headers(&quic_connection));
+                        }
+                        Err(ProtoReadError::H3(error_code)) => {
+                            let violation =
+                                Violation::new(error_code, "Invalid frame on session stream")
+                                    .on_stream(stream_id);
+                            return Err(reject_response(&quic_connection, violation));
+                        }
+                        Err(ProtoReadError::IO(_io_error)) => {
+                            return Err(ConnectingError::with_no_connection(&quic_connection));
+                        }
+                    };
+
+                    if !matches!(frame.kind(), FrameKind::Exercise(_) | FrameKind::Unknown(_)) {
+                        return Ok(frame);
+                    }
+
+                    if let Err(violation) = driver.unknown().on_frame(stream_id, &frame) {
+                        return Err(reject_response(&quic_connection, violation));
+                    }
+                }
+            };
+
+            let frame = match with_timeout(timeouts.session_request, read_response).await {
+                Some(Ok(frame)) => frame,
+                Some(Err(error)) => return Err(error),
+                None => {
+                    close_on_timeout(&quic_connection, ErrorCode::NoError);
+                    return Err(ConnectingError::connection_error(ConnectionError::TimedOut));
+                }
+            };
+
+            if !matches!(frame.kind(), FrameKind::Headers) {
+                let violation =
+                    Violation::new(ErrorCode::FrameUnexpected, "Response is not HEADERS")
+                        .on_stream(stream_id)
+                        .in_phase(ProtocolPhase::SessionEstablishment)
+                        .with_frame(frame.kind());
+                close_on_violation(&quic_connection, &violation);
+                return Err(ConnectingError::connection_error(
+                    ConnectionError::local_h3_error(violation, &quic_connection),
                 ));
             }
-            Err(ProtoReadError::IO(_io_error)) => {
-                return Err(ConnectingError::with_no_connection(&quic_connection));
-            }
-        };
 
-        if !matches!(frame.kind(), FrameKind::Headers) {
-            quic_connection.close(varint_w2q(ErrorCode::FrameUnexpected.to_code()), b"");
-            return Err(ConnectingError::ConnectionError(
-                ConnectionError::local_h3_error(ErrorCode::FrameUnexpected),
-            ));
-        }
+            let headers = match Headers::with_frame_limited(&frame, stream_id, &header_limits) {
+                Ok(headers) => headers,
+                Err(ErrorCode::ExcessiveLoad) => {
+                    return Err(reject_response_headers(&quic_connection));
+                }
+                Err(error_code) => {
+                    let violation = Violation::new(error_code, "Invalid HEADERS frame")
+                        .on_stream(stream_id)
+                        .in_phase(ProtocolPhase::SessionEstablishment)
+                        .with_frame(FrameKind::Headers);
+                    close_on_violation(&quic_connection, &violation);
+                    return Err(ConnectingError::connection_error(
+                        ConnectionError::local_h3_error(violation, &quic_connection),
+                    ));
+                }
+            };
+
+            let session_response = match SessionResponseProto::try_from(headers) {
+                Ok(session_response) => session_response,
+                Err(_) => {
+                    let violation =
+                        Violation::new(ErrorCode::Message, "Malformed session response")
+                            .on_stream(stream_id)
+                            .in_phase(ProtocolPhase::SessionEstablishment)
+                            .with_frame(FrameKind::Headers);
+                    close_on_violation(&quic_connection, &violation);
+                    return Err(ConnectingError::connection_error(
+                        ConnectionError::local_h3_error(violation, &quic_connection),
+                    ));
+                }
+            };
+
+            if !session_response.code().is_informational() {
+                break session_response;
+            }
 
-        let headers = match Headers::with_frame(&frame, stream_id) {
-            Ok(headers) => headers,
-            Err(error_code) => {
-                quic_connection.close(varint_w2q(error_code.to_code()), b"");
-                return Err(ConnectingError::ConnectionError(
-                    ConnectionError::local_h3_error(error_code),
+            debug!(
+                "Interim session response ({})",
+                session_response.code().into_inner()
+            );
+
+            interim_responses += 1;
+            if interim_responses > MAX_INTERIM_RESPONSES {
+                let violation =
+                    Violation::new(ErrorCode::ExcessiveLoad, "Too many interim responses")
+                        .on_stream(stream_id)
+                        .in_phase(ProtocolPhase::SessionEstablishment)
+                        .with_frame(FrameKind::Headers);
+                close_on_violation(&quic_connection, &violation);
+                return Err(ConnectingError::connection_error(
+                    ConnectionError::local_h3_error(violation, &quic_connection),
                 ));
             }
         };
 
-        let session_response = match SessionResponseProto::try_from(headers) {
-            Ok(session_response) => session_response,
-            Err(_) => {
-                quic_connection.close(varint_w2q(ErrorCode::Message.to_code()), b"");
-                return Err(ConnectingError::ConnectionError(
-                    ConnectionError::local_h3_error(ErrorCode::Message),
-                ));
+        report.timings.session_exchange = Some(stopwatch.lap());
+
+        let subprotocol = session_response
+            .protocol()
+            .filter(|_| session_response.code().is_successful());
+
+        if let Some(subprotocol) = &subprotocol {
+            if !options.subprotocols.contains(subprotocol) {
+                debug!("Server selected a protocol not offered: '{}'", subprotocol);
+                quic_connection.close(
+                    varint_w2q(ErrorCode::Message.to_code()),
+                    b"Unexpected protocol",
+                );
+                return Err(ConnectingError::UnexpectedSubprotocol(subprotocol.clone()));
+            }
+        }
+
+        #[cfg(feature = "compression")]
+        let codec = match session_response.headers().get(ENCODING_HEADER) {
+            Some(name) if session_response.code().is_successful() => {
+                match options.codecs.get(name) {
+                    Some(codec) => Some(codec.clone()),
+                    None => {
+                        debug!("Server selected a codec not offered: '{}'", name);
+                        quic_connection.close(
+                            varint_w2q(ErrorCode::Message.to_code()),
+                            b"Unexpected codec",
+                        );
+                        return Err(ConnectingError::UnexpectedCodec(name.to_string()));
+                    }
+                }
             }
+            _ => None,
         };
 
         if session_response.code().is_successful() {
             match driver.register_session(stream_session).await {
                 Ok(()) => {}
                 Err(driver_error) => {
-                    return Err(ConnectingError::ConnectionError(
+                    return Err(ConnectingError::connection_error(
                         ConnectionError::with_driver_error(driver_error, &quic_connection),
                     ))
                 }
             }
         } else {
-            return Err(ConnectingError::SessionRejected);
+            return Err(ConnectingError::session_rejected());
         }
 
-        Ok(Connection::new(quic_connection, driver, session_id))
-    }
-}
-
-type DynFutureIncomingSession =
-    dyn Future<Output = Result<SessionRequest, ConnectionError>> + Send + Sync;
-
-/// [`Future`] for an in-progress incoming connection attempt.
-///
-/// Created by [`Endpoint::accept`].
-pub struct IncomingSession(Pin<Box<DynFutureIncomingSession>>);
+        let resumption_token = session_response
+            .headers()
+            .get(RESUMPTION_TOKEN_HEADER)
+            .and_then(|token| token.parse().ok());
 
-impl IncomingSession {
-    fn new(quic_connecting: quinn::Connecting) -> Self {
-        Self(Box::pin(Self::accept(quic_connecting)))
-    }
+        let datagram_timestamps = self.side.driver_config.datagram_timestamps
+            && session_response.headers().get(DATAGRAM_TIMESTAMPS_HEADER) == Some("?1");
 
-    async fn accept(quic_connecting: quinn::Connecting) -> Result<SessionRequest, ConnectionError> {
-        let quic_connection = quic_connecting.await?;
+        let draft = session_response
+            .headers()
+            .get(DRAFT_HEADER)
+            .map(str::to_string);
+        let draft_header = draft.is_some();
 
-        let driver = Driver::init(quic_connection.clone());
+        let connection = Connection::new(quic_connection, driver, session_id)
+            .with_resumption_token(resumption_token)
+            .with_datagram_timestamps(datagram_timestamps)
+            .with_draft(draft, draft_header)
+            .with_subprotocol(subprotocol)
+            .with_url(Some(url.clone()))
+            .with_request_info(request_info);
 
-        let _settings = driver.accept_settings().await.map_err(|driver_error| {
-            ConnectionError::with_driver_error(driver_error, &quic_connection)
-        })?;
+        #[cfg(feature = "compression")]
+        let connection = connection.with_codec(codec);
 
-        // TODO(biagio): validate settings
+        Ok(connection)
+    }
 
-        let stream_session = driver.accept_session().await.map_err(|driver_error| {
-            ConnectionError::with_driver_error(driver_error, &quic_connection)
-        })?;
+    /// Returns statistics about connection attempts made by this endpoint.
+    pub fn connect_stats(&self) -> ConnectStats {
+        ConnectStats {
+            attempts: self.side.connect_counters.attempts.load(Ordering::Relaxed),
+            retries: self.side.connect_counters.retries.load(Ordering::Relaxed),
+        }
+    }
 
-        Ok(SessionRequest::new(quic_connection, driver, stream_session))
+    async fn connect_quic(
+        &self,
+        socket_address: SocketAddr,
+        server_name: &str,
+        worker: Option<&Worker>,
+    ) -> Result<quinn::Connection, quinn::ConnectionError> {
+        let mut attempt = 1;
+
+        loop {
+            self.side
+                .connect_counters
+                .attempts
+                .fetch_add(1, Ordering::Relaxed);
+
+            let connecting = spawning_on(worker, || {
+                self.endpoint.connect(socket_address, server_name)
+            });
+            let result = connecting
+                .expect("QUIC connection parameters must be validated")
+                .await;
+
+            match result {
+                Err(error)
+                    if attempt < self.side.max_connect_attempts && Self::is_retryable(&error) =>
+                {
+                    debug!(
+                        "Connection attempt {} failed ({}): retrying",
+                        attempt, error
+                    );
+                    self.side
+                        .connect_counters
+                        .retries
+                        .fetch_add(1, Ordering::Relaxed);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
     }
-}
 
-impl Future for IncomingSession {
-    type Output = Result<SessionRequest, ConnectionError>;
+    fn is_retryable(error: &quinn::ConnectionError) -> bool {
+        use quinn_proto::TransportErrorCode;
 
-    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        Future::poll(self.0.as_mut(), cx)
+        match error {
+            // A version mismatch would fail again, with the same versions.
+            quinn::ConnectionError::Reset => true,
+            quinn::ConnectionError::TransportError(error) => {
+                error.code == TransportErrorCode::INVALID_TOKEN
+            }
+            quinn::ConnectionError::ConnectionClosed(close) => {
+                close.error_code == TransportErrorCode::INVALID_TOKEN
+            }
+            _ => false,
+        }
     }
 }
 
-/// A incoming client session request.
+/// Options of a client connection.
 ///
-/// Server should use methods [`accept`](Self::accept) or [`not_found`](Self::not_found)
-/// in order to validate or reject the client request.
-pub struct SessionRequest {
-    quic_connection: quinn::Connection,
-    driver: Driver,
-    stream_session: StreamSession,
+/// See [`Endpoint::connect_with_options`].
+#[derive(Clone, Debug, Default)]
+pub struct ConnectOptions {
+    server_name: Option<String>,
+    timeouts: Option<Timeouts>,
+    worker: Option<Worker>,
+    subprotocols: Vec<String>,
+    #[cfg(feature = "compression")]
+    codecs: Codecs,
 }
 
-impl SessionRequest {
-    pub(crate) fn new(
-        quic_connection: quinn::Connection,
-        driver: Driver,
-        stream_session: StreamSession,
-    ) -> Self {
-        Self {
-            quic_connection,
-            driver,
-            stream_session,
-        }
+impl ConnectOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    /// Returns the `:authority` field of the request.
-    pub fn authority(&self) -> &str {
-        self.stream_session.request().authority()
+    /// Overrides the TLS server name, which is the URL host by default.
+    ///
+    /// The server name is sent in the TLS `server_name` extension (SNI) and it must be
+    /// covered by the certificate presented by the server. The URL host is still dialed
+    /// and used as the authority of the session request.
+    /// This allows, for instance, dialing a server by IP address while validating the
+    /// name of its certificate.
+    pub fn server_name<S>(mut self, server_name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.server_name = Some(server_name.into());
+        self
     }
 
-    /// Returns the `:path` field of the request.
-    pub fn path(&self) -> &str {
-        self.stream_session.request().path()
+    /// Overrides the timeouts configured for the client
+    /// (see [`ClientConfigBuilder::timeouts`](crate::config::ClientConfigBuilder::timeouts)).
+    ///
+    /// The [`idle`](Timeouts::idle) timeout is ignored.
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = Some(timeouts);
+        self
     }
 
-    /// Returns the `origin` field of the request if present.
-    pub fn origin(&self) -> Option<&str> {
-        self.stream_session.request().origin()
+    /// Pins the connection to `worker`: its QUIC connection driver and internal tasks
+    /// are spawned on the worker, instead of with the spawner of the client
+    /// (see [`ClientConfigBuilder::spawn_with`](crate::config::ClientConfigBuilder::spawn_with)).
+    ///
+    /// See [`WorkerPool`](crate::worker::WorkerPool).
+    pub fn pin_to(mut self, worker: &Worker) -> Self {
+        self.worker = Some(worker.clone());
+        self
     }
 
-    /// Returns the `user-agent` field of the request if present.
-    pub fn user_agent(&self) -> Option<&str> {
-        self.stream_session.request().user_agent()
-    }
+    /// Offers application protocols to the server, in order of preference
+    /// (e.g., `["chat-v2", "chat-v1"]`), similarly to WebSocket subprotocols.
+    ///
+    /// They are sent in the `wt-available-protocols` request header. The server selects
+    /// at most one of them (see
+    /// [`SessionRequest::select_protocol`](crate::endpoint::SessionRequest::select_protocol)),
+    /// available with [`Connection::subprotocol`] once connected. Each protocol must be
+    /// a non-empty string of printable ASCII characters.
+    pub fn subprotocols<I, S>(mut self, protocols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.subprotocols = protocols.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Offers compression codecs to the server, in order of preference.
+    ///
+    /// They are sent in the `wt-accept-encoding` request header. The server selects at
+    /// most one of them (see [`SessionRequest::select_codec`]), available with
+    /// [`Connection::codec`] once connected.
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    pub fn compression(mut self, codecs: Codecs) -> Self {
+        self.codecs = codecs;
+        self
+    }
+}
+
+/// Diagnostics of a connection attempt.
+///
+/// See [`Endpoint::connect_with_report`].
+#[derive(Clone, Debug, Default)]
+pub struct ConnectReport {
+    stage: ConnectStage,
+    resolved_addresses: Vec<SocketAddr>,
+    remote_address: Option<SocketAddr>,
+    quic_handshake: Option<Result<(), ConnectionError>>,
+    timings: ConnectTimings,
+}
+
+impl ConnectReport {
+    /// Returns the last stage reached: the one that failed (or was interrupted by the
+    /// deadline) if the attempt did not succeed.
+    #[inline(always)]
+    pub fn stage(&self) -> ConnectStage {
+        self.stage
+    }
+
+    /// Returns the addresses the server name has been resolved to.
+    #[inline(always)]
+    pub fn resolved_addresses(&self) -> &[SocketAddr] {
+        &self.resolved_addresses
+    }
+
+    /// Returns the address the QUIC handshake has been attempted with, if any.
+    #[inline(always)]
+    pub fn remote_address(&self) -> Option<SocketAddr> {
+        self.remote_address
+    }
+
+    /// Returns the result of the QUIC handshake, or `None` if it has not completed.
+    #[inline(always)]
+    pub fn quic_handshake(&self) -> Option<Result<(), &ConnectionError>> {
+        self.quic_handshake
+            .as_ref()
+            .map(|result| result.as_ref().map(|_| ()))
+    }
+
+    /// Returns the durations of the completed phases.
+    #[inline(always)]
+    pub fn timings(&self) -> &ConnectTimings {
+        &self.timings
+    }
+}
+
+/// A stage of a client connection attempt.
+///
+/// See [`ConnectReport::stage`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConnectStage {
+    /// Parsing and validation of the URL.
+    #[default]
+    UrlValidation,
+
+    /// Resolution of the server name.
+    DnsResolution,
+
+    /// QUIC (and TLS) handshake.
+    QuicHandshake,
+
+    /// Exchange of the HTTP3 SETTINGS.
+    SettingsExchange,
+
+    /// Exchange of the WebTransport session request and response.
+    SessionExchange,
+
+    /// The connection has been established.
+    Established,
+}
+
+/// Statistics about connection attempts of a client [`Endpoint`].
+///
+/// See [`Endpoint::connect_stats`].
+#[derive(Copy, Clone, Debug)]
+pub struct ConnectStats {
+    attempts: u64,
+    retries: u64,
+}
+
+impl ConnectStats {
+    /// Returns the total number of QUIC handshakes attempted.
+    #[inline(always)]
+    pub fn attempts(&self) -> u64 {
+        self.attempts
+    }
+
+    /// Returns the number of QUIC handshakes attempted again after a retryable failure.
+    #[inline(always)]
+    pub fn retries(&self) -> u64 {
+        self.retries
+    }
+}
+
+#[derive(Default)]
+struct ConnectCounters {
+    attempts: AtomicU64,
+    retries: AtomicU64,
+}
+
+/// An operational event of a server [`Endpoint`].
+///
+/// See [`Endpoint::events`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum EndpointEvent {
+    /// A WebTransport session has been established.
+    ConnectionAccepted {
+        /// The address of the client.
+        remote_address: SocketAddr,
+
+        /// The [stable identifier](Connection::stable_id) of the connection.
+        stable_id: usize,
+    },
+
+    /// A connection attempt failed before its WebTransport session was established
+    /// (QUIC handshake, SETTINGS exchange, or session request).
+    ///
+    /// Sessions handed to an extended CONNECT handler, or rejected by the application,
+    /// are not reported.
+    HandshakeFailed {
+        /// The address of the client.
+        remote_address: SocketAddr,
+
+        /// The reason of the failure.
+        error: ConnectionError,
+    },
+
+    /// A connection, previously reported as [accepted](Self::ConnectionAccepted), is closed.
+    ConnectionClosed {
+        /// The address of the client.
+        remote_address: SocketAddr,
+
+        /// The [stable identifier](Connection::stable_id) of the connection.
+        stable_id: usize,
+
+        /// The reason of the closing.
+        reason: ConnectionError,
+    },
+
+    /// A session request has been rejected, because its principal exceeded its quota
+    /// (see [`Quotas`]).
+    ///
+    /// It is not reported as [`HandshakeFailed`](Self::HandshakeFailed).
+    QuotaExceeded {
+        /// The address of the client.
+        remote_address: SocketAddr,
+
+        /// The principal the session was accounted to.
+        principal: String,
+    },
+
+    /// A connection attempt has to wait, as the maximum number of connections setting up
+    /// at the same time is reached
+    /// (see [`ServerConfigBuilder::max_concurrent_setups`](crate::config::ServerConfigBuilder::max_concurrent_setups)).
+    AcceptQueueFull {
+        /// The address of the client.
+        remote_address: SocketAddr,
+    },
+}
+
+/// Subscription to the operational events of a server [`Endpoint`].
+///
+/// See [`Endpoint::events`].
+pub struct EndpointEvents {
+    receiver: broadcast::Receiver<EndpointEvent>,
+    missed: u64,
+}
+
+impl EndpointEvents {
+    /// Number of events buffered for a subscriber, before the oldest ones are dropped.
+    const CAPACITY: usize = 1024;
+
+    /// Returns the next event.
+    ///
+    /// Returns `None` once the endpoint is dropped.
+    pub async fn next(&mut self) -> Option<EndpointEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) => return Some(event),
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    debug!("Endpoint events subscriber lagging: {} missed", missed);
+                    self.missed += missed;
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Returns the number of events dropped because this subscriber did not keep up.
+    #[inline(always)]
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+}
+
+type DynFutureIncomingSession =
+    dyn Future<Output = Result<SessionRequest, ConnectionError>> + Send + Sync;
+
+/// [`Future`] for an in-progress incoming connection attempt.
+///
+/// Created by [`Endpoint::accept`].
+pub struct IncomingSession {
+    pending: Option<(quinn::Connecting, ServerContext)>,
+    accepting: Option<Pin<Box<DynFutureIncomingSession>>>,
+    relocation: Option<Arc<Relocation>>,
+    worker: Option<Worker>,
+}
+
+impl IncomingSession {
+    fn new(
+        quic_connecting: quinn::Connecting,
+        relocation: Option<Arc<Relocation>>,
+        context: ServerContext,
+    ) -> Self {
+        Self {
+            pending: Some((quic_connecting, context)),
+            accepting: None,
+            relocation,
+            worker: None,
+        }
+    }
+
+    /// Pins this connection to `worker`: its QUIC connection driver and internal tasks
+    /// are spawned on the worker, instead of with the spawner of the server
+    /// (see [`ServerConfigBuilder::spawn_with`](crate::config::ServerConfigBuilder::spawn_with)).
+    ///
+    /// The connection cannot be pinned once this future has been polled.
+    /// See [`WorkerPool`](crate::worker::WorkerPool).
+    pub fn pin_to(mut self, worker: &Worker) -> Self {
+        if let Some((_, context)) = &mut self.pending {
+            context.driver_config.spawner = worker.spawner();
+            self.worker = Some(worker.clone());
+        }
+        self
+    }
+
+    /// Overrides the timeouts configured for the server, for this connection only
+    /// (see [`ServerConfigBuilder::timeouts`](crate::config::ServerConfigBuilder::timeouts)).
+    ///
+    /// The [`idle`](Timeouts::idle) timeout is ignored. Timeouts cannot be changed
+    /// once this future has been polled.
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        if let Some((_, context)) = &mut self.pending {
+            context.timeouts = timeouts;
+        }
+        self
+    }
+
+    async fn accept(
+        quic_connecting: quinn::Connecting,
+        context: ServerContext,
+    ) -> Result<SessionRequest, ConnectionError> {
+        let remote_address = quic_connecting.remote_address();
+        let events = context.events.clone();
+
+        let result = Self::accept_session(quic_connecting, context).await;
+
+        match &result {
+            Ok(_) | Err(ConnectionError::HandedOff(_)) => {}
+            Err(error) => {
+                let _ = events.send(EndpointEvent::HandshakeFailed {
+                    remote_address,
+                    error: error.clone(),
+                });
+            }
+        }
+
+        result
+    }
+
+    async fn accept_session(
+        quic_connecting: quinn::Connecting,
+        context: ServerContext,
+    ) -> Result<SessionRequest, ConnectionError> {
+        let remote_address = quic_connecting.remote_address();
+        let mut stopwatch = Stopwatch::start();
+        let mut timings = ConnectTimings::default();
+
+        let quic_connection = with_timeout(context.timeouts.handshake, quic_connecting)
+            .await
+            .ok_or(ConnectionError::TimedOut)??;
+        timings.quic_handshake = Some(stopwatch.lap());
+
+        // The setup slot is released once SETTINGS are received: the session request is
+        // then up to the client.
+        let mut reserving = context.memory_budget.is_some();
+        let setup = async {
+            let memory = match &context.memory_budget {
+                Some(memory_budget) => memory_budget.reserve().await?,
+                None => MemoryAccount::default(),
+            };
+            reserving = false;
+
+            let _setup_slot = match &context.setup_slots {
+                Some(setup_slots) => Some(match setup_slots.clone().try_acquire_owned() {
+                    Ok(setup_slot) => setup_slot,
+                    Err(_) => {
+                        let _ = context
+                            .events
+                            .send(EndpointEvent::AcceptQueueFull { remote_address });
+
+                        setup_slots
+                            .clone()
+                            .acquire_owned()
+                            .await
+                            .expect("Semaphore is never closed")
+                    }
+                }),
+                None => None,
+            };
+
+            let driver = Driver::init(
+                quic_connection.clone(),
+                context.driver_config.clone(),
+                memory,
+            );
+
+            let settings = driver.accept_settings().await;
+
+            Some((driver, settings))
+        };
+
+        let budget_exceeded = || {
+            quic_connection.close(
+                varint_w2q(ErrorCode::ExcessiveLoad.to_code()),
+                b"Memory budget exhausted",
+            );
+            ConnectionError::MemoryBudgetExceeded
+        };
+
+        let (driver, settings) = match with_timeout(context.timeouts.settings_exchange, setup).await
+        {
+            Some(Some(setup)) => setup,
+            Some(None) => return Err(budget_exceeded()),
+            None if reserving => {
+                debug!("Timed out waiting for the memory budget");
+                return Err(budget_exceeded());
+            }
+            None => {
+                close_on_timeout(&quic_connection, ErrorCode::MissingSettings);
+                return Err(ConnectionError::TimedOut);
+            }
+        };
+
+        let _settings = settings.map_err(|driver_error| {
+            ConnectionError::with_driver_error(driver_error, &quic_connection)
+        })?;
+
+        timings.settings_exchange = Some(stopwatch.lap());
+
+        // TODO(biagio): validate settings
+
+        let server_name = quic_connection
+            .handshake_data()
+            .and_then(|data| data.downcast::<TlsInfo>().ok())
+            .and_then(|info| info.server_name);
+
+        let accept_session = async {
+            loop {
+                let mut stream_session = match driver.accept_session().await {
+                    Ok(stream_session) => stream_session,
+                    Err(driver_error) => {
+                        return Err(ConnectionError::with_driver_error(
+                            driver_error,
+                            &quic_connection,
+                        ))
+                    }
+                };
+
+                let status_code = match context
+                    .request_validation
+                    .check(stream_session.request(), server_name.as_deref())
+                {
+                    Ok(()) => return Ok(stream_session),
+                    Err(status_code) => status_code,
+                };
+
+                debug!("Session request rejected by validation ({})", status_code);
+
+                let frame = SessionResponseProto::with_status_code(status_code)
+                    .headers()
+                    .generate_frame(stream_session.id());
+
+                if stream_session.write_frame(frame).await.is_ok() {
+                    stream_session.finish().await;
+                }
+            }
+        };
+
+        let stream_session = with_timeout(context.timeouts.session_request, accept_session)
+            .await
+            .ok_or_else(|| {
+                close_on_timeout(&quic_connection, ErrorCode::NoError);
+                ConnectionError::TimedOut
+            })??;
+
+        let handler = (!stream_session.request().is_webtransport()).then(|| {
+            let protocol = stream_session.request().protocol().to_string();
+            let handler = context
+                .extended_connect_handlers
+                .get(&protocol)
+                .cloned()
+                .expect("Driver only accepts registered protocols");
+
+            (protocol, handler)
+        });
+
+        let spawner = context.driver_config.spawner.clone();
+
+        let session_request = SessionRequest::new(
+            quic_connection,
+            driver,
+            stream_session,
+            context,
+            stopwatch,
+            timings,
+        );
+
+        match handler {
+            Some((protocol, handler)) => {
+                debug!(
+                    "Session request handed to the '{}' protocol handler",
+                    protocol
+                );
+                spawner.spawn(handler(session_request));
+                Err(ConnectionError::HandedOff(protocol))
+            }
+            None => Ok(session_request),
+        }
+    }
+}
+
+impl Future for IncomingSession {
+    type Output = Result<SessionRequest, ConnectionError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some((quic_connecting, context)) = self.pending.take() {
+            if let Some(relocation) = self.relocation.take() {
+                relocation.settle(self.worker.take());
+            }
+            self.accepting = Some(Box::pin(Self::accept(quic_connecting, context)));
+        }
+
+        let accepting = self
+            .accepting
+            .as_mut()
+            .expect("Accepting future is set on first poll");
+
+        Future::poll(accepting.as_mut(), cx)
+    }
+}
+
+impl Drop for IncomingSession {
+    fn drop(&mut self) {
+        if let Some(relocation) = self.relocation.take() {
+            relocation.settle(None);
+        }
+    }
+}
+
+/// A incoming client session request.
+///
+/// Server should use methods [`accept`](Self::accept) or [`not_found`](Self::not_found)
+/// in order to validate or reject the client request.
+pub struct SessionRequest {
+    quic_connection: quinn::Connection,
+    driver: Driver,
+    stream_session: StreamSession,
+    response_headers: Headers,
+    principal: Option<String>,
+    draft: Option<String>,
+    subprotocol: Option<String>,
+    #[cfg(feature = "compression")]
+    codec: Option<Arc<dyn Compression>>,
+    url: Option<Url>,
+    context: ServerContext,
+    stopwatch: Stopwatch,
+    timings: ConnectTimings,
+}
+
+impl SessionRequest {
+    fn new(
+        quic_connection: quinn::Connection,
+        driver: Driver,
+        stream_session: StreamSession,
+        context: ServerContext,
+        stopwatch: Stopwatch,
+        timings: ConnectTimings,
+    ) -> Self {
+        let mut response_headers = context.response_headers.as_ref().clone();
+        let mut draft = None;
+
+        // Firefox does not support the draft header (and other protocols do not use it)
+        if !stream_session.request().is_webtransport() {
+            response_headers.remove(DRAFT_HEADER);
+        } else if stream_session
+            .request()
+            .user_agent()
+            .unwrap_or_default()
+            .contains("firefox")
+        {
+            draft = response_headers.remove(DRAFT_HEADER);
+        }
+
+        let url = url_validation::parse(&format!(
+            "https://{}{}",
+            stream_session.request().authority(),
+            stream_session.request().path()
+        ))
+        .ok();
+
+        Self {
+            quic_connection,
+            driver,
+            stream_session,
+            response_headers,
+            principal: None,
+            draft,
+            subprotocol: None,
+            #[cfg(feature = "compression")]
+            codec: None,
+            url,
+            context,
+            stopwatch,
+            timings,
+        }
+    }
+
+    /// Returns the `:method` field of the request (i.e., `CONNECT`).
+    pub fn method(&self) -> &str {
+        self.stream_session.request().method()
+    }
+
+    /// Returns the `:protocol` field of the request.
+    ///
+    /// It is `webtransport`, unless an
+    /// [extended CONNECT handler](crate::config::ServerConfigBuilder::extended_connect_handler)
+    /// is registered for another protocol.
+    pub fn protocol(&self) -> &str {
+        self.stream_session.request().protocol()
+    }
+
+    /// Returns the `:authority` field of the request.
+    pub fn authority(&self) -> &str {
+        self.stream_session.request().authority()
+    }
+
+    /// Returns the `:path` field of the request.
+    pub fn path(&self) -> &str {
+        self.stream_session.request().path()
+    }
+
+    /// Returns the URL requested: the `https` URL made of the `:authority` and `:path` of
+    /// the request, normalized as on the client.
+    ///
+    /// It is `None` if they do not form a valid URL. It remains available on the
+    /// [`Connection`] once accepted (see [`Connection::url`]).
+    pub fn url(&self) -> Option<&Url> {
+        self.url.as_ref()
+    }
+
+    /// Returns the `origin` field of the request if present.
+    pub fn origin(&self) -> Option<&str> {
+        self.stream_session.request().origin()
+    }
+
+    /// Returns the `user-agent` field of the request if present.
+    pub fn user_agent(&self) -> Option<&str> {
+        self.stream_session.request().user_agent()
+    }
 
     /// Returns all header fields associated with the request.
-    pub fn headers(&self) -> &HashMap<String, String> {
-        self.stream_session.request().headers().as_ref()
+    pub fn headers(&self) -> &Headers {
+        self.stream_session.request().headers()
+    }
+
+    /// Adds a header field to the response for this request only.
+    ///
+    /// If the key is already present (e.g., from
+    /// [`ServerConfigBuilder::add_response_header`](crate::config::ServerConfigBuilder::add_response_header)),
+    /// the value is updated. Pseudo-header fields (e.g., `:status`) are ignored.
+    pub fn add_response_header<K, V>(&mut self, key: K, value: V)
+    where
+        K: ToString,
+        V: ToString,
+    {
+        self.response_headers.insert(key, value);
+    }
+
+    /// Removes a header field from the response for this request only.
+    pub fn remove_response_header<K>(&mut self, key: K)
+    where
+        K: AsRef<str>,
+    {
+        self.response_headers.remove(key);
+    }
+
+    /// Returns all header fields which will be added to the response.
+    pub fn response_headers(&self) -> &Headers {
+        &self.response_headers
+    }
+
+    /// Returns the application protocols offered by the client, in order of preference
+    /// (see [`ConnectOptions::subprotocols`]).
+    ///
+    /// The list is empty if the client did not offer any protocol.
+    pub fn subprotocols(&self) -> Vec<String> {
+        self.stream_session.request().available_protocols()
+    }
+
+    /// Selects the application protocol of the session, among the ones offered by the
+    /// client and the `supported` ones.
+    ///
+    /// The first protocol offered by the client (i.e., its preferred one) that is
+    /// supported is selected, and returned: `supported` can be a list of strings, or of an
+    /// application type naming its protocols (e.g., an `enum` implementing `AsRef<str>`).
+    /// The selection is sent in the `wt-protocol` response header, and is available with
+    /// [`Connection::subprotocol`] once accepted. If none matches, `None` is returned:
+    /// the request can be rejected, or accepted without protocol.
+    pub fn select_protocol<I, T>(&mut self, supported: I) -> Option<T>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        let mut supported = supported.into_iter().collect::<Vec<_>>();
+
+        let index = self.subprotocols().iter().find_map(|offered| {
+            supported
+                .iter()
+                .position(|protocol| protocol.as_ref() == offered)
+        })?;
+
+        let protocol = supported.swap_remove(index);
+        self.subprotocol = Some(protocol.as_ref().to_string());
+
+        Some(protocol)
+    }
+
+    /// Selects the compression codec of the session, among the ones offered by the client
+    /// (see [`ConnectOptions::compression`]) and the `supported` ones.
+    ///
+    /// The first codec offered by the client (i.e., its preferred one) that is supported
+    /// is selected, and returned. The selection is sent in the `wt-encoding` response
+    /// header, and is available with [`Connection::codec`] once accepted. If none matches,
+    /// `None` is returned: the session is not compressed.
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    pub fn select_codec(&mut self, supported: &Codecs) -> Option<Arc<dyn Compression>> {
+        let codec = supported
+            .select(self.headers().get(ACCEPT_ENCODING_HEADER)?)?
+            .clone();
+
+        self.response_headers.insert(ENCODING_HEADER, codec.name());
+        self.codec = Some(codec.clone());
+
+        Some(codec)
+    }
+
+    /// Sets the principal the session is accounted to (e.g., an authenticated tenant),
+    /// if [`Quotas`] are enabled.
+    ///
+    /// See [`ServerConfigBuilder::quotas`](crate::config::ServerConfigBuilder::quotas).
+    pub fn set_principal<P>(&mut self, principal: P)
+    where
+        P: Into<String>,
+    {
+        self.principal = Some(principal.into());
+    }
+
+    /// Returns the principal set with [`set_principal`](Self::set_principal).
+    pub fn principal(&self) -> Option<&str> {
+        self.principal.as_deref()
+    }
+
+    /// Returns the application state sealed in the resumption token presented by the client.
+    ///
+    /// It returns `None` if the client did not present a token, if the token cannot be
+    /// redeemed (e.g., expired), or if resumption tokens are not enabled
+    /// (see [`ServerConfigBuilder::resumption_tokens`](crate::config::ServerConfigBuilder::resumption_tokens)).
+    pub fn resumption_state(&self) -> Option<Vec<u8>> {
+        let tokens = self.context.resumption_tokens.as_ref()?;
+        let token = self
+            .stream_session
+            .request()
+            .get(RESUMPTION_TOKEN_HEADER)?
+            .parse()
+            .ok()?;
+
+        let state = tokens.redeem(&token);
+
+        if state.is_none() {
+            debug!("Resumption token presented by the client cannot be redeemed");
+        }
+
+        state
+    }
+
+    /// Issues a resumption token sealing `state`, sent with the response to this request.
+    ///
+    /// It returns `false` (and no token is sent) if resumption tokens are not enabled
+    /// (see [`ServerConfigBuilder::resumption_tokens`](crate::config::ServerConfigBuilder::resumption_tokens))
+    /// or the token cannot be sealed.
+    pub fn issue_resumption_token(&mut self, state: &[u8]) -> bool {
+        let token = match self.context.resumption_tokens.as_ref() {
+            Some(tokens) => tokens.issue(state),
+            None => None,
+        };
+
+        match token {
+            Some(token) => {
+                self.response_headers.insert(RESUMPTION_TOKEN_HEADER, token);
+                true
+            }
+            None => false,
+        }
     }
 
     /// Accepts the client request and it establishes the WebTransport session.
-    pub async fn accept(mut self) -> Result<Connection, ConnectionError> {
-        let user_agent = self.user_agent().unwrap_or_default();
+    pub async fn accept(self) -> Result<Connection, ConnectionError> {
+        self.establish(SessionResponseProto::ok()).await
+    }
 
-        let mut response = SessionResponseProto::ok();
+    /// Holds the session request open while `authorization` completes (e.g., a round trip
+    /// to an external identity provider), then accepts or rejects it.
+    ///
+    /// If `authorization` does not complete within the [timeout](Deferral::timeout), it is
+    /// dropped, the request is rejected with `504` and [`ConnectionError::TimedOut`] is
+    /// returned. If the client closes the connection meanwhile, its error is returned. On
+    /// rejection, `None` is returned.
+    ///
+    /// **Note**: the QUIC idle timers of both endpoints keep running during the wait. Unless
+    /// [progress](Deferral::progress) is reported (each interim response resets them), the
+    /// wait must stay below the idle timeout, or the connection is closed. No other session
+    /// request is processed on the connection meanwhile.
+    pub async fn accept_deferred<F>(
+        mut self,
+        authorization: F,
+        deferral: Deferral,
+    ) -> Result<Option<Connection>, ConnectionError>
+    where
+        F: Future<Output = Authorization>,
+    {
+        let quic_connection = self.quic_connection.clone();
+
+        let deadline = tokio::time::sleep(deferral.timeout);
+        tokio::pin!(deadline);
+        tokio::pin!(authorization);
+
+        let mut progress = deferral.progress.map(|period| {
+            let mut interval = tokio::time::interval_at(Instant::now() + period, period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            interval
+        });
+
+        let authorization = loop {
+            tokio::select! {
+                authorization = &mut authorization => break Some(authorization),
+                () = &mut deadline => break None,
+                () = progress_tick(&mut progress) => {
+                    let response = SessionResponseProto::with_status_code(StatusCode::PROCESSING);
+                    self.send_response(response).await?;
+                }
+                reason = quic_connection.closed() => return Err(reason.into()),
+            }
+        };
+
+        match authorization {
+            Some(Authorization::Accept) => self.accept().await.map(Some),
+            Some(Authorization::Reject(status_code)) => {
+                let status_code = StatusCode::try_from(status_code)
+                    .ok()
+                    .filter(|status_code| {
+                        !status_code.is_informational() && !status_code.is_successful()
+                    })
+                    .unwrap_or_else(|| StatusCode::try_from(403u16).expect("Valid status code"));
+                self.reject(SessionResponseProto::with_status_code(status_code))
+                    .await;
+                Ok(None)
+            }
+            None => {
+                debug!("Deferred authorization timed out");
+                let status_code = StatusCode::try_from(504u16).expect("Valid status code");
+                self.reject(SessionResponseProto::with_status_code(status_code))
+                    .await;
+                Err(ConnectionError::TimedOut)
+            }
+        }
+    }
+
+    /// Rejects the client request by replying with `404` status code.
+    pub async fn not_found(self) {
+        self.reject(SessionResponseProto::not_found()).await;
+    }
+
+    /// Rejects the client request by replying with `status_code`.
+    #[cfg(feature = "masque")]
+    pub(crate) async fn reject_with_status(self, status_code: StatusCode) {
+        self.reject(SessionResponseProto::with_status_code(status_code))
+            .await;
+    }
 
-        // Chrome support
-        if !user_agent.contains("firefox") {
-            response.add("sec-webtransport-http3-draft", "draft02");
+    /// Replies to the client request with `response`.
+    ///
+    /// A successful (`2xx`) status accepts the request and establishes the WebTransport
+    /// session, like [`accept`](Self::accept); any other status rejects it
+    /// (in that case `None` is returned).
+    ///
+    /// Header fields of `response` replace the ones with the same name configured for the
+    /// response (see [`add_response_header`](Self::add_response_header)).
+    /// Status codes outside the range `100..=599` are replied as `500`.
+    #[cfg(feature = "http")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http")))]
+    pub async fn respond(
+        mut self,
+        response: http::Response<()>,
+    ) -> Result<Option<Connection>, ConnectionError> {
+        let status_code = StatusCode::try_from(response.status().as_u16())
+            .unwrap_or_else(|_| StatusCode::try_from(500u16).expect("Valid status code"));
+
+        for key in response.headers().keys() {
+            self.response_headers.remove(key);
+        }
+
+        for (key, value) in response.headers() {
+            if let Ok(value) = value.to_str() {
+                self.response_headers.append(key, value);
+            }
+        }
+
+        let response = SessionResponseProto::with_status_code(status_code);
+
+        if status_code.is_successful() {
+            self.establish(response).await.map(Some)
+        } else {
+            self.reject(response).await;
+            Ok(None)
+        }
+    }
+
+    async fn establish(
+        self,
+        response: SessionResponseProto,
+    ) -> Result<Connection, ConnectionError> {
+        let remote_address = self.quic_connection.remote_address();
+        let events = self.context.events.clone();
+        let principal = self.principal.clone();
+
+        let result = self.establish_session(response).await;
+
+        match &result {
+            Ok(connection) => {
+                let stable_id = connection.stable_id();
+                let _ = events.send(EndpointEvent::ConnectionAccepted {
+                    remote_address,
+                    stable_id,
+                });
+
+                connection.on_closed(move |reason| {
+                    let _ = events.send(EndpointEvent::ConnectionClosed {
+                        remote_address,
+                        stable_id,
+                        reason,
+                    });
+                });
+            }
+            Err(ConnectionError::QuotaExceeded) => {
+                let _ = events.send(EndpointEvent::QuotaExceeded {
+                    remote_address,
+                    principal: principal.unwrap_or_default(),
+                });
+            }
+            Err(error) => {
+                let _ = events.send(EndpointEvent::HandshakeFailed {
+                    remote_address,
+                    error: error.clone(),
+                });
+            }
+        }
+
+        result
+    }
+
+    async fn establish_session(
+        mut self,
+        response: SessionResponseProto,
+    ) -> Result<Connection, ConnectionError> {
+        let quota = match (&self.context.quotas, &self.principal) {
+            (Some(quotas), Some(principal)) => match quotas.admit(principal) {
+                Some(quota) => Some(quota),
+                None => {
+                    debug!("Session rejected: quota of '{}' exceeded", principal);
+
+                    let status_code = StatusCode::try_from(429u16).expect("Valid status code");
+                    self.reject(SessionResponseProto::with_status_code(status_code))
+                        .await;
+                    return Err(ConnectionError::QuotaExceeded);
+                }
+            },
+            _ => None,
+        };
+
+        let datagram_timestamps = self.context.driver_config.datagram_timestamps
+            && self.headers().get(DATAGRAM_TIMESTAMPS_HEADER) == Some("?1");
+
+        if datagram_timestamps {
+            self.response_headers
+                .insert(DATAGRAM_TIMESTAMPS_HEADER, "?1");
+        }
+
+        let announced = self.response_headers.get(DRAFT_HEADER).map(str::to_string);
+        let draft_header = announced.is_some();
+        let draft = announced.or_else(|| self.draft.take());
+
+        let mut response = self.build_response(response);
+        if let Some(subprotocol) = &self.subprotocol {
+            response.set_protocol(subprotocol);
         }
 
         self.send_response(response).await?;
 
+        self.timings.session_exchange = Some(self.stopwatch.lap());
+
         let session_id = self.stream_session.session_id();
+        let request_info = SessionRequestInfo::new(self.headers().clone());
 
         self.driver
             .register_session(self.stream_session)
@@ -389,47 +2276,969 @@ impl SessionRequest {
                 ConnectionError::with_driver_error(driver_error, &self.quic_connection)
             })?;
 
-        Ok(Connection::new(
-            self.quic_connection,
-            self.driver,
-            session_id,
-        ))
-    }
-
-    /// Rejects the client request by replying with `404` status code.
-    pub async fn not_found(mut self) {
-        let user_agent = self.user_agent().unwrap_or_default();
+        if let Some(quota) = quota {
+            self.driver
+                .spawner()
+                .spawn(quota.accounting(self.quic_connection.clone()));
+            self.driver.set_quota(quota);
+        }
 
-        let mut response = SessionResponseProto::not_found();
+        let mut connection = Connection::new(self.quic_connection, self.driver, session_id)
+            .with_connect_timings(self.timings)
+            .with_quic_version(self.context.quic_version)
+            .with_timeouts(self.context.timeouts)
+            .with_datagram_timestamps(datagram_timestamps)
+            .with_draft(draft, draft_header)
+            .with_subprotocol(self.subprotocol)
+            .with_url(self.url)
+            .with_request_info(request_info);
+
+        #[cfg(feature = "compression")]
+        {
+            connection = connection.with_codec(self.codec);
+        }
 
-        // Chrome support
-        if !user_agent.contains("firefox") {
-            response.add("sec-webtransport-http3-draft", "draft02");
+        if let Some(registry) = &self.context.registry {
+            connection.register(registry);
         }
 
+        Ok(connection)
+    }
+
+    async fn reject(mut self, response: SessionResponseProto) {
+        let response = self.build_response(response);
         let _ = self.send_response(response).await;
         self.stream_session.finish().await;
     }
 
+    fn build_response(&self, mut response: SessionResponseProto) -> SessionResponseProto {
+        for (key, value) in self.response_headers.iter() {
+            if !key.starts_with(':') {
+                response.append(key, value);
+            }
+        }
+
+        response
+    }
+
     async fn send_response(
         &mut self,
         response: SessionResponseProto,
     ) -> Result<(), ConnectionError> {
         let frame = response.headers().generate_frame(self.stream_session.id());
 
-        match self.stream_session.write_frame(frame).await {
+        let write_response = async {
+            if let Some(frame) = self.driver.grease().frame() {
+                self.stream_session.write_frame(frame).await?;
+            }
+
+            self.stream_session.write_frame(frame).await
+        };
+
+        match write_response.await {
             Ok(()) => Ok(()),
             Err(ProtoWriteError::NotConnected) => {
                 Err(ConnectionError::no_connect(&self.quic_connection))
             }
             Err(ProtoWriteError::Stopped) => {
-                self.quic_connection
-                    .close(varint_w2q(ErrorCode::ClosedCriticalStream.to_code()), b"");
+                let violation =
+                    Violation::new(ErrorCode::ClosedCriticalStream, "Session stream stopped")
+                        .on_stream(self.stream_session.id())
+                        .in_phase(ProtocolPhase::SessionEstablishment);
+                close_on_violation(&self.quic_connection, &violation);
 
                 Err(ConnectionError::local_h3_error(
-                    ErrorCode::ClosedCriticalStream,
+                    violation,
+                    &self.quic_connection,
                 ))
             }
         }
     }
 }
+
+/// Maximum number of interim (1xx) responses a client accepts before the final response
+/// to its session request (see [`Deferral::progress`]).
+pub const MAX_INTERIM_RESPONSES: usize = 32;
+
+/// The outcome of a deferred authorization (see [`SessionRequest::accept_deferred`]).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Authorization {
+    /// The session is accepted.
+    Accept,
+
+    /// The request is rejected with the given status code (e.g., `403`).
+    ///
+    /// Codes outside the range `300..=599` are replied as `403`.
+    Reject(u16),
+}
+
+/// How long, and how, a session request is held open (see
+/// [`SessionRequest::accept_deferred`]).
+#[derive(Copy, Clone, Debug)]
+pub struct Deferral {
+    timeout: Duration,
+    progress: Option<Duration>,
+}
+
+impl Deferral {
+    /// Default maximum duration of the wait.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Creates a deferral with the [default timeout](Self::DEFAULT_TIMEOUT), reporting
+    /// no progress.
+    pub fn new() -> Self {
+        Self {
+            timeout: Self::DEFAULT_TIMEOUT,
+            progress: None,
+        }
+    }
+
+    /// Sets the maximum duration of the wait.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Reports progress to the client every `interval` during the wait, with an interim
+    /// `102` (Processing) response.
+    ///
+    /// Clients of this crate restart their
+    /// [session request timeout](crate::config::Timeouts::session_request) on each interim
+    /// response, up to [`MAX_INTERIM_RESPONSES`] times (beyond, they close the connection);
+    /// other clients should ignore them (as mandated by HTTP).
+    pub fn progress(mut self, interval: Duration) -> Self {
+        self.progress = Some(interval);
+        self
+    }
+}
+
+impl Default for Deferral {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converts the CONNECT request into an [`http::Request`].
+///
+/// The URI is built from the `:authority` and `:path` fields (it is left empty if they
+/// are not valid). Pseudo-header fields are omitted, as well as fields not representable
+/// with [`http::HeaderMap`].
+#[cfg(feature = "http")]
+#[cfg_attr(docsrs, doc(cfg(feature = "http")))]
+impl From<&SessionRequest> for http::Request<()> {
+    fn from(session_request: &SessionRequest) -> Self {
+        let mut request = http::Request::new(());
+
+        *request.method_mut() = http::Method::CONNECT;
+
+        *request.uri_mut() = http::Uri::builder()
+            .scheme("https")
+            .authority(session_request.authority())
+            .path_and_query(session_request.path())
+            .build()
+            .unwrap_or_default();
+
+        for (key, value) in session_request.headers().iter() {
+            if key.starts_with(':') {
+                continue;
+            }
+
+            if let (Ok(key), Ok(value)) = (
+                http::HeaderName::try_from(key),
+                http::HeaderValue::try_from(value),
+            ) {
+                request.headers_mut().append(key, value);
+            }
+        }
+
+        request
+    }
+}
+
+/// Awaits the next tick of `interval`, if any, or never completes.
+async fn progress_tick(interval: &mut Option<tokio::time::Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Awaits `future`, at most for `timeout` (if any).
+///
+/// Returns `None` if the timeout elapsed.
+async fn with_timeout<F>(timeout: Option<Duration>, future: F) -> Option<F::Output>
+where
+    F: Future,
+{
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+/// Closes a connection because a phase timed out.
+fn close_on_timeout(quic_connection: &quinn::Connection, error_code: ErrorCode) {
+    debug!("Connection phase timed out: closing connection");
+    quic_connection.close(varint_w2q(error_code.to_code()), b"Timeout");
+}
+
+/// Closes the connection on a `violation` of the session response.
+fn reject_response(quic_connection: &quinn::Connection, violation: Violation) -> ConnectingError {
+    let violation = violation.in_phase(ProtocolPhase::SessionEstablishment);
+    close_on_violation(quic_connection, &violation);
+    ConnectingError::connection_error(ConnectionError::local_h3_error(violation, quic_connection))
+}
+
+/// Closes the connection on a session response exceeding the header limits.
+fn reject_response_headers(quic_connection: &quinn::Connection) -> ConnectingError {
+    debug!("Session response headers exceed the limits: closing connection");
+    quic_connection.close(
+        varint_w2q(ErrorCode::ExcessiveLoad.to_code()),
+        b"Response headers too large",
+    );
+    ConnectingError::ResponseHeadersTooLarge
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FallbackResponse;
+    use crate::test_utils;
+    use quinn_proto::TransportErrorCode;
+    use wtransport_proto::bytes::BufferReader;
+    use wtransport_proto::frame::Frame;
+    use wtransport_proto::ids::StreamId;
+    use wtransport_proto::settings::Settings;
+    use wtransport_proto::stream_header::StreamHeader;
+    use wtransport_proto::varint::VarInt;
+
+    #[test]
+    fn retryable_handshake_errors() {
+        let transport_error = |code| {
+            quinn::ConnectionError::TransportError(quinn_proto::TransportError {
+                code,
+                frame: None,
+                reason: String::new(),
+            })
+        };
+
+        assert!(Endpoint::<Client>::is_retryable(
+            &quinn::ConnectionError::Reset
+        ));
+        assert!(Endpoint::<Client>::is_retryable(&transport_error(
+            TransportErrorCode::INVALID_TOKEN
+        )));
+
+        assert!(!Endpoint::<Client>::is_retryable(
+            &quinn::ConnectionError::VersionMismatch
+        ));
+        assert!(!Endpoint::<Client>::is_retryable(
+            &quinn::ConnectionError::TimedOut
+        ));
+        assert!(!Endpoint::<Client>::is_retryable(&transport_error(
+            TransportErrorCode::PROTOCOL_VIOLATION
+        )));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn phase_timeouts_follow_paused_clock() {
+        let start = tokio::time::Instant::now();
+
+        let result = with_timeout(
+            Some(Duration::from_secs(3600)),
+            std::future::pending::<()>(),
+        )
+        .await;
+        assert!(result.is_none());
+        assert_eq!(start.elapsed(), Duration::from_secs(3600));
+
+        assert_eq!(with_timeout(None, async { 7 }).await, Some(7));
+    }
+
+    /// Returns the URL of a UDP socket never answering.
+    fn silent_url(socket: &std::net::UdpSocket) -> String {
+        let port = socket.local_addr().unwrap().port();
+        format!("https://localhost:{port}/")
+    }
+
+    fn server_and_client() -> (Endpoint<Server>, Endpoint<Client>) {
+        let certificate = test_utils::certificate();
+        let client = Endpoint::client(test_utils::client_config(&certificate).build()).unwrap();
+        let server = Endpoint::server(test_utils::server_config(certificate).build()).unwrap();
+
+        (server, client)
+    }
+
+    /// Accepts the sessions of `server`, forever.
+    fn accept(server: Endpoint<Server>) {
+        tokio::spawn(async move {
+            loop {
+                let incoming_session = server.accept().await;
+                tokio::spawn(async move {
+                    if let Ok(session_request) = incoming_session.await {
+                        let _connection = session_request.accept().await;
+                        std::future::pending::<()>().await;
+                    }
+                });
+            }
+        });
+    }
+
+    #[tokio::test]
+    async fn connect_any_after_failure() {
+        let (server, client) = server_and_client();
+        let url = test_utils::url(&server);
+        accept(server);
+
+        let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let silent = silent_url(&silent);
+
+        // The third attempt starts as soon as the second one fails, while the first one
+        // is still pending: 1 stagger in total, instead of 2.
+        let stagger = Duration::from_secs(1);
+        let (index, _connection) = tokio::time::timeout(
+            stagger * 19 / 10,
+            client.connect_any([silent.as_str(), "invalid url", &url], stagger),
+        )
+        .await
+        .expect("Started before the stagger")
+        .unwrap();
+        assert_eq!(index, 2);
+    }
+
+    #[tokio::test]
+    async fn connect_any_stagger() {
+        let (server, client) = server_and_client();
+        let url = test_utils::url(&server);
+        accept(server);
+
+        let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let silent = silent_url(&silent);
+
+        for stagger in [Duration::ZERO, Duration::from_millis(50)] {
+            let (index, _connection) = tokio::time::timeout(
+                Duration::from_secs(5),
+                client.connect_any([&silent, &url], stagger),
+            )
+            .await
+            .expect("Not blocked by the silent candidate")
+            .unwrap();
+            assert_eq!(index, 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn connect_any_all_failed() {
+        let (_server, client) = server_and_client();
+
+        let error = match client
+            .connect_any(["invalid url", "https://"], Duration::from_secs(60))
+            .await
+        {
+            Ok(_) => panic!("Invalid URLs connected"),
+            Err(error) => error,
+        };
+
+        let urls = error
+            .failures()
+            .iter()
+            .map(|(url, _)| url.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(urls, ["invalid url", "https://"]);
+
+        match client
+            .connect_any(Vec::<String>::new(), Duration::ZERO)
+            .await
+        {
+            Ok(_) => panic!("No candidate connected"),
+            Err(error) => assert!(error.failures().is_empty()),
+        }
+    }
+
+    #[tokio::test]
+    async fn header_field_size_limits() {
+        let certificate = test_utils::certificate();
+        let long = "a".repeat(100);
+
+        // Request: the `:path` field is too large for the server.
+        let server = Endpoint::server(
+            test_utils::server_config(certificate.clone())
+                .max_request_header_field_size(64)
+                .build(),
+        )
+        .unwrap();
+        let client = Endpoint::client(test_utils::client_config(&certificate).build()).unwrap();
+        let url = test_utils::url(&server);
+        accept(server);
+
+        assert!(client.connect(format!("{url}{long}")).await.is_err());
+        assert!(client.connect(&url).await.is_ok());
+
+        // Response: a field of the server is too large for the client.
+        let server = Endpoint::server(
+            test_utils::server_config(certificate.clone())
+                .add_response_header("x-long", &long)
+                .build(),
+        )
+        .unwrap();
+        let url = test_utils::url(&server);
+        accept(server);
+
+        let client = Endpoint::client(
+            test_utils::client_config(&certificate)
+                .max_response_header_field_size(64)
+                .build(),
+        )
+        .unwrap();
+        assert!(client.connect(&url).await.is_err());
+
+        let client = Endpoint::client(
+            test_utils::client_config(&certificate)
+                .max_response_header_field_size(256)
+                .build(),
+        )
+        .unwrap();
+        assert!(client.connect(&url).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn additional_bind_addresses() {
+        let certificate = test_utils::certificate();
+        let server = Endpoint::server(
+            test_utils::server_config(certificate.clone())
+                .additional_bind_address(SocketAddr::from((std::net::Ipv4Addr::LOCALHOST, 0)))
+                .build(),
+        )
+        .unwrap();
+        let client = Endpoint::client(test_utils::client_config(&certificate).build()).unwrap();
+
+        let addresses = server.local_addrs().unwrap();
+        assert_eq!(addresses.len(), 2);
+        assert_eq!(addresses[0], server.local_addr().unwrap());
+        assert_ne!(addresses[0].port(), addresses[1].port());
+        accept(server);
+
+        // Sessions are accepted on every socket.
+        for address in addresses {
+            let url = format!("https://localhost:{}/", address.port());
+            let connection = client.connect(url).await.unwrap();
+            assert_eq!(connection.remote_address(), address);
+        }
+    }
+
+    #[tokio::test]
+    async fn try_accept() {
+        let (server, client) = server_and_client();
+        let url = test_utils::url(&server);
+        assert!(server.try_accept().is_none());
+
+        let connecting = tokio::spawn(async move { client.connect(url).await.map(|_| ()) });
+
+        let incoming_session = loop {
+            match server.try_accept() {
+                Some(incoming_session) => break incoming_session,
+                None => tokio::time::sleep(Duration::from_millis(5)).await,
+            }
+        };
+        accept_session_request(incoming_session);
+
+        connecting.await.unwrap().unwrap();
+        assert!(server.try_accept().is_none());
+    }
+
+    #[tokio::test]
+    async fn poll_accept() {
+        let certificate = test_utils::certificate();
+        let server = Endpoint::server(
+            test_utils::server_config(certificate.clone())
+                .additional_bind_address(SocketAddr::from((std::net::Ipv4Addr::LOCALHOST, 0)))
+                .build(),
+        )
+        .unwrap();
+        let client = Endpoint::client(test_utils::client_config(&certificate).build()).unwrap();
+
+        let accept = || std::future::poll_fn(|cx| server.poll_accept(cx));
+        assert!(poll_once(accept()).is_none());
+
+        // Attempts are polled on every socket, and pending accepts are kept between calls.
+        for address in server.local_addrs().unwrap() {
+            let url = format!("https://localhost:{}/", address.port());
+
+            let ((), connection) = tokio::join!(
+                async { accept_session_request(accept().await) },
+                client.connect(url)
+            );
+
+            assert_eq!(connection.unwrap().remote_address(), address);
+        }
+    }
+
+    #[tokio::test]
+    async fn max_concurrent_setups() {
+        let certificate = test_utils::certificate();
+        let server = Endpoint::server(
+            test_utils::server_config(certificate.clone())
+                .max_concurrent_setups(Some(1))
+                .build(),
+        )
+        .unwrap();
+        let client = Endpoint::client(test_utils::client_config(&certificate).build()).unwrap();
+        let url = test_utils::url(&server);
+        let mut events = server.events();
+
+        // A bare QUIC connection never sends its SETTINGS: it holds the only setup slot.
+        let (_quic_endpoint, quic_connection) =
+            test_utils::quic_connect(&server, &certificate).await;
+        accept_session_request(server.accept().await);
+
+        let connecting = tokio::spawn(async move { client.connect(url).await.map(|_| ()) });
+        let incoming_session = server.accept().await;
+        accept_session_request(incoming_session);
+
+        assert!(matches!(
+            events.next().await.unwrap(),
+            EndpointEvent::AcceptQueueFull { .. }
+        ));
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!connecting.is_finished());
+
+        // Closing the connection releases its slot.
+        quic_connection.close(quinn::VarInt::from_u32(0), b"");
+        connecting.await.unwrap().unwrap();
+    }
+
+    /// Sends a request with `headers` on a bare HTTP3 connection to a server serving
+    /// `fallback`, returning the code stopping the request stream and the response.
+    async fn bare_request(
+        fallback: Option<FallbackResponse>,
+        headers: &[(&str, &str)],
+    ) -> (VarInt, Vec<u8>, quinn::Connection) {
+        let certificate = test_utils::certificate();
+        let mut server_config = test_utils::server_config(certificate.clone());
+        if let Some(fallback) = fallback {
+            server_config = server_config.fallback_response(fallback);
+        }
+        let server = Endpoint::server(server_config.build()).unwrap();
+
+        let (_quic_endpoint, quic_connection) =
+            test_utils::quic_connect(&server, &certificate).await;
+        let incoming_session = server.accept().await;
+        let accepting = tokio::spawn(async move { incoming_session.await.map(|_| ()) });
+
+        let mut control = Vec::new();
+        StreamHeader::new_control().write(&mut control).unwrap();
+        Settings::builder()
+            .enable_webtransport()
+            .enable_h3_datagrams()
+            .build()
+            .generate_frame()
+            .write(&mut control)
+            .unwrap();
+        let mut control_stream = quic_connection.open_uni().await.unwrap();
+        control_stream.write_all(&control).await.unwrap();
+
+        let (mut send, mut recv) = quic_connection.open_bi().await.unwrap();
+        let mut request = Vec::new();
+        headers
+            .iter()
+            .copied()
+            .collect::<Headers>()
+            .generate_frame(crate::driver::utils::streamid_q2w(send.id()))
+            .write(&mut request)
+            .unwrap();
+        let _ = send.write_all(&request).await;
+
+        let code = send.stopped().await.unwrap();
+        let response = recv.read_to_end(4096).await.unwrap();
+
+        accepting.abort();
+        (
+            crate::driver::utils::varint_q2w(code),
+            response,
+            quic_connection,
+        )
+    }
+
+    #[tokio::test]
+    async fn fallback_response() {
+        let fallback = FallbackResponse::new(426)
+            .header("upgrade", "webtransport")
+            .text("Open a WebTransport session");
+        let request = [
+            (":method", "GET"),
+            (":scheme", "https"),
+            (":authority", "localhost"),
+            (":path", "/"),
+        ];
+
+        let (code, response, quic_connection) = bare_request(Some(fallback), &request).await;
+        assert_eq!(code, ErrorCode::NoError.to_code());
+
+        let mut reader = BufferReader::new(&response);
+        let headers = Frame::read_from_buffer(&mut reader).unwrap().unwrap();
+        assert!(matches!(headers.kind(), FrameKind::Headers));
+        let headers = Headers::with_frame(&headers, StreamId::new(VarInt::from_u32(0))).unwrap();
+        assert_eq!(headers.get(":status"), Some("426"));
+        assert_eq!(headers.get("upgrade"), Some("webtransport"));
+
+        let body = Frame::read_from_buffer(&mut reader).unwrap().unwrap();
+        assert!(matches!(body.kind(), FrameKind::Data));
+        assert_eq!(body.payload(), b"Open a WebTransport session");
+        assert_eq!(reader.capacity(), 0);
+
+        // The connection is not affected.
+        assert!(quic_connection.close_reason().is_none());
+    }
+
+    #[tokio::test]
+    async fn fallback_response_classic_connect() {
+        let fallback = FallbackResponse::new(404);
+        let request = [(":method", "CONNECT"), (":authority", "localhost:443")];
+
+        let (code, response, _quic_connection) = bare_request(Some(fallback), &request).await;
+        assert_eq!(code, ErrorCode::NoError.to_code());
+
+        let mut reader = BufferReader::new(&response);
+        let headers = Frame::read_from_buffer(&mut reader).unwrap().unwrap();
+        let headers = Headers::with_frame(&headers, StreamId::new(VarInt::from_u32(0))).unwrap();
+        assert_eq!(headers.get(":status"), Some("404"));
+        assert_eq!(reader.capacity(), 0);
+    }
+
+    #[tokio::test]
+    async fn no_fallback_response() {
+        let request = [
+            (":method", "GET"),
+            (":scheme", "https"),
+            (":authority", "localhost"),
+            (":path", "/"),
+        ];
+        let (code, response, _quic_connection) = bare_request(None, &request).await;
+        assert_eq!(code, ErrorCode::RequestRejected.to_code());
+        assert!(response.is_empty());
+
+        let request = [(":method", "CONNECT"), (":authority", "localhost:443")];
+        let (code, response, _quic_connection) = bare_request(None, &request).await;
+        assert_eq!(code, ErrorCode::Message.to_code());
+        assert!(response.is_empty());
+    }
+
+    /// Connects a client configured by `client_config` to a server adding `headers` to
+    /// its session response.
+    async fn connect_with_response_headers<F>(
+        client_config: F,
+        headers: Vec<(String, String)>,
+    ) -> Result<Connection, ConnectingError>
+    where
+        F: FnOnce(
+            crate::config::ClientConfigBuilder<crate::config::WantsTransportConfigClient>,
+        )
+            -> crate::config::ClientConfigBuilder<crate::config::WantsTransportConfigClient>,
+    {
+        let certificate = test_utils::certificate();
+        let server =
+            Endpoint::server(test_utils::server_config(certificate.clone()).build()).unwrap();
+        let client =
+            Endpoint::client(client_config(test_utils::client_config(&certificate)).build())
+                .unwrap();
+
+        let (_, connected) = tokio::join!(
+            async {
+                let mut session_request = server.accept().await.await.unwrap();
+                for (key, value) in headers {
+                    session_request.add_response_header(key, value);
+                }
+                session_request.accept().await
+            },
+            client.connect(test_utils::url(&server)),
+        );
+
+        connected
+    }
+
+    #[tokio::test]
+    async fn response_header_limits() {
+        let fields = |count: usize, size: usize| {
+            (0..count)
+                .map(|i| (format!("x-field-{i}"), "a".repeat(size)))
+                .collect::<Vec<_>>()
+        };
+
+        // Within the default limits.
+        assert!(
+            connect_with_response_headers(|config| config, fields(8, 1024))
+                .await
+                .is_ok()
+        );
+
+        // The HEADERS frame is larger than the limit: rejected before being decoded.
+        assert!(matches!(
+            connect_with_response_headers(
+                |config| config.max_response_header_size(1024),
+                fields(4, 1024)
+            )
+            .await,
+            Err(ConnectingError::ResponseHeadersTooLarge)
+        ));
+
+        // The default limit is 64 KiB, here exceeded once decoded only.
+        assert!(matches!(
+            connect_with_response_headers(|config| config, fields(80, 1024)).await,
+            Err(ConnectingError::ResponseHeadersTooLarge)
+        ));
+
+        // Too many fields, in a small frame (the response also has `:status` and the
+        // draft header field).
+        assert!(matches!(
+            connect_with_response_headers(
+                |config| config.max_response_header_count(5),
+                fields(4, 1)
+            )
+            .await,
+            Err(ConnectingError::ResponseHeadersTooLarge)
+        ));
+        assert!(connect_with_response_headers(
+            |config| config.max_response_header_count(6),
+            fields(4, 1)
+        )
+        .await
+        .is_ok());
+    }
+
+    /// Accepts the session request of `incoming_session`, if any.
+    fn accept_session_request(incoming_session: IncomingSession) {
+        tokio::spawn(async move {
+            if let Ok(session_request) = incoming_session.await {
+                let _connection = session_request.accept().await;
+                std::future::pending::<()>().await;
+            }
+        });
+    }
+
+    #[tokio::test]
+    async fn driver_event_budget() {
+        let certificate = test_utils::certificate();
+        let peers = test_utils::connect_with(
+            test_utils::server_config(certificate.clone())
+                .driver_event_budget(0)
+                .build(),
+            test_utils::client_config(&certificate).build(),
+        )
+        .await;
+
+        // With a budget of 1 event, the driver yields before each event, but still
+        // processes all of them.
+        for _ in 0..16 {
+            let mut stream = peers
+                .client_connection
+                .open_uni()
+                .await
+                .unwrap()
+                .await
+                .unwrap();
+            stream.write_all(b"data").await.unwrap();
+            stream.finish().await.unwrap();
+            peers.client_connection.send_datagram(b"datagram").unwrap();
+        }
+
+        for _ in 0..16 {
+            let mut stream = peers.server_connection.accept_uni().await.unwrap();
+            let mut buffer = [0; 4];
+            stream.read_exact(&mut buffer).await.unwrap();
+            assert_eq!(&buffer, b"data");
+        }
+    }
+
+    #[tokio::test]
+    async fn events() {
+        let certificate = test_utils::certificate();
+        let server =
+            Endpoint::server(test_utils::server_config(certificate.clone()).build()).unwrap();
+        let client = Endpoint::client(test_utils::client_config(&certificate).build()).unwrap();
+        let mut events = server.events();
+
+        let (server_connection, client_connection) = tokio::join!(
+            async { server.accept().await.await.unwrap().accept().await.unwrap() },
+            async { client.connect(test_utils::url(&server)).await.unwrap() },
+        );
+        let client_address = client.local_addr().unwrap();
+
+        assert!(matches!(
+            events.next().await.unwrap(),
+            EndpointEvent::ConnectionAccepted { remote_address, stable_id }
+                if remote_address == client_address
+                    && stable_id == server_connection.stable_id()
+        ));
+
+        client_connection.close(wtransport_proto::varint::VarInt::from_u32(0), b"");
+        assert!(matches!(
+            events.next().await.unwrap(),
+            EndpointEvent::ConnectionClosed {
+                stable_id,
+                reason: ConnectionError::ApplicationClosed(_),
+                ..
+            } if stable_id == server_connection.stable_id()
+        ));
+
+        // A bare QUIC connection closed before its SETTINGS.
+        let (_quic_endpoint, quic_connection) =
+            test_utils::quic_connect(&server, &certificate).await;
+        let incoming_session = server.accept().await;
+        quic_connection.close(quinn::VarInt::from_u32(0), b"");
+        assert!(incoming_session.await.is_err());
+        assert!(matches!(
+            events.next().await.unwrap(),
+            EndpointEvent::HandshakeFailed {
+                error: ConnectionError::ApplicationClosed(_),
+                ..
+            }
+        ));
+        assert_eq!(events.missed(), 0);
+    }
+
+    #[tokio::test]
+    async fn quota_exceeded_event() {
+        let certificate = test_utils::certificate();
+        let quotas = Quotas::new(crate::quota::QuotaLimits::new().max_sessions(1));
+        let server = Endpoint::server(
+            test_utils::server_config(certificate.clone())
+                .quotas(quotas)
+                .build(),
+        )
+        .unwrap();
+        let client = Endpoint::client(test_utils::client_config(&certificate).build()).unwrap();
+        let mut events = server.events();
+
+        let accept = |incoming_session: IncomingSession| async move {
+            let mut session_request = incoming_session.await.unwrap();
+            session_request.set_principal("alice");
+            session_request.accept().await
+        };
+
+        let (accepted, connected) = tokio::join!(
+            async { accept(server.accept().await).await },
+            client.connect(test_utils::url(&server)),
+        );
+        let (_server_connection, _client_connection) = (accepted.unwrap(), connected.unwrap());
+        assert!(matches!(
+            events.next().await.unwrap(),
+            EndpointEvent::ConnectionAccepted { .. }
+        ));
+
+        let (accepted, connected) = tokio::join!(
+            async { accept(server.accept().await).await },
+            client.connect(test_utils::url(&server)),
+        );
+        assert!(matches!(accepted, Err(ConnectionError::QuotaExceeded)));
+        assert!(connected.is_err());
+        assert!(matches!(
+            events.next().await.unwrap(),
+            EndpointEvent::QuotaExceeded { principal, .. } if principal == "alice"
+        ));
+    }
+
+    /// Defers the session request of a client with `session_request` timeout, returning the
+    /// outcome on (server, client).
+    async fn connect_deferred<F>(
+        authorization: F,
+        deferral: Deferral,
+        session_request: Duration,
+    ) -> (
+        Result<Option<Connection>, ConnectionError>,
+        Result<Connection, ConnectingError>,
+    )
+    where
+        F: Future<Output = Authorization>,
+    {
+        let (server, client) = server_and_client();
+        let options = ConnectOptions::new()
+            .timeouts(Timeouts::default().with_session_request(Some(session_request)));
+
+        tokio::join!(
+            async {
+                let session_request = server.accept().await.await.unwrap();
+                session_request
+                    .accept_deferred(authorization, deferral)
+                    .await
+            },
+            client.connect_with_options(test_utils::url(&server), &options),
+        )
+    }
+
+    #[tokio::test]
+    async fn accept_deferred() {
+        // The progress keeps the client waiting past its session request timeout.
+        let authorization = async {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            Authorization::Accept
+        };
+        let deferral = Deferral::new().progress(Duration::from_millis(50));
+
+        let (accepted, connected) =
+            connect_deferred(authorization, deferral, Duration::from_millis(150)).await;
+        assert!(accepted.unwrap().is_some());
+        assert!(connected.is_ok());
+    }
+
+    #[tokio::test]
+    async fn accept_deferred_rejected() {
+        for code in [401, 200] {
+            let authorization = async move { Authorization::Reject(code) };
+
+            let (accepted, connected) =
+                connect_deferred(authorization, Deferral::new(), Duration::from_secs(5)).await;
+            assert!(accepted.unwrap().is_none());
+            assert!(matches!(
+                connected,
+                Err(ConnectingError::SessionRejected(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn accept_deferred_timed_out() {
+        let deferral = Deferral::new().timeout(Duration::from_millis(100));
+
+        let (accepted, connected) =
+            connect_deferred(std::future::pending(), deferral, Duration::from_secs(5)).await;
+        assert!(matches!(accepted, Err(ConnectionError::TimedOut)));
+        assert!(matches!(
+            connected,
+            Err(ConnectingError::SessionRejected(_))
+        ));
+
+        // Without progress, the client gives up first.
+        let (accepted, connected) = connect_deferred(
+            std::future::pending(),
+            Deferral::new(),
+            Duration::from_millis(100),
+        )
+        .await;
+        assert!(accepted.is_err());
+        assert!(matches!(
+            connected,
+            Err(ConnectingError::ConnectionError(
+                ConnectionError::TimedOut,
+                _
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn too_many_interim_responses() {
+        let deferral = Deferral::new().progress(Duration::from_millis(1));
+
+        let (accepted, connected) =
+            connect_deferred(std::future::pending(), deferral, Duration::from_secs(5)).await;
+        assert!(accepted.is_err());
+        assert!(matches!(
+            connected,
+            Err(ConnectingError::ConnectionError(ConnectionError::LocalH3Error(error), _))
+                if error.violation().rule() == "Too many interim responses"
+        ));
+    }
+}
@@ -1,11 +1,20 @@
+use crate::connection::ConnectTimings;
+use crate::connection::IncomingHalf;
+use crate::connection::OutgoingHalf;
 use crate::driver::utils::varint_q2w;
 use crate::driver::DriverError;
+use crate::driver::Violation;
+use crate::framing::ReadMessageError;
 use std::fmt::Display;
+use std::net::SocketAddr;
 use wtransport_proto::error::ErrorCode;
+use wtransport_proto::frame::FrameKind;
+use wtransport_proto::ids::StreamId;
 use wtransport_proto::varint::VarInt;
 
 /// An enumeration representing various errors that can occur during a WebTransport connection.
-#[derive(thiserror::Error, Debug)]
+#[derive(thiserror::Error, Clone, Debug)]
+#[non_exhaustive]
 pub enum ConnectionError {
     /// The connection was aborted by the peer (protocol level).
     #[error("Connection aborted by peer: {0}")]
@@ -30,6 +39,23 @@ pub enum ConnectionError {
     /// The connection was closed because a QUIC protocol error.
     #[error("QUIC protocol error")]
     QuicProto,
+
+    /// The session request has been handed to the handler registered for its extended
+    /// CONNECT protocol (see
+    /// [`ServerConfigBuilder::extended_connect_handler`](crate::config::ServerConfigBuilder::extended_connect_handler)).
+    #[error("Session request handed to the '{0}' protocol handler")]
+    HandedOff(String),
+
+    /// The session request has been rejected, because its principal exceeded its quota
+    /// (see [`Quotas`](crate::quota::Quotas)).
+    #[error("Session quota exceeded")]
+    QuotaExceeded,
+
+    /// The connection has been refused, because the memory budget of the endpoint is
+    /// exhausted, or was still exhausted once the SETTINGS exchange timed out
+    /// (see [`MemoryBudget`](crate::budget::MemoryBudget)).
+    #[error("Memory budget exhausted")]
+    MemoryBudgetExceeded,
 }
 
 impl ConnectionError {
@@ -38,7 +64,7 @@ impl ConnectionError {
         quic_connection: &quinn::Connection,
     ) -> Self {
         match driver_error {
-            DriverError::Proto(error_code) => Self::local_h3_error(error_code),
+            DriverError::Proto(violation) => Self::local_h3_error(violation, quic_connection),
             DriverError::NotConnected => Self::no_connect(quic_connection),
         }
     }
@@ -50,17 +76,120 @@ impl ConnectionError {
             .into()
     }
 
-    pub(crate) fn local_h3_error(error_code: ErrorCode) -> Self {
-        ConnectionError::LocalH3Error(H3Error { code: error_code })
+    pub(crate) fn local_h3_error(
+        violation: Violation,
+        quic_connection: &quinn::Connection,
+    ) -> Self {
+        ConnectionError::LocalH3Error(H3Error {
+            violation: ProtocolViolation {
+                peer_address: quic_connection.remote_address(),
+                error_code: violation.error_code,
+                rule: violation.rule,
+                stream_id: violation.stream_id,
+                frame_kind: violation.frame_kind,
+                phase: violation.phase,
+            },
+        })
+    }
+
+    /// Returns the reason given by the peer application, if it closed the connection.
+    pub fn application_close(&self) -> Option<&ApplicationClose> {
+        match self {
+            ConnectionError::ApplicationClosed(close) => Some(close),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the connection was closed (or aborted) locally.
+    pub fn is_local(&self) -> bool {
+        matches!(
+            self,
+            ConnectionError::LocallyClosed | ConnectionError::LocalH3Error(_)
+        )
+    }
+
+    /// Returns how a connection should be closed to propagate this error.
+    ///
+    /// It is `Some` when the error carries an error code: the HTTP3 error code
+    /// of a [local protocol violation](Self::LocalH3Error), or the application error code
+    /// of a [peer close](Self::ApplicationClosed). The action can be applied with
+    /// [`Connection::close_with`](crate::Connection::close_with), e.g., on a related connection
+    /// when proxying.
+    pub fn close_action(&self) -> Option<CloseAction> {
+        match self {
+            ConnectionError::LocalH3Error(h3_error) => Some(CloseAction {
+                error_code: h3_error.violation.error_code(),
+                reason: h3_error.violation.rule.as_bytes().into(),
+            }),
+            ConnectionError::ApplicationClosed(close) => Some(CloseAction {
+                error_code: close.code,
+                reason: close.reason.clone(),
+            }),
+            ConnectionError::ConnectionClosed(_)
+            | ConnectionError::LocallyClosed
+            | ConnectionError::TimedOut
+            | ConnectionError::QuicProto
+            | ConnectionError::HandedOff(_)
+            | ConnectionError::QuotaExceeded
+            | ConnectionError::MemoryBudgetExceeded => None,
+        }
+    }
+}
+
+/// The error code and reason to close a connection with.
+///
+/// See [`ConnectionError::close_action`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CloseAction {
+    error_code: VarInt,
+    reason: Box<[u8]>,
+}
+
+impl CloseAction {
+    /// Returns the error code.
+    pub fn error_code(&self) -> VarInt {
+        self.error_code
+    }
+
+    /// Returns the reason.
+    pub fn reason(&self) -> &[u8] {
+        &self.reason
     }
 }
 
 /// An enumeration representing various errors that can occur during a WebTransport client connecting.
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum ConnectingError {
     /// URL provided for connection is not valid.
     #[error("Invalid URL: {0}")]
-    InvalidUrl(String),
+    InvalidUrl(InvalidUrl),
+
+    /// The TLS server name is not a valid DNS name nor IP address
+    /// (see [`ConnectOptions::server_name`](crate::endpoint::ConnectOptions::server_name)).
+    #[error("Invalid server name: '{0}'")]
+    InvalidServerName(String),
+
+    /// An application protocol offered is not valid
+    /// (see [`ConnectOptions::subprotocols`](crate::endpoint::ConnectOptions::subprotocols)).
+    #[error("Invalid subprotocol: '{0}'")]
+    InvalidSubprotocol(String),
+
+    /// The server selected an application protocol which has not been offered.
+    #[error("Server selected an unexpected subprotocol: '{0}'")]
+    UnexpectedSubprotocol(String),
+
+    /// The server selected a compression codec which has not been offered
+    /// (see [`ConnectOptions::compression`](crate::endpoint::ConnectOptions::compression)).
+    #[cfg(feature = "compression")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+    #[error("Server selected an unexpected codec: '{0}'")]
+    UnexpectedCodec(String),
+
+    /// The headers of the server's response exceed the limits
+    /// (see [`ClientConfigBuilder::max_response_header_size`](crate::config::ClientConfigBuilder::max_response_header_size)).
+    #[error("Server response headers too large")]
+    ResponseHeadersTooLarge,
 
     /// Failure during DNS resolution.
     #[error("Cannot resolve domain: {0}")]
@@ -71,27 +200,168 @@ pub enum ConnectingError {
     DnsNotFound,
 
     /// Connection error during handshaking.
-    #[error(transparent)]
-    ConnectionError(ConnectionError),
+    #[error("{0}")]
+    ConnectionError(#[source] ConnectionError, ConnectTimings),
 
     /// Request rejected.
     #[error("Server rejected WebTransport session request")]
-    SessionRejected,
+    SessionRejected(ConnectTimings),
+
+    /// The connection has not been established before the deadline
+    /// (see [`Endpoint::connect_with_report`](crate::Endpoint::connect_with_report)).
+    #[error("Connection deadline exceeded")]
+    DeadlineExceeded(ConnectTimings),
+}
+
+/// The reason a WebTransport URL is not valid.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InvalidUrl {
+    /// The URL has no scheme (e.g., `example.com/path`).
+    #[error("missing scheme")]
+    MissingScheme,
+
+    /// The scheme is not `https`.
+    #[error("scheme must be 'https', not '{0}'")]
+    SchemeNotHttps(String),
+
+    /// The URL contains user information (e.g., `user:password@`).
+    #[error("user information is not allowed")]
+    UserInfo,
+
+    /// The URL contains a fragment (e.g., `#section`).
+    #[error("fragment is not allowed")]
+    Fragment,
+
+    /// The host is not valid (e.g., malformed international domain name).
+    #[error("invalid host: {0}")]
+    InvalidHost(String),
+
+    /// The port is not valid.
+    #[error("invalid port")]
+    InvalidPort,
+
+    /// The URL is malformed.
+    #[error("{0}")]
+    Malformed(String),
+}
+
+impl InvalidUrl {
+    pub(crate) fn with_parse_error(error: url::ParseError) -> Self {
+        match error {
+            url::ParseError::RelativeUrlWithoutBase => InvalidUrl::MissingScheme,
+            url::ParseError::InvalidPort => InvalidUrl::InvalidPort,
+            url::ParseError::EmptyHost
+            | url::ParseError::IdnaError
+            | url::ParseError::InvalidIpv4Address
+            | url::ParseError::InvalidIpv6Address
+            | url::ParseError::InvalidDomainCharacter => InvalidUrl::InvalidHost(error.to_string()),
+            _ => InvalidUrl::Malformed(error.to_string()),
+        }
+    }
 }
 
 impl ConnectingError {
+    /// Returns the timings of the phases completed before the failure.
+    ///
+    /// Returns `None` if the failure occurred before the QUIC handshake
+    /// (e.g., invalid URL or DNS resolution).
+    pub fn timings(&self) -> Option<&ConnectTimings> {
+        match self {
+            ConnectingError::ConnectionError(_, timings) => Some(timings),
+            ConnectingError::SessionRejected(timings) => Some(timings),
+            ConnectingError::DeadlineExceeded(timings) => Some(timings),
+            _ => None,
+        }
+    }
+
+    /// Returns the connection error that made the handshake fail, if any.
+    pub fn as_connection_error(&self) -> Option<&ConnectionError> {
+        match self {
+            ConnectingError::ConnectionError(connection_error, _) => Some(connection_error),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn connection_error(connection_error: ConnectionError) -> Self {
+        ConnectingError::ConnectionError(connection_error, ConnectTimings::default())
+    }
+
+    pub(crate) fn session_rejected() -> Self {
+        ConnectingError::SessionRejected(ConnectTimings::default())
+    }
+
     pub(crate) fn with_no_connection(quic_connection: &quinn::Connection) -> Self {
-        ConnectingError::ConnectionError(
+        Self::connection_error(
             quic_connection
                 .close_reason()
                 .expect("QUIC connection is still alive on close-cast")
                 .into(),
         )
     }
+
+    pub(crate) fn with_timings(mut self, timings: ConnectTimings) -> Self {
+        match &mut self {
+            ConnectingError::ConnectionError(_, current) => *current = timings,
+            ConnectingError::SessionRejected(current) => *current = timings,
+            _ => {}
+        }
+
+        self
+    }
+}
+
+/// An error that arise when all the candidates of
+/// [`Endpoint::connect_any`](crate::Endpoint::connect_any) fail.
+#[derive(thiserror::Error, Debug)]
+#[error("All {} connection candidates failed", .failures.len())]
+pub struct ConnectAnyError {
+    failures: Vec<(String, ConnectingError)>,
+}
+
+impl ConnectAnyError {
+    pub(crate) fn new(failures: Vec<(String, ConnectingError)>) -> Self {
+        Self { failures }
+    }
+
+    /// Returns the failure of each candidate URL, in the order they failed.
+    pub fn failures(&self) -> &[(String, ConnectingError)] {
+        &self.failures
+    }
+}
+
+/// An error that arise when joining halves of different connections,
+/// see [`IncomingHalf::reunite`](crate::connection::IncomingHalf::reunite).
+#[derive(thiserror::Error)]
+#[error("Halves do not belong to the same connection")]
+pub struct ReuniteError {
+    incoming: IncomingHalf,
+    outgoing: OutgoingHalf,
+}
+
+impl ReuniteError {
+    pub(crate) fn new(incoming: IncomingHalf, outgoing: OutgoingHalf) -> Self {
+        Self { incoming, outgoing }
+    }
+
+    /// Returns the halves which failed to be joined.
+    pub fn into_halves(self) -> (IncomingHalf, OutgoingHalf) {
+        (self.incoming, self.outgoing)
+    }
+}
+
+impl std::fmt::Debug for ReuniteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReuniteError")
+            .field("incoming", &self.incoming.connection().stable_id())
+            .field("outgoing", &self.outgoing.connection().stable_id())
+            .finish()
+    }
 }
 
 /// An error that arise from writing to a stream.
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum StreamWriteError {
     /// Connection has been dropped.
     #[error("Not connected")]
@@ -108,6 +378,7 @@ pub enum StreamWriteError {
 
 /// An error that arise from reading from a stream.
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum StreamReadError {
     /// Connection has been dropped.
     #[error("Not connected")]
@@ -122,8 +393,29 @@ pub enum StreamReadError {
     QuicProto,
 }
 
+impl StreamWriteError {
+    /// Returns the error code of the peer, if it stopped the stream.
+    pub fn stopped_code(&self) -> Option<VarInt> {
+        match self {
+            StreamWriteError::Stopped(code) => Some(*code),
+            _ => None,
+        }
+    }
+}
+
+impl StreamReadError {
+    /// Returns the error code of the peer, if it reset the stream.
+    pub fn reset_code(&self) -> Option<VarInt> {
+        match self {
+            StreamReadError::Reset(code) => Some(*code),
+            _ => None,
+        }
+    }
+}
+
 /// An error that arise from reading from a stream.
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum StreamReadExactError {
     /// The stream finished before all bytes were read.
     #[error("Stream finished too early")]
@@ -136,22 +428,34 @@ pub enum StreamReadExactError {
 
 /// An error that arise from sending a datagram.
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum SendDatagramError {
     /// Connection has been dropped.
     #[error("Not connected")]
     NotConnected,
 
-    /// The peer does not support receiving datagram frames.
+    /// The peer does not support datagrams: it did not negotiate HTTP3 datagrams, or does
+    /// not accept QUIC datagram frames.
+    ///
+    /// See [`Connection::datagram_support`](crate::Connection::datagram_support).
     #[error("Peer does not support datagrams")]
     UnsupportedByPeer,
 
     /// The datagram is larger than the connection can currently accommodate.
     #[error("Datagram payload too large")]
     TooLarge,
+
+    /// The datagram does not fit in the free space of the QUIC datagram send buffer.
+    ///
+    /// Only reported by [`Connection::send_datagram_batch`](crate::Connection::send_datagram_batch),
+    /// which does not drop the datagrams already queued to make room.
+    #[error("Datagram send buffer full")]
+    BufferFull,
 }
 
 /// An error that arise when opening a new stream.
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum StreamOpeningError {
     /// Connection has been dropped.
     #[error("Not connected")]
@@ -160,15 +464,125 @@ pub enum StreamOpeningError {
     /// The peer refused the stream, stopping it during initialization.
     #[error("Opening stream refused")]
     Refused,
+
+    /// Too many opens are already queued, waiting for the peer's stream credit.
+    ///
+    /// See [`ServerConfigBuilder::stream_open_queue`](crate::config::ServerConfigBuilder::stream_open_queue).
+    #[error("Stream open queue full")]
+    QueueFull,
+
+    /// The stream has not been opened in time (see [`OpeningBiStream::with_timeout`]).
+    ///
+    /// [`OpeningBiStream::with_timeout`]: crate::stream::OpeningBiStream::with_timeout
+    #[error("Opening stream timed out")]
+    TimedOut,
+}
+
+/// An error that arise from opening a stream within a deadline
+/// (see [`Connection::open_bi_now`](crate::Connection::open_bi_now)).
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum OpenStreamError {
+    /// The connection is no longer available.
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+
+    /// The stream cannot be opened (e.g., [in time](StreamOpeningError::TimedOut)).
+    #[error(transparent)]
+    Opening(#[from] StreamOpeningError),
+}
+
+/// An error that arise from writing to an [`ExpiringSendStream`](crate::stream::ExpiringSendStream).
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum ExpiringWriteError {
+    /// The connection is no longer available.
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+
+    /// A new stream cannot be opened.
+    #[error(transparent)]
+    Opening(#[from] StreamOpeningError),
+
+    /// The stream cannot be written.
+    #[error(transparent)]
+    Write(#[from] StreamWriteError),
+}
+
+/// An error that arise from forwarding data between streams
+/// (see [`copy_spliced`](crate::io::copy_spliced)).
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum SpliceError {
+    /// The source stream cannot be read.
+    #[error(transparent)]
+    Read(#[from] StreamReadError),
+
+    /// The destination stream cannot be written.
+    #[error(transparent)]
+    Write(#[from] StreamWriteError),
+}
+
+/// An error that arise from opening, or accepting, a stream with a preamble
+/// (see [`Connection::open_bi_with_preamble`](crate::Connection::open_bi_with_preamble)).
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum PreambleError {
+    /// The connection is no longer available.
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+
+    /// The stream cannot be opened.
+    #[error(transparent)]
+    Opening(#[from] StreamOpeningError),
+
+    /// The preamble cannot be sent.
+    #[error(transparent)]
+    Write(#[from] StreamWriteError),
+
+    /// The preamble cannot be received (e.g., the stream finished before it).
+    #[error(transparent)]
+    Read(#[from] StreamReadExactError),
+
+    /// The preamble is larger than the allowed size.
+    #[error("preamble too large")]
+    TooLarge,
+
+    /// The preamble cannot be encoded or decoded.
+    #[cfg(feature = "serde")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+    #[error("codec error: {0}")]
+    Codec(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl From<ReadMessageError> for PreambleError {
+    fn from(error: ReadMessageError) -> Self {
+        match error {
+            ReadMessageError::Read(error) => PreambleError::Read(error),
+            ReadMessageError::TooLarge => PreambleError::TooLarge,
+        }
+    }
 }
 
 /// Reason given by an application for closing the connection
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ApplicationClose {
     code: VarInt,
     reason: Box<[u8]>,
 }
 
+impl ApplicationClose {
+    /// Returns the application error code.
+    pub fn code(&self) -> VarInt {
+        self.code
+    }
+
+    /// Returns the reason.
+    pub fn reason(&self) -> &[u8] {
+        &self.reason
+    }
+}
+
 impl Display for ApplicationClose {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if self.reason.is_empty() {
@@ -184,7 +598,7 @@ impl Display for ApplicationClose {
 }
 
 /// Reason given by the transport for closing the connection.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ConnectionClose(quinn::ConnectionClose);
 
 impl Display for ConnectionClose {
@@ -194,14 +608,97 @@ impl Display for ConnectionClose {
 }
 
 /// A struct representing an error in the HTTP3 layer.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct H3Error {
-    code: ErrorCode,
+    violation: ProtocolViolation,
+}
+
+impl H3Error {
+    /// Returns the details of the protocol violation committed by the peer.
+    pub fn violation(&self) -> &ProtocolViolation {
+        &self.violation
+    }
 }
 
 impl Display for H3Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.code.fmt(f)
+        self.violation.error_code.fmt(f)?;
+        write!(f, " ({}", self.violation.rule)?;
+
+        if let Some(phase) = self.violation.phase {
+            write!(f, ", during {}", phase)?;
+        }
+
+        write!(f, ")")
+    }
+}
+
+/// Details of an HTTP3 protocol violation committed by the peer.
+///
+/// The same details are logged (at `WARN` level) when the connection is closed.
+#[derive(Clone, Debug)]
+pub struct ProtocolViolation {
+    peer_address: SocketAddr,
+    error_code: ErrorCode,
+    rule: &'static str,
+    stream_id: Option<StreamId>,
+    frame_kind: Option<FrameKind>,
+    phase: Option<ProtocolPhase>,
+}
+
+impl ProtocolViolation {
+    /// Returns the address of the peer.
+    pub fn peer_address(&self) -> SocketAddr {
+        self.peer_address
+    }
+
+    /// Returns the HTTP3 error code the connection was closed with.
+    pub fn error_code(&self) -> VarInt {
+        self.error_code.to_code()
+    }
+
+    /// Returns a description of the violated rule.
+    pub fn rule(&self) -> &'static str {
+        self.rule
+    }
+
+    /// Returns the identifier of the stream of the violation, if any.
+    pub fn stream_id(&self) -> Option<VarInt> {
+        self.stream_id.map(StreamId::into_varint)
+    }
+
+    /// Returns the type of the offending frame (as encoded on the wire), if any.
+    pub fn frame_kind(&self) -> Option<VarInt> {
+        self.frame_kind.map(FrameKind::id)
+    }
+
+    /// Returns the protocol phase the violation happened in, if known.
+    pub fn phase(&self) -> Option<ProtocolPhase> {
+        self.phase
+    }
+}
+
+/// A phase of the HTTP3 protocol of a WebTransport connection.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProtocolPhase {
+    /// Exchange of the SETTINGS of both endpoints.
+    Settings,
+
+    /// Exchange of the CONNECT request and its response.
+    SessionEstablishment,
+
+    /// The WebTransport session is established.
+    Session,
+}
+
+impl Display for ProtocolPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolPhase::Settings => write!(f, "settings exchange"),
+            ProtocolPhase::SessionEstablishment => write!(f, "session establishment"),
+            ProtocolPhase::Session => write!(f, "established session"),
+        }
     }
 }
 
@@ -225,3 +722,85 @@ impl From<quinn::ConnectionError> for ConnectionError {
         }
     }
 }
+
+impl From<quinn::WriteError> for StreamWriteError {
+    fn from(error: quinn::WriteError) -> Self {
+        match error {
+            quinn::WriteError::Stopped(code) => StreamWriteError::Stopped(varint_q2w(code)),
+            quinn::WriteError::ConnectionLost(_) => StreamWriteError::NotConnected,
+            quinn::WriteError::UnknownStream => StreamWriteError::QuicProto,
+            quinn::WriteError::ZeroRttRejected => StreamWriteError::QuicProto,
+        }
+    }
+}
+
+impl StreamWriteError {
+    pub(crate) fn from_quinn_stopped(error: quinn::StoppedError) -> Self {
+        match error {
+            quinn::StoppedError::ConnectionLost(_) => StreamWriteError::NotConnected,
+            quinn::StoppedError::UnknownStream => StreamWriteError::QuicProto,
+            quinn::StoppedError::ZeroRttRejected => StreamWriteError::QuicProto,
+        }
+    }
+}
+
+impl From<quinn::ReadError> for StreamReadError {
+    fn from(error: quinn::ReadError) -> Self {
+        match error {
+            quinn::ReadError::Reset(code) => StreamReadError::Reset(varint_q2w(code)),
+            quinn::ReadError::ConnectionLost(_) => StreamReadError::NotConnected,
+            quinn::ReadError::UnknownStream => StreamReadError::QuicProto,
+            quinn::ReadError::IllegalOrderedRead => StreamReadError::QuicProto,
+            quinn::ReadError::ZeroRttRejected => StreamReadError::QuicProto,
+        }
+    }
+}
+
+impl StreamReadExactError {
+    pub(crate) fn from_quinn(error: quinn::ReadExactError) -> Self {
+        match error {
+            quinn::ReadExactError::FinishedEarly => StreamReadExactError::FinishedEarly,
+            quinn::ReadExactError::ReadError(read) => StreamReadExactError::Read(read.into()),
+        }
+    }
+}
+
+impl SendDatagramError {
+    pub(crate) fn from_quinn(error: quinn::SendDatagramError) -> Self {
+        match error {
+            // Datagrams are always enabled locally by this crate.
+            quinn::SendDatagramError::UnsupportedByPeer | quinn::SendDatagramError::Disabled => {
+                SendDatagramError::UnsupportedByPeer
+            }
+            quinn::SendDatagramError::TooLarge => SendDatagramError::TooLarge,
+            quinn::SendDatagramError::ConnectionLost(_) => SendDatagramError::NotConnected,
+        }
+    }
+}
+
+#[cfg(feature = "quinn-compat")]
+#[cfg_attr(docsrs, doc(cfg(feature = "quinn-compat")))]
+impl From<quinn::StoppedError> for StreamWriteError {
+    #[inline(always)]
+    fn from(error: quinn::StoppedError) -> Self {
+        StreamWriteError::from_quinn_stopped(error)
+    }
+}
+
+#[cfg(feature = "quinn-compat")]
+#[cfg_attr(docsrs, doc(cfg(feature = "quinn-compat")))]
+impl From<quinn::ReadExactError> for StreamReadExactError {
+    #[inline(always)]
+    fn from(error: quinn::ReadExactError) -> Self {
+        StreamReadExactError::from_quinn(error)
+    }
+}
+
+#[cfg(feature = "quinn-compat")]
+#[cfg_attr(docsrs, doc(cfg(feature = "quinn-compat")))]
+impl From<quinn::SendDatagramError> for SendDatagramError {
+    #[inline(always)]
+    fn from(error: quinn::SendDatagramError) -> Self {
+        SendDatagramError::from_quinn(error)
+    }
+}
@@ -0,0 +1,560 @@
+use crate::driver::streams::QuicRecvStream;
+use crate::driver::Violation;
+use crate::stream::RecvStream;
+use bytes::Bytes;
+use ring::rand::SecureRandom;
+use ring::rand::SystemRandom;
+use std::borrow::Cow;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::debug;
+use tracing::trace;
+use wtransport_proto::error::ErrorCode;
+use wtransport_proto::frame::Frame;
+use wtransport_proto::ids::StreamId;
+use wtransport_proto::stream_header::StreamHeader;
+use wtransport_proto::varint::VarInt;
+
+/// What to do with HTTP3 frames, or unidirectional streams, of a type this implementation
+/// does not know.
+///
+/// Reserved types (sent by peers to exercise the extensibility of HTTP3, also known as
+/// *grease*) are handled as unknown types.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UnknownPolicy {
+    /// Ignores them, as required by HTTP3.
+    ///
+    /// Unknown streams are stopped with `H3_STREAM_CREATION_ERROR`.
+    #[default]
+    Ignore,
+
+    /// Passes them to the application (see [`Connection::accept_unknown`](crate::Connection::accept_unknown)).
+    ///
+    /// If the application does not keep up, they are dropped (as with [`UnknownPolicy::Ignore`]),
+    /// and counted in [`UnknownStats::dropped`].
+    Surface,
+
+    /// Closes the connection, with `H3_FRAME_UNEXPECTED` for frames and
+    /// `H3_STREAM_CREATION_ERROR` for streams.
+    ///
+    /// **Note**: this is not compliant with HTTP3, and it is meant for testing peers.
+    Close,
+}
+
+/// Reserved settings, frame types and stream types (also known as *grease*) sent to the peer,
+/// checking that it ignores what it does not know, as required by HTTP3.
+///
+/// Reserved types and values are picked at random. By default, nothing is sent.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Grease {
+    settings: bool,
+    frames: bool,
+    uni_streams: bool,
+    interval: Option<Duration>,
+}
+
+impl Grease {
+    /// Maximum payload size of reserved frames and streams.
+    const MAX_PAYLOAD_SIZE: usize = 16;
+
+    /// Creates a configuration sending nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a configuration sending a reserved setting, reserved frames and a stream
+    /// of reserved type.
+    pub fn all() -> Self {
+        Self::new().settings(true).frames(true).uni_streams(true)
+    }
+
+    /// Sets whether a reserved setting is sent in SETTINGS.
+    pub fn settings(mut self, enabled: bool) -> Self {
+        self.settings = enabled;
+        self
+    }
+
+    /// Sets whether reserved frames are sent on the control stream (after SETTINGS),
+    /// and on the session stream (before the HEADERS of the request, or of the response).
+    pub fn frames(mut self, enabled: bool) -> Self {
+        self.frames = enabled;
+        self
+    }
+
+    /// Sets whether a unidirectional stream of reserved type is opened (after SETTINGS).
+    ///
+    /// **Note**: the stream takes up stream credit from the peer.
+    pub fn uni_streams(mut self, enabled: bool) -> Self {
+        self.uni_streams = enabled;
+        self
+    }
+
+    /// Sends a reserved frame on the control stream, and opens a stream of reserved type,
+    /// every `interval` (if enabled).
+    ///
+    /// By default, they are only sent once, after SETTINGS.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Returns the interval reserved frames and streams are sent at, if repeated.
+    #[inline(always)]
+    pub(crate) fn repeat_interval(&self) -> Option<Duration> {
+        self.interval.filter(|_| self.frames || self.uni_streams)
+    }
+
+    /// Returns a reserved setting (identifier and value) to send, if enabled.
+    pub(crate) fn setting(&self) -> Option<(VarInt, VarInt)> {
+        self.settings
+            .then(|| (random_exercise_id(), VarInt::from_u32(random_u32())))
+    }
+
+    /// Returns a reserved frame to send, if enabled.
+    pub(crate) fn frame(&self) -> Option<Frame<'static>> {
+        self.frames
+            .then(|| Frame::new_exercise(random_exercise_id(), Cow::Owned(random_payload())))
+    }
+
+    /// Returns the header and payload of a stream of reserved type to open, if enabled.
+    pub(crate) fn uni_stream(&self) -> Option<(StreamHeader, Vec<u8>)> {
+        self.uni_streams.then(|| {
+            (
+                StreamHeader::new_exercise(random_exercise_id()),
+                random_payload(),
+            )
+        })
+    }
+}
+
+fn random_u32() -> u32 {
+    let mut bytes = [0; 4];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("System random available");
+    u32::from_le_bytes(bytes)
+}
+
+/// Returns a random reserved identifier (`0x1f * N + 0x21`), shared by settings,
+/// frame types and stream types.
+fn random_exercise_id() -> VarInt {
+    VarInt::try_from_u64(0x1f * u64::from(random_u32()) + 0x21)
+        .expect("Reserved identifier fits in a varint")
+}
+
+fn random_payload() -> Vec<u8> {
+    let mut payload = vec![0; random_u32() as usize % (Grease::MAX_PAYLOAD_SIZE + 1)];
+    SystemRandom::new()
+        .fill(&mut payload)
+        .expect("System random available");
+    payload
+}
+
+/// A frame or a unidirectional stream of unknown type, passed to the application.
+///
+/// See [`UnknownPolicy::Surface`].
+#[non_exhaustive]
+pub enum Unknown {
+    /// A frame of unknown type.
+    Frame(UnknownFrame),
+
+    /// A unidirectional stream of unknown type.
+    UniStream(UnknownUniStream),
+}
+
+/// A frame of unknown type.
+#[derive(Clone, Debug)]
+pub struct UnknownFrame {
+    stream_id: StreamId,
+    kind: VarInt,
+    payload: Bytes,
+}
+
+impl UnknownFrame {
+    /// Returns the ID of the stream the frame was received on.
+    #[inline(always)]
+    pub fn stream_id(&self) -> StreamId {
+        self.stream_id
+    }
+
+    /// Returns the frame type.
+    #[inline(always)]
+    pub fn kind(&self) -> VarInt {
+        self.kind
+    }
+
+    /// Returns the frame payload.
+    #[inline(always)]
+    pub fn payload(&self) -> &Bytes {
+        &self.payload
+    }
+}
+
+/// A unidirectional stream of unknown type.
+pub struct UnknownUniStream {
+    kind: VarInt,
+    stream: RecvStream,
+}
+
+impl UnknownUniStream {
+    /// Returns the stream type.
+    #[inline(always)]
+    pub fn kind(&self) -> VarInt {
+        self.kind
+    }
+
+    /// Returns the stream, positioned right after its type.
+    #[inline(always)]
+    pub fn into_stream(self) -> RecvStream {
+        self.stream
+    }
+}
+
+/// Counters of the frames and unidirectional streams of unknown type received on a connection.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnknownStats {
+    pub(crate) frames: u64,
+    pub(crate) uni_streams: u64,
+    pub(crate) dropped: u64,
+}
+
+impl UnknownStats {
+    /// Returns the number of frames of unknown type received.
+    #[inline(always)]
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Returns the number of unidirectional streams of unknown type received.
+    #[inline(always)]
+    pub fn uni_streams(&self) -> u64 {
+        self.uni_streams
+    }
+
+    /// Returns the number of frames and streams dropped because the application
+    /// queue was full (see [`UnknownPolicy::Surface`]).
+    #[inline(always)]
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+/// An unknown frame or stream queued for the application.
+pub(crate) enum UnknownEvent {
+    Frame(UnknownFrame),
+    UniStream(VarInt, QuicRecvStream),
+}
+
+impl UnknownEvent {
+    pub(crate) fn into_unknown<F>(self, stream: F) -> Unknown
+    where
+        F: FnOnce(QuicRecvStream) -> RecvStream,
+    {
+        match self {
+            UnknownEvent::Frame(frame) => Unknown::Frame(frame),
+            UnknownEvent::UniStream(kind, quic_stream) => Unknown::UniStream(UnknownUniStream {
+                kind,
+                stream: stream(quic_stream),
+            }),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    frames: AtomicU64,
+    uni_streams: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// Applies the [`UnknownPolicy`] of a connection, and counts unknown frames and streams.
+#[derive(Clone)]
+pub(crate) struct UnknownHandler {
+    frames: UnknownPolicy,
+    uni_streams: UnknownPolicy,
+    counters: Arc<Counters>,
+    surfaced: mpsc::Sender<UnknownEvent>,
+}
+
+impl UnknownHandler {
+    pub(crate) fn new(
+        frames: UnknownPolicy,
+        uni_streams: UnknownPolicy,
+        surfaced: mpsc::Sender<UnknownEvent>,
+    ) -> Self {
+        Self {
+            frames,
+            uni_streams,
+            counters: Arc::default(),
+            surfaced,
+        }
+    }
+
+    pub(crate) fn stats(&self) -> UnknownStats {
+        UnknownStats {
+            frames: self.counters.frames.load(Ordering::Relaxed),
+            uni_streams: self.counters.uni_streams.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Handles a frame of unknown (or reserved) type received on `stream_id`.
+    pub(crate) fn on_frame(&self, stream_id: StreamId, frame: &Frame) -> Result<(), Violation> {
+        self.counters.frames.fetch_add(1, Ordering::Relaxed);
+        trace!(
+            "Unknown frame (stream_id: {}): {:?}",
+            stream_id,
+            frame.kind()
+        );
+
+        match self.frames {
+            UnknownPolicy::Ignore => {}
+            UnknownPolicy::Surface => self.surface(UnknownEvent::Frame(UnknownFrame {
+                stream_id,
+                kind: frame.kind().id(),
+                payload: Bytes::copy_from_slice(frame.payload()),
+            })),
+            UnknownPolicy::Close => {
+                return Err(
+                    Violation::new(ErrorCode::FrameUnexpected, "Unknown frame type")
+                        .on_stream(stream_id)
+                        .with_frame(frame.kind()),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handles a unidirectional stream of unknown (or reserved) type.
+    pub(crate) fn on_uni_stream(
+        &self,
+        kind: VarInt,
+        stream: QuicRecvStream,
+    ) -> Result<(), Violation> {
+        self.counters.uni_streams.fetch_add(1, Ordering::Relaxed);
+        trace!("Unknown stream (stream_id: {}): {}", stream.id(), kind);
+
+        match self.uni_streams {
+            UnknownPolicy::Ignore => ignore_uni_stream(stream),
+            UnknownPolicy::Surface => self.surface(UnknownEvent::UniStream(kind, stream)),
+            UnknownPolicy::Close => {
+                return Err(
+                    Violation::new(ErrorCode::StreamCreation, "Unknown stream type")
+                        .on_stream(stream.id()),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn surface(&self, event: UnknownEvent) {
+        match self.surfaced.try_send(event) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(event)) => {
+                debug!("Unknown frames and streams queue is full: dropping");
+                self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+
+                if let UnknownEvent::UniStream(_, stream) = event {
+                    ignore_uni_stream(stream);
+                }
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {}
+        }
+    }
+}
+
+fn ignore_uni_stream(mut stream: QuicRecvStream) {
+    // The stream might already be reset by the peer.
+    let _ = stream.stop(ErrorCode::StreamCreation.to_code());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+    use crate::Endpoint;
+    use tokio::time::timeout;
+
+    fn is_reserved(kind: VarInt) -> bool {
+        kind.into_inner() >= 0x21 && (kind.into_inner() - 0x21) % 0x1f == 0
+    }
+
+    /// Connects a client greasing with `grease` to a server applying `policy`.
+    async fn connect_greased(policy: UnknownPolicy, grease: Grease) -> test_utils::Peers {
+        let certificate = test_utils::certificate();
+        let client_config = test_utils::client_config(&certificate)
+            .grease(grease)
+            .build();
+        let server_config = test_utils::server_config(certificate)
+            .unknown_frames(policy)
+            .unknown_uni_streams(policy)
+            .build();
+
+        test_utils::connect_with(server_config, client_config).await
+    }
+
+    /// Sends `uni_stream` on a bare QUIC connection to a server closing on unknown types.
+    async fn close_with(uni_stream: &[u8]) -> quinn::ConnectionError {
+        let certificate = test_utils::certificate();
+        let server = Endpoint::server(
+            test_utils::server_config(certificate.clone())
+                .unknown_frames(UnknownPolicy::Close)
+                .unknown_uni_streams(UnknownPolicy::Close)
+                .build(),
+        )
+        .unwrap();
+
+        let (_endpoint, quic_connection) = test_utils::quic_connect(&server, &certificate).await;
+        let incoming_session = server.accept().await;
+        let accepting = tokio::spawn(async move { incoming_session.await.map(|_| ()) });
+
+        let mut stream = quic_connection.open_uni().await.unwrap();
+        stream.write_all(uni_stream).await.unwrap();
+
+        let error = timeout(Duration::from_secs(5), quic_connection.closed())
+            .await
+            .expect("Connection closed by the server");
+
+        accepting.abort();
+        error
+    }
+
+    fn application_error_code(error: &quinn::ConnectionError) -> Option<u64> {
+        match error {
+            quinn::ConnectionError::ApplicationClosed(close) => Some(close.error_code.into_inner()),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn ignore() {
+        let peers = connect_greased(UnknownPolicy::Ignore, Grease::all()).await;
+
+        timeout(Duration::from_secs(5), async {
+            loop {
+                let stats = peers.server_connection.unknown_stats();
+                if stats.frames() >= 1 && stats.uni_streams() >= 1 {
+                    break stats;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Unknown frames and streams counted");
+
+        // Nothing is surfaced, and the connection is still usable.
+        assert!(timeout(
+            Duration::from_millis(100),
+            peers.server_connection.accept_unknown()
+        )
+        .await
+        .is_err());
+        let mut stream = peers
+            .client_connection
+            .open_uni()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        stream.write_all(b"ping").await.unwrap();
+        stream.finish().await.unwrap();
+        let mut stream = peers.server_connection.accept_uni().await.unwrap();
+        let mut buffer = [0; 4];
+        stream.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(&buffer, b"ping");
+
+        assert_eq!(peers.server_connection.unknown_stats().dropped(), 0);
+    }
+
+    #[tokio::test]
+    async fn surface() {
+        let peers = connect_greased(UnknownPolicy::Surface, Grease::all()).await;
+
+        let mut frame = None;
+        let mut uni_stream = None;
+
+        while frame.is_none() || uni_stream.is_none() {
+            match timeout(
+                Duration::from_secs(5),
+                peers.server_connection.accept_unknown(),
+            )
+            .await
+            .expect("Unknown frame or stream surfaced")
+            .unwrap()
+            {
+                Unknown::Frame(unknown) => frame = Some(unknown),
+                Unknown::UniStream(unknown) => uni_stream = Some(unknown),
+            }
+        }
+
+        let frame = frame.unwrap();
+        assert!(is_reserved(frame.kind()));
+        assert!(frame.payload().len() <= Grease::MAX_PAYLOAD_SIZE);
+
+        let uni_stream = uni_stream.unwrap();
+        assert!(is_reserved(uni_stream.kind()));
+        let mut stream = uni_stream.into_stream();
+        let mut payload = Vec::new();
+        while let Some(read) = stream
+            .read_chunk(Grease::MAX_PAYLOAD_SIZE + 1)
+            .await
+            .unwrap()
+        {
+            payload.extend_from_slice(&read);
+        }
+        assert!(payload.len() <= Grease::MAX_PAYLOAD_SIZE);
+    }
+
+    #[tokio::test]
+    async fn grease_interval() {
+        let grease = Grease::new()
+            .frames(true)
+            .interval(Duration::from_millis(10));
+        let peers = connect_greased(UnknownPolicy::Ignore, grease).await;
+
+        timeout(Duration::from_secs(5), async {
+            while peers.server_connection.unknown_stats().frames() < 5 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Reserved frames sent repeatedly");
+
+        // The driver keeps serving the connection between reserved frames.
+        peers.client_connection.send_datagram(b"ping").unwrap();
+        let datagram = timeout(
+            Duration::from_secs(5),
+            peers.server_connection.receive_datagram(),
+        )
+        .await
+        .expect("Datagram received")
+        .unwrap();
+        assert_eq!(datagram.payload().as_ref(), b"ping");
+    }
+
+    #[tokio::test]
+    async fn close_on_unknown_uni_stream() {
+        // A stream of reserved type.
+        let error = close_with(&[0x21]).await;
+        assert_eq!(
+            application_error_code(&error),
+            Some(ErrorCode::StreamCreation.to_code().into_inner())
+        );
+    }
+
+    #[tokio::test]
+    async fn close_on_unknown_frame() {
+        // A control stream, SETTINGS, then a frame of reserved type.
+        let error = close_with(&[0x00, 0x04, 0x00, 0x21, 0x00]).await;
+        assert_eq!(
+            application_error_code(&error),
+            Some(ErrorCode::FrameUnexpected.to_code().into_inner())
+        );
+    }
+}
@@ -0,0 +1,379 @@
+use crate::error::ConnectionError;
+use crate::error::StreamOpeningError;
+use crate::error::StreamWriteError;
+use crate::Connection;
+use crate::RecvStream;
+use crate::SendStream;
+use std::fmt;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tracing::debug;
+
+/// Status code of a file transfer response.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Status(u16);
+
+impl Status {
+    /// The file follows in the body.
+    pub const OK: Status = Status(200);
+
+    /// The request is malformed (e.g., the path escapes the server root).
+    pub const BAD_REQUEST: Status = Status(400);
+
+    /// The file does not exist or it cannot be read.
+    pub const NOT_FOUND: Status = Status(404);
+
+    /// Creates a status from its code.
+    #[inline(always)]
+    pub const fn from_code(code: u16) -> Self {
+        Self(code)
+    }
+
+    /// Returns the status code.
+    #[inline(always)]
+    pub const fn code(self) -> u16 {
+        self.0
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// An error during a file transfer.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum FileTransferError {
+    /// The connection is no longer available.
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+
+    /// The stream cannot be opened.
+    #[error(transparent)]
+    StreamOpening(#[from] StreamOpeningError),
+
+    /// The stream cannot be finished.
+    #[error(transparent)]
+    Write(#[from] StreamWriteError),
+
+    /// Reading or writing the stream (or the file) failed.
+    #[error("file transfer I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The path is too long to be sent.
+    #[error("request path too long")]
+    PathTooLong,
+
+    /// The body is shorter than the announced length.
+    #[error("body truncated ({received} bytes out of {expected})")]
+    TruncatedBody {
+        /// Bytes actually received.
+        received: u64,
+
+        /// Length announced by the server.
+        expected: u64,
+    },
+}
+
+/// Server side of the file transfer protocol, serving files from a root directory.
+#[derive(Clone, Debug)]
+pub struct FileServer {
+    root: PathBuf,
+}
+
+impl FileServer {
+    /// Creates a server for the files in `root`.
+    pub fn new<P>(root: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self { root: root.into() }
+    }
+
+    /// Returns the root directory.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Serves the requests of `connection`, until it is closed.
+    ///
+    /// Each request is handled on its own task.
+    pub async fn serve(&self, connection: &Connection) -> ConnectionError {
+        loop {
+            let (send_stream, recv_stream) = match connection.accept_bi().await {
+                Ok(stream) => stream,
+                Err(error) => return error,
+            };
+
+            let server = self.clone();
+
+            connection.spawner().spawn(async move {
+                if let Err(error) = server.handle(send_stream, recv_stream).await {
+                    debug!("File transfer failed: {}", error);
+                }
+            });
+        }
+    }
+
+    /// Handles a single request on a bidirectional stream.
+    ///
+    /// It returns the status sent to the client.
+    pub async fn handle(
+        &self,
+        mut send_stream: SendStream,
+        mut recv_stream: RecvStream,
+    ) -> Result<Status, FileTransferError> {
+        let path_len = recv_stream.read_u16().await?;
+
+        let mut path = String::new();
+        (&mut recv_stream)
+            .take(u64::from(path_len))
+            .read_to_string(&mut path)
+            .await?;
+
+        let file = match self.resolve(&path).await {
+            Ok(file_path) => open(&file_path).await,
+            Err(status) => Err(status),
+        };
+
+        match file {
+            Ok((mut file, length)) => {
+                debug!("Serving '{}' ({} bytes)", path, length);
+
+                send_stream.write_u16(Status::OK.code()).await?;
+                send_stream.write_u64(length).await?;
+                tokio::io::copy(&mut (&mut file).take(length), &mut send_stream).await?;
+                send_stream.finish().await?;
+
+                Ok(Status::OK)
+            }
+            Err(status) => {
+                debug!("Cannot serve '{}': {}", path, status);
+
+                send_stream.write_u16(status.code()).await?;
+                send_stream.write_u64(0).await?;
+                send_stream.finish().await?;
+
+                Ok(status)
+            }
+        }
+    }
+
+    /// Resolves `path` into the root directory, then follows its symbolic links, rejecting
+    /// paths escaping the root either way.
+    async fn resolve(&self, path: &str) -> Result<PathBuf, Status> {
+        let joined = self.join(path).ok_or(Status::BAD_REQUEST)?;
+
+        let root = tokio::fs::canonicalize(&self.root)
+            .await
+            .map_err(|_| Status::NOT_FOUND)?;
+        let resolved = tokio::fs::canonicalize(&joined)
+            .await
+            .map_err(|_| Status::NOT_FOUND)?;
+
+        if !resolved.starts_with(&root) {
+            return Err(Status::BAD_REQUEST);
+        }
+
+        Ok(resolved)
+    }
+
+    /// Joins `path` to the root directory, rejecting paths escaping it lexically.
+    fn join(&self, path: &str) -> Option<PathBuf> {
+        let mut resolved = self.root.clone();
+
+        for component in Path::new(path.trim_start_matches('/')).components() {
+            match component {
+                Component::Normal(name) => resolved.push(name),
+                Component::CurDir => {}
+                _ => return None,
+            }
+        }
+
+        Some(resolved)
+    }
+}
+
+/// Response of a file transfer, returned by [`fetch`].
+pub struct FileResponse {
+    status: Status,
+    length: u64,
+    body: tokio::io::Take<RecvStream>,
+}
+
+impl FileResponse {
+    /// Returns the status code.
+    #[inline(always)]
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    /// Returns the length of the body, as announced by the server.
+    #[inline(always)]
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    /// Returns `true` if the body is empty.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the body, to be read as a stream (e.g., for large files).
+    pub fn into_body(self) -> tokio::io::Take<RecvStream> {
+        self.body
+    }
+
+    /// Reads the entire body.
+    pub async fn read_to_end(mut self) -> Result<Vec<u8>, FileTransferError> {
+        let mut body = Vec::new();
+        self.body.read_to_end(&mut body).await?;
+
+        if body.len() as u64 != self.length {
+            return Err(FileTransferError::TruncatedBody {
+                received: body.len() as u64,
+                expected: self.length,
+            });
+        }
+
+        Ok(body)
+    }
+}
+
+/// Requests the file at `path` on a new bidirectional stream of `connection`.
+pub async fn fetch(connection: &Connection, path: &str) -> Result<FileResponse, FileTransferError> {
+    let path_len = u16::try_from(path.len()).map_err(|_| FileTransferError::PathTooLong)?;
+
+    let (mut send_stream, mut recv_stream) = connection.open_bi().await?.await?;
+
+    send_stream.write_u16(path_len).await?;
+    send_stream.write_all(path.as_bytes()).await?;
+    send_stream.finish().await?;
+
+    let status = Status::from_code(recv_stream.read_u16().await?);
+    let length = recv_stream.read_u64().await?;
+
+    Ok(FileResponse {
+        status,
+        length,
+        body: recv_stream.take(length),
+    })
+}
+
+async fn open(file_path: &Path) -> Result<(tokio::fs::File, u64), Status> {
+    let file = tokio::fs::File::open(file_path)
+        .await
+        .map_err(|_| Status::NOT_FOUND)?;
+
+    let metadata = file.metadata().await.map_err(|_| Status::NOT_FOUND)?;
+
+    if !metadata.is_file() {
+        return Err(Status::NOT_FOUND);
+    }
+
+    Ok((file, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+
+    /// A temporary root directory, removed on drop.
+    struct Root(PathBuf);
+
+    impl Root {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "wtransport-file-transfer-{}-{name}",
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(path.join("root/dir")).unwrap();
+            Self(path)
+        }
+
+        fn path(&self) -> PathBuf {
+            self.0.join("root")
+        }
+    }
+
+    impl Drop for Root {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn join() {
+        let server = FileServer::new("/srv");
+
+        assert_eq!(
+            server.join("/a/./b.txt"),
+            Some(PathBuf::from("/srv/a/b.txt"))
+        );
+        assert_eq!(server.join("../etc/passwd"), None);
+        assert_eq!(server.join("a/../../b"), None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn resolve_symlinks() {
+        let root = Root::new("symlinks");
+        std::fs::write(root.path().join("a.txt"), "a").unwrap();
+        std::fs::write(root.0.join("secret.txt"), "secret").unwrap();
+        std::os::unix::fs::symlink(root.path().join("a.txt"), root.path().join("inside")).unwrap();
+        std::os::unix::fs::symlink(root.0.join("secret.txt"), root.path().join("outside")).unwrap();
+        std::os::unix::fs::symlink(&root.0, root.path().join("dir/parent")).unwrap();
+
+        let server = FileServer::new(root.path());
+        let canonical = std::fs::canonicalize(root.path()).unwrap();
+
+        assert_eq!(server.resolve("a.txt").await, Ok(canonical.join("a.txt")));
+        assert_eq!(server.resolve("inside").await, Ok(canonical.join("a.txt")));
+        assert_eq!(server.resolve("outside").await, Err(Status::BAD_REQUEST));
+        assert_eq!(
+            server.resolve("dir/parent/secret.txt").await,
+            Err(Status::BAD_REQUEST)
+        );
+        assert_eq!(server.resolve("missing.txt").await, Err(Status::NOT_FOUND));
+    }
+
+    #[tokio::test]
+    async fn fetch_files() {
+        let root = Root::new("fetch");
+        let large = (0..4 * 1024 * 1024)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        std::fs::write(root.path().join("large.bin"), &large).unwrap();
+
+        let peers = test_utils::connect().await;
+        let server = FileServer::new(root.path());
+        let server_connection = peers.server_connection;
+        let serving = tokio::spawn(async move { server.serve(&server_connection).await });
+
+        let response = fetch(&peers.client_connection, "/large.bin").await.unwrap();
+        assert_eq!(response.status(), Status::OK);
+        assert_eq!(response.len(), large.len() as u64);
+        assert!(response.read_to_end().await.unwrap() == large);
+
+        for (path, status) in [
+            ("missing.txt", Status::NOT_FOUND),
+            ("dir", Status::NOT_FOUND),
+            ("../large.bin", Status::BAD_REQUEST),
+        ] {
+            let response = fetch(&peers.client_connection, path).await.unwrap();
+            assert_eq!(response.status(), status, "{path}");
+            assert!(response.is_empty());
+            assert!(response.read_to_end().await.unwrap().is_empty());
+        }
+
+        serving.abort();
+    }
+}
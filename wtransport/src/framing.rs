@@ -0,0 +1,117 @@
+use crate::error::StreamReadExactError;
+use crate::error::StreamWriteError;
+use crate::RecvStream;
+use crate::SendStream;
+use wtransport_proto::bytes::BytesReader;
+use wtransport_proto::bytes::BytesWriter;
+use wtransport_proto::varint::VarInt;
+
+/// An error reading a message from a stream.
+#[derive(Debug)]
+pub(crate) enum ReadMessageError {
+    /// The stream cannot be read (or it finished in the middle of a message).
+    Read(StreamReadExactError),
+
+    /// The message is larger than the allowed size.
+    TooLarge,
+}
+
+impl From<StreamReadExactError> for ReadMessageError {
+    fn from(error: StreamReadExactError) -> Self {
+        ReadMessageError::Read(error)
+    }
+}
+
+/// Appends `varint` to `buffer`.
+pub(crate) fn put_varint(buffer: &mut Vec<u8>, varint: VarInt) {
+    buffer
+        .put_varint(varint)
+        .expect("Vec has unbounded capacity");
+}
+
+/// Reads a varint from the beginning of `bytes`, advancing it.
+#[cfg(feature = "pubsub")]
+pub(crate) fn get_varint(bytes: &mut &[u8]) -> Option<VarInt> {
+    bytes.get_varint()
+}
+
+/// Reads a varint from `stream`.
+///
+/// It returns [`None`] if the stream finished before the first byte.
+pub(crate) async fn read_varint(
+    stream: &mut RecvStream,
+) -> Result<Option<VarInt>, StreamReadExactError> {
+    let mut buffer = [0; VarInt::MAX_SIZE];
+
+    match stream.read(&mut buffer[..1]).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Ok(None),
+        Err(error) => return Err(StreamReadExactError::Read(error)),
+    }
+
+    let varint_size = VarInt::parse_size(buffer[0]);
+    stream.read_exact(&mut buffer[1..varint_size]).await?;
+
+    Ok((&buffer[..varint_size]).get_varint())
+}
+
+/// Writes `message` on `stream`, prefixed by its length.
+pub(crate) async fn write_message(
+    stream: &mut SendStream,
+    message: &[u8],
+) -> Result<(), StreamWriteError> {
+    let mut buffer = Vec::with_capacity(VarInt::MAX_SIZE + message.len());
+    put_varint(
+        &mut buffer,
+        VarInt::try_from(message.len() as u64).expect("Message cannot be larger than varint max"),
+    );
+    buffer.extend_from_slice(message);
+
+    stream.write_all(&buffer).await
+}
+
+/// Reads a message written with [`write_message`] from `stream`.
+///
+/// It returns [`None`] if the stream finished before the message.
+pub(crate) async fn read_message(
+    stream: &mut RecvStream,
+    max_len: usize,
+) -> Result<Option<Vec<u8>>, ReadMessageError> {
+    let len = match read_varint(stream).await? {
+        Some(len) => len.into_inner(),
+        None => return Ok(None),
+    };
+
+    if len > max_len as u64 {
+        return Err(ReadMessageError::TooLarge);
+    }
+
+    let mut message = vec![0; len as usize];
+    stream.read_exact(&mut message).await?;
+
+    Ok(Some(message))
+}
+
+/// Reads `stream` until it finishes, up to `max_len` bytes.
+#[cfg(any(feature = "objects", feature = "pubsub"))]
+pub(crate) async fn read_to_end(
+    stream: &mut RecvStream,
+    max_len: usize,
+) -> Result<Vec<u8>, ReadMessageError> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 4096];
+
+    while let Some(read) = stream
+        .read(&mut chunk)
+        .await
+        .map_err(|error| ReadMessageError::Read(StreamReadExactError::Read(error)))?
+    {
+        if buffer.len() + read > max_len {
+            return Err(ReadMessageError::TooLarge);
+        }
+
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+
+    Ok(buffer)
+}
@@ -0,0 +1,158 @@
+use crate::error::SpliceError;
+use crate::RecvStream;
+use crate::SendStream;
+
+/// Forwards the data received on `recv` to `send`, till `recv` is finished, then finishes
+/// `send`. The streams can belong to different sessions.
+///
+/// Data is passed chunk by chunk, as received by QUIC: it is never copied into an
+/// intermediate buffer. A chunk is only read once the previous one is accepted by
+/// the flow control of `send`, hence a slow destination pauses the source, whose
+/// peer is then blocked by flow control too.
+///
+/// Returns the number of bytes forwarded. On error, none of the streams is closed: the
+/// caller decides how to propagate the failure (e.g., resetting `send` if `recv` was reset,
+/// see [`RecvStream::end`]).
+pub async fn copy_spliced(
+    recv: &mut RecvStream,
+    send: &mut SendStream,
+) -> Result<u64, SpliceError> {
+    let mut forwarded = 0;
+
+    while let Some(chunk) = recv.read_chunk(usize::MAX).await? {
+        forwarded += chunk.len() as u64;
+        send.write_chunk(chunk).await?;
+    }
+
+    send.finish().await?;
+
+    Ok(forwarded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::StreamReadError;
+    use crate::error::StreamWriteError;
+    use crate::stream::StreamEnd;
+    use crate::test_utils;
+    use wtransport_proto::varint::VarInt;
+
+    #[tokio::test]
+    async fn copy_between_sessions() {
+        let source = test_utils::connect().await;
+        let destination = test_utils::connect().await;
+        let data = (0..1024 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+
+        let mut send = source
+            .client_connection
+            .open_uni()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        let writing = {
+            let data = data.clone();
+            tokio::spawn(async move {
+                send.write_all(&data).await.unwrap();
+                send.finish().await.unwrap();
+            })
+        };
+
+        let mut recv = source.server_connection.accept_uni().await.unwrap();
+        let mut forward = destination
+            .server_connection
+            .open_uni()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        let (forwarded, received) = tokio::join!(copy_spliced(&mut recv, &mut forward), async {
+            let mut stream = destination.client_connection.accept_uni().await.unwrap();
+            let mut received = Vec::new();
+            while let Some(chunk) = stream.read_chunk(usize::MAX).await.unwrap() {
+                received.extend_from_slice(&chunk);
+            }
+            received
+        },);
+
+        writing.await.unwrap();
+        assert_eq!(forwarded.unwrap(), data.len() as u64);
+        assert!(matches!(recv.end(), Some(StreamEnd::Finished)));
+        assert_eq!(received, data);
+    }
+
+    #[tokio::test]
+    async fn source_reset() {
+        let peers = test_utils::connect().await;
+
+        let mut send = peers
+            .client_connection
+            .open_uni()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        send.write_all(b"partial").await.unwrap();
+
+        let mut recv = peers.server_connection.accept_uni().await.unwrap();
+        let mut forward = peers
+            .server_connection
+            .open_uni()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+
+        let (result, ()) = tokio::join!(copy_spliced(&mut recv, &mut forward), async {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            send.reset(VarInt::from_u32(42));
+        });
+
+        assert!(matches!(
+            result,
+            Err(SpliceError::Read(StreamReadError::Reset(code))) if code == VarInt::from_u32(42)
+        ));
+        assert!(matches!(recv.end(), Some(StreamEnd::Reset(code)) if code == VarInt::from_u32(42)));
+    }
+
+    #[tokio::test]
+    async fn destination_stopped() {
+        let peers = test_utils::connect().await;
+
+        let mut send = peers
+            .client_connection
+            .open_uni()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        let mut recv = peers.server_connection.accept_uni().await.unwrap();
+
+        let mut forward = peers
+            .server_connection
+            .open_uni()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        forward.write_all(b"header").await.unwrap();
+        peers
+            .client_connection
+            .accept_uni()
+            .await
+            .unwrap()
+            .stop(VarInt::from_u32(7));
+
+        send.write_all(b"data").await.unwrap();
+        send.finish().await.unwrap();
+
+        // Whether the chunk is written before the STOP_SENDING is received or not, the
+        // stream does not finish.
+        let result = copy_spliced(&mut recv, &mut forward).await;
+        assert!(matches!(
+            result,
+            Err(SpliceError::Write(StreamWriteError::Stopped(code))) if code == VarInt::from_u32(7)
+        ));
+    }
+}
@@ -21,10 +21,71 @@
 //! let connection = incoming_request.accept().await.unwrap();
 //! # }
 //! ```
+//!
+//! # Time
+//!
+//! The timers and time measurements of this crate are based on [`tokio::time`]:
+//! the phase [timeouts](config::Timeouts) (handshake, settings exchange, session request
+//! and drain), connection timings, [milestones](Connection::milestones), last activity
+//! of connections and the expiry of [resumption tokens](resumption::ResumptionTokens).
+//! Hence, tests can control them with a paused clock (see
+//! [`pause`](https://docs.rs/tokio/1/tokio/time/fn.pause.html), with the `test-util`
+//! feature of `tokio`). In particular, [`Timeouts::handshake`](config::Timeouts::handshake)
+//! bounds the handshake on this clock.
+//!
+//! Only those timers can be mocked. The QUIC timers (idle timeout, keep-alive and loss
+//! recovery, including the retransmissions during the handshake) are driven by `quinn`,
+//! which reads the system clock directly and offers no way to inject another one: tests
+//! of those behaviors must run in real time (e.g., with short timeouts).
+//!
+//! # Dependency versions
+//!
+//! Some APIs take or return types of [`rustls`] (e.g., custom TLS configurations) and, with
+//! the `quinn-compat` feature, of [`quinn`]. Values built with another version of those
+//! crates are rejected by the compiler: use the re-exports [`tls::rustls`] and [`quinn`],
+//! which are always the versions this crate depends on.
+//!
+//! Upgrading `rustls` is a breaking release of this crate. APIs of `quinn` follow the
+//! semver of `quinn` instead (see [`config`](config#quinn-compat-feature)).
 #![cfg_attr(docsrs, feature(doc_cfg))]
 #![warn(missing_docs)]
 
+/// HTTP3 alternative services.
+pub mod alt_svc;
+
+/// Co-hosting of an `axum` HTTPS server with a WebTransport endpoint, advertised with `Alt-Svc`.
+#[cfg(feature = "axum")]
+#[cfg_attr(docsrs, doc(cfg(feature = "axum")))]
+pub mod cohost;
+
+/// Encoding of typed payloads (e.g., JSON or CBOR), with a user-provided [`Codec`](codec::Codec).
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+pub mod codec;
+
 /// Client and server configurations.
+///
+/// # `quinn-compat` feature
+///
+/// The `quinn-compat` feature exposes the underlying [`quinn`] configuration types
+/// (e.g., [`ServerConfigBuilder::with_transport_config`](config::ServerConfigBuilder::with_transport_config))
+/// for accessing QUIC features not wrapped by this crate yet.
+///
+/// Those APIs follow the semver of `quinn` rather than the one of this crate:
+/// they might break with any release upgrading `quinn`.
+/// The version of `quinn` they expect is re-exported as [`crate::quinn`], and
+/// [`compat`] converts between the types of both crates.
+///
+/// # Receive windows
+///
+/// The [stream receive window](config::ServerConfigBuilder::stream_receive_window)
+/// applies to all the streams: session, control and data streams. Windows per stream type
+/// are not available, as the underlying QUIC implementation does not support them.
+///
+/// As each stream has its own window, an unread data stream cannot starve the session and
+/// control streams (which are always read by this crate), unless the
+/// [receive window](config::ServerConfigBuilder::receive_window) of the connection is
+/// exhausted.
 pub mod config;
 
 /// WebTransport connection.
@@ -39,12 +100,152 @@ pub mod error;
 /// Interfaces for sending and receiving data.
 pub mod stream;
 
+/// Forwarding of data between streams, e.g., in relays.
+pub mod io;
+
 /// TLS specific configurations.
 pub mod tls;
 
+/// Conversions between the types of this crate and the ones of [`quinn`].
+///
+/// With this feature, the stream and datagram errors of this crate also implement `From`
+/// the matching errors of `quinn`.
+#[cfg(feature = "quinn-compat")]
+#[cfg_attr(docsrs, doc(cfg(feature = "quinn-compat")))]
+pub mod compat;
+
+/// Capsules exchanged on the session stream.
+pub mod capsule;
+
 /// Datagrams module.
 pub mod datagram;
 
+/// Endpoint discovery through DNS `HTTPS` records.
+pub mod dns;
+
+/// Application-level session resumption tokens.
+///
+/// The server seals some application state in a token, issued in the session response.
+/// When reconnecting, the client presents the token back (see
+/// [`Endpoint::connect_resuming`](crate::Endpoint::connect_resuming)), so that the server
+/// restores the state (see [`SessionRequest::resumption_state`](endpoint::SessionRequest::resumption_state)).
+pub mod resumption;
+
+/// Endpoint UDP socket utilities (non-QUIC traffic, packet taps and statistics).
+pub mod socket;
+
+/// Reachability self-test of a server (UDP and certificate), e.g., on startup.
+pub mod self_test;
+
+/// Pinning of connections to dedicated worker threads, optionally with CPU affinity.
+pub mod worker;
+
+/// Server-chosen QUIC connection IDs, e.g., embedding routing information for
+/// load balancers.
+pub mod cid;
+
+/// Quotas of sessions, streams and bandwidth per principal (e.g., an authenticated tenant),
+/// across an endpoint.
+pub mod quota;
+
+/// Endpoint-level memory budget, counting the memory buffered by the connections.
+pub mod budget;
+
+/// Injection of failures into live connections (dropped datagrams, reset streams,
+/// stalled flow control, delayed SETTINGS), for chaos testing.
+#[cfg(feature = "chaos")]
+#[cfg_attr(docsrs, doc(cfg(feature = "chaos")))]
+pub mod chaos;
+
+/// Concurrency-limited handling of incoming streams and datagrams
+/// (see [`Connection::serve`]), and tasks supervised by a connection
+/// (see [`Connection::spawn_supervised`]).
+pub mod serve;
+
+/// Handling of HTTP3 frames and unidirectional streams of unknown type (including reserved
+/// types), e.g., to observe protocol extensions and grease sent by peers, and sending of
+/// grease (see [`Grease`](extension::Grease)).
+pub mod extension;
+
+/// Compression of stream payloads, with [Brotli](compression::Brotli) or pluggable codecs.
+///
+/// The codec of a session is negotiated in the session headers (see
+/// [`Codecs`](compression::Codecs)). Streams are then wrapped into a
+/// [`CompressedSendStream`](compression::CompressedSendStream) and a
+/// [`CompressedRecvStream`](compression::CompressedRecvStream), which decodes within memory
+/// limits.
+#[cfg(feature = "compression")]
+#[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+pub mod compression;
+
+/// A simple file transfer protocol over bidirectional streams.
+///
+/// Each file is transferred on its own bidirectional stream:
+///
+///   * the client sends the length of the path (`u16`) followed by the path (UTF-8),
+///     then it finishes its side of the stream;
+///   * the server replies with a [`Status`](file_transfer::Status) code (`u16`), the length
+///     of the body (`u64`) and the body, then it finishes the stream.
+///
+/// Integers are encoded in network byte-order.
+#[cfg(feature = "file-transfer")]
+#[cfg_attr(docsrs, doc(cfg(feature = "file-transfer")))]
+pub mod file_transfer;
+
+/// A MASQUE connect-udp proxy ([RFC 9298](https://www.rfc-editor.org/rfc/rfc9298)),
+/// served on the same endpoint as WebTransport sessions.
+///
+/// Requests are handed to the proxy as an extended CONNECT protocol (see
+/// [`ServerConfigBuilder::masque_udp_proxy`](config::ServerConfigBuilder::masque_udp_proxy)),
+/// and UDP payloads are relayed in datagrams with context ID `0`.
+#[cfg(feature = "masque")]
+#[cfg_attr(docsrs, doc(cfg(feature = "masque")))]
+pub mod masque;
+
+/// Media-like objects (Media over QUIC style), each sent on its own stream.
+///
+/// Objects are identified by track, group and object number, and their streams are
+/// prioritized (e.g., favoring the newest group) by the [`ObjectSender`](objects::ObjectSender).
+#[cfg(feature = "objects")]
+#[cfg_attr(docsrs, doc(cfg(feature = "objects")))]
+pub mod objects;
+
+/// Ordered and reliable message channels over unidirectional streams.
+///
+/// An [`OrderedSender`](ordered::OrderedSender) sends messages on a long-lived
+/// unidirectional stream, resuming on a new stream if the peer stops the current one.
+/// The peer accepts channels with an [`OrderedListener`](ordered::OrderedListener).
+#[cfg(feature = "ordered")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ordered")))]
+pub mod ordered;
+
+/// Named channels (publish/subscribe) over a session.
+///
+/// The [`Subscriber`](pubsub::Subscriber) sends subscribe and unsubscribe messages
+/// on a control stream, the [`Publisher`](pubsub::Publisher) sends the payloads of
+/// subscribed channels on datagrams or unidirectional streams.
+#[cfg(feature = "pubsub")]
+#[cfg_attr(docsrs, doc(cfg(feature = "pubsub")))]
+pub mod pubsub;
+
+/// Request/response calls over bidirectional streams.
+///
+/// Each call is carried by its own bidirectional stream: the caller sends the method
+/// name, its deadline and the request, the callee answers with a [`RpcStatus`](rpc::RpcStatus)
+/// and the response. Cancelled calls reset the stream, and calls exceeding their deadline
+/// are abandoned, so that the callee drops its handler.
+///
+/// With the `serde` feature, typed payloads can be encoded with a user-provided
+/// [`Codec`](rpc::Codec).
+#[cfg(feature = "rpc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rpc")))]
+pub mod rpc;
+
+/// `tower` integration for session handling.
+#[cfg(feature = "tower")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tower")))]
+pub mod service;
+
 #[doc(inline)]
 pub use config::ClientConfig;
 
@@ -63,4 +264,19 @@ pub use stream::RecvStream;
 #[doc(inline)]
 pub use stream::SendStream;
 
+/// The [`quinn`](https://docs.rs/quinn/0.10) crate, in the version this crate depends on.
+#[cfg(feature = "quinn-compat")]
+#[cfg_attr(docsrs, doc(cfg(feature = "quinn-compat")))]
+pub use quinn;
+
 mod driver;
+
+mod tls_session;
+
+mod url_validation;
+
+mod framing;
+
+#[cfg(test)]
+#[allow(dead_code)] // Each test uses a subset of the helpers.
+mod test_utils;
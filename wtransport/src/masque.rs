@@ -0,0 +1,538 @@
+use crate::datagram::DatagramContexts;
+use crate::endpoint::SessionRequest;
+use crate::error::ConnectionError;
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tracing::debug;
+use wtransport_proto::ids::StatusCode;
+use wtransport_proto::varint::VarInt;
+
+/// The `:protocol` of connect-udp requests.
+pub const CONNECT_UDP_PROTOCOL: &str = "connect-udp";
+
+/// Prefix of the default connect-udp URI template.
+const WELL_KNOWN_PREFIX: &str = "/.well-known/masque/udp/";
+
+/// Context ID of the datagrams carrying UDP payloads.
+const UDP_PAYLOAD_CONTEXT: VarInt = VarInt::from_u32(0);
+
+/// Maximum size of a UDP payload.
+const MAX_UDP_PAYLOAD: usize = 65527;
+
+/// An error while proxying a connect-udp request.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum MasqueError {
+    /// The request path does not follow the connect-udp URI template
+    /// (replied with `400`).
+    #[error("invalid connect-udp target")]
+    InvalidTarget,
+
+    /// The target host cannot be resolved (replied with `502`).
+    #[error("cannot resolve target: {0}")]
+    Resolution(io::Error),
+
+    /// No resolved address of the target is allowed by the policy (replied with `403`).
+    #[error("target denied by policy")]
+    Denied,
+
+    /// The UDP socket towards the target cannot be set up (replied with `502`).
+    #[error("UDP socket error: {0}")]
+    Socket(io::Error),
+
+    /// The session cannot be established.
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+}
+
+/// Target of a connect-udp request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UdpTarget {
+    host: String,
+    port: u16,
+}
+
+impl UdpTarget {
+    /// Parses the target from a request path following the default URI template
+    /// (`/.well-known/masque/udp/{target_host}/{target_port}/`).
+    ///
+    /// Returns `None` if the path does not follow the template.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let mut segments = path.strip_prefix(WELL_KNOWN_PREFIX)?.split('/');
+
+        let host = percent_decode(segments.next()?)?;
+        let port = segments.next()?.parse().ok()?;
+
+        if host.is_empty() || port == 0 || segments.next() != Some("") || segments.next().is_some()
+        {
+            return None;
+        }
+
+        Some(Self { host, port })
+    }
+
+    /// Returns the target host (a domain name or an IP address).
+    #[inline(always)]
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Returns the target port.
+    #[inline(always)]
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+impl fmt::Display for UdpTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// Policy deciding which targets a [`UdpProxy`] relays to.
+///
+/// It is invoked for each resolved address of the target, in order: the first allowed
+/// address is used.
+pub trait UdpProxyPolicy: Send + Sync + 'static {
+    /// Returns whether `request` can be relayed to `address` (resolved from `target`).
+    fn allow(&self, request: &SessionRequest, target: &UdpTarget, address: SocketAddr) -> bool;
+}
+
+impl<F> UdpProxyPolicy for F
+where
+    F: Fn(&SessionRequest, &UdpTarget, SocketAddr) -> bool + Send + Sync + 'static,
+{
+    #[inline(always)]
+    fn allow(&self, request: &SessionRequest, target: &UdpTarget, address: SocketAddr) -> bool {
+        self(request, target, address)
+    }
+}
+
+/// The default policy, allowing only [public](is_public_ip) addresses.
+struct PublicOnly;
+
+impl UdpProxyPolicy for PublicOnly {
+    fn allow(&self, _request: &SessionRequest, _target: &UdpTarget, address: SocketAddr) -> bool {
+        is_public_ip(address.ip())
+    }
+}
+
+/// Returns whether `ip` is a public unicast address.
+///
+/// Unspecified, loopback, private (RFC 1918), shared (RFC 6598), IETF protocol assignments
+/// (`192.0.0.0/24`), benchmarking (`198.18.0.0/15`), reserved (`240.0.0.0/4`), link-local,
+/// site-local (`fec0::/10`), unique local (`fc00::/7`), broadcast and multicast addresses
+/// are not public.
+///
+/// IPv6 addresses embedding an IPv4 address are checked as this IPv4 address: IPv4-mapped,
+/// NAT64 (`64:ff9b::/96`) and 6to4 (`2002::/16`) addresses. Deprecated IPv4-compatible
+/// addresses (`::a.b.c.d`) and local-use NAT64 addresses (`64:ff9b:1::/48`) are not public.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match embedded_ipv4(ip) {
+            Some(ip) => is_public_ipv4(ip),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [first, second, third, _] = ip.octets();
+
+    !(ip.is_unspecified()
+        || first == 0
+        || ip.is_loopback()
+        || ip.is_private()
+        || (first == 100 && (second & 0xc0) == 64)
+        || (first == 192 && second == 0 && third == 0)
+        || (first == 198 && (second & 0xfe) == 18)
+        || (first & 0xf0) == 240
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast())
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+    let first = segments[0];
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || segments[..6] == [0; 6]
+        || segments[..3] == [0x64, 0xff9b, 1]
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80
+        || (first & 0xffc0) == 0xfec0
+        || ip.is_multicast())
+}
+
+/// Returns the IPv4 address embedded in an IPv4-mapped, NAT64 or 6to4 address.
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let [a, b, c, d, e, f, g, h] = ip.segments();
+
+    match [a, b, c, d, e, f] {
+        [0, 0, 0, 0, 0, 0xffff] | [0x64, 0xff9b, 0, 0, 0, 0] => Some(ipv4(g, h)),
+        [0x2002, ..] => Some(ipv4(b, c)),
+        _ => None,
+    }
+}
+
+fn ipv4(high: u16, low: u16) -> Ipv4Addr {
+    Ipv4Addr::from((u32::from(high) << 16) | u32::from(low))
+}
+
+/// A connect-udp proxy ([RFC 9298](https://www.rfc-editor.org/rfc/rfc9298)).
+///
+/// Each accepted request gets its own UDP socket, connected to the target: UDP payloads
+/// are relayed in datagrams with context ID `0`, other contexts are dropped.
+///
+/// Register it for [`CONNECT_UDP_PROTOCOL`] with
+/// [`ServerConfigBuilder::masque_udp_proxy`](crate::config::ServerConfigBuilder::masque_udp_proxy).
+#[derive(Clone)]
+pub struct UdpProxy {
+    policy: Arc<dyn UdpProxyPolicy>,
+    idle_timeout: Option<Duration>,
+}
+
+impl UdpProxy {
+    /// Creates a proxy with the default policy and no idle timeout.
+    ///
+    /// The default policy only allows public targets (see [`is_public_ip`]).
+    pub fn new() -> Self {
+        Self {
+            policy: Arc::new(PublicOnly),
+            idle_timeout: None,
+        }
+    }
+
+    /// Sets the policy deciding which targets are allowed.
+    ///
+    /// **Note**: the policy replaces the default one, so it should deny internal
+    /// addresses (see [`is_public_ip`]) unless the proxy is meant to reach them.
+    pub fn policy<P>(mut self, policy: P) -> Self
+    where
+        P: UdpProxyPolicy,
+    {
+        self.policy = Arc::new(policy);
+        self
+    }
+
+    /// Closes the session once no datagram has been relayed (in either direction)
+    /// for `timeout`.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Proxies a connect-udp `request`, until its session is closed.
+    ///
+    /// The request is rejected (and an [`Err`] returned) if the target is invalid, cannot
+    /// be resolved or is denied by the policy.
+    pub async fn serve(&self, mut request: SessionRequest) -> Result<(), MasqueError> {
+        let (target, socket) = match self.connect(&request).await {
+            Ok(connected) => connected,
+            Err(error) => {
+                let status_code = match error {
+                    MasqueError::InvalidTarget => 400u16,
+                    MasqueError::Denied => 403,
+                    _ => 502,
+                };
+
+                debug!("connect-udp request rejected ({}): {}", status_code, error);
+                request
+                    .reject_with_status(
+                        StatusCode::try_from(status_code).expect("Valid status code"),
+                    )
+                    .await;
+                return Err(error);
+            }
+        };
+
+        request.add_response_header("capsule-protocol", "?1");
+
+        let connection = Arc::new(request.accept().await?);
+
+        debug!("Relaying UDP to {}", target);
+
+        let contexts = DatagramContexts::new(connection.clone());
+        let mut context = contexts
+            .register(UDP_PAYLOAD_CONTEXT)
+            .expect("Context is registered once");
+
+        let mut buffer = vec![0; MAX_UDP_PAYLOAD];
+
+        loop {
+            let relay = async {
+                tokio::select! {
+                    payload = context.receive() => match payload {
+                        Some(payload) => {
+                            if let Err(error) = socket.send(&payload).await {
+                                debug!("Cannot relay UDP payload to target: {}", error);
+                            }
+                            true
+                        }
+                        None => false,
+                    },
+                    received = socket.recv(&mut buffer) => {
+                        match received {
+                            Ok(len) => {
+                                if let Err(error) = context.send(&buffer[..len]) {
+                                    debug!("Cannot relay UDP payload to client: {}", error);
+                                }
+                            }
+                            // E.g., ICMP port unreachable on the connected socket
+                            Err(error) => debug!("UDP receive error: {}", error),
+                        }
+                        true
+                    }
+                }
+            };
+
+            let active = match self.idle_timeout {
+                Some(timeout) => tokio::time::timeout(timeout, relay).await.ok(),
+                None => Some(relay.await),
+            };
+
+            match active {
+                Some(true) => {}
+                Some(false) => break,
+                None => {
+                    debug!("connect-udp session idle: closing");
+                    connection.close(VarInt::from_u32(0), b"Idle");
+                    break;
+                }
+            }
+        }
+
+        debug!("Relaying UDP to {} ended", target);
+
+        Ok(())
+    }
+
+    pub(crate) async fn handle(self, request: SessionRequest) {
+        if let Err(error) = self.serve(request).await {
+            debug!("connect-udp proxying failed: {}", error);
+        }
+    }
+
+    async fn connect(
+        &self,
+        request: &SessionRequest,
+    ) -> Result<(UdpTarget, UdpSocket), MasqueError> {
+        let target = UdpTarget::from_path(request.path()).ok_or(MasqueError::InvalidTarget)?;
+
+        let address = tokio::net::lookup_host((target.host(), target.port()))
+            .await
+            .map_err(MasqueError::Resolution)?
+            .find(|address| self.policy.allow(request, &target, *address))
+            .ok_or(MasqueError::Denied)?;
+
+        let bind_address: IpAddr = match address {
+            SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+            SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+        };
+
+        let socket = UdpSocket::bind(SocketAddr::new(bind_address, 0))
+            .await
+            .map_err(MasqueError::Socket)?;
+
+        socket.connect(address).await.map_err(MasqueError::Socket)?;
+
+        Ok((target, socket))
+    }
+}
+
+impl Default for UdpProxy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for UdpProxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UdpProxy")
+            .field("idle_timeout", &self.idle_timeout)
+            .finish()
+    }
+}
+
+/// Decodes the percent-encoded octets of a URI segment (e.g., `2001%3Adb8%3A%3A1`).
+fn percent_decode(segment: &str) -> Option<String> {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            let hex = bytes.get(index + 1..index + 3)?;
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+
+            let hex = std::str::from_utf8(hex).expect("Hex digits are ASCII");
+            decoded.push(u8::from_str_radix(hex, 16).expect("Valid hex digits"));
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn public_ips() {
+        for ip in [
+            "93.184.216.34",
+            "8.8.8.8",
+            "2606:2800:220:1::1",
+            "::ffff:8.8.8.8",
+            "64:ff9b::8.8.8.8",
+            "2002:0808:0808::1",
+        ] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn non_public_ips() {
+        for ip in [
+            // Unspecified and "this network".
+            "0.0.0.0",
+            "0.1.2.3",
+            "::",
+            // Loopback.
+            "127.0.0.1",
+            "127.255.0.1",
+            "::1",
+            // Private.
+            "10.0.0.1",
+            "10.255.255.255",
+            "172.16.0.1",
+            "172.31.255.255",
+            "192.168.0.1",
+            "192.168.255.255",
+            // Shared address space.
+            "100.64.0.1",
+            "100.127.255.255",
+            // Link-local.
+            "169.254.0.1",
+            "169.254.169.254",
+            "fe80::1",
+            "febf::1",
+            // Unique local.
+            "fc00::1",
+            "fd12:3456::1",
+            // Broadcast and multicast.
+            "255.255.255.255",
+            "224.0.0.1",
+            "ff02::1",
+            // IETF protocol assignments, benchmarking and reserved.
+            "192.0.0.1",
+            "198.18.0.1",
+            "198.19.255.255",
+            "240.0.0.1",
+            "250.1.2.3",
+            // Site-local.
+            "fec0::1",
+            "feff::1",
+            // IPv4-mapped.
+            "::ffff:127.0.0.1",
+            "::ffff:10.0.0.1",
+            "::ffff:169.254.169.254",
+            // NAT64 and 6to4 of internal addresses.
+            "64:ff9b::127.0.0.1",
+            "64:ff9b::10.0.0.1",
+            "2002:c0a8:0001::1",
+            "2002:0a00:0001:1::1",
+            // IPv4-compatible and local-use NAT64.
+            "::8.8.8.8",
+            "::10.0.0.1",
+            "64:ff9b:1::8.8.8.8",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+
+        // Bounds of the ranges.
+        for ip in [
+            "172.15.255.255",
+            "172.32.0.0",
+            "100.63.255.255",
+            "100.128.0.0",
+            "192.0.1.0",
+            "198.17.255.255",
+            "198.20.0.0",
+            "fe7f::1",
+        ] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn target_from_path() {
+        let target = UdpTarget::from_path("/.well-known/masque/udp/example.com/443/").unwrap();
+        assert_eq!(target.host(), "example.com");
+        assert_eq!(target.port(), 443);
+        assert_eq!(target.to_string(), "example.com:443");
+
+        let target = UdpTarget::from_path("/.well-known/masque/udp/192.0.2.6/53/").unwrap();
+        assert_eq!(target.host(), "192.0.2.6");
+        assert_eq!(target.to_string(), "192.0.2.6:53");
+
+        let target =
+            UdpTarget::from_path("/.well-known/masque/udp/2001%3Adb8%3A%3A42/443/").unwrap();
+        assert_eq!(target.host(), "2001:db8::42");
+        assert_eq!(target.to_string(), "[2001:db8::42]:443");
+
+        for path in [
+            "/.well-known/masque/udp/example.com/443",
+            "/.well-known/masque/udp/example.com/443/extra",
+            "/.well-known/masque/udp/example.com/443//",
+            "/.well-known/masque/udp/example.com/0/",
+            "/.well-known/masque/udp/example.com/65536/",
+            "/.well-known/masque/udp/example.com/port/",
+            "/.well-known/masque/udp//443/",
+            "/.well-known/masque/udp/example.com/",
+            "/.well-known/masque/ip/example.com/443/",
+            "/masque/udp/example.com/443/",
+            "/.well-known/masque/udp/bad%zz/443/",
+        ] {
+            assert_eq!(UdpTarget::from_path(path), None, "{path}");
+        }
+    }
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(percent_decode("example.com").unwrap(), "example.com");
+        assert_eq!(percent_decode("").unwrap(), "");
+        assert_eq!(percent_decode("2001%3adb8%3A%3A1").unwrap(), "2001:db8::1");
+        assert_eq!(percent_decode("%41%42c").unwrap(), "ABc");
+        assert_eq!(percent_decode("%C3%A9").unwrap(), "\u{e9}");
+
+        // Truncated or invalid escapes.
+        assert_eq!(percent_decode("%"), None);
+        assert_eq!(percent_decode("%4"), None);
+        assert_eq!(percent_decode("%g1"), None);
+        assert_eq!(percent_decode("%+1"), None);
+        // Not UTF-8.
+        assert_eq!(percent_decode("%ff"), None);
+    }
+}
@@ -0,0 +1,308 @@
+use crate::error::ConnectionError;
+use crate::error::StreamOpeningError;
+use crate::error::StreamReadExactError;
+use crate::error::StreamWriteError;
+use crate::framing;
+use crate::framing::ReadMessageError;
+use crate::Connection;
+use crate::RecvStream;
+use crate::SendStream;
+use bytes::Bytes;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::sync::oneshot;
+use tracing::debug;
+use wtransport_proto::varint::VarInt;
+
+/// Number of incoming objects buffered by [`ObjectReceiver`].
+const RECEIVER_CAPACITY: usize = 64;
+
+/// An error sending or receiving objects.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum ObjectError {
+    /// The connection is no longer available.
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+
+    /// The stream of the object cannot be opened.
+    #[error(transparent)]
+    StreamOpening(#[from] StreamOpeningError),
+
+    /// The stream of the object cannot be written.
+    #[error(transparent)]
+    Write(#[from] StreamWriteError),
+
+    /// The stream of the object cannot be read.
+    #[error(transparent)]
+    Read(#[from] StreamReadExactError),
+
+    /// The object is larger than the allowed size.
+    #[error("object too large")]
+    TooLarge,
+
+    /// The task writing a payload of [`ObjectSender::send_batch`] panicked or was cancelled.
+    #[error("object write task failed")]
+    WriteTaskFailed,
+}
+
+impl From<ReadMessageError> for ObjectError {
+    fn from(error: ReadMessageError) -> Self {
+        match error {
+            ReadMessageError::Read(error) => ObjectError::Read(error),
+            ReadMessageError::TooLarge => ObjectError::TooLarge,
+        }
+    }
+}
+
+/// Identification of an object, sent at the beginning of its stream.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ObjectHeader {
+    /// Track the object belongs to.
+    pub track: VarInt,
+
+    /// Group of the object in the track (e.g., a group of pictures).
+    pub group: VarInt,
+
+    /// Object sequence number in the group.
+    pub object: VarInt,
+}
+
+impl ObjectHeader {
+    fn encode(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(3 * VarInt::MAX_SIZE);
+        framing::put_varint(&mut buffer, self.track);
+        framing::put_varint(&mut buffer, self.group);
+        framing::put_varint(&mut buffer, self.object);
+        buffer
+    }
+
+    async fn read(stream: &mut RecvStream) -> Result<Self, ObjectError> {
+        Ok(Self {
+            track: read_varint(stream).await?,
+            group: read_varint(stream).await?,
+            object: read_varint(stream).await?,
+        })
+    }
+}
+
+/// An object to send, see [`ObjectSender`].
+#[derive(Clone, Debug)]
+pub struct Object {
+    /// Identification of the object.
+    pub header: ObjectHeader,
+
+    /// Send priority of the object's stream (see [`SendStream::set_priority`]).
+    ///
+    /// Streams with higher priority are sent first (e.g., the newest group).
+    pub priority: i32,
+
+    /// Payload of the object.
+    pub payload: Bytes,
+}
+
+/// Sends media-like objects, each on its own unidirectional stream.
+///
+/// Using one stream per object avoids head-of-line blocking between objects:
+/// a lost packet only delays its own object.
+pub struct ObjectSender {
+    connection: Arc<Connection>,
+}
+
+impl ObjectSender {
+    /// Creates a sender on `connection`.
+    pub fn new(connection: Arc<Connection>) -> Self {
+        Self { connection }
+    }
+
+    /// Sends an object, waiting for its payload to be written.
+    pub async fn send(&self, object: Object) -> Result<(), ObjectError> {
+        let stream = self.open(&object).await?;
+        write_object(stream, object.payload).await
+    }
+
+    /// Sends a batch of objects.
+    ///
+    /// All streams are opened and prioritized before writing any payload, so that
+    /// the objects of the batch are sent according to their priority.
+    /// Payloads are written concurrently, each on its own task.
+    pub async fn send_batch<I>(&self, objects: I) -> Result<(), ObjectError>
+    where
+        I: IntoIterator<Item = Object>,
+    {
+        let mut streams = Vec::new();
+
+        for object in objects {
+            let stream = self.open(&object).await?;
+            streams.push((stream, object.payload));
+        }
+
+        let writes = streams
+            .into_iter()
+            .map(|(stream, payload)| {
+                let (result_tx, result) = oneshot::channel();
+                self.connection.spawner().spawn(async move {
+                    let _ = result_tx.send(write_object(stream, payload).await);
+                });
+                result
+            })
+            .collect::<Vec<_>>();
+
+        for write in writes {
+            write.await.map_err(|_| ObjectError::WriteTaskFailed)??;
+        }
+
+        Ok(())
+    }
+
+    async fn open(&self, object: &Object) -> Result<SendStream, ObjectError> {
+        let mut stream = self.connection.open_uni().await?.await?;
+        stream.set_priority(object.priority);
+        stream.write_all(&object.header.encode()).await?;
+        Ok(stream)
+    }
+}
+
+async fn read_varint(stream: &mut RecvStream) -> Result<VarInt, StreamReadExactError> {
+    framing::read_varint(stream)
+        .await?
+        .ok_or(StreamReadExactError::FinishedEarly)
+}
+
+async fn write_object(mut stream: SendStream, payload: Bytes) -> Result<(), ObjectError> {
+    stream.write_all(&payload).await?;
+    stream.finish().await?;
+    Ok(())
+}
+
+/// An incoming object, see [`ObjectReceiver`].
+pub struct IncomingObject {
+    header: ObjectHeader,
+    stream: RecvStream,
+}
+
+impl IncomingObject {
+    /// Returns the identification of the object.
+    pub fn header(&self) -> ObjectHeader {
+        self.header
+    }
+
+    /// Returns the stream of the payload (e.g., for large objects).
+    pub fn into_stream(self) -> RecvStream {
+        self.stream
+    }
+
+    /// Reads the entire payload, up to `max_len` bytes.
+    pub async fn read_payload(mut self, max_len: usize) -> Result<Bytes, ObjectError> {
+        let payload = framing::read_to_end(&mut self.stream, max_len).await?;
+        Ok(Bytes::from(payload))
+    }
+}
+
+/// Receives the objects sent by the peer [`ObjectSender`].
+///
+/// The receiver takes over the incoming unidirectional streams of the connection.
+pub struct ObjectReceiver {
+    objects: mpsc::Receiver<IncomingObject>,
+}
+
+impl ObjectReceiver {
+    /// Creates a receiver on `connection`.
+    pub fn new(connection: Arc<Connection>) -> Self {
+        let (objects_tx, objects) = mpsc::channel(RECEIVER_CAPACITY);
+
+        let spawner = connection.spawner().clone();
+
+        spawner.spawn(async move {
+            while let Ok(mut stream) = connection.accept_uni().await {
+                let objects_tx = objects_tx.clone();
+
+                // Headers are read concurrently, so that a slow stream does not block the others.
+                connection.spawner().spawn(async move {
+                    match ObjectHeader::read(&mut stream).await {
+                        Ok(header) => {
+                            let _ = objects_tx.send(IncomingObject { header, stream }).await;
+                        }
+                        Err(error) => debug!("Invalid object stream: {}", error),
+                    }
+                });
+            }
+        });
+
+        Self { objects }
+    }
+
+    /// Receives the next object, in the order their headers are received.
+    ///
+    /// It returns [`None`] if the connection is closed.
+    pub async fn recv(&mut self) -> Option<IncomingObject> {
+        self.objects.recv().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+
+    fn object(group: u32, payload: &'static [u8]) -> Object {
+        Object {
+            header: ObjectHeader {
+                track: VarInt::from_u32(1),
+                group: VarInt::from_u32(group),
+                object: VarInt::from_u32(1_000_000),
+            },
+            priority: group as i32,
+            payload: Bytes::from_static(payload),
+        }
+    }
+
+    #[tokio::test]
+    async fn send_and_receive() {
+        let peers = test_utils::connect().await;
+        let sender = ObjectSender::new(Arc::new(peers.client_connection));
+        let mut receiver = ObjectReceiver::new(Arc::new(peers.server_connection));
+
+        sender.send(object(0, b"first")).await.unwrap();
+
+        let incoming = receiver.recv().await.unwrap();
+        assert_eq!(incoming.header(), object(0, b"").header);
+        assert_eq!(&incoming.read_payload(1024).await.unwrap()[..], b"first");
+
+        sender.send(object(1, b"too large")).await.unwrap();
+        let incoming = receiver.recv().await.unwrap();
+        assert!(matches!(
+            incoming.read_payload(4).await,
+            Err(ObjectError::TooLarge)
+        ));
+    }
+
+    #[tokio::test]
+    async fn send_batch() {
+        let peers = test_utils::connect().await;
+        let sender = ObjectSender::new(Arc::new(peers.client_connection));
+        let mut receiver = ObjectReceiver::new(Arc::new(peers.server_connection));
+
+        sender
+            .send_batch([object(0, b"a"), object(1, b"b"), object(2, b"")])
+            .await
+            .unwrap();
+
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            let incoming = receiver.recv().await.unwrap();
+            let group = incoming.header().group.into_inner();
+            received.push((group, incoming.read_payload(1024).await.unwrap()));
+        }
+        received.sort();
+
+        assert_eq!(
+            received,
+            [
+                (0, Bytes::from_static(b"a")),
+                (1, Bytes::from_static(b"b")),
+                (2, Bytes::new())
+            ]
+        );
+    }
+}
@@ -0,0 +1,516 @@
+use crate::error::ConnectionError;
+use crate::error::StreamOpeningError;
+use crate::error::StreamReadError;
+use crate::error::StreamReadExactError;
+use crate::error::StreamWriteError;
+use crate::framing;
+use crate::framing::ReadMessageError;
+use crate::Connection;
+use crate::RecvStream;
+use crate::SendStream;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::debug;
+use wtransport_proto::varint::VarInt;
+
+/// Maximum size of a message.
+const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Maximum number of channels accepted by an [`OrderedListener`].
+const MAX_CHANNELS: usize = 1024;
+
+/// Time given to an incoming stream to send its channel identifier.
+const HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time given to the sender to resume an interrupted channel on a new stream.
+const RESUME_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An error of an ordered channel.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum OrderedError {
+    /// The connection is no longer available.
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+
+    /// The stream cannot be opened.
+    #[error(transparent)]
+    StreamOpening(#[from] StreamOpeningError),
+
+    /// The stream cannot be written.
+    #[error(transparent)]
+    Write(#[from] StreamWriteError),
+
+    /// The stream cannot be read.
+    #[error(transparent)]
+    Read(#[from] StreamReadExactError),
+
+    /// A message is larger than the allowed size.
+    #[error("message too large")]
+    TooLarge,
+
+    /// Messages were lost: the stream was resumed after the replay window of the sender.
+    #[error("messages lost (expected {expected}, resumed at {resumed})")]
+    Gap {
+        /// Sequence number of the next expected message.
+        expected: u64,
+
+        /// Sequence number of the first message after resumption.
+        resumed: u64,
+    },
+
+    /// The channel was interrupted and no new stream resumed it in time.
+    #[error("channel interrupted")]
+    Interrupted,
+}
+
+impl From<ReadMessageError> for OrderedError {
+    fn from(error: ReadMessageError) -> Self {
+        match error {
+            ReadMessageError::Read(error) => OrderedError::Read(error),
+            ReadMessageError::TooLarge => OrderedError::TooLarge,
+        }
+    }
+}
+
+/// Sending side of an ordered, reliable message channel.
+///
+/// Messages are sent, in order, on a long-lived unidirectional stream. Each stream starts
+/// with the channel identifier, and each message is prefixed by its sequence number.
+///
+/// If the peer stops the stream, the sender transparently opens a new one and replays
+/// the last messages (up to the replay window); the receiver skips the duplicates.
+pub struct OrderedSender {
+    connection: Arc<Connection>,
+    channel_id: VarInt,
+    stream: Option<SendStream>,
+    next_seq: u64,
+    replay: VecDeque<(u64, Bytes)>,
+    replay_window: usize,
+}
+
+impl OrderedSender {
+    /// Opens the channel `channel_id` on `connection`.
+    ///
+    /// The last `replay_window` messages are kept, in order to be replayed on a new stream.
+    pub async fn open(
+        connection: Arc<Connection>,
+        channel_id: VarInt,
+        replay_window: usize,
+    ) -> Result<Self, OrderedError> {
+        let mut sender = Self {
+            connection,
+            channel_id,
+            stream: None,
+            next_seq: 0,
+            replay: VecDeque::with_capacity(replay_window),
+            replay_window,
+        };
+
+        sender.stream = Some(sender.open_stream().await?);
+
+        Ok(sender)
+    }
+
+    /// Returns the channel identifier.
+    pub fn channel_id(&self) -> VarInt {
+        self.channel_id
+    }
+
+    /// Sends a message.
+    pub async fn send<P>(&mut self, payload: P) -> Result<(), OrderedError>
+    where
+        P: Into<Bytes>,
+    {
+        let seq = self.next_seq;
+        let payload = payload.into();
+        self.next_seq += 1;
+
+        if self.replay_window > 0 {
+            if self.replay.len() == self.replay_window {
+                self.replay.pop_front();
+            }
+            self.replay.push_back((seq, payload.clone()));
+        }
+
+        let stream = self.stream.as_mut().expect("Stream is open");
+
+        match write_entry(stream, seq, &payload).await {
+            Ok(()) => Ok(()),
+            Err(StreamWriteError::Stopped(code)) => {
+                debug!("Ordered channel stream stopped (code: {}): resuming", code);
+                self.resume(seq, &payload).await
+            }
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Finishes the channel: the receiver gets all messages, then the end of channel.
+    pub async fn finish(mut self) -> Result<(), OrderedError> {
+        let mut stream = self.stream.take().expect("Stream is open");
+        stream.finish().await?;
+        Ok(())
+    }
+
+    async fn resume(&mut self, seq: u64, payload: &Bytes) -> Result<(), OrderedError> {
+        let mut stream = self.open_stream().await?;
+
+        if self.replay.is_empty() {
+            write_entry(&mut stream, seq, payload).await?;
+        }
+
+        for (seq, payload) in &self.replay {
+            write_entry(&mut stream, *seq, payload).await?;
+        }
+
+        self.stream = Some(stream);
+
+        Ok(())
+    }
+
+    async fn open_stream(&self) -> Result<SendStream, OrderedError> {
+        let mut stream = self.connection.open_uni().await?.await?;
+
+        let mut header = Vec::new();
+        framing::put_varint(&mut header, self.channel_id);
+        stream.write_all(&header).await?;
+
+        Ok(stream)
+    }
+}
+
+async fn write_entry(
+    stream: &mut SendStream,
+    seq: u64,
+    payload: &[u8],
+) -> Result<(), StreamWriteError> {
+    let mut buffer = Vec::new();
+    framing::put_varint(
+        &mut buffer,
+        VarInt::try_from(seq).expect("Sequence numbers exhausted"),
+    );
+    stream.write_all(&buffer).await?;
+
+    framing::write_message(stream, payload).await
+}
+
+/// Receiving side of an ordered, reliable message channel.
+///
+/// Created by [`OrderedListener::accept`].
+pub struct OrderedReceiver {
+    channel_id: VarInt,
+    stream: Option<RecvStream>,
+    streams: mpsc::UnboundedReceiver<RecvStream>,
+    next_seq: u64,
+    resume_timeout: Duration,
+}
+
+impl OrderedReceiver {
+    /// Returns the channel identifier.
+    pub fn channel_id(&self) -> VarInt {
+        self.channel_id
+    }
+
+    /// Receives the next message.
+    ///
+    /// It returns [`None`] once the sender finished the channel.
+    ///
+    /// If the stream is reset and the sender does not resume the channel on a new stream
+    /// within 10 seconds, it returns [`OrderedError::Interrupted`].
+    pub async fn recv(&mut self) -> Result<Option<Bytes>, OrderedError> {
+        loop {
+            let stream = match self.stream.as_mut() {
+                Some(stream) => stream,
+                None => {
+                    let stream = tokio::time::timeout(self.resume_timeout, self.streams.recv())
+                        .await
+                        .ok()
+                        .flatten()
+                        .ok_or(OrderedError::Interrupted)?;
+                    self.stream.insert(stream)
+                }
+            };
+
+            match read_entry(stream).await {
+                Ok(Some((seq, _))) if seq < self.next_seq => continue,
+                Ok(Some((seq, payload))) if seq == self.next_seq => {
+                    self.next_seq += 1;
+                    return Ok(Some(payload));
+                }
+                Ok(Some((seq, _))) => {
+                    return Err(OrderedError::Gap {
+                        expected: self.next_seq,
+                        resumed: seq,
+                    })
+                }
+                Ok(None) => return Ok(None),
+                Err(OrderedError::Read(StreamReadExactError::Read(StreamReadError::Reset(
+                    code,
+                )))) => {
+                    debug!("Ordered channel stream reset (code: {}): resuming", code);
+                    self.stream = None;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+async fn read_entry(stream: &mut RecvStream) -> Result<Option<(u64, Bytes)>, OrderedError> {
+    let seq = match framing::read_varint(stream).await? {
+        Some(seq) => seq.into_inner(),
+        None => return Ok(None),
+    };
+
+    let payload = framing::read_message(stream, MAX_MESSAGE_SIZE)
+        .await?
+        .ok_or(StreamReadExactError::FinishedEarly)?;
+
+    Ok(Some((seq, Bytes::from(payload))))
+}
+
+/// Accepts the ordered channels opened by the peer.
+///
+/// The listener takes over the incoming unidirectional streams of the connection,
+/// routing them to channels by identifier.
+///
+/// Channel identifiers are read concurrently: a stream not sending its identifier within
+/// 10 seconds is dropped. At most 1024 channels can be open at the same time; streams of
+/// further channels are dropped.
+pub struct OrderedListener {
+    receivers: mpsc::UnboundedReceiver<OrderedReceiver>,
+}
+
+impl OrderedListener {
+    /// Creates a listener for the channels of `connection`.
+    pub fn new(connection: Arc<Connection>) -> Self {
+        let (receivers_tx, receivers) = mpsc::unbounded_channel();
+
+        let spawner = connection.spawner().clone();
+
+        spawner.spawn(async move {
+            let (headers_tx, mut headers) = mpsc::unbounded_channel();
+            let mut channels = Channels::default();
+
+            loop {
+                tokio::select! {
+                    stream = connection.accept_uni() => {
+                        let mut stream = match stream {
+                            Ok(stream) => stream,
+                            Err(_) => return,
+                        };
+
+                        let headers_tx = headers_tx.clone();
+                        connection.spawner().spawn(async move {
+                            let channel_id = tokio::time::timeout(
+                                HEADER_TIMEOUT,
+                                framing::read_varint(&mut stream),
+                            )
+                            .await;
+
+                            match channel_id {
+                                Ok(Ok(Some(channel_id))) => {
+                                    let _ = headers_tx.send((channel_id, stream));
+                                }
+                                Ok(_) => {}
+                                Err(_) => debug!("Ordered channel header timed out"),
+                            }
+                        });
+                    }
+                    Some((channel_id, stream)) = headers.recv() => {
+                        if let Some(receiver) = channels.route(channel_id, stream) {
+                            if receivers_tx.send(receiver).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                }
+            }
+        });
+
+        Self { receivers }
+    }
+
+    /// Accepts the next channel opened by the peer.
+    ///
+    /// It returns [`None`] if the connection is closed.
+    pub async fn accept(&mut self) -> Option<OrderedReceiver> {
+        self.receivers.recv().await
+    }
+}
+
+/// Streams of the channels accepted by an [`OrderedListener`], by identifier.
+#[derive(Default)]
+struct Channels(HashMap<VarInt, mpsc::UnboundedSender<RecvStream>>);
+
+impl Channels {
+    /// Routes `stream` to its channel, returning the receiver of a new channel.
+    fn route(&mut self, channel_id: VarInt, stream: RecvStream) -> Option<OrderedReceiver> {
+        if let Some(streams) = self.0.get(&channel_id) {
+            if streams.send(stream).is_err() {
+                self.0.remove(&channel_id);
+            }
+            return None;
+        }
+
+        if self.0.len() >= MAX_CHANNELS {
+            self.0.retain(|_, streams| !streams.is_closed());
+
+            if self.0.len() >= MAX_CHANNELS {
+                debug!("Too many ordered channels, dropping {}", channel_id);
+                return None;
+            }
+        }
+
+        let (streams_tx, streams) = mpsc::unbounded_channel();
+        self.0.insert(channel_id, streams_tx);
+
+        Some(OrderedReceiver {
+            channel_id,
+            stream: Some(stream),
+            streams,
+            next_seq: 0,
+            resume_timeout: RESUME_TIMEOUT,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::endpoint::Client;
+    use crate::endpoint::Server;
+    use crate::test_utils::Peers;
+    use crate::Endpoint;
+
+    /// Keeps the endpoints alive.
+    type Endpoints = (Endpoint<Server>, Endpoint<Client>);
+
+    async fn listen() -> (Endpoints, Arc<Connection>, OrderedListener) {
+        let Peers {
+            server,
+            client,
+            server_connection,
+            client_connection,
+        } = crate::test_utils::connect().await;
+
+        let listener = OrderedListener::new(Arc::new(server_connection));
+        ((server, client), Arc::new(client_connection), listener)
+    }
+
+    async fn channel(replay_window: usize) -> (Endpoints, OrderedSender, OrderedReceiver) {
+        let (endpoints, connection, mut listener) = listen().await;
+
+        let mut sender = OrderedSender::open(connection, VarInt::from_u32(7), replay_window)
+            .await
+            .unwrap();
+        sender.send(&b"a"[..]).await.unwrap();
+
+        let mut receiver = listener.accept().await.unwrap();
+        assert_eq!(receiver.channel_id(), VarInt::from_u32(7));
+        assert_eq!(&receiver.recv().await.unwrap().unwrap()[..], b"a");
+
+        (endpoints, sender, receiver)
+    }
+
+    /// Stops the current stream of `receiver`, and awaits the sender observes it.
+    async fn interrupt(sender: &mut OrderedSender, receiver: &mut OrderedReceiver) {
+        receiver.stream.take().unwrap().stop(VarInt::from_u32(0));
+
+        // Bytes written on the stopped stream are never read.
+        let stream = sender.stream.as_mut().unwrap();
+        while !matches!(stream.write(&[0]).await, Err(StreamWriteError::Stopped(_))) {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn resume_replays_messages() {
+        let (_endpoints, mut sender, mut receiver) = channel(2).await;
+
+        sender.send(&b"b"[..]).await.unwrap();
+        assert_eq!(&receiver.recv().await.unwrap().unwrap()[..], b"b");
+
+        interrupt(&mut sender, &mut receiver).await;
+
+        // Resumed on a new stream, replaying "b" (skipped by the receiver) and "c".
+        sender.send(&b"c"[..]).await.unwrap();
+        sender.send(&b"d"[..]).await.unwrap();
+        assert_eq!(&receiver.recv().await.unwrap().unwrap()[..], b"c");
+        assert_eq!(&receiver.recv().await.unwrap().unwrap()[..], b"d");
+
+        sender.finish().await.unwrap();
+        assert!(receiver.recv().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn resume_without_replay() {
+        let (_endpoints, mut sender, mut receiver) = channel(0).await;
+
+        interrupt(&mut sender, &mut receiver).await;
+
+        sender.send(&b"b"[..]).await.unwrap();
+        assert_eq!(&receiver.recv().await.unwrap().unwrap()[..], b"b");
+    }
+
+    #[tokio::test]
+    async fn gap() {
+        let (_endpoints, connection, mut listener) = listen().await;
+
+        let mut stream = connection.open_uni().await.unwrap().await.unwrap();
+        let mut header = Vec::new();
+        framing::put_varint(&mut header, VarInt::from_u32(1));
+        stream.write_all(&header).await.unwrap();
+        write_entry(&mut stream, 0, b"a").await.unwrap();
+        write_entry(&mut stream, 2, b"c").await.unwrap();
+
+        let mut receiver = listener.accept().await.unwrap();
+        assert_eq!(&receiver.recv().await.unwrap().unwrap()[..], b"a");
+        assert!(matches!(
+            receiver.recv().await,
+            Err(OrderedError::Gap {
+                expected: 1,
+                resumed: 2
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn stalled_header() {
+        let (_endpoints, connection, mut listener) = listen().await;
+
+        // Only the first byte of a 2-byte channel identifier.
+        let mut stalled = connection.open_uni().await.unwrap().await.unwrap();
+        stalled.write_all(&[0x40]).await.unwrap();
+
+        let mut sender = OrderedSender::open(connection, VarInt::from_u32(3), 0)
+            .await
+            .unwrap();
+        sender.send(&b"a"[..]).await.unwrap();
+
+        let mut receiver = tokio::time::timeout(Duration::from_secs(1), listener.accept())
+            .await
+            .expect("Not blocked by the stalled stream")
+            .unwrap();
+        assert_eq!(receiver.channel_id(), VarInt::from_u32(3));
+        assert_eq!(&receiver.recv().await.unwrap().unwrap()[..], b"a");
+    }
+
+    #[tokio::test]
+    async fn reset_without_resumption() {
+        let (_endpoints, mut sender, mut receiver) = channel(0).await;
+        receiver.resume_timeout = Duration::from_millis(100);
+
+        // The sender gives up the channel, yet the connection stays open.
+        sender.stream.take().unwrap().reset(VarInt::from_u32(0));
+
+        let result = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .expect("Not blocked until the connection is closed");
+        assert!(matches!(result, Err(OrderedError::Interrupted)));
+    }
+}
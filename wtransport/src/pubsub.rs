@@ -0,0 +1,604 @@
+use crate::error::ConnectionError;
+use crate::error::SendDatagramError;
+use crate::error::StreamOpeningError;
+use crate::error::StreamWriteError;
+use crate::framing;
+use crate::framing::ReadMessageError;
+use crate::Connection;
+use crate::RecvStream;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+use tracing::debug;
+use wtransport_proto::varint::VarInt;
+
+/// Maximum size of a control message.
+const MAX_CONTROL_MESSAGE_SIZE: usize = 4096;
+
+/// Maximum size of a payload delivered on a stream.
+///
+/// Each incoming stream buffers its payload, up to this size, before delivering it.
+const MAX_STREAM_PAYLOAD_SIZE: usize = 1024 * 1024;
+
+/// Maximum number of subscriptions over a session.
+const MAX_SUBSCRIPTIONS: usize = 1024;
+
+/// Number of payloads buffered for each [`Subscription`].
+const SUBSCRIPTION_CAPACITY: usize = 64;
+
+/// How a published payload is delivered to subscribers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Delivery {
+    /// Unreliable and unordered delivery, with a datagram.
+    ///
+    /// The payload must fit into a datagram.
+    Datagram,
+
+    /// Reliable delivery, with a unidirectional stream.
+    ///
+    /// Payloads published on different streams might be received out of order.
+    Stream,
+}
+
+/// An error in the pub/sub layer.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum PubSubError {
+    /// The connection is no longer available.
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+
+    /// The control stream, or a payload stream, cannot be opened.
+    #[error(transparent)]
+    StreamOpening(#[from] StreamOpeningError),
+
+    /// The control stream, or a payload stream, cannot be written.
+    #[error(transparent)]
+    Write(#[from] StreamWriteError),
+
+    /// The payload cannot be sent as a datagram.
+    #[error(transparent)]
+    Datagram(#[from] SendDatagramError),
+
+    /// The control stream has been closed.
+    #[error("control stream closed")]
+    ControlClosed,
+
+    /// The maximum number of subscriptions has been reached.
+    #[error("too many subscriptions")]
+    TooManySubscriptions,
+}
+
+/// Control message, sent by [`Subscriber`] to [`Publisher`].
+#[derive(Debug, PartialEq, Eq)]
+enum ControlMessage {
+    Subscribe { id: VarInt, channel: String },
+    Unsubscribe { id: VarInt },
+}
+
+impl ControlMessage {
+    const SUBSCRIBE: VarInt = VarInt::from_u32(0x00);
+    const UNSUBSCRIBE: VarInt = VarInt::from_u32(0x01);
+
+    fn encode(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+
+        match self {
+            ControlMessage::Subscribe { id, channel } => {
+                framing::put_varint(&mut buffer, Self::SUBSCRIBE);
+                framing::put_varint(&mut buffer, *id);
+                buffer.extend_from_slice(channel.as_bytes());
+            }
+            ControlMessage::Unsubscribe { id } => {
+                framing::put_varint(&mut buffer, Self::UNSUBSCRIBE);
+                framing::put_varint(&mut buffer, *id);
+            }
+        }
+
+        buffer
+    }
+
+    fn decode(mut bytes: &[u8]) -> Option<Self> {
+        let message_type = framing::get_varint(&mut bytes)?;
+        let id = framing::get_varint(&mut bytes)?;
+
+        match message_type {
+            Self::SUBSCRIBE => Some(ControlMessage::Subscribe {
+                id,
+                channel: std::str::from_utf8(bytes).ok()?.to_string(),
+            }),
+            Self::UNSUBSCRIBE if bytes.is_empty() => Some(ControlMessage::Unsubscribe { id }),
+            _ => None,
+        }
+    }
+}
+
+type Channels = Arc<Mutex<HashMap<VarInt, mpsc::Sender<Bytes>>>>;
+
+/// Subscribing side of named channels over a session.
+///
+/// The subscriber opens a bidirectional *control* stream, on which it sends
+/// subscribe and unsubscribe messages to the peer [`Publisher`].
+/// Payloads are received on datagrams and unidirectional streams, tagged with
+/// the identifier of the subscription.
+///
+/// Once created, the subscriber takes over the datagrams and the incoming
+/// unidirectional streams of the connection.
+pub struct Subscriber {
+    channels: Channels,
+    control: mpsc::UnboundedSender<ControlMessage>,
+    next_id: Mutex<u64>,
+}
+
+impl Subscriber {
+    /// Creates a subscriber, opening the control stream on `connection`.
+    pub async fn new(connection: Arc<Connection>) -> Result<Self, PubSubError> {
+        let (mut control_stream, _) = connection.open_bi().await?.await?;
+        let (control, mut control_rx) = mpsc::unbounded_channel::<ControlMessage>();
+        let channels = Channels::default();
+
+        let spawner = connection.spawner().clone();
+
+        spawner.spawn(async move {
+            while let Some(message) = control_rx.recv().await {
+                if let Err(error) =
+                    framing::write_message(&mut control_stream, &message.encode()).await
+                {
+                    debug!("Pub/sub control stream closed: {}", error);
+                    return;
+                }
+            }
+        });
+
+        spawner.spawn(Self::receive_datagrams(
+            connection.clone(),
+            channels.clone(),
+        ));
+        spawner.spawn(Self::receive_streams(connection, channels.clone()));
+
+        Ok(Self {
+            channels,
+            control,
+            next_id: Mutex::new(0),
+        })
+    }
+
+    /// Subscribes to `channel`.
+    ///
+    /// Payloads published by the peer after it receives the subscription are
+    /// delivered to the returned [`Subscription`].
+    ///
+    /// At most 1024 subscriptions can be active at the same time.
+    pub fn subscribe(&self, channel: &str) -> Result<Subscription, PubSubError> {
+        let mut channels = self.channels.lock().unwrap();
+        if channels.len() >= MAX_SUBSCRIPTIONS {
+            return Err(PubSubError::TooManySubscriptions);
+        }
+
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            let id = VarInt::try_from(*next_id).expect("Subscription ids exhausted");
+            *next_id += 1;
+            id
+        };
+
+        // Registered before subscribing, so that no payload is lost.
+        let (sender, receiver) = mpsc::channel(SUBSCRIPTION_CAPACITY);
+        channels.insert(id, sender);
+
+        self.control
+            .send(ControlMessage::Subscribe {
+                id,
+                channel: channel.to_string(),
+            })
+            .map_err(|_| {
+                channels.remove(&id);
+                PubSubError::ControlClosed
+            })?;
+        drop(channels);
+
+        Ok(Subscription {
+            id,
+            channel: channel.to_string(),
+            receiver,
+            channels: self.channels.clone(),
+            control: self.control.clone(),
+        })
+    }
+
+    async fn receive_datagrams(connection: Arc<Connection>, channels: Channels) {
+        while let Ok(datagram) = connection.receive_datagram().await {
+            let mut bytes = &*datagram;
+            let id = match framing::get_varint(&mut bytes) {
+                Some(id) => id,
+                None => continue,
+            };
+
+            let payload = datagram.payload().slice(datagram.len() - bytes.len()..);
+
+            if let Some(sender) = channels.lock().unwrap().get(&id) {
+                // Datagrams are unreliable: they are dropped if the subscription is lagging.
+                let _ = sender.try_send(payload);
+            }
+        }
+    }
+
+    async fn receive_streams(connection: Arc<Connection>, channels: Channels) {
+        while let Ok(stream) = connection.accept_uni().await {
+            connection
+                .spawner()
+                .spawn(Self::receive_stream(stream, channels.clone()));
+        }
+    }
+
+    async fn receive_stream(mut stream: RecvStream, channels: Channels) {
+        let id = match framing::read_varint(&mut stream).await {
+            Ok(Some(id)) => id,
+            _ => return,
+        };
+
+        let payload = match framing::read_to_end(&mut stream, MAX_STREAM_PAYLOAD_SIZE).await {
+            Ok(payload) => Bytes::from(payload),
+            Err(error) => {
+                debug!("Pub/sub payload stream failed: {:?}", error);
+                return;
+            }
+        };
+
+        let sender = channels.lock().unwrap().get(&id).cloned();
+
+        if let Some(sender) = sender {
+            let _ = sender.send(payload).await;
+        }
+    }
+}
+
+/// A subscription to a channel, created by [`Subscriber::subscribe`].
+///
+/// Dropping it unsubscribes from the channel.
+pub struct Subscription {
+    id: VarInt,
+    channel: String,
+    receiver: mpsc::Receiver<Bytes>,
+    channels: Channels,
+    control: mpsc::UnboundedSender<ControlMessage>,
+}
+
+impl Subscription {
+    /// Returns the name of the channel.
+    pub fn channel(&self) -> &str {
+        &self.channel
+    }
+
+    /// Receives the next payload.
+    ///
+    /// It returns [`None`] if the connection is closed.
+    pub async fn recv(&mut self) -> Option<Bytes> {
+        self.receiver.recv().await
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.channels.lock().unwrap().remove(&self.id);
+        let _ = self
+            .control
+            .send(ControlMessage::Unsubscribe { id: self.id });
+    }
+}
+
+/// Publishing side of named channels over a session.
+///
+/// The publisher accepts the control stream opened by the peer [`Subscriber`],
+/// and it tracks its subscriptions.
+pub struct Publisher {
+    connection: Arc<Connection>,
+    subscriptions: Arc<Mutex<Subscriptions>>,
+}
+
+#[derive(Default)]
+struct Subscriptions {
+    by_channel: HashMap<String, Vec<VarInt>>,
+    by_id: HashMap<VarInt, String>,
+}
+
+impl Publisher {
+    /// Creates a publisher, accepting the control stream on `connection`.
+    pub async fn new(connection: Arc<Connection>) -> Result<Self, PubSubError> {
+        let (_, mut control_stream) = connection.accept_bi().await?;
+        let subscriptions = Arc::new(Mutex::new(Subscriptions::default()));
+
+        let task_subscriptions = subscriptions.clone();
+        connection.spawner().spawn(async move {
+            loop {
+                let message = match framing::read_message(
+                    &mut control_stream,
+                    MAX_CONTROL_MESSAGE_SIZE,
+                )
+                .await
+                {
+                    Ok(Some(message)) => message,
+                    Ok(None) => return,
+                    Err(ReadMessageError::Read(error)) => {
+                        debug!("Pub/sub control stream closed: {}", error);
+                        return;
+                    }
+                    Err(ReadMessageError::TooLarge) => {
+                        debug!("Pub/sub control message too large");
+                        return;
+                    }
+                };
+
+                match ControlMessage::decode(&message) {
+                    Some(message) => task_subscriptions.lock().unwrap().apply(message),
+                    None => debug!("Invalid pub/sub control message"),
+                }
+            }
+        });
+
+        Ok(Self {
+            connection,
+            subscriptions,
+        })
+    }
+
+    /// Returns `true` if the peer is subscribed to `channel`.
+    pub fn is_subscribed(&self, channel: &str) -> bool {
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .by_channel
+            .contains_key(channel)
+    }
+
+    /// Publishes `payload` on `channel`.
+    ///
+    /// It returns the number of subscriptions the payload was sent to
+    /// (`0` if the peer is not subscribed).
+    pub async fn publish(
+        &self,
+        channel: &str,
+        payload: &[u8],
+        delivery: Delivery,
+    ) -> Result<usize, PubSubError> {
+        let ids = self
+            .subscriptions
+            .lock()
+            .unwrap()
+            .by_channel
+            .get(channel)
+            .cloned()
+            .unwrap_or_default();
+
+        for id in &ids {
+            let mut buffer = Vec::with_capacity(VarInt::MAX_SIZE + payload.len());
+            framing::put_varint(&mut buffer, *id);
+
+            match delivery {
+                Delivery::Datagram => {
+                    buffer.extend_from_slice(payload);
+                    self.connection.send_datagram(buffer)?;
+                }
+                Delivery::Stream => {
+                    let mut stream = self.connection.open_uni().await?.await?;
+                    stream.write_all(&buffer).await?;
+                    stream.write_all(payload).await?;
+                    stream.finish().await?;
+                }
+            }
+        }
+
+        Ok(ids.len())
+    }
+}
+
+impl Subscriptions {
+    fn apply(&mut self, message: ControlMessage) {
+        match message {
+            ControlMessage::Subscribe { id, channel } => {
+                // A subscription reusing an id replaces the previous one.
+                self.remove(id);
+
+                if self.by_id.len() >= MAX_SUBSCRIPTIONS {
+                    debug!("Too many pub/sub subscriptions, ignoring {}", id);
+                    return;
+                }
+
+                self.by_channel.entry(channel.clone()).or_default().push(id);
+                self.by_id.insert(id, channel);
+            }
+            ControlMessage::Unsubscribe { id } => self.remove(id),
+        }
+    }
+
+    fn remove(&mut self, id: VarInt) {
+        if let Some(channel) = self.by_id.remove(&id) {
+            if let Some(ids) = self.by_channel.get_mut(&channel) {
+                ids.retain(|other| *other != id);
+
+                if ids.is_empty() {
+                    self.by_channel.remove(&channel);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+    use std::time::Duration;
+
+    async fn pubsub() -> (Subscriber, Publisher) {
+        let peers = test_utils::connect().await;
+        let subscriber = Subscriber::new(Arc::new(peers.client_connection))
+            .await
+            .expect("Control stream opened");
+
+        // The control stream is visible to the peer once written.
+        let first = subscriber.subscribe("").expect("Subscribed");
+        let publisher = Publisher::new(Arc::new(peers.server_connection))
+            .await
+            .expect("Control stream accepted");
+        drop(first);
+        wait_subscriptions(&publisher, 0).await;
+
+        (subscriber, publisher)
+    }
+
+    async fn wait_subscriptions(publisher: &Publisher, count: usize) {
+        while publisher.subscriptions.lock().unwrap().by_id.len() != count {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+
+    #[test]
+    fn control_message() {
+        let messages = [
+            ControlMessage::Subscribe {
+                id: VarInt::from_u32(42),
+                channel: "news/sport".to_string(),
+            },
+            ControlMessage::Unsubscribe {
+                id: VarInt::from_u32(1_000_000),
+            },
+        ];
+
+        for message in messages {
+            assert_eq!(ControlMessage::decode(&message.encode()), Some(message));
+        }
+
+        assert_eq!(ControlMessage::decode(&[0x05, 0x00]), None);
+    }
+
+    #[test]
+    fn subscriptions() {
+        let mut subscriptions = Subscriptions::default();
+
+        for id in 0..2 {
+            subscriptions.apply(ControlMessage::Subscribe {
+                id: VarInt::from_u32(id),
+                channel: "a".to_string(),
+            });
+        }
+
+        subscriptions.apply(ControlMessage::Unsubscribe {
+            id: VarInt::from_u32(0),
+        });
+        assert_eq!(subscriptions.by_channel["a"], [VarInt::from_u32(1)]);
+
+        subscriptions.apply(ControlMessage::Unsubscribe {
+            id: VarInt::from_u32(1),
+        });
+        assert!(subscriptions.by_channel.is_empty());
+        assert!(subscriptions.by_id.is_empty());
+    }
+
+    #[test]
+    fn duplicate_subscription_id() {
+        let mut subscriptions = Subscriptions::default();
+
+        for channel in ["a", "b"] {
+            subscriptions.apply(ControlMessage::Subscribe {
+                id: VarInt::from_u32(0),
+                channel: channel.to_string(),
+            });
+        }
+
+        assert!(!subscriptions.by_channel.contains_key("a"));
+        assert_eq!(subscriptions.by_channel["b"], [VarInt::from_u32(0)]);
+        assert_eq!(subscriptions.by_id.len(), 1);
+    }
+
+    #[test]
+    fn max_subscriptions() {
+        let mut subscriptions = Subscriptions::default();
+
+        for id in 0..=MAX_SUBSCRIPTIONS as u32 {
+            subscriptions.apply(ControlMessage::Subscribe {
+                id: VarInt::from_u32(id),
+                channel: id.to_string(),
+            });
+        }
+
+        assert_eq!(subscriptions.by_id.len(), MAX_SUBSCRIPTIONS);
+        assert_eq!(subscriptions.by_channel.len(), MAX_SUBSCRIPTIONS);
+    }
+
+    #[tokio::test]
+    async fn fan_out() {
+        let (subscriber, publisher) = pubsub().await;
+
+        let mut a1 = subscriber.subscribe("a").unwrap();
+        let mut a2 = subscriber.subscribe("a").unwrap();
+        let mut b = subscriber.subscribe("b").unwrap();
+        wait_subscriptions(&publisher, 3).await;
+        assert!(publisher.is_subscribed("a"));
+        assert!(!publisher.is_subscribed("c"));
+
+        let sent = publisher
+            .publish("a", b"stream", Delivery::Stream)
+            .await
+            .unwrap();
+        assert_eq!(sent, 2);
+        assert_eq!(&a1.recv().await.unwrap()[..], b"stream");
+        assert_eq!(&a2.recv().await.unwrap()[..], b"stream");
+
+        let sent = publisher
+            .publish("b", b"datagram", Delivery::Datagram)
+            .await
+            .unwrap();
+        assert_eq!(sent, 1);
+        assert_eq!(&b.recv().await.unwrap()[..], b"datagram");
+        assert!(a1.receiver.try_recv().is_err());
+
+        assert_eq!(
+            publisher.publish("c", b"", Delivery::Stream).await.unwrap(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn unsubscribe() {
+        let (subscriber, publisher) = pubsub().await;
+
+        let a1 = subscriber.subscribe("a").unwrap();
+        let mut a2 = subscriber.subscribe("a").unwrap();
+        wait_subscriptions(&publisher, 2).await;
+
+        drop(a1);
+        wait_subscriptions(&publisher, 1).await;
+        assert!(publisher.is_subscribed("a"));
+
+        let sent = publisher
+            .publish("a", b"payload", Delivery::Stream)
+            .await
+            .unwrap();
+        assert_eq!(sent, 1);
+        assert_eq!(&a2.recv().await.unwrap()[..], b"payload");
+
+        drop(a2);
+        wait_subscriptions(&publisher, 0).await;
+        assert!(!publisher.is_subscribed("a"));
+    }
+
+    #[tokio::test]
+    async fn too_many_subscriptions() {
+        let (subscriber, _publisher) = pubsub().await;
+
+        let subscriptions = (0..MAX_SUBSCRIPTIONS)
+            .map(|_| subscriber.subscribe("a").unwrap())
+            .collect::<Vec<_>>();
+
+        assert!(matches!(
+            subscriber.subscribe("a"),
+            Err(PubSubError::TooManySubscriptions)
+        ));
+
+        drop(subscriptions);
+        assert!(subscriber.subscribe("a").is_ok());
+    }
+}
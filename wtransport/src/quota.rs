@@ -0,0 +1,511 @@
+use crate::driver::utils::varint_w2q;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::debug;
+use wtransport_proto::error::ErrorCode;
+
+/// Limits enforced for each principal by [`Quotas`].
+///
+/// Every limit is disabled by default.
+#[derive(Copy, Clone, Debug)]
+pub struct QuotaLimits {
+    max_sessions: Option<usize>,
+    max_streams: Option<usize>,
+    max_bytes: Option<u64>,
+    max_principals: usize,
+    accounting_interval: Duration,
+}
+
+impl QuotaLimits {
+    /// Default interval between two accountings of the bytes of a session.
+    pub const DEFAULT_ACCOUNTING_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Default maximum number of principals tracked.
+    pub const DEFAULT_MAX_PRINCIPALS: usize = 65_536;
+
+    /// Creates limits, all disabled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of sessions of a principal.
+    ///
+    /// Further session requests are rejected with `429` (Too Many Requests).
+    pub fn max_sessions(mut self, value: usize) -> Self {
+        self.max_sessions = Some(value);
+        self
+    }
+
+    /// Sets the maximum number of streams in use by the sessions of a principal.
+    ///
+    /// Streams opened by the peer beyond the limit are refused (stopped with
+    /// `H3_REQUEST_REJECTED`) when accepted. Streams opened locally are counted, but never
+    /// refused.
+    pub fn max_streams(mut self, value: usize) -> Self {
+        self.max_streams = Some(value);
+        self
+    }
+
+    /// Sets the maximum number of bytes (sent and received, as UDP payload) by the
+    /// sessions of a principal, cumulated since its first session
+    /// (or since [`Quotas::reset`]).
+    ///
+    /// Once exceeded, the sessions are closed with `H3_EXCESSIVE_LOAD`, and further
+    /// session requests are rejected.
+    pub fn max_bytes(mut self, value: u64) -> Self {
+        self.max_bytes = Some(value);
+        self
+    }
+
+    /// Sets the maximum number of principals tracked (at least 1).
+    ///
+    /// Principals without session are remembered for their cumulative bytes. Once the
+    /// limit is reached, the one with the fewest bytes is forgotten to track a new
+    /// principal (its byte count restarts from zero). If every tracked principal has a
+    /// session, session requests of new principals are rejected.
+    /// By default, it is [`DEFAULT_MAX_PRINCIPALS`](Self::DEFAULT_MAX_PRINCIPALS).
+    pub fn max_principals(mut self, value: usize) -> Self {
+        self.max_principals = value.max(1);
+        self
+    }
+
+    /// Sets the interval between two accountings of the bytes of a session.
+    ///
+    /// The bandwidth quota can be exceeded by what a session transfers within an interval.
+    /// By default, it is [`DEFAULT_ACCOUNTING_INTERVAL`](Self::DEFAULT_ACCOUNTING_INTERVAL).
+    pub fn accounting_interval(mut self, interval: Duration) -> Self {
+        self.accounting_interval = interval;
+        self
+    }
+}
+
+impl Default for QuotaLimits {
+    fn default() -> Self {
+        Self {
+            max_sessions: None,
+            max_streams: None,
+            max_bytes: None,
+            max_principals: Self::DEFAULT_MAX_PRINCIPALS,
+            accounting_interval: Self::DEFAULT_ACCOUNTING_INTERVAL,
+        }
+    }
+}
+
+/// Live usage of a principal.
+///
+/// See [`Quotas::usage`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct QuotaUsage {
+    sessions: usize,
+    streams: usize,
+    bytes: u64,
+}
+
+impl QuotaUsage {
+    /// Returns the number of sessions.
+    #[inline(always)]
+    pub fn sessions(&self) -> usize {
+        self.sessions
+    }
+
+    /// Returns the number of streams in use.
+    #[inline(always)]
+    pub fn streams(&self) -> usize {
+        self.streams
+    }
+
+    /// Returns the cumulative number of bytes, as of the last accounting.
+    #[inline(always)]
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+/// Quotas of sessions, streams and bandwidth per principal, across an endpoint.
+///
+/// The principal of a session is provided by the application (e.g., an authenticated
+/// tenant) with [`SessionRequest::set_principal`](crate::endpoint::SessionRequest::set_principal),
+/// before accepting the request. Sessions without a principal are not subject to quotas.
+///
+/// Quotas are enabled with
+/// [`ServerConfigBuilder::quotas`](crate::config::ServerConfigBuilder::quotas): the
+/// application keeps a clone to query the live usage.
+#[derive(Clone)]
+pub struct Quotas(Arc<QuotasInner>);
+
+struct QuotasInner {
+    limits: QuotaLimits,
+    principals: Mutex<HashMap<String, Arc<Usage>>>,
+}
+
+#[derive(Default)]
+struct Usage {
+    sessions: AtomicUsize,
+    streams: AtomicUsize,
+    bytes: AtomicU64,
+}
+
+impl Usage {
+    fn snapshot(&self) -> QuotaUsage {
+        QuotaUsage {
+            sessions: self.sessions.load(Ordering::Relaxed),
+            streams: self.streams.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Quotas {
+    /// Creates quotas enforcing `limits`.
+    pub fn new(limits: QuotaLimits) -> Self {
+        Self(Arc::new(QuotasInner {
+            limits,
+            principals: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    /// Returns the limits.
+    #[inline(always)]
+    pub fn limits(&self) -> &QuotaLimits {
+        &self.0.limits
+    }
+
+    /// Returns the live usage of `principal`, if it is tracked.
+    pub fn usage(&self, principal: &str) -> Option<QuotaUsage> {
+        self.0
+            .principals
+            .lock()
+            .expect("Quotas lock")
+            .get(principal)
+            .map(|usage| usage.snapshot())
+    }
+
+    /// Returns the live usage of every principal.
+    pub fn usages(&self) -> Vec<(String, QuotaUsage)> {
+        self.0
+            .principals
+            .lock()
+            .expect("Quotas lock")
+            .iter()
+            .map(|(principal, usage)| (principal.clone(), usage.snapshot()))
+            .collect()
+    }
+
+    /// Resets the cumulative bytes of `principal`, and forgets it if it has no session.
+    pub fn reset(&self, principal: &str) {
+        let mut principals = self.0.principals.lock().expect("Quotas lock");
+
+        if let Some(usage) = principals.get(principal) {
+            usage.bytes.store(0, Ordering::Relaxed);
+
+            if usage.sessions.load(Ordering::Relaxed) == 0 {
+                principals.remove(principal);
+            }
+        }
+    }
+
+    /// Admits a new session of `principal`, if within its quotas.
+    pub(crate) fn admit(&self, principal: &str) -> Option<QuotaAccount> {
+        let limits = &self.0.limits;
+        let mut principals = self.0.principals.lock().expect("Quotas lock");
+
+        if !principals.contains_key(principal) && principals.len() >= limits.max_principals {
+            let evicted = principals
+                .iter()
+                .filter(|(_, usage)| usage.sessions.load(Ordering::Relaxed) == 0)
+                .min_by_key(|(_, usage)| usage.bytes.load(Ordering::Relaxed))
+                .map(|(principal, _)| principal.clone())?;
+
+            debug!("Quotas: forgetting principal '{}'", evicted);
+            principals.remove(&evicted);
+        }
+
+        let usage = principals.entry(principal.to_string()).or_default();
+
+        let sessions = usage.sessions.load(Ordering::Relaxed);
+        let bytes = usage.bytes.load(Ordering::Relaxed);
+
+        if limits.max_sessions.is_some_and(|max| sessions >= max)
+            || limits.max_bytes.is_some_and(|max| bytes >= max)
+        {
+            return None;
+        }
+
+        usage.sessions.fetch_add(1, Ordering::Relaxed);
+
+        Some(QuotaAccount {
+            principal: principal.to_string(),
+            limits: *limits,
+            usage: usage.clone(),
+        })
+    }
+}
+
+impl std::fmt::Debug for Quotas {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Quotas")
+            .field("limits", &self.0.limits)
+            .finish()
+    }
+}
+
+/// A session admitted by [`Quotas`], counted till dropped.
+pub(crate) struct QuotaAccount {
+    principal: String,
+    limits: QuotaLimits,
+    usage: Arc<Usage>,
+}
+
+impl QuotaAccount {
+    #[inline(always)]
+    pub(crate) fn principal(&self) -> &str {
+        &self.principal
+    }
+
+    /// Counts a stream opened by the peer, if within the quota, till the returned slot
+    /// is dropped.
+    pub(crate) fn reserve_stream(&self) -> Option<QuotaStreamSlot> {
+        let max = match self.limits.max_streams {
+            Some(max) => max,
+            None => return Some(self.stream_slot()),
+        };
+
+        self.usage
+            .streams
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |streams| {
+                (streams < max).then_some(streams + 1)
+            })
+            .ok()?;
+
+        Some(QuotaStreamSlot(self.usage.clone()))
+    }
+
+    /// Counts a stream opened locally (never refused), till the returned slot is dropped.
+    pub(crate) fn stream_slot(&self) -> QuotaStreamSlot {
+        self.usage.streams.fetch_add(1, Ordering::Relaxed);
+        QuotaStreamSlot(self.usage.clone())
+    }
+
+    /// Accounts the bytes of `quic_connection` till it is closed, closing it once the
+    /// bandwidth quota is exceeded.
+    pub(crate) fn accounting(
+        &self,
+        quic_connection: quinn::Connection,
+    ) -> impl Future<Output = ()> + Send + 'static {
+        let usage = self.usage.clone();
+        let limits = self.limits;
+
+        async move {
+            let mut accounted = 0;
+
+            loop {
+                let closed = tokio::select! {
+                    () = tokio::time::sleep(limits.accounting_interval) => false,
+                    _ = quic_connection.closed() => true,
+                };
+
+                let stats = quic_connection.stats();
+                let bytes = stats.udp_tx.bytes + stats.udp_rx.bytes;
+                let total =
+                    usage.bytes.fetch_add(bytes - accounted, Ordering::Relaxed) + bytes - accounted;
+                accounted = bytes;
+
+                if closed {
+                    return;
+                }
+
+                if limits.max_bytes.is_some_and(|max| total > max) {
+                    debug!("Bandwidth quota exceeded: closing connection");
+                    quic_connection.close(
+                        varint_w2q(ErrorCode::ExcessiveLoad.to_code()),
+                        b"Quota exceeded",
+                    );
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for QuotaAccount {
+    fn drop(&mut self) {
+        self.usage.sessions.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A stream counted by a [`QuotaAccount`].
+pub(crate) struct QuotaStreamSlot(Arc<Usage>);
+
+impl Drop for QuotaStreamSlot {
+    fn drop(&mut self) {
+        self.0.streams.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::StreamWriteError;
+    use crate::test_utils;
+    use crate::Endpoint;
+
+    #[test]
+    fn max_sessions() {
+        let quotas = Quotas::new(QuotaLimits::new().max_sessions(2));
+
+        let first = quotas.admit("alice").unwrap();
+        let second = quotas.admit("alice").unwrap();
+        assert!(quotas.admit("alice").is_none());
+        assert!(quotas.admit("bob").is_some());
+        assert_eq!(quotas.usage("alice").unwrap().sessions(), 2);
+
+        drop(first);
+        assert_eq!(quotas.usage("alice").unwrap().sessions(), 1);
+        let _third = quotas.admit("alice").unwrap();
+        assert!(quotas.admit("alice").is_none());
+
+        drop(second);
+        assert_eq!(quotas.usage("alice").unwrap().sessions(), 1);
+    }
+
+    #[test]
+    fn max_bytes() {
+        let quotas = Quotas::new(QuotaLimits::new().max_bytes(100));
+
+        let account = quotas.admit("alice").unwrap();
+        account.usage.bytes.store(100, Ordering::Relaxed);
+        drop(account);
+
+        assert!(quotas.admit("alice").is_none());
+        quotas.reset("alice");
+        assert!(quotas.usage("alice").is_none());
+        assert!(quotas.admit("alice").is_some());
+    }
+
+    #[test]
+    fn reserve_stream() {
+        let quotas = Quotas::new(QuotaLimits::new().max_streams(2));
+        let account = quotas.admit("alice").unwrap();
+
+        let first = account.reserve_stream().unwrap();
+        let _second = account.reserve_stream().unwrap();
+        assert!(account.reserve_stream().is_none());
+        assert_eq!(quotas.usage("alice").unwrap().streams(), 2);
+
+        // Local streams are counted, but never refused.
+        let local = account.stream_slot();
+        assert_eq!(quotas.usage("alice").unwrap().streams(), 3);
+        drop(local);
+        assert!(account.reserve_stream().is_none());
+
+        drop(first);
+        assert!(account.reserve_stream().is_some());
+    }
+
+    #[test]
+    fn reserve_stream_concurrently() {
+        const MAX_STREAMS: usize = 8;
+
+        let quotas = Quotas::new(QuotaLimits::new().max_streams(MAX_STREAMS));
+        let account = Arc::new(quotas.admit("alice").unwrap());
+
+        let threads = (0..32)
+            .map(|_| {
+                let account = account.clone();
+                std::thread::spawn(move || {
+                    (0..MAX_STREAMS)
+                        .filter_map(|_| account.reserve_stream())
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let slots = threads
+            .into_iter()
+            .flat_map(|thread| thread.join().expect("Thread panic"))
+            .collect::<Vec<_>>();
+
+        assert_eq!(slots.len(), MAX_STREAMS);
+        assert_eq!(quotas.usage("alice").unwrap().streams(), MAX_STREAMS);
+
+        drop(slots);
+        assert_eq!(quotas.usage("alice").unwrap().streams(), 0);
+    }
+
+    #[test]
+    fn max_principals() {
+        let quotas = Quotas::new(QuotaLimits::new().max_principals(2));
+
+        let alice = quotas.admit("alice").unwrap();
+        alice.usage.bytes.store(10, Ordering::Relaxed);
+        let bob = quotas.admit("bob").unwrap();
+
+        // Every tracked principal has a session.
+        assert!(quotas.admit("carol").is_none());
+        assert!(quotas.usage("carol").is_none());
+
+        // Known principals are still admitted.
+        let _alice = quotas.admit("alice").unwrap();
+
+        // The idle principal with the fewest bytes is forgotten.
+        drop(alice);
+        drop(bob);
+        let _carol = quotas.admit("carol").unwrap();
+        assert!(quotas.usage("bob").is_none());
+        assert_eq!(quotas.usage("alice").unwrap().bytes(), 10);
+        assert_eq!(quotas.usages().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn refuse_streams_beyond_quota() {
+        let certificate = test_utils::certificate();
+        let quotas = Quotas::new(QuotaLimits::new().max_streams(1));
+        let server = Endpoint::server(
+            test_utils::server_config(certificate.clone())
+                .quotas(quotas.clone())
+                .build(),
+        )
+        .unwrap();
+        let client = Endpoint::client(test_utils::client_config(&certificate).build()).unwrap();
+
+        let (server_connection, client_connection) = tokio::join!(
+            async {
+                let mut session_request = server.accept().await.await.unwrap();
+                session_request.set_principal("alice");
+                session_request.accept().await.unwrap()
+            },
+            async { client.connect(test_utils::url(&server)).await.unwrap() },
+        );
+
+        let mut first = client_connection.open_uni().await.unwrap().await.unwrap();
+        first.write_all(b"first").await.unwrap();
+        let _accepted = server_connection.accept_uni().await.unwrap();
+        assert_eq!(quotas.usage("alice").unwrap().streams(), 1);
+
+        let mut second = client_connection.open_uni().await.unwrap().await.unwrap();
+        second.write_all(b"second").await.unwrap();
+
+        let accepted =
+            tokio::time::timeout(Duration::from_millis(200), server_connection.accept_uni()).await;
+        assert!(accepted.is_err());
+
+        let code = loop {
+            match second.write(b"second").await {
+                Ok(_) => tokio::task::yield_now().await,
+                Err(StreamWriteError::Stopped(code)) => break code,
+                Err(error) => panic!("Unexpected error: {error}"),
+            }
+        };
+        assert_eq!(
+            code.into_inner(),
+            ErrorCode::RequestRejected.to_code().into_inner()
+        );
+        assert_eq!(quotas.usage("alice").unwrap().streams(), 1);
+    }
+}
@@ -0,0 +1,247 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+use tokio::time::Instant;
+
+/// Header field carrying a resumption token.
+///
+/// The server issues the token in the session response, the client presents it back
+/// in the session request when reconnecting.
+pub const RESUMPTION_TOKEN_HEADER: &str = "wt-resumption-token";
+
+/// Size of the issue timestamp prepended to the state.
+const TIMESTAMP_SIZE: usize = 8;
+
+/// Encryption of resumption tokens.
+///
+/// Tokens are sealed and opened by servers only: clients store them as opaque values.
+/// Implementations must provide authenticated encryption, so that tokens can be neither
+/// read nor forged by clients.
+///
+/// Servers redeeming each other's tokens (e.g., behind a load balancer) must share their keys.
+pub trait TokenCrypto: Send + Sync {
+    /// Encrypts `plaintext`, returning `None` on failure.
+    fn seal(&self, plaintext: &[u8]) -> Option<Vec<u8>>;
+
+    /// Authenticates and decrypts `token`, returning `None` if it is not valid.
+    fn open(&self, token: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// [`TokenCrypto`] based on the TLS session ticket encrypter of `rustls`.
+///
+/// Keys are randomly generated for this process and periodically rotated: tokens
+/// cannot be redeemed by other processes, and not after a few hours.
+pub struct TicketerCrypto(Arc<dyn rustls::server::ProducesTickets>);
+
+impl TicketerCrypto {
+    /// Creates an encrypter with fresh keys.
+    pub fn new() -> Result<Self, rustls::Error> {
+        rustls::Ticketer::new().map(Self)
+    }
+}
+
+impl TokenCrypto for TicketerCrypto {
+    fn seal(&self, plaintext: &[u8]) -> Option<Vec<u8>> {
+        self.0.encrypt(plaintext)
+    }
+
+    fn open(&self, token: &[u8]) -> Option<Vec<u8>> {
+        self.0.decrypt(token)
+    }
+}
+
+/// Issuer of application-level resumption tokens, on server side.
+///
+/// A token seals some application state (e.g., a session identifier or a cursor) along
+/// with its issue time. On reconnection, the client presents the token back, and the
+/// server restores the state without another round of application handshake.
+///
+/// See [`ServerConfigBuilder::resumption_tokens`](crate::config::ServerConfigBuilder::resumption_tokens).
+#[derive(Clone)]
+pub struct ResumptionTokens {
+    crypto: Arc<dyn TokenCrypto>,
+    lifetime: Duration,
+    clock: UnixClock,
+}
+
+impl ResumptionTokens {
+    /// Creates an issuer of tokens encrypted by `crypto`, valid for `lifetime`.
+    pub fn new<C>(crypto: C, lifetime: Duration) -> Self
+    where
+        C: TokenCrypto + 'static,
+    {
+        Self {
+            crypto: Arc::new(crypto),
+            lifetime,
+            clock: UnixClock::new(),
+        }
+    }
+
+    /// Returns the validity of the tokens.
+    pub fn lifetime(&self) -> Duration {
+        self.lifetime
+    }
+
+    /// Issues a token sealing `state`.
+    ///
+    /// It returns `None` if the encryption fails.
+    pub fn issue(&self, state: &[u8]) -> Option<ResumptionToken> {
+        let mut plaintext = Vec::with_capacity(TIMESTAMP_SIZE + state.len());
+        plaintext.extend_from_slice(&self.clock.now().as_secs().to_be_bytes());
+        plaintext.extend_from_slice(state);
+
+        self.crypto.seal(&plaintext).map(ResumptionToken::new)
+    }
+
+    /// Redeems `token`, returning the sealed state.
+    ///
+    /// It returns `None` if the token is not authentic or expired.
+    pub fn redeem(&self, token: &ResumptionToken) -> Option<Vec<u8>> {
+        let mut plaintext = self.crypto.open(&token.bytes)?;
+
+        if plaintext.len() < TIMESTAMP_SIZE {
+            return None;
+        }
+
+        let issued_at = u64::from_be_bytes(
+            plaintext[..TIMESTAMP_SIZE]
+                .try_into()
+                .expect("Timestamp has fixed size"),
+        );
+
+        let age = self
+            .clock
+            .now()
+            .saturating_sub(Duration::from_secs(issued_at));
+
+        if age > self.lifetime {
+            return None;
+        }
+
+        plaintext.drain(..TIMESTAMP_SIZE);
+
+        Some(plaintext)
+    }
+}
+
+/// Time since the Unix epoch, advancing with the tokio clock.
+///
+/// The system time is read once: issue times remain comparable between processes,
+/// while tests can control the expiry with a paused clock.
+#[derive(Clone, Copy)]
+struct UnixClock {
+    origin: Duration,
+    origin_instant: Instant,
+}
+
+impl UnixClock {
+    fn new() -> Self {
+        Self {
+            origin: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default(),
+            origin_instant: Instant::now(),
+        }
+    }
+
+    fn now(&self) -> Duration {
+        self.origin + self.origin_instant.elapsed()
+    }
+}
+
+/// An opaque resumption token.
+///
+/// Its string representation (hexadecimal) is the value of the [`RESUMPTION_TOKEN_HEADER`]
+/// field: clients can persist it, and parse it back with [`FromStr`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ResumptionToken {
+    bytes: Vec<u8>,
+}
+
+impl ResumptionToken {
+    fn new(bytes: Vec<u8>) -> Self {
+        Self { bytes }
+    }
+
+    /// Returns the raw bytes of the token.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+/// Malformed resumption token.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid resumption token")]
+pub struct InvalidResumptionToken;
+
+impl FromStr for ResumptionToken {
+    type Err = InvalidResumptionToken;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || s.len() % 2 != 0 || !s.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return Err(InvalidResumptionToken);
+        }
+
+        let bytes = (0..s.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&s[index..index + 2], 16).expect("Valid hex digits"))
+            .collect();
+
+        Ok(Self::new(bytes))
+    }
+}
+
+impl fmt::Display for ResumptionToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in &self.bytes {
+            write!(f, "{byte:02x}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn issue_redeem() {
+        let tokens = ResumptionTokens::new(TicketerCrypto::new().unwrap(), Duration::from_secs(60));
+
+        let token = tokens.issue(b"state").unwrap();
+        assert_eq!(tokens.redeem(&token).unwrap(), b"state");
+
+        let parsed = token.to_string().parse::<ResumptionToken>().unwrap();
+        assert_eq!(parsed, token);
+        assert_eq!(tokens.redeem(&parsed).unwrap(), b"state");
+
+        let mut tampered = token.clone();
+        *tampered.bytes.last_mut().unwrap() ^= 1;
+        assert!(tokens.redeem(&tampered).is_none());
+
+        let other = ResumptionTokens::new(TicketerCrypto::new().unwrap(), Duration::from_secs(60));
+        assert!(other.redeem(&token).is_none());
+
+        let token = tokens.issue(b"state").unwrap();
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert_eq!(tokens.redeem(&token).unwrap(), b"state");
+        tokio::time::advance(Duration::from_secs(31)).await;
+        assert!(tokens.redeem(&token).is_none());
+    }
+
+    #[test]
+    fn parse_token() {
+        assert!("".parse::<ResumptionToken>().is_err());
+        assert!("abc".parse::<ResumptionToken>().is_err());
+        assert!("zz".parse::<ResumptionToken>().is_err());
+        assert!("+1".parse::<ResumptionToken>().is_err());
+        assert_eq!(
+            "0aFf".parse::<ResumptionToken>().unwrap().as_bytes(),
+            [0x0a, 0xff]
+        );
+    }
+}
@@ -0,0 +1,578 @@
+use crate::error::ConnectionError;
+use crate::error::StreamOpeningError;
+use crate::error::StreamReadError;
+use crate::error::StreamReadExactError;
+use crate::error::StreamWriteError;
+use crate::framing;
+use crate::framing::ReadMessageError;
+use crate::Connection;
+use crate::RecvStream;
+use crate::SendStream;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::debug;
+use wtransport_proto::varint::VarInt;
+
+/// Maximum size of a method name.
+const MAX_METHOD_SIZE: usize = 1024;
+
+/// Maximum size of a request or response payload.
+const MAX_PAYLOAD_SIZE: usize = 16 * 1024 * 1024;
+
+/// Stream error code for a cancelled call.
+pub const CANCELLED: VarInt = VarInt::from_u32(0x52_43);
+
+/// Status of an RPC response.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RpcStatus(VarInt);
+
+impl RpcStatus {
+    /// The call succeeded.
+    pub const OK: RpcStatus = RpcStatus(VarInt::from_u32(0));
+
+    /// The method is not served by the peer.
+    pub const UNKNOWN_METHOD: RpcStatus = RpcStatus(VarInt::from_u32(1));
+
+    /// The request is malformed (e.g., it cannot be decoded).
+    pub const INVALID_REQUEST: RpcStatus = RpcStatus(VarInt::from_u32(2));
+
+    /// The handler failed.
+    pub const INTERNAL: RpcStatus = RpcStatus(VarInt::from_u32(3));
+
+    /// The deadline of the call expired before the handler completed.
+    pub const DEADLINE_EXCEEDED: RpcStatus = RpcStatus(VarInt::from_u32(4));
+
+    /// Creates a status from its code.
+    ///
+    /// Codes below `0x100` are reserved to this crate.
+    #[inline(always)]
+    pub const fn from_code(code: VarInt) -> Self {
+        Self(code)
+    }
+
+    /// Returns the status code.
+    #[inline(always)]
+    pub const fn code(self) -> VarInt {
+        self.0
+    }
+}
+
+/// An error of an RPC call.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum RpcError {
+    /// The connection is no longer available.
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+
+    /// The stream of the call cannot be opened.
+    #[error(transparent)]
+    StreamOpening(#[from] StreamOpeningError),
+
+    /// The request cannot be sent.
+    #[error(transparent)]
+    Write(#[from] StreamWriteError),
+
+    /// The response cannot be received.
+    #[error(transparent)]
+    Read(#[from] StreamReadExactError),
+
+    /// The response is larger than the allowed size.
+    #[error("response too large")]
+    TooLarge,
+
+    /// The peer answered with an error status.
+    #[error("call failed with status {}", .status.code())]
+    Status {
+        /// Status sent by the peer.
+        status: RpcStatus,
+
+        /// Details sent by the peer with the status.
+        details: Bytes,
+    },
+
+    /// The deadline expired before the response.
+    #[error("deadline expired")]
+    DeadlineExpired,
+
+    /// The payload cannot be encoded or decoded.
+    #[cfg(feature = "serde")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+    #[error("codec error: {0}")]
+    Codec(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl From<ReadMessageError> for RpcError {
+    fn from(error: ReadMessageError) -> Self {
+        match error {
+            ReadMessageError::Read(error) => RpcError::Read(error),
+            ReadMessageError::TooLarge => RpcError::TooLarge,
+        }
+    }
+}
+
+/// Calls `method` on the peer, with a `request` payload.
+///
+/// Each call is carried by its own bidirectional stream. If `deadline` expires,
+/// or the returned future is dropped before completion, the call is cancelled:
+/// the request stream is reset with [`CANCELLED`], and the peer drops its handler.
+///
+/// The deadline is also sent to the peer, which abandons the call once it expires
+/// (answering with [`RpcStatus::DEADLINE_EXCEEDED`]), even if the reset is not received.
+pub async fn call(
+    connection: &Connection,
+    method: &str,
+    request: &[u8],
+    deadline: Option<Duration>,
+) -> Result<Bytes, RpcError> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout(
+            deadline,
+            call_impl(connection, method, request, Some(deadline)),
+        )
+        .await
+        .map_err(|_| RpcError::DeadlineExpired)?,
+        None => call_impl(connection, method, request, None).await,
+    }
+}
+
+async fn call_impl(
+    connection: &Connection,
+    method: &str,
+    request: &[u8],
+    deadline: Option<Duration>,
+) -> Result<Bytes, RpcError> {
+    let (send_stream, mut recv_stream) = connection.open_bi().await?.await?;
+    let mut send_stream = CancelGuard(Some(send_stream));
+
+    let stream = send_stream.0.as_mut().expect("Stream is not finished yet");
+    framing::write_message(stream, method.as_bytes()).await?;
+    let mut buffer = Vec::new();
+    framing::put_varint(&mut buffer, encode_deadline(deadline));
+    stream.write_all(&buffer).await?;
+    framing::write_message(stream, request).await?;
+
+    let status = framing::read_varint(&mut recv_stream)
+        .await?
+        .ok_or(StreamReadExactError::FinishedEarly)?;
+
+    let payload = framing::read_message(&mut recv_stream, MAX_PAYLOAD_SIZE)
+        .await?
+        .ok_or(StreamReadExactError::FinishedEarly)?;
+
+    // The callee stops the request stream once it has answered.
+    let _ = send_stream.finish().await;
+
+    match RpcStatus::from_code(status) {
+        RpcStatus::OK => Ok(Bytes::from(payload)),
+        status => Err(RpcError::Status {
+            status,
+            details: Bytes::from(payload),
+        }),
+    }
+}
+
+/// Resets the request stream, unless the call completes.
+struct CancelGuard(Option<SendStream>);
+
+impl CancelGuard {
+    async fn finish(&mut self) -> Result<(), StreamWriteError> {
+        match self.0.take() {
+            Some(mut stream) => stream.finish().await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        if let Some(stream) = self.0.take() {
+            stream.reset(CANCELLED);
+        }
+    }
+}
+
+/// Encodes `deadline` in milliseconds, rounded up: `0` means no deadline.
+fn encode_deadline(deadline: Option<Duration>) -> VarInt {
+    match deadline {
+        Some(deadline) => {
+            let millis = deadline.as_nanos().div_ceil(1_000_000).max(1);
+            VarInt::try_from(u64::try_from(millis).unwrap_or(u64::MAX)).unwrap_or(VarInt::MAX)
+        }
+        None => VarInt::from_u32(0),
+    }
+}
+
+/// Decodes a deadline encoded with [`encode_deadline`].
+fn decode_deadline(millis: VarInt) -> Option<Duration> {
+    match millis.into_inner() {
+        0 => None,
+        millis => Some(Duration::from_millis(millis)),
+    }
+}
+
+/// Result of an RPC handler: the response payload, or an error status with details.
+pub type RpcResult = Result<Bytes, (RpcStatus, Bytes)>;
+
+type Handler = Arc<dyn Fn(Bytes) -> Pin<Box<dyn Future<Output = RpcResult> + Send>> + Send + Sync>;
+
+/// Server side of the RPC layer, dispatching calls to handlers by method name.
+#[derive(Clone, Default)]
+pub struct RpcServer {
+    handlers: HashMap<String, Handler>,
+}
+
+impl RpcServer {
+    /// Creates a server without handlers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `handler` for `method`, replacing any previous one.
+    ///
+    /// The handler future is dropped if the caller cancels the call.
+    pub fn route<F, Fut>(mut self, method: &str, handler: F) -> Self
+    where
+        F: Fn(Bytes) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = RpcResult> + Send + 'static,
+    {
+        self.handlers.insert(
+            method.to_string(),
+            Arc::new(move |request| Box::pin(handler(request))),
+        );
+        self
+    }
+
+    /// Serves the calls of `connection`, until it is closed.
+    ///
+    /// Each call is handled on its own task.
+    pub async fn serve(&self, connection: &Connection) -> ConnectionError {
+        loop {
+            let (send_stream, recv_stream) = match connection.accept_bi().await {
+                Ok(stream) => stream,
+                Err(error) => return error,
+            };
+
+            let server = self.clone();
+
+            connection.spawner().spawn(async move {
+                if let Err(error) = server.handle(send_stream, recv_stream).await {
+                    debug!("RPC call failed: {}", error);
+                }
+            });
+        }
+    }
+
+    /// Handles a single call on a bidirectional stream.
+    ///
+    /// If the deadline sent by the caller expires, the handler is dropped and the call
+    /// answered with [`RpcStatus::DEADLINE_EXCEEDED`].
+    pub async fn handle(
+        &self,
+        mut send_stream: SendStream,
+        mut recv_stream: RecvStream,
+    ) -> Result<(), RpcError> {
+        let method = framing::read_message(&mut recv_stream, MAX_METHOD_SIZE)
+            .await?
+            .ok_or(StreamReadExactError::FinishedEarly)?;
+
+        let deadline = framing::read_varint(&mut recv_stream)
+            .await?
+            .ok_or(StreamReadExactError::FinishedEarly)?;
+        let deadline = decode_deadline(deadline)
+            .and_then(|deadline| tokio::time::Instant::now().checked_add(deadline));
+
+        let request = framing::read_message(&mut recv_stream, MAX_PAYLOAD_SIZE)
+            .await?
+            .ok_or(StreamReadExactError::FinishedEarly)?;
+
+        let handler = std::str::from_utf8(&method)
+            .ok()
+            .and_then(|method| self.handlers.get(method));
+
+        let response = match handler {
+            Some(handler) => {
+                let response = handler(Bytes::from(request));
+                let expired = async {
+                    match deadline {
+                        Some(deadline) => tokio::time::sleep_until(deadline).await,
+                        None => std::future::pending().await,
+                    }
+                };
+
+                tokio::select! {
+                    response = response => response,
+                    error = cancelled(&mut recv_stream) => {
+                        debug!("RPC call cancelled by peer");
+                        return Err(RpcError::Read(StreamReadExactError::Read(error)));
+                    }
+                    () = expired => {
+                        debug!("RPC call deadline expired");
+                        Err((RpcStatus::DEADLINE_EXCEEDED, Bytes::new()))
+                    }
+                }
+            }
+            None => Err((RpcStatus::UNKNOWN_METHOD, Bytes::new())),
+        };
+
+        let (status, payload) = match response {
+            Ok(payload) => (RpcStatus::OK, payload),
+            Err((status, details)) => (status, details),
+        };
+
+        let mut buffer = Vec::new();
+        framing::put_varint(&mut buffer, status.code());
+        send_stream.write_all(&buffer).await?;
+        framing::write_message(&mut send_stream, &payload).await?;
+        send_stream.finish().await?;
+
+        Ok(())
+    }
+}
+
+/// Awaits for the caller to reset the request stream.
+///
+/// The caller does not send anything after the request: the stream is only finished
+/// once the response is received.
+async fn cancelled(recv_stream: &mut RecvStream) -> StreamReadError {
+    let mut buffer = [0; 1];
+
+    loop {
+        match recv_stream.read(&mut buffer).await {
+            Ok(Some(_)) => continue,
+            Ok(None) => std::future::pending::<()>().await,
+            Err(error) => return error,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+pub use typed::*;
+
+#[cfg(feature = "serde")]
+mod typed {
+    use super::*;
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+
+    #[doc(no_inline)]
+    pub use crate::codec::Codec;
+
+    /// Like [`call`], with payloads encoded by `C`.
+    #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+    pub async fn call_with<C, Req, Resp>(
+        connection: &Connection,
+        method: &str,
+        request: &Req,
+        deadline: Option<Duration>,
+    ) -> Result<Resp, RpcError>
+    where
+        C: Codec,
+        Req: Serialize,
+        Resp: DeserializeOwned,
+    {
+        let request = C::encode(request).map_err(|error| RpcError::Codec(Box::new(error)))?;
+        let response = call(connection, method, &request, deadline).await?;
+        C::decode(&response).map_err(|error| RpcError::Codec(Box::new(error)))
+    }
+
+    impl RpcServer {
+        /// Like [`RpcServer::route`], with payloads encoded by `C`.
+        ///
+        /// Requests that cannot be decoded are answered with [`RpcStatus::INVALID_REQUEST`].
+        #[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+        pub fn route_with<C, Req, Resp, F, Fut>(self, method: &str, handler: F) -> Self
+        where
+            C: Codec + 'static,
+            Req: DeserializeOwned + Send + 'static,
+            Resp: Serialize + Send + 'static,
+            F: Fn(Req) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = Result<Resp, (RpcStatus, Bytes)>> + Send + 'static,
+        {
+            let handler = Arc::new(handler);
+
+            self.route(method, move |request: Bytes| {
+                let handler = handler.clone();
+
+                async move {
+                    let request = C::decode(&request).map_err(|error| {
+                        (RpcStatus::INVALID_REQUEST, Bytes::from(error.to_string()))
+                    })?;
+
+                    let response = handler(request).await?;
+
+                    C::encode(&response)
+                        .map(Bytes::from)
+                        .map_err(|error| (RpcStatus::INTERNAL, Bytes::from(error.to_string())))
+                }
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering;
+
+    /// Sets the flag when dropped, with the handler future owning it.
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn server(dropped: Arc<AtomicBool>) -> RpcServer {
+        RpcServer::new()
+            .route("echo", |request| async move { Ok(request) })
+            .route("fail", |_| async move {
+                Err((RpcStatus::INTERNAL, Bytes::from_static(b"boom")))
+            })
+            .route("hang", move |_| {
+                let flag = DropFlag(dropped.clone());
+                async move {
+                    let _flag = flag;
+                    std::future::pending().await
+                }
+            })
+    }
+
+    async fn wait_flag(flag: &AtomicBool) {
+        while !flag.load(Ordering::SeqCst) {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+
+    #[test]
+    fn deadline_encoding() {
+        assert_eq!(encode_deadline(None), VarInt::from_u32(0));
+        assert_eq!(decode_deadline(VarInt::from_u32(0)), None);
+
+        assert_eq!(
+            encode_deadline(Some(Duration::from_nanos(1))),
+            VarInt::from_u32(1)
+        );
+        assert_eq!(
+            encode_deadline(Some(Duration::from_micros(1500))),
+            VarInt::from_u32(2)
+        );
+        assert_eq!(encode_deadline(Some(Duration::MAX)), VarInt::MAX);
+
+        let deadline = Some(Duration::from_secs(3));
+        assert_eq!(decode_deadline(encode_deadline(deadline)), deadline);
+    }
+
+    #[tokio::test]
+    async fn calls() {
+        let peers = test_utils::connect().await;
+        let server_connection = peers.server_connection;
+        server_connection.spawner().clone().spawn(async move {
+            server(Default::default()).serve(&server_connection).await;
+        });
+        let connection = &peers.client_connection;
+
+        let response = call(connection, "echo", b"hello", None).await.unwrap();
+        assert_eq!(&response[..], b"hello");
+
+        let response = call(connection, "echo", b"", Some(Duration::from_secs(5)))
+            .await
+            .unwrap();
+        assert!(response.is_empty());
+
+        match call(connection, "fail", b"", None).await {
+            Err(RpcError::Status { status, details }) => {
+                assert_eq!(status, RpcStatus::INTERNAL);
+                assert_eq!(&details[..], b"boom");
+            }
+            result => panic!("Unexpected result: {result:?}"),
+        }
+
+        assert!(matches!(
+            call(connection, "unknown", b"", None).await,
+            Err(RpcError::Status {
+                status: RpcStatus::UNKNOWN_METHOD,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn cancelled_call_drops_handler() {
+        let peers = test_utils::connect().await;
+        let dropped = Arc::new(AtomicBool::new(false));
+        let server_connection = peers.server_connection;
+        let rpc_server = server(dropped.clone());
+        server_connection.spawner().clone().spawn(async move {
+            rpc_server.serve(&server_connection).await;
+        });
+
+        // Without deadline, only the reset of the stream cancels the call.
+        let result = tokio::time::timeout(
+            Duration::from_millis(50),
+            call(&peers.client_connection, "hang", b"", None),
+        )
+        .await;
+        assert!(result.is_err());
+        wait_flag(&dropped).await;
+
+        assert!(matches!(
+            call(
+                &peers.client_connection,
+                "hang",
+                b"",
+                Some(Duration::from_millis(50))
+            )
+            .await,
+            Err(RpcError::DeadlineExpired)
+        ));
+    }
+
+    #[tokio::test]
+    async fn deadline_sent_to_peer() {
+        let peers = test_utils::connect().await;
+        let dropped = Arc::new(AtomicBool::new(false));
+        let rpc_server = server(dropped.clone());
+
+        let handled = async {
+            let (send_stream, recv_stream) = peers.server_connection.accept_bi().await.unwrap();
+            rpc_server.handle(send_stream, recv_stream).await
+        };
+
+        // A caller that neither resets the stream, nor enforces the deadline itself.
+        let caller = async {
+            let (mut send_stream, mut recv_stream) = peers
+                .client_connection
+                .open_bi()
+                .await
+                .unwrap()
+                .await
+                .unwrap();
+            framing::write_message(&mut send_stream, b"hang")
+                .await
+                .unwrap();
+            let mut buffer = Vec::new();
+            framing::put_varint(
+                &mut buffer,
+                encode_deadline(Some(Duration::from_millis(20))),
+            );
+            send_stream.write_all(&buffer).await.unwrap();
+            framing::write_message(&mut send_stream, b"").await.unwrap();
+
+            let status = framing::read_varint(&mut recv_stream).await.unwrap();
+            (send_stream, status)
+        };
+
+        let (handled, (_send_stream, status)) = tokio::join!(handled, caller);
+        assert!(handled.is_ok());
+        assert_eq!(status, Some(RpcStatus::DEADLINE_EXCEEDED.code()));
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,483 @@
+use crate::alt_svc::AltSvc;
+use crate::config::ClientConfigBuilder;
+use crate::config::WantsRootStore;
+use crate::endpoint::Server;
+use crate::tls::Certificate;
+use crate::Endpoint;
+use rustls::AlertDescription;
+use rustls::RootCertStore;
+use std::fmt;
+use std::future::poll_fn;
+use std::future::Future;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::Duration;
+use std::time::Instant;
+use tokio::net::lookup_host;
+use tracing::debug;
+use wtransport_proto::WEBTRANSPORT_ALPN;
+
+/// A reachability self-test of a server endpoint.
+///
+/// The server dials itself, over loopback and through its advertised addresses, as a
+/// client would: each attempt completes a QUIC handshake, validating the certificate for
+/// the [server name](Self::new). Targets are dialed from the host of the server, hence
+/// public addresses are only reachable if the network supports hairpinning (NAT loopback).
+///
+/// See [`Endpoint::self_test`].
+#[derive(Clone)]
+pub struct SelfTest {
+    server_name: String,
+    public_addresses: Vec<SocketAddr>,
+    alt_svcs: Vec<AltSvc>,
+    root_store: RootCertStore,
+    timeout: Duration,
+}
+
+impl SelfTest {
+    /// Default time allowed to each attempt.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+
+    /// Creates a self-test validating the certificate for `server_name`, against the
+    /// local (native) root certificates.
+    pub fn new<S>(server_name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            server_name: server_name.into(),
+            public_addresses: Vec::new(),
+            alt_svcs: Vec::new(),
+            root_store: ClientConfigBuilder::<WantsRootStore>::native_cert_store(),
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Trusts the certificates of `certificate` too (e.g., a self-signed one).
+    pub fn trust(mut self, certificate: &Certificate) -> Self {
+        for certificate in certificate.certificates_der() {
+            let _ = self
+                .root_store
+                .add(&rustls::Certificate(certificate.to_vec()));
+        }
+        self
+    }
+
+    /// Dials `address` too, the public address of the server (e.g., behind a NAT).
+    pub fn public_address(mut self, address: SocketAddr) -> Self {
+        self.public_addresses.push(address);
+        self
+    }
+
+    /// Dials the endpoint advertised by `alt_svc` too.
+    ///
+    /// Its host (the [server name](Self::new) if not set) is resolved, and every resolved
+    /// address is dialed.
+    pub fn alt_svc(mut self, alt_svc: AltSvc) -> Self {
+        self.alt_svcs.push(alt_svc);
+        self
+    }
+
+    /// Sets the time allowed to each attempt, after which the target is considered
+    /// [unreachable](SelfTestOutcome::Unreachable).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl Endpoint<Server> {
+    /// Runs a reachability self-test of this endpoint, typically on startup.
+    ///
+    /// All targets are dialed concurrently (see [`SelfTest`]). Test connections are closed
+    /// right after their handshake: if the application is accepting sessions, they are
+    /// observed as failed incoming sessions.
+    pub async fn self_test(&self, self_test: &SelfTest) -> SelfTestReport {
+        let mut targets = Vec::new();
+
+        match self.local_addrs() {
+            Ok(addresses) => targets.extend(
+                addresses
+                    .into_iter()
+                    .map(|address| (SelfTestTarget::Loopback, Ok(loopback(address)))),
+            ),
+            Err(error) => targets.push((SelfTestTarget::Loopback, Err(error.to_string()))),
+        }
+
+        targets.extend(
+            self_test
+                .public_addresses
+                .iter()
+                .map(|address| (SelfTestTarget::Public, Ok(*address))),
+        );
+
+        for alt_svc in &self_test.alt_svcs {
+            let host = alt_svc.host().unwrap_or(&self_test.server_name);
+            let target = SelfTestTarget::AltSvc(alt_svc.clone());
+
+            match lookup_host((host, alt_svc.port())).await {
+                Ok(addresses) => {
+                    targets.extend(addresses.map(|address| (target.clone(), Ok(address))))
+                }
+                Err(error) => {
+                    targets.push((target, Err(format!("Cannot resolve '{host}': {error}"))))
+                }
+            }
+        }
+
+        let mut tls_config = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(self_test.root_store.clone())
+            .with_no_client_auth();
+        tls_config.alpn_protocols = vec![WEBTRANSPORT_ALPN.to_vec()];
+        let quic_config = quinn::ClientConfig::new(Arc::new(tls_config));
+
+        let attempts = targets.into_iter().map(|(target, address)| {
+            let quic_config = quic_config.clone();
+
+            async move {
+                let (address, outcome) = match address {
+                    Ok(address) => {
+                        let outcome = dial(
+                            address,
+                            quic_config,
+                            &self_test.server_name,
+                            self_test.timeout,
+                        )
+                        .await;
+                        (Some(address), outcome)
+                    }
+                    Err(reason) => (None, SelfTestOutcome::Failed(reason)),
+                };
+
+                debug!("Self-test of {:?} ({:?}): {}", target, address, outcome);
+
+                SelfTestCheck {
+                    target,
+                    address,
+                    outcome,
+                }
+            }
+        });
+
+        SelfTestReport {
+            checks: join_all(attempts).await,
+        }
+    }
+}
+
+/// Returns the loopback address reaching a server bound to `address`.
+fn loopback(address: SocketAddr) -> SocketAddr {
+    match address.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => (Ipv4Addr::LOCALHOST, address.port()).into(),
+        IpAddr::V6(ip) if ip.is_unspecified() => (Ipv6Addr::LOCALHOST, address.port()).into(),
+        _ => address,
+    }
+}
+
+async fn dial(
+    address: SocketAddr,
+    quic_config: quinn::ClientConfig,
+    server_name: &str,
+    timeout: Duration,
+) -> SelfTestOutcome {
+    let bind_address: SocketAddr = match address {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+
+    let endpoint = match quinn::Endpoint::client(bind_address) {
+        Ok(endpoint) => endpoint,
+        Err(error) => return SelfTestOutcome::Failed(error.to_string()),
+    };
+
+    let start = Instant::now();
+
+    let connecting = match endpoint.connect_with(quic_config, address, server_name) {
+        Ok(connecting) => connecting,
+        Err(error) => return SelfTestOutcome::Failed(error.to_string()),
+    };
+
+    let outcome = match tokio::time::timeout(timeout, connecting).await {
+        Ok(Ok(connection)) => {
+            connection.close(quinn::VarInt::from_u32(0), b"Self-test");
+            SelfTestOutcome::Reachable(start.elapsed())
+        }
+        Ok(Err(quinn::ConnectionError::TransportError(error)))
+            if is_certificate_alert(error.code) =>
+        {
+            SelfTestOutcome::InvalidCertificate(error.reason)
+        }
+        Ok(Err(quinn::ConnectionError::TimedOut)) | Err(_) => SelfTestOutcome::Unreachable,
+        Ok(Err(error)) => SelfTestOutcome::Refused(error.to_string()),
+    };
+
+    endpoint.close(quinn::VarInt::from_u32(0), b"");
+
+    outcome
+}
+
+/// Returns whether `code` reports a TLS alert about a certificate (raised by the local
+/// certificate validation).
+fn is_certificate_alert(code: quinn_proto::TransportErrorCode) -> bool {
+    [
+        AlertDescription::BadCertificate,
+        AlertDescription::UnsupportedCertificate,
+        AlertDescription::CertificateRevoked,
+        AlertDescription::CertificateExpired,
+        AlertDescription::CertificateUnknown,
+        AlertDescription::UnknownCA,
+    ]
+    .into_iter()
+    .any(|alert| code == quinn_proto::TransportErrorCode::crypto(alert.get_u8()))
+}
+
+/// Runs `futures` concurrently, returning their outputs in order.
+async fn join_all<F>(futures: impl IntoIterator<Item = F>) -> Vec<F::Output>
+where
+    F: Future,
+{
+    let mut futures = futures
+        .into_iter()
+        .map(Box::pin)
+        .enumerate()
+        .collect::<Vec<_>>();
+    let mut outputs = Vec::with_capacity(futures.len());
+
+    poll_fn(|cx| {
+        let mut index = 0;
+
+        while index < futures.len() {
+            match futures[index].1.as_mut().poll(cx) {
+                Poll::Ready(output) => outputs.push((futures.swap_remove(index).0, output)),
+                Poll::Pending => index += 1,
+            }
+        }
+
+        if futures.is_empty() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await;
+
+    outputs.sort_by_key(|(index, _)| *index);
+    outputs.into_iter().map(|(_, output)| output).collect()
+}
+
+/// The result of a [`SelfTest`].
+#[derive(Clone, Debug)]
+pub struct SelfTestReport {
+    checks: Vec<SelfTestCheck>,
+}
+
+impl SelfTestReport {
+    /// Returns the checks, the loopback ones first.
+    pub fn checks(&self) -> &[SelfTestCheck] {
+        &self.checks
+    }
+
+    /// Returns whether all the targets are reachable, with a valid certificate.
+    pub fn is_ok(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| matches!(check.outcome, SelfTestOutcome::Reachable(_)))
+    }
+}
+
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            writeln!(f, "{}", check)?;
+        }
+        Ok(())
+    }
+}
+
+/// The check of a target of a [`SelfTest`].
+#[derive(Clone, Debug)]
+pub struct SelfTestCheck {
+    target: SelfTestTarget,
+    address: Option<SocketAddr>,
+    outcome: SelfTestOutcome,
+}
+
+impl SelfTestCheck {
+    /// Returns the target dialed.
+    pub fn target(&self) -> &SelfTestTarget {
+        &self.target
+    }
+
+    /// Returns the address dialed, or `None` if it could not be determined.
+    pub fn address(&self) -> Option<SocketAddr> {
+        self.address
+    }
+
+    /// Returns the outcome.
+    pub fn outcome(&self) -> &SelfTestOutcome {
+        &self.outcome
+    }
+}
+
+impl fmt::Display for SelfTestCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.target {
+            SelfTestTarget::Loopback => write!(f, "loopback")?,
+            SelfTestTarget::Public => write!(f, "public address")?,
+            SelfTestTarget::AltSvc(alt_svc) => write!(f, "alt-svc '{}'", alt_svc)?,
+        }
+
+        if let Some(address) = self.address {
+            write!(f, " ({})", address)?;
+        }
+
+        write!(f, ": {}", self.outcome)
+    }
+}
+
+/// A kind of target of a [`SelfTest`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SelfTestTarget {
+    /// A bind address of the endpoint, over loopback if bound to an unspecified address.
+    Loopback,
+
+    /// A public address (see [`SelfTest::public_address`]).
+    Public,
+
+    /// An advertised alternative service (see [`SelfTest::alt_svc`]).
+    AltSvc(AltSvc),
+}
+
+/// The diagnosis of a target of a [`SelfTest`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SelfTestOutcome {
+    /// The handshake completed, with a valid certificate, after the given duration.
+    Reachable(Duration),
+
+    /// The server did not answer in time: UDP traffic is likely blocked (e.g., by a firewall),
+    /// not forwarded (e.g., by a NAT), or the address is wrong.
+    Unreachable,
+
+    /// The server answered, but its certificate was rejected (e.g., it is expired, it does not
+    /// cover the server name, or its issuer is not trusted).
+    InvalidCertificate(String),
+
+    /// The server answered, but refused the connection (e.g., TLS or QUIC version mismatch).
+    Refused(String),
+
+    /// The target could not be dialed (e.g., name resolution failed).
+    Failed(String),
+}
+
+impl fmt::Display for SelfTestOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SelfTestOutcome::Reachable(duration) => {
+                write!(f, "reachable (handshake in {:?})", duration)
+            }
+            SelfTestOutcome::Unreachable => {
+                write!(f, "unreachable (check firewalls and UDP port forwarding)")
+            }
+            SelfTestOutcome::InvalidCertificate(reason) => {
+                write!(f, "invalid certificate ({})", reason)
+            }
+            SelfTestOutcome::Refused(reason) => write!(f, "refused ({})", reason),
+            SelfTestOutcome::Failed(reason) => write!(f, "failed ({})", reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+    use std::net::UdpSocket;
+
+    #[test]
+    fn certificate_alerts() {
+        let alert =
+            |alert: AlertDescription| quinn_proto::TransportErrorCode::crypto(alert.get_u8());
+
+        assert!(is_certificate_alert(alert(AlertDescription::UnknownCA)));
+        assert!(is_certificate_alert(alert(
+            AlertDescription::BadCertificate
+        )));
+        assert!(!is_certificate_alert(alert(
+            AlertDescription::NoApplicationProtocol
+        )));
+        assert!(!is_certificate_alert(alert(
+            AlertDescription::HandshakeFailure
+        )));
+        assert!(!is_certificate_alert(
+            quinn_proto::TransportErrorCode::PROTOCOL_VIOLATION
+        ));
+    }
+
+    #[tokio::test]
+    async fn join_all_in_order() {
+        let outputs = join_all([50, 0, 20].map(|delay| async move {
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            delay
+        }))
+        .await;
+        assert_eq!(outputs, [50, 0, 20]);
+
+        assert!(join_all(Vec::<std::future::Ready<()>>::new())
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn self_test() {
+        let certificate = test_utils::certificate();
+        let server =
+            Endpoint::server(test_utils::server_config(certificate.clone()).build()).unwrap();
+
+        // Nothing answers on this port.
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        let report = server
+            .self_test(
+                &SelfTest::new("localhost")
+                    .trust(&certificate)
+                    .public_address(silent.local_addr().unwrap())
+                    .timeout(Duration::from_millis(200)),
+            )
+            .await;
+
+        let [loopback, public] = report.checks() else {
+            panic!("Unexpected checks: {report}");
+        };
+        assert_eq!(loopback.target(), &SelfTestTarget::Loopback);
+        assert_eq!(loopback.address(), server.local_addr().ok());
+        assert!(matches!(loopback.outcome(), SelfTestOutcome::Reachable(_)));
+        assert_eq!(public.target(), &SelfTestTarget::Public);
+        assert_eq!(public.outcome(), &SelfTestOutcome::Unreachable);
+        assert!(!report.is_ok());
+        assert!(report.to_string().starts_with("loopback (127.0.0.1:"));
+
+        // Not trusted, or not valid for the name.
+        for self_test in [
+            SelfTest::new("localhost"),
+            SelfTest::new("example.com").trust(&certificate),
+        ] {
+            let report = server.self_test(&self_test).await;
+            assert!(matches!(
+                report.checks()[0].outcome(),
+                SelfTestOutcome::InvalidCertificate(_)
+            ));
+        }
+
+        let report = server
+            .self_test(&SelfTest::new("localhost").trust(&certificate))
+            .await;
+        assert!(report.is_ok(), "{report}");
+    }
+}
@@ -0,0 +1,477 @@
+use crate::datagram::Datagram;
+use crate::driver::utils::varint_w2q;
+use crate::error::ConnectionError;
+use crate::Connection;
+use crate::RecvStream;
+use crate::SendStream;
+use std::any::Any;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+use tokio::sync::Semaphore;
+use tracing::debug;
+use tracing::warn;
+use wtransport_proto::varint::VarInt;
+
+/// An incoming stream or datagram, passed to the handler of [`Connection::serve`].
+#[non_exhaustive]
+pub enum Incoming {
+    /// A bidirectional stream opened by the peer.
+    Bi(SendStream, RecvStream),
+
+    /// A unidirectional stream opened by the peer.
+    Uni(RecvStream),
+
+    /// A datagram received from the peer.
+    Datagram(Datagram),
+}
+
+/// What to do when a supervised task panics.
+///
+/// See [`Connection::spawn_supervised`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PanicPolicy {
+    /// Logs the panic, leaving the connection open.
+    #[default]
+    Log,
+
+    /// Logs the panic and closes the connection with the given application error code.
+    Close(VarInt),
+}
+
+/// Limits of [`Connection::serve`].
+#[derive(Copy, Clone, Debug)]
+pub struct ServeLimits {
+    max_concurrent_handlers: usize,
+    datagrams: bool,
+    panic_policy: PanicPolicy,
+}
+
+impl ServeLimits {
+    /// Default maximum number of handlers running concurrently.
+    pub const DEFAULT_MAX_CONCURRENT_HANDLERS: usize = 64;
+
+    /// Creates the default limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of handlers running concurrently (at least 1).
+    ///
+    /// Once reached, no more streams nor datagrams are accepted until a handler completes:
+    /// the peer is then limited by its stream credit.
+    ///
+    /// By default, it is [`DEFAULT_MAX_CONCURRENT_HANDLERS`](Self::DEFAULT_MAX_CONCURRENT_HANDLERS).
+    pub fn max_concurrent_handlers(mut self, value: usize) -> Self {
+        self.max_concurrent_handlers = value.max(1);
+        self
+    }
+
+    /// Sets whether datagrams are passed to the handler (enabled by default).
+    ///
+    /// When disabled, datagrams are left to [`Connection::receive_datagram`].
+    pub fn datagrams(mut self, enabled: bool) -> Self {
+        self.datagrams = enabled;
+        self
+    }
+
+    /// Sets what to do when a handler panics (by default, [`PanicPolicy::Log`]).
+    pub fn on_panic(mut self, policy: PanicPolicy) -> Self {
+        self.panic_policy = policy;
+        self
+    }
+}
+
+impl Default for ServeLimits {
+    fn default() -> Self {
+        Self {
+            max_concurrent_handlers: Self::DEFAULT_MAX_CONCURRENT_HANDLERS,
+            datagrams: true,
+            panic_policy: PanicPolicy::default(),
+        }
+    }
+}
+
+/// Outcome of [`Connection::serve`].
+#[derive(Debug)]
+pub struct ServeReport {
+    error: ConnectionError,
+    handled: u64,
+    panicked: u64,
+}
+
+impl ServeReport {
+    /// Returns the error that ended the connection.
+    pub fn error(&self) -> &ConnectionError {
+        &self.error
+    }
+
+    /// Returns the number of streams and datagrams passed to the handler.
+    pub fn handled(&self) -> u64 {
+        self.handled
+    }
+
+    /// Returns the number of handlers which panicked, at the time the connection ended.
+    pub fn panicked(&self) -> u64 {
+        self.panicked
+    }
+}
+
+/// Outcome of a supervised task.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Supervised {
+    Completed,
+    Panicked,
+    Cancelled,
+}
+
+impl Connection {
+    /// Spawns `future` on a task supervised by this connection.
+    ///
+    /// The task is spawned like the other internal tasks of the connection, and it is
+    /// cancelled (i.e., `future` is dropped) once the connection is closed.
+    /// If `future` panics, the panic does not propagate: it is logged along with the session
+    /// context, then `policy` is applied.
+    pub fn spawn_supervised<F>(&self, future: F, policy: PanicPolicy)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let supervised = self.supervise(future, policy);
+
+        self.spawner().spawn(async move {
+            supervised.await;
+        });
+    }
+
+    fn supervise<F>(
+        &self,
+        future: F,
+        policy: PanicPolicy,
+    ) -> impl Future<Output = Supervised> + Send + 'static
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let quic_connection = self.quic_connection().clone();
+        let session_id = self.session_id();
+
+        async move {
+            tokio::select! {
+                result = CatchUnwind(Box::pin(future)) => match result {
+                    Ok(()) => Supervised::Completed,
+                    Err(panic) => {
+                        warn!(
+                            peer = %quic_connection.remote_address(),
+                            quic_id = quic_connection.stable_id(),
+                            session_id = ?session_id,
+                            policy = ?policy,
+                            "Supervised task panicked: {}",
+                            panic_message(panic.as_ref())
+                        );
+
+                        if let PanicPolicy::Close(error_code) = policy {
+                            quic_connection.close(varint_w2q(error_code), b"Task panicked");
+                        }
+
+                        Supervised::Panicked
+                    }
+                },
+                _ = quic_connection.closed() => Supervised::Cancelled,
+            }
+        }
+    }
+
+    /// Accepts incoming streams and datagrams until the connection ends, running `handler` on
+    /// each of them.
+    ///
+    /// Every handler runs on its own [supervised](Self::spawn_supervised) task, and at most
+    /// [`ServeLimits::max_concurrent_handlers`] run concurrently.
+    /// A panicking handler is isolated: it is counted in the returned report and handled
+    /// as configured by [`ServeLimits::on_panic`].
+    ///
+    /// Handlers still running when the connection ends are cancelled, not awaited.
+    pub async fn serve<H, F>(&self, handler: H, limits: ServeLimits) -> ServeReport
+    where
+        H: Fn(Incoming) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        let semaphore = Arc::new(Semaphore::new(limits.max_concurrent_handlers));
+        let panicked = Arc::new(AtomicU64::new(0));
+        let mut handled = 0;
+
+        let error = loop {
+            let permit = semaphore
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("Semaphore is never closed");
+
+            let incoming = tokio::select! {
+                stream = self.accept_bi() => stream.map(|(send, recv)| Incoming::Bi(send, recv)),
+                stream = self.accept_uni() => stream.map(Incoming::Uni),
+                datagram = self.receive_datagram(), if limits.datagrams => {
+                    datagram.map(Incoming::Datagram)
+                }
+            };
+
+            let incoming = match incoming {
+                Ok(incoming) => incoming,
+                Err(error) => break error,
+            };
+
+            handled += 1;
+
+            let handler = handler.clone();
+            let supervised =
+                self.supervise(async move { handler(incoming).await }, limits.panic_policy);
+            let panicked = panicked.clone();
+
+            self.spawner().spawn(async move {
+                if supervised.await == Supervised::Panicked {
+                    panicked.fetch_add(1, Ordering::Relaxed);
+                }
+
+                drop(permit);
+            });
+        };
+
+        debug!("Serving ended: {}", error);
+
+        ServeReport {
+            error,
+            handled,
+            panicked: panicked.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Future catching the panics of the inner future.
+struct CatchUnwind<F>(Pin<Box<F>>);
+
+impl<F> Future for CatchUnwind<F>
+where
+    F: Future,
+{
+    type Output = std::thread::Result<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let future = self.0.as_mut();
+
+        match std::panic::catch_unwind(AssertUnwindSafe(|| future.poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Err(panic) => Poll::Ready(Err(panic)),
+        }
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "<non-string payload>"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+    use std::time::Duration;
+
+    /// Waits until `counter` reaches `value`.
+    async fn wait_for(counter: &AtomicU64, value: u64) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while counter.load(Ordering::Relaxed) < value {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Counter reached");
+    }
+
+    #[derive(Default)]
+    struct Counters {
+        active: AtomicU64,
+        max_active: AtomicU64,
+        streams: AtomicU64,
+        datagrams: AtomicU64,
+    }
+
+    #[tokio::test]
+    async fn serve_bounded_concurrency() {
+        let peers = test_utils::connect().await;
+        let server_connection = peers.server_connection;
+        let counters = Arc::new(Counters::default());
+
+        let handler_counters = counters.clone();
+        let handler = move |incoming| {
+            let counters = handler_counters.clone();
+            async move {
+                let active = counters.active.fetch_add(1, Ordering::Relaxed) + 1;
+                counters.max_active.fetch_max(active, Ordering::Relaxed);
+
+                match incoming {
+                    Incoming::Uni(mut stream) => {
+                        let mut buffer = [0; 8];
+                        while let Ok(Some(_)) = stream.read(&mut buffer).await {}
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        counters.streams.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Incoming::Datagram(datagram) => {
+                        assert_eq!(datagram.payload().as_ref(), b"datagram");
+                        counters.datagrams.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Incoming::Bi(..) => unreachable!("No bidirectional stream is opened"),
+                }
+
+                counters.active.fetch_sub(1, Ordering::Relaxed);
+            }
+        };
+
+        let serving = tokio::spawn(async move {
+            server_connection
+                .serve(handler, ServeLimits::new().max_concurrent_handlers(2))
+                .await
+        });
+
+        for _ in 0..6 {
+            let mut stream = peers
+                .client_connection
+                .open_uni()
+                .await
+                .unwrap()
+                .await
+                .unwrap();
+            stream.write_all(b"stream").await.unwrap();
+            stream.finish().await.unwrap();
+        }
+        peers.client_connection.send_datagram(b"datagram").unwrap();
+
+        wait_for(&counters.streams, 6).await;
+        wait_for(&counters.datagrams, 1).await;
+        assert_eq!(counters.max_active.load(Ordering::Relaxed), 2);
+
+        peers.client_connection.close(VarInt::from_u32(0), b"");
+        let report = serving.await.unwrap();
+        assert!(matches!(
+            report.error(),
+            ConnectionError::ApplicationClosed(_)
+        ));
+        assert_eq!(report.handled(), 7);
+        assert_eq!(report.panicked(), 0);
+    }
+
+    #[tokio::test]
+    async fn serve_without_datagrams() {
+        let peers = test_utils::connect().await;
+        let server_connection = Arc::new(peers.server_connection);
+
+        let serving = server_connection.clone();
+        let serving = tokio::spawn(async move {
+            serving
+                .serve(
+                    |_incoming| async { panic!("Nothing to handle") },
+                    ServeLimits::new().datagrams(false),
+                )
+                .await
+        });
+
+        peers.client_connection.send_datagram(b"datagram").unwrap();
+        let datagram = server_connection.receive_datagram().await.unwrap();
+        assert_eq!(datagram.payload().as_ref(), b"datagram");
+
+        peers.client_connection.close(VarInt::from_u32(0), b"");
+        let report = serving.await.unwrap();
+        assert_eq!(report.handled(), 0);
+    }
+
+    #[tokio::test]
+    async fn supervised_panics() {
+        let peers = test_utils::connect().await;
+
+        // Logged only: the connection stays open.
+        let (done, finished) = tokio::sync::oneshot::channel::<()>();
+        peers.server_connection.spawn_supervised(
+            async move {
+                let _done = done;
+                panic!("Logged panic");
+            },
+            PanicPolicy::Log,
+        );
+        assert!(finished.await.is_err());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(peers.server_connection.is_alive());
+
+        // Closing the connection.
+        peers.server_connection.spawn_supervised(
+            async { panic!("Closing panic") },
+            PanicPolicy::Close(VarInt::from_u32(42)),
+        );
+        match peers
+            .client_connection
+            .accept_uni()
+            .await
+            .map(|_| ())
+            .unwrap_err()
+        {
+            ConnectionError::ApplicationClosed(close) => {
+                assert_eq!(close.code(), VarInt::from_u32(42));
+            }
+            error => panic!("Unexpected error: {error}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn supervised_cancelled_on_close() {
+        let peers = test_utils::connect().await;
+
+        let (dropped, cancelled) = tokio::sync::oneshot::channel::<()>();
+        peers.server_connection.spawn_supervised(
+            async move {
+                let _dropped = dropped;
+                std::future::pending::<()>().await;
+            },
+            PanicPolicy::Log,
+        );
+
+        peers.client_connection.close(VarInt::from_u32(0), b"");
+        tokio::time::timeout(Duration::from_secs(5), cancelled)
+            .await
+            .expect("Task cancelled")
+            .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn serve_counts_panics() {
+        let peers = test_utils::connect().await;
+        let server_connection = peers.server_connection;
+
+        let serving = tokio::spawn(async move {
+            server_connection
+                .serve(
+                    |_incoming| async { panic!("Handler panic") },
+                    ServeLimits::new(),
+                )
+                .await
+        });
+
+        for _ in 0..2 {
+            peers.client_connection.send_datagram(b"datagram").unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        peers.client_connection.close(VarInt::from_u32(0), b"");
+        let report = serving.await.unwrap();
+        assert_eq!(report.handled(), 2);
+        assert_eq!(report.panicked(), 2);
+    }
+}
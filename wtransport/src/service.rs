@@ -0,0 +1,75 @@
+use crate::endpoint::Server;
+use crate::endpoint::SessionRequest;
+use crate::Connection;
+use crate::Endpoint;
+use std::future::Future;
+use tower::BoxError;
+use tower::Service;
+use tower::ServiceExt;
+use tracing::debug;
+
+/// Decision taken by a session handler [`Service`] on a [`SessionRequest`].
+///
+/// See [`Endpoint::serve`].
+pub enum SessionHandlerDecision {
+    /// Accepts the request, establishing the WebTransport session.
+    Accept(SessionRequest),
+
+    /// Rejects the request (see [`SessionRequest::not_found`]).
+    Reject(SessionRequest),
+}
+
+impl Endpoint<Server> {
+    /// Runs the server, dispatching every session request to `service`.
+    ///
+    /// Each incoming session is processed on its own task: the request is passed to a
+    /// clone of `service` (therefore, standard `tower` layers like timeouts, rate limits or
+    /// load shedding can be composed around the session acceptance). Once accepted,
+    /// the connection is handed to `handler`.
+    ///
+    /// If `service` fails, the request is dropped and the underlying QUIC connection closed.
+    ///
+    /// This future never completes.
+    #[cfg_attr(docsrs, doc(cfg(feature = "tower")))]
+    pub async fn serve<S, H, F>(&self, service: S, handler: H)
+    where
+        S: Service<SessionRequest, Response = SessionHandlerDecision> + Clone + Send + 'static,
+        S::Future: Send,
+        S::Error: Into<BoxError>,
+        H: Fn(Connection) -> F + Clone + Send + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        loop {
+            let incoming_session = self.accept().await;
+            let service = service.clone();
+            let handler = handler.clone();
+
+            self.spawner().spawn(async move {
+                let session_request = match incoming_session.await {
+                    Ok(session_request) => session_request,
+                    Err(error) => {
+                        debug!("Incoming session failed: {}", error);
+                        return;
+                    }
+                };
+
+                let decision = service.oneshot(session_request).await.map_err(Into::into);
+
+                match decision {
+                    Ok(SessionHandlerDecision::Accept(session_request)) => {
+                        match session_request.accept().await {
+                            Ok(connection) => handler(connection).await,
+                            Err(error) => debug!("Session accept failed: {}", error),
+                        }
+                    }
+                    Ok(SessionHandlerDecision::Reject(session_request)) => {
+                        session_request.not_found().await;
+                    }
+                    Err(error) => {
+                        debug!("Session service failed: {}", error);
+                    }
+                }
+            });
+        }
+    }
+}
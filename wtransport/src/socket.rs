@@ -0,0 +1,584 @@
+use quinn::udp::RecvMeta;
+use quinn::udp::Transmit;
+use quinn::udp::UdpState;
+use quinn::AsyncUdpSocket;
+use socket2::Socket;
+use std::fmt::Debug;
+use std::io::IoSliceMut;
+use std::net::SocketAddr;
+use std::net::UdpSocket;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+use std::time::SystemTime;
+
+/// Application handler for UDP datagrams which are not QUIC packets.
+///
+/// It allows sharing the endpoint's UDP port with other protocols (e.g., STUN for ICE).
+/// Every incoming datagram whose first byte does not have the QUIC *fixed bit* set
+/// (see [RFC 9443](https://www.rfc-editor.org/rfc/rfc9443)) is passed to this handler
+/// instead of being silently dropped by the QUIC stack.
+///
+/// As the fixed bit demultiplexes the traffic, an endpoint with a handler never allows its
+/// peers to grease the bit (see
+/// [`ServerConfigBuilder::grease_quic_bit`](crate::config::ServerConfigBuilder::grease_quic_bit)).
+///
+/// The handler is invoked on the endpoint I/O task: it MUST NOT block.
+pub trait ExternalPacketHandler: Send + Sync + 'static {
+    /// Handles a non-QUIC datagram `packet` received from `source`.
+    ///
+    /// `socket` can be used to reply on the same UDP socket of the endpoint.
+    fn handle_packet(&self, packet: &[u8], source: SocketAddr, socket: &ExternalSocket);
+}
+
+/// Sending side of the endpoint socket for non-QUIC traffic.
+///
+/// See [`ExternalPacketHandler`].
+#[derive(Debug)]
+pub struct ExternalSocket(UdpSocket);
+
+impl ExternalSocket {
+    /// Sends a datagram to `destination` on the endpoint UDP socket.
+    ///
+    /// The socket is non-blocking: if the send buffer is full, an
+    /// [`std::io::ErrorKind::WouldBlock`] error is returned.
+    #[inline(always)]
+    pub fn send_to(&self, packet: &[u8], destination: SocketAddr) -> std::io::Result<usize> {
+        self.0.send_to(packet, destination)
+    }
+
+    /// Returns the local address the socket is bound to.
+    #[inline(always)]
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.0.local_addr()
+    }
+}
+
+/// Direction of a UDP datagram reported to a [`PacketTap`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PacketDirection {
+    /// Sent by the endpoint.
+    Sent,
+
+    /// Received by the endpoint.
+    Received,
+}
+
+/// Metadata of a UDP datagram reported to a [`PacketTap`].
+#[derive(Copy, Clone, Debug)]
+pub struct PacketInfo {
+    timestamp: SystemTime,
+    size: usize,
+    direction: PacketDirection,
+    remote: SocketAddr,
+}
+
+impl PacketInfo {
+    /// Returns the time the datagram was handed to, or returned by, the socket.
+    #[inline(always)]
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+
+    /// Returns the size of the UDP payload, in bytes.
+    #[inline(always)]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns whether the datagram was sent or received.
+    #[inline(always)]
+    pub fn direction(&self) -> PacketDirection {
+        self.direction
+    }
+
+    /// Returns the address of the peer (destination or source).
+    #[inline(always)]
+    pub fn remote(&self) -> SocketAddr {
+        self.remote
+    }
+}
+
+/// Application tap for the UDP datagrams of the endpoint socket.
+///
+/// Every datagram sent or received by the QUIC stack is reported, as well as the
+/// non-QUIC datagrams received (see [`ExternalPacketHandler`]). Datagrams sent directly
+/// with an [`ExternalSocket`] are not reported. Batched (GSO/GRO) datagrams are reported
+/// one by one.
+///
+/// The tap only observes: the datagrams are not altered. It is invoked on the endpoint
+/// I/O task: it MUST NOT block.
+pub trait PacketTap: Send + Sync + 'static {
+    /// Reports a datagram sent or received by the endpoint.
+    fn on_packet(&self, packet: &PacketInfo);
+}
+
+impl<F> PacketTap for F
+where
+    F: Fn(&PacketInfo) + Send + Sync + 'static,
+{
+    #[inline(always)]
+    fn on_packet(&self, packet: &PacketInfo) {
+        self(packet)
+    }
+}
+
+/// Statistics of a UDP socket of an endpoint, as reported by the operating system.
+///
+/// They tell whether datagrams are lost in the local receive buffer (e.g., when the
+/// endpoint does not keep up) rather than on the network. Values the operating system
+/// does not report are `None`: drops and queue depths are only available on Linux.
+///
+/// See [`Endpoint::socket_stats`](crate::Endpoint::socket_stats).
+#[derive(Copy, Clone, Debug)]
+pub struct SocketStats {
+    local_address: SocketAddr,
+    receive_buffer_size: Option<usize>,
+    send_buffer_size: Option<usize>,
+    receive_queue: Option<u64>,
+    send_queue: Option<u64>,
+    drops: Option<u64>,
+}
+
+impl SocketStats {
+    pub(crate) fn read(socket: &Socket) -> std::io::Result<Self> {
+        let local_address = socket
+            .local_addr()?
+            .as_socket()
+            .expect("UDP socket has an IP address");
+
+        let queues = meminfo::read(socket);
+
+        Ok(Self {
+            local_address,
+            receive_buffer_size: socket.recv_buffer_size().ok(),
+            send_buffer_size: socket.send_buffer_size().ok(),
+            receive_queue: queues.map(|queues| queues.receive_queue),
+            send_queue: queues.map(|queues| queues.send_queue),
+            drops: queues.map(|queues| queues.drops),
+        })
+    }
+
+    /// Returns the local address the socket is bound to.
+    #[inline(always)]
+    pub fn local_address(&self) -> SocketAddr {
+        self.local_address
+    }
+
+    /// Returns the size of the receive buffer (`SO_RCVBUF`), in bytes.
+    ///
+    /// **Note**: Linux reports twice the requested size, to account for its bookkeeping.
+    #[inline(always)]
+    pub fn receive_buffer_size(&self) -> Option<usize> {
+        self.receive_buffer_size
+    }
+
+    /// Returns the size of the send buffer (`SO_SNDBUF`), in bytes.
+    #[inline(always)]
+    pub fn send_buffer_size(&self) -> Option<usize> {
+        self.send_buffer_size
+    }
+
+    /// Returns the memory used by the datagrams waiting to be read by the endpoint, in bytes.
+    #[inline(always)]
+    pub fn receive_queue(&self) -> Option<u64> {
+        self.receive_queue
+    }
+
+    /// Returns the memory used by the datagrams waiting to be sent, in bytes.
+    #[inline(always)]
+    pub fn send_queue(&self) -> Option<u64> {
+        self.send_queue
+    }
+
+    /// Returns the number of datagrams dropped by the operating system since the socket
+    /// creation, mostly because the receive buffer was full.
+    #[inline(always)]
+    pub fn drops(&self) -> Option<u64> {
+        self.drops
+    }
+}
+
+/// Queue depths and drops of a socket, read with the `SO_MEMINFO` socket option.
+///
+/// The drop counter is the one the kernel reports per datagram with `SO_RXQ_OVFL`. That
+/// option is not enabled: its control message would not fit in the control buffer the
+/// QUIC stack passes to `recvmsg`, and the packet info and ECN messages would be truncated.
+#[cfg(target_os = "linux")]
+mod meminfo {
+    use socket2::Socket;
+    use std::os::unix::io::AsRawFd;
+
+    #[cfg(not(any(target_arch = "sparc", target_arch = "sparc64")))]
+    const SO_MEMINFO: libc::c_int = 55;
+    #[cfg(any(target_arch = "sparc", target_arch = "sparc64"))]
+    const SO_MEMINFO: libc::c_int = 0x0057;
+
+    /// Number of values reported by `SO_MEMINFO`.
+    const SK_MEMINFO_VARS: usize = libc::SK_MEMINFO_DROPS as usize + 1;
+
+    #[derive(Copy, Clone)]
+    pub(super) struct Queues {
+        pub(super) receive_queue: u64,
+        pub(super) send_queue: u64,
+        pub(super) drops: u64,
+    }
+
+    pub(super) fn read(socket: &Socket) -> Option<Queues> {
+        let mut meminfo = [0u32; SK_MEMINFO_VARS];
+        let mut len = std::mem::size_of_val(&meminfo) as libc::socklen_t;
+
+        // SAFETY: the buffer and its length are valid for the call.
+        let result = unsafe {
+            libc::getsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_SOCKET,
+                SO_MEMINFO,
+                meminfo.as_mut_ptr().cast(),
+                &mut len,
+            )
+        };
+
+        if result != 0 || (len as usize) < std::mem::size_of_val(&meminfo) {
+            return None;
+        }
+
+        Some(Queues {
+            receive_queue: meminfo[libc::SK_MEMINFO_RMEM_ALLOC as usize].into(),
+            send_queue: meminfo[libc::SK_MEMINFO_WMEM_ALLOC as usize].into(),
+            drops: meminfo[libc::SK_MEMINFO_DROPS as usize].into(),
+        })
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod meminfo {
+    use socket2::Socket;
+
+    #[derive(Copy, Clone)]
+    pub(super) struct Queues {
+        pub(super) receive_queue: u64,
+        pub(super) send_queue: u64,
+        pub(super) drops: u64,
+    }
+
+    pub(super) fn read(_socket: &Socket) -> Option<Queues> {
+        None
+    }
+}
+
+/// A UDP socket demultiplexing QUIC and external traffic, and reporting datagrams to a tap.
+pub(crate) struct DemuxSocket {
+    inner: Box<dyn AsyncUdpSocket>,
+    handler: Option<(Arc<dyn ExternalPacketHandler>, ExternalSocket)>,
+    tap: Option<Arc<dyn PacketTap>>,
+}
+
+impl DemuxSocket {
+    pub(crate) fn new(
+        inner: Box<dyn AsyncUdpSocket>,
+        handler: Option<(Arc<dyn ExternalPacketHandler>, UdpSocket)>,
+        tap: Option<Arc<dyn PacketTap>>,
+    ) -> Self {
+        Self {
+            inner,
+            handler: handler.map(|(handler, external)| (handler, ExternalSocket(external))),
+            tap,
+        }
+    }
+
+    #[inline(always)]
+    fn report(tap: &dyn PacketTap, size: usize, direction: PacketDirection, remote: SocketAddr) {
+        tap.on_packet(&PacketInfo {
+            timestamp: SystemTime::now(),
+            size,
+            direction,
+            remote,
+        });
+    }
+
+    /// Checks whether a datagram might carry a QUIC packet.
+    ///
+    /// Long header packets of a supported QUIC version are recognized even with the fixed
+    /// bit cleared (e.g., a client greasing it from the parameters of a previous connection).
+    #[inline(always)]
+    fn is_quic(datagram: &[u8]) -> bool {
+        const LONG_HEADER: u8 = 0x80;
+        const FIXED_BIT: u8 = 0x40;
+
+        match datagram {
+            [first, ..] if first & FIXED_BIT != 0 => true,
+            [first, v0, v1, v2, v3, ..] if first & LONG_HEADER != 0 => {
+                let version = u32::from_be_bytes([*v0, *v1, *v2, *v3]);
+                quinn_proto::DEFAULT_SUPPORTED_VERSIONS.contains(&version)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl AsyncUdpSocket for DemuxSocket {
+    #[inline(always)]
+    fn poll_send(
+        &self,
+        state: &UdpState,
+        cx: &mut Context,
+        transmits: &[Transmit],
+    ) -> Poll<std::io::Result<usize>> {
+        let result = self.inner.poll_send(state, cx, transmits);
+
+        if let (Some(tap), Poll::Ready(Ok(num_transmits))) = (&self.tap, &result) {
+            for transmit in transmits.iter().take(*num_transmits) {
+                let segment_size = transmit
+                    .segment_size
+                    .unwrap_or(transmit.contents.len())
+                    .max(1);
+
+                for datagram in transmit.contents.chunks(segment_size) {
+                    Self::report(
+                        tap.as_ref(),
+                        datagram.len(),
+                        PacketDirection::Sent,
+                        transmit.destination,
+                    );
+                }
+            }
+        }
+
+        result
+    }
+
+    fn poll_recv(
+        &self,
+        cx: &mut Context,
+        bufs: &mut [IoSliceMut<'_>],
+        meta: &mut [RecvMeta],
+    ) -> Poll<std::io::Result<usize>> {
+        let num_msgs = match self.inner.poll_recv(cx, bufs, meta) {
+            Poll::Ready(Ok(num_msgs)) => num_msgs,
+            other => return other,
+        };
+
+        for (buf, meta) in bufs.iter().zip(meta.iter_mut()).take(num_msgs) {
+            let stride = meta.stride.max(1);
+            let mut all_external = true;
+
+            for datagram in buf[..meta.len].chunks(stride) {
+                if let Some(tap) = &self.tap {
+                    Self::report(
+                        tap.as_ref(),
+                        datagram.len(),
+                        PacketDirection::Received,
+                        meta.addr,
+                    );
+                }
+
+                match &self.handler {
+                    Some((handler, external)) if !Self::is_quic(datagram) => {
+                        handler.handle_packet(datagram, meta.addr, external);
+                    }
+                    _ => all_external = false,
+                }
+            }
+
+            // Segments not consumed here are anyway discarded by the QUIC stack
+            // as they cannot be decoded.
+            if all_external {
+                meta.len = 0;
+            }
+        }
+
+        Poll::Ready(Ok(num_msgs))
+    }
+
+    #[inline(always)]
+    fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    #[inline(always)]
+    fn may_fragment(&self) -> bool {
+        self.inner.may_fragment()
+    }
+}
+
+impl Debug for DemuxSocket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DemuxSocket")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+    use crate::Endpoint;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+
+    #[derive(Clone, Default)]
+    struct CountingHandler(Arc<AtomicUsize>);
+
+    impl ExternalPacketHandler for CountingHandler {
+        fn handle_packet(&self, _packet: &[u8], _source: SocketAddr, _socket: &ExternalSocket) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// A long header Initial packet of QUIC v1, with the fixed bit greased.
+    fn greased_initial() -> Vec<u8> {
+        let mut packet = vec![0x80, 0x00, 0x00, 0x00, 0x01];
+        packet.resize(1200, 0);
+        packet
+    }
+
+    #[test]
+    fn demux() {
+        // STUN binding request, DTLS and RTP.
+        assert!(!DemuxSocket::is_quic(&[0x00, 0x01, 0x00, 0x00]));
+        assert!(!DemuxSocket::is_quic(&[0x16, 0xfe, 0xfd, 0x00]));
+        assert!(!DemuxSocket::is_quic(&[0x80, 0x60, 0x12, 0x34, 0x00]));
+        assert!(!DemuxSocket::is_quic(&[]));
+
+        // Long header (v1) and short header.
+        assert!(DemuxSocket::is_quic(&[0xc0, 0x00, 0x00, 0x00, 0x01]));
+        assert!(DemuxSocket::is_quic(&[0x40, 0x12]));
+
+        // Greased long header.
+        assert!(DemuxSocket::is_quic(&greased_initial()));
+    }
+
+    #[tokio::test]
+    async fn greased_packet_not_external() {
+        let handler = CountingHandler::default();
+        let config = test_utils::server_config(test_utils::certificate())
+            .external_packet_handler(handler.clone())
+            .build();
+        let server = Endpoint::server(config).unwrap();
+        let server_address = server.local_addr().unwrap();
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.send_to(&greased_initial(), server_address).unwrap();
+        socket
+            .send_to(&[0x00, 0x01, 0x00, 0x00], server_address)
+            .unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while handler.0.load(Ordering::Relaxed) == 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("STUN packet handled");
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(handler.0.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn session_with_handler() {
+        let handler = CountingHandler::default();
+        let certificate = test_utils::certificate();
+        let client_config = test_utils::client_config(&certificate)
+            .external_packet_handler(handler.clone())
+            .build();
+        let server_config = test_utils::server_config(certificate)
+            .external_packet_handler(handler.clone())
+            .build();
+
+        let peers = test_utils::connect_with(server_config, client_config).await;
+        let mut stream = peers
+            .client_connection
+            .open_uni()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        stream.write_all(b"ping").await.unwrap();
+        stream.finish().await.unwrap();
+
+        let mut stream = peers.server_connection.accept_uni().await.unwrap();
+        let mut buffer = [0; 4];
+        stream.read_exact(&mut buffer).await.unwrap();
+
+        assert_eq!(&buffer, b"ping");
+        assert_eq!(handler.0.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn packet_tap() {
+        let certificate = test_utils::certificate();
+        let client_packets = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let server_packets = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let tap = |packets: &Arc<std::sync::Mutex<Vec<PacketInfo>>>| {
+            let packets = packets.clone();
+            move |packet: &PacketInfo| packets.lock().unwrap().push(*packet)
+        };
+        let client_config = test_utils::client_config(&certificate)
+            .packet_tap(tap(&client_packets))
+            .build();
+        let server_config = test_utils::server_config(certificate)
+            .packet_tap(tap(&server_packets))
+            .build();
+
+        let before = SystemTime::now();
+        let peers = test_utils::connect_with(server_config, client_config).await;
+        let server_address = peers.server.local_addr().unwrap();
+        let client_address = peers.client.local_addr().unwrap();
+
+        let client_packets = client_packets.lock().unwrap().clone();
+        let server_packets = server_packets.lock().unwrap().clone();
+
+        for (packets, remote) in [
+            (&client_packets, server_address),
+            (&server_packets, client_address),
+        ] {
+            for direction in [PacketDirection::Sent, PacketDirection::Received] {
+                assert!(packets.iter().any(|packet| packet.direction() == direction));
+            }
+
+            assert!(packets.iter().all(|packet| packet.remote() == remote
+                && packet.size() > 0
+                && packet.timestamp() >= before));
+        }
+
+        // The first packet is the padded Initial packet of the client.
+        assert_eq!(client_packets[0].direction(), PacketDirection::Sent);
+        assert!(client_packets[0].size() >= 1200);
+        assert_eq!(server_packets[0].direction(), PacketDirection::Received);
+        assert_eq!(server_packets[0].size(), client_packets[0].size());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn socket_stats() {
+        use socket2::Domain;
+        use socket2::Type;
+
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, None).unwrap();
+        socket.set_recv_buffer_size(4096).unwrap();
+        socket
+            .bind(&SocketAddr::from(([127, 0, 0, 1], 0)).into())
+            .unwrap();
+
+        let stats = SocketStats::read(&socket).unwrap();
+        assert_eq!(stats.receive_queue(), Some(0));
+        assert_eq!(stats.send_queue(), Some(0));
+        assert_eq!(stats.drops(), Some(0));
+        assert!(stats.receive_buffer_size().unwrap() >= 4096);
+
+        // Nothing is read: the receive buffer overflows.
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        for _ in 0..64 {
+            sender.send_to(&[0; 1024], stats.local_address()).unwrap();
+        }
+
+        let stats = SocketStats::read(&socket).unwrap();
+        assert!(stats.receive_queue().unwrap() > 0);
+        assert!(stats.drops().unwrap() > 0);
+    }
+}
@@ -3,13 +3,27 @@ use crate::driver::streams::unilocal::StreamUniLocalQuic;
 use crate::driver::streams::ProtoWriteError;
 use crate::driver::streams::QuicRecvStream;
 use crate::driver::streams::QuicSendStream;
+use crate::driver::utils::varint_q2w;
+#[cfg(feature = "chaos")]
+use crate::driver::utils::varint_w2q;
+use crate::driver::utils::OpenQueue;
+use crate::driver::utils::StreamFlow;
+use crate::driver::utils::StreamGuard;
+use crate::error::ExpiringWriteError;
 use crate::error::StreamOpeningError;
 use crate::error::StreamReadError;
+use crate::error::StreamReadExactError;
 use crate::error::StreamWriteError;
+use crate::Connection;
+use bytes::Bytes;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::task::ready;
 use std::task::Context;
 use std::task::Poll;
+use std::time::Duration;
+use std::time::Instant;
 use tokio::io::ReadBuf;
 use wtransport_proto::ids::SessionId;
 use wtransport_proto::ids::StreamId;
@@ -17,12 +31,25 @@ use wtransport_proto::stream_header::StreamHeader;
 use wtransport_proto::varint::VarInt;
 
 /// A stream that can only be used to send data.
-pub struct SendStream(QuicSendStream);
+pub struct SendStream {
+    stream: QuicSendStream,
+    guard: StreamGuard,
+    flow: Arc<StreamFlow>,
+    #[cfg(feature = "chaos")]
+    chaos_reset: Option<VarInt>,
+}
 
 impl SendStream {
     #[inline(always)]
-    pub(crate) fn new(stream: QuicSendStream) -> Self {
-        Self(stream)
+    pub(crate) fn new(stream: QuicSendStream, guard: StreamGuard) -> Self {
+        let flow = guard.stream_flow(stream.id());
+        Self {
+            stream,
+            guard,
+            flow,
+            #[cfg(feature = "chaos")]
+            chaos_reset: None,
+        }
     }
 
     /// Writes bytes to the stream.
@@ -32,28 +59,115 @@ impl SendStream {
     /// indicating that only a prefix of `buf` was written.
     #[inline(always)]
     pub async fn write(&mut self, buf: &[u8]) -> Result<usize, StreamWriteError> {
-        self.0.write(buf).await
+        #[cfg(feature = "chaos")]
+        self.inject_chaos()?;
+        let written = self.stream.write(buf).await?;
+        self.guard.touch();
+        self.flow.sent(written);
+        Ok(written)
     }
 
     /// Convenience method to write an entire buffer to the stream.
     #[inline(always)]
     pub async fn write_all(&mut self, buf: &[u8]) -> Result<(), StreamWriteError> {
-        self.0.write_all(buf).await
+        #[cfg(feature = "chaos")]
+        self.inject_chaos()?;
+        self.stream.write_all(buf).await?;
+        self.guard.touch();
+        self.flow.sent(buf.len());
+        Ok(())
+    }
+
+    /// Writes a chunk of data to the stream, without copy.
+    ///
+    /// The whole chunk is written: this waits for congestion and flow control as needed.
+    /// See [`RecvStream::read_chunk`] and [`copy_spliced`](crate::io::copy_spliced).
+    #[inline(always)]
+    pub async fn write_chunk(&mut self, chunk: Bytes) -> Result<(), StreamWriteError> {
+        #[cfg(feature = "chaos")]
+        self.inject_chaos()?;
+        let len = chunk.len();
+        self.stream.write_chunk(chunk).await?;
+        self.guard.touch();
+        self.flow.sent(len);
+        Ok(())
     }
 
     /// Shut down the stream gracefully.
     ///
     /// No new data may be written after calling this method. Completes when the peer has
-    /// acknowledged all sent data, retransmitting data as needed.
+    /// acknowledged all sent data, including the end of the stream (FIN), retransmitting
+    /// data as needed. If the peer stops the stream first, [`StreamWriteError::Stopped`]
+    /// is returned.
+    ///
+    /// This only closes the sending side: the [`RecvStream`] of a bidirectional stream
+    /// remains readable (half-close), and the peer observes [`StreamEnd::Finished`].
     #[inline(always)]
     pub async fn finish(&mut self) -> Result<(), StreamWriteError> {
-        self.0.finish().await
+        self.stream.finish().await
+    }
+
+    /// Polls to write bytes to the stream.
+    ///
+    /// This is the poll-based variant of [`write`](Self::write), for manual [`Future`]
+    /// implementations.
+    #[inline(always)]
+    pub fn poll_write(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, StreamWriteError>> {
+        #[cfg(feature = "chaos")]
+        self.inject_chaos()?;
+        let result = ready!(self.stream.poll_write(cx, buf));
+        if let Ok(written) = result {
+            self.guard.touch();
+            self.flow.sent(written);
+        }
+        Poll::Ready(result)
+    }
+
+    /// Polls to shut down the stream gracefully.
+    ///
+    /// See [`finish`](Self::finish).
+    #[inline(always)]
+    pub fn poll_finish(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), StreamWriteError>> {
+        self.stream.poll_finish(cx)
+    }
+
+    /// Polls for the stream to be stopped by the peer.
+    ///
+    /// See [`stopped`](Self::stopped).
+    #[inline(always)]
+    pub fn poll_stopped(&mut self, cx: &mut Context<'_>) -> Poll<StreamWriteError> {
+        self.stream.poll_stopped(cx)
     }
 
     /// Returns the [`StreamId`] associated.
     #[inline(always)]
     pub fn id(&self) -> StreamId {
-        self.0.id()
+        self.stream.id()
+    }
+
+    /// Returns the endpoint that opened the stream.
+    #[inline(always)]
+    pub fn initiator(&self) -> StreamInitiator {
+        StreamInitiator::of(self.id())
+    }
+
+    /// Returns whether the stream is unidirectional or bidirectional.
+    #[inline(always)]
+    pub fn direction(&self) -> StreamDirection {
+        StreamDirection::of(self.id())
+    }
+
+    /// Returns the ID of the session the stream belongs to.
+    ///
+    /// It is `None` for the streams outside of any session (see
+    /// [`Connection::accept_unknown`](crate::Connection::accept_unknown)).
+    #[inline(always)]
+    pub fn session_id(&self) -> Option<SessionId> {
+        self.guard.session_id()
     }
 
     /// Sets the priority of the send stream.
@@ -65,13 +179,13 @@ impl SendStream {
     /// impact on performance.
     #[inline(always)]
     pub fn set_priority(&self, priority: i32) {
-        self.0.set_priority(priority)
+        self.stream.set_priority(priority)
     }
 
     /// Gets the priority of the send stream.
     #[inline(always)]
     pub fn priority(&self) -> i32 {
-        self.0.priority()
+        self.stream.priority()
     }
 
     /// Closes the send stream immediately.
@@ -81,7 +195,7 @@ impl SendStream {
     /// already been made to finish the stream, the peer may still receive all written data.
     #[inline(always)]
     pub fn reset(self, error_code: VarInt) {
-        self.0.reset(error_code)
+        self.stream.reset(error_code)
     }
 
     /// Awaits for the stream to be stopped by the peer.
@@ -89,31 +203,301 @@ impl SendStream {
     /// If the stream is stopped the error code will be stored in [`StreamWriteError::Stopped`].
     #[inline(always)]
     pub async fn stopped(mut self) -> StreamWriteError {
-        self.0.stopped().await
+        self.stream.stopped().await
+    }
+
+    /// Applies the reset injected into the stream: writes then fail as if the peer
+    /// stopped it.
+    #[cfg(feature = "chaos")]
+    fn inject_chaos(&mut self) -> Result<(), StreamWriteError> {
+        if let Some(error_code) = self.flow.take_injected_reset() {
+            self.stream.abort(error_code);
+            self.chaos_reset = Some(error_code);
+        }
+
+        match self.chaos_reset {
+            Some(error_code) => Err(StreamWriteError::Stopped(error_code)),
+            None => Ok(()),
+        }
     }
 }
 
 /// A stream that can only be used to receive data.
-pub struct RecvStream(QuicRecvStream);
+pub struct RecvStream {
+    stream: QuicRecvStream,
+    guard: StreamGuard,
+    flow: Arc<StreamFlow>,
+    end: Option<StreamEnd>,
+    #[cfg(feature = "chaos")]
+    chaos_stopped: Option<VarInt>,
+    #[cfg(feature = "chaos")]
+    chaos_stall: Option<Pin<Box<tokio::time::Sleep>>>,
+}
 
 impl RecvStream {
     #[inline(always)]
-    pub(crate) fn new(stream: QuicRecvStream) -> Self {
-        Self(stream)
+    pub(crate) fn new(stream: QuicRecvStream, guard: StreamGuard) -> Self {
+        let flow = guard.stream_flow(stream.id());
+        Self {
+            stream,
+            guard,
+            flow,
+            end: None,
+            #[cfg(feature = "chaos")]
+            chaos_stopped: None,
+            #[cfg(feature = "chaos")]
+            chaos_stall: None,
+        }
     }
 
     /// Read data contiguously from the stream.
     ///
-    /// On success, returns the number of bytes read into `buf`.
+    /// On success, returns the number of bytes read into `buf`, or `None` once the peer
+    /// finished the stream. If the peer reset the stream, [`StreamReadError::Reset`] is
+    /// returned instead (see [`end`](Self::end)).
     #[inline(always)]
     pub async fn read(&mut self, buf: &mut [u8]) -> Result<Option<usize>, StreamReadError> {
-        self.0.read(buf).await
+        #[cfg(feature = "chaos")]
+        std::future::poll_fn(|cx| self.poll_chaos(cx)).await?;
+        let result = self.stream.read(buf).await;
+        self.record(&result);
+        let read = result?;
+        self.guard.touch();
+        self.flow.received(read.unwrap_or(0));
+        Ok(read)
+    }
+
+    /// Polls to read data contiguously from the stream.
+    ///
+    /// This is the poll-based variant of [`read`](Self::read), for manual [`Future`]
+    /// implementations.
+    #[inline(always)]
+    pub fn poll_read(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<Option<usize>, StreamReadError>> {
+        #[cfg(feature = "chaos")]
+        ready!(self.poll_chaos(cx))?;
+        let result = ready!(self.stream.poll_read(cx, buf));
+        self.record(&result);
+        if let Ok(read) = result {
+            self.guard.touch();
+            self.flow.received(read.unwrap_or(0));
+        }
+        Poll::Ready(result)
+    }
+
+    /// Reads data contiguously from the stream, until `buf` is completely filled.
+    #[inline(always)]
+    pub async fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), StreamReadExactError> {
+        #[cfg(feature = "chaos")]
+        std::future::poll_fn(|cx| self.poll_chaos(cx))
+            .await
+            .map_err(StreamReadExactError::Read)?;
+        match self.stream.read_exact(buf).await {
+            Ok(()) => {}
+            Err(StreamReadExactError::FinishedEarly) => {
+                self.end = Some(StreamEnd::Finished);
+                return Err(StreamReadExactError::FinishedEarly);
+            }
+            Err(StreamReadExactError::Read(error)) => {
+                self.record_error(&error);
+                return Err(StreamReadExactError::Read(error));
+            }
+        }
+        self.guard.touch();
+        self.flow.received(buf.len());
+        Ok(())
+    }
+
+    /// Reads the next chunk of data from the stream, of at most `max_length` bytes, without
+    /// copy.
+    ///
+    /// Returns `None` once the peer finished the stream, like [`read`](Self::read).
+    #[inline(always)]
+    pub async fn read_chunk(
+        &mut self,
+        max_length: usize,
+    ) -> Result<Option<Bytes>, StreamReadError> {
+        #[cfg(feature = "chaos")]
+        std::future::poll_fn(|cx| self.poll_chaos(cx)).await?;
+        let chunk = match self.stream.read_chunk(max_length).await {
+            Ok(chunk) => chunk,
+            Err(error) => {
+                self.record_error(&error);
+                return Err(error);
+            }
+        };
+        match &chunk {
+            Some(chunk) => {
+                self.guard.touch();
+                self.flow.received(chunk.len());
+            }
+            None => self.end = Some(StreamEnd::Finished),
+        }
+        Ok(chunk)
+    }
+
+    /// Returns how the peer ended the stream, once observed by a read.
+    ///
+    /// This tells a graceful end of the data ([`StreamEnd::Finished`]) from an abort
+    /// ([`StreamEnd::Reset`]). It is `None` as long as reads have not reached the end.
+    #[inline(always)]
+    pub fn end(&self) -> Option<StreamEnd> {
+        self.end
+    }
+
+    /// Closes the receive stream immediately, asking the peer to stop sending with `error_code`.
+    ///
+    /// The [`SendStream`] of a bidirectional stream is not affected. The peer observes
+    /// [`StreamWriteError::Stopped`].
+    #[inline(always)]
+    pub fn stop(mut self, error_code: VarInt) {
+        // The stream might already be finished or reset by the peer.
+        let _ = self.stream.stop(error_code);
     }
 
     /// Returns the [`StreamId`] associated.
     #[inline(always)]
     pub fn id(&self) -> StreamId {
-        self.0.id()
+        self.stream.id()
+    }
+
+    /// Returns the endpoint that opened the stream.
+    #[inline(always)]
+    pub fn initiator(&self) -> StreamInitiator {
+        StreamInitiator::of(self.id())
+    }
+
+    /// Returns whether the stream is unidirectional or bidirectional.
+    #[inline(always)]
+    pub fn direction(&self) -> StreamDirection {
+        StreamDirection::of(self.id())
+    }
+
+    /// Returns the ID of the session the stream belongs to.
+    ///
+    /// It is `None` for the streams outside of any session (see
+    /// [`Connection::accept_unknown`](crate::Connection::accept_unknown)).
+    #[inline(always)]
+    pub fn session_id(&self) -> Option<SessionId> {
+        self.guard.session_id()
+    }
+
+    /// Waits for the stall of the reads to end, then applies the stop injected into the
+    /// stream: reads then fail as if the peer reset it.
+    #[cfg(feature = "chaos")]
+    fn poll_chaos(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), StreamReadError>> {
+        if let Some(deadline) = self.flow.stalled_until() {
+            let stall = self
+                .chaos_stall
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline)));
+            if stall.deadline() != deadline {
+                stall.as_mut().reset(deadline);
+            }
+            ready!(stall.as_mut().poll(cx));
+        }
+        self.chaos_stall = None;
+
+        if let Some(error_code) = self.flow.take_injected_stop() {
+            let _ = self.stream.stop(error_code);
+            self.chaos_stopped = Some(error_code);
+        }
+
+        match self.chaos_stopped {
+            Some(error_code) => {
+                self.end = Some(StreamEnd::Reset(error_code));
+                Poll::Ready(Err(StreamReadError::Reset(error_code)))
+            }
+            None => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn record(&mut self, result: &Result<Option<usize>, StreamReadError>) {
+        match result {
+            Ok(Some(_)) => {}
+            Ok(None) => self.end = Some(StreamEnd::Finished),
+            Err(error) => self.record_error(error),
+        }
+    }
+
+    fn record_error(&mut self, error: &StreamReadError) {
+        if let Some(code) = error.reset_code() {
+            self.end = Some(StreamEnd::Reset(code));
+        }
+    }
+}
+
+/// How the peer ended its sending side of a stream.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StreamEnd {
+    /// The peer finished the stream: all its data was delivered.
+    Finished,
+
+    /// The peer reset the stream with an error code, abandoning the data not delivered yet.
+    Reset(VarInt),
+}
+
+/// The endpoint that opened a stream.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StreamInitiator {
+    /// The stream was opened by the client.
+    Client,
+
+    /// The stream was opened by the server.
+    Server,
+}
+
+impl StreamInitiator {
+    fn of(id: StreamId) -> Self {
+        if id.is_client_initiated() {
+            Self::Client
+        } else {
+            Self::Server
+        }
+    }
+}
+
+/// The directionality of a stream.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StreamDirection {
+    /// Only the initiator sends data.
+    Unidirectional,
+
+    /// Both endpoints send data.
+    Bidirectional,
+}
+
+impl StreamDirection {
+    fn of(id: StreamId) -> Self {
+        if id.is_bidirectional() {
+            Self::Bidirectional
+        } else {
+            Self::Unidirectional
+        }
+    }
+}
+
+impl std::fmt::Debug for SendStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SendStream")
+            .field("id", &self.id())
+            .field("initiator", &self.initiator())
+            .field("direction", &self.direction())
+            .field("session_id", &self.session_id())
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for RecvStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RecvStream")
+            .field("id", &self.id())
+            .field("initiator", &self.initiator())
+            .field("direction", &self.direction())
+            .field("session_id", &self.session_id())
+            .finish()
     }
 }
 
@@ -124,17 +508,28 @@ impl tokio::io::AsyncWrite for SendStream {
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<std::io::Result<usize>> {
-        tokio::io::AsyncWrite::poll_write(Pin::new(&mut self.0), cx, buf)
+        #[cfg(feature = "chaos")]
+        self.inject_chaos().map_err(chaos_write_error)?;
+        let result = ready!(tokio::io::AsyncWrite::poll_write(
+            Pin::new(&mut self.stream),
+            cx,
+            buf
+        ));
+        if let Ok(written) = result {
+            self.guard.touch();
+            self.flow.sent(written);
+        }
+        Poll::Ready(result)
     }
 
     #[inline(always)]
     fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
-        tokio::io::AsyncWrite::poll_flush(Pin::new(&mut self.0), cx)
+        tokio::io::AsyncWrite::poll_flush(Pin::new(&mut self.stream), cx)
     }
 
     #[inline(always)]
     fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
-        tokio::io::AsyncWrite::poll_shutdown(Pin::new(&mut self.0), cx)
+        tokio::io::AsyncWrite::poll_shutdown(Pin::new(&mut self.stream), cx)
     }
 
     #[inline(always)]
@@ -143,12 +538,23 @@ impl tokio::io::AsyncWrite for SendStream {
         cx: &mut Context<'_>,
         bufs: &[std::io::IoSlice<'_>],
     ) -> Poll<Result<usize, std::io::Error>> {
-        tokio::io::AsyncWrite::poll_write_vectored(Pin::new(&mut self.0), cx, bufs)
+        #[cfg(feature = "chaos")]
+        self.inject_chaos().map_err(chaos_write_error)?;
+        let result = ready!(tokio::io::AsyncWrite::poll_write_vectored(
+            Pin::new(&mut self.stream),
+            cx,
+            bufs
+        ));
+        if let Ok(written) = result {
+            self.guard.touch();
+            self.flow.sent(written);
+        }
+        Poll::Ready(result)
     }
 
     #[inline(always)]
     fn is_write_vectored(&self) -> bool {
-        tokio::io::AsyncWrite::is_write_vectored(&self.0)
+        tokio::io::AsyncWrite::is_write_vectored(&self.stream)
     }
 }
 
@@ -159,7 +565,158 @@ impl tokio::io::AsyncRead for RecvStream {
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>,
     ) -> Poll<std::io::Result<()>> {
-        tokio::io::AsyncRead::poll_read(Pin::new(&mut self.0), cx, buf)
+        #[cfg(feature = "chaos")]
+        if let Err(StreamReadError::Reset(error_code)) = ready!(self.poll_chaos(cx)) {
+            return Poll::Ready(Err(std::io::Error::new(
+                std::io::ErrorKind::ConnectionReset,
+                quinn::ReadError::Reset(varint_w2q(error_code)),
+            )));
+        }
+        let filled = buf.filled().len();
+        let result = ready!(tokio::io::AsyncRead::poll_read(
+            Pin::new(&mut self.stream),
+            cx,
+            buf
+        ));
+        match &result {
+            Ok(()) if buf.filled().len() == filled && buf.remaining() > 0 => {
+                self.end = Some(StreamEnd::Finished);
+            }
+            Ok(()) => {}
+            Err(io_error) => {
+                if let Some(quinn::ReadError::Reset(code)) = io_error
+                    .get_ref()
+                    .and_then(|error| error.downcast_ref::<quinn::ReadError>())
+                {
+                    self.end = Some(StreamEnd::Reset(varint_q2w(*code)));
+                }
+            }
+        }
+        if result.is_ok() {
+            self.guard.touch();
+            self.flow.received(buf.filled().len() - filled);
+        }
+        Poll::Ready(result)
+    }
+}
+
+/// Returns the I/O error of a write failing with `error`, as quinn does.
+#[cfg(feature = "chaos")]
+fn chaos_write_error(error: StreamWriteError) -> std::io::Error {
+    match error {
+        StreamWriteError::Stopped(error_code) => std::io::Error::new(
+            std::io::ErrorKind::ConnectionReset,
+            quinn::WriteError::Stopped(varint_w2q(error_code)),
+        ),
+        error => std::io::Error::other(error),
+    }
+}
+
+/// A unidirectional send stream abandoning stale data (partial reliability).
+///
+/// Each write comes with a deadline. If the data cannot be entirely written before
+/// the deadline (e.g., because of congestion or flow control), the current stream
+/// is reset, dropping the buffered data, and the next write transparently opens a
+/// new stream. This is useful for live media, where stale data must not delay fresh data.
+///
+/// The peer observes the abandoned stream as reset with the
+/// [expired code](Self::with_expired_code), and the following data on a new stream.
+pub struct ExpiringSendStream {
+    connection: Arc<Connection>,
+    stream: Option<SendStream>,
+    expired_code: VarInt,
+    expired_count: u64,
+}
+
+impl ExpiringSendStream {
+    /// Default stream error code for abandoned data.
+    pub const DEFAULT_EXPIRED_CODE: VarInt = VarInt::from_u32(0);
+
+    /// Creates a new expiring stream on `connection`.
+    ///
+    /// The first stream is opened on the first write.
+    pub fn new(connection: Arc<Connection>) -> Self {
+        Self {
+            connection,
+            stream: None,
+            expired_code: Self::DEFAULT_EXPIRED_CODE,
+            expired_count: 0,
+        }
+    }
+
+    /// Sets the error code used to reset streams with expired data.
+    pub fn with_expired_code(mut self, code: VarInt) -> Self {
+        self.expired_code = code;
+        self
+    }
+
+    /// Writes the entire `buf`, unless `deadline` expires.
+    ///
+    /// It returns `false` if the deadline expired (possibly before the call): the stream
+    /// is reset and the data (as well as the one previously buffered) is abandoned.
+    ///
+    /// If the write fails, the stream is dropped: the next write opens a new one.
+    pub async fn write_all(
+        &mut self,
+        buf: &[u8],
+        deadline: Instant,
+    ) -> Result<bool, ExpiringWriteError> {
+        if deadline <= Instant::now() {
+            self.expire();
+            return Ok(false);
+        }
+
+        let connection = &self.connection;
+        let stream = &mut self.stream;
+
+        let write = async {
+            let stream = match stream {
+                Some(stream) => stream,
+                None => stream.insert(connection.open_uni().await?.await?),
+            };
+
+            stream.write_all(buf).await?;
+
+            Ok::<_, ExpiringWriteError>(())
+        };
+
+        match tokio::time::timeout_at(deadline.into(), write).await {
+            Ok(Ok(())) => Ok(true),
+            Ok(Err(error)) => {
+                self.stream = None;
+                Err(error)
+            }
+            Err(_) => {
+                self.expire();
+                Ok(false)
+            }
+        }
+    }
+
+    fn expire(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            stream.reset(self.expired_code);
+        }
+
+        self.expired_count += 1;
+    }
+
+    /// Returns the number of streams abandoned because of expired data.
+    pub fn expired_count(&self) -> u64 {
+        self.expired_count
+    }
+
+    /// Returns the [`StreamId`] of the current stream, if any.
+    pub fn id(&self) -> Option<StreamId> {
+        self.stream.as_ref().map(SendStream::id)
+    }
+
+    /// Finishes the current stream, if any.
+    pub async fn finish(&mut self) -> Result<(), StreamWriteError> {
+        match self.stream.take() {
+            Some(mut stream) => stream.finish().await,
+            None => Ok(()),
+        }
     }
 }
 
@@ -168,21 +725,56 @@ type DynFutureUniStream = dyn Future<Output = Result<SendStream, StreamOpeningEr
 /// [`Future`] for an in-progress opening unidirectional stream.
 ///
 /// See [`Connection::open_uni`](crate::Connection::open_uni).
+///
+/// Dropping the future before completion aborts the opening: the stream is reset (with
+/// `H3_REQUEST_CANCELLED`), so that the peer never accepts a half-opened stream, and its
+/// place in the [open queue](crate::config::ServerConfigBuilder::stream_open_queue), if
+/// any, is released.
 pub struct OpeningUniStream(Pin<Box<DynFutureUniStream>>);
 
 impl OpeningUniStream {
-    pub(crate) fn new(session_id: SessionId, quic_stream: StreamUniLocalQuic) -> Self {
+    pub(crate) fn new(
+        session_id: SessionId,
+        quic_stream: StreamUniLocalQuic,
+        guard: StreamGuard,
+    ) -> Self {
+        let upgrading = quic_stream.upgrade(StreamHeader::new_webtransport(session_id));
+
         Self(Box::pin(async move {
-            match quic_stream
-                .upgrade(StreamHeader::new_webtransport(session_id))
-                .await
-            {
-                Ok(stream) => Ok(SendStream(stream.upgrade().into_stream())),
+            match upgrading.await {
+                Ok(stream) => Ok(SendStream::new(stream.upgrade().into_stream(), guard)),
                 Err(ProtoWriteError::NotConnected) => Err(StreamOpeningError::NotConnected),
                 Err(ProtoWriteError::Stopped) => Err(StreamOpeningError::Refused),
             }
         }))
     }
+
+    pub(crate) fn queued(
+        session_id: SessionId,
+        quic_connection: quinn::Connection,
+        queue: OpenQueue,
+        guard: StreamGuard,
+    ) -> Self {
+        Self(Box::pin(async move {
+            let turn = queue.enter().await.ok_or(StreamOpeningError::QueueFull)?;
+            let quic_stream = StreamUniLocalQuic::open_uni(&quic_connection)
+                .await
+                .ok_or(StreamOpeningError::NotConnected)?;
+            drop(turn);
+
+            Self::new(session_id, quic_stream, guard).await
+        }))
+    }
+
+    /// Bounds the opening to `timeout`, failing with [`StreamOpeningError::TimedOut`]
+    /// (the opening being then aborted, as on drop).
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self(Box::pin(async move {
+            tokio::time::timeout(timeout, self)
+                .await
+                .unwrap_or(Err(StreamOpeningError::TimedOut))
+        }))
+    }
 }
 
 impl Future for OpeningUniStream {
@@ -199,21 +791,62 @@ type DynFutureBiStream =
 /// [`Future`] for an in-progress opening bidirectional stream.
 ///
 /// See [`Connection::open_bi`](crate::Connection::open_bi).
+///
+/// Dropping the future before completion aborts the opening: the stream is reset (with
+/// `H3_REQUEST_CANCELLED`), so that the peer never accepts a half-opened stream, and its
+/// place in the [open queue](crate::config::ServerConfigBuilder::stream_open_queue), if
+/// any, is released.
 pub struct OpeningBiStream(Pin<Box<DynFutureBiStream>>);
 
 impl OpeningBiStream {
-    pub(crate) fn new(session_id: SessionId, quic_stream: StreamBiLocalQuic) -> Self {
+    pub(crate) fn new(
+        session_id: SessionId,
+        quic_stream: StreamBiLocalQuic,
+        guard: StreamGuard,
+    ) -> Self {
+        let upgrading = quic_stream.upgrade().upgrade(session_id);
+
         Self(Box::pin(async move {
-            match quic_stream.upgrade().upgrade(session_id).await {
+            match upgrading.await {
                 Ok(stream) => {
                     let stream = stream.into_stream();
-                    Ok((SendStream::new(stream.0), RecvStream::new(stream.1)))
+                    Ok((
+                        SendStream::new(stream.0, guard.clone()),
+                        RecvStream::new(stream.1, guard),
+                    ))
                 }
                 Err(ProtoWriteError::NotConnected) => Err(StreamOpeningError::NotConnected),
                 Err(ProtoWriteError::Stopped) => Err(StreamOpeningError::Refused),
             }
         }))
     }
+
+    pub(crate) fn queued(
+        session_id: SessionId,
+        quic_connection: quinn::Connection,
+        queue: OpenQueue,
+        guard: StreamGuard,
+    ) -> Self {
+        Self(Box::pin(async move {
+            let turn = queue.enter().await.ok_or(StreamOpeningError::QueueFull)?;
+            let quic_stream = StreamBiLocalQuic::open_bi(&quic_connection)
+                .await
+                .ok_or(StreamOpeningError::NotConnected)?;
+            drop(turn);
+
+            Self::new(session_id, quic_stream, guard).await
+        }))
+    }
+
+    /// Bounds the opening to `timeout`, failing with [`StreamOpeningError::TimedOut`]
+    /// (the opening being then aborted, as on drop).
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self(Box::pin(async move {
+            tokio::time::timeout(timeout, self)
+                .await
+                .unwrap_or(Err(StreamOpeningError::TimedOut))
+        }))
+    }
 }
 
 impl Future for OpeningBiStream {
@@ -224,3 +857,170 @@ impl Future for OpeningBiStream {
         Future::poll(self.0.as_mut(), cx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+    use std::time::Duration;
+
+    /// Reads `stream` until it ends, returning the data and the reset code, if any.
+    async fn read_until_end(stream: &mut RecvStream) -> (Vec<u8>, Option<VarInt>) {
+        let mut data = Vec::new();
+        let mut buffer = [0; 4096];
+
+        loop {
+            match stream.read(&mut buffer).await {
+                Ok(Some(read)) => data.extend_from_slice(&buffer[..read]),
+                Ok(None) => return (data, None),
+                Err(StreamReadError::Reset(code)) => return (data, Some(code)),
+                Err(error) => panic!("Unexpected error: {error}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn expiring_stream() {
+        let peers = test_utils::connect().await;
+        let code = VarInt::from_u32(42);
+        let mut stream =
+            ExpiringSendStream::new(Arc::new(peers.client_connection)).with_expired_code(code);
+        let in_time = || Instant::now() + Duration::from_secs(5);
+
+        assert!(stream.write_all(b"first", in_time()).await.unwrap());
+        let first_id = stream.id().unwrap();
+        let mut first = peers.server_connection.accept_uni().await.unwrap();
+
+        // Expired before the call: the stream is reset without writing.
+        assert!(!stream.write_all(b"stale", Instant::now()).await.unwrap());
+        assert_eq!(stream.expired_count(), 1);
+        assert!(stream.id().is_none());
+        assert_eq!(read_until_end(&mut first).await.1, Some(code));
+
+        // The next write opens a new stream.
+        assert!(stream.write_all(b"fresh", in_time()).await.unwrap());
+        assert_ne!(stream.id(), Some(first_id));
+        stream.finish().await.unwrap();
+
+        let mut second = peers.server_connection.accept_uni().await.unwrap();
+        assert_eq!(read_until_end(&mut second).await, (b"fresh".to_vec(), None));
+    }
+
+    #[tokio::test]
+    async fn expiring_stream_blocked() {
+        let peers = test_utils::connect().await;
+        let mut stream = ExpiringSendStream::new(Arc::new(peers.client_connection));
+
+        // Not read by the peer: flow control blocks the write until the deadline.
+        let data = vec![0; 16 * 1024 * 1024];
+        let deadline = Instant::now() + Duration::from_millis(50);
+        assert!(!stream.write_all(&data, deadline).await.unwrap());
+        assert_eq!(stream.expired_count(), 1);
+
+        let mut abandoned = peers.server_connection.accept_uni().await.unwrap();
+        let (received, code) = read_until_end(&mut abandoned).await;
+        assert!(received.len() < data.len());
+        assert_eq!(code, Some(ExpiringSendStream::DEFAULT_EXPIRED_CODE));
+    }
+
+    #[tokio::test]
+    async fn half_close() {
+        let peers = test_utils::connect().await;
+
+        let (mut client_send, mut client_recv) = peers
+            .client_connection
+            .open_bi()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        client_send.write_all(b"request").await.unwrap();
+        client_send.finish().await.unwrap();
+
+        // The request is complete, but the response can still be sent.
+        let (mut server_send, mut server_recv) = peers.server_connection.accept_bi().await.unwrap();
+        assert_eq!(server_recv.end(), None);
+        assert_eq!(
+            read_until_end(&mut server_recv).await,
+            (b"request".to_vec(), None)
+        );
+        assert_eq!(server_recv.end(), Some(StreamEnd::Finished));
+
+        server_send.write_all(b"response").await.unwrap();
+        server_send.finish().await.unwrap();
+        assert_eq!(
+            read_until_end(&mut client_recv).await,
+            (b"response".to_vec(), None)
+        );
+        assert_eq!(client_recv.end(), Some(StreamEnd::Finished));
+    }
+
+    #[tokio::test]
+    async fn reset_and_stop() {
+        let peers = test_utils::connect().await;
+        let code = VarInt::from_u32(7);
+
+        let mut stream = peers
+            .client_connection
+            .open_uni()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        stream.write_all(b"partial").await.unwrap();
+
+        // Reset once accepted: a reset could discard the stream header.
+        let mut accepted = peers.server_connection.accept_uni().await.unwrap();
+        stream.reset(code);
+        assert_eq!(read_until_end(&mut accepted).await.1, Some(code));
+        assert_eq!(accepted.end(), Some(StreamEnd::Reset(code)));
+
+        // The peer finished early: read_exact reports it too.
+        let mut stream = peers
+            .client_connection
+            .open_uni()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        stream.write_all(b"ab").await.unwrap();
+        stream.finish().await.unwrap();
+
+        let mut stream = peers.server_connection.accept_uni().await.unwrap();
+        let mut buffer = [0; 4];
+        assert!(matches!(
+            stream.read_exact(&mut buffer).await,
+            Err(StreamReadExactError::FinishedEarly)
+        ));
+        assert_eq!(stream.end(), Some(StreamEnd::Finished));
+
+        // Stopping the receiving side leaves the sending side open.
+        let (mut client_send, _client_recv) = peers
+            .client_connection
+            .open_bi()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        client_send.write_all(b"x").await.unwrap();
+
+        let (mut server_send, server_recv) = peers.server_connection.accept_bi().await.unwrap();
+        server_recv.stop(code);
+        let error = client_send.stopped().await;
+        assert!(matches!(error, StreamWriteError::Stopped(stopped) if stopped == code));
+        server_send.write_all(b"still open").await.unwrap();
+        server_send.finish().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn session_stream_end() {
+        let peers = test_utils::connect().await;
+        let session_stream = peers.server_connection.session_stream();
+        assert_eq!(session_stream.end(), None);
+
+        // Closed without ending the session stream.
+        peers.client_connection.close(VarInt::from_u32(0), b"");
+        assert!(session_stream.ended().await.is_err());
+        assert_eq!(session_stream.end(), None);
+    }
+}
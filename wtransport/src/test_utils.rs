@@ -0,0 +1,145 @@
+//! Helpers for tests running a server and a client on loopback.
+
+use crate::config::ClientConfigBuilder;
+use crate::config::ServerConfigBuilder;
+use crate::config::WantsTransportConfigClient;
+use crate::config::WantsTransportConfigServer;
+use crate::endpoint::Client;
+use crate::endpoint::Server;
+use crate::tls::Certificate;
+use crate::ClientConfig;
+use crate::Connection;
+use crate::Endpoint;
+use crate::ServerConfig;
+use std::net::Ipv4Addr;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Generates a self-signed certificate for `localhost`.
+pub(crate) fn certificate() -> Certificate {
+    let certificate = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])
+        .expect("Certificate generation failed");
+
+    Certificate::new(
+        vec![certificate
+            .serialize_der()
+            .expect("DER serialization failed")],
+        certificate.serialize_private_key_der(),
+    )
+}
+
+/// Returns a server configuration bound to an ephemeral loopback port.
+pub(crate) fn server_config(
+    certificate: Certificate,
+) -> ServerConfigBuilder<WantsTransportConfigServer> {
+    ServerConfig::builder()
+        .with_bind_address(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+        .with_certificate(certificate)
+}
+
+/// Returns a TLS client configuration trusting only `certificate`.
+fn tls_client_config(certificate: &Certificate) -> rustls::ClientConfig {
+    let mut roots = rustls::RootCertStore::empty();
+    for der in certificate.certificates_der() {
+        roots
+            .add(&rustls::Certificate(der.to_vec()))
+            .expect("Valid certificate");
+    }
+
+    rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth()
+}
+
+/// Returns a client configuration trusting only `certificate`.
+pub(crate) fn client_config(
+    certificate: &Certificate,
+) -> ClientConfigBuilder<WantsTransportConfigClient> {
+    let tls_config = tls_client_config(certificate);
+
+    ClientConfig::builder()
+        .with_bind_address(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+        .with_custom_tls(tls_config)
+        .expect("Valid TLS configuration")
+}
+
+/// Establishes a bare QUIC connection to `server` (no HTTP3 layer), trusting only
+/// `certificate`.
+pub(crate) async fn quic_connect(
+    server: &Endpoint<Server>,
+    certificate: &Certificate,
+) -> (quinn::Endpoint, quinn::Connection) {
+    let mut tls_config = tls_client_config(certificate);
+    tls_config.alpn_protocols = vec![wtransport_proto::WEBTRANSPORT_ALPN.to_vec()];
+
+    let mut endpoint = quinn::Endpoint::client(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+        .expect("QUIC client endpoint");
+    endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(tls_config)));
+
+    let address = server.local_addr().expect("Bound endpoint");
+    let connection = endpoint
+        .connect(address, "localhost")
+        .expect("Valid QUIC connection parameters")
+        .await
+        .expect("QUIC connection established");
+
+    (endpoint, connection)
+}
+
+/// A server and a client, with an established session.
+pub(crate) struct Peers {
+    pub(crate) server: Endpoint<Server>,
+    pub(crate) client: Endpoint<Client>,
+    pub(crate) server_connection: Connection,
+    pub(crate) client_connection: Connection,
+}
+
+/// Returns the URL of `server`.
+pub(crate) fn url(server: &Endpoint<Server>) -> String {
+    let port = server.local_addr().expect("Bound endpoint").port();
+    format!("https://localhost:{port}/")
+}
+
+/// Establishes a session with the default configurations.
+pub(crate) async fn connect() -> Peers {
+    let certificate = certificate();
+    let client_config = client_config(&certificate).build();
+    connect_with(server_config(certificate).build(), client_config).await
+}
+
+/// Establishes a session between endpoints configured with `server_config` and
+/// `client_config`.
+pub(crate) async fn connect_with(
+    server_config: ServerConfig,
+    client_config: ClientConfig,
+) -> Peers {
+    let server = Endpoint::server(server_config).expect("Server endpoint");
+    let client = Endpoint::client(client_config).expect("Client endpoint");
+
+    let (server_connection, client_connection) = tokio::join!(
+        async {
+            server
+                .accept()
+                .await
+                .await
+                .expect("Session request")
+                .accept()
+                .await
+                .expect("Session accepted")
+        },
+        async {
+            client
+                .connect(url(&server))
+                .await
+                .expect("Session established")
+        },
+    );
+
+    Peers {
+        server,
+        client,
+        server_connection,
+        client_connection,
+    }
+}
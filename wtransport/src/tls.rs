@@ -1,6 +1,18 @@
+use rustls::server::ResolvesServerCert;
+use rustls::sign::CertifiedKey;
 use std::path::Path;
+use std::sync::Arc;
+use tracing::debug;
+
+/// The [`rustls`](https://docs.rs/rustls/0.21) crate, in the version this crate depends on.
+pub use rustls;
 
 /// A server TLS certificate.
+///
+/// The same certificate can be shared with an HTTPS server running alongside the
+/// WebTransport endpoint (e.g., for advertising it with `Alt-Svc`): see
+/// [`certificates_der`](Self::certificates_der) and [`private_key_der`](Self::private_key_der).
+#[derive(Clone)]
 pub struct Certificate {
     pub(crate) certificates: Vec<rustls::Certificate>,
     pub(crate) key: rustls::PrivateKey,
@@ -37,4 +49,272 @@ impl Certificate {
 
         Ok(Self::new(certificates, private_key))
     }
+
+    /// Returns the certificate chain, each one *DER-encoded* *X.509*.
+    pub fn certificates_der(&self) -> impl Iterator<Item = &[u8]> {
+        self.certificates
+            .iter()
+            .map(|certificate| certificate.0.as_slice())
+    }
+
+    /// Returns the *DER-encoded* private key.
+    pub fn private_key_der(&self) -> &[u8] {
+        &self.key.0
+    }
+}
+
+/// A private key kept outside of the process memory (e.g., in an HSM, through PKCS#11,
+/// or in a cloud KMS).
+///
+/// See [`ServerConfigBuilder::with_signing_key`](crate::config::ServerConfigBuilder::with_signing_key).
+///
+/// **Note**: [`sign`](Self::sign) is called synchronously during the TLS handshake,
+/// from the endpoint driving task: a slow signer delays all the handshakes of the endpoint.
+pub trait SigningKey: Send + Sync + 'static {
+    /// Returns the signature scheme of the key.
+    fn scheme(&self) -> SignatureScheme;
+
+    /// Signs `message`, returning the signature encoded as per TLS 1.3 (RFC 8446).
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, SigningError>;
+}
+
+/// A signature scheme usable in TLS 1.3 handshakes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SignatureScheme {
+    /// ECDSA on the P-256 curve, with SHA-256.
+    EcdsaP256Sha256,
+
+    /// ECDSA on the P-384 curve, with SHA-384.
+    EcdsaP384Sha384,
+
+    /// Ed25519.
+    Ed25519,
+
+    /// RSASSA-PSS, with SHA-256.
+    RsaPssSha256,
+
+    /// RSASSA-PSS, with SHA-384.
+    RsaPssSha384,
+
+    /// RSASSA-PSS, with SHA-512.
+    RsaPssSha512,
+}
+
+impl SignatureScheme {
+    fn to_rustls(self) -> rustls::SignatureScheme {
+        match self {
+            SignatureScheme::EcdsaP256Sha256 => rustls::SignatureScheme::ECDSA_NISTP256_SHA256,
+            SignatureScheme::EcdsaP384Sha384 => rustls::SignatureScheme::ECDSA_NISTP384_SHA384,
+            SignatureScheme::Ed25519 => rustls::SignatureScheme::ED25519,
+            SignatureScheme::RsaPssSha256 => rustls::SignatureScheme::RSA_PSS_SHA256,
+            SignatureScheme::RsaPssSha384 => rustls::SignatureScheme::RSA_PSS_SHA384,
+            SignatureScheme::RsaPssSha512 => rustls::SignatureScheme::RSA_PSS_SHA512,
+        }
+    }
+
+    fn algorithm(self) -> rustls::SignatureAlgorithm {
+        match self {
+            SignatureScheme::EcdsaP256Sha256 | SignatureScheme::EcdsaP384Sha384 => {
+                rustls::SignatureAlgorithm::ECDSA
+            }
+            SignatureScheme::Ed25519 => rustls::SignatureAlgorithm::ED25519,
+            SignatureScheme::RsaPssSha256
+            | SignatureScheme::RsaPssSha384
+            | SignatureScheme::RsaPssSha512 => rustls::SignatureAlgorithm::RSA,
+        }
+    }
+}
+
+/// Error of a [`SigningKey`].
+#[derive(thiserror::Error, Debug)]
+#[error("signing failed: {0}")]
+pub struct SigningError(String);
+
+impl SigningError {
+    /// Creates an error with a description of the failure.
+    pub fn new<S>(reason: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self(reason.into())
+    }
+}
+
+/// Adapter of a [`SigningKey`] to rustls.
+struct ExternalSigningKey(Arc<dyn SigningKey>);
+
+impl rustls::sign::SigningKey for ExternalSigningKey {
+    fn choose_scheme(
+        &self,
+        offered: &[rustls::SignatureScheme],
+    ) -> Option<Box<dyn rustls::sign::Signer>> {
+        let scheme = self.0.scheme();
+
+        if offered.contains(&scheme.to_rustls()) {
+            Some(Box::new(ExternalSigner(self.0.clone())))
+        } else {
+            None
+        }
+    }
+
+    fn algorithm(&self) -> rustls::SignatureAlgorithm {
+        self.0.scheme().algorithm()
+    }
+}
+
+struct ExternalSigner(Arc<dyn SigningKey>);
+
+impl rustls::sign::Signer for ExternalSigner {
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, rustls::Error> {
+        self.0.sign(message).map_err(|error| {
+            debug!("Aborting TLS handshake: {}", error);
+            rustls::Error::General(error.to_string())
+        })
+    }
+
+    fn scheme(&self) -> rustls::SignatureScheme {
+        self.0.scheme().to_rustls()
+    }
+}
+
+/// Certificate resolver always presenting a certificate signed by a [`SigningKey`].
+pub(crate) struct ExternalKeyResolver(Arc<CertifiedKey>);
+
+impl ExternalKeyResolver {
+    pub(crate) fn new(certificates: Vec<Vec<u8>>, signing_key: Arc<dyn SigningKey>) -> Self {
+        let certificates = certificates.into_iter().map(rustls::Certificate).collect();
+
+        Self(Arc::new(CertifiedKey::new(
+            certificates,
+            Arc::new(ExternalSigningKey(signing_key)),
+        )))
+    }
+}
+
+impl ResolvesServerCert for ExternalKeyResolver {
+    fn resolve(&self, _client_hello: rustls::server::ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.0.clone())
+    }
+}
+
+/// The `ClientHello` of an incoming TLS handshake.
+///
+/// See [`ServerConfigBuilder::on_client_hello`](crate::config::ServerConfigBuilder::on_client_hello).
+pub struct ClientHello<'a> {
+    server_name: Option<&'a str>,
+    alpn_protocols: Vec<&'a [u8]>,
+}
+
+impl ClientHello<'_> {
+    /// Returns the server name indication (SNI) of the client, if any.
+    pub fn server_name(&self) -> Option<&str> {
+        self.server_name
+    }
+
+    /// Returns the application protocols (ALPN) offered by the client.
+    pub fn alpn_protocols(&self) -> &[&[u8]] {
+        &self.alpn_protocols
+    }
+}
+
+/// The decision of a `ClientHello` hook.
+pub enum ClientHelloAction {
+    /// Continues the handshake with the configured certificate.
+    Accept,
+
+    /// Continues the handshake, presenting this certificate.
+    ///
+    /// The private key is parsed on each handshake: prefer [`Accept`](Self::Accept)
+    /// for the usual certificate.
+    Certificate(Certificate),
+
+    /// Aborts the handshake.
+    Reject,
+}
+
+/// Parameters negotiated by the TLS handshake of a connection.
+///
+/// See [`Connection::tls_info`](crate::Connection::tls_info).
+///
+/// **Note**: the key exchange group and whether the session was resumed are not reported
+/// by the TLS implementation in use for QUIC connections.
+#[derive(Clone, Debug)]
+pub struct TlsInfo {
+    pub(crate) protocol_version: Option<rustls::ProtocolVersion>,
+    pub(crate) cipher_suite: Option<rustls::CipherSuite>,
+    pub(crate) early_data_accepted: Option<bool>,
+    pub(crate) server_name: Option<String>,
+}
+
+impl TlsInfo {
+    /// Returns the negotiated protocol version (always TLS 1.3 for QUIC).
+    ///
+    /// Returns `None` if the handshake has not progressed far enough.
+    pub fn protocol_version(&self) -> Option<rustls::ProtocolVersion> {
+        self.protocol_version
+    }
+
+    /// Returns the negotiated cipher suite (its name is given by [`rustls::CipherSuite::as_str`]).
+    ///
+    /// Returns `None` if the handshake has not progressed far enough.
+    pub fn cipher_suite(&self) -> Option<rustls::CipherSuite> {
+        self.cipher_suite
+    }
+
+    /// Returns whether the early data (0-RTT) sent was accepted by the server.
+    ///
+    /// On server side, `None` is always returned.
+    pub fn early_data_accepted(&self) -> Option<bool> {
+        self.early_data_accepted
+    }
+
+    /// Returns the server name (SNI) sent by the client.
+    ///
+    /// On client side, `None` is always returned.
+    pub fn server_name(&self) -> Option<&str> {
+        self.server_name.as_deref()
+    }
+}
+
+pub(crate) type ClientHelloHook = dyn Fn(&ClientHello) -> ClientHelloAction + Send + Sync;
+
+/// Certificate resolver invoking a `ClientHello` hook before the configured resolver.
+pub(crate) struct ClientHelloResolver {
+    hook: Arc<ClientHelloHook>,
+    inner: Arc<dyn ResolvesServerCert>,
+}
+
+impl ClientHelloResolver {
+    pub(crate) fn new(hook: Arc<ClientHelloHook>, inner: Arc<dyn ResolvesServerCert>) -> Self {
+        Self { hook, inner }
+    }
+}
+
+impl ResolvesServerCert for ClientHelloResolver {
+    fn resolve(&self, client_hello: rustls::server::ClientHello) -> Option<Arc<CertifiedKey>> {
+        let action = (self.hook)(&ClientHello {
+            server_name: client_hello.server_name(),
+            alpn_protocols: client_hello
+                .alpn()
+                .map(Iterator::collect)
+                .unwrap_or_default(),
+        });
+
+        match action {
+            ClientHelloAction::Accept => self.inner.resolve(client_hello),
+            ClientHelloAction::Certificate(certificate) => {
+                match rustls::sign::any_supported_type(&certificate.key) {
+                    Ok(key) => Some(Arc::new(CertifiedKey::new(certificate.certificates, key))),
+                    Err(error) => {
+                        debug!("Rejecting TLS handshake: invalid private key ({})", error);
+                        None
+                    }
+                }
+            }
+            ClientHelloAction::Reject => {
+                debug!("Rejecting TLS handshake: rejected by ClientHello hook");
+                None
+            }
+        }
+    }
 }
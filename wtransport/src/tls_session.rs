@@ -0,0 +1,290 @@
+//! QUIC crypto sessions of `quinn`, reporting the negotiated TLS parameters.
+//!
+//! The rustls session of `quinn-proto` does not give access to the underlying TLS
+//! connection: the negotiated cipher suite is read from the `ServerHello` message,
+//! which the session reads (client side) or writes (server side) unencrypted.
+
+use crate::tls::TlsInfo;
+use quinn_proto::crypto;
+use quinn_proto::crypto::rustls::HandshakeData;
+use quinn_proto::crypto::ExportKeyingMaterialError;
+use quinn_proto::crypto::KeyPair;
+use quinn_proto::crypto::Keys;
+use quinn_proto::crypto::UnsupportedVersion;
+use quinn_proto::transport_parameters::TransportParameters;
+use quinn_proto::ConnectError;
+use quinn_proto::ConnectionId;
+use quinn_proto::Side;
+use quinn_proto::TransportError;
+use rustls::CipherSuite;
+use rustls::ProtocolVersion;
+use std::any::Any;
+use std::sync::Arc;
+
+/// Client crypto configuration starting [`TlsSession`]s.
+pub(crate) struct ClientCrypto(pub(crate) Arc<rustls::ClientConfig>);
+
+impl crypto::ClientConfig for ClientCrypto {
+    fn start_session(
+        self: Arc<Self>,
+        version: u32,
+        server_name: &str,
+        params: &TransportParameters,
+    ) -> Result<Box<dyn crypto::Session>, ConnectError> {
+        let inner = self.0.clone().start_session(version, server_name, params)?;
+        Ok(Box::new(TlsSession::new(inner, Side::Client)))
+    }
+}
+
+/// Server crypto configuration starting [`TlsSession`]s.
+pub(crate) struct ServerCrypto(pub(crate) Arc<rustls::ServerConfig>);
+
+impl crypto::ServerConfig for ServerCrypto {
+    fn initial_keys(
+        &self,
+        version: u32,
+        dst_cid: &ConnectionId,
+        side: Side,
+    ) -> Result<Keys, UnsupportedVersion> {
+        self.0.initial_keys(version, dst_cid, side)
+    }
+
+    fn retry_tag(&self, version: u32, orig_dst_cid: &ConnectionId, packet: &[u8]) -> [u8; 16] {
+        self.0.retry_tag(version, orig_dst_cid, packet)
+    }
+
+    fn start_session(
+        self: Arc<Self>,
+        version: u32,
+        params: &TransportParameters,
+    ) -> Box<dyn crypto::Session> {
+        let inner = self.0.clone().start_session(version, params);
+        Box::new(TlsSession::new(inner, Side::Server))
+    }
+}
+
+/// Maximum number of bytes of the `ServerHello` read to find the cipher suite.
+const SERVER_HELLO_PREFIX_SIZE: usize = 4 + 2 + 32 + 1 + 32 + 2;
+
+/// The rustls session of `quinn`, whose handshake data is a [`TlsInfo`].
+struct TlsSession {
+    inner: Box<dyn crypto::Session>,
+    side: Side,
+    server_hello: Vec<u8>,
+    cipher_suite: Option<CipherSuite>,
+}
+
+impl TlsSession {
+    fn new(inner: Box<dyn crypto::Session>, side: Side) -> Self {
+        Self {
+            inner,
+            side,
+            server_hello: Vec::new(),
+            cipher_suite: None,
+        }
+    }
+
+    /// Looks for the cipher suite in the first handshake bytes sent by the server.
+    fn on_server_bytes(&mut self, bytes: &[u8]) {
+        if self.cipher_suite.is_some() || self.server_hello.len() >= SERVER_HELLO_PREFIX_SIZE {
+            return;
+        }
+
+        let missing = SERVER_HELLO_PREFIX_SIZE - self.server_hello.len();
+        self.server_hello
+            .extend_from_slice(&bytes[..bytes.len().min(missing)]);
+        self.cipher_suite = server_hello_cipher_suite(&self.server_hello);
+    }
+}
+
+impl crypto::Session for TlsSession {
+    fn initial_keys(&self, dst_cid: &ConnectionId, side: Side) -> Keys {
+        self.inner.initial_keys(dst_cid, side)
+    }
+
+    fn handshake_data(&self) -> Option<Box<dyn Any>> {
+        let handshake_data = self
+            .inner
+            .handshake_data()?
+            .downcast::<HandshakeData>()
+            .ok()?;
+
+        Some(Box::new(TlsInfo {
+            // QUIC requires TLS 1.3 (RFC 9001, section 4.2).
+            protocol_version: self.cipher_suite.map(|_| ProtocolVersion::TLSv1_3),
+            cipher_suite: self.cipher_suite,
+            early_data_accepted: self.inner.early_data_accepted(),
+            server_name: handshake_data.server_name,
+        }))
+    }
+
+    fn peer_identity(&self) -> Option<Box<dyn Any>> {
+        self.inner.peer_identity()
+    }
+
+    fn early_crypto(&self) -> Option<(Box<dyn crypto::HeaderKey>, Box<dyn crypto::PacketKey>)> {
+        self.inner.early_crypto()
+    }
+
+    fn early_data_accepted(&self) -> Option<bool> {
+        self.inner.early_data_accepted()
+    }
+
+    fn is_handshaking(&self) -> bool {
+        self.inner.is_handshaking()
+    }
+
+    fn read_handshake(&mut self, buf: &[u8]) -> Result<bool, TransportError> {
+        if self.side == Side::Client {
+            self.on_server_bytes(buf);
+        }
+
+        self.inner.read_handshake(buf)
+    }
+
+    fn transport_parameters(&self) -> Result<Option<TransportParameters>, TransportError> {
+        self.inner.transport_parameters()
+    }
+
+    fn write_handshake(&mut self, buf: &mut Vec<u8>) -> Option<Keys> {
+        let start = buf.len();
+        let keys = self.inner.write_handshake(buf);
+
+        if self.side == Side::Server {
+            self.on_server_bytes(&buf[start..]);
+        }
+
+        keys
+    }
+
+    fn next_1rtt_keys(&mut self) -> Option<KeyPair<Box<dyn crypto::PacketKey>>> {
+        self.inner.next_1rtt_keys()
+    }
+
+    fn is_valid_retry(&self, orig_dst_cid: &ConnectionId, header: &[u8], payload: &[u8]) -> bool {
+        self.inner.is_valid_retry(orig_dst_cid, header, payload)
+    }
+
+    fn export_keying_material(
+        &self,
+        output: &mut [u8],
+        label: &[u8],
+        context: &[u8],
+    ) -> Result<(), ExportKeyingMaterialError> {
+        self.inner.export_keying_material(output, label, context)
+    }
+}
+
+/// Returns the cipher suite of a (possibly truncated) `ServerHello` message
+/// (see [RFC 8446](https://www.rfc-editor.org/rfc/rfc8446#section-4.1.3)).
+fn server_hello_cipher_suite(message: &[u8]) -> Option<CipherSuite> {
+    const SERVER_HELLO: u8 = 2;
+    // Type (1), length (3), legacy version (2) and random (32).
+    const SESSION_ID_OFFSET: usize = 38;
+
+    if *message.first()? != SERVER_HELLO {
+        return None;
+    }
+
+    let session_id_len = usize::from(*message.get(SESSION_ID_OFFSET)?);
+    let offset = SESSION_ID_OFFSET + 1 + session_id_len;
+    let cipher_suite = message.get(offset..offset + 2)?;
+
+    Some(CipherSuite::from(u16::from_be_bytes([
+        cipher_suite[0],
+        cipher_suite[1],
+    ])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+
+    #[test]
+    fn server_hello() {
+        let mut message = vec![0x02, 0x00, 0x00, 0x5a, 0x03, 0x03];
+        message.extend_from_slice(&[0x42; 32]);
+        message.push(0x00);
+        message.extend_from_slice(&[0x13, 0x01, 0x00]);
+
+        assert_eq!(
+            server_hello_cipher_suite(&message),
+            Some(CipherSuite::TLS13_AES_128_GCM_SHA256)
+        );
+
+        // Truncated.
+        assert_eq!(server_hello_cipher_suite(&message[..40]), None);
+
+        // With a legacy session id.
+        message[38] = 0x01;
+        message.insert(39, 0xff);
+        assert_eq!(
+            server_hello_cipher_suite(&message),
+            Some(CipherSuite::TLS13_AES_128_GCM_SHA256)
+        );
+
+        // Not a ServerHello.
+        message[0] = 0x01;
+        assert_eq!(server_hello_cipher_suite(&message), None);
+    }
+
+    #[tokio::test]
+    async fn tls_info() {
+        let peers = test_utils::connect().await;
+
+        let server_info = peers.server_connection.tls_info().unwrap();
+        let client_info = peers.client_connection.tls_info().unwrap();
+
+        for info in [&server_info, &client_info] {
+            assert_eq!(info.protocol_version(), Some(ProtocolVersion::TLSv1_3));
+            assert!(rustls::DEFAULT_CIPHER_SUITES
+                .iter()
+                .any(|suite| Some(suite.suite()) == info.cipher_suite()));
+        }
+
+        assert_eq!(server_info.cipher_suite(), client_info.cipher_suite());
+        assert_eq!(server_info.server_name(), Some("localhost"));
+        assert_eq!(server_info.early_data_accepted(), None);
+        assert_eq!(client_info.server_name(), None);
+        assert_eq!(client_info.early_data_accepted(), Some(false));
+    }
+
+    #[tokio::test]
+    async fn tls_info_cipher_suite() {
+        let certificate = test_utils::certificate();
+        let mut roots = rustls::RootCertStore::empty();
+        for der in certificate.certificates_der() {
+            roots.add(&rustls::Certificate(der.to_vec())).unwrap();
+        }
+
+        let tls_config = rustls::ClientConfig::builder()
+            .with_cipher_suites(&[rustls::cipher_suite::TLS13_CHACHA20_POLY1305_SHA256])
+            .with_safe_default_kx_groups()
+            .with_protocol_versions(&[&rustls::version::TLS13])
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let client_config = crate::ClientConfig::builder()
+            .with_bind_address(std::net::SocketAddr::from((
+                std::net::Ipv4Addr::LOCALHOST,
+                0,
+            )))
+            .with_custom_tls(tls_config)
+            .unwrap()
+            .build();
+
+        let peers = test_utils::connect_with(
+            test_utils::server_config(certificate).build(),
+            client_config,
+        )
+        .await;
+
+        for connection in [&peers.server_connection, &peers.client_connection] {
+            assert_eq!(
+                connection.tls_info().unwrap().cipher_suite(),
+                Some(CipherSuite::TLS13_CHACHA20_POLY1305_SHA256)
+            );
+        }
+    }
+}
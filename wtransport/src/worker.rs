@@ -0,0 +1,618 @@
+use crate::driver::utils::Spawner;
+use quinn::AsyncTimer;
+use quinn::AsyncUdpSocket;
+use quinn::Runtime;
+use quinn::TokioRuntime;
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Waker;
+use std::time::Instant;
+use tokio::runtime::Handle;
+use tokio::sync::oneshot;
+use tokio::sync::Notify;
+
+/// A pool of workers, each one a single-threaded runtime on a dedicated thread.
+///
+/// A connection pinned to a worker (see
+/// [`IncomingSession::pin_to`](crate::endpoint::IncomingSession::pin_to) and
+/// [`ConnectOptions::pin_to`](crate::endpoint::ConnectOptions::pin_to)) has all its
+/// internal tasks run on that worker: the QUIC connection driver (packet processing,
+/// timers) and the WebTransport session tasks. The application tasks of the connection
+/// must be spawned on the worker too, with
+/// [`Connection::spawn_supervised`](crate::Connection::spawn_supervised) or
+/// [`Worker::spawn`]. This keeps the state of a connection in the caches of a single
+/// core, e.g., in high-throughput relays.
+///
+/// **Note**: the UDP socket of the endpoint is still read by the runtime the endpoint is
+/// created on. An incoming connection starts on that runtime too: it moves to its worker
+/// once the [`IncomingSession`](crate::endpoint::IncomingSession) is first polled.
+///
+/// Dropping the pool does not cancel the tasks spawned on its workers (with
+/// [`Worker::spawn`], or by pinned connections): each worker keeps running until they
+/// have completed. Tasks spawned directly with the runtime [handle](Worker::handle) are
+/// not waited for, they are cancelled once the worker stops.
+pub struct WorkerPool {
+    workers: Vec<Worker>,
+    next: AtomicUsize,
+    _shutdown: Vec<oneshot::Sender<()>>,
+}
+
+impl WorkerPool {
+    /// Starts a pool of `workers` workers, scheduled freely by the operating system.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers` is `0`.
+    pub fn new(workers: usize) -> io::Result<Self> {
+        Self::start((0..workers).map(|_| None))
+    }
+
+    /// Starts a pool of one worker per core of `cores`, each worker thread being pinned
+    /// to its core (CPU affinity).
+    ///
+    /// Affinity is only supported on Linux: elsewhere, an error of kind
+    /// [`io::ErrorKind::Unsupported`] is returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cores` is empty.
+    pub fn with_cores<I>(cores: I) -> io::Result<Self>
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        Self::start(cores.into_iter().map(Some))
+    }
+
+    fn start<I>(cores: I) -> io::Result<Self>
+    where
+        I: Iterator<Item = Option<usize>>,
+    {
+        let mut workers = Vec::new();
+        let mut shutdown = Vec::new();
+
+        for (index, core) in cores.enumerate() {
+            let (started_sender, started) = mpsc::channel();
+            let (shutdown_sender, shutdown_receiver) = oneshot::channel::<()>();
+
+            let tasks = Arc::new(TaskCount::default());
+            let worker_tasks = tasks.clone();
+
+            std::thread::Builder::new()
+                .name(format!("wtransport-worker-{index}"))
+                .spawn(move || {
+                    let runtime = core.map_or(Ok(()), set_affinity).and_then(|()| {
+                        tokio::runtime::Builder::new_current_thread()
+                            .enable_all()
+                            .build()
+                    });
+
+                    match runtime {
+                        Ok(runtime) => {
+                            let _ = started_sender.send(Ok(runtime.handle().clone()));
+                            runtime.block_on(async move {
+                                let _ = shutdown_receiver.await;
+                                worker_tasks.completed().await;
+                            });
+                        }
+                        Err(error) => {
+                            let _ = started_sender.send(Err(error));
+                        }
+                    }
+                })?;
+
+            let handle = started
+                .recv()
+                .map_err(|_| io::Error::other("Worker thread panicked"))??;
+
+            workers.push(Worker {
+                index,
+                core,
+                handle,
+                tasks,
+            });
+            shutdown.push(shutdown_sender);
+        }
+
+        assert!(!workers.is_empty(), "A pool needs at least one worker");
+
+        Ok(Self {
+            workers,
+            next: AtomicUsize::new(0),
+            _shutdown: shutdown,
+        })
+    }
+
+    /// Returns the workers of the pool.
+    #[inline(always)]
+    pub fn workers(&self) -> &[Worker] {
+        &self.workers
+    }
+
+    /// Returns the next worker, in round-robin order.
+    pub fn next(&self) -> &Worker {
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.workers.len();
+        &self.workers[index]
+    }
+
+    /// Returns the worker assigned to `key`, always the same one for equal keys.
+    ///
+    /// This allows pinning related connections together (e.g., both legs of a relayed
+    /// session, keyed by session name).
+    pub fn worker_for<K>(&self, key: &K) -> &Worker
+    where
+        K: Hash + ?Sized,
+    {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.workers[(hasher.finish() % self.workers.len() as u64) as usize]
+    }
+}
+
+impl fmt::Debug for WorkerPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WorkerPool")
+            .field("workers", &self.workers)
+            .finish()
+    }
+}
+
+/// A worker of a [`WorkerPool`], the scheduling hint of pinned connections.
+#[derive(Clone)]
+pub struct Worker {
+    index: usize,
+    core: Option<usize>,
+    handle: Handle,
+    tasks: Arc<TaskCount>,
+}
+
+impl Worker {
+    /// Returns the index of the worker in its pool.
+    #[inline(always)]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the core the worker is pinned to, if any.
+    #[inline(always)]
+    pub fn core(&self) -> Option<usize> {
+        self.core
+    }
+
+    /// Returns the handle of the runtime of the worker.
+    ///
+    /// Tasks spawned with the handle do not keep the worker running once the pool is
+    /// dropped (see [`spawn`](Self::spawn)).
+    #[inline(always)]
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// Spawns a task on the worker.
+    ///
+    /// The worker keeps running until the task completes, even if the pool is dropped.
+    pub fn spawn<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let task = TaskGuard::new(&self.tasks);
+        self.handle.spawn(async move {
+            let _task = task;
+            future.await;
+        });
+    }
+
+    pub(crate) fn spawner(&self) -> Spawner {
+        let worker = self.clone();
+        Spawner::new(move |task| worker.spawn(task))
+    }
+}
+
+impl fmt::Debug for Worker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Worker")
+            .field("index", &self.index)
+            .field("core", &self.core)
+            .finish()
+    }
+}
+
+/// Number of tasks running on a worker.
+#[derive(Default)]
+struct TaskCount {
+    running: AtomicUsize,
+    completed: Notify,
+}
+
+impl TaskCount {
+    /// Waits for all the tasks to be completed.
+    async fn completed(&self) {
+        while self.running.load(Ordering::Acquire) > 0 {
+            self.completed.notified().await;
+        }
+    }
+}
+
+/// A task counted as running, until dropped.
+struct TaskGuard(Arc<TaskCount>);
+
+impl TaskGuard {
+    fn new(tasks: &Arc<TaskCount>) -> Self {
+        tasks.running.fetch_add(1, Ordering::AcqRel);
+        Self(tasks.clone())
+    }
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        if self.0.running.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.0.completed.notify_one();
+        }
+    }
+}
+
+thread_local! {
+    /// Worker of the QUIC connections created by the current thread (see [`spawning_on`]).
+    static SPAWN_ON: RefCell<Option<Worker>> = const { RefCell::new(None) };
+}
+
+/// Calls `f`, the QUIC connections it creates being driven by `worker`.
+pub(crate) fn spawning_on<F, R>(worker: Option<&Worker>, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let previous = SPAWN_ON.with(|spawn_on| spawn_on.replace(worker.cloned()));
+    let result = f();
+    SPAWN_ON.with(|spawn_on| *spawn_on.borrow_mut() = previous);
+    result
+}
+
+/// The `quinn` runtime of an endpoint, driving QUIC connections on the worker they are
+/// pinned to.
+///
+/// A client connection is pinned when created (see [`spawning_on`]). The driver of an
+/// incoming connection is spawned by the endpoint before its session can be pinned: it
+/// starts on the runtime of the endpoint, then it is moved (see [`Relocation`]).
+#[derive(Debug, Default)]
+pub(crate) struct QuicRuntime {
+    incoming: AtomicBool,
+    relocations: Mutex<VecDeque<Arc<Relocation>>>,
+    accepting: Mutex<()>,
+}
+
+impl QuicRuntime {
+    /// Makes the drivers of the connections spawned from now on relocatable, each one
+    /// taken by [`relocation`](Self::relocation) in order.
+    ///
+    /// Called once the endpoint driver is spawned, for server endpoints.
+    pub(crate) fn relocate_incoming(&self) {
+        self.incoming.store(true, Ordering::Relaxed);
+    }
+
+    /// Locks the accepts of incoming connections, so that concurrent accepts take the
+    /// relocations in the order of the connections.
+    pub(crate) fn accepting(&self) -> MutexGuard<'_, ()> {
+        self.accepting
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Returns the relocation of the driver of the connection just accepted.
+    pub(crate) fn relocation(&self, _accepting: &MutexGuard<'_, ()>) -> Option<Arc<Relocation>> {
+        self.relocations
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .pop_front()
+    }
+}
+
+impl Runtime for QuicRuntime {
+    fn new_timer(&self, i: Instant) -> Pin<Box<dyn AsyncTimer>> {
+        TokioRuntime.new_timer(i)
+    }
+
+    fn spawn(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>) {
+        if let Some(worker) = SPAWN_ON.with(|spawn_on| spawn_on.borrow().clone()) {
+            worker.spawn(future);
+        } else if self.incoming.load(Ordering::Relaxed) {
+            let relocation = Arc::new(Relocation::default());
+            self.relocations
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .push_back(relocation.clone());
+
+            tokio::spawn(Relocatable {
+                future: Some(future),
+                relocation,
+                settled: false,
+            });
+        } else {
+            tokio::spawn(future);
+        }
+    }
+
+    fn wrap_udp_socket(&self, t: std::net::UdpSocket) -> io::Result<Box<dyn AsyncUdpSocket>> {
+        TokioRuntime.wrap_udp_socket(t)
+    }
+}
+
+/// Where the driver of an incoming connection runs, decided once its session is polled.
+#[derive(Debug, Default)]
+pub(crate) struct Relocation(Mutex<RelocationState>);
+
+#[derive(Debug, Default)]
+enum RelocationState {
+    #[default]
+    Undecided,
+    Waiting(Waker),
+    Stay,
+    Move(Worker),
+}
+
+impl Relocation {
+    /// Moves the driver to `worker`, or keeps it on the runtime of the endpoint if `None`.
+    pub(crate) fn settle(&self, worker: Option<Worker>) {
+        let state = match worker {
+            Some(worker) => RelocationState::Move(worker),
+            None => RelocationState::Stay,
+        };
+
+        if let RelocationState::Waiting(waker) = std::mem::replace(&mut *self.lock(), state) {
+            waker.wake();
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, RelocationState> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// A connection driver, moved to a worker once its [`Relocation`] is settled.
+struct Relocatable {
+    future: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+    relocation: Arc<Relocation>,
+    settled: bool,
+}
+
+impl Future for Relocatable {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if !self.settled {
+            let relocation = self.relocation.clone();
+            let mut state = relocation.lock();
+
+            match &*state {
+                RelocationState::Undecided => *state = RelocationState::Waiting(cx.waker().clone()),
+                RelocationState::Waiting(waker) if !waker.will_wake(cx.waker()) => {
+                    *state = RelocationState::Waiting(cx.waker().clone())
+                }
+                RelocationState::Waiting(_) => {}
+                RelocationState::Stay => self.settled = true,
+                RelocationState::Move(worker) => {
+                    let worker = worker.clone();
+                    drop(state);
+
+                    if let Some(future) = self.future.take() {
+                        worker.spawn(future);
+                    }
+                    return Poll::Ready(());
+                }
+            }
+        }
+
+        match self.future.as_mut() {
+            Some(future) => future.as_mut().poll(cx),
+            None => Poll::Ready(()),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_affinity(core: usize) -> io::Result<()> {
+    if core >= libc::CPU_SETSIZE as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Core {core} out of range"),
+        ));
+    }
+
+    // SAFETY: the set is a plain bitmask, valid when zeroed; the core is within its bounds.
+    let result = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_affinity(_core: usize) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "CPU affinity is only supported on Linux",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::endpoint::ConnectOptions;
+    use crate::test_utils;
+    use crate::Endpoint;
+    use std::time::Duration;
+    use tokio::sync::mpsc as async_mpsc;
+
+    fn thread_name() -> String {
+        std::thread::current()
+            .name()
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    /// Reports the thread of each poll, until `polls`.
+    async fn report_threads(threads: async_mpsc::UnboundedSender<String>, polls: usize) {
+        for _ in 0..polls {
+            let _ = threads.send(thread_name());
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn pool() {
+        let pool = WorkerPool::new(2).unwrap();
+        assert_eq!(pool.workers().len(), 2);
+        assert_eq!(
+            [
+                pool.next().index(),
+                pool.next().index(),
+                pool.next().index()
+            ],
+            [0, 1, 0]
+        );
+        assert_eq!(
+            pool.worker_for("session").index(),
+            pool.worker_for("session").index()
+        );
+
+        let (sender, receiver) = oneshot::channel();
+        pool.workers()[1].spawn(async move {
+            let _ = sender.send(thread_name());
+        });
+        assert_eq!(receiver.await.unwrap(), "wtransport-worker-1");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn affinity() {
+        let pool = WorkerPool::with_cores([0]).unwrap();
+        assert_eq!(pool.workers()[0].core(), Some(0));
+
+        let error = WorkerPool::with_cores([libc::CPU_SETSIZE as usize]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn dropped_pool_completes_tasks() {
+        let pool = WorkerPool::new(1).unwrap();
+        let (resume, resumed) = oneshot::channel::<()>();
+        let (done, completed) = oneshot::channel();
+
+        pool.next().spawn(async move {
+            let _ = resumed.await;
+            let _ = done.send(());
+        });
+
+        drop(pool);
+        resume.send(()).unwrap();
+        completed.await.expect("Task cancelled");
+    }
+
+    #[tokio::test]
+    async fn relocation() {
+        let pool = WorkerPool::new(1).unwrap();
+        let worker = pool.next();
+        let runtime = QuicRuntime::default();
+
+        // Created by a client: the driver starts on the worker.
+        let (threads, mut reported) = async_mpsc::unbounded_channel();
+        spawning_on(Some(worker), || {
+            runtime.spawn(Box::pin(report_threads(threads, 1)))
+        });
+        assert_eq!(reported.recv().await.unwrap(), "wtransport-worker-0");
+
+        // Not relocatable yet (e.g., the endpoint driver).
+        let (threads, mut reported) = async_mpsc::unbounded_channel();
+        runtime.spawn(Box::pin(report_threads(threads, 1)));
+        assert_eq!(reported.recv().await.unwrap(), thread_name());
+        assert!(runtime.relocation(&runtime.accepting()).is_none());
+
+        // Incoming: moved once settled.
+        runtime.relocate_incoming();
+        let (threads, mut reported) = async_mpsc::unbounded_channel();
+        runtime.spawn(Box::pin(report_threads(threads, 100)));
+        assert_eq!(reported.recv().await.unwrap(), thread_name());
+
+        let relocation = runtime.relocation(&runtime.accepting()).unwrap();
+        relocation.settle(Some(worker.clone()));
+        while reported.recv().await.unwrap() != "wtransport-worker-0" {}
+
+        // Settled to stay.
+        let (threads, mut reported) = async_mpsc::unbounded_channel();
+        runtime.spawn(Box::pin(report_threads(threads, 3)));
+        runtime
+            .relocation(&runtime.accepting())
+            .unwrap()
+            .settle(None);
+        while let Some(thread) = reported.recv().await {
+            assert_eq!(thread, thread_name());
+        }
+    }
+
+    #[tokio::test]
+    async fn pinned_connection() {
+        let pool = WorkerPool::new(2).unwrap();
+        let certificate = test_utils::certificate();
+        let server =
+            Endpoint::server(test_utils::server_config(certificate.clone()).build()).unwrap();
+        let client = Endpoint::client(test_utils::client_config(&certificate).build()).unwrap();
+
+        let (server_connection, client_connection) = tokio::join!(
+            async {
+                server
+                    .accept()
+                    .await
+                    .pin_to(&pool.workers()[0])
+                    .await
+                    .unwrap()
+                    .accept()
+                    .await
+                    .unwrap()
+            },
+            async {
+                client
+                    .connect_with_options(
+                        test_utils::url(&server),
+                        &ConnectOptions::new().pin_to(&pool.workers()[1]),
+                    )
+                    .await
+                    .unwrap()
+            },
+        );
+
+        // The connections outlive the pool.
+        drop(pool);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut stream = client_connection.open_uni().await.unwrap().await.unwrap();
+        stream.write_all(b"pinned").await.unwrap();
+        stream.finish().await.unwrap();
+
+        let mut stream = server_connection.accept_uni().await.unwrap();
+        let mut data = [0; 6];
+        stream.read_exact(&mut data).await.unwrap();
+        assert_eq!(&data, b"pinned");
+    }
+}